@@ -0,0 +1,239 @@
+//! Throughput of the generation pipeline over three fixtures shaped like a village on
+//! Zealand, a perimeter block on Nørrebro and a stretch of the West Jutland coast. The
+//! fixtures are synthetic, not recorded extracts: buildings, roads, water and land use laid
+//! out on regular grids inside each bbox, in the Overpass JSON the downloads return, so
+//! they time the same code paths without shipping OSM data. Run with `cargo bench`;
+//! criterion keeps the last run's results in `target/criterion` and reports the change
+//! against them, so a redesign is measured by benching before and after it.
+
+use arnis::args::Args;
+use arnis::bbr_codes::CodeTables;
+use arnis::coordinate_system::cartesian::XZBBox;
+use arnis::coordinate_system::geographic::LLBBox;
+use arnis::coordinate_system::transformation::CoordTransformer;
+use arnis::data_processing::{self, GenerationOptions};
+use arnis::enrichment::bbr::{self, BbrBuilding};
+use arnis::ground::Ground;
+use arnis::height_estimation::{self, BlockMedians};
+use arnis::osm_parser::{self, OsmData, ProcessedElement};
+use arnis::terrain_pipeline::{FlattenMask, GaussianBlur, SlopeAnalysis, TerrainPipeline};
+use arnis::world_editor::WorldFormat;
+use arnis::world_utils;
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use std::path::PathBuf;
+
+struct Fixture {
+    name: &'static str,
+    json: &'static str,
+    /// min_lat,min_lng,max_lat,max_lng, as given to `--bbox`
+    bbox: &'static str,
+}
+
+const FIXTURES: [Fixture; 3] = [
+    Fixture {
+        name: "village",
+        json: include_str!("fixtures/village.json"),
+        bbox: "55.5200,11.9000,55.5227,11.9057",
+    },
+    Fixture {
+        name: "copenhagen_block",
+        json: include_str!("fixtures/copenhagen_block.json"),
+        bbox: "55.6900,12.5500,55.6915,12.5532",
+    },
+    Fixture {
+        name: "coast",
+        json: include_str!("fixtures/coast.json"),
+        bbox: "56.0000,8.1200,56.0036,8.1264",
+    },
+];
+
+impl Fixture {
+    fn bbox(&self) -> LLBBox {
+        LLBBox::from_str(self.bbox).expect("fixture bbox")
+    }
+
+    fn osm_data(&self) -> OsmData {
+        serde_json::from_str(self.json).expect("fixture JSON")
+    }
+
+    fn parse(&self) -> (Vec<ProcessedElement>, XZBBox) {
+        osm_parser::parse_osm_data(self.osm_data(), self.bbox(), 1.0, false)
+    }
+
+    fn args(&self, output_dir: &str) -> Args {
+        Args::parse_from(["arnis", "--output-dir", output_dir, "--bbox", self.bbox])
+    }
+}
+
+/// Rolling terrain the size of the fixture's area, in meters, falling below sea level
+/// towards its western edge
+fn relief(xzbbox: &XZBBox) -> Vec<Vec<f64>> {
+    let width = (xzbbox.max_x() - xzbbox.min_x() + 1) as usize;
+    let depth = (xzbbox.max_z() - xzbbox.min_z() + 1) as usize;
+    (0..depth)
+        .map(|z| {
+            (0..width)
+                .map(|x| {
+                    let (x, z) = (x as f64, z as f64);
+                    x.min(120.0) / 10.0 - 2.0 + 3.0 * (x / 37.0).sin() * (z / 53.0).cos()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parsing");
+    for fixture in &FIXTURES {
+        group.throughput(Throughput::Bytes(fixture.json.len() as u64));
+        group.bench_function(fixture.name, |b| b.iter(|| fixture.parse()));
+    }
+    group.finish();
+}
+
+/// One BBR registration per building of the fixture, at the middle of its footprint, with
+/// usages taken in turn from a few common codes
+fn bbr_buildings(
+    elements: &[ProcessedElement],
+    transformer: &CoordTransformer,
+) -> Vec<BbrBuilding> {
+    const USAGES: [u16; 4] = [120, 130, 322, 414];
+    elements
+        .iter()
+        .filter(|element| element.tags().contains_key("building"))
+        .enumerate()
+        .filter_map(|(i, element)| {
+            let (min_x, min_z, max_x, max_z) = element.nodes().fold(
+                (i32::MAX, i32::MAX, i32::MIN, i32::MIN),
+                |(min_x, min_z, max_x, max_z), node| {
+                    (
+                        min_x.min(node.x),
+                        min_z.min(node.z),
+                        max_x.max(node.x),
+                        max_z.max(node.z),
+                    )
+                },
+            );
+            (min_x <= max_x).then(|| BbrBuilding {
+                id: format!("{i:08}"),
+                usage: USAGES[i % USAGES.len()],
+                wall_material: Some(1),
+                roof_material: None,
+                point: transformer.inverse_transform(
+                    (min_x + max_x) as f64 / 2.0 + 0.5,
+                    (min_z + max_z) as f64 / 2.0 + 0.5,
+                ),
+            })
+        })
+        .collect()
+}
+
+/// The enrichment steps that run without a download: tagging buildings with their BBR
+/// registrations, then estimating the levels of the buildings that have none
+fn enrichment(c: &mut Criterion) {
+    let mut group = c.benchmark_group("enrichment");
+    let codes = CodeTables::built_in();
+    for fixture in &FIXTURES {
+        let (elements, xzbbox) = fixture.parse();
+        let (transformer, _) =
+            CoordTransformer::llbbox_to_xzbbox(&fixture.bbox(), 1.0).expect("fixture transform");
+        let buildings = bbr_buildings(&elements, &transformer);
+        group.throughput(Throughput::Elements(elements.len() as u64));
+        group.bench_function(fixture.name, |b| {
+            b.iter_batched(
+                || elements.clone(),
+                |mut elements| {
+                    bbr::apply_building_data(&mut elements, &buildings, &transformer, &codes);
+                    let medians = BlockMedians::of(&elements, &xzbbox);
+                    height_estimation::estimate_building_levels(&mut elements, &medians, &xzbbox)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn terrain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("terrain");
+    for fixture in &FIXTURES {
+        let (_, xzbbox) = fixture.parse();
+        let grid = relief(&xzbbox);
+        let sea: Vec<Vec<bool>> = grid
+            .iter()
+            .map(|row| row.iter().map(|&h| h < 0.0).collect())
+            .collect();
+        let cells = grid.iter().map(Vec::len).sum::<usize>();
+        group.throughput(Throughput::Elements(cells as u64));
+        group.bench_function(fixture.name, |b| {
+            b.iter_batched(
+                || grid.clone(),
+                |grid| {
+                    let mut pipeline = TerrainPipeline::new();
+                    pipeline.push(GaussianBlur { sigma: 2.0 });
+                    pipeline.push(FlattenMask {
+                        mask: &sea,
+                        max_height: 0.0,
+                    });
+                    pipeline.push(SlopeAnalysis {
+                        cell_size: 1.0,
+                        steep_deg: 30.0,
+                    });
+                    pipeline.run(grid)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generation");
+    // Every iteration writes a world, so fewer samples keep the run in minutes
+    group.sample_size(10);
+    let output = tempfile::tempdir().expect("temporary output directory");
+    let output_dir = output.path().to_str().expect("UTF-8 temporary path");
+    for fixture in &FIXTURES {
+        let args = fixture.args(output_dir);
+        let (elements, xzbbox) = fixture.parse();
+        group.throughput(Throughput::Elements(elements.len() as u64));
+        group.bench_function(fixture.name, |b| {
+            b.iter_batched(
+                || {
+                    let world = world_utils::create_new_world(output.path()).expect("new world");
+                    (elements.clone(), PathBuf::from(world))
+                },
+                |(elements, world)| {
+                    let options = GenerationOptions {
+                        path: world,
+                        format: WorldFormat::JavaAnvil,
+                        level_name: None,
+                        spawn_point: None,
+                        update_spawn_after_generation: false,
+                        coverage_map: None,
+                        world_xzbbox: xzbbox.clone(),
+                        block_medians: None,
+                    };
+                    let ground = Ground::new_flat(args.ground_level);
+                    data_processing::generate_world_with_options(
+                        elements,
+                        Vec::new(),
+                        xzbbox.clone(),
+                        fixture.bbox(),
+                        ground,
+                        &args,
+                        options,
+                    )
+                    .expect("generation")
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parsing, enrichment, terrain, generation);
+criterion_main!(benches);
@@ -0,0 +1,324 @@
+//! Generation areas given by name (`--area "Odense Kommune"`, `--area 8000`).
+//!
+//! The kommune or postnummer is looked up in DAWA (DAGI boundaries on Dataforsyningen), its
+//! bounding box becomes the `--bbox` and generation is clipped to the boundary polygon:
+//! the boundary is added to the elements as a marker way so it follows map transformations,
+//! and taken out again before processing to build the mask of the area's blocks.
+
+use crate::args::Cli;
+use crate::coordinate_system::cartesian::XZBBox;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, SyntheticIds, BOUNDARY_IDS};
+use crate::floodfill_cache::CoordinateBitmap;
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
+use clap::CommandFactory;
+use std::collections::HashMap;
+use std::ffi::OsString;
+
+const DAWA_URL: &str = "https://api.dataforsyningen.dk";
+/// Tag of the marker ways carrying the boundary rings through the pipeline
+const BOUNDARY_TAG: &str = "arnis:area_boundary";
+
+/// Boundary of a named area: every ring of its polygons as (lat, lng), combined even-odd
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AreaBoundary {
+    pub name: String,
+    pub rings: Vec<Vec<(f64, f64)>>,
+}
+
+impl AreaBoundary {
+    pub fn bbox(&self) -> Result<LLBBox, String> {
+        let points = || self.rings.iter().flatten();
+        let min_lat = points().map(|p| p.0).fold(f64::MAX, f64::min);
+        let max_lat = points().map(|p| p.0).fold(f64::MIN, f64::max);
+        let min_lng = points().map(|p| p.1).fold(f64::MAX, f64::min);
+        let max_lng = points().map(|p| p.1).fold(f64::MIN, f64::max);
+        LLBBox::new(min_lat, min_lng, max_lat, max_lng)
+    }
+
+    /// Marker ways for the boundary rings, projected like the OSM data
+    pub fn marker_ways(&self, transformer: &CoordTransformer) -> Vec<ProcessedElement> {
+        let mut ids = SyntheticIds::new(BOUNDARY_IDS);
+        self.rings
+            .iter()
+            .map(|ring| {
+                let nodes = ring
+                    .iter()
+                    .filter_map(|&(lat, lng)| {
+                        let point = transformer.transform_point(LLPoint::new(lat, lng).ok()?);
+                        Some(ProcessedNode {
+                            id: ids.next_id(),
+                            tags: HashMap::new(),
+                            x: point.x,
+                            z: point.z,
+                            exact: None,
+                        })
+                    })
+                    .collect();
+                ProcessedElement::Way(ProcessedWay {
+                    id: ids.next_id(),
+                    nodes,
+                    tags: HashMap::from([(BOUNDARY_TAG.to_string(), self.name.clone())]),
+                })
+            })
+            .collect()
+    }
+}
+
+/// DAWA request for an area: a four-digit postnummer, otherwise a kommune name
+fn area_request(query: &str) -> Result<reqwest::Url, String> {
+    let query = query.trim();
+    let url = if query.len() == 4 && query.chars().all(|c| c.is_ascii_digit()) {
+        reqwest::Url::parse_with_params(
+            &format!("{DAWA_URL}/postnumre"),
+            &[("nr", query), ("format", "geojson"), ("srid", "4326")],
+        )
+    } else {
+        let name = query
+            .strip_suffix(" Kommune")
+            .or_else(|| query.strip_suffix(" kommune"))
+            .unwrap_or(query);
+        reqwest::Url::parse_with_params(
+            &format!("{DAWA_URL}/kommuner"),
+            &[("navn", name), ("format", "geojson"), ("srid", "4326")],
+        )
+    };
+    url.map_err(|e| format!("Invalid area '{query}': {e}"))
+}
+
+/// Looks up the boundary of a kommune or postnummer in DAWA.
+pub fn fetch_area(query: &str) -> Result<AreaBoundary, String> {
+    println!("Looking up area '{query}' in DAWA...");
+    let url = area_request(query)?;
+    let bytes = enrichment::download("DAWA", "area", url.as_str(), url.as_str())?;
+    let rings = parse_boundary(&bytes)?;
+    if rings.is_empty() {
+        return Err(format!("No kommune or postnummer named '{query}' in DAWA"));
+    }
+    Ok(AreaBoundary {
+        name: query.to_string(),
+        rings,
+    })
+}
+
+/// Rings of the first feature in a GeoJSON response, as (lat, lng)
+fn parse_boundary(bytes: &[u8]) -> Result<Vec<Vec<(f64, f64)>>, String> {
+    let json: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid DAWA response: {e}"))?;
+    let feature = match json.get("features") {
+        Some(features) => match features.as_array().and_then(|f| f.first()) {
+            Some(feature) => feature,
+            None => return Ok(Vec::new()),
+        },
+        None => &json,
+    };
+    let geometry = feature
+        .get("geometry")
+        .ok_or_else(|| "Invalid DAWA response: feature without geometry".to_string())?;
+    let coordinates = geometry.get("coordinates");
+    let polygons: Vec<&serde_json::Value> = match geometry.get("type").and_then(|t| t.as_str()) {
+        Some("Polygon") => coordinates.into_iter().collect(),
+        Some("MultiPolygon") => coordinates
+            .and_then(|c| c.as_array())
+            .map(|polygons| polygons.iter().collect())
+            .unwrap_or_default(),
+        other => return Err(format!("Unsupported boundary geometry {other:?}")),
+    };
+    Ok(polygons
+        .into_iter()
+        .filter_map(|polygon| polygon.as_array())
+        .flatten()
+        .filter_map(enrichment::geojson_line)
+        .filter(|ring| ring.len() >= 3)
+        .collect())
+}
+
+/// The `--area` and whether `--bbox` is given, parsed by clap with `--bbox` made optional.
+/// `None` when the command line does not parse; the full parse reports why.
+fn area_query(args: &[OsString]) -> Option<(Option<String>, bool)> {
+    let optional_bbox = |command: clap::Command| command.mut_arg("bbox", |arg| arg.required(false));
+    let matches = optional_bbox(Cli::command())
+        .mut_subcommand("generate", optional_bbox)
+        .try_get_matches_from(args)
+        .ok()?;
+    let matches = match matches.subcommand() {
+        Some(("generate", generate)) => generate,
+        Some(_) => return Some((None, false)),
+        None => &matches,
+    };
+    let query = matches.get_one::<String>("area").cloned();
+    Some((query, matches.contains_id("bbox")))
+}
+
+/// Resolves `--area` before the full parse: looks the area up and adds its bbox as
+/// `--bbox` when none is given. Returns the arguments and the boundary, if any.
+pub fn resolve_area_args(
+    args: Vec<OsString>,
+) -> Result<(Vec<OsString>, Option<AreaBoundary>), String> {
+    let Some((Some(query), has_bbox)) = area_query(&args) else {
+        return Ok((args, None));
+    };
+
+    let boundary = fetch_area(&query)?;
+    let mut args = args;
+    if !has_bbox {
+        let bbox = boundary.bbox()?;
+        let (min, max) = (bbox.min(), bbox.max());
+        println!(
+            "Area '{query}': bbox {},{},{},{}",
+            min.lat(),
+            min.lng(),
+            max.lat(),
+            max.lng()
+        );
+        args.push("--bbox".into());
+        args.push(format!("{},{},{},{}", min.lat(), min.lng(), max.lat(), max.lng()).into());
+    }
+    Ok((args, Some(boundary)))
+}
+
+/// Takes the boundary marker ways out of the elements and rasterizes them into the mask of
+/// blocks inside the area. Returns `None` when no area is given.
+pub fn take_boundary_mask(
+    elements: &mut Vec<ProcessedElement>,
+    xzbbox: &XZBBox,
+) -> Option<CoordinateBitmap> {
+    let mut rings: Vec<Vec<(i32, i32)>> = Vec::new();
+    elements.retain(|element| match element {
+        ProcessedElement::Way(way) if way.tags.contains_key(BOUNDARY_TAG) => {
+            rings.push(way.nodes.iter().map(|n| (n.x, n.z)).collect());
+            false
+        }
+        _ => true,
+    });
+    if rings.is_empty() {
+        return None;
+    }
+
+    let mut mask = CoordinateBitmap::new(xzbbox);
+    fill_rings(&mut mask, &rings, xzbbox);
+    Some(mask)
+}
+
+/// Sets the blocks inside the rings, combined even-odd, in the mask
+pub(crate) fn fill_rings(mask: &mut CoordinateBitmap, rings: &[Vec<(i32, i32)>], xzbbox: &XZBBox) {
+    for z in xzbbox.min_z()..=xzbbox.max_z() {
+        for (from, to) in row_spans(rings, z) {
+            for x in from.max(xzbbox.min_x())..=to.min(xzbbox.max_x()) {
+                mask.set(x, z);
+            }
+        }
+    }
+}
+
+/// Even-odd spans (inclusive x ranges) of the rings along the centre line of row `z`
+fn row_spans(rings: &[Vec<(i32, i32)>], z: i32) -> Vec<(i32, i32)> {
+    let zc = z as f64 + 0.5;
+    let mut crossings: Vec<f64> = Vec::new();
+    for ring in rings {
+        for (i, &(x1, z1)) in ring.iter().enumerate() {
+            let (x2, z2) = ring[(i + 1) % ring.len()];
+            let (z1f, z2f) = (z1 as f64, z2 as f64);
+            if (z1f <= zc) != (z2f <= zc) {
+                crossings.push(x1 as f64 + (zc - z1f) / (z2f - z1f) * (x2 - x1) as f64);
+            }
+        }
+    }
+    crossings.sort_by(f64::total_cmp);
+    crossings
+        .chunks_exact(2)
+        .filter_map(|pair| {
+            let (from, to) = (
+                (pair[0] - 0.5).ceil() as i32,
+                (pair[1] - 0.5).floor() as i32,
+            );
+            (from <= to).then_some((from, to))
+        })
+        .collect()
+}
+
+/// Whether any part of an element lies inside the area
+pub fn touches_area(element: &ProcessedElement, mask: &CoordinateBitmap) -> bool {
+    match element {
+        ProcessedElement::Relation(relation) => relation
+            .members
+            .iter()
+            .any(|member| member.way.nodes.iter().any(|n| mask.contains(n.x, n.z))),
+        _ => element.nodes().any(|n| mask.contains(n.x, n.z)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_area_with_clap() {
+        let argv = |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+        assert_eq!(
+            area_query(&argv(&["arnis", "--area", "8000"])),
+            Some((Some("8000".to_string()), false))
+        );
+        assert_eq!(
+            area_query(&argv(&[
+                "arnis",
+                "generate",
+                "--area=Odense Kommune",
+                "--bbox",
+                "55.3,10.3,55.4,10.4"
+            ])),
+            Some((Some("Odense Kommune".to_string()), true))
+        );
+        assert_eq!(
+            area_query(&argv(&[
+                "arnis",
+                "preview",
+                "--bbox",
+                "55.3,10.3,55.4,10.4"
+            ])),
+            Some((None, false))
+        );
+    }
+
+    #[test]
+    fn parses_geojson_boundaries() {
+        let body = br#"{"type":"FeatureCollection","features":[{"type":"Feature",
+            "geometry":{"type":"MultiPolygon","coordinates":[
+                [[[10.0,55.0],[10.2,55.0],[10.2,55.1],[10.0,55.0]]],
+                [[[10.5,55.2],[10.6,55.2],[10.6,55.3],[10.5,55.2]]]]}}]}"#;
+        let rings = parse_boundary(body).unwrap();
+        assert_eq!(rings.len(), 2);
+        assert_eq!(rings[0][1], (55.0, 10.2));
+
+        let boundary = AreaBoundary {
+            name: "Test".to_string(),
+            rings,
+        };
+        let bbox = boundary.bbox().unwrap();
+        assert_eq!((bbox.min().lat(), bbox.max().lng()), (55.0, 10.6));
+
+        let empty = br#"{"type":"FeatureCollection","features":[]}"#;
+        assert!(parse_boundary(empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn postnumre_and_kommuner_are_told_apart() {
+        assert!(area_request("8000").unwrap().path().ends_with("/postnumre"));
+        let kommune = area_request("Odense Kommune").unwrap();
+        assert!(kommune.path().ends_with("/kommuner"));
+        assert!(kommune.query().unwrap().contains("navn=Odense&"));
+    }
+
+    #[test]
+    fn mask_follows_the_rings_even_odd() {
+        // A 10x10 square with a 4x4 hole
+        let rings = vec![
+            vec![(0, 0), (10, 0), (10, 10), (0, 10)],
+            vec![(3, 3), (7, 3), (7, 7), (3, 7)],
+        ];
+        assert_eq!(row_spans(&rings, 1), vec![(0, 9)]);
+        assert_eq!(row_spans(&rings, 5), vec![(0, 2), (7, 9)]);
+        assert!(row_spans(&rings, 12).is_empty());
+    }
+}
@@ -1,25 +1,61 @@
+use crate::area::AreaBoundary;
 use crate::coordinate_system::geographic::LLBBox;
-use clap::Parser;
+use crate::coordinate_system::snap::SnapMode;
+use crate::dhm::ResampleMethod;
+use crate::element_processing::man_made::FloodScenario;
+use crate::element_processing::natural::Tide;
+use crate::elevation_data::ElevationMode;
+use crate::feature_index::StrippableClass;
+use crate::map_transformation::anchor::Anchor;
+use crate::overlay::Overlay;
+use crate::world_editor::BorderStyle;
+use crate::world_utils::{GameMode, TimeOfDay};
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Duration;
 
 /// Command-line arguments parser
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 pub struct Args {
-    /// Bounding box of the area (min_lat,min_lng,max_lat,max_lng) (required)
+    /// Bounding box of the area (min_lat,min_lng,max_lat,max_lng) (required unless --area)
     #[arg(long, allow_hyphen_values = true, value_parser = LLBBox::from_str)]
     pub bbox: LLBBox,
 
+    /// Kommune name or postnummer to generate instead of a bbox, e.g. "Odense Kommune" or
+    /// 8000 (optional). The boundary is looked up in DAWA and generation is clipped to it.
+    #[arg(long)]
+    pub area: Option<String>,
+
+    /// Boundary of --area, resolved before the arguments are parsed
+    #[arg(skip)]
+    pub area_boundary: Option<AreaBoundary>,
+
+    /// GeoJSON file of polygons kept free of generation, e.g. for your own builds (optional).
+    /// A feature's `mode` property is `terrain` (ground only, the default) or `empty`.
+    #[arg(long)]
+    pub exclusion_zones: Option<PathBuf>,
+
     /// JSON file containing OSM data (optional)
     #[arg(long, group = "location")]
     pub file: Option<String>,
 
+    /// Local OSM extract (.osm or .osm.pbf) to read instead of querying Overpass (optional),
+    /// e.g. Geofabrik's Denmark extract. Only what the bbox needs is kept.
+    #[arg(long, conflicts_with = "file")]
+    pub osm_file: Option<PathBuf>,
+
     /// Path to an extracted OSM land polygons shapefile (.shp).
     /// Recommended dataset: land-polygons-complete-4326 from osmdata.openstreetmap.de.
     #[arg(long)]
     pub land_polygons: Option<PathBuf>,
 
+    /// Leave this run out of the local run history (history.jsonl in the Arnis config
+    /// directory)
+    #[arg(long)]
+    pub no_history: bool,
+
     /// JSON file to save OSM data to (optional)
     #[arg(long, group = "location")]
     pub save_json_file: Option<String>,
@@ -37,14 +73,43 @@ pub struct Args {
     #[arg(long, default_value = "requests")]
     pub downloader: String,
 
+    /// Overpass API mirror to download from (optional, repeatable), replacing the public ones.
+    /// The mirrors are tried in turn, moving on when one is overloaded.
+    #[arg(long = "overpass-url", value_name = "URL")]
+    pub overpass_urls: Vec<String>,
+
     /// World scale to use, in blocks per meter
     #[arg(long, default_value_t = 1.0)]
     pub scale: f64,
 
+    /// How positions are snapped to blocks: the block they lie in, or the nearest whole
+    /// coordinate
+    #[arg(long, value_enum, default_value_t = SnapMode::Floor)]
+    pub snap: SnapMode,
+
     /// Ground level to use in the Minecraft world
     #[arg(long, default_value_t = -62)]
     pub ground_level: i32,
 
+    /// How real elevation maps to Y: `relative` fits the local height range above the
+    /// ground level, `absolute` keeps real meters above sea level (see --meters-per-block).
+    #[arg(long, value_enum, default_value_t = ElevationMode::Relative)]
+    pub elevation_mode: ElevationMode,
+
+    /// Real meters per block of height in absolute elevation mode
+    #[arg(long, default_value_t = 1.0)]
+    pub meters_per_block: f64,
+
+    /// Minecraft Y of sea level in absolute elevation mode (defaults to 16 blocks above the
+    /// ground level, leaving room for land below sea level)
+    #[arg(long)]
+    pub sea_level_y: Option<i32>,
+
+    /// Vertical exaggeration of the terrain, e.g. 3.0 to make flat landscapes dramatic.
+    /// Still compressed to fit the build height in relative elevation mode.
+    #[arg(long, default_value_t = 1.0)]
+    pub terrain_exaggeration: f64,
+
     /// Enable terrain (optional)
     #[arg(long)]
     pub terrain: bool,
@@ -53,10 +118,27 @@ pub struct Args {
     #[arg(long, default_value_t = true)]
     pub interior: bool,
 
+    /// Furnish shops, offices, churches and schools for their use, taking the usage from
+    /// BBR, the Danish building register, where OSM does not tag it (optional, BBR needs
+    /// --dhm-token)
+    #[arg(long)]
+    pub interiors: bool,
+
+    /// TOML file extending the BBR code tables; `arnis codes --bbr` shows the tables and
+    /// the codes the last run could not map (optional)
+    #[arg(long)]
+    pub bbr_codes: Option<PathBuf>,
+
     /// Enable roof generation (optional)
     #[arg(long, default_value_t = true)]
     pub roof: bool,
 
+    /// Within this many blocks of the edge of the whole area, where features are often
+    /// clipped, build buildings without interiors and with flat roofs, and end roads running
+    /// off the edge at a barrier (optional, 0 = off)
+    #[arg(long, default_value_t = 0)]
+    pub edge_margin: u32,
+
     /// Enable filling ground (optional)
     #[arg(long, default_value_t = false)]
     pub fillground: bool,
@@ -67,6 +149,108 @@ pub struct Args {
     #[arg(long, default_value_t = true)]
     pub city_boundaries: bool,
 
+    /// Fill residential, commercial and retail areas that have no mapped buildings with
+    /// generic perimeter blocks along their streets (optional). The massing is synthetic
+    /// and listed as such in the --manifest.
+    #[arg(long)]
+    pub urban_infill: bool,
+
+    /// Fetch street names and house numbers from DAWA, the Danish address register, and
+    /// place street-name signs at intersections and house-number signs at buildings (optional)
+    #[arg(long)]
+    pub addresses: bool,
+
+    /// Fetch town, village and landmark names from Danske Stednavne and mark each place
+    /// with a post of name signs at its centre (optional)
+    #[arg(long)]
+    pub place_names: bool,
+
+    /// Choose ground materials from the GeoDanmark orthophoto where OSM has no landuse:
+    /// bare fields, heath, sand and gravel instead of grass (optional, needs --dhm-token)
+    #[arg(long)]
+    pub ortho: bool,
+
+    /// Write arnis-coverage.png before any block is placed, showing where the DHM terrain
+    /// and the orthophoto have data and, with --interiors, which buildings BBR matched, so a
+    /// wrong token or missing coverage can be caught early (optional)
+    #[arg(long)]
+    pub coverage_map: bool,
+
+    /// Stop the run before any block is placed when the DHM terrain or the orthophoto
+    /// covers less than this share of the area, or BBR matches less of the buildings' area,
+    /// in percent (optional)
+    #[arg(long, value_name = "PERCENT")]
+    pub min_coverage: Option<f64>,
+
+    /// Render the generated world top down, blend it at 50% over the GeoDanmark
+    /// orthophoto into arnis-ortho-check.png and report how far the two are offset, to
+    /// catch projection and scale mistakes on a small test area (optional, needs
+    /// --dhm-token, Java only)
+    #[arg(long)]
+    pub ortho_check: bool,
+
+    /// Decide land and sea from the GeoDanmark coastline instead of the OSM coastline,
+    /// with harbour basins and breakwaters (optional, needs --dhm-token)
+    #[arg(long)]
+    pub geodanmark_coast: bool,
+
+    /// Take road widths, lanes and surfaces OSM does not tag from the GeoDanmark road
+    /// centerlines, and build motorways as separate carriageways (optional, needs
+    /// --dhm-token)
+    #[arg(long)]
+    pub geodanmark_roads: bool,
+
+    /// Fetch heaths, meadows, bogs and lakes protected under §3 from Danmarks Miljøportal
+    /// and render them where OSM only maps grass or nothing (optional)
+    #[arg(long)]
+    pub protected_nature: bool,
+
+    /// Plant each field with its declared crop from Markkort, Landbrugsstyrelsen's field
+    /// parcel map: cereals, rape, maize, potatoes, beets or grass (optional)
+    #[arg(long)]
+    pub field_crops: bool,
+
+    /// Crop year of the Markkort fields for --field-crops; the latest year Markkort
+    /// publishes if not given (optional)
+    #[arg(long, value_name = "YEAR")]
+    pub field_crops_year: Option<u16>,
+
+    /// Type forests as deciduous, coniferous or mixed, with their main species, from the
+    /// Miljøstyrelsen forest map where OSM has no leaf_type (optional)
+    #[arg(long)]
+    pub forest_types: bool,
+
+    /// Cover countryside without OSM landuse with fields, meadow, forest or heath from
+    /// CORINE Land Cover, refined block by block by the orthophoto with --ortho (optional)
+    #[arg(long)]
+    pub land_cover: bool,
+
+    /// Build wind turbines to the hub height and rotor diameter in Energistyrelsen's
+    /// turbine register, adding the turbines OSM does not map (optional)
+    #[arg(long)]
+    pub wind_turbines: bool,
+
+    /// Build the burial mounds, dolmens and rune stones registered in Fund og Fortidsminder
+    /// that OSM does not map (optional)
+    #[arg(long)]
+    pub ancient_monuments: bool,
+
+    /// Directory of community landmark builds (.schem) for the landmarks in the registry,
+    /// e.g. Rundetårn; landmarks with a build there are built from it at --scale 1 (optional)
+    #[arg(long)]
+    pub landmarks: Option<PathBuf>,
+
+    /// GTFS feed directory (e.g. Rejseplanen's GTFS.zip unzipped) whose stops get bus stop
+    /// signs with route numbers, shelters and rail platforms (optional)
+    #[arg(long)]
+    pub gtfs: Option<PathBuf>,
+
+    /// Keep only allowlisted fields on register data and drop anything shaped like a CPR,
+    /// CVR or phone number before it reaches the world, its signs, books or metadata
+    /// (optional). What was removed is counted in arnis-privacy-audit.json.
+    #[arg(long)]
+    pub privacy_audit: bool,
+
     /// Dataforsyningen token for DHM (Danmarks Højdemodel) high-res terrain.
     /// Create one at https://dataforsyningen.dk under your profile.
     /// Provides 0.4m resolution terrain instead of the default AWS tiles.
@@ -74,6 +258,157 @@ pub struct Args {
     #[arg(long, env = "DHM_TOKEN")]
     pub dhm_token: Option<String>,
 
+    /// Attempts per round when a DHM request fails, with 2s, 4s, ... backoff between them
+    #[arg(long, default_value_t = 3)]
+    pub dhm_retries: u32,
+
+    /// Rounds of DHM attempts before giving up
+    #[arg(long, default_value_t = 5)]
+    pub dhm_rounds: u32,
+
+    /// Pause between DHM retry rounds (seconds)
+    #[arg(long, value_parser = parse_duration, default_value = "60")]
+    pub dhm_round_pause: Duration,
+
+    /// Give up on DHM after this many seconds of retrying, instead of after all rounds (optional)
+    #[arg(long, value_parser = parse_duration)]
+    pub dhm_total_timeout: Option<Duration>,
+
+    /// Local GeoTIFF DEM in EPSG:25832 (e.g. a downloaded DHM tile) used as terrain source
+    /// instead of fetching DHM, for offline generation. Requires --terrain.
+    #[arg(long)]
+    pub dem: Option<PathBuf>,
+
+    /// Interpolation used when mapping DHM pixels onto the terrain grid
+    /// (nearest/bilinear/bicubic). Nearest shows terracing when upsampling.
+    #[arg(long, value_enum, default_value_t = ResampleMethod::Bilinear)]
+    pub dhm_resample: ResampleMethod,
+
+    /// DHM resolution in metres per pixel, from the native 0.4 upwards (optional).
+    /// By default large bboxes are fetched in tiles at 1.6 or 3.2 and smoothed more lightly.
+    #[arg(long)]
+    pub dhm_resolution: Option<f64>,
+
+    /// Lower sea areas to a sampled sea-floor depth instead of flat sea level (optional).
+    /// Requires --terrain with DHM. Uses EMODnet Bathymetry unless --bathymetry-token is set.
+    #[arg(long)]
+    pub bathymetry: bool,
+
+    /// Turn sea-level flats connected to the open sea into water (optional), so harbours,
+    /// fjords and lagoons are water even where OSM water polygons are missing.
+    /// Requires --terrain with DHM.
+    #[arg(long)]
+    pub carve_sea: bool,
+
+    /// Render tidal flats (wetland=tidalflat) at high or low tide (optional): shallow water,
+    /// or exposed mud with tidal channels. Without it flats are patchy mud and water.
+    #[arg(long, value_enum)]
+    pub tide: Option<Tide>,
+
+    /// Flood scenario for movable flood barriers and harbour gates
+    /// (man_made=storm_surge_barrier / flood_gate): open at normal water, shut in a storm surge.
+    #[arg(long, value_enum, default_value_t = FloodScenario::Normal)]
+    pub flood_scenario: FloodScenario,
+
+    /// Make rivers, canals and harbour fairways boatable (optional): channels at least 5
+    /// blocks wide and 2 deep, clear of reeds and marked with red and green lane buoys, a
+    /// current running down rivers, lateral buoys and beacons at harbour entrances, and
+    /// bridges over them and harbour basins raised for headroom without pillars in the water.
+    #[arg(long)]
+    pub navigable_waterways: bool,
+
+    /// Dataforsyningen token for GST's Danmarks Dybdemodel, used as bathymetry source.
+    /// Can also be set via the BATHYMETRY_TOKEN environment variable.
+    #[arg(long, env = "BATHYMETRY_TOKEN")]
+    pub bathymetry_token: Option<String>,
+
+    /// Pin a lat/lon to a fixed block coordinate, as "lat,lng,x,z" (optional), instead of
+    /// putting the bbox's north-west corner at 0,0. Runs with the same anchor and scale share
+    /// one coordinate frame, so overlapping or adjacent exports can be merged.
+    #[arg(long, allow_hyphen_values = true, value_parser = Anchor::from_str)]
+    pub anchor: Option<Anchor>,
+
+    /// Directory to export the generation layers to as GIS files (optional).
+    /// Writes the parsed elements and road corridors as GeoJSON and the terrain as an
+    /// ESRI ASCII grid, so the world can be overlaid with the source data in QGIS.
+    #[arg(long)]
+    pub export_gis: Option<PathBuf>,
+
+    /// File to export the terrain border rows and sea level to (optional), so a neighbouring
+    /// bbox can be generated to match this one at the seam with --seam-from.
+    #[arg(long)]
+    pub seam_export: Option<PathBuf>,
+
+    /// Seam sidecar exported by the run of an adjacent bbox (optional, repeatable). The shared
+    /// border is pinned to its heights and sea level so both terrains meet exactly; use with
+    /// --elevation-mode absolute.
+    #[arg(long)]
+    pub seam_from: Vec<PathBuf>,
+
+    /// Render accessibility features (optional): tactile paving on kerbs, slab ramps at
+    /// wheelchair-accessible entrances and marked disabled parking bays.
+    #[arg(long)]
+    pub accessibility: bool,
+
+    /// Skip age-restricted venues such as pubs, casinos and adult shops (optional).
+    /// Buildings housing them are kept, but without their names and venue details.
+    #[arg(long)]
+    pub school_mode: bool,
+
+    /// Colour road surfaces by tag attributes (optional), for thematic maps.
+    /// A preset (speed, road-class, cycling) or rules like "maxspeed >= 80 => red; bicycle => lime".
+    #[arg(long, value_parser = Overlay::from_str)]
+    pub overlay: Option<Overlay>,
+
+    /// Lay a rideable minecart network along railways (optional): powered rail boosters
+    /// and button-operated stops at stations and platforms.
+    #[arg(long)]
+    pub minecart_transit: bool,
+
+    /// Write a guided tour datapack visiting the main landmarks (optional, Java only).
+    /// Start it in game with /function arnis_tour:start.
+    #[arg(long)]
+    pub tour: bool,
+
+    /// Write a flythrough camera path datapack following the main roads and the coastline
+    /// (optional, Java only). Start it in game with /function arnis_camera:start.
+    #[arg(long)]
+    pub camera_path: bool,
+
+    /// Place a lectern at spawn with a book about the world: its area, buildings, longest
+    /// road, highest point, data sources and credits (optional, Java only)
+    #[arg(long)]
+    pub info_book: bool,
+
+    /// Lock the daylight cycle at this time of day (optional, Java only)
+    #[arg(long, value_enum)]
+    pub time_of_day: Option<TimeOfDay>,
+
+    /// Default game mode of the world (optional, Java only)
+    #[arg(long, value_enum)]
+    pub gamemode: Option<GameMode>,
+
+    /// Showcase world preset (optional, Java only): locked golden hour, clear weather
+    /// and spectator mode. --time-of-day and --gamemode override the preset.
+    #[arg(long)]
+    pub showcase: bool,
+
+    /// How the world ends at the edge of the area (optional): a vanilla world border
+    /// (square, around the longest side; Java), an ocean fade-out, a bedrock and glass wall,
+    /// or void outside the area (Java)
+    #[arg(long, value_enum)]
+    pub border: Option<BorderStyle>,
+
+    /// Record which chunks every feature class was written into (arnis-features.json,
+    /// optional, Java only), so `arnis strip` can later remove a single feature class.
+    #[arg(long)]
+    pub feature_index: bool,
+
+    /// Write a checksum manifest (arnis-manifest.json) into the world (optional).
+    /// Records input data checksums, the config and output region hashes for `arnis verify`.
+    #[arg(long)]
+    pub manifest: bool,
+
     /// Enable debug mode (optional)
     #[arg(long)]
     pub debug: bool,
@@ -83,6 +418,171 @@ pub struct Args {
     pub timeout: Option<Duration>,
 }
 
+/// Top-level command line: a subcommand, or the generation flags directly.
+/// Calling `arnis --bbox ...` without a subcommand is kept working as `arnis generate`.
+#[derive(Parser, Debug)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub generate: Option<Args>,
+}
+
+impl Cli {
+    /// Resolves the legacy flag-only invocation into `Command::Generate`.
+    pub fn into_command(self) -> Result<Command, String> {
+        match (self.command, self.generate) {
+            (Some(command), _) => Ok(command),
+            (None, Some(args)) => Ok(Command::Generate(Box::new(args))),
+            (None, None) => Err("No command given. Run with --help for usage.".to_string()),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Generate a Minecraft world for a bounding box (default)
+    Generate(Box<Args>),
+
+    /// Print the world size and tile plan for a bounding box without generating anything
+    Preview(PreviewArgs),
+
+    /// Download the OSM data for a bounding box to a JSON file for later use with --file
+    FetchBundle(FetchBundleArgs),
+
+    /// Re-check a generated world against its checksum manifest
+    Verify(VerifyArgs),
+
+    /// Remove or regenerate one feature class of a world generated with --feature-index
+    Strip(StripArgs),
+
+    /// Merge two Java worlds generated with the same --anchor into a new world
+    Merge(MergeArgs),
+
+    /// Check whether a newer Arnis release is available
+    Update,
+
+    /// Inspect or clean the local elevation tile cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    /// List the local history of generation runs, or compare two of them
+    History {
+        #[command(subcommand)]
+        action: HistoryAction,
+    },
+
+    /// Print the code tables that register data is mapped with
+    Codes(CodesArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct PreviewArgs {
+    /// Bounding box of the area (min_lat,min_lng,max_lat,max_lng) (required)
+    #[arg(long, allow_hyphen_values = true, value_parser = LLBBox::from_str)]
+    pub bbox: LLBBox,
+
+    /// World scale to use, in blocks per meter
+    #[arg(long, default_value_t = 1.0)]
+    pub scale: f64,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FetchBundleArgs {
+    /// Bounding box of the area (min_lat,min_lng,max_lat,max_lng) (required)
+    #[arg(long, allow_hyphen_values = true, value_parser = LLBBox::from_str)]
+    pub bbox: LLBBox,
+
+    /// JSON file to write the OSM data to (required)
+    #[arg(long)]
+    pub output: PathBuf,
+
+    /// Downloader method (requests/curl/wget) (optional)
+    #[arg(long, default_value = "requests")]
+    pub downloader: String,
+
+    /// Enable debug mode (optional)
+    #[arg(long)]
+    pub debug: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    /// World directory (Java) or .mcworld file (Bedrock) written with --manifest (required)
+    pub world: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StripArgs {
+    /// Java world directory generated with --feature-index (required)
+    pub world: PathBuf,
+
+    /// Feature class to remove. Every block of the class in the chunks it was written into
+    /// is removed, whatever placed it: hedges, gardens, roller coasters and player builds in
+    /// those chunks included (required)
+    #[arg(long, value_enum)]
+    pub feature: StrippableClass,
+
+    /// Java world generated with --feature-index for the same --anchor and scale from newer
+    /// data, e.g. for a smaller bbox around a change; its blocks of the class are copied
+    /// into the stripped world, regenerating the class without regenerating the world
+    /// around it (optional)
+    #[arg(long, value_name = "WORLD")]
+    pub regenerate_from: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct MergeArgs {
+    /// First Java world directory; its level.dat and datapacks are kept (required)
+    pub world_a: PathBuf,
+
+    /// Second Java world directory (required)
+    pub world_b: PathBuf,
+
+    /// Directory to write the merged world to; must not exist or be empty (required)
+    #[arg(long)]
+    pub out: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CodesArgs {
+    /// The BBR usage, wall material and roofing codes, with the codes the last run could
+    /// not map (required)
+    #[arg(long, required = true)]
+    pub bbr: bool,
+
+    /// TOML file extending the BBR code tables, as given to --bbr-codes
+    #[arg(long)]
+    pub bbr_codes: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq)]
+pub enum CacheAction {
+    /// Show how many elevation tiles are cached and their total size
+    Info,
+    /// Delete cached tiles older than the cache age limit
+    Clean,
+    /// Delete all cached tiles
+    Clear,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum HistoryAction {
+    /// List the recorded runs
+    List,
+    /// Compare the options, durations and statistics of two runs
+    Diff {
+        /// Number of the first run, or `last` (required)
+        run1: String,
+        /// Number of the second run, or `last` (required)
+        run2: String,
+    },
+}
+
 /// Validates CLI arguments after parsing.
 /// For Java Edition: `--path` is required and must point to an existing directory
 /// where a new world will be created automatically.
@@ -97,6 +597,43 @@ pub fn validate_args(args: &Args) -> Result<(), String> {
         }
     }
 
+    if let Some(ref dem) = args.dem {
+        if !dem.is_file() {
+            return Err(format!("DEM file does not exist: {}", dem.display()));
+        }
+        if !args.terrain {
+            return Err("The --dem flag requires --terrain.".to_string());
+        }
+    }
+
+    if args.bbr_codes.is_some() {
+        crate::bbr_codes::CodeTables::load(args.bbr_codes.as_deref())?;
+    }
+
+    if args.terrain_exaggeration.is_nan() || args.terrain_exaggeration <= 0.0 {
+        return Err("--terrain-exaggeration must be greater than 0.".to_string());
+    }
+
+    if args
+        .dhm_resolution
+        .is_some_and(|resolution| resolution.is_nan() || resolution <= 0.0)
+    {
+        return Err("--dhm-resolution must be greater than 0.".to_string());
+    }
+
+    if args.elevation_mode == ElevationMode::Absolute {
+        if args.meters_per_block.is_nan() || args.meters_per_block <= 0.0 {
+            return Err("--meters-per-block must be greater than 0.".to_string());
+        }
+        if args.sea_level_y.is_some_and(|y| y < args.ground_level) {
+            return Err("--sea-level-y must not be below --ground-level.".to_string());
+        }
+    }
+
+    if args.bathymetry && !args.terrain {
+        return Err("The --bathymetry flag requires --terrain.".to_string());
+    }
+
     if args.bedrock {
         // Bedrock: path is optional; if provided, it must be an existing directory
         if let Some(ref path) = args.path {
@@ -214,6 +751,66 @@ mod tests {
         assert!(result.unwrap_err().contains("does not exist"));
     }
 
+    #[test]
+    fn test_bathymetry_requires_terrain() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let tmp_path = tmpdir.path().to_str().unwrap();
+
+        let cmd = [
+            "arnis",
+            "--output-dir",
+            tmp_path,
+            "--bbox",
+            "1,2,3,4",
+            "--bathymetry",
+        ];
+        let args = Args::parse_from(cmd.iter());
+        assert!(validate_args(&args).is_err());
+
+        let cmd = [
+            "arnis",
+            "--output-dir",
+            tmp_path,
+            "--bbox",
+            "1,2,3,4",
+            "--bathymetry",
+            "--terrain",
+        ];
+        let args = Args::parse_from(cmd.iter());
+        assert!(validate_args(&args).is_ok());
+    }
+
+    #[test]
+    fn test_cli_without_subcommand_generates() {
+        let cmd = ["arnis", "--bbox", "1,2,3,4", "--terrain"];
+        let command = Cli::parse_from(cmd.iter()).into_command().unwrap();
+        assert!(matches!(command, Command::Generate(args) if args.terrain));
+
+        let cmd = ["arnis", "generate", "--bbox", "1,2,3,4", "--terrain"];
+        let command = Cli::parse_from(cmd.iter()).into_command().unwrap();
+        assert!(matches!(command, Command::Generate(args) if args.terrain));
+    }
+
+    #[test]
+    fn test_cli_subcommands() {
+        let cmd = ["arnis", "preview", "--bbox", "1,2,3,4", "--scale", "2"];
+        let command = Cli::parse_from(cmd.iter()).into_command().unwrap();
+        assert!(matches!(command, Command::Preview(args) if args.scale == 2.0));
+
+        let cmd = ["arnis", "cache", "clear"];
+        let command = Cli::parse_from(cmd.iter()).into_command().unwrap();
+        assert!(matches!(
+            command,
+            Command::Cache {
+                action: CacheAction::Clear
+            }
+        ));
+
+        // Generation flags are not accepted next to another subcommand
+        let cmd = ["arnis", "--terrain", "update"];
+        assert!(Cli::try_parse_from(cmd.iter()).is_err());
+    }
+
     #[test]
     fn test_required_options() {
         let tmpdir = tempfile::tempdir().unwrap();
@@ -0,0 +1,171 @@
+//! Sea-floor depth for below-sea areas of the DHM terrain.
+//!
+//! DHM only covers land, so harbors, fjords and the open sea come back at (or are
+//! filled to) 0m. When `--bathymetry` is enabled, a depth raster is fetched for the
+//! same bbox and sea cells are lowered to the sampled depth before smoothing, so the
+//! sea floor slopes away from the coast instead of sitting flat at sea level.
+
+use crate::args::Args;
+use crate::coordinate_system::geographic::LLBBox;
+use crate::dhm::{decode_geotiff, resample_raster_to_grid, wgs84_to_utm32n, ResampleMethod};
+use reqwest::blocking::Client;
+use std::time::Duration;
+
+/// EMODnet Bathymetry WCS (public, ~115m resolution, no token required)
+const EMODNET_WCS_URL: &str = "https://ows.emodnet-bathymetry.eu/wcs";
+/// GST Danmarks Dybdemodel WCS on Dataforsyningen (token required)
+const GST_DYBDEMODEL_WCS_URL: &str = "https://api.dataforsyningen.dk/dybdemodel_wcs_DAF";
+/// Maximum raster size requested from the depth services
+const MAX_REQUEST_PIXELS: usize = 1024;
+/// DHM cells at or below this height (meters) are considered sea for depth replacement
+const SEA_CELL_MAX_M: f64 = 0.5;
+
+/// Where to fetch sea-floor depth from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BathymetrySource {
+    /// EMODnet Bathymetry mean depth (pan-European)
+    Emodnet,
+    /// GST Danmarks Dybdemodel, authenticated with a Dataforsyningen token
+    Gst(String),
+}
+
+impl BathymetrySource {
+    /// Returns the configured source, or `None` when `--bathymetry` is off.
+    pub fn from_args(args: &Args) -> Option<Self> {
+        if !args.bathymetry {
+            return None;
+        }
+        Some(match &args.bathymetry_token {
+            Some(token) => Self::Gst(token.clone()),
+            None => Self::Emodnet,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Emodnet => "EMODnet Bathymetry",
+            Self::Gst(_) => "Danmarks Dybdemodel",
+        }
+    }
+
+    fn coverage_url(&self, bbox: &LLBBox, width: usize, height: usize) -> String {
+        match self {
+            Self::Emodnet => format!(
+                "{EMODNET_WCS_URL}?SERVICE=WCS&VERSION=2.0.1&REQUEST=GetCoverage\
+                 &COVERAGEID=emodnet__mean&FORMAT=image/tiff\
+                 &SUBSET=Lat({},{})&SUBSET=Long({},{})",
+                bbox.min().lat(),
+                bbox.max().lat(),
+                bbox.min().lng(),
+                bbox.max().lng()
+            ),
+            Self::Gst(token) => {
+                let (min_e, min_n) = wgs84_to_utm32n(bbox.min().lat(), bbox.min().lng());
+                let (max_e, max_n) = wgs84_to_utm32n(bbox.max().lat(), bbox.max().lng());
+                format!(
+                    "{GST_DYBDEMODEL_WCS_URL}?SERVICE=WCS&REQUEST=GetCoverage&VERSION=1.0.0\
+                     &COVERAGE=dybdemodel\
+                     &CRS=EPSG:25832&RESPONSE_CRS=EPSG:25832\
+                     &BBOX={min_e},{min_n},{max_e},{max_n}\
+                     &WIDTH={width}&HEIGHT={height}\
+                     &FORMAT=GTiff\
+                     &token={token}"
+                )
+            }
+        }
+    }
+}
+
+/// Fetches sea-floor elevations (negative meters below sea level) resampled to the
+/// terrain grid. Cells without depth coverage are NaN.
+pub fn fetch_bathymetry(
+    bbox: &LLBBox,
+    grid_width: usize,
+    grid_height: usize,
+    source: &BathymetrySource,
+) -> Result<Vec<Vec<f64>>, String> {
+    println!("Fetching sea-floor depth from {}...", source.name());
+
+    let url = source.coverage_url(
+        bbox,
+        grid_width.min(MAX_REQUEST_PIXELS),
+        grid_height.min(MAX_REQUEST_PIXELS),
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(120))
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {e}"))?;
+
+    // The request URL may contain a token, so it is deliberately left out of errors.
+    let resp = client
+        .get(&url)
+        .send()
+        .map_err(|e| format!("{} request failed: {}", source.name(), e.without_url()))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!("{} returned status {status}", source.name()));
+    }
+
+    let bytes = resp
+        .bytes()
+        .map_err(|e| format!("Failed to read {} response: {e}", source.name()))?;
+    if bytes.first() == Some(&b'<') {
+        let text = String::from_utf8_lossy(&bytes[..bytes.len().min(300)]);
+        return Err(format!("{} returned error: {text}", source.name()));
+    }
+
+    crate::manifest::record_input("bathymetry", source.name(), &bytes);
+
+    let raster = decode_geotiff(&bytes)?;
+    Ok(resample_raster_to_grid(
+        &raster,
+        grid_width,
+        grid_height,
+        ResampleMethod::Bilinear,
+    ))
+}
+
+/// Lowers sea cells of the DHM height grid to the sampled sea-floor elevation.
+/// Land cells and cells without depth coverage are left untouched.
+/// Returns the number of cells changed.
+pub fn apply_bathymetry(height_grid: &mut [Vec<f64>], depths: &[Vec<f64>]) -> usize {
+    let mut lowered = 0;
+    for (row, depth_row) in height_grid.iter_mut().zip(depths) {
+        for (h, &depth) in row.iter_mut().zip(depth_row) {
+            if *h <= SEA_CELL_MAX_M && depth.is_finite() && depth < *h {
+                *h = depth;
+                lowered += 1;
+            }
+        }
+    }
+    lowered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_sea_cells_take_the_sampled_depth() {
+        let mut heights = vec![vec![0.0, 0.3, 4.0, 0.0]];
+        let depths = vec![vec![-6.0, -2.0, -1.0, f64::NAN]];
+
+        let lowered = apply_bathymetry(&mut heights, &depths);
+
+        assert_eq!(lowered, 2);
+        assert_eq!(heights, vec![vec![-6.0, -2.0, 4.0, 0.0]]);
+    }
+
+    #[test]
+    fn gst_source_requires_token_in_url() {
+        let bbox = LLBBox::new(55.0, 10.0, 55.01, 10.01).unwrap();
+        let url = BathymetrySource::Gst("abc".to_string()).coverage_url(&bbox, 10, 10);
+        assert!(url.starts_with(GST_DYBDEMODEL_WCS_URL));
+        assert!(url.ends_with("&token=abc"));
+
+        let url = BathymetrySource::Emodnet.coverage_url(&bbox, 10, 10);
+        assert!(url.contains("SUBSET=Lat(55,55.01)"));
+    }
+}
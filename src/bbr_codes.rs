@@ -0,0 +1,295 @@
+//! Code tables for the BBR building data (`arnis codes --bbr`).
+//!
+//! BBR registers a building's usage (`byg021BygningensAnvendelse`), wall material
+//! (`byg032YdervæggensMateriale`) and roofing (`byg033Tagdækningsmateriale`) as numeric
+//! codes. The tables here map them to how the building is furnished and to the OSM
+//! `building:material` and `roof:material` values. Codes a run meets without a mapping are
+//! counted in the run history, and `arnis codes --bbr` lists them next to the tables. A
+//! TOML file given with `--bbr-codes` extends the tables or overrides their entries:
+//!
+//! ```toml
+//! [usage]
+//! 323 = "shop"
+//!
+//! [wall_material]
+//! 3 = "cement_block"
+//!
+//! [roof_material]
+//! 90 = "roof_tiles"
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Furnishings a usage code can map to; `rooms` is the generic rooms of any building
+pub const FURNISHINGS: [&str; 5] = ["shop", "office", "church", "school", "rooms"];
+
+/// Usage codes (`byg021`): code, BBR description, furnishing
+const USAGE: &[(u16, &str, &str)] = &[
+    (110, "Stuehus til landbrugsejendom", "rooms"),
+    (120, "Fritliggende enfamiliehus", "rooms"),
+    (121, "Sammenbygget enfamiliehus", "rooms"),
+    (122, "Enfamiliehus i tæt-lav bebyggelse", "rooms"),
+    (130, "Række-, kæde- eller dobbelthus", "rooms"),
+    (131, "Række-, kæde- og klyngehus", "rooms"),
+    (132, "Dobbelthus", "rooms"),
+    (140, "Etagebolig", "rooms"),
+    (150, "Kollegium", "rooms"),
+    (160, "Boligbygning til døgninstitution", "rooms"),
+    (190, "Anden bygning til helårsbeboelse", "rooms"),
+    (320, "Kontor, handel og lager", "office"),
+    (321, "Kontor", "office"),
+    (322, "Detailhandel", "shop"),
+    (323, "Lager", "rooms"),
+    (324, "Butikscenter", "shop"),
+    (325, "Tankstation", "shop"),
+    (329, "Anden bygning til kontor, handel og lager", "office"),
+    (410, "Kultur", "rooms"),
+    (411, "Biograf, teater, koncertsted", "rooms"),
+    (412, "Museum", "rooms"),
+    (413, "Bibliotek", "rooms"),
+    (414, "Kirke eller anden bygning til trosudøvelse", "church"),
+    (415, "Forsamlingshus", "rooms"),
+    (420, "Undervisning og forskning", "school"),
+    (421, "Grundskole", "school"),
+    (422, "Universitet", "school"),
+    (429, "Anden bygning til undervisning og forskning", "school"),
+    (510, "Sommerhus", "rooms"),
+];
+
+/// Wall material codes (`byg032`): code, BBR description, `building:material`
+const WALL_MATERIAL: &[(u16, &str, &str)] = &[
+    (1, "Mursten", "brick"),
+    (2, "Letbetonsten", "cement_block"),
+    (4, "Bindingsværk", "timber_framing"),
+    (5, "Træ", "wood"),
+    (6, "Betonelementer", "concrete"),
+    (8, "Metal", "metal"),
+    (11, "Plastmaterialer", "plastic"),
+    (12, "Glas", "glass"),
+];
+
+/// Roofing codes (`byg033`): code, BBR description, `roof:material`
+const ROOF_MATERIAL: &[(u16, &str, &str)] = &[
+    (1, "Built-up", "tar_paper"),
+    (2, "Tagpap med taghældning", "tar_paper"),
+    (3, "Fibercement herunder asbest", "eternit"),
+    (4, "Cementsten", "concrete"),
+    (5, "Tegl", "roof_tiles"),
+    (6, "Metal", "metal"),
+    (7, "Stråtag", "thatch"),
+    (10, "Fibercement, asbestfri", "eternit"),
+    (11, "PVC", "plastic"),
+    (12, "Glas", "glass"),
+    (20, "Levende tage", "grass"),
+];
+
+/// A BBR code field with a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CodeField {
+    Usage,
+    WallMaterial,
+    RoofMaterial,
+}
+
+impl CodeField {
+    pub const ALL: [CodeField; 3] = [
+        CodeField::Usage,
+        CodeField::WallMaterial,
+        CodeField::RoofMaterial,
+    ];
+
+    /// Name of the field in the TOML extensions and the run history
+    pub fn key(self) -> &'static str {
+        match self {
+            CodeField::Usage => "usage",
+            CodeField::WallMaterial => "wall_material",
+            CodeField::RoofMaterial => "roof_material",
+        }
+    }
+
+    fn built_in(self) -> &'static [(u16, &'static str, &'static str)] {
+        match self {
+            CodeField::Usage => USAGE,
+            CodeField::WallMaterial => WALL_MATERIAL,
+            CodeField::RoofMaterial => ROOF_MATERIAL,
+        }
+    }
+
+    /// Name of the statistic counting the run's buildings with `code` unmapped
+    pub fn unmapped_stat(self, code: u16) -> String {
+        format!("bbr_unmapped:{}:{code}", self.key())
+    }
+
+    /// The field and code of an unmapped-code statistic
+    pub fn from_unmapped_stat(stat: &str) -> Option<(Self, u16)> {
+        let mut parts = stat.strip_prefix("bbr_unmapped:")?.split(':');
+        let (key, code) = (parts.next()?, parts.next()?.parse().ok()?);
+        let field = CodeField::ALL
+            .into_iter()
+            .find(|field| field.key() == key)?;
+        Some((field, code))
+    }
+}
+
+impl fmt::Display for CodeField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CodeField::Usage => "Usage (byg021BygningensAnvendelse)",
+            CodeField::WallMaterial => "Wall material (byg032YdervæggensMateriale)",
+            CodeField::RoofMaterial => "Roofing (byg033Tagdækningsmateriale)",
+        })
+    }
+}
+
+/// A code's mapping and where it comes from
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeEntry {
+    pub description: String,
+    pub value: String,
+    pub extension: bool,
+}
+
+/// The code tables: the built-in ones with any extensions on top
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeTables {
+    tables: BTreeMap<CodeField, BTreeMap<u16, CodeEntry>>,
+}
+
+/// Layout of an extension file. TOML keys are strings, so the codes are parsed after.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Extensions {
+    #[serde(default)]
+    usage: BTreeMap<String, String>,
+    #[serde(default)]
+    wall_material: BTreeMap<String, String>,
+    #[serde(default)]
+    roof_material: BTreeMap<String, String>,
+}
+
+impl CodeTables {
+    pub fn built_in() -> Self {
+        let tables = CodeField::ALL
+            .into_iter()
+            .map(|field| {
+                let entries = field.built_in().iter().map(|&(code, description, value)| {
+                    let entry = CodeEntry {
+                        description: description.to_string(),
+                        value: value.to_string(),
+                        extension: false,
+                    };
+                    (code, entry)
+                });
+                (field, entries.collect())
+            })
+            .collect();
+        Self { tables }
+    }
+
+    /// The built-in tables, extended by the TOML file at `path` if one is given
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let mut tables = Self::built_in();
+        if let Some(path) = path {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+            tables
+                .extend(&text)
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+        Ok(tables)
+    }
+
+    /// Adds the mappings of an extension file, replacing built-in ones with the same code
+    fn extend(&mut self, toml_text: &str) -> Result<(), String> {
+        let extensions: Extensions = toml::from_str(toml_text).map_err(|e| e.to_string())?;
+        let fields = [
+            (CodeField::Usage, extensions.usage),
+            (CodeField::WallMaterial, extensions.wall_material),
+            (CodeField::RoofMaterial, extensions.roof_material),
+        ];
+        for (field, mappings) in fields {
+            for (code, value) in mappings {
+                let code: u16 = code
+                    .parse()
+                    .map_err(|_| format!("{} code \"{code}\" is not a number", field.key()))?;
+                if field == CodeField::Usage && !FURNISHINGS.contains(&value.as_str()) {
+                    return Err(format!(
+                        "usage {code} maps to \"{value}\"; expected one of {}",
+                        FURNISHINGS.join(", ")
+                    ));
+                }
+                let table = self.tables.entry(field).or_default();
+                let description = table
+                    .get(&code)
+                    .map(|entry| entry.description.clone())
+                    .unwrap_or_default();
+                let entry = CodeEntry {
+                    description,
+                    value,
+                    extension: true,
+                };
+                table.insert(code, entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// The value `code` maps to, if it has a mapping
+    pub fn value(&self, field: CodeField, code: u16) -> Option<&str> {
+        let entry = self.tables.get(&field)?.get(&code)?;
+        Some(entry.value.as_str())
+    }
+
+    /// The entries of a table in code order
+    pub fn entries(&self, field: CodeField) -> impl Iterator<Item = (u16, &CodeEntry)> {
+        self.tables
+            .get(&field)
+            .into_iter()
+            .flatten()
+            .map(|(&code, entry)| (code, entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions_add_and_override_codes() {
+        let mut tables = CodeTables::built_in();
+        assert_eq!(tables.value(CodeField::Usage, 322), Some("shop"));
+        assert_eq!(tables.value(CodeField::WallMaterial, 3), None);
+
+        tables
+            .extend("[usage]\n323 = \"shop\"\n[wall_material]\n3 = \"cement_block\"\n")
+            .unwrap();
+        assert_eq!(tables.value(CodeField::Usage, 323), Some("shop"));
+        assert_eq!(
+            tables.value(CodeField::WallMaterial, 3),
+            Some("cement_block")
+        );
+        let (_, lager) = tables
+            .entries(CodeField::Usage)
+            .find(|&(code, _)| code == 323)
+            .unwrap();
+        assert_eq!(lager.description, "Lager");
+        assert!(lager.extension);
+
+        assert!(tables.extend("[usage]\n120 = \"castle\"\n").is_err());
+        assert!(tables.extend("[colour]\n1 = \"red\"\n").is_err());
+    }
+
+    #[test]
+    fn unmapped_stats_name_their_field_and_code() {
+        let stat = CodeField::RoofMaterial.unmapped_stat(90);
+        assert_eq!(stat, "bbr_unmapped:roof_material:90");
+        assert_eq!(
+            CodeField::from_unmapped_stat(&stat),
+            Some((CodeField::RoofMaterial, 90))
+        );
+        assert_eq!(CodeField::from_unmapped_stat("osm_elements"), None);
+    }
+}
@@ -52,11 +52,14 @@ impl BedrockBlock {
 /// and their Bedrock Edition equivalents. Many blocks are identical, but some
 /// require translation of property names or values.
 pub fn to_bedrock_block(block: Block) -> BedrockBlock {
-    let java_name = block.name();
+    let java_name = block.name().unwrap_or("air");
 
     // Most blocks have the same name in both editions
     // Handle special cases first, then fall back to direct mapping
     match java_name {
+        // Bedrock has no separate air for caves and tunnels
+        "cave_air" => BedrockBlock::simple("air"),
+
         // Grass block is just "grass_block" in both editions
         "grass_block" => BedrockBlock::simple("grass_block"),
 
@@ -251,6 +254,18 @@ pub fn to_bedrock_block(block: Block) -> BedrockBlock {
             "rail",
             vec![("rail_direction", BedrockBlockStateValue::Int(0))],
         ),
+        "powered_rail" => BedrockBlock::with_states(
+            "golden_rail",
+            vec![
+                ("rail_direction", BedrockBlockStateValue::Int(0)),
+                ("rail_data_bit", BedrockBlockStateValue::Bool(true)),
+            ],
+        ),
+
+        "beetroots" => BedrockBlock::with_states(
+            "beetroot",
+            vec![("growth", BedrockBlockStateValue::Int(7))],
+        ),
 
         // Farmland with moisture
         "farmland" => BedrockBlock::with_states(
@@ -740,7 +755,7 @@ pub fn to_bedrock_block_with_properties(
     block: Block,
     java_properties: Option<&fastnbt::Value>,
 ) -> BedrockBlock {
-    let java_name = block.name();
+    let java_name = block.name().unwrap_or("air");
 
     // If no stored properties were passed, fall back to block.properties()
     // so that blocks placed via set_block_absolute (e.g. doors with half=upper/lower)
@@ -787,6 +802,16 @@ pub fn to_bedrock_block_with_properties(
         return convert_trapdoor(java_name, props_map);
     }
 
+    // Handle crops with age property (Java age → Bedrock growth, both 0-7)
+    if matches!(java_name, "wheat" | "carrots" | "potatoes") {
+        return convert_crop(java_name, props_map);
+    }
+
+    // Handle flowing water with level property (Java level → Bedrock liquid_depth)
+    if java_name == "water" {
+        return convert_water(props_map);
+    }
+
     // Fall back to basic conversion without properties
     to_bedrock_block(block)
 }
@@ -1135,6 +1160,41 @@ fn convert_trapdoor(
     }
 }
 
+/// Convert a Java crop to Bedrock format at the same stage of growth.
+fn convert_crop(
+    java_name: &str,
+    props: Option<&std::collections::HashMap<String, fastnbt::Value>>,
+) -> BedrockBlock {
+    // Crops without an age are fully grown
+    let growth = props
+        .and_then(|props| match props.get("age") {
+            Some(fastnbt::Value::String(age)) => age.parse().ok(),
+            _ => None,
+        })
+        .unwrap_or(7);
+    BedrockBlock::with_states(
+        java_name,
+        vec![("growth", BedrockBlockStateValue::Int(growth))],
+    )
+}
+
+/// Convert Java water to Bedrock format, keeping the level of flowing water.
+fn convert_water(
+    props: Option<&std::collections::HashMap<String, fastnbt::Value>>,
+) -> BedrockBlock {
+    // Water without a level is a source block
+    let depth = props
+        .and_then(|props| match props.get("level") {
+            Some(fastnbt::Value::String(level)) => level.parse().ok(),
+            _ => None,
+        })
+        .unwrap_or(0);
+    BedrockBlock::with_states(
+        "water",
+        vec![("liquid_depth", BedrockBlockStateValue::Int(depth))],
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1150,6 +1210,42 @@ mod tests {
         assert_eq!(bedrock.name, "minecraft:air");
     }
 
+    #[test]
+    fn test_crop_growth() {
+        use crate::block_definitions::{WHEAT, WHEAT_YOUNG};
+        let young = to_bedrock_block_with_properties(WHEAT_YOUNG, None);
+        assert_eq!(young.name, "minecraft:wheat");
+        assert!(matches!(
+            young.states.get("growth"),
+            Some(BedrockBlockStateValue::Int(3))
+        ));
+        let ripe = to_bedrock_block_with_properties(WHEAT, None);
+        assert!(matches!(
+            ripe.states.get("growth"),
+            Some(BedrockBlockStateValue::Int(7))
+        ));
+    }
+
+    #[test]
+    fn test_water_depth() {
+        use crate::block_definitions::WATER;
+        let level = fastnbt::Value::Compound(std::collections::HashMap::from([(
+            "level".to_string(),
+            fastnbt::Value::String("3".to_string()),
+        )]));
+        let flowing = to_bedrock_block_with_properties(WATER, Some(&level));
+        assert_eq!(flowing.name, "minecraft:water");
+        assert!(matches!(
+            flowing.states.get("liquid_depth"),
+            Some(BedrockBlockStateValue::Int(3))
+        ));
+        let source = to_bedrock_block_with_properties(WATER, None);
+        assert!(matches!(
+            source.states.get("liquid_depth"),
+            Some(BedrockBlockStateValue::Int(0))
+        ));
+    }
+
     #[test]
     fn test_grass_block() {
         let bedrock = to_bedrock_block(GRASS_BLOCK);
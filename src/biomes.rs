@@ -0,0 +1,357 @@
+//! Biomes of the generated world.
+//!
+//! Minecraft tints grass, leaves and water by biome and decides from it whether rain or snow
+//! falls, but the chunks are written without biomes, so the game makes everything plains.
+//! Each 4×4 column of the world is given the biome of what was built there instead: forest,
+//! birch forest or taiga under woods by their leaf type, swamp on bogs and marshes, beach on
+//! sand, river along waterways and on lakes, and ocean over the sea. Greenland gets the snowy
+//! and frozen variants, so snow falls there. Java worlds store the biomes by name, Bedrock
+//! worlds by their numeric IDs.
+
+use crate::block_definitions::SAND;
+use crate::bresenham::bresenham_line;
+use crate::coordinate_system::cartesian::XZBBox;
+use crate::floodfill_cache::{CoordinateBitmap, FloodFillCache};
+use crate::osm_parser::{ProcessedElement, ProcessedMemberRole, ProcessedRelation};
+use crate::world_editor::WorldEditor;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// Width of the columns Minecraft stores biomes for
+pub const BIOME_CELL: i32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Biome {
+    #[default]
+    Plains,
+    Forest,
+    BirchForest,
+    Taiga,
+    Swamp,
+    Beach,
+    River,
+    Ocean,
+    SnowyPlains,
+    SnowyTaiga,
+    SnowyBeach,
+    FrozenRiver,
+    FrozenOcean,
+}
+
+impl Biome {
+    /// Namespaced biome ID
+    pub fn name(self) -> &'static str {
+        match self {
+            Biome::Plains => "minecraft:plains",
+            Biome::Forest => "minecraft:forest",
+            Biome::BirchForest => "minecraft:birch_forest",
+            Biome::Taiga => "minecraft:taiga",
+            Biome::Swamp => "minecraft:swamp",
+            Biome::Beach => "minecraft:beach",
+            Biome::River => "minecraft:river",
+            Biome::Ocean => "minecraft:ocean",
+            Biome::SnowyPlains => "minecraft:snowy_plains",
+            Biome::SnowyTaiga => "minecraft:snowy_taiga",
+            Biome::SnowyBeach => "minecraft:snowy_beach",
+            Biome::FrozenRiver => "minecraft:frozen_river",
+            Biome::FrozenOcean => "minecraft:frozen_ocean",
+        }
+    }
+
+    /// Numeric biome ID of Bedrock Edition
+    pub fn bedrock_id(self) -> i32 {
+        match self {
+            Biome::Ocean => 0,
+            Biome::Plains => 1,
+            Biome::Forest => 4,
+            Biome::Taiga => 5,
+            Biome::Swamp => 6,
+            Biome::River => 7,
+            Biome::FrozenRiver => 11,
+            Biome::SnowyPlains => 12,
+            Biome::Beach => 16,
+            Biome::SnowyBeach => 26,
+            Biome::BirchForest => 27,
+            Biome::SnowyTaiga => 30,
+            Biome::FrozenOcean => 46,
+        }
+    }
+
+    /// The biome in an arctic climate, where snow falls instead of rain
+    pub fn wintry(self) -> Self {
+        match self {
+            Biome::Forest | Biome::BirchForest | Biome::Taiga => Biome::SnowyTaiga,
+            Biome::Beach => Biome::SnowyBeach,
+            Biome::River => Biome::FrozenRiver,
+            Biome::Ocean => Biome::FrozenOcean,
+            Biome::Plains | Biome::Swamp => Biome::SnowyPlains,
+            wintry => wintry,
+        }
+    }
+
+    /// Which biome wins where the areas of two overlap: water over the shore over woods
+    fn rank(self) -> u8 {
+        match self {
+            Biome::River => 3,
+            Biome::Beach => 2,
+            Biome::Swamp => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Biome of an area with the given tags, if it has one of its own
+fn area_biome(tags: &HashMap<String, String>) -> Option<Biome> {
+    let tag = |key: &str| tags.get(key).map(String::as_str);
+    match (tag("natural"), tag("landuse")) {
+        (Some("wood"), _) | (_, Some("forest")) => Some(match (tag("leaf_type"), tag("genus")) {
+            (_, Some("Betula")) => Biome::BirchForest,
+            (Some("needleleaved"), _) => Biome::Taiga,
+            _ => Biome::Forest,
+        }),
+        (Some("wetland"), _) => match tag("wetland") {
+            Some("bog" | "marsh" | "swamp" | "fen" | "reedbed") | None => Some(Biome::Swamp),
+            _ => None,
+        },
+        (Some("beach" | "sand" | "dune"), _) => Some(Biome::Beach),
+        (Some("water"), _) | (_, Some("reservoir" | "basin")) => Some(Biome::River),
+        _ if tag("waterway") == Some("riverbank") => Some(Biome::River),
+        _ => None,
+    }
+}
+
+/// Whether a way is a waterway drawn as a line, given the river biome along it
+fn is_waterway_line(tags: &HashMap<String, String>) -> bool {
+    matches!(
+        tags.get("waterway").map(String::as_str),
+        Some("river" | "stream" | "canal")
+    )
+}
+
+/// The biomes of the mapped areas and waterways, by biome cell
+#[derive(Default)]
+pub struct BiomeMap {
+    cells: HashMap<(i32, i32), Biome>,
+}
+
+impl BiomeMap {
+    fn insert(&mut self, cell: (i32, i32), biome: Biome) {
+        let entry = self.cells.entry(cell).or_insert(biome);
+        if biome.rank() > entry.rank() {
+            *entry = biome;
+        }
+    }
+
+    /// Biome of the cell containing (`x`, `z`), if anything mapped there has one
+    pub fn get(&self, x: i32, z: i32) -> Option<Biome> {
+        self.cells
+            .get(&(x.div_euclid(BIOME_CELL), z.div_euclid(BIOME_CELL)))
+            .copied()
+    }
+}
+
+/// Whether (`x`, `z`) is the column a biome cell is sampled at
+fn is_cell_centre(x: i32, z: i32) -> bool {
+    x.rem_euclid(BIOME_CELL) == BIOME_CELL / 2 && z.rem_euclid(BIOME_CELL) == BIOME_CELL / 2
+}
+
+/// Collects the biomes of the elements. Areas give their biome to the cells whose centre
+/// they cover, waterways to every cell they run through.
+pub fn collect_biome_map(
+    elements: &[ProcessedElement],
+    flood_fill_cache: &FloodFillCache,
+    timeout: Option<&Duration>,
+) -> BiomeMap {
+    let mut map = BiomeMap::default();
+    let cell = |x: i32, z: i32| (x.div_euclid(BIOME_CELL), z.div_euclid(BIOME_CELL));
+    for element in elements {
+        match element {
+            ProcessedElement::Way(way) if is_waterway_line(&way.tags) => {
+                for pair in way.nodes.windows(2) {
+                    for (x, _, z) in
+                        bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z)
+                    {
+                        map.insert(cell(x, z), Biome::River);
+                    }
+                }
+            }
+            ProcessedElement::Way(way) => {
+                let Some(biome) = area_biome(&way.tags) else {
+                    continue;
+                };
+                for (x, z) in flood_fill_cache.get_or_compute(way, timeout) {
+                    if is_cell_centre(x, z) {
+                        map.insert(cell(x, z), biome);
+                    }
+                }
+            }
+            ProcessedElement::Relation(relation) => {
+                let Some(biome) = area_biome(&relation.tags) else {
+                    continue;
+                };
+                for (x, z) in relation_fill(relation, flood_fill_cache, timeout) {
+                    if is_cell_centre(x, z) {
+                        map.insert(cell(x, z), biome);
+                    }
+                }
+            }
+            ProcessedElement::Node(_) => {}
+        }
+    }
+    map
+}
+
+/// The blocks of a relation's area: inside its outer rings and outside its inner ones, so
+/// a clearing mapped as a hole in a wood keeps the biome around it
+fn relation_fill(
+    relation: &ProcessedRelation,
+    flood_fill_cache: &FloodFillCache,
+    timeout: Option<&Duration>,
+) -> Vec<(i32, i32)> {
+    // Filled multipolygons are cached as assembled rings with their holes already cut out
+    let rings = flood_fill_cache.multipolygon_rings(relation.id);
+    if !rings.is_empty() {
+        return rings
+            .iter()
+            .flat_map(|ring| flood_fill_cache.get_or_compute(ring, timeout))
+            .collect();
+    }
+    let fill = |role: ProcessedMemberRole| {
+        relation
+            .members
+            .iter()
+            .filter(move |member| member.role == role)
+            .flat_map(|member| flood_fill_cache.get_or_compute(&member.way, timeout))
+    };
+    let holes: HashSet<(i32, i32)> = fill(ProcessedMemberRole::Inner).collect();
+    fill(ProcessedMemberRole::Outer)
+        .filter(|block| !holes.contains(block))
+        .collect()
+}
+
+/// Gives every biome cell of the world its biome: that of the mapped area there, else ocean
+/// over the sea and beach on sand, else plains. `sea` holds the sea filled in from the
+/// coastline; the sea the terrain carves below sea level counts as well, but pools, ponds
+/// and ditches do not. `wintry` gives the arctic variants.
+pub fn assign_biomes(
+    editor: &mut WorldEditor,
+    map: &BiomeMap,
+    sea: &CoordinateBitmap,
+    xzbbox: &XZBBox,
+    wintry: bool,
+) {
+    let cells_x = xzbbox.min_x().div_euclid(BIOME_CELL)..=xzbbox.max_x().div_euclid(BIOME_CELL);
+    let cells_z = xzbbox.min_z().div_euclid(BIOME_CELL)..=xzbbox.max_z().div_euclid(BIOME_CELL);
+    for cell_x in cells_x {
+        for cell_z in cells_z.clone() {
+            // Sample the middle of the cell, or the nearest column inside the world
+            let x = (cell_x * BIOME_CELL + BIOME_CELL / 2).clamp(xzbbox.min_x(), xzbbox.max_x());
+            let z = (cell_z * BIOME_CELL + BIOME_CELL / 2).clamp(xzbbox.min_z(), xzbbox.max_z());
+            let biome = map.get(x, z).unwrap_or_else(|| {
+                if sea.contains(x, z) || editor.is_carved_sea(x, z) {
+                    Biome::Ocean
+                } else if editor.check_for_block(x, 0, z, Some(&[SAND])) {
+                    Biome::Beach
+                } else {
+                    Biome::Plains
+                }
+            });
+            editor.set_biome(x, z, if wintry { biome.wintry() } else { biome });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn areas_get_the_biome_of_what_grows_there() {
+        assert_eq!(
+            area_biome(&tags(&[("landuse", "forest")])),
+            Some(Biome::Forest)
+        );
+        assert_eq!(
+            area_biome(&tags(&[("natural", "wood"), ("leaf_type", "needleleaved")])),
+            Some(Biome::Taiga)
+        );
+        assert_eq!(
+            area_biome(&tags(&[("landuse", "forest"), ("genus", "Betula")])),
+            Some(Biome::BirchForest)
+        );
+        assert_eq!(
+            area_biome(&tags(&[("natural", "wetland"), ("wetland", "bog")])),
+            Some(Biome::Swamp)
+        );
+        assert_eq!(
+            area_biome(&tags(&[("natural", "beach")])),
+            Some(Biome::Beach)
+        );
+        assert_eq!(
+            area_biome(&tags(&[("natural", "water")])),
+            Some(Biome::River)
+        );
+        assert_eq!(area_biome(&tags(&[("landuse", "farmland")])), None);
+        assert!(is_waterway_line(&tags(&[("waterway", "stream")])));
+        assert!(!is_waterway_line(&tags(&[("waterway", "ditch")])));
+    }
+
+    #[test]
+    fn water_wins_over_the_woods_it_runs_through() {
+        let mut map = BiomeMap::default();
+        map.insert((0, 0), Biome::Forest);
+        map.insert((0, 0), Biome::River);
+        map.insert((0, 0), Biome::Taiga);
+        assert_eq!(map.get(3, 1), Some(Biome::River));
+        assert_eq!(map.get(-1, 1), None);
+        assert!(is_cell_centre(-2, 6));
+        assert!(!is_cell_centre(0, 2));
+        assert_eq!(Biome::Ocean.wintry(), Biome::FrozenOcean);
+        assert_eq!(Biome::Taiga.wintry(), Biome::SnowyTaiga);
+    }
+
+    #[test]
+    fn clearings_in_a_wood_keep_out_of_its_biome() {
+        use crate::osm_parser::{ProcessedMember, ProcessedRelation};
+        use crate::test_utilities::way;
+        use std::sync::Arc;
+
+        let member = |role: ProcessedMemberRole, id: u64, points: &[(i32, i32)]| {
+            let ProcessedElement::Way(way) = way(id, &[], points) else {
+                unreachable!()
+            };
+            ProcessedMember {
+                role,
+                way: Arc::new(way),
+            }
+        };
+        // A wood, cached as a filled multipolygon, and a lake, filled from its members
+        let elements: Vec<ProcessedElement> = [(1, "landuse", "forest"), (2, "natural", "water")]
+            .into_iter()
+            .map(|(id, key, value)| {
+                let offset = id as i32 * 100;
+                let ring = |from: i32, to: i32| {
+                    let (from, to) = (offset + from, offset + to);
+                    [(from, from), (to, from), (to, to), (from, to), (from, from)]
+                };
+                ProcessedElement::Relation(ProcessedRelation {
+                    id,
+                    tags: tags(&[("type", "multipolygon"), (key, value)]),
+                    members: vec![
+                        member(ProcessedMemberRole::Outer, id * 10, &ring(0, 40)),
+                        member(ProcessedMemberRole::Inner, id * 10 + 1, &ring(14, 30)),
+                    ],
+                })
+            })
+            .collect();
+        let xzbbox = XZBBox::rect_from_xz_lengths(300.0, 300.0).unwrap();
+        let cache = FloodFillCache::precompute(&elements, &xzbbox, None);
+        let map = collect_biome_map(&elements, &cache, None);
+
+        assert_eq!(map.get(102, 102), Some(Biome::Forest));
+        assert_eq!(map.get(122, 122), None);
+        assert_eq!(map.get(202, 202), Some(Biome::River));
+        assert_eq!(map.get(222, 222), None);
+    }
+}
@@ -96,238 +96,252 @@ impl Block {
         "minecraft"
     }
 
-    pub fn name(&self) -> &str {
+    /// The block of a Minecraft block name such as "stone_bricks", if it is one of ours
+    pub fn from_name(name: &str) -> Option<Block> {
+        (0..=u8::MAX)
+            .map(Block::new)
+            .find(|block| block.name() == Some(name))
+    }
+
+    /// Minecraft name of the block, or `None` for an ID no block has been given
+    pub fn name(&self) -> Option<&'static str> {
         match self.id {
-            0 => "acacia_planks",
-            1 => "air",
-            2 => "andesite",
-            3 => "birch_leaves",
-            4 => "birch_log",
-            5 => "black_concrete",
-            6 => "blackstone",
-            7 => "blue_orchid",
-            8 => "blue_terracotta",
-            9 => "bricks",
-            10 => "cauldron",
-            11 => "chiseled_stone_bricks",
-            12 => "cobblestone_wall",
-            13 => "cobblestone",
-            14 => "polished_blackstone_bricks",
-            15 => "cracked_stone_bricks",
-            16 => "crimson_planks",
-            17 => "cut_sandstone",
-            18 => "cyan_concrete",
-            19 => "dark_oak_planks",
-            20 => "deepslate_bricks",
-            21 => "diorite",
-            22 => "dirt",
-            23 => "end_stone_bricks",
-            24 => "farmland",
-            25 => "glass",
-            26 => "glowstone",
-            27 => "granite",
-            28 => "grass_block",
-            29 => "short_grass",
-            30 => "gravel",
-            31 => "gray_concrete",
-            32 => "gray_terracotta",
-            33 => "green_terracotta",
-            34 => "green_wool",
-            35 => "hay_block",
-            36 => "iron_bars",
-            37 => "iron_block",
-            38 => "jungle_planks",
-            39 => "ladder",
-            40 => "light_blue_concrete",
-            41 => "light_blue_terracotta",
-            42 => "light_gray_concrete",
-            43 => "moss_block",
-            44 => "mossy_cobblestone",
-            45 => "mud_bricks",
-            46 => "nether_bricks",
-            47 => "netherite_block",
-            48 => "oak_fence",
-            49 => "oak_leaves",
-            50 => "oak_log",
-            51 => "oak_planks",
-            52 => "oak_slab",
-            53 => "orange_terracotta",
-            54 => "podzol",
-            55 => "polished_andesite",
-            56 => "polished_basalt",
-            57 => "quartz_block",
-            58 => "polished_blackstone",
-            59 => "polished_deepslate",
-            60 => "polished_diorite",
-            61 => "polished_granite",
-            62 => "prismarine",
-            63 => "purpur_block",
-            64 => "purpur_pillar",
-            65 => "quartz_bricks",
-            66 => "rail",
-            67 => "poppy",
-            68 => "red_nether_bricks",
-            69 => "red_terracotta",
-            70 => "red_wool",
-            71 => "sand",
-            72 => "sandstone",
-            73 => "scaffolding",
-            74 => "smooth_quartz",
-            75 => "smooth_red_sandstone",
-            76 => "smooth_sandstone",
-            77 => "smooth_stone",
-            78 => "sponge",
-            79 => "spruce_log",
-            80 => "spruce_planks",
-            81 => "stone_slab",
-            82 => "stone_brick_slab",
-            83 => "stone_bricks",
-            84 => "stone",
-            85 => "terracotta",
-            86 => "warped_planks",
-            87 => "water",
-            88 => "white_concrete",
-            89 => "azure_bluet",
-            90 => "white_stained_glass",
-            91 => "white_terracotta",
-            92 => "white_wool",
-            93 => "yellow_concrete",
-            94 => "dandelion",
-            95 => "yellow_wool",
-            96 => "lime_concrete",
-            97 => "cyan_wool",
-            98 => "blue_concrete",
-            99 => "purple_concrete",
-            100 => "red_concrete",
-            101 => "magenta_concrete",
-            102 => "brown_wool",
-            103 => "oxidized_copper",
-            104 => "yellow_terracotta",
-            105 => "carrots",
-            106 => "dark_oak_door",
-            107 => "dark_oak_door",
-            108 => "potatoes",
-            109 => "wheat",
-            110 => "bedrock",
-            111 => "snow_block",
-            112 => "snow",
-            113 => "oak_sign",
-            114 => "andesite_wall",
-            115 => "stone_brick_wall",
-            116..=125 => "rail",
-            126 => "coarse_dirt",
-            127 => "iron_ore",
-            128 => "coal_ore",
-            129 => "gold_ore",
-            130 => "copper_ore",
-            131 => "clay",
-            132 => "dirt_path",
-            133 => "ice",
-            134 => "packed_ice",
-            135 => "mud",
-            136 => "dead_bush",
-            137..=138 => "tall_grass",
-            139 => "crafting_table",
-            140 => "furnace",
-            141 => "white_carpet",
-            142 => "bookshelf",
-            143 => "oak_pressure_plate",
-            144 => "oak_stairs",
-            155 => "chest",
-            156 => "red_carpet",
-            157 => "anvil",
-            158 => "note_block",
-            159 => "oak_door",
-            160 => "brewing_stand",
-            161 => "red_bed", // North head
-            162 => "red_bed", // North foot
-            163 => "red_bed", // East head
-            164 => "red_bed", // East foot
-            165 => "red_bed", // South head
-            166 => "red_bed", // South foot
-            167 => "red_bed", // West head
-            168 => "red_bed", // West foot
-            169 => "gray_stained_glass",
-            170 => "light_gray_stained_glass",
-            171 => "brown_stained_glass",
-            172 => "tinted_glass",
-            173 => "oak_trapdoor",
-            174 => "brown_concrete",
-            175 => "black_terracotta",
-            176 => "brown_terracotta",
-            177 => "stone_brick_stairs",
-            178 => "mud_brick_stairs",
-            179 => "polished_blackstone_brick_stairs",
-            180 => "brick_stairs",
-            181 => "polished_granite_stairs",
-            182 => "end_stone_brick_stairs",
-            183 => "polished_diorite_stairs",
-            184 => "smooth_sandstone_stairs",
-            185 => "quartz_stairs",
-            186 => "polished_andesite_stairs",
-            187 => "nether_brick_stairs",
-            188 => "barrel",
-            189 => "fern",
-            190 => "cobweb",
-            191 => "chiseled_bookshelf",
-            192 => "chiseled_bookshelf",
-            193 => "chiseled_bookshelf",
-            194 => "chiseled_bookshelf",
-            195 => "chipped_anvil",
-            196 => "damaged_anvil",
-            197 => "large_fern",
-            198 => "large_fern",
-            199 => "chain",
-            200 => "end_rod",
-            201 => "lightning_rod",
-            202 => "gold_block",
-            203 => "sea_lantern",
-            204 => "orange_concrete",
-            205 => "orange_wool",
-            206 => "blue_wool",
-            207 => "green_concrete",
-            208 => "brick_wall",
-            209 => "redstone_block",
-            210 => "chain",
-            211 => "chain",
-            212 => "spruce_door",
-            213 => "spruce_door",
-            214 => "smooth_stone_slab",
-            215 => "glass_pane",
-            216 => "light_gray_terracotta",
-            217 => "oak_slab",
-            218 => "oak_door",
-            219 => "dark_oak_log",
-            220 => "dark_oak_leaves",
-            221 => "jungle_log",
-            222 => "jungle_leaves",
-            223 => "acacia_log",
-            224 => "acacia_leaves",
-            225 => "spruce_leaves",
-            226 => "cyan_stained_glass",
-            227 => "blue_stained_glass",
-            228 => "light_blue_stained_glass",
-            229 => "daylight_detector",
-            230 => "red_stained_glass",
-            231 => "yellow_stained_glass",
-            232 => "purple_stained_glass",
-            233 => "orange_stained_glass",
-            234 => "magenta_stained_glass",
-            235 => "potted_poppy",
-            236 => "oak_trapdoor",
-            237 => "oak_trapdoor",
-            238 => "oak_trapdoor",
-            239 => "oak_trapdoor",
-            240 => "quartz_slab",
-            241 => "dark_oak_trapdoor",
-            242 => "spruce_trapdoor",
-            243 => "birch_trapdoor",
-            244 => "mud_brick_slab",
-            245 => "brick_slab",
-            246 => "potted_red_tulip",
-            247 => "potted_dandelion",
-            248 => "potted_blue_orchid",
-            _ => panic!("Invalid id"),
+            0 => Some("acacia_planks"),
+            1 => Some("air"),
+            2 => Some("andesite"),
+            3 => Some("birch_leaves"),
+            4 => Some("birch_log"),
+            5 => Some("black_concrete"),
+            6 => Some("blackstone"),
+            7 => Some("blue_orchid"),
+            8 => Some("blue_terracotta"),
+            9 => Some("bricks"),
+            10 => Some("cauldron"),
+            11 => Some("chiseled_stone_bricks"),
+            12 => Some("cobblestone_wall"),
+            13 => Some("cobblestone"),
+            14 => Some("polished_blackstone_bricks"),
+            15 => Some("cracked_stone_bricks"),
+            16 => Some("crimson_planks"),
+            17 => Some("cut_sandstone"),
+            18 => Some("cyan_concrete"),
+            19 => Some("dark_oak_planks"),
+            20 => Some("deepslate_bricks"),
+            21 => Some("diorite"),
+            22 => Some("dirt"),
+            23 => Some("end_stone_bricks"),
+            24 => Some("farmland"),
+            25 => Some("glass"),
+            26 => Some("glowstone"),
+            27 => Some("granite"),
+            28 => Some("grass_block"),
+            29 => Some("short_grass"),
+            30 => Some("gravel"),
+            31 => Some("gray_concrete"),
+            32 => Some("gray_terracotta"),
+            33 => Some("green_terracotta"),
+            34 => Some("green_wool"),
+            35 => Some("hay_block"),
+            36 => Some("iron_bars"),
+            37 => Some("iron_block"),
+            38 => Some("jungle_planks"),
+            39 => Some("ladder"),
+            40 => Some("light_blue_concrete"),
+            41 => Some("light_blue_terracotta"),
+            42 => Some("light_gray_concrete"),
+            43 => Some("moss_block"),
+            44 => Some("mossy_cobblestone"),
+            45 => Some("mud_bricks"),
+            46 => Some("nether_bricks"),
+            47 => Some("netherite_block"),
+            48 => Some("oak_fence"),
+            49 => Some("oak_leaves"),
+            50 => Some("oak_log"),
+            51 => Some("oak_planks"),
+            52 => Some("oak_slab"),
+            53 => Some("orange_terracotta"),
+            54 => Some("podzol"),
+            55 => Some("polished_andesite"),
+            56 => Some("polished_basalt"),
+            57 => Some("quartz_block"),
+            58 => Some("polished_blackstone"),
+            59 => Some("polished_deepslate"),
+            60 => Some("polished_diorite"),
+            61 => Some("polished_granite"),
+            62 => Some("prismarine"),
+            63 => Some("purpur_block"),
+            64 => Some("purpur_pillar"),
+            65 => Some("quartz_bricks"),
+            66 => Some("rail"),
+            67 => Some("poppy"),
+            68 => Some("red_nether_bricks"),
+            69 => Some("red_terracotta"),
+            70 => Some("red_wool"),
+            71 => Some("sand"),
+            72 => Some("sandstone"),
+            73 => Some("scaffolding"),
+            74 => Some("smooth_quartz"),
+            75 => Some("smooth_red_sandstone"),
+            76 => Some("smooth_sandstone"),
+            77 => Some("smooth_stone"),
+            78 => Some("sponge"),
+            79 => Some("spruce_log"),
+            80 => Some("spruce_planks"),
+            81 => Some("stone_slab"),
+            82 => Some("stone_brick_slab"),
+            83 => Some("stone_bricks"),
+            84 => Some("stone"),
+            85 => Some("terracotta"),
+            86 => Some("warped_planks"),
+            87 => Some("water"),
+            88 => Some("white_concrete"),
+            89 => Some("azure_bluet"),
+            90 => Some("white_stained_glass"),
+            91 => Some("white_terracotta"),
+            92 => Some("white_wool"),
+            93 => Some("yellow_concrete"),
+            94 => Some("dandelion"),
+            95 => Some("yellow_wool"),
+            96 => Some("lime_concrete"),
+            97 => Some("cyan_wool"),
+            98 => Some("blue_concrete"),
+            99 => Some("purple_concrete"),
+            100 => Some("red_concrete"),
+            101 => Some("magenta_concrete"),
+            102 => Some("brown_wool"),
+            103 => Some("oxidized_copper"),
+            104 => Some("yellow_terracotta"),
+            105 => Some("carrots"),
+            106 => Some("dark_oak_door"),
+            107 => Some("dark_oak_door"),
+            108 => Some("potatoes"),
+            109 => Some("wheat"),
+            110 => Some("bedrock"),
+            111 => Some("snow_block"),
+            112 => Some("snow"),
+            113 => Some("oak_sign"),
+            114 => Some("andesite_wall"),
+            115 => Some("stone_brick_wall"),
+            116..=125 => Some("rail"),
+            126 => Some("coarse_dirt"),
+            127 => Some("iron_ore"),
+            128 => Some("coal_ore"),
+            129 => Some("gold_ore"),
+            130 => Some("copper_ore"),
+            131 => Some("clay"),
+            132 => Some("dirt_path"),
+            133 => Some("ice"),
+            134 => Some("packed_ice"),
+            135 => Some("mud"),
+            136 => Some("dead_bush"),
+            137..=138 => Some("tall_grass"),
+            139 => Some("crafting_table"),
+            140 => Some("furnace"),
+            141 => Some("white_carpet"),
+            142 => Some("bookshelf"),
+            143 => Some("oak_pressure_plate"),
+            144 => Some("oak_stairs"),
+            145 => Some("lectern"),
+            146 => Some("cave_air"),
+            147 => Some("wheat"),
+            148 => Some("beetroots"),
+            155 => Some("chest"),
+            156 => Some("red_carpet"),
+            157 => Some("anvil"),
+            158 => Some("note_block"),
+            159 => Some("oak_door"),
+            160 => Some("brewing_stand"),
+            161 => Some("red_bed"), // North head
+            162 => Some("red_bed"), // North foot
+            163 => Some("red_bed"), // East head
+            164 => Some("red_bed"), // East foot
+            165 => Some("red_bed"), // South head
+            166 => Some("red_bed"), // South foot
+            167 => Some("red_bed"), // West head
+            168 => Some("red_bed"), // West foot
+            169 => Some("gray_stained_glass"),
+            170 => Some("light_gray_stained_glass"),
+            171 => Some("brown_stained_glass"),
+            172 => Some("tinted_glass"),
+            173 => Some("oak_trapdoor"),
+            174 => Some("brown_concrete"),
+            175 => Some("black_terracotta"),
+            176 => Some("brown_terracotta"),
+            177 => Some("stone_brick_stairs"),
+            178 => Some("mud_brick_stairs"),
+            179 => Some("polished_blackstone_brick_stairs"),
+            180 => Some("brick_stairs"),
+            181 => Some("polished_granite_stairs"),
+            182 => Some("end_stone_brick_stairs"),
+            183 => Some("polished_diorite_stairs"),
+            184 => Some("smooth_sandstone_stairs"),
+            185 => Some("quartz_stairs"),
+            186 => Some("polished_andesite_stairs"),
+            187 => Some("nether_brick_stairs"),
+            188 => Some("barrel"),
+            189 => Some("fern"),
+            190 => Some("cobweb"),
+            191 => Some("chiseled_bookshelf"),
+            192 => Some("chiseled_bookshelf"),
+            193 => Some("chiseled_bookshelf"),
+            194 => Some("chiseled_bookshelf"),
+            195 => Some("chipped_anvil"),
+            196 => Some("damaged_anvil"),
+            197 => Some("large_fern"),
+            198 => Some("large_fern"),
+            199 => Some("chain"),
+            200 => Some("end_rod"),
+            201 => Some("lightning_rod"),
+            202 => Some("gold_block"),
+            203 => Some("sea_lantern"),
+            204 => Some("orange_concrete"),
+            205 => Some("orange_wool"),
+            206 => Some("blue_wool"),
+            207 => Some("green_concrete"),
+            208 => Some("brick_wall"),
+            209 => Some("redstone_block"),
+            210 => Some("chain"),
+            211 => Some("chain"),
+            212 => Some("spruce_door"),
+            213 => Some("spruce_door"),
+            214 => Some("smooth_stone_slab"),
+            215 => Some("glass_pane"),
+            216 => Some("light_gray_terracotta"),
+            217 => Some("oak_slab"),
+            218 => Some("oak_door"),
+            219 => Some("dark_oak_log"),
+            220 => Some("dark_oak_leaves"),
+            221 => Some("jungle_log"),
+            222 => Some("jungle_leaves"),
+            223 => Some("acacia_log"),
+            224 => Some("acacia_leaves"),
+            225 => Some("spruce_leaves"),
+            226 => Some("cyan_stained_glass"),
+            227 => Some("blue_stained_glass"),
+            228 => Some("light_blue_stained_glass"),
+            229 => Some("daylight_detector"),
+            230 => Some("red_stained_glass"),
+            231 => Some("yellow_stained_glass"),
+            232 => Some("purple_stained_glass"),
+            233 => Some("orange_stained_glass"),
+            234 => Some("magenta_stained_glass"),
+            235 => Some("potted_poppy"),
+            236 => Some("oak_trapdoor"),
+            237 => Some("oak_trapdoor"),
+            238 => Some("oak_trapdoor"),
+            239 => Some("oak_trapdoor"),
+            240 => Some("quartz_slab"),
+            241 => Some("dark_oak_trapdoor"),
+            242 => Some("spruce_trapdoor"),
+            243 => Some("birch_trapdoor"),
+            244 => Some("mud_brick_slab"),
+            245 => Some("brick_slab"),
+            246 => Some("potted_red_tulip"),
+            247 => Some("potted_dandelion"),
+            248 => Some("potted_blue_orchid"),
+            249..=252 => Some("powered_rail"),
+            253 => Some("stone_button"),
+            _ => None,
         }
     }
 
@@ -665,6 +679,50 @@ impl Block {
                 map.insert("half".to_string(), Value::String("top".to_string()));
                 map
             })),
+            // Powered rails, powered (boosters) and unpowered (station stops)
+            249 => Some(Value::Compound({
+                let mut map = HashMap::new();
+                map.insert(
+                    "shape".to_string(),
+                    Value::String("north_south".to_string()),
+                );
+                map.insert("powered".to_string(), Value::String("true".to_string()));
+                map
+            })),
+            250 => Some(Value::Compound({
+                let mut map = HashMap::new();
+                map.insert("shape".to_string(), Value::String("east_west".to_string()));
+                map.insert("powered".to_string(), Value::String("true".to_string()));
+                map
+            })),
+            251 => Some(Value::Compound({
+                let mut map = HashMap::new();
+                map.insert(
+                    "shape".to_string(),
+                    Value::String("north_south".to_string()),
+                );
+                map.insert("powered".to_string(), Value::String("false".to_string()));
+                map
+            })),
+            252 => Some(Value::Compound({
+                let mut map = HashMap::new();
+                map.insert("shape".to_string(), Value::String("east_west".to_string()));
+                map.insert("powered".to_string(), Value::String("false".to_string()));
+                map
+            })),
+            // Stone button on the floor
+            253 => Some(Value::Compound({
+                let mut map = HashMap::new();
+                map.insert("face".to_string(), Value::String("floor".to_string()));
+                map.insert("facing".to_string(), Value::String("north".to_string()));
+                map
+            })),
+            // Green, half-grown wheat and fully grown beetroots
+            147..=148 => Some(Value::Compound({
+                let mut map = HashMap::new();
+                map.insert("age".to_string(), Value::String("3".to_string()));
+                map
+            })),
             _ => None,
         }
     }
@@ -866,6 +924,10 @@ pub const WHITE_CARPET: Block = Block::new(141);
 pub const BOOKSHELF: Block = Block::new(142);
 pub const OAK_PRESSURE_PLATE: Block = Block::new(143);
 pub const OAK_STAIRS: Block = Block::new(144);
+pub const LECTERN: Block = Block::new(145);
+pub const CAVE_AIR: Block = Block::new(146);
+pub const WHEAT_YOUNG: Block = Block::new(147);
+pub const BEETROOTS: Block = Block::new(148);
 pub const CHEST: Block = Block::new(155);
 pub const RED_CARPET: Block = Block::new(156);
 pub const ANVIL: Block = Block::new(157);
@@ -962,6 +1024,11 @@ pub const BRICK_SLAB: Block = Block::new(245);
 pub const POTTED_RED_TULIP: Block = Block::new(246);
 pub const POTTED_DANDELION: Block = Block::new(247);
 pub const POTTED_BLUE_ORCHID: Block = Block::new(248);
+pub const POWERED_RAIL_NORTH_SOUTH: Block = Block::new(249);
+pub const POWERED_RAIL_EAST_WEST: Block = Block::new(250);
+pub const POWERED_RAIL_NORTH_SOUTH_OFF: Block = Block::new(251);
+pub const POWERED_RAIL_EAST_WEST_OFF: Block = Block::new(252);
+pub const STONE_BUTTON: Block = Block::new(253);
 
 /// Maps a block to its corresponding stair variant
 #[inline]
@@ -0,0 +1,281 @@
+//! Flythrough camera path datapack (`--camera-path`).
+//!
+//! Follows the main roads and the coastline of the area in one continuous route and writes
+//! it into the Java world as a datapack that moves the player along it one block step per
+//! tick, so a flythrough video can be recorded without placing keyframes by hand.
+//! `/function arnis_camera:start` puts everyone in spectator mode and starts the flight,
+//! `/function arnis_camera:stop` ends it early. The waypoints are also kept as
+//! `camera_path.json` in the datapack, with the yaw and pitch of every frame, for import
+//! into replay editors.
+//!
+//! The route's source lines are stored in `lines.json`, so tiled generation joins the roads
+//! and coastline of every tile into one route.
+
+use crate::osm_parser::ProcessedElement;
+use crate::tour::{text_component, Datapack};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Road classes the camera follows
+const MAIN_ROADS: &[&str] = &["motorway", "trunk", "primary", "secondary"];
+/// Lines shorter than this (blocks) are too short to be worth flying along
+const MIN_LINE_LENGTH: f64 = 32.0;
+/// Most frames (ticks) in a flight; longer routes take bigger steps
+const MAX_FRAMES: usize = 2400;
+/// The camera flies this high above the smoothed ground
+const CAMERA_HEIGHT: i32 = 40;
+/// Frames over which the ground under the camera is averaged
+const HEIGHT_WINDOW: usize = 20;
+/// The camera looks at the ground this many frames ahead
+const LOOK_AHEAD: usize = 30;
+const NAMESPACE: &str = "arnis_camera";
+
+/// A point on the ground along a road or the coast
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GroundPoint {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// A camera position with the view direction, in Minecraft degrees
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Frame {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f64,
+    pub pitch: f64,
+}
+
+fn line_length(line: &[GroundPoint]) -> f64 {
+    line.windows(2)
+        .map(|pair| distance(&pair[0], &pair[1]))
+        .sum()
+}
+
+fn distance(a: &GroundPoint, b: &GroundPoint) -> f64 {
+    (((b.x - a.x) as f64).powi(2) + ((b.z - a.z) as f64).powi(2)).sqrt()
+}
+
+/// Collects the main roads and coastline ways as lines on the ground.
+/// `ground_level` gives the ground Y at a world coordinate.
+pub fn collect_lines(
+    elements: &[ProcessedElement],
+    ground_level: impl Fn(i32, i32) -> i32,
+) -> Vec<Vec<GroundPoint>> {
+    elements
+        .iter()
+        .filter_map(|element| {
+            let ProcessedElement::Way(way) = element else {
+                return None;
+            };
+            let followed = way
+                .tags
+                .get("highway")
+                .is_some_and(|h| MAIN_ROADS.contains(&h.as_str()))
+                || way.tags.get("natural").is_some_and(|n| n == "coastline");
+            if !followed {
+                return None;
+            }
+            let line: Vec<GroundPoint> = way
+                .nodes
+                .iter()
+                .map(|node| GroundPoint {
+                    x: node.x,
+                    y: ground_level(node.x, node.z),
+                    z: node.z,
+                })
+                .collect();
+            (line_length(&line) >= MIN_LINE_LENGTH).then_some(line)
+        })
+        .collect()
+}
+
+/// Joins lines into one route: starts with the longest line and always continues with the
+/// line whose nearer end is closest, flying straight over the gaps.
+fn chain_lines(mut lines: Vec<Vec<GroundPoint>>) -> Vec<GroundPoint> {
+    let mut route = Vec::new();
+    let Some(first) =
+        (0..lines.len()).max_by(|&a, &b| line_length(&lines[a]).total_cmp(&line_length(&lines[b])))
+    else {
+        return route;
+    };
+    route.extend(lines.swap_remove(first));
+
+    while !lines.is_empty() {
+        let last = *route.last().unwrap();
+        let (next, reversed) = (0..lines.len())
+            .flat_map(|i| [(i, false), (i, true)])
+            .min_by(|&(a, a_rev), &(b, b_rev)| {
+                let end = |i: usize, rev: bool| {
+                    let line = &lines[i];
+                    distance(&last, if rev { line.last() } else { line.first() }.unwrap())
+                };
+                end(a, a_rev).total_cmp(&end(b, b_rev))
+            })
+            .unwrap();
+        let mut line = lines.swap_remove(next);
+        if reversed {
+            line.reverse();
+        }
+        route.extend(line);
+    }
+    route
+}
+
+/// Points along the route every `step` blocks
+fn resample(route: &[GroundPoint], step: f64) -> Vec<(f64, f64, f64)> {
+    let mut points = Vec::new();
+    let Some(first) = route.first() else {
+        return points;
+    };
+    points.push((first.x as f64, first.y as f64, first.z as f64));
+    let mut carried = 0.0;
+    for pair in route.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let length = distance(a, b);
+        let mut at = step - carried;
+        while at <= length {
+            let t = at / length;
+            points.push((
+                a.x as f64 + t * (b.x - a.x) as f64,
+                a.y as f64 + t * (b.y - a.y) as f64,
+                a.z as f64 + t * (b.z - a.z) as f64,
+            ));
+            at += step;
+        }
+        carried = (carried + length) % step;
+    }
+    points
+}
+
+/// Camera frames along the lines: one step per tick at a smoothed height above the
+/// ground, looking ahead along the route.
+pub fn camera_frames(lines: Vec<Vec<GroundPoint>>) -> Vec<Frame> {
+    let route = chain_lines(lines);
+    let step = (line_length(&route) / MAX_FRAMES as f64).max(1.0);
+    let points = resample(&route, step);
+    let n = points.len();
+
+    (0..n)
+        .map(|i| {
+            let window = i.saturating_sub(HEIGHT_WINDOW / 2)..(i + HEIGHT_WINDOW / 2).min(n);
+            let ground = window.clone().map(|j| points[j].1).sum::<f64>() / window.len() as f64;
+            let (x, z) = (points[i].0 + 0.5, points[i].2 + 0.5);
+            let y = ground + CAMERA_HEIGHT as f64;
+            let target = points[(i + LOOK_AHEAD).min(n - 1)];
+            let (dx, dy, dz) = (target.0 + 0.5 - x, target.1 - y, target.2 + 0.5 - z);
+            let horizontal = (dx * dx + dz * dz).sqrt().max(1.0);
+            Frame {
+                x,
+                y,
+                z,
+                // Minecraft yaw: 0 faces south (+Z), 90 faces west (-X)
+                yaw: (-dx).atan2(dz).to_degrees(),
+                pitch: (-dy).atan2(horizontal).to_degrees(),
+            }
+        })
+        .collect()
+}
+
+/// Writes (or extends) the camera path datapack in a Java world and returns its directory.
+/// Returns `Ok(None)` when the world has no roads or coastline to follow.
+pub fn write_camera_datapack(
+    world_dir: &Path,
+    lines: Vec<Vec<GroundPoint>>,
+) -> Result<Option<PathBuf>, String> {
+    let pack = Datapack::new(world_dir, NAMESPACE);
+
+    // Join with the lines of previously generated tiles
+    let mut lines = lines;
+    if let Ok(text) = fs::read_to_string(pack.dir.join("lines.json")) {
+        if let Ok(existing) = serde_json::from_str::<Vec<Vec<GroundPoint>>>(&text) {
+            lines.extend(existing);
+        }
+    }
+    let frames = camera_frames(lines.clone());
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+
+    pack.create("Arnis flythrough camera path of the generated area")?;
+    pack.write("lines.json", to_json(&lines)?)?;
+    pack.write("camera_path.json", to_json(&frames)?)?;
+
+    pack.write_function(
+        "start",
+        format!(
+            "# Flythrough of the generated area: /function {NAMESPACE}:start\n\
+             gamemode spectator @a\n\
+             function {NAMESPACE}:frame_1\n"
+        ),
+    )?;
+    for (index, frame) in frames.iter().enumerate() {
+        let mut contents = format!(
+            "tp @a {:.2} {:.2} {:.2} {:.1} {:.1}\n",
+            frame.x, frame.y, frame.z, frame.yaw, frame.pitch
+        );
+        contents.push_str(&if index + 1 < frames.len() {
+            format!("schedule function {NAMESPACE}:frame_{} 1t\n", index + 2)
+        } else {
+            format!(
+                "title @a title {}\n",
+                text_component("End of the flythrough")
+            )
+        });
+        pack.write_function(&format!("frame_{}", index + 1), contents)?;
+    }
+    let clear: String = (1..=frames.len())
+        .map(|i| format!("schedule clear {NAMESPACE}:frame_{i}\n"))
+        .collect();
+    pack.write_function("stop", clear)?;
+
+    Ok(Some(pack.dir))
+}
+
+fn to_json(value: &impl Serialize) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("Failed to serialize camera path: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::way;
+
+    #[test]
+    fn follows_main_roads_and_the_coast() {
+        let elements = vec![
+            way(1, &[("highway", "primary")], &[(0, 0), (100, 0)]),
+            // Runs back towards the end of the road, so it is flown in reverse
+            way(2, &[("natural", "coastline")], &[(100, 60), (110, 0)]),
+            way(3, &[("highway", "residential")], &[(0, 10), (100, 10)]),
+            way(4, &[("highway", "secondary")], &[(0, 20), (5, 20)]),
+        ];
+        let lines = collect_lines(&elements, |_, _| 2);
+        assert_eq!(lines.len(), 2);
+
+        let route = chain_lines(lines);
+        assert_eq!(route.first().map(|p| (p.x, p.z)), Some((0, 0)));
+        assert_eq!(route.last().map(|p| (p.x, p.z)), Some((100, 60)));
+
+        let frames = camera_frames(collect_lines(&elements, |_, _| 2));
+        assert!(frames.iter().all(|f| f.y == (2 + CAMERA_HEIGHT) as f64));
+        // Flying east along the road, looking down ahead
+        assert!((frames[0].yaw + 90.0).abs() < 1e-6);
+        assert!(frames[0].pitch > 0.0);
+    }
+
+    #[test]
+    fn resamples_at_even_steps() {
+        let route = [
+            GroundPoint { x: 0, y: 0, z: 0 },
+            GroundPoint { x: 3, y: 0, z: 0 },
+            GroundPoint { x: 3, y: 0, z: 4 },
+        ];
+        let points = resample(&route, 2.0);
+        assert_eq!(points.len(), 4);
+        assert_eq!(points[2], (3.0, 0.0, 1.0));
+    }
+}
@@ -9,6 +9,8 @@ use std::collections::HashMap;
 
 /// Clips a way to the bounding box using Sutherland-Hodgman for polygons or
 /// simple line clipping for polylines. Preserves endpoint IDs for ring assembly.
+/// Clips at the nodes' fractional positions, and the points where the way crosses the
+/// bbox edge keep theirs as well.
 pub fn clip_way_to_bbox(nodes: &[ProcessedNode], xzbbox: &XZBBox) -> Vec<ProcessedNode> {
     if nodes.is_empty() {
         return Vec::new();
@@ -37,7 +39,7 @@ pub fn clip_way_to_bbox(nodes: &[ProcessedNode], xzbbox: &XZBBox) -> Vec<Process
     let max_x = xzbbox.max_x() as f64;
     let max_z = xzbbox.max_z() as f64;
 
-    let mut polygon: Vec<(f64, f64)> = nodes.iter().map(|n| (n.x as f64, n.z as f64)).collect();
+    let mut polygon: Vec<(f64, f64)> = nodes.iter().map(ProcessedNode::position).collect();
 
     polygon = clip_polygon_sutherland_hodgman(polygon, min_x, min_z, max_x, max_z);
 
@@ -127,11 +129,15 @@ pub fn clip_water_ring_to_bbox(
     let mut result: Vec<ProcessedNode> = polygon
         .iter()
         .enumerate()
-        .map(|(i, &(x, z))| ProcessedNode {
-            id: 1_000_000_000 + i as u64,
-            tags: HashMap::new(),
-            x: x.clamp(min_x, max_x).round() as i32,
-            z: z.clamp(min_z, max_z).round() as i32,
+        .map(|(i, &(x, z))| {
+            let (x, z) = (x.clamp(min_x, max_x), z.clamp(min_z, max_z));
+            ProcessedNode {
+                id: 1_000_000_000 + i as u64,
+                tags: HashMap::new(),
+                x: x.round() as i32,
+                z: z.round() as i32,
+                exact: Some((x, z)),
+            }
         })
         .collect();
 
@@ -188,7 +194,7 @@ fn clip_polyline_to_bbox(nodes: &[ProcessedNode], xzbbox: &XZBBox) -> Vec<Proces
 
     for i in 0..nodes.len() {
         let current = &nodes[i];
-        let current_point = (current.x as f64, current.z as f64);
+        let current_point = current.position();
         let current_inside = point_in_bbox(current_point, min_x, min_z, max_x, max_z);
 
         if current_inside {
@@ -196,8 +202,7 @@ fn clip_polyline_to_bbox(nodes: &[ProcessedNode], xzbbox: &XZBBox) -> Vec<Proces
         }
 
         if i + 1 < nodes.len() {
-            let next = &nodes[i + 1];
-            let next_point = (next.x as f64, next.z as f64);
+            let next_point = nodes[i + 1].position();
             let next_inside = point_in_bbox(next_point, min_x, min_z, max_x, max_z);
 
             if current_inside != next_inside {
@@ -215,6 +220,7 @@ fn clip_polyline_to_bbox(nodes: &[ProcessedNode], xzbbox: &XZBBox) -> Vec<Proces
                         x: intersection.0.round() as i32,
                         z: intersection.1.round() as i32,
                         tags: HashMap::new(),
+                        exact: Some(intersection),
                     });
                 }
             } else if !current_inside && !next_inside {
@@ -244,6 +250,7 @@ fn clip_polyline_to_bbox(nodes: &[ProcessedNode], xzbbox: &XZBBox) -> Vec<Proces
                             x: intersection.0.round() as i32,
                             z: intersection.1.round() as i32,
                             tags: HashMap::new(),
+                            exact: Some(intersection),
                         });
                     }
                 }
@@ -699,6 +706,7 @@ fn assign_node_ids_preserving_endpoints(
                             x: coord.0.round() as i32,
                             z: coord.1.round() as i32,
                             tags: HashMap::new(),
+                            exact: Some(coord),
                         };
                     }
                 }
@@ -709,6 +717,7 @@ fn assign_node_ids_preserving_endpoints(
                             x: coord.0.round() as i32,
                             z: coord.1.round() as i32,
                             tags: HashMap::new(),
+                            exact: Some(coord),
                         };
                     }
                 }
@@ -719,6 +728,7 @@ fn assign_node_ids_preserving_endpoints(
                 x: coord.0.round() as i32,
                 z: coord.1.round() as i32,
                 tags: HashMap::new(),
+                exact: Some(coord),
             }
         })
         .collect()
@@ -0,0 +1,120 @@
+//! School mode: filters age-restricted venues out of the generated world.
+//!
+//! Some Danish school IT policies do not allow pubs, casinos or adult shops in worlds
+//! generated for classes. With `--school-mode`, standalone venues of these kinds are
+//! dropped entirely. Buildings that house one keep their shape, but lose the venue tags
+//! (amenity/shop, name, brand, opening hours, ...) so no signage or venue-specific
+//! interior is generated for them.
+
+use crate::osm_parser::ProcessedElement;
+use std::collections::HashMap;
+
+/// `amenity=*` values that are skipped in school mode.
+const RESTRICTED_AMENITIES: &[&str] = &[
+    "bar",
+    "biergarten",
+    "brothel",
+    "casino",
+    "gambling",
+    "nightclub",
+    "pub",
+    "stripclub",
+    "swingerclub",
+    "love_hotel",
+];
+
+/// `shop=*` values that are skipped in school mode.
+const RESTRICTED_SHOPS: &[&str] = &["erotic", "adult", "sex", "gambling"];
+
+/// `leisure=*` values that are skipped in school mode.
+const RESTRICTED_LEISURE: &[&str] = &["adult_gaming_centre"];
+
+/// Tags describing the venue itself, removed from buildings that house one.
+const VENUE_TAGS: &[&str] = &[
+    "amenity",
+    "shop",
+    "leisure",
+    "name",
+    "brand",
+    "operator",
+    "opening_hours",
+    "website",
+    "advertising",
+];
+
+fn is_restricted(tags: &HashMap<String, String>) -> bool {
+    let has = |key: &str, values: &[&str]| {
+        tags.get(key)
+            .is_some_and(|value| values.contains(&value.as_str()))
+    };
+    has("amenity", RESTRICTED_AMENITIES)
+        || has("shop", RESTRICTED_SHOPS)
+        || has("leisure", RESTRICTED_LEISURE)
+}
+
+fn tags_mut(element: &mut ProcessedElement) -> &mut HashMap<String, String> {
+    match element {
+        ProcessedElement::Node(n) => &mut n.tags,
+        ProcessedElement::Way(w) => &mut w.tags,
+        ProcessedElement::Relation(r) => &mut r.tags,
+    }
+}
+
+/// Removes age-restricted venues from the element list.
+/// Returns the number of elements that were dropped or stripped of their venue tags.
+pub fn apply_school_mode(elements: &mut Vec<ProcessedElement>) -> usize {
+    let mut filtered = 0;
+    elements.retain_mut(|element| {
+        if !is_restricted(element.tags()) {
+            return true;
+        }
+        filtered += 1;
+
+        let tags = tags_mut(element);
+        if !tags.contains_key("building") && !tags.contains_key("building:part") {
+            return false;
+        }
+        for key in VENUE_TAGS {
+            tags.remove(*key);
+        }
+        true
+    });
+    filtered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_parser::{ProcessedNode, ProcessedWay};
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn drops_venues_and_strips_their_buildings() {
+        let mut elements = vec![
+            ProcessedElement::Node(ProcessedNode {
+                id: 1,
+                tags: tags(&[("amenity", "pub"), ("name", "Kroen")]),
+                x: 0,
+                z: 0,
+                exact: None,
+            }),
+            ProcessedElement::Node(ProcessedNode {
+                id: 2,
+                tags: tags(&[("amenity", "school")]),
+                x: 0,
+                z: 0,
+                exact: None,
+            }),
+            ProcessedElement::Way(ProcessedWay {
+                id: 3,
+                nodes: Vec::new(),
+                tags: tags(&[("building", "yes"), ("amenity", "casino"), ("name", "X")]),
+            }),
+        ];
+
+        assert_eq!(apply_school_mode(&mut elements), 2);
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[0].id(), 2);
+        assert_eq!(elements[1].tags(), &tags(&[("building", "yes")]));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod cartesian;
 pub mod geographic;
+pub mod snap;
 pub mod transformation;
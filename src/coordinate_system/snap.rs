@@ -0,0 +1,49 @@
+//! Snapping of fractional block positions to whole blocks (`--snap`).
+//!
+//! Positions are projected as fractions of a block, kept as such on the parsed nodes
+//! ([`ProcessedNode::exact`](crate::osm_parser::ProcessedNode::exact)) and snapped to whole
+//! blocks once. The mode travels with the
+//! [`CoordTransformer`](super::transformation::CoordTransformer), so an OSM node and a
+//! register feature projected by it land on blocks by the same rule: a road and its
+//! sidewalk, projected separately, can no longer end up a block apart because one was
+//! truncated and the other rounded.
+
+/// Slack for positions computed a hair below a whole block, e.g. 9.9999999 for 10
+const EPSILON: f64 = 1e-9;
+
+/// How fractional positions are snapped to blocks
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapMode {
+    /// The block the position lies in
+    #[default]
+    Floor,
+    /// The nearest whole coordinate, shifting features half a block towards the northwest
+    Nearest,
+}
+
+impl SnapMode {
+    /// Snaps a fractional block coordinate to a block.
+    #[inline]
+    pub fn snap(self, value: f64) -> i32 {
+        match self {
+            SnapMode::Floor => (value + EPSILON).floor() as i32,
+            SnapMode::Nearest => (value + 0.5 + EPSILON).floor() as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snaps_consistently_on_both_sides_of_zero() {
+        assert_eq!(SnapMode::Floor.snap(2.7), 2);
+        assert_eq!(SnapMode::Floor.snap(9.999_999_999_9), 10);
+        // Truncation would put -0.5 and 0.5 on the same block
+        assert_eq!(SnapMode::Floor.snap(-0.5), -1);
+        assert_eq!(SnapMode::Nearest.snap(2.5), 3);
+        assert_eq!(SnapMode::Nearest.snap(-2.5), -2);
+        assert_eq!(SnapMode::Nearest.snap(-2.6), -3);
+    }
+}
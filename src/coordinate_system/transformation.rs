@@ -1,5 +1,20 @@
 use super::cartesian::{XZBBox, XZPoint};
 use super::geographic::{LLBBox, LLPoint};
+use super::snap::SnapMode;
+
+/// Relative difference in east-west extent between the north and south edges beyond which
+/// the linear lat/lng projection visibly stretches the poleward part of the world
+const MAX_EDGE_STRETCH: f64 = 0.05;
+
+/// How longitude maps to X. Latitude always maps linearly to Z.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Longitude maps linearly to X, so east-west distances are true at the mean latitude only
+    Linear,
+    /// Sinusoidal about the bbox's central meridian: every row of blocks spans its true
+    /// east-west distance, and X is as wide as the bbox edge nearest the equator
+    Sinusoidal { centre_lng: f64, widest_lat: f64 },
+}
 
 /// Transform geographic space (within llbbox) to a local tangential cartesian space (within xzbbox)
 pub struct CoordTransformer {
@@ -9,6 +24,8 @@ pub struct CoordTransformer {
     scale_factor_z: f64,
     min_lat: f64,
     min_lng: f64,
+    projection: Projection,
+    snap: SnapMode,
 }
 
 impl CoordTransformer {
@@ -20,6 +37,14 @@ impl CoordTransformer {
         self.scale_factor_z
     }
 
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.projection == Projection::Linear
+    }
+
     pub fn llbbox_to_xzbbox(
         llbbox: &LLBBox,
         scale: f64,
@@ -30,7 +55,26 @@ impl CoordTransformer {
             return Err(format!("{}: scale <= 0.0", &err_header));
         }
 
-        let (scale_factor_z, scale_factor_x) = geo_distance(llbbox.min(), llbbox.max());
+        let (scale_factor_z, mut scale_factor_x) = geo_distance(llbbox.min(), llbbox.max());
+        // Tall boxes far from the equator would be stretched towards the pole by the
+        // linear grid, so they are projected sinusoidally
+        let projection = if edge_stretch(llbbox) > MAX_EDGE_STRETCH {
+            let (min, max) = (llbbox.min(), llbbox.max());
+            let widest_lat = if min.lat() > 0.0 {
+                min.lat()
+            } else if max.lat() < 0.0 {
+                max.lat()
+            } else {
+                0.0
+            };
+            scale_factor_x = lon_distance(widest_lat, min.lng(), max.lng());
+            Projection::Sinusoidal {
+                centre_lng: (min.lng() + max.lng()) / 2.0,
+                widest_lat,
+            }
+        } else {
+            Projection::Linear
+        };
         let scale_factor_z: f64 = scale_factor_z.floor() * scale;
         let scale_factor_x: f64 = scale_factor_x.floor() * scale;
 
@@ -45,22 +89,79 @@ impl CoordTransformer {
                 scale_factor_z,
                 min_lat: llbbox.min().lat(),
                 min_lng: llbbox.min().lng(),
+                projection,
+                snap: SnapMode::default(),
             },
             xzbbox,
         ))
     }
 
+    /// The transformer, snapping the points it transforms by `snap` (`--snap`)
+    pub fn with_snap(self, snap: SnapMode) -> Self {
+        Self { snap, ..self }
+    }
+
     pub fn transform_point(&self, llpoint: LLPoint) -> XZPoint {
+        let (x, z) = self.project(llpoint);
+        XZPoint::new(self.snap.snap(x), self.snap.snap(z))
+    }
+
+    /// Fractional Minecraft XZ position of a point, also for points outside the bbox
+    pub fn project(&self, llpoint: LLPoint) -> (f64, f64) {
         // Calculate the relative position within the bounding box
-        let rel_x: f64 = (llpoint.lng() - self.min_lng) / self.len_lng;
+        let rel_x: f64 = match self.projection {
+            Projection::Linear => (llpoint.lng() - self.min_lng) / self.len_lng,
+            Projection::Sinusoidal {
+                centre_lng,
+                widest_lat,
+            } => {
+                let narrowing = cos_deg(llpoint.lat()) / cos_deg(widest_lat);
+                0.5 + (llpoint.lng() - centre_lng) / self.len_lng * narrowing
+            }
+        };
         let rel_z: f64 = 1.0 - (llpoint.lat() - self.min_lat) / self.len_lat;
 
-        // Apply scaling factors for each dimension and convert to Minecraft coordinates
-        let x: i32 = (rel_x * self.scale_factor_x) as i32;
-        let z: i32 = (rel_z * self.scale_factor_z) as i32;
+        // Apply scaling factors for each dimension
+        (rel_x * self.scale_factor_x, rel_z * self.scale_factor_z)
+    }
+
+    /// Inverse of `transform_point`: maps a (fractional) Minecraft XZ position back to
+    /// geographic space, returned as (lat, lng). Use `x + 0.5` / `z + 0.5` for block centers.
+    pub fn inverse_transform(&self, x: f64, z: f64) -> (f64, f64) {
+        let rel_x: f64 = x / self.scale_factor_x;
+        let rel_z: f64 = z / self.scale_factor_z;
+
+        let lat: f64 = self.min_lat + (1.0 - rel_z) * self.len_lat;
+        let lng: f64 = match self.projection {
+            Projection::Linear => self.min_lng + rel_x * self.len_lng,
+            Projection::Sinusoidal {
+                centre_lng,
+                widest_lat,
+            } => {
+                let narrowing = cos_deg(lat) / cos_deg(widest_lat);
+                centre_lng + (rel_x - 0.5) * self.len_lng / narrowing
+            }
+        };
+
+        (lat, lng)
+    }
+}
+
+fn cos_deg(degrees: f64) -> f64 {
+    degrees.to_radians().cos()
+}
 
-        XZPoint::new(x, z)
+/// How much longer the bbox's edge nearest the equator is than its edge nearest the pole,
+/// relative to the former. The linear projection gives both edges the same number of blocks.
+pub fn edge_stretch(bbox: &LLBBox) -> f64 {
+    let (min, max) = (bbox.min(), bbox.max());
+    let width_at = |lat: f64| lon_distance(lat, min.lng(), max.lng());
+    let (south, north) = (width_at(min.lat()), width_at(max.lat()));
+    let (wide, narrow) = (south.max(north), south.min(north));
+    if wide <= 0.0 {
+        return 0.0;
     }
+    (wide - narrow) / wide
 }
 
 // (lat meters, lon meters)
@@ -112,9 +213,11 @@ pub fn lat_lon_to_minecraft_coords(
     let rel_x: f64 = (lon - bbox.min().lng()) / (bbox.max().lng() - bbox.min().lng());
     let rel_z: f64 = 1.0 - (lat - bbox.min().lat()) / (bbox.max().lat() - bbox.min().lat());
 
-    // Apply scaling factors for each dimension and convert to Minecraft coordinates
-    let x: i32 = (rel_x * scale_factor_x) as i32;
-    let z: i32 = (rel_z * scale_factor_z) as i32;
+    // Apply scaling factors for each dimension and convert to Minecraft coordinates.
+    // Snapped rather than truncated, so points west or north of the bbox land on blocks
+    // like the points inside it
+    let x: i32 = SnapMode::Floor.snap(rel_x * scale_factor_x);
+    let z: i32 = SnapMode::Floor.snap(rel_z * scale_factor_z);
 
     (x, z)
 }
@@ -172,6 +275,64 @@ mod test {
         test_llxztransform_one_scale_one_factor(0.1, 0.2, 0.7);
     }
 
+    // this ensures that inverse_transform maps block centers back into the same block
+    #[test]
+    pub fn test_inverse_transform_roundtrip() {
+        let llbbox = get_llbbox_arnis();
+        let (transformer, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&llbbox, 1.0).unwrap();
+
+        for (x, z) in [(0, 0), (17, 42), (xzbbox.max_x() - 1, xzbbox.max_z() - 1)] {
+            let (lat, lng) = transformer.inverse_transform(x as f64 + 0.5, z as f64 + 0.5);
+            let xzpoint = transformer.transform_point(LLPoint::new(lat, lng).unwrap());
+            assert_eq!(xzpoint, XZPoint::new(x, z));
+        }
+    }
+
+    // this ensures that the snap mode travels with the transformer
+    #[test]
+    pub fn test_transform_point_snaps_by_mode() {
+        let llbbox = get_llbbox_arnis();
+        let (floor, _) = CoordTransformer::llbbox_to_xzbbox(&llbbox, 1.0).unwrap();
+        let (lat, lng) = floor.inverse_transform(17.7, 42.2);
+        let llpoint = LLPoint::new(lat, lng).unwrap();
+        assert_eq!(floor.transform_point(llpoint), XZPoint::new(17, 42));
+
+        let nearest = floor.with_snap(SnapMode::Nearest);
+        assert_eq!(nearest.transform_point(llpoint), XZPoint::new(18, 42));
+    }
+
+    // this ensures that tall arctic boxes keep their shape and still round-trip
+    #[test]
+    pub fn test_arctic_boxes_are_projected_sinusoidally() {
+        let copenhagen = LLBBox::new(55.6, 12.5, 55.7, 12.6).unwrap();
+        let (transformer, _) = CoordTransformer::llbbox_to_xzbbox(&copenhagen, 1.0).unwrap();
+        assert!(transformer.is_linear());
+
+        let nuuk_fjord = LLBBox::new(64.0, -52.0, 66.0, -50.0).unwrap();
+        let (transformer, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&nuuk_fjord, 1.0).unwrap();
+        assert!(!transformer.is_linear());
+
+        // The south edge spans the whole world, the north edge only its true width
+        let width_at = |lat: f64| {
+            let west = transformer.project(LLPoint::new(lat, -52.0).unwrap()).0;
+            let east = transformer.project(LLPoint::new(lat, -50.0).unwrap()).0;
+            east - west
+        };
+        assert!((width_at(64.0) - transformer.scale_factor_x()).abs() < 1e-6);
+        let true_ratio = 66f64.to_radians().cos() / 64f64.to_radians().cos();
+        assert!((width_at(66.0) / width_at(64.0) - true_ratio).abs() < 1e-9);
+
+        for (x, z) in [
+            (0, 0),
+            (4000, 9000),
+            (xzbbox.max_x() - 1, xzbbox.max_z() - 1),
+        ] {
+            let (lat, lng) = transformer.inverse_transform(x as f64 + 0.5, z as f64 + 0.5);
+            let xzpoint = transformer.transform_point(LLPoint::new(lat, lng).unwrap());
+            assert_eq!(xzpoint, XZPoint::new(x, z));
+        }
+    }
+
     // this ensures that invalid inputs can be handled correctly
     #[test]
     pub fn test_invalid_construct() {
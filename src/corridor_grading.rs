@@ -0,0 +1,213 @@
+//! Cut-and-fill for main road and railway corridors on terrain.
+//!
+//! Motorways, main roads and railways are not draped over every bump of the DHM terrain.
+//! Their centreline profile is smoothed into an even grade and the corridor is reshaped to
+//! it before anything is placed: where the grade lies above the terrain the corridor is
+//! filled and flanked by grassy embankments, where it cuts into the terrain shallow cuts are
+//! sloped back and deep cuts get a stone retaining wall along the corridor edge.
+
+use crate::bresenham::bresenham_line;
+use crate::coordinate_system::cartesian::XZPoint;
+use crate::element_processing::highways::{highway_mask_radius, is_ground_level_highway};
+use crate::ground::Ground;
+use crate::osm_parser::{ProcessedElement, ProcessedWay};
+use std::collections::HashMap;
+
+/// Centreline samples averaged on each side when smoothing the grade
+const GRADE_WINDOW: usize = 12;
+/// Half width of a graded railway corridor in blocks
+const RAILWAY_HALF_WIDTH: i32 = 2;
+/// Embankments and shallow cuts slope one block per this many blocks
+const EMBANKMENT_RUN: i32 = 2;
+/// How far embankments and cut slopes reach beyond the corridor
+const MAX_SIDE_WIDTH: i32 = 8;
+/// Cuts at least this deep get a retaining wall instead of a slope
+const WALL_MIN_HEIGHT: i32 = 3;
+
+/// A column of retaining wall: blocks from `bottom` to `top` (absolute Y, inclusive)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetainingWall {
+    pub x: i32,
+    pub z: i32,
+    pub bottom: i32,
+    pub top: i32,
+}
+
+/// Half width of the corridor to grade for a way, or `None` for ways that follow the terrain
+fn corridor_half_width(way: &ProcessedWay, scale: f64, mapped_widths: bool) -> Option<i32> {
+    if !is_ground_level_highway(&way.tags) {
+        return None;
+    }
+    if let Some(highway) = way.tags.get("highway") {
+        return match highway.as_str() {
+            "motorway" | "motorway_link" | "trunk" | "trunk_link" | "primary" | "secondary" => {
+                highway_mask_radius(&way.tags, scale, mapped_widths)
+            }
+            _ => None,
+        };
+    }
+    match way.tags.get("railway").map(String::as_str) {
+        Some("rail" | "light_rail") => Some(RAILWAY_HALF_WIDTH),
+        _ => None,
+    }
+}
+
+/// Smooths a terrain profile into an even grade with a moving average
+fn smooth_profile(profile: &[i32], window: usize) -> Vec<i32> {
+    (0..profile.len())
+        .map(|i| {
+            let samples = &profile[i.saturating_sub(window)..(i + window + 1).min(profile.len())];
+            let sum: i64 = samples.iter().map(|&y| y as i64).sum();
+            (sum as f64 / samples.len() as f64).round() as i32
+        })
+        .collect()
+}
+
+/// Reshapes the ground along main road and railway corridors and returns the retaining walls
+/// to build along deep cuts. `origin` is the world's minimum corner, where the ground grid
+/// starts.
+pub fn grade_corridors(
+    ground: &mut Ground,
+    elements: &[ProcessedElement],
+    scale: f64,
+    mapped_widths: bool,
+    origin: XZPoint,
+) -> Vec<RetainingWall> {
+    let mut walls = Vec::new();
+    if !ground.elevation_enabled {
+        return walls;
+    }
+
+    for element in elements {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        let Some(half_width) = corridor_half_width(way, scale, mapped_widths) else {
+            continue;
+        };
+        grade_way(ground, way, half_width, origin, &mut walls);
+    }
+    walls
+}
+
+fn grade_way(
+    ground: &mut Ground,
+    way: &ProcessedWay,
+    half_width: i32,
+    origin: XZPoint,
+    walls: &mut Vec<RetainingWall>,
+) {
+    let mut centreline: Vec<(i32, i32)> = way
+        .nodes
+        .windows(2)
+        .flat_map(|pair| bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z))
+        .map(|(x, _, z)| (x, z))
+        .collect();
+    centreline.dedup();
+    if centreline.len() < 2 {
+        return;
+    }
+
+    let grid_point = |(x, z): (i32, i32)| XZPoint::new(x - origin.x, z - origin.z);
+    let level = |ground: &Ground, cell: (i32, i32)| ground.level(grid_point(cell));
+    let profile: Vec<i32> = centreline.iter().map(|&p| level(ground, p)).collect();
+    let grade = smooth_profile(&profile, GRADE_WINDOW);
+
+    // Graded level of every corridor cell, and the nearest grade of every side cell
+    let mut corridor: HashMap<(i32, i32), i32> = HashMap::new();
+    let mut sides: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+    let reach = half_width + MAX_SIDE_WIDTH;
+    for (&(cx, cz), &road_y) in centreline.iter().zip(&grade) {
+        for dx in -reach..=reach {
+            for dz in -reach..=reach {
+                let cell = (cx + dx, cz + dz);
+                let distance = dx.abs().max(dz.abs()) - half_width;
+                if distance <= 0 {
+                    corridor.entry(cell).or_insert(road_y);
+                } else if sides.get(&cell).is_none_or(|&(d, _)| distance < d) {
+                    sides.insert(cell, (distance, road_y));
+                }
+            }
+        }
+    }
+
+    let mut graded: Vec<((i32, i32), i32)> = Vec::new();
+    for (cell, (distance, road_y)) in sides {
+        if corridor.contains_key(&cell) {
+            continue;
+        }
+        let terrain_y = level(ground, cell);
+        let slope = (distance - 1) / EMBANKMENT_RUN;
+        if terrain_y < road_y {
+            // Fill: embankment falling away from the corridor
+            if road_y - slope > terrain_y {
+                graded.push((cell, road_y - slope));
+            }
+        } else if terrain_y - road_y >= WALL_MIN_HEIGHT {
+            if distance == 1 {
+                walls.push(RetainingWall {
+                    x: cell.0,
+                    z: cell.1,
+                    bottom: road_y + 1,
+                    top: terrain_y,
+                });
+            }
+        } else if road_y + slope < terrain_y {
+            // Shallow cut: slope back up to the terrain
+            graded.push((cell, road_y + slope));
+        }
+    }
+    graded.extend(corridor);
+
+    for (cell, y) in graded {
+        ground.set_level(grid_point(cell), y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_parser::ProcessedNode;
+
+    #[test]
+    fn smoothing_evens_out_bumps_but_keeps_the_trend() {
+        let bumpy = [10, 10, 14, 10, 10, 6, 10, 10];
+        assert_eq!(smooth_profile(&bumpy, 2), vec![11, 11, 11, 10, 10, 9, 9, 9]);
+
+        let climb: Vec<i32> = (0..10).collect();
+        assert_eq!(smooth_profile(&climb, 3)[5], 5);
+    }
+
+    #[test]
+    fn corridors_are_graded_in_grid_coordinates() {
+        // Flat terrain at Y 10 with a bump of 5 blocks across the middle of the grid
+        let heights = (0..40)
+            .map(|_| {
+                (0..40)
+                    .map(|x| if (18..=22).contains(&x) { 15 } else { 10 })
+                    .collect()
+            })
+            .collect();
+        let mut ground = Ground::from_heights(heights);
+
+        // The grid starts at world (100, 100), and the way crosses the bump along grid row 20
+        let node = |id, x| ProcessedNode {
+            id,
+            tags: HashMap::new(),
+            x,
+            z: 120,
+            exact: None,
+        };
+        let way = ProcessedWay {
+            id: 1,
+            nodes: vec![node(1, 102), node(2, 138)],
+            tags: HashMap::new(),
+        };
+        let mut walls = Vec::new();
+        grade_way(&mut ground, &way, 2, XZPoint::new(100, 100), &mut walls);
+
+        assert!(ground.level(XZPoint::new(20, 20)) < 15);
+        assert_eq!(ground.level(XZPoint::new(20, 35)), 15);
+        assert_eq!(ground.level(XZPoint::new(5, 35)), 10);
+    }
+}
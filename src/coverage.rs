@@ -0,0 +1,169 @@
+//! Coverage map of the terrain, orthophoto and BBR data (`--coverage-map`).
+//!
+//! A wrong Dataforsyningen token or a bbox reaching outside Denmark does not stop a run:
+//! the terrain falls back to the global tiles and the ground stays grass, and the mistake
+//! only shows once the world is built. Once the terrain and orthophoto have arrived, and
+//! before any block is placed, the coverage map shows which parts of the area each source
+//! has data for: green where all requested sources do, amber where some do and red where
+//! none do. With `--interiors`, the BBR matches are drawn over the building footprints, and
+//! their share is that of the buildings' area given a BBR usage. The share each source
+//! covers is printed alongside, so a run with missing coverage can be stopped early, or
+//! stops by itself below `--min-coverage`. The GUI draws the terrain and orthophoto map over
+//! the selected area before anything is generated.
+
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+/// Longest side of the coverage map; bigger areas are sampled more coarsely
+const MAX_MAP_PIXELS: usize = 2048;
+
+const COVERED: [f64; 3] = [60.0, 170.0, 80.0];
+const PARTIAL: [f64; 3] = [230.0, 170.0, 40.0];
+const MISSING: [f64; 3] = [200.0, 40.0, 40.0];
+
+/// A data source drawn on the map: its name and whether it has data for a grid cell
+pub struct CoverageSource<'a> {
+    pub name: &'static str,
+    pub covers: Box<dyn Fn(i32, i32) -> bool + 'a>,
+    /// The cells the source is meant to cover, for sources that only describe some
+    /// features, such as buildings; `None` for the whole area
+    pub within: Option<Box<dyn Fn(i32, i32) -> bool + 'a>>,
+}
+
+impl CoverageSource<'_> {
+    fn applies(&self, x: i32, z: i32) -> bool {
+        self.within.as_ref().is_none_or(|within| within(x, z))
+    }
+}
+
+/// Cells of the map a source covers
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceCoverage {
+    pub name: &'static str,
+    pub covered: usize,
+    pub cells: usize,
+}
+
+impl SourceCoverage {
+    pub fn percent(&self) -> f64 {
+        if self.cells == 0 {
+            return 0.0;
+        }
+        self.covered as f64 * 100.0 / self.cells as f64
+    }
+}
+
+/// Color for a cell covered by `fraction` of the sources, from red through amber to green
+fn heat_color(fraction: f64) -> Rgb<u8> {
+    let (from, to, t) = if fraction < 0.5 {
+        (MISSING, PARTIAL, fraction * 2.0)
+    } else {
+        (PARTIAL, COVERED, fraction * 2.0 - 1.0)
+    };
+    let mix = |i: usize| (from[i] + (to[i] - from[i]) * t).round() as u8;
+    Rgb([mix(0), mix(1), mix(2)])
+}
+
+/// Draws the coverage of a grid of `grid` cells, counted from the northwest corner, with
+/// one pixel per sampled cell. Each cell is colored by the share of the sources meant to
+/// cover it that do; a cell no source is meant to cover counts as covered.
+pub fn coverage_map(
+    grid: (usize, usize),
+    sources: &[CoverageSource],
+) -> (RgbImage, Vec<SourceCoverage>) {
+    let step = grid.0.max(grid.1).div_ceil(MAX_MAP_PIXELS).max(1);
+    let (width, height) = (grid.0.div_ceil(step), grid.1.div_ceil(step));
+    let mut image = RgbImage::new(width as u32, height as u32);
+    let mut coverage: Vec<SourceCoverage> = sources
+        .iter()
+        .map(|source| SourceCoverage {
+            name: source.name,
+            covered: 0,
+            cells: 0,
+        })
+        .collect();
+
+    for (px, pz, pixel) in image.enumerate_pixels_mut() {
+        let (x, z) = ((px as usize * step) as i32, (pz as usize * step) as i32);
+        let (mut covered, mut applying) = (0, 0);
+        for (source, count) in sources.iter().zip(&mut coverage) {
+            if !source.applies(x, z) {
+                continue;
+            }
+            applying += 1;
+            count.cells += 1;
+            if (source.covers)(x, z) {
+                count.covered += 1;
+                covered += 1;
+            }
+        }
+        *pixel = if applying == 0 {
+            heat_color(1.0)
+        } else {
+            heat_color(covered as f64 / applying as f64)
+        };
+    }
+    (image, coverage)
+}
+
+/// Writes the coverage map to `path` and returns what each source covers.
+pub fn write_coverage_map(
+    path: &Path,
+    grid: (usize, usize),
+    sources: &[CoverageSource],
+) -> Result<Vec<SourceCoverage>, String> {
+    let (image, coverage) = coverage_map(grid, sources);
+    image
+        .save(path)
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_cells_by_the_share_of_sources_covering_them() {
+        let sources = [
+            CoverageSource {
+                name: "DHM",
+                covers: Box::new(|x, _| x < 5),
+                within: None,
+            },
+            CoverageSource {
+                name: "Orthophoto",
+                covers: Box::new(|_, _| true),
+                within: None,
+            },
+            // Two buildings in the last two rows, the left one matched
+            CoverageSource {
+                name: "BBR",
+                covers: Box::new(|x, _| x < 2),
+                within: Some(Box::new(|x, z| z >= 2 && !(4..8).contains(&x))),
+            },
+        ];
+        let (image, coverage) = coverage_map((10, 4), &sources);
+        assert_eq!((image.width(), image.height()), (10, 4));
+        assert_eq!(coverage[0].percent(), 50.0);
+        assert_eq!(coverage[1].percent(), 100.0);
+        assert_eq!((coverage[2].covered, coverage[2].cells), (4, 12));
+        assert_eq!(*image.get_pixel(0, 0), heat_color(1.0));
+        assert_eq!(*image.get_pixel(0, 3), heat_color(1.0));
+        assert_eq!(*image.get_pixel(9, 0), Rgb([230, 170, 40]));
+        assert_eq!(*image.get_pixel(9, 3), heat_color(1.0 / 3.0));
+    }
+
+    #[test]
+    fn samples_large_grids() {
+        let sources = [CoverageSource {
+            name: "DHM",
+            covers: Box::new(|_, z| z >= 3000),
+            within: None,
+        }];
+        let (image, coverage) = coverage_map((5000, 6000), &sources);
+        assert_eq!((image.width(), image.height()), (1667, 2000));
+        assert_eq!(coverage[0].percent(), 50.0);
+        assert_eq!(*image.get_pixel(0, 0), Rgb([200, 40, 40]));
+    }
+}
@@ -1,24 +1,55 @@
+use crate::area;
 use crate::args::Args;
-use crate::block_definitions::{BEDROCK, DIRT, GRASS_BLOCK, SMOOTH_STONE, STONE, WATER};
-use crate::coordinate_system::cartesian::XZBBox;
+use crate::biomes;
+use crate::block_definitions::{
+    Block, BEDROCK, COARSE_DIRT, DIRT, GRASS_BLOCK, GRAVEL, SMOOTH_STONE, STONE, STONE_BRICKS,
+    WATER,
+};
+use crate::camera_path;
+use crate::corridor_grading;
+use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
 use crate::coordinate_system::geographic::LLBBox;
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::coverage::{self, CoverageSource};
+use crate::deterministic_rng::coord_rng;
 use crate::element_processing::*;
+use crate::enrichment;
+use crate::exclusion;
+use crate::feature_index::FeatureIndex;
 use crate::floodfill_cache::{CoordinateBitmap, FloodFillCache};
+use crate::geology::{self, Geology, GroundCover};
 use crate::ground::Ground;
+use crate::height_estimation::{self, BlockMedians};
+use crate::info_book;
+use crate::landmarks::{self, LandmarkBuilds};
 use crate::map_renderer;
-use crate::osm_parser::{ProcessedElement, ProcessedMemberRole};
+use crate::ortho_alignment;
+use crate::osm_parser::{ProcessedElement, ProcessedMemberRole, ProcessedWay};
 use crate::progress::{emit_gui_progress_update, emit_map_preview_ready, emit_open_mcworld_file};
+use crate::topology_repair;
+use crate::tour;
 #[cfg(feature = "gui")]
 use crate::telemetry::{send_log, LogLevel};
 use crate::urban_ground;
 use crate::world_editor::{WorldEditor, WorldFormat};
 use colored::Colorize;
+use image::RgbImage;
 use indicatif::{ProgressBar, ProgressStyle};
+use rand::Rng;
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub const MIN_Y: i32 = -64;
+/// Offset from the orthophoto, in meters, beyond which `--ortho-check` warns
+const MAX_ORTHO_OFFSET_M: f64 = 5.0;
+/// Terrain slopes (degrees) from which natural ground turns to coarse dirt
+const MODERATE_SLOPE_DEG: f64 = 25.0;
+/// Terrain slopes (degrees) from which natural ground is exposed stone and gravel, e.g. cliffs
+const STEEP_SLOPE_DEG: f64 = 40.0;
+/// Salt for the stone/gravel pattern on steep slopes
+const CLIFF_GRAVEL_SALT: u64 = 0x5104_e000;
+
 fn build_building_buffer_mask(
     centroids: &[(i32, i32)],
     xzbbox: &XZBBox,
@@ -45,11 +76,36 @@ pub struct GenerationOptions {
     pub level_name: Option<String>,
     pub spawn_point: Option<(i32, i32)>,
     pub update_spawn_after_generation: bool,
+    /// Where to write the coverage map before any block is placed (`--coverage-map`)
+    pub coverage_map: Option<PathBuf>,
+    /// Bounds of the whole world, of which a tiled run generates one tile at a time
+    pub world_xzbbox: XZBBox,
+    /// Median building levels of the whole world's landuse blocks in a tiled run. Without
+    /// them the medians are taken from the elements being generated.
+    pub block_medians: Option<BlockMedians>,
+}
+
+/// Surface and subsurface blocks for natural (non-urban) ground: flats keep grass,
+/// moderate slopes get coarse dirt and steep slopes expose stone with patches of gravel.
+fn natural_ground_blocks(slope: Option<f64>, x: i32, z: i32) -> (Block, Block) {
+    match slope {
+        Some(deg) if deg >= STEEP_SLOPE_DEG => {
+            let surface = if coord_rng(x, z, CLIFF_GRAVEL_SALT).random_bool(0.3) {
+                GRAVEL
+            } else {
+                STONE
+            };
+            (surface, STONE)
+        }
+        Some(deg) if deg >= MODERATE_SLOPE_DEG => (COARSE_DIRT, DIRT),
+        _ => (GRASS_BLOCK, DIRT),
+    }
 }
 
 /// Generate world with explicit format options (used by GUI for Bedrock support)
 pub fn generate_world_with_options(
-    elements: Vec<ProcessedElement>,
+    mut elements: Vec<ProcessedElement>,
+    land_cover_ways: Vec<ProcessedWay>,
     xzbbox: XZBBox,
     llbbox: LLBBox,
     ground: Ground,
@@ -59,6 +115,34 @@ pub fn generate_world_with_options(
     let output_path = options.path.clone();
     let world_format = options.format;
 
+    // Clip to the boundary of a named area (--area): drop what lies wholly outside it
+    let area_mask = area::take_boundary_mask(&mut elements, &xzbbox);
+    if let Some(mask) = &area_mask {
+        let before = elements.len();
+        elements.retain(|element| area::touches_area(element, mask));
+        println!(
+            "Area: {} blocks inside the boundary, {} elements outside it dropped",
+            mask.count(),
+            before - elements.len()
+        );
+    }
+
+    // Keep the exclusion zones free (--exclusion-zones): drop what lies wholly inside them,
+    // the editor keeps the rest from reaching into them
+    let exclusion_masks = exclusion::take_exclusion_masks(&mut elements, &xzbbox);
+    if let Some(masks) = &exclusion_masks {
+        let before = elements.len();
+        elements.retain(|element| !exclusion::wholly_excluded(element, &masks.features));
+        println!(
+            "Exclusion zones: {} blocks kept free, {} elements inside them dropped",
+            masks.features.count(),
+            before - elements.len()
+        );
+    }
+
+    // Churches whose tower is mapped as a building of its own get no tower of ours
+    churches::mark_mapped_towers(&mut elements);
+
     // Create editor with appropriate format
     let mut editor: WorldEditor = WorldEditor::new_with_format_and_name(
         options.path,
@@ -68,21 +152,93 @@ pub fn generate_world_with_options(
         options.level_name.clone(),
         options.spawn_point,
     );
+    editor.set_world_xzbbox(&options.world_xzbbox);
+    // Cut-and-fill main road and rail corridors before anything is placed on the terrain
+    let mut ground = ground;
+    let retaining_walls = corridor_grading::grade_corridors(
+        &mut ground,
+        &elements,
+        args.scale,
+        args.geodanmark_roads,
+        XZPoint::new(xzbbox.min_x(), xzbbox.min_z()),
+    );
     let ground = Arc::new(ground);
 
+    // Orthophoto ground classes for natural ground without mapped landuse (--ortho)
+    let ortho = fetch_ortho(args, &llbbox, &xzbbox);
+
+    // Coverage of the terrain, orthophoto and BBR matches, before any block is placed
+    // (--coverage-map, --min-coverage)
+    if options.coverage_map.is_some() || args.min_coverage.is_some() {
+        let bbr = args
+            .interiors
+            .then(|| enrichment::bbr::matched_footprints(&elements, &xzbbox));
+        check_coverage(
+            options.coverage_map.as_deref(),
+            &xzbbox,
+            &ground,
+            ortho.as_ref(),
+            bbr.as_ref(),
+            args,
+        )?;
+    }
+
     println!("{} Processing data...", "[4/7]".bold());
 
+    // Clean malformed area polygons before they are flood filled
+    let repairs = topology_repair::repair_polygons(&mut elements);
+    if !repairs.is_empty() {
+        println!(
+            "Topology repair: closed {} rings, untangled {} self-intersecting rings, merged duplicate nodes in {} ways",
+            repairs.closed.len(),
+            repairs.untangled.len(),
+            repairs.deduplicated.len()
+        );
+        if args.debug {
+            println!("  Closed: {:?}", repairs.closed);
+            println!("  Untangled: {:?}", repairs.untangled);
+            println!("  Duplicate nodes: {:?}", repairs.deduplicated);
+        }
+    }
+
+    // Buildings without a height take the median levels of the mapped ones in their block
+    let medians = options
+        .block_medians
+        .unwrap_or_else(|| BlockMedians::of(&elements, &xzbbox));
+    let estimated = height_estimation::estimate_building_levels(&mut elements, &medians, &xzbbox);
+    if estimated > 0 {
+        println!("Estimated the levels of {estimated} buildings from their neighbours");
+    }
+
     // Build highway connectivity map once before processing
     let highway_connectivity = highways::build_highway_connectivity_map(&elements);
+    let transit_stations = args
+        .minecart_transit
+        .then(|| railways::collect_transit_stations(&elements));
+    let navigable_channels = args
+        .navigable_waterways
+        .then(|| waterways::collect_navigable_channels(&elements, &xzbbox, args.scale));
 
     // Set ground reference in the editor to enable elevation-aware block placement
     editor.set_ground(Arc::clone(&ground));
 
+    // Datapacks are Java only
+    let tour_stops = (args.tour && world_format == WorldFormat::JavaAnvil)
+        .then(|| tour::select_landmarks(&elements, |x, z| editor.get_ground_level(x, z)));
+    let camera_lines = (args.camera_path && world_format == WorldFormat::JavaAnvil)
+        .then(|| camera_path::collect_lines(&elements, |x, z| editor.get_ground_level(x, z)));
+    let feature_index = (args.feature_index && world_format == WorldFormat::JavaAnvil)
+        .then(|| FeatureIndex::from_elements(&elements, &xzbbox));
+    let world_stats = (args.info_book && world_format == WorldFormat::JavaAnvil).then(|| {
+        info_book::collect_stats(&elements, &llbbox, &xzbbox, &ground, args.scale)
+    });
+
     println!("{} Processing terrain...", "[5/7]".bold());
     emit_gui_progress_update(25.0, "Processing terrain...");
 
     // Pre-compute all flood fills in parallel for better CPU utilization
-    let mut flood_fill_cache = FloodFillCache::precompute(&elements, args.timeout.as_ref());
+    let mut flood_fill_cache =
+        FloodFillCache::precompute(&elements, &xzbbox, args.timeout.as_ref());
 
     // Collect building footprints to prevent trees from spawning inside buildings
     // Uses a memory-efficient bitmap (~1 bit per coordinate) instead of a HashSet (~24 bytes per coordinate)
@@ -91,8 +247,21 @@ pub fn generate_world_with_options(
         flood_fill_cache.collect_dry_land_mask(&elements, &xzbbox);
     let explicit_water_mask: CoordinateBitmap =
         flood_fill_cache.collect_explicit_water_mask(&elements, &xzbbox);
-    let road_mask: CoordinateBitmap =
-        highways::collect_ground_highway_mask(&elements, &xzbbox, args.scale);
+    // Mapped allotment plots, which the allotment site's own grid of plots leaves alone
+    let allotment_plots: CoordinateBitmap =
+        flood_fill_cache.collect_allotment_plot_mask(&elements, &xzbbox);
+    let road_mask: CoordinateBitmap = highways::collect_ground_highway_mask(
+        &elements,
+        &xzbbox,
+        args.scale,
+        args.geodanmark_roads,
+    );
+    let ploughed_land_mask = args
+        .fillground
+        .then(|| flood_fill_cache.collect_ploughed_land_mask(&elements, &xzbbox));
+
+    // Biomes of the mapped areas, before the processing loop clears the flood fill cache
+    let biome_map = biomes::collect_biome_map(&elements, &flood_fill_cache, args.timeout.as_ref());
 
     // Collect building centroids before the processing loop clears the flood fill cache.
     let building_centroids = flood_fill_cache.collect_building_centroids(&elements);
@@ -160,8 +329,70 @@ pub fn generate_world_with_options(
         })
         .collect();
     let has_coastline_context = !coastline_ways.is_empty();
+    // Ferries are berthed after the sea is filled in
+    let ferry_routes: Vec<_> = elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Way(way) if way.tags.get("route").is_some_and(|v| v == "ferry") => {
+                Some(way.clone())
+            }
+            _ => None,
+        })
+        .collect();
+    // Marinas too, so only their land is paved and the boats lie on the sea
+    let marinas: Vec<_> = elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Way(way)
+                if way.tags.get("leisure").is_some_and(|v| v == "marina") =>
+            {
+                Some(way.clone())
+            }
+            _ => None,
+        })
+        .collect();
+    // And the buoys of harbour entrances and fairways (--navigable-waterways), which float
+    // on the sea as well
+    let lateral_marks: Vec<_> = elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Node(node)
+                if args.navigable_waterways && node.tags.contains_key("seamark:type") =>
+            {
+                Some(node.clone())
+            }
+            _ => None,
+        })
+        .collect();
+    // Streams watermills may stand on, to turn their wheels towards
+    let has_watermills = elements.iter().any(|element| {
+        element
+            .tags()
+            .get("man_made")
+            .is_some_and(|v| v == "watermill")
+    });
+    let mill_streams: Vec<_> = elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Way(way) if has_watermills && mills::is_mill_stream(way) => {
+                Some(way.clone())
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut infilled_areas: usize = 0;
+    let address_signs = args
+        .addresses
+        .then(|| enrichment::addresses::plan_signs(&elements));
+    let place_markers = args
+        .place_names
+        .then(|| enrichment::place_names::plan_markers(&elements));
+
+    let mut landmark_builds = args.landmarks.as_deref().map(LandmarkBuilds::new);
 
     // Process all elements
+    editor.set_excluded(exclusion_masks.as_ref().map(|masks| &masks.features));
     for element in elements.into_iter() {
         process_pb.inc(1);
         current_progress_prcs += progress_increment_prcs;
@@ -180,9 +411,27 @@ pub fn generate_world_with_options(
             process_pb.set_message("");
         }
 
+        // Registered landmarks are built from their prefab instead (--landmarks)
+        if let (Some(builds), Some(prefab)) = (
+            landmark_builds.as_mut(),
+            element.tags().get(landmarks::LANDMARK_TAG),
+        ) {
+            if builds.place(&mut editor, &element, prefab) {
+                continue;
+            }
+        }
+
+        if args.accessibility {
+            accessibility::generate_accessibility(&mut editor, &element, args, &flood_fill_cache);
+        }
+
         match &element {
             ProcessedElement::Way(way) => {
-                if way.tags.contains_key("building") || way.tags.contains_key("building:part") {
+                if man_made::is_building_structure(&way.tags) {
+                    man_made::generate_man_made(&mut editor, &element, args, &mill_streams);
+                } else if way.tags.contains_key("building")
+                    || way.tags.contains_key("building:part")
+                {
                     // Skip building outlines that are suppressed by building relations with parts.
                     // The individual building:part ways will render instead.
                     if !suppressed_building_outlines.contains(&way.id) {
@@ -202,14 +451,36 @@ pub fn generate_world_with_options(
                         args,
                         &highway_connectivity,
                         &flood_fill_cache,
+                        navigable_channels.as_ref(),
                     );
                 } else if way.tags.contains_key("landuse") {
+                    if args.urban_infill
+                        && infill::generate_perimeter_blocks(
+                            &mut editor,
+                            way,
+                            args,
+                            &flood_fill_cache,
+                            &building_footprints,
+                            &road_mask,
+                        )
+                    {
+                        infilled_areas += 1;
+                    }
                     landuse::generate_landuse(
                         &mut editor,
                         way,
                         args,
                         &flood_fill_cache,
                         &building_footprints,
+                        &allotment_plots,
+                    );
+                } else if allotments::is_mapped_plot(way) {
+                    allotments::generate_mapped_plot(
+                        &mut editor,
+                        way,
+                        args,
+                        &flood_fill_cache,
+                        &building_footprints,
                     );
                 } else if way
                     .tags
@@ -228,6 +499,8 @@ pub fn generate_world_with_options(
                     .unwrap_or(false)
                 {
                     // Coastlines are handled in a dedicated ocean pass after element processing.
+                } else if golf::is_golf_area(&way.tags) {
+                    golf::generate_golf(&mut editor, way, args, &flood_fill_cache);
                 } else if way.tags.contains_key("natural") {
                     natural::generate_natural(
                         &mut editor,
@@ -247,18 +520,28 @@ pub fn generate_world_with_options(
                         &building_footprints,
                     );
                 } else if way.tags.contains_key("barrier") {
-                    barriers::generate_barriers(&mut editor, &element);
+                    barriers::generate_barriers(&mut editor, &element, args);
                 } else if let Some(val) = way.tags.get("waterway") {
                     if val == "dock" {
                         // docks count as water areas
                         water_areas::generate_water_area_from_way(&mut editor, way, &xzbbox);
                     } else {
-                        waterways::generate_waterways(&mut editor, way);
+                        waterways::generate_waterways(
+                            &mut editor,
+                            way,
+                            args,
+                            navigable_channels.as_ref(),
+                        );
                     }
                 } else if way.tags.contains_key("bridge") {
                     //bridges::generate_bridges(&mut editor, way, ground_level); // TODO FIX
                 } else if way.tags.contains_key("railway") {
-                    railways::generate_railways(&mut editor, way);
+                    railways::generate_railways(
+                        &mut editor,
+                        way,
+                        args,
+                        transit_stations.as_deref(),
+                    );
                 } else if way.tags.contains_key("roller_coaster") {
                     railways::generate_roller_coaster(&mut editor, way);
                 } else if way.tags.contains_key("aeroway") || way.tags.contains_key("area:aeroway")
@@ -269,9 +552,11 @@ pub fn generate_world_with_options(
                 } else if way.tags.get("tomb") == Some(&"pyramid".to_string()) {
                     historic::generate_pyramid(&mut editor, way, args, &flood_fill_cache);
                 } else if way.tags.contains_key("man_made") {
-                    man_made::generate_man_made(&mut editor, &element, args);
+                    man_made::generate_man_made(&mut editor, &element, args, &mill_streams);
                 } else if way.tags.contains_key("power") {
-                    power::generate_power(&mut editor, &element);
+                    power::generate_power(&mut editor, &element, args, &flood_fill_cache);
+                } else if way.tags.get("place").is_some_and(|v| v == "islet") {
+                    habitat::generate_nesting_islet(&mut editor, way, args, &flood_fill_cache);
                 } else if way.tags.contains_key("place") {
                     landuse::generate_place(&mut editor, way, args, &flood_fill_cache);
                 }
@@ -302,26 +587,31 @@ pub fn generate_world_with_options(
                         args,
                         &highway_connectivity,
                         &flood_fill_cache,
+                        navigable_channels.as_ref(),
                     );
                 } else if node.tags.contains_key("tourism") {
                     tourisms::generate_tourisms(&mut editor, node);
                 } else if node.tags.contains_key("man_made") {
-                    man_made::generate_man_made_nodes(&mut editor, node);
+                    man_made::generate_man_made_nodes(&mut editor, node, args.scale, &mill_streams);
                 } else if node.tags.contains_key("power") {
-                    power::generate_power_nodes(&mut editor, node);
+                    power::generate_power_nodes(&mut editor, node, args.scale);
                 } else if node.tags.contains_key("historic") {
                     historic::generate_historic(&mut editor, node);
                 } else if node.tags.contains_key("emergency") {
                     emergency::generate_emergency(&mut editor, node);
                 } else if node.tags.contains_key("advertising") {
                     advertising::generate_advertising(&mut editor, node);
+                } else if node.tags.get("leisure").is_some_and(|v| v == "bird_hide") {
+                    habitat::generate_bird_hide(&mut editor, node);
                 }
             }
             ProcessedElement::Relation(rel) => {
                 let is_building_relation = rel.tags.contains_key("building")
                     || rel.tags.contains_key("building:part")
                     || rel.tags.get("type").map(|t| t.as_str()) == Some("building");
-                if is_building_relation {
+                if man_made::is_building_structure(&rel.tags) {
+                    man_made::generate_man_made(&mut editor, &element, args, &mill_streams);
+                } else if is_building_relation {
                     buildings::generate_building_from_relation(
                         &mut editor,
                         rel,
@@ -352,6 +642,7 @@ pub fn generate_world_with_options(
                         args,
                         &flood_fill_cache,
                         &building_footprints,
+                        &allotment_plots,
                     );
                 } else if rel.tags.get("leisure") == Some(&"park".to_string()) {
                     leisure::generate_leisure_from_relation(
@@ -362,11 +653,12 @@ pub fn generate_world_with_options(
                         &building_footprints,
                     );
                 } else if rel.tags.contains_key("man_made") {
-                    man_made::generate_man_made(&mut editor, &element, args);
+                    man_made::generate_man_made(&mut editor, &element, args, &mill_streams);
                 }
                 // Release flood fill cache entries for all ways in this relation
                 let way_ids: Vec<u64> = rel.members.iter().map(|m| m.way.id).collect();
                 flood_fill_cache.remove_relation_ways(&way_ids);
+                flood_fill_cache.remove_multipolygon(rel.id);
             }
         }
         // Element is dropped here, freeing its memory immediately
@@ -376,7 +668,10 @@ pub fn generate_world_with_options(
 
     // Render oceans after element processing so coastlines do not get treated as
     // generic natural polygons, while still preserving deliberately placed
-    // structures that already occupy the same surface blocks.
+    // structures that already occupy the same surface blocks. The sea is ground, so it
+    // also fills the terrain-only exclusion zones.
+    editor.set_excluded(exclusion_masks.as_ref().map(|masks| &masks.ground));
+    let mut sea_mask = CoordinateBitmap::new(&xzbbox);
     let used_external_land_polygons = if let Some(path) = args.land_polygons.as_deref() {
         if coastline_ways.is_empty() {
             if args.debug {
@@ -392,6 +687,7 @@ pub fn generate_world_with_options(
                 &llbbox,
                 &xzbbox,
                 args.scale,
+                &mut sea_mask,
             ) {
                 Ok(generated) => generated,
                 Err(err) => {
@@ -417,13 +713,80 @@ pub fn generate_world_with_options(
             &building_footprints,
             &explicit_water_mask,
             &urban_lookup,
+            &mut sea_mask,
+        );
+    }
+
+    editor.set_excluded(exclusion_masks.as_ref().map(|masks| &masks.features));
+
+    if !land_cover_ways.is_empty() {
+        let covered = enrichment::land_cover::generate_land_cover(
+            &mut editor,
+            &land_cover_ways,
+            ortho.as_ref(),
+            XZPoint::new(xzbbox.min_x(), xzbbox.min_z()),
+            args,
+            &flood_fill_cache,
+            &building_footprints,
+        );
+        println!("Land cover: covered {covered} unmapped blocks from CORINE");
+    }
+
+    if !ferry_routes.is_empty() {
+        let berthed = ferries::generate_ferries(&mut editor, &ferry_routes);
+        println!(
+            "Ferries: marked {} routes and berthed {berthed} ferries",
+            ferry_routes.len()
+        );
+    }
+
+    for marina in &marinas {
+        let area = flood_fill_cache.get_or_compute(marina, args.timeout.as_ref());
+        harbours::generate_marina(&mut editor, &area, marina.id);
+    }
+
+    for mark in &lateral_marks {
+        waterways::generate_lateral_mark(&mut editor, mark);
+    }
+
+    if infilled_areas > 0 {
+        println!(
+            "Urban infill: synthetic perimeter blocks in {infilled_areas} areas without mapped buildings"
+        );
+    }
+
+    if let Some(signs) = address_signs {
+        let placed = enrichment::addresses::place_signs(
+            &mut editor,
+            signs,
+            &road_mask,
+            &building_footprints,
         );
+        println!("Addresses: placed {placed} street-name and house-number signs");
+    }
+
+    if let Some(markers) = place_markers {
+        let placed = enrichment::place_names::place_markers(
+            &mut editor,
+            markers,
+            &road_mask,
+            &building_footprints,
+        );
+        println!("Place names: built {placed} place-name markers");
     }
 
     // Drop remaining caches
     drop(highway_connectivity);
     drop(flood_fill_cache);
 
+    for wall in &retaining_walls {
+        for y in wall.bottom..=wall.top {
+            editor.set_block_absolute(STONE_BRICKS, wall.x, y, wall.z, None, None);
+        }
+    }
+    drop(retaining_walls);
+    editor.set_excluded(exclusion_masks.as_ref().map(|masks| &masks.ground));
+
     // Generate ground layer
     let total_blocks: u64 = xzbbox.bounding_rect().total_blocks();
     let desired_updates: u64 = 1500;
@@ -450,6 +813,7 @@ pub fn generate_world_with_options(
     // Check if terrain elevation is enabled; when disabled, we can skip ground level lookups entirely
     let terrain_enabled = ground.elevation_enabled;
     let sea_level_y = ground.sea_level_y();
+    let subsurface = args.fillground.then(|| Geology::of(&llbbox));
     let mut debug_building_buffer_water_cells: u64 = 0;
     let mut debug_building_buffer_explicit_water_cells: u64 = 0;
     let mut debug_building_buffer_reclaimed_cells: u64 = 0;
@@ -473,6 +837,16 @@ pub fn generate_world_with_options(
 
             for x in chunk_min_x..=chunk_max_x {
                 for z in chunk_min_z..=chunk_max_z {
+                    // Position in the ground grid, which starts at the world's minimum corner
+                    let grid_point = XZPoint::new(x - xzbbox.min_x(), z - xzbbox.min_z());
+                    if area_mask.as_ref().is_some_and(|mask| !mask.contains(x, z))
+                        || exclusion_masks
+                            .as_ref()
+                            .is_some_and(|masks| masks.ground.contains(x, z))
+                    {
+                        continue;
+                    }
+
                     // Get ground level, when terrain is enabled, look it up once per block
                     // When disabled, use constant ground_level (no function call overhead)
                     let ground_y = if terrain_enabled {
@@ -524,7 +898,23 @@ pub fn generate_world_with_options(
                             debug_building_buffer_remaining_cells += 1;
                         }
                     }
-                    let surface_block = if is_urban { SMOOTH_STONE } else { GRASS_BLOCK };
+                    let (surface_block, subsurface_block) = if is_urban {
+                        (SMOOTH_STONE, DIRT)
+                    } else {
+                        let natural = if terrain_enabled {
+                            natural_ground_blocks(editor.get_ground_slope(x, z), x, z)
+                        } else {
+                            (GRASS_BLOCK, DIRT)
+                        };
+                        // The orthophoto only recolors grass; slopes keep their dirt and stone
+                        match ortho
+                            .as_ref()
+                            .and_then(|ortho| ortho.class_at(grid_point.x, grid_point.z))
+                        {
+                            Some(class) if natural.0 == GRASS_BLOCK => class.blocks(),
+                            _ => natural,
+                        }
+                    };
 
                     if reclaim_dry_land {
                         editor.set_block_absolute(
@@ -535,20 +925,59 @@ pub fn generate_world_with_options(
                             Some(&[WATER]),
                             None,
                         );
-                        editor.set_block_absolute(DIRT, x, ground_y - 1, z, Some(&[WATER]), None);
-                        editor.set_block_absolute(DIRT, x, ground_y - 2, z, Some(&[WATER]), None);
+                        editor.set_block_absolute(
+                            subsurface_block,
+                            x,
+                            ground_y - 1,
+                            z,
+                            Some(&[WATER]),
+                            None,
+                        );
+                        editor.set_block_absolute(
+                            subsurface_block,
+                            x,
+                            ground_y - 2,
+                            z,
+                            Some(&[WATER]),
+                            None,
+                        );
+                    }
+
+                    // Coastal cliffs: expose layered chalk down to the foot of the face
+                    if terrain_enabled && !is_urban && editor.is_coastal_cliff(x, z) {
+                        let foot = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                            .iter()
+                            .map(|&(dx, dz)| editor.get_ground_level(x + dx, z + dz))
+                            .min()
+                            .unwrap_or(ground_y);
+                        let steep = editor
+                            .get_ground_slope(x, z)
+                            .is_some_and(|deg| deg >= STEEP_SLOPE_DEG);
+                        let face_top = if steep { ground_y } else { ground_y - 1 };
+                        for y in foot.min(ground_y - 2)..=face_top {
+                            editor.set_block_if_absent_absolute(
+                                geology::chalk_stratum(x, y, z),
+                                x,
+                                y,
+                                z,
+                            );
+                        }
                     }
 
                     // Add default dirt and grass layer if there isn't a stone layer already
                     if !editor.check_for_block_absolute(x, ground_y, z, Some(&[STONE]), None) {
                         editor.set_block_if_absent_absolute(surface_block, x, ground_y, z);
-                        editor.set_block_if_absent_absolute(DIRT, x, ground_y - 1, z);
-                        editor.set_block_if_absent_absolute(DIRT, x, ground_y - 2, z);
+                        editor.set_block_if_absent_absolute(subsurface_block, x, ground_y - 1, z);
+                        editor.set_block_if_absent_absolute(subsurface_block, x, ground_y - 2, z);
                     }
 
                     // Fill water for areas at or below sea level (DHM terrain)
                     if let Some(sly) = sea_level_y {
-                        if ground_y < sly && !reclaim_dry_land && has_surface_water {
+                        let carved_sea = !has_surface_water && editor.is_carved_sea(x, z);
+                        if ground_y < sly
+                            && !reclaim_dry_land
+                            && (has_surface_water || carved_sea)
+                        {
                             // Fill water from ground surface up to sea level
                             for wy in (ground_y + 1)..=sly {
                                 editor.set_block_if_absent_absolute(WATER, x, wy, z);
@@ -559,22 +988,25 @@ pub fn generate_world_with_options(
                                 x,
                                 ground_y,
                                 z,
-                                None,
+                                carved_sea.then_some(&[surface_block][..]),
                                 None,
                             );
                         }
                     }
 
-                    // Fill underground with stone
-                    if args.fillground {
-                        editor.fill_column_absolute(
-                            STONE,
-                            x,
-                            z,
-                            MIN_Y + 1,
-                            ground_y - 3,
-                            true, // skip_existing: don't overwrite blocks placed by element processing
-                        );
+                    // Fill underground with topsoil, till and the bedrock of the region
+                    if let Some(region) = subsurface {
+                        let cover = if is_urban {
+                            GroundCover::Paved
+                        } else if ploughed_land_mask
+                            .as_ref()
+                            .is_some_and(|mask| mask.contains(x, z))
+                        {
+                            GroundCover::Ploughed
+                        } else {
+                            GroundCover::Natural
+                        };
+                        geology::fill_column(&mut editor, region, cover, x, z, ground_y);
                     }
                     // Generate a bedrock level at MIN_Y
                     editor.set_block_absolute(BEDROCK, x, MIN_Y, z, None, Some(&[BEDROCK]));
@@ -620,9 +1052,56 @@ pub fn generate_world_with_options(
         );
     }
 
+    // Biomes follow the finished surface, so grass, foliage and water are tinted to match
+    let wintry = crate::data_sources::Realm::of(&llbbox) == crate::data_sources::Realm::Greenland;
+    biomes::assign_biomes(&mut editor, &biome_map, &sea_mask, &xzbbox, wintry);
+
+    // Ocean or wall around the outer edge of the whole area
+    editor.set_excluded(None);
+    if let Some(style) = args.border.filter(|style| style.is_built()) {
+        editor.build_border(style, &options.world_xzbbox, sea_level_y);
+    }
+
+    // Info book on a lectern next to where the player starts
+    if let Some(stats) = &world_stats {
+        let spawn = options
+            .spawn_point
+            .unwrap_or((xzbbox.min_x() + 2, xzbbox.min_z() + 2));
+        info_book::write_info_book(&mut editor, spawn, stats, args);
+    }
+
     // Save world
     editor.save();
 
+    if let Some(stops) = tour_stops {
+        match tour::write_tour_datapack(&output_path, stops) {
+            Ok(Some(pack_dir)) => println!(
+                "Tour datapack written to {} (run /function arnis_tour:start)",
+                pack_dir.display()
+            ),
+            Ok(None) => println!("No landmarks found for the tour datapack"),
+            Err(e) => eprintln!("{} {}", "Warning:".yellow().bold(), e),
+        }
+    }
+
+    if let Some(lines) = camera_lines {
+        match camera_path::write_camera_datapack(&output_path, lines) {
+            Ok(Some(pack_dir)) => println!(
+                "Camera path datapack written to {} (run /function arnis_camera:start)",
+                pack_dir.display()
+            ),
+            Ok(None) => println!("No main roads or coastline found for the camera path"),
+            Err(e) => eprintln!("{} {}", "Warning:".yellow().bold(), e),
+        }
+    }
+
+    if let Some(index) = feature_index {
+        match index.write(&output_path) {
+            Ok(path) => println!("Feature index written to {}", path.display()),
+            Err(e) => eprintln!("{} {}", "Warning:".yellow().bold(), e),
+        }
+    }
+
     emit_gui_progress_update(99.0, "Finalizing world...");
 
     // Update player spawn Y coordinate based on terrain height after generation
@@ -665,6 +1144,269 @@ pub fn generate_world_with_options(
     Ok(output_path)
 }
 
+/// Orthophoto ground classes of the area (`--ortho`), or `None` with a warning when the
+/// orthophoto cannot be had
+fn fetch_ortho(
+    args: &Args,
+    llbbox: &LLBBox,
+    xzbbox: &XZBBox,
+) -> Option<enrichment::ortho::OrthoGround> {
+    match (args.ortho, args.dhm_token.as_deref()) {
+        (false, _) => None,
+        (true, None) => {
+            eprintln!(
+                "{} --ortho needs a Dataforsyningen token (--dhm-token); ground stays grass.",
+                "Warning:".yellow().bold()
+            );
+            None
+        }
+        (true, Some(_)) if !crate::data_sources::Realm::of(llbbox).has_danish_data() => {
+            eprintln!(
+                "{} The GeoDanmark orthophoto only covers Denmark; ground stays grass.",
+                "Warning:".yellow().bold()
+            );
+            None
+        }
+        (true, Some(token)) => {
+            match enrichment::ortho::fetch_ortho_ground(llbbox, token, coverage_grid(xzbbox)) {
+                Ok(ortho) => Some(ortho),
+                Err(e) => {
+                    eprintln!(
+                        "{} Orthophoto unavailable: {e}. Ground stays grass.",
+                        "Warning:".yellow().bold()
+                    );
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Cells of the terrain grid of `xzbbox`, one per block
+fn coverage_grid(xzbbox: &XZBBox) -> (usize, usize) {
+    (
+        (xzbbox.max_x() - xzbbox.min_x() + 1) as usize,
+        (xzbbox.max_z() - xzbbox.min_z() + 1) as usize,
+    )
+}
+
+/// The requested terrain and orthophoto data, and the building footprints matched to BBR
+/// out of all of them, as sources of the coverage map. Terrain coverage is only drawn for
+/// DHM or a local DEM.
+fn coverage_sources<'a>(
+    xzbbox: &XZBBox,
+    ground: &'a Ground,
+    ortho: Option<&'a enrichment::ortho::OrthoGround>,
+    bbr: Option<&'a (CoordinateBitmap, CoordinateBitmap)>,
+    args: &Args,
+) -> Vec<CoverageSource<'a>> {
+    let mut sources = Vec::new();
+    if args.terrain && (args.dem.is_some() || args.dhm_token.is_some()) {
+        sources.push(CoverageSource {
+            name: if args.dem.is_some() {
+                "Local DEM"
+            } else {
+                "DHM terrain"
+            },
+            covers: Box::new(|x, z| ground.is_measured(XZPoint::new(x, z))),
+            within: None,
+        });
+    }
+    if args.ortho && args.dhm_token.is_some() {
+        sources.push(CoverageSource {
+            name: "Orthophoto",
+            covers: Box::new(move |x, z| ortho.is_some_and(|ortho| ortho.covers(x, z))),
+            within: None,
+        });
+    }
+    // The bitmaps are in world coordinates, the map in grid cells
+    if let Some((footprints, matched)) = bbr.filter(|_| args.dhm_token.is_some()) {
+        let (min_x, min_z) = (xzbbox.min_x(), xzbbox.min_z());
+        sources.push(CoverageSource {
+            name: "BBR buildings",
+            covers: Box::new(move |x, z| matched.contains(min_x + x, min_z + z)),
+            within: Some(Box::new(move |x, z| {
+                footprints.contains(min_x + x, min_z + z)
+            })),
+        });
+    }
+    sources
+}
+
+/// Writes the coverage map of the requested terrain, orthophoto and BBR data if `path` is
+/// given and prints what each source covers. A source covering less than `--min-coverage`
+/// of the area stops the run.
+fn check_coverage(
+    path: Option<&Path>,
+    xzbbox: &XZBBox,
+    ground: &Ground,
+    ortho: Option<&enrichment::ortho::OrthoGround>,
+    bbr: Option<&(CoordinateBitmap, CoordinateBitmap)>,
+    args: &Args,
+) -> Result<(), String> {
+    let sources = coverage_sources(xzbbox, ground, ortho, bbr, args);
+    if sources.is_empty() {
+        eprintln!(
+            "{} --coverage-map and --min-coverage need DHM terrain (--terrain with --dhm-token or --dem), --ortho or --interiors; coverage is not checked.",
+            "Warning:".yellow().bold()
+        );
+        return Ok(());
+    }
+
+    let grid = coverage_grid(xzbbox);
+    let coverage = match path {
+        Some(path) => match coverage::write_coverage_map(path, grid, &sources) {
+            Ok(coverage) => coverage,
+            Err(e) => {
+                eprintln!("{} {e}", "Warning:".yellow().bold());
+                coverage::coverage_map(grid, &sources).1
+            }
+        },
+        None => coverage::coverage_map(grid, &sources).1,
+    };
+    let shares: Vec<String> = coverage
+        .iter()
+        .map(|source| format!("{} {:.0}%", source.name, source.percent()))
+        .collect();
+    match path {
+        Some(path) => println!("Coverage: {} (map: {})", shares.join(", "), path.display()),
+        None => println!("Coverage: {}", shares.join(", ")),
+    }
+
+    let below = |share: f64| -> Vec<&str> {
+        coverage
+            .iter()
+            .filter(|source| source.percent() < share)
+            .map(|source| source.name)
+            .collect()
+    };
+    if let Some(min_coverage) = args.min_coverage {
+        let short = below(min_coverage);
+        if !short.is_empty() {
+            return Err(format!(
+                "{} covers less than {min_coverage}% of the area (--min-coverage); stopped before any block was placed",
+                short.join(" and ")
+            ));
+        }
+    }
+    let missing = below(90.0);
+    if !missing.is_empty() {
+        eprintln!(
+            "{} {} missing for parts of the area; check the token and the bbox, or stop the run now.",
+            "Warning:".yellow().bold(),
+            missing.join(" and ")
+        );
+    }
+    Ok(())
+}
+
+/// Coverage of the DHM terrain and the orthophoto over `args.bbox`, fetched on their own
+/// before anything is generated, for the GUI's coverage preview
+pub fn coverage_preview(args: &Args) -> Result<(RgbImage, Vec<coverage::SourceCoverage>), String> {
+    let (_, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&args.bbox, args.scale)
+        .map_err(|e| format!("Failed to create coordinate transformer: {e}"))?;
+    let ground = crate::ground::generate_ground_data(args);
+    let ortho = fetch_ortho(args, &args.bbox, &xzbbox);
+    let sources = coverage_sources(&xzbbox, &ground, ortho.as_ref(), None, args);
+    if sources.is_empty() {
+        return Err("The coverage preview needs a Dataforsyningen token".to_string());
+    }
+    Ok(coverage::coverage_map(coverage_grid(&xzbbox), &sources))
+}
+
+/// Check of the saved world against the GeoDanmark orthophoto (`--ortho-check`), made on
+/// the map preview once every tile is generated
+#[derive(Clone)]
+pub struct OrthoCheck {
+    /// Where the world blended over the orthophoto is written
+    pub path: PathBuf,
+    /// Area of the whole world
+    pub llbbox: LLBBox,
+    pub scale: f64,
+    /// Dataforsyningen token for the orthophoto
+    pub token: String,
+}
+
+impl OrthoCheck {
+    /// The check of a Java world of `llbbox`, or `None` with a warning when it cannot be made
+    pub fn new(path: PathBuf, llbbox: LLBBox, scale: f64, token: Option<&str>) -> Option<Self> {
+        let Some(token) = token else {
+            eprintln!(
+                "{} --ortho-check needs a Dataforsyningen token (--dhm-token); no check is made.",
+                "Warning:".yellow().bold()
+            );
+            return None;
+        };
+        if !crate::data_sources::Realm::of(&llbbox).has_danish_data() {
+            eprintln!(
+                "{} The GeoDanmark orthophoto only covers Denmark; no check is made.",
+                "Warning:".yellow().bold()
+            );
+            return None;
+        }
+        Some(Self {
+            path,
+            llbbox,
+            scale,
+            token: token.to_string(),
+        })
+    }
+}
+
+/// Renders the saved world top down and checks it against the orthophoto, for runs without
+/// a map preview
+pub fn check_ortho_alignment(world_dir: &Path, xzbbox: &XZBBox, check: &OrthoCheck) {
+    match map_renderer::render_world_image(
+        world_dir,
+        xzbbox.min_x(),
+        xzbbox.max_x(),
+        xzbbox.min_z(),
+        xzbbox.max_z(),
+    ) {
+        Ok(render) => compare_with_ortho(&render, check),
+        Err(e) => eprintln!("{} {e}", "Warning:".yellow().bold()),
+    }
+}
+
+/// Blends a top-down render of the world over the orthophoto into the check's image and
+/// prints how far the world sits from the photo.
+fn compare_with_ortho(render: &RgbImage, check: &OrthoCheck) {
+    let path = &check.path;
+    let size = (render.width() as usize, render.height() as usize);
+    let photo = enrichment::ortho::fetch_ortho_image(&check.llbbox, &check.token, size);
+    let checked = photo.and_then(|photo| {
+        ortho_alignment::blend(render, &photo)
+            .save(path)
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        Ok(ortho_alignment::estimate_offset(render, &photo))
+    });
+    match checked {
+        Ok(Some((dx, dz))) => {
+            // One render pixel per block
+            let (east, south) = (dx / check.scale, dz / check.scale);
+            println!(
+                "Orthophoto alignment: the world sits {:.1} m {} and {:.1} m {} of the orthophoto (check: {})",
+                east.abs(),
+                if east < 0.0 { "west" } else { "east" },
+                south.abs(),
+                if south < 0.0 { "north" } else { "south" },
+                path.display()
+            );
+            if east.hypot(south) > MAX_ORTHO_OFFSET_M {
+                eprintln!(
+                    "{} The world is visibly offset from the orthophoto; check the projection and scale before generating a large area.",
+                    "Warning:".yellow().bold()
+                );
+            }
+        }
+        Ok(None) => println!(
+            "Orthophoto alignment: too little in common to estimate an offset (check: {})",
+            path.display()
+        ),
+        Err(e) => eprintln!("{} {e}", "Warning:".yellow().bold()),
+    }
+}
+
 /// Information needed to generate a map preview after world generation is complete
 #[derive(Clone)]
 pub struct MapPreviewInfo {
@@ -674,6 +1416,8 @@ pub struct MapPreviewInfo {
     pub min_z: i32,
     pub max_z: i32,
     pub world_area: i64,
+    /// Check of the rendered map against the orthophoto (`--ortho-check`)
+    pub ortho_check: Option<OrthoCheck>,
 }
 
 impl MapPreviewInfo {
@@ -688,6 +1432,7 @@ impl MapPreviewInfo {
             min_z: xzbbox.min_z(),
             max_z: xzbbox.max_z(),
             world_area: world_width * world_height,
+            ortho_check: None,
         }
     }
 }
@@ -702,25 +1447,36 @@ pub const MAX_MAP_PREVIEW_AREA: i64 = 6400 * 6900;
 /// For Java worlds only, and only if the world area is within limits.
 pub fn start_map_preview_generation(info: MapPreviewInfo) {
     if info.world_area > MAX_MAP_PREVIEW_AREA {
+        if info.ortho_check.is_some() {
+            eprintln!(
+                "{} The world is too large for a map preview; no orthophoto check is made.",
+                "Warning:".yellow().bold()
+            );
+        }
         return;
     }
 
     std::thread::spawn(move || {
         // Use catch_unwind to prevent any panic from affecting the application
         let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            map_renderer::render_world_map(
+            let image = map_renderer::render_world_image(
                 &info.world_path,
                 info.min_x,
                 info.max_x,
                 info.min_z,
                 info.max_z,
-            )
+            )?;
+            map_renderer::save_world_map(&info.world_path, &image)?;
+            Ok::<_, String>(image)
         }));
 
         match result {
-            Ok(Ok(_path)) => {
+            Ok(Ok(image)) => {
                 // Notify the GUI that the map preview is ready
                 emit_map_preview_ready();
+                if let Some(check) = &info.ortho_check {
+                    compare_with_ortho(&image, check);
+                }
             }
             Ok(Err(e)) => {
                 eprintln!("Warning: Failed to generate map preview: {}", e);
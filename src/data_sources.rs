@@ -0,0 +1,176 @@
+//! Choosing data sources by where the bbox lies.
+//!
+//! DHM, DAWA and Danske Stednavne only cover Denmark proper. Greenland and the Faroe
+//! Islands are part of the realm but need other sources: their terrain comes from ArcticDEM,
+//! which covers all land north of 60°N, anywhere else falls back to the global elevation
+//! tiles, and the Danish-only enrichments are skipped with a warning instead of failing on
+//! empty responses.
+
+use crate::args::Args;
+use crate::coordinate_system::geographic::LLBBox;
+
+/// Part of the realm (or the world) a bbox lies in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Realm {
+    Denmark,
+    Greenland,
+    FaroeIslands,
+    Elsewhere,
+}
+
+impl Realm {
+    /// Realm of the bbox centre
+    pub fn of(bbox: &LLBBox) -> Self {
+        let lat = (bbox.min().lat() + bbox.max().lat()) / 2.0;
+        let lng = (bbox.min().lng() + bbox.max().lng()) / 2.0;
+        if (54.4..=57.9).contains(&lat) && (7.8..=15.3).contains(&lng) {
+            Realm::Denmark
+        } else if (61.3..=62.5).contains(&lat) && (-7.8..=-6.2).contains(&lng) {
+            Realm::FaroeIslands
+        } else if (59.5..=83.8).contains(&lat) && (-74.0..=-10.0).contains(&lng) {
+            Realm::Greenland
+        } else {
+            Realm::Elsewhere
+        }
+    }
+
+    /// Whether DHM, DAWA and Danske Stednavne cover the realm
+    pub fn has_danish_data(self) -> bool {
+        self == Realm::Denmark
+    }
+
+    /// Whether the ArcticDEM mosaic covers the realm
+    pub fn has_arcticdem(self) -> bool {
+        matches!(self, Realm::Greenland | Realm::FaroeIslands)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Realm::Denmark => "Denmark",
+            Realm::Greenland => "Greenland",
+            Realm::FaroeIslands => "the Faroe Islands",
+            Realm::Elsewhere => "outside the Danish realm",
+        }
+    }
+}
+
+/// Warning that the Danish-only enrichments the run asks for add nothing in `realm`, or
+/// `None` if the realm is Denmark or the run asks for none of them
+pub fn danish_only_warning(args: &Args, realm: Realm) -> Option<String> {
+    // Enrichments whose sources only cover Denmark: whether the run asks for it, its
+    // source, and what it adds to the world
+    let enrichments = [
+        (args.addresses, "DAWA", "address signs"),
+        (args.place_names, "Danske Stednavne", "place-name markers"),
+        (args.interiors, "BBR", "use-specific interiors"),
+        (args.protected_nature, "Miljøportal", "§3 nature areas"),
+        (args.field_crops, "Markkort", "field crops"),
+        (args.forest_types, "the forest map", "forest types"),
+        (
+            args.wind_turbines,
+            "the turbine register",
+            "registered turbines",
+        ),
+        (
+            args.ancient_monuments,
+            "Fund og Fortidsminder",
+            "ancient monuments",
+        ),
+        (args.geodanmark_coast, "GeoDanmark", "GeoDanmark coastline"),
+        (
+            args.geodanmark_roads,
+            "GeoDanmark",
+            "GeoDanmark road attributes",
+        ),
+    ];
+    let requested: Vec<_> = enrichments
+        .into_iter()
+        .filter(|&(requested, ..)| requested)
+        .collect();
+    if realm.has_danish_data() || requested.is_empty() {
+        return None;
+    }
+    let mut sources: Vec<&str> = requested.iter().map(|&(_, source, _)| source).collect();
+    sources.dedup();
+    let additions: Vec<&str> = requested.iter().map(|&(.., adds)| adds).collect();
+    let cover = if sources.len() == 1 {
+        "covers"
+    } else {
+        "cover"
+    };
+    let mut warning = format!(
+        "{} only {cover} Denmark, not {}; the run adds no {}.",
+        join_words(&sources, "and"),
+        realm.name(),
+        join_words(&additions, "or"),
+    );
+    if let Some(first) = warning.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    Some(warning)
+}
+
+/// "a", "a and b", "a, b and c"
+fn join_words(words: &[&str], conjunction: &str) -> String {
+    match words {
+        [] => String::new(),
+        [word] => word.to_string(),
+        [rest @ .., last] => format!("{} {conjunction} {last}", rest.join(", ")),
+    }
+}
+
+/// Whether the bbox centre lies in Jutland, west of Funen and the Kattegat islands. Its
+/// conifer woods are mostly spruce plantations on former heath.
+pub fn is_jutland(bbox: &LLBBox) -> bool {
+    let lat = (bbox.min().lat() + bbox.max().lat()) / 2.0;
+    let lng = (bbox.min().lng() + bbox.max().lng()) / 2.0;
+    Realm::of(bbox) == Realm::Denmark
+        && lng < 11.0
+        && (lng < 9.75 || (lat > 55.7 && !(lng > 10.45 && lat < 56.15)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn realms_follow_the_bbox_centre() {
+        let bbox = |lat: f64, lng: f64| LLBBox::new(lat, lng, lat + 0.01, lng + 0.01).unwrap();
+        assert_eq!(Realm::of(&bbox(55.67, 12.56)), Realm::Denmark);
+        assert_eq!(Realm::of(&bbox(64.17, -51.74)), Realm::Greenland);
+        assert_eq!(Realm::of(&bbox(62.01, -6.77)), Realm::FaroeIslands);
+        assert_eq!(Realm::of(&bbox(59.91, 10.75)), Realm::Elsewhere);
+        assert!(!Realm::Greenland.has_danish_data());
+        assert!(Realm::FaroeIslands.has_arcticdem());
+        assert!(!Realm::Denmark.has_arcticdem());
+
+        assert!(is_jutland(&bbox(56.16, 9.55)), "Silkeborg");
+        assert!(is_jutland(&bbox(56.15, 10.2)), "Aarhus");
+        assert!(!is_jutland(&bbox(55.40, 10.39)), "Odense");
+        assert!(!is_jutland(&bbox(55.67, 12.56)), "Copenhagen");
+    }
+
+    #[test]
+    fn the_warning_names_the_requested_sources_only() {
+        let args = |flags: &[&str]| {
+            let bbox = ["--bbox", "64.1,-51.8,64.2,-51.7"];
+            let mut argv = vec!["arnis", "--output-dir", "."];
+            argv.extend(bbox.iter().chain(flags));
+            Args::parse_from(argv)
+        };
+        assert_eq!(danish_only_warning(&args(&[]), Realm::Greenland), None);
+        let all_geodanmark = args(&["--addresses", "--geodanmark-coast", "--geodanmark-roads"]);
+        assert_eq!(danish_only_warning(&all_geodanmark, Realm::Denmark), None);
+        assert_eq!(
+            danish_only_warning(&all_geodanmark, Realm::Greenland).unwrap(),
+            "DAWA and GeoDanmark only cover Denmark, not Greenland; the run adds no address \
+             signs, GeoDanmark coastline or GeoDanmark road attributes."
+        );
+        assert_eq!(
+            danish_only_warning(&args(&["--wind-turbines"]), Realm::FaroeIslands).unwrap(),
+            "The turbine register only covers Denmark, not the Faroe Islands; the run adds no \
+             registered turbines."
+        );
+    }
+}
@@ -1,16 +1,20 @@
+use crate::bathymetry::{apply_bathymetry, fetch_bathymetry, BathymetrySource};
 use crate::coordinate_system::geographic::LLBBox;
 use crate::coordinate_system::transformation::geo_distance;
-use crate::elevation_data::ElevationData;
-use crate::progress::{emit_gui_progress_update, is_running_with_gui};
+use crate::elevation_data::{ElevationData, ElevationMode, HeightMapping};
+use crate::progress::{cancel_requested, emit_gui_progress_update, is_running_with_gui};
+use crate::terrain_pipeline::{
+    FlattenMask, GaussianBlur, RestoreUnsmoothed, SlopeAnalysis, TerrainPipeline,
+};
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use reqwest::blocking::Client;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Duration;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 /// Convert WGS84 (lat, lon) to ETRS89/UTM32N (easting, northing).
-fn wgs84_to_utm32n(lat: f64, lon: f64) -> (f64, f64) {
+pub(crate) fn wgs84_to_utm32n(lat: f64, lon: f64) -> (f64, f64) {
     let a = 6378137.0_f64;
     let f = 1.0 / 298.257223563;
     let k0 = 0.9996;
@@ -56,32 +60,205 @@ fn wgs84_to_utm32n(lat: f64, lon: f64) -> (f64, f64) {
     (easting, northing)
 }
 
+/// Convert ETRS89/UTM32N (easting, northing) to WGS84 (lat, lon).
+pub(crate) fn utm32n_to_wgs84(easting: f64, northing: f64) -> (f64, f64) {
+    let a = 6378137.0_f64;
+    let f = 1.0 / 298.257223563;
+    let k0 = 0.9996;
+    let lon0 = 9.0_f64;
+
+    let e2 = 2.0 * f - f * f;
+    let e_prime2 = e2 / (1.0 - e2);
+    let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+
+    // Footpoint latitude of the meridian arc
+    let m = northing / k0;
+    let mu = m / (a * (1.0 - e2 / 4.0 - 3.0 * e2 * e2 / 64.0 - 5.0 * e2.powi(3) / 256.0));
+    let phi1 = mu
+        + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+        + (21.0 * e1 * e1 / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+        + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+        + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+    let n1 = a / (1.0 - e2 * phi1.sin().powi(2)).sqrt();
+    let r1 = a * (1.0 - e2) / (1.0 - e2 * phi1.sin().powi(2)).powf(1.5);
+    let t1 = phi1.tan().powi(2);
+    let c1 = e_prime2 * phi1.cos().powi(2);
+    let d = (easting - 500000.0) / (n1 * k0);
+
+    let lat = phi1
+        - (n1 * phi1.tan() / r1)
+            * (d * d / 2.0
+                - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1 * c1 - 9.0 * e_prime2) * d.powi(4) / 24.0
+                + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1 * t1
+                    - 252.0 * e_prime2
+                    - 3.0 * c1 * c1)
+                    * d.powi(6)
+                    / 720.0);
+    let lon = lon0.to_radians()
+        + (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+            + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1 * c1 + 8.0 * e_prime2 + 24.0 * t1 * t1)
+                * d.powi(5)
+                / 120.0)
+            / phi1.cos();
+
+    (lat.to_degrees(), lon.to_degrees())
+}
+
+/// Largest raster side (pixels) the DHM WCS serves in one request
+const MAX_REQUEST_PIXELS: usize = 2048;
+/// Native resolution of the DHM terrain model (metres per pixel)
+const DHM_NATIVE_RESOLUTION: f64 = 0.4;
+/// Coarser resolutions (metres per pixel) used for large bboxes, finest first
+const LOD_RESOLUTIONS: [f64; 2] = [1.6, 3.2];
+/// Pixels a level-of-detail fetch may total before the next coarser resolution is used
+const LOD_PIXEL_BUDGET: f64 = 4096.0 * 4096.0;
+
+/// Picks the DHM resolution for a bbox spanning `span` metres whose terrain grid is
+/// `grid_size` blocks along its longer side. Bboxes that fit one request keep one pixel per
+/// block (`None`); larger ones are fetched in tiles at 1.6m, or at 3.2m once 1.6m would
+/// exceed the pixel budget, but never finer than one pixel per block.
+fn auto_resolution(span: (f64, f64), grid_size: usize, metres_per_block: f64) -> Option<f64> {
+    if grid_size <= MAX_REQUEST_PIXELS {
+        return None;
+    }
+    let fits = |resolution: f64| (span.0 / resolution) * (span.1 / resolution) <= LOD_PIXEL_BUDGET;
+    let resolution = LOD_RESOLUTIONS
+        .into_iter()
+        .find(|&resolution| fits(resolution))
+        .unwrap_or(LOD_RESOLUTIONS[LOD_RESOLUTIONS.len() - 1]);
+    Some(resolution.max(metres_per_block))
+}
+
+/// Splits a `width`x`height` raster into requests of at most `MAX_REQUEST_PIXELS` per side,
+/// as (column, row, width, height) with row 0 at the northern edge.
+fn raster_tiles(width: usize, height: usize) -> Vec<(usize, usize, usize, usize)> {
+    let mut tiles = Vec::new();
+    for row in (0..height).step_by(MAX_REQUEST_PIXELS) {
+        for col in (0..width).step_by(MAX_REQUEST_PIXELS) {
+            tiles.push((
+                col,
+                row,
+                MAX_REQUEST_PIXELS.min(width - col),
+                MAX_REQUEST_PIXELS.min(height - row),
+            ));
+        }
+    }
+    tiles
+}
+
 /// Fetch high-resolution elevation data from DHM via Dataforsyningen WCS.
 /// Returns an ElevationData grid matching the Minecraft world dimensions.
 pub fn fetch_dhm_elevation(
     bbox: &LLBBox,
     scale: f64,
-    ground_level: i32,
+    mapping: &HeightMapping,
     token: &str,
+    options: &DemOptions,
     debug: bool,
 ) -> Result<ElevationData, Box<dyn std::error::Error>> {
     println!("{}", "Fetching DHM high-resolution terrain...".bold());
     emit_gui_progress_update(12.0, "Fetching DHM terrain data...");
 
-    let (base_scale_z, base_scale_x) = geo_distance(bbox.min(), bbox.max());
-    let grid_width = (base_scale_x.floor() * scale) as usize;
-    let grid_height = (base_scale_z.floor() * scale) as usize;
-
-    if grid_width == 0 || grid_height == 0 {
-        return Err("Grid dimensions are zero".into());
-    }
+    let (grid_width, grid_height) = grid_dimensions(bbox, scale)?;
 
     let (min_e, min_n) = wgs84_to_utm32n(bbox.min().lat(), bbox.min().lng());
     let (max_e, max_n) = wgs84_to_utm32n(bbox.max().lat(), bbox.max().lng());
 
-    let req_width = grid_width.min(2048);
-    let req_height = grid_height.min(2048);
+    let span = (max_e - min_e, max_n - min_n);
+    let resolution = match options.resolution {
+        Some(resolution) => Some(resolution.max(DHM_NATIVE_RESOLUTION)),
+        None => auto_resolution(
+            span,
+            grid_width.max(grid_height),
+            span.0 / grid_width as f64,
+        ),
+    };
+    let (raster_width, raster_height) = match resolution {
+        Some(resolution) => (
+            (span.0 / resolution).ceil().max(1.0) as usize,
+            (span.1 / resolution).ceil().max(1.0) as usize,
+        ),
+        None => (
+            grid_width.min(MAX_REQUEST_PIXELS),
+            grid_height.min(MAX_REQUEST_PIXELS),
+        ),
+    };
+    let tiles = raster_tiles(raster_width, raster_height);
+    if let Some(resolution) = resolution {
+        println!(
+            "DHM level of detail: {resolution:.1}m per pixel, {raster_width}x{raster_height} pixels in {} tile(s)",
+            tiles.len()
+        );
+    }
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(120))
+        .build()?;
+
+    let pixel_e = span.0 / raster_width as f64;
+    let pixel_n = span.1 / raster_height as f64;
+    let mut raster = DecodedRaster {
+        width: raster_width,
+        height: raster_height,
+        values: vec![f32::NAN; raster_width * raster_height],
+    };
+    for (index, &(col, row, width, height)) in tiles.iter().enumerate() {
+        if tiles.len() > 1 {
+            let status = format!("Fetching DHM terrain tile {}/{}...", index + 1, tiles.len());
+            println!("{status}");
+            emit_gui_progress_update(12.0, &status);
+        }
+        let tile_bbox = (
+            min_e + col as f64 * pixel_e,
+            max_n - (row + height) as f64 * pixel_n,
+            min_e + (col + width) as f64 * pixel_e,
+            max_n - row as f64 * pixel_n,
+        );
+        let bytes = fetch_dhm_tile(
+            &client,
+            tile_bbox,
+            (width, height),
+            token,
+            &options.retry,
+            debug,
+        )?;
+        let tile = decode_dhm_tile(&bytes)?;
+        // The compressed response is no longer needed once decoded
+        drop(bytes);
 
+        if tiles.len() == 1 {
+            raster = tile;
+            break;
+        }
+        if tile.width != width || tile.height != height {
+            return Err(format!(
+                "DHM tile {}/{} is {}x{} pixels, expected {width}x{height}",
+                index + 1,
+                tiles.len(),
+                tile.width,
+                tile.height
+            )
+            .into());
+        }
+        for (y, tile_row) in tile.values.chunks(width).enumerate() {
+            let start = (row + y) * raster_width + col;
+            raster.values[start..start + width].copy_from_slice(tile_row);
+        }
+    }
+    emit_gui_progress_update(15.0, "Processing DHM terrain...");
+    build_elevation_data(&raster, bbox, scale, mapping, options, resolution.is_some())
+}
+
+/// Requests the terrain raster for one UTM bbox, retrying as configured.
+fn fetch_dhm_tile(
+    client: &Client,
+    (min_e, min_n, max_e, max_n): (f64, f64, f64, f64),
+    (req_width, req_height): (usize, usize),
+    token: &str,
+    retry: &RetryPolicy,
+    debug: bool,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     let url = format!(
         "https://api.dataforsyningen.dk/dhm_wcs_DAF?\
          SERVICE=WCS&REQUEST=GetCoverage&VERSION=1.0.0\
@@ -93,162 +270,386 @@ pub fn fetch_dhm_elevation(
          &token={token}"
     );
 
-    let client = Client::builder()
-        .timeout(Duration::from_secs(120))
-        .build()?;
-
-    let quick_retries = 3;
-    let max_rounds = 5;
-    let mut last_err = String::new();
-    let (content_type, bytes) = 'retry: {
-        for round in 1..=max_rounds {
-            if round > 1 {
-                println!(
-                    "DHM still failing after round {}. Waiting 60s before next round...",
-                    round - 1
-                );
-                std::thread::sleep(Duration::from_secs(60));
-            }
+    let (content_type, bytes) = retry.run("DHM WCS", || {
+        let resp = match client.get(&url).send() {
+            Ok(r) => r,
+            Err(e) => return Attempt::Retry(format!("Request failed: {e}")),
+        };
 
-            for attempt in 1..=quick_retries {
-                if attempt > 1 {
-                    let delay = Duration::from_secs(2u64.pow(attempt as u32 - 1));
-                    println!(
-                        "DHM request round {}/{} attempt {}/{} (retrying in {}s)...",
-                        round,
-                        max_rounds,
-                        attempt,
-                        quick_retries,
-                        delay.as_secs()
-                    );
-                    std::thread::sleep(delay);
-                }
+        let status = resp.status();
+        if status.is_server_error() || status == 429 {
+            let body = resp.text().unwrap_or_default();
+            return Attempt::Retry(format!(
+                "DHM WCS returned status {status}: {}",
+                &body[..body.len().min(500)]
+            ));
+        }
 
-                let resp = match client.get(&url).send() {
-                    Ok(r) => r,
-                    Err(e) => {
-                        last_err = format!("Request failed: {e}");
-                        continue;
-                    }
-                };
-
-                let status = resp.status();
-                if status.is_server_error() || status == 429 {
-                    let body = resp.text().unwrap_or_default();
-                    last_err = format!(
-                        "DHM WCS returned status {status}: {}",
-                        &body[..body.len().min(500)]
-                    );
-                    continue;
-                }
+        if !status.is_success() {
+            let body = resp.text().unwrap_or_default();
+            if status == reqwest::StatusCode::FORBIDDEN && debug {
+                print_dhm_auth_debug(token, &url);
+            }
+            return Attempt::Fail(format!(
+                "DHM WCS returned status {status}: {}",
+                &body[..body.len().min(500)]
+            ));
+        }
 
-                if !status.is_success() {
-                    let body = resp.text().unwrap_or_default();
-                    if status == reqwest::StatusCode::FORBIDDEN && debug {
-                        print_dhm_auth_debug(token, &url);
-                    }
-                    return Err(format!(
-                        "DHM WCS returned status {status}: {}",
-                        &body[..body.len().min(500)]
-                    )
-                    .into());
-                }
+        let ct = resp
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
 
-                let ct = resp
-                    .headers()
-                    .get("content-type")
-                    .and_then(|v| v.to_str().ok())
-                    .unwrap_or("")
-                    .to_string();
-
-                match resp.bytes() {
-                    Ok(b) => break 'retry (ct, b),
-                    Err(e) => {
-                        last_err = format!("Failed to read response body: {e}");
-                        continue;
-                    }
-                }
-            }
+        match read_dhm_body(resp) {
+            Ok(b) => Attempt::Done((ct, b)),
+            Err(e) => Attempt::Retry(format!("Failed to read response body: {e}")),
         }
-        return Err(format!(
-            "DHM WCS failed after {max_rounds} rounds of {quick_retries} attempts: {last_err}"
-        )
-        .into());
-    };
+    })?;
 
     if content_type.contains("xml") || (bytes.len() > 5 && bytes[0] == b'<') {
         let text = String::from_utf8_lossy(&bytes[..bytes.len().min(500)]);
         return Err(format!("DHM WCS returned error: {text}").into());
     }
 
+    crate::manifest::record_input(
+        "dhm",
+        &format!(
+            "dhm_wcs_DAF dhm_terraen BBOX={min_e},{min_n},{max_e},{max_n} {req_width}x{req_height}"
+        ),
+        &bytes,
+    );
+
     println!(
         "Received {} bytes of DHM terrain data. Parsing...",
         bytes.len()
     );
-    emit_gui_progress_update(15.0, "Processing DHM terrain...");
+    Ok(bytes)
+}
 
-    let cursor = std::io::Cursor::new(bytes.to_vec());
-    let mut decoder = tiff::decoder::Decoder::new(cursor)
-        .map_err(|e| format!("Failed to decode GeoTIFF: {e}"))?;
+/// Decodes a DHM GeoTIFF response, reporting progress per TIFF chunk.
+fn decode_dhm_tile(bytes: &[u8]) -> Result<DecodedRaster, String> {
+    emit_gui_progress_update(14.0, "Parsing DHM terrain...");
+    let progress_bar = cli_progress_bar(
+        0,
+        "{spinner:.green} [{elapsed_precise}] [{bar:45.cyan/blue}] {pos}/{len} TIFF chunks",
+    );
+    let mut last_percent = 0;
+    let raster = decode_geotiff_with_progress(bytes, |done, total| {
+        if let Some(progress_bar) = &progress_bar {
+            progress_bar.set_length(total as u64);
+            progress_bar.set_position(done as u64);
+        }
+        let percent = done * 100 / total.max(1);
+        if percent >= last_percent + 5 {
+            last_percent = percent;
+            emit_gui_progress_update(
+                14.0 + percent as f64 / 100.0,
+                &format!("Parsing DHM terrain... {percent}%"),
+            );
+        }
+    })?;
+    if let Some(progress_bar) = progress_bar {
+        progress_bar.finish_and_clear();
+    }
+    Ok(raster)
+}
 
-    let (tiff_width, tiff_height) = decoder
-        .dimensions()
-        .map_err(|e| format!("Failed to read TIFF dimensions: {e}"))?;
+/// Progress bar for the CLI; `None` when the GUI reports progress instead.
+fn cli_progress_bar(len: u64, template: &str) -> Option<ProgressBar> {
+    if is_running_with_gui() {
+        return None;
+    }
+    let progress_bar = ProgressBar::new(len);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template(template)
+            .unwrap()
+            .progress_chars("#> "),
+    );
+    Some(progress_bar)
+}
+
+/// Bytes downloaded between two GUI progress updates
+const DOWNLOAD_PROGRESS_STEP: usize = 256 * 1024;
 
-    let image_data = decoder
-        .read_image()
-        .map_err(|e| format!("Failed to read TIFF image data: {e}"))?;
-
-    let raw_heights: Vec<f64> = match image_data {
-        tiff::decoder::DecodingResult::F32(data) => data.iter().map(|&v| v as f64).collect(),
-        tiff::decoder::DecodingResult::F64(data) => data.to_vec(),
-        tiff::decoder::DecodingResult::U8(data) => data.iter().map(|&v| v as f64).collect(),
-        tiff::decoder::DecodingResult::U16(data) => data.iter().map(|&v| v as f64).collect(),
-        tiff::decoder::DecodingResult::I16(data) => data.iter().map(|&v| v as f64).collect(),
-        _ => return Err("Unsupported TIFF pixel format".into()),
+/// Sleeps for `delay`, waking early when the GUI cancels generation.
+/// Returns `false` if generation was cancelled.
+fn wait_unless_cancelled(delay: Duration) -> bool {
+    const SLICE: Duration = Duration::from_millis(200);
+    let deadline = Instant::now() + delay;
+    loop {
+        if cancel_requested() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep(SLICE.min(deadline - now));
+    }
+}
+
+/// Reads the DHM response body block by block, so the download shows progress on the
+/// CLI and moves the GUI progress from 12% towards 14% instead of appearing to hang.
+fn read_dhm_body(mut resp: reqwest::blocking::Response) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let total = resp.content_length().filter(|&len| len > 0);
+    let progress_bar = match total {
+        Some(total) => cli_progress_bar(
+            total,
+            "{spinner:.green} [{elapsed_precise}] [{bar:45.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})",
+        ),
+        None => cli_progress_bar(
+            0,
+            "{spinner:.green} [{elapsed_precise}] {bytes} of DHM terrain ({bytes_per_sec})",
+        ),
     };
 
+    let mut body = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut last_emitted = 0;
+    loop {
+        if cancel_requested() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "cancelled",
+            ));
+        }
+        let read = resp.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&buffer[..read]);
+        if let Some(progress_bar) = &progress_bar {
+            progress_bar.set_position(body.len() as u64);
+        }
+
+        if body.len() - last_emitted >= DOWNLOAD_PROGRESS_STEP {
+            last_emitted = body.len();
+            let mb = body.len() as f64 / (1024.0 * 1024.0);
+            match total {
+                Some(total) => {
+                    let ratio = (body.len() as f64 / total as f64).min(1.0);
+                    emit_gui_progress_update(
+                        12.0 + ratio * 2.0,
+                        &format!("Downloading DHM terrain... {:.0}%", ratio * 100.0),
+                    );
+                }
+                None => emit_gui_progress_update(
+                    12.0,
+                    &format!("Downloading DHM terrain... {mb:.1} MB"),
+                ),
+            }
+        }
+    }
+
+    if let Some(progress_bar) = progress_bar {
+        progress_bar.finish_and_clear();
+    }
+    Ok(body)
+}
+
+/// Load elevation from a local GeoTIFF DEM in EPSG:25832 (e.g. a downloaded DHM tile)
+/// and run it through the same resample/scale pipeline as `fetch_dhm_elevation`.
+pub fn load_dem_elevation(
+    path: &Path,
+    bbox: &LLBBox,
+    scale: f64,
+    mapping: &HeightMapping,
+    options: &DemOptions,
+) -> Result<ElevationData, Box<dyn std::error::Error>> {
     println!(
-        "DHM TIFF: {}x{} pixels, resampling to {}x{} grid...",
-        tiff_width, tiff_height, grid_width, grid_height
+        "{} {}",
+        "Loading local DEM:".bold(),
+        path.display().to_string().bright_white()
     );
+    emit_gui_progress_update(12.0, "Loading local DEM...");
 
-    let mut height_grid: Vec<Vec<f64>> = vec![vec![0.0; grid_width]; grid_height];
-    let nodata = -9999.0;
-
-    for gz in 0..grid_height {
-        for gx in 0..grid_width {
-            let tx = (gx as f64 / grid_width as f64 * tiff_width as f64) as usize;
-            let tz = (gz as f64 / grid_height as f64 * tiff_height as f64) as usize;
-            let tx = tx.min(tiff_width as usize - 1);
-            let tz = tz.min(tiff_height as usize - 1);
-
-            let idx = tz * tiff_width as usize + tx;
-            let h = if idx < raw_heights.len() {
-                raw_heights[idx]
-            } else {
-                0.0
-            };
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read DEM {}: {e}", path.display()))?;
+    crate::manifest::record_input("dem", &path.display().to_string(), &bytes);
+
+    let georef = read_georeference(&bytes)?;
+    let raster = decode_geotiff(&bytes)?;
+    drop(bytes);
+
+    let (min_e, min_n) = wgs84_to_utm32n(bbox.min().lat(), bbox.min().lng());
+    let (max_e, max_n) = wgs84_to_utm32n(bbox.max().lat(), bbox.max().lng());
+    let raster = crop_to_utm_bbox(&raster, &georef, (min_e, min_n, max_e, max_n))?;
+
+    emit_gui_progress_update(15.0, "Processing DEM terrain...");
+    build_elevation_data(&raster, bbox, scale, mapping, options, false)
+}
+
+/// ArcticDEM mosaic on ArcGIS Living Atlas, served in any spatial reference
+const ARCTICDEM_URL: &str =
+    "https://elevation2.arcgis.com/arcgis/rest/services/Polar/ArcticDEM/ImageServer/exportImage";
+
+/// Fetch ArcticDEM elevation for areas without DHM coverage, such as Greenland and the
+/// Faroes. The mosaic is requested in EPSG:4326 over the bbox, so its pixels line up with
+/// the lat/lng terrain grid.
+pub fn fetch_arcticdem_elevation(
+    bbox: &LLBBox,
+    scale: f64,
+    mapping: &HeightMapping,
+    options: &DemOptions,
+) -> Result<ElevationData, Box<dyn std::error::Error>> {
+    println!("{}", "Fetching ArcticDEM terrain...".bold());
+    emit_gui_progress_update(12.0, "Fetching ArcticDEM terrain...");
+
+    let (grid_width, grid_height) = grid_dimensions(bbox, scale)?;
+    let (width, height) = (
+        grid_width.min(MAX_REQUEST_PIXELS),
+        grid_height.min(MAX_REQUEST_PIXELS),
+    );
+    let url = format!(
+        "{ARCTICDEM_URL}?bbox={},{},{},{}&bboxSR=4326&imageSR=4326\
+         &size={width},{height}&format=tiff&pixelType=F32&noData={DHM_NODATA}\
+         &interpolation=RSP_BilinearInterpolation&f=image",
+        bbox.min().lng(),
+        bbox.min().lat(),
+        bbox.max().lng(),
+        bbox.max().lat()
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(300))
+        .build()?;
+    let bytes = options
+        .retry
+        .run("ArcticDEM", || match client.get(&url).send() {
+            Ok(resp) if resp.status().is_success() => match resp.bytes() {
+                Ok(bytes) => Attempt::Done(bytes.to_vec()),
+                Err(e) => Attempt::Retry(format!("Failed to read response body: {e}")),
+            },
+            Ok(resp) => Attempt::Retry(format!("ArcticDEM returned status {}", resp.status())),
+            Err(e) => Attempt::Retry(format!("Request failed: {e}")),
+        })?;
+    crate::manifest::record_input("arcticdem", ARCTICDEM_URL, &bytes);
+
+    emit_gui_progress_update(14.0, "Parsing ArcticDEM terrain...");
+    let raster = decode_geotiff(&bytes)?;
+    drop(bytes);
 
-            height_grid[gz][gx] = if h <= nodata { 0.0 } else { h };
+    emit_gui_progress_update(15.0, "Processing ArcticDEM terrain...");
+    let level_of_detail = (width, height) != (grid_width, grid_height);
+    build_elevation_data(&raster, bbox, scale, mapping, options, level_of_detail)
+}
+
+/// Grid size in cells (one per block) for the bbox at the given scale.
+fn grid_dimensions(bbox: &LLBBox, scale: f64) -> Result<(usize, usize), String> {
+    let (base_scale_z, base_scale_x) = geo_distance(bbox.min(), bbox.max());
+    let grid_width = (base_scale_x.floor() * scale) as usize;
+    let grid_height = (base_scale_z.floor() * scale) as usize;
+
+    if grid_width == 0 || grid_height == 0 {
+        return Err("Grid dimensions are zero".to_string());
+    }
+    Ok((grid_width, grid_height))
+}
+
+/// Resamples a DEM raster covering the bbox onto the terrain grid, fills nodata, applies
+/// bathymetry and smoothing, and maps the heights to Minecraft Y. `level_of_detail` marks
+/// a raster fetched in tiles at a coarser resolution, which gets the lighter smoothing.
+fn build_elevation_data(
+    raster: &DecodedRaster,
+    bbox: &LLBBox,
+    scale: f64,
+    mapping: &HeightMapping,
+    options: &DemOptions,
+    level_of_detail: bool,
+) -> Result<ElevationData, Box<dyn std::error::Error>> {
+    let (grid_width, grid_height) = grid_dimensions(bbox, scale)?;
+    let ground_level = mapping.ground_level;
+    let resample = options.resample;
+
+    println!(
+        "DHM TIFF: {}x{} pixels, resampling to {}x{} grid ({:?})...",
+        raster.width, raster.height, grid_width, grid_height, resample
+    );
+
+    let mut height_grid = resample_raster_to_grid(raster, grid_width, grid_height, resample);
+
+    let measured: Option<Vec<Vec<bool>>> = options.keep_measured.then(|| {
+        height_grid
+            .iter()
+            .map(|row| row.iter().map(|h| !h.is_nan()).collect())
+            .collect()
+    });
+    let (filled_land, filled_sea) = fill_nodata_cells(&mut height_grid);
+    if filled_land + filled_sea > 0 {
+        println!(
+            "DHM nodata: interpolated {filled_land} inland cells, {filled_sea} cells treated as sea"
+        );
+    }
+
+    if let Some(source) = options.bathymetry {
+        match fetch_bathymetry(bbox, grid_width, grid_height, source) {
+            Ok(depths) => {
+                let lowered = apply_bathymetry(&mut height_grid, &depths);
+                println!("Bathymetry: lowered {lowered} sea cells to the sampled depth");
+            }
+            Err(e) => {
+                eprintln!(
+                    "{} Bathymetry unavailable: {e}. Keeping a flat sea floor.",
+                    "Warning:".yellow().bold()
+                );
+            }
         }
     }
 
+    let sea = sea_flat_mask(&height_grid);
+    let sea_cells = sea.iter().flatten().filter(|&&sea| sea).count();
+
     let grid_size = (grid_width.min(grid_height) as f64).max(1.0);
-    let sigma = 7.0 * (grid_size / 100.0).sqrt();
-    println!("Smoothing DHM terrain (sigma={:.1})...", sigma);
-    emit_gui_progress_update(16.0, "Smoothing DHM terrain... 0%");
-    let height_grid = dhm_gaussian_blur(&height_grid, sigma);
-    emit_gui_progress_update(19.5, "Smoothing DHM terrain... 100%");
+    let sigma = smoothing_sigma(grid_size, level_of_detail);
+    // Without open sea in the area there is no coastline to find cliffs along
+    let cliffs = (sea_cells > 0)
+        .then(|| coastal_cliff_mask(&height_grid, &sea))
+        .flatten();
 
-    let mut min_h = f64::MAX;
-    let mut max_h = f64::MIN;
-    for row in &height_grid {
-        for &h in row {
-            min_h = min_h.min(h);
-            max_h = max_h.max(h);
-        }
+    let carved_sea = options.carve_sea_flats.then(|| {
+        println!("Sea carving: {sea_cells} sea-level flats connected to the open sea become water");
+        sea
+    });
+
+    let mut pipeline = TerrainPipeline::new();
+    pipeline.push(GaussianBlur { sigma });
+    let coastal_cliffs = cliffs.map(|(mask, unsmoothed)| {
+        let cells = mask.iter().flatten().filter(|&&cliff| cliff).count();
+        println!("Coastal cliffs: {cells} cells keep their unsmoothed face");
+        pipeline.push(RestoreUnsmoothed {
+            cells: unsmoothed,
+            blend: (2.0 * sigma).ceil() as usize,
+        });
+        mask
+    });
+    if let Some(mask) = &carved_sea {
+        pipeline.push(FlattenMask {
+            mask,
+            max_height: SEA_FLAT_MAX_M,
+        });
     }
+    pipeline.push(SlopeAnalysis {
+        cell_size: 1.0 / scale,
+        steep_deg: 40.0,
+    });
+    let height_grid = pipeline.run(height_grid);
+
+    let (min_h, max_h) = height_grid
+        .par_iter()
+        .map(|row| {
+            row.iter()
+                .fold((f64::MAX, f64::MIN), |(lo, hi), &h| (lo.min(h), hi.max(h)))
+        })
+        .reduce(
+            || (f64::MAX, f64::MIN),
+            |(lo1, hi1), (lo2, hi2)| (lo1.min(lo2), hi1.max(hi2)),
+        );
 
     let height_range = max_h - min_h;
     println!(
@@ -259,7 +660,7 @@ pub fn fetch_dhm_elevation(
     const MAX_Y: i32 = 319;
     const TERRAIN_HEIGHT_BUFFER: i32 = 15;
     let available_y_range = (MAX_Y - TERRAIN_HEIGHT_BUFFER - ground_level) as f64;
-    let ideal_scaled_range = height_range * scale;
+    let ideal_scaled_range = height_range * scale * mapping.exaggeration;
 
     let scaled_range = if ideal_scaled_range <= available_y_range {
         ideal_scaled_range
@@ -268,7 +669,19 @@ pub fn fetch_dhm_elevation(
         height_range * compression
     };
 
-    let sea_level_y = if height_range > 0.0 && min_h < 0.5 {
+    let absolute = mapping.mode == ElevationMode::Absolute;
+    if absolute {
+        println!(
+            "Absolute elevation: 1 block = {:.2}m, sea level at Y={}",
+            mapping.meters_per_block / mapping.exaggeration,
+            mapping.sea_level_y
+        );
+        mapping.warn_if_clipped(min_h, max_h);
+    }
+
+    let sea_level_y = if absolute {
+        (min_h < 0.5).then_some(mapping.sea_level_y)
+    } else if height_range > 0.0 && min_h < 0.5 {
         let sea_relative = (0.0 - min_h) / height_range;
         let sea_scaled = sea_relative * scaled_range;
         Some(
@@ -279,11 +692,14 @@ pub fn fetch_dhm_elevation(
         None
     };
 
-    let mc_heights: Vec<Vec<i32>> = height_grid
-        .iter()
+    let mut mc_heights: Vec<Vec<i32>> = height_grid
+        .par_iter()
         .map(|row| {
             row.iter()
                 .map(|&h| {
+                    if absolute {
+                        return mapping.absolute_y(h);
+                    }
                     let relative = if height_range > 0.0 {
                         (h - min_h) / height_range
                     } else {
@@ -297,6 +713,15 @@ pub fn fetch_dhm_elevation(
         })
         .collect();
 
+    // Carved flats need at least one block of water above them
+    if let (Some(mask), Some(sly)) = (&carved_sea, sea_level_y) {
+        for (row, mask_row) in mc_heights.iter_mut().zip(mask) {
+            for (y, _) in row.iter_mut().zip(mask_row).filter(|(_, &sea)| sea) {
+                *y = (*y).min(sly - 1);
+            }
+        }
+    }
+
     if let Some(sly) = sea_level_y {
         println!("DHM sea level at Minecraft Y={sly}");
     }
@@ -315,9 +740,727 @@ pub fn fetch_dhm_elevation(
         width: grid_width,
         height: grid_height,
         sea_level_y,
+        carved_sea: carved_sea.filter(|_| sea_level_y.is_some()),
+        coastal_cliffs: coastal_cliffs.filter(|_| sea_level_y.is_some()),
+        measured,
+    })
+}
+
+/// Highest height (meters) of a sea-level flat that is carved into water
+const SEA_FLAT_MAX_M: f64 = 0.3;
+
+/// Marks the cells at or below [`SEA_FLAT_MAX_M`] that are connected to the grid edge
+/// through other such cells: harbours, fjords and lagoons open to the sea. Enclosed
+/// low-lying land (e.g. diked polders) is not connected and stays dry.
+fn sea_flat_mask(grid: &[Vec<f64>]) -> Vec<Vec<bool>> {
+    let h = grid.len();
+    let w = grid.first().map_or(0, Vec::len);
+    let mut mask = vec![vec![false; w]; h];
+    if w == 0 || h == 0 {
+        return mask;
+    }
+    let is_flat = |x: usize, z: usize| grid[z][x] <= SEA_FLAT_MAX_M;
+
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    for x in 0..w {
+        stack.push((x, 0));
+        stack.push((x, h - 1));
+    }
+    for z in 0..h {
+        stack.push((0, z));
+        stack.push((w - 1, z));
+    }
+
+    while let Some((x, z)) = stack.pop() {
+        if mask[z][x] || !is_flat(x, z) {
+            continue;
+        }
+        mask[z][x] = true;
+        if x > 0 {
+            stack.push((x - 1, z));
+        }
+        if x + 1 < w {
+            stack.push((x + 1, z));
+        }
+        if z > 0 {
+            stack.push((x, z - 1));
+        }
+        if z + 1 < h {
+            stack.push((x, z + 1));
+        }
+    }
+    mask
+}
+
+/// Drop (meters) towards the sea from which a coastal slope is built as a cliff
+const CLIFF_MIN_DROP_M: f64 = 10.0;
+/// Cells over which that drop has to happen
+const CLIFF_RUN: usize = 6;
+/// Cells a cliff may stand back from the sea, leaving room for a beach at its foot
+const CLIFF_SEA_REACH: usize = 20;
+
+/// Finds coastal cliffs in the unsmoothed terrain: land within [`CLIFF_SEA_REACH`] cells of
+/// the open sea in `sea` (see [`sea_flat_mask`], so polders behind dikes are not sea) that
+/// drops by more than [`CLIFF_MIN_DROP_M`] within [`CLIFF_RUN`] cells. Returns the cliff mask
+/// and the unsmoothed heights of every cell within [`CLIFF_RUN`] of a cliff cell, or `None`
+/// without cliffs.
+#[allow(clippy::type_complexity)]
+fn coastal_cliff_mask(
+    grid: &[Vec<f64>],
+    sea: &[Vec<bool>],
+) -> Option<(Vec<Vec<bool>>, Vec<(usize, usize, f64)>)> {
+    let h = grid.len();
+    let w = grid.first().map_or(0, Vec::len);
+    let is_sea = |x: usize, z: usize| sea[z][x];
+
+    // Distance of every cell to the sea, up to the reach
+    let mut sea_distance = vec![vec![usize::MAX; w]; h];
+    let mut queue = std::collections::VecDeque::new();
+    for (z, row) in sea_distance.iter_mut().enumerate() {
+        for (x, distance) in row.iter_mut().enumerate() {
+            if is_sea(x, z) {
+                *distance = 0;
+                queue.push_back((x, z));
+            }
+        }
+    }
+    while let Some((x, z)) = queue.pop_front() {
+        let distance = sea_distance[z][x];
+        if distance >= CLIFF_SEA_REACH {
+            continue;
+        }
+        let neighbours = [
+            (x.wrapping_sub(1), z),
+            (x + 1, z),
+            (x, z.wrapping_sub(1)),
+            (x, z + 1),
+        ];
+        for (nx, nz) in neighbours {
+            if nx < w && nz < h && sea_distance[nz][nx] == usize::MAX {
+                sea_distance[nz][nx] = distance + 1;
+                queue.push_back((nx, nz));
+            }
+        }
+    }
+
+    let window = |x: usize, z: usize| {
+        let xs = x.saturating_sub(CLIFF_RUN)..(x + CLIFF_RUN + 1).min(w);
+        let zs = z.saturating_sub(CLIFF_RUN)..(z + CLIFF_RUN + 1).min(h);
+        zs.flat_map(move |z| xs.clone().map(move |x| (x, z)))
+    };
+    let mut mask = vec![vec![false; w]; h];
+    let mut found = false;
+    for (z, (row, distances)) in mask.iter_mut().zip(&sea_distance).enumerate() {
+        for (x, (cliff, &distance)) in row.iter_mut().zip(distances).enumerate() {
+            if distance == 0 || distance > CLIFF_SEA_REACH {
+                continue;
+            }
+            let lowest = window(x, z)
+                .map(|(nx, nz)| grid[nz][nx])
+                .fold(f64::MAX, f64::min);
+            if grid[z][x] - lowest > CLIFF_MIN_DROP_M {
+                *cliff = true;
+                found = true;
+            }
+        }
+    }
+    if !found {
+        return None;
+    }
+
+    let mut unsmoothed = Vec::new();
+    let mut kept = vec![vec![false; w]; h];
+    for (z, row) in mask.iter().enumerate() {
+        for x in (0..w).filter(|&x| row[x]) {
+            for (nx, nz) in window(x, z) {
+                if !kept[nz][nx] {
+                    kept[nz][nx] = true;
+                    unsmoothed.push((nx, nz, grid[nz][nx]));
+                }
+            }
+        }
+    }
+    Some((mask, unsmoothed))
+}
+
+/// DHM marks cells without coverage with this value (or anything below it).
+const DHM_NODATA: f64 = -9999.0;
+
+/// A single-band raster decoded from a GeoTIFF, stored row-major. Heights are kept as
+/// f32 (millimetre precision at Danish elevations) to halve the memory of large rasters.
+pub(crate) struct DecodedRaster {
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f32>,
+}
+
+/// GDAL_NODATA TIFF tag (ASCII), written by GDAL and most DEM exports.
+const GDAL_NODATA_TAG: u16 = 42113;
+
+/// Converts the samples of one decoded strip or tile to f32.
+fn chunk_samples(data: tiff::decoder::DecodingResult) -> Vec<f32> {
+    use tiff::decoder::DecodingResult;
+
+    match data {
+        DecodingResult::F16(data) => data.into_iter().map(f32::from).collect(),
+        DecodingResult::F32(data) => data,
+        DecodingResult::F64(data) => data.iter().map(|&v| v as f32).collect(),
+        DecodingResult::U8(data) => data.iter().map(|&v| v as f32).collect(),
+        DecodingResult::U16(data) => data.iter().map(|&v| v as f32).collect(),
+        DecodingResult::U32(data) => data.iter().map(|&v| v as f32).collect(),
+        DecodingResult::U64(data) => data.iter().map(|&v| v as f32).collect(),
+        DecodingResult::I8(data) => data.iter().map(|&v| v as f32).collect(),
+        DecodingResult::I16(data) => data.iter().map(|&v| v as f32).collect(),
+        DecodingResult::I32(data) => data.iter().map(|&v| v as f32).collect(),
+        DecodingResult::I64(data) => data.iter().map(|&v| v as f32).collect(),
+    }
+}
+
+/// Decodes the first band of a GeoTIFF into f32 values.
+///
+/// The image is decoded one strip or tile at a time straight into the output raster, so
+/// peak memory stays close to the size of the raster itself instead of holding a full
+/// decoded copy of every band next to it. Strip and tile layouts as well as the common
+/// compressions (Deflate, LZW, PackBits, with or without predictor) are handled by the
+/// `tiff` decoder. Multi-band rasters are reduced to their first band, and pixels matching
+/// the file's GDAL_NODATA value become NaN.
+pub(crate) fn decode_geotiff(bytes: &[u8]) -> Result<DecodedRaster, String> {
+    decode_geotiff_with_progress(bytes, |_, _| {})
+}
+
+/// [`decode_geotiff`], calling `progress(done, total)` after every decoded strip or tile.
+pub(crate) fn decode_geotiff_with_progress(
+    bytes: &[u8],
+    mut progress: impl FnMut(usize, usize),
+) -> Result<DecodedRaster, String> {
+    use tiff::tags::Tag;
+
+    let cursor = std::io::Cursor::new(bytes);
+    let mut decoder = tiff::decoder::Decoder::new(cursor)
+        .map_err(|e| format!("Failed to decode GeoTIFF: {e}"))?;
+
+    let (tiff_width, tiff_height) = decoder
+        .dimensions()
+        .map_err(|e| format!("Failed to read TIFF dimensions: {e}"))?;
+    let (width, height) = (tiff_width as usize, tiff_height as usize);
+
+    let nodata: Option<f32> = decoder
+        .get_tag_ascii_string(Tag::Unknown(GDAL_NODATA_TAG))
+        .ok()
+        .and_then(|s| {
+            s.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+                .parse()
+                .ok()
+        });
+
+    // Chunks are numbered row by row; for band-separate files the first band's chunks
+    // come first, so only those are read.
+    let (chunk_width, chunk_height) = decoder.chunk_dimensions();
+    let (chunk_width, chunk_height) = (chunk_width.max(1) as usize, chunk_height.max(1) as usize);
+    let chunks_across = width.div_ceil(chunk_width);
+    let chunks_down = height.div_ceil(chunk_height);
+
+    let total_chunks = chunks_across * chunks_down;
+
+    let mut values = vec![f32::NAN; width * height];
+    for index in 0..total_chunks {
+        let chunk = decoder
+            .read_chunk(index as u32)
+            .map_err(|e| format!("Failed to read TIFF image data: {e}"))?;
+        let (data_width, data_height) = decoder.chunk_data_dimensions(index as u32);
+        let (data_width, data_height) = (data_width as usize, data_height as usize);
+        let samples = chunk_samples(chunk);
+
+        let pixels = data_width * data_height;
+        if pixels == 0 {
+            continue;
+        }
+        if samples.len() < pixels {
+            return Err(format!(
+                "TIFF chunk {index} holds {} samples for {data_width}x{data_height} pixels",
+                samples.len()
+            ));
+        }
+        // Interleaved bands are stored per pixel; keep the first
+        let samples_per_pixel = samples.len() / pixels;
+
+        let x0 = (index % chunks_across) * chunk_width;
+        let y0 = (index / chunks_across) * chunk_height;
+        for row in 0..data_height.min(height.saturating_sub(y0)) {
+            let columns = data_width.min(width.saturating_sub(x0));
+            let out_start = (y0 + row) * width + x0;
+            for (col, out) in values[out_start..out_start + columns]
+                .iter_mut()
+                .enumerate()
+            {
+                *out = samples[(row * data_width + col) * samples_per_pixel];
+            }
+        }
+        progress(index + 1, total_chunks);
+    }
+
+    if let Some(nodata) = nodata {
+        for v in values.iter_mut().filter(|v| **v == nodata) {
+            *v = f32::NAN;
+        }
+    }
+
+    Ok(DecodedRaster {
+        width,
+        height,
+        values,
+    })
+}
+
+/// GeoKey holding the EPSG code of a projected CRS.
+const PROJECTED_CRS_GEOKEY: u16 = 3072;
+/// ETRS89 / UTM zone 32N, the CRS of DHM
+const EPSG_UTM32N: u16 = 25832;
+
+/// Axis-aligned georeferencing of a GeoTIFF: map coordinates of the outer corner of the
+/// top-left pixel, and the pixel size in map units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct GeoTransform {
+    pub origin_x: f64,
+    pub origin_y: f64,
+    pub pixel_width: f64,
+    pub pixel_height: f64,
+}
+
+/// Reads the georeferencing of a GeoTIFF from its ModelTiepoint/ModelPixelScale or
+/// ModelTransformation tags. Fails for rotated rasters and for CRSs other than EPSG:25832.
+pub(crate) fn read_georeference(bytes: &[u8]) -> Result<GeoTransform, String> {
+    use tiff::tags::Tag;
+
+    let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(bytes))
+        .map_err(|e| format!("Failed to decode GeoTIFF: {e}"))?;
+
+    if let Ok(keys) = decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag) {
+        // Header of 4 values, then (key id, location, count, value) per key
+        let epsg = keys
+            .get(4..)
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .find(|key| key[0] == PROJECTED_CRS_GEOKEY && key[1] == 0)
+            .map(|key| key[3]);
+        if let Some(epsg) = epsg.filter(|&epsg| epsg != EPSG_UTM32N) {
+            return Err(format!(
+                "DEM uses EPSG:{epsg}, but only EPSG:{EPSG_UTM32N} (ETRS89 / UTM 32N) is supported"
+            ));
+        }
+    }
+
+    if let (Ok(tiepoint), Ok(pixel_scale)) = (
+        decoder.get_tag_f64_vec(Tag::ModelTiepointTag),
+        decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag),
+    ) {
+        if tiepoint.len() >= 6 && pixel_scale.len() >= 2 {
+            return Ok(GeoTransform {
+                origin_x: tiepoint[3] - tiepoint[0] * pixel_scale[0],
+                origin_y: tiepoint[4] + tiepoint[1] * pixel_scale[1],
+                pixel_width: pixel_scale[0],
+                pixel_height: pixel_scale[1],
+            });
+        }
+    }
+
+    if let Ok(m) = decoder.get_tag_f64_vec(Tag::ModelTransformationTag) {
+        if m.len() >= 8 {
+            if m[1] != 0.0 || m[4] != 0.0 {
+                return Err("Rotated DEM rasters are not supported".to_string());
+            }
+            return Ok(GeoTransform {
+                origin_x: m[3],
+                origin_y: m[7],
+                pixel_width: m[0],
+                pixel_height: -m[5],
+            });
+        }
+    }
+
+    Err("DEM has no georeferencing (ModelTiepoint/ModelPixelScale tags)".to_string())
+}
+
+/// Cuts the pixel window covering a UTM bbox (min_e, min_n, max_e, max_n) out of a
+/// georeferenced raster. Parts of the bbox outside the raster become NaN.
+pub(crate) fn crop_to_utm_bbox(
+    raster: &DecodedRaster,
+    georef: &GeoTransform,
+    (min_e, min_n, max_e, max_n): (f64, f64, f64, f64),
+) -> Result<DecodedRaster, String> {
+    let x0 = ((min_e - georef.origin_x) / georef.pixel_width).floor() as i64;
+    let x1 = ((max_e - georef.origin_x) / georef.pixel_width).ceil() as i64;
+    let y0 = ((georef.origin_y - max_n) / georef.pixel_height).floor() as i64;
+    let y1 = ((georef.origin_y - min_n) / georef.pixel_height).ceil() as i64;
+
+    let (raster_w, raster_h) = (raster.width as i64, raster.height as i64);
+    if x1 <= 0 || y1 <= 0 || x0 >= raster_w || y0 >= raster_h || x1 <= x0 || y1 <= y0 {
+        return Err("DEM does not cover the selected area".to_string());
+    }
+
+    let width = (x1 - x0) as usize;
+    let height = (y1 - y0) as usize;
+    let mut values = vec![f32::NAN; width * height];
+    for y in y0.max(0)..y1.min(raster_h) {
+        for x in x0.max(0)..x1.min(raster_w) {
+            values[(y - y0) as usize * width + (x - x0) as usize] =
+                raster.values[(y * raster_w + x) as usize];
+        }
+    }
+
+    Ok(DecodedRaster {
+        width,
+        height,
+        values,
     })
 }
 
+/// How failed DHM requests are retried: rounds of quick attempts with exponential backoff
+/// (2s, 4s, ...), with a longer pause between rounds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts per round
+    pub attempts: u32,
+    /// Rounds before giving up
+    pub rounds: u32,
+    /// Pause before each round after the first
+    pub round_pause: Duration,
+    /// Give up once retrying would run past this time since the first attempt
+    pub total_timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            rounds: 5,
+            round_pause: Duration::from_secs(60),
+            total_timeout: None,
+        }
+    }
+}
+
+/// What one attempt at a retried request came to
+enum Attempt<T> {
+    Done(T),
+    /// Failed in a way worth trying again, such as a timeout or a 5xx status
+    Retry(String),
+    /// Failed in a way that trying again won't fix
+    Fail(String),
+}
+
+impl RetryPolicy {
+    /// Wait before the given attempt of a round (both 1-based)
+    fn delay_before(&self, round: u32, attempt: u32) -> Duration {
+        match (round, attempt) {
+            (1, 1) => Duration::ZERO,
+            (_, 1) => self.round_pause,
+            _ => Duration::from_secs(1 << (attempt - 1).min(6)),
+        }
+    }
+
+    /// Makes attempts at a request to `service` until one is done or fails for good, waiting
+    /// between them as the policy says and stopping early when generation is cancelled.
+    fn run<T>(&self, service: &str, mut attempt: impl FnMut() -> Attempt<T>) -> Result<T, String> {
+        let started = Instant::now();
+        let mut last_err = String::new();
+        for round in 1..=self.rounds {
+            for try_number in 1..=self.attempts {
+                let delay = self.delay_before(round, try_number);
+                if let Some(limit) = self.total_timeout {
+                    if started.elapsed() + delay > limit {
+                        return Err(format!(
+                            "{service} gave up after {}s (--dhm-total-timeout): {last_err}",
+                            limit.as_secs()
+                        ));
+                    }
+                }
+
+                if round > 1 || try_number > 1 {
+                    let status = format!(
+                        "{service} request round {round}/{} attempt {try_number}/{}",
+                        self.rounds, self.attempts
+                    );
+                    println!("{status} (retrying in {}s)...", delay.as_secs());
+                    emit_gui_progress_update(12.0, &format!("{status}..."));
+                }
+                if !wait_unless_cancelled(delay) {
+                    return Err(format!("{service} download cancelled"));
+                }
+
+                match attempt() {
+                    Attempt::Done(value) => return Ok(value),
+                    Attempt::Retry(e) => last_err = e,
+                    Attempt::Fail(e) => return Err(e),
+                }
+            }
+        }
+        if cancel_requested() {
+            return Err(format!("{service} download cancelled"));
+        }
+        Err(format!(
+            "{service} failed after {} rounds of {} attempts: {last_err}",
+            self.rounds, self.attempts
+        ))
+    }
+}
+
+/// Gaussian sigma (grid cells) for smoothing the resampled terrain. The blur widens with the
+/// grid because a single capped request upsamples ever coarser pixels; tiled level-of-detail
+/// rasters keep their pixel size, so their blur stops growing at the size of one request
+/// instead of flattening municipality-scale builds.
+fn smoothing_sigma(grid_size: f64, level_of_detail: bool) -> f64 {
+    let size = if level_of_detail {
+        grid_size.min(MAX_REQUEST_PIXELS as f64)
+    } else {
+        grid_size
+    };
+    7.0 * (size / 100.0).sqrt()
+}
+
+/// Processing options shared by the DHM and local DEM terrain paths.
+pub struct DemOptions<'a> {
+    pub resample: ResampleMethod,
+    pub bathymetry: Option<&'a BathymetrySource>,
+    /// Turn sea-level flats connected to the open sea into water (`--carve-sea`)
+    pub carve_sea_flats: bool,
+    /// Retries of the DHM download; unused for local DEMs
+    pub retry: RetryPolicy,
+    /// DHM resolution in metres per pixel (`--dhm-resolution`), chosen from the bbox size
+    /// when `None`; unused for local DEMs
+    pub resolution: Option<f64>,
+    /// Keep which cells the DEM measured, for the coverage check (`--coverage-map`,
+    /// `--min-coverage`)
+    pub keep_measured: bool,
+}
+
+/// How raster pixels are interpolated when mapped onto the terrain grid.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ResampleMethod {
+    /// Nearest pixel; shows terracing when the raster is coarser than the grid
+    Nearest,
+    /// Weighted average of the 4 surrounding pixels
+    #[default]
+    Bilinear,
+    /// Catmull-Rom spline over the 16 surrounding pixels, sharper than bilinear
+    Bicubic,
+}
+
+impl DecodedRaster {
+    /// Pixel value, or NaN for nodata and out-of-range pixels.
+    fn pixel(&self, x: isize, y: isize) -> f64 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        match self.values.get(y * self.width + x) {
+            Some(&h) if h as f64 > DHM_NODATA => h as f64,
+            _ => f64::NAN,
+        }
+    }
+
+    /// Bilinear sample at fractional pixel coordinates, renormalized over the valid
+    /// pixels so nodata neighbours do not drag the result towards -9999.
+    fn sample_bilinear(&self, sx: f64, sy: f64) -> f64 {
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let mut sum = 0.0;
+        let mut weight_sum = 0.0;
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let h = self.pixel(x0 + dx, y0 + dy);
+            if !h.is_nan() && weight > 0.0 {
+                sum += h * weight;
+                weight_sum += weight;
+            }
+        }
+
+        if weight_sum > 0.0 {
+            sum / weight_sum
+        } else {
+            self.pixel(sx.round() as isize, sy.round() as isize)
+        }
+    }
+
+    /// Catmull-Rom bicubic sample. Falls back to bilinear next to nodata pixels.
+    fn sample_bicubic(&self, sx: f64, sy: f64) -> f64 {
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let mut rows = [0.0; 4];
+        for (row, dy) in rows.iter_mut().zip(-1..=2) {
+            let p = [-1, 0, 1, 2].map(|dx| self.pixel(x0 + dx, y0 + dy));
+            if p.iter().any(|h| h.is_nan()) {
+                return self.sample_bilinear(sx, sy);
+            }
+            *row = catmull_rom(p, fx);
+        }
+        catmull_rom(rows, fy)
+    }
+}
+
+fn catmull_rom(p: [f64; 4], t: f64) -> f64 {
+    0.5 * (2.0 * p[1]
+        + (-p[0] + p[2]) * t
+        + (2.0 * p[0] - 5.0 * p[1] + 4.0 * p[2] - p[3]) * t * t
+        + (-p[0] + 3.0 * p[1] - 3.0 * p[2] + p[3]) * t * t * t)
+}
+
+/// Maps raster pixels onto a grid of the given size. Nodata and missing pixels become NaN.
+pub(crate) fn resample_raster_to_grid(
+    raster: &DecodedRaster,
+    grid_width: usize,
+    grid_height: usize,
+    method: ResampleMethod,
+) -> Vec<Vec<f64>> {
+    let mut grid: Vec<Vec<f64>> = vec![vec![f64::NAN; grid_width]; grid_height];
+    if raster.width == 0 || raster.height == 0 {
+        return grid;
+    }
+
+    let scale_x = raster.width as f64 / grid_width as f64;
+    let scale_z = raster.height as f64 / grid_height as f64;
+
+    grid.par_iter_mut().enumerate().for_each(|(gz, row)| {
+        for (gx, cell) in row.iter_mut().enumerate() {
+            *cell = match method {
+                ResampleMethod::Nearest => raster.pixel(
+                    (gx as f64 * scale_x) as isize,
+                    (gz as f64 * scale_z) as isize,
+                ),
+                // Align pixel centers: grid cell center -> raster pixel space
+                ResampleMethod::Bilinear => raster.sample_bilinear(
+                    (gx as f64 + 0.5) * scale_x - 0.5,
+                    (gz as f64 + 0.5) * scale_z - 0.5,
+                ),
+                ResampleMethod::Bicubic => raster.sample_bicubic(
+                    (gx as f64 + 0.5) * scale_x - 0.5,
+                    (gz as f64 + 0.5) * scale_z - 0.5,
+                ),
+            };
+        }
+    });
+
+    grid
+}
+
+/// Nodata regions whose valid border averages at or below this height (meters) and that
+/// touch the grid edge are assumed to be open sea rather than missing land coverage.
+const NODATA_SEA_BORDER_MAX_M: f64 = 1.0;
+/// Maximum number of border samples used for inverse-distance interpolation per region.
+const NODATA_IDW_MAX_SAMPLES: usize = 64;
+
+/// Fills NaN (nodata) cells in place.
+///
+/// Each 4-connected nodata region is classified on its own: regions touching the grid
+/// edge whose valid neighbours sit at sea level are filled with 0.0 (sea), everything
+/// else is interpolated by inverse distance weighting from the valid cells bordering
+/// the region. Returns (interpolated cells, sea cells).
+fn fill_nodata_cells(grid: &mut [Vec<f64>]) -> (usize, usize) {
+    let h = grid.len();
+    if h == 0 {
+        return (0, 0);
+    }
+    let w = grid[0].len();
+
+    let mut visited = vec![vec![false; w]; h];
+    let mut filled_land = 0;
+    let mut filled_sea = 0;
+    let mut any_valid = false;
+
+    for y in 0..h {
+        for x in 0..w {
+            if !grid[y][x].is_nan() {
+                any_valid = true;
+                continue;
+            }
+            if visited[y][x] {
+                continue;
+            }
+
+            // Flood the nodata region and collect its valid border cells.
+            let mut region: Vec<(usize, usize)> = Vec::new();
+            let mut border: Vec<(usize, usize, f64)> = Vec::new();
+            let mut touches_edge = false;
+            let mut stack = vec![(x, y)];
+            visited[y][x] = true;
+
+            while let Some((cx, cy)) = stack.pop() {
+                region.push((cx, cy));
+                if cx == 0 || cy == 0 || cx == w - 1 || cy == h - 1 {
+                    touches_edge = true;
+                }
+
+                let neighbours = [
+                    (cx.wrapping_sub(1), cy),
+                    (cx + 1, cy),
+                    (cx, cy.wrapping_sub(1)),
+                    (cx, cy + 1),
+                ];
+                for (nx, ny) in neighbours {
+                    if nx >= w || ny >= h {
+                        continue;
+                    }
+                    let value = grid[ny][nx];
+                    if value.is_nan() {
+                        if !visited[ny][nx] {
+                            visited[ny][nx] = true;
+                            stack.push((nx, ny));
+                        }
+                    } else {
+                        border.push((nx, ny, value));
+                    }
+                }
+            }
+
+            if border.is_empty() {
+                // Nothing to interpolate from yet; handled below if the whole grid is empty.
+                continue;
+            }
+
+            let border_mean = border.iter().map(|b| b.2).sum::<f64>() / border.len() as f64;
+            if touches_edge && border_mean <= NODATA_SEA_BORDER_MAX_M {
+                for &(rx, ry) in &region {
+                    grid[ry][rx] = 0.0;
+                }
+                filled_sea += region.len();
+                continue;
+            }
+
+            let step = border.len().div_ceil(NODATA_IDW_MAX_SAMPLES);
+            let samples: Vec<(usize, usize, f64)> = border.into_iter().step_by(step).collect();
+            for &(rx, ry) in &region {
+                let mut weighted = 0.0;
+                let mut weight_sum = 0.0;
+                for &(sx, sy, value) in &samples {
+                    let dx = rx as f64 - sx as f64;
+                    let dy = ry as f64 - sy as f64;
+                    let weight = 1.0 / (dx * dx + dy * dy);
+                    weighted += value * weight;
+                    weight_sum += weight;
+                }
+                grid[ry][rx] = weighted / weight_sum;
+            }
+            filled_land += region.len();
+        }
+    }
+
+    if !any_valid {
+        // No coverage at all: keep the previous flat sea-level behaviour.
+        for row in grid.iter_mut() {
+            row.fill(0.0);
+        }
+        filled_sea = w * h;
+    }
+
+    (filled_land, filled_sea)
+}
+
 fn print_dhm_auth_debug(token: &str, url: &str) {
     let trimmed = token.trim();
     let leading_or_trailing_whitespace = trimmed.len() != token.len();
@@ -345,140 +1488,299 @@ fn redact_dhm_token(url: &str) -> String {
     }
 }
 
-fn emit_dhm_smoothing_progress(
-    completed_units: usize,
-    total_units: usize,
-    last_bucket: &AtomicUsize,
-    progress_bar: Option<&ProgressBar>,
-) {
-    if total_units == 0 {
-        return;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain_pipeline::TerrainPass;
+
+    #[test]
+    fn utm_conversion_round_trips() {
+        // Aarhus and Bornholm, the latter well east of the zone's central meridian
+        for (lat, lon) in [(56.1572, 10.2107), (55.1037, 14.7065)] {
+            let (easting, northing) = wgs84_to_utm32n(lat, lon);
+            let (back_lat, back_lon) = utm32n_to_wgs84(easting, northing);
+            assert!((back_lat - lat).abs() < 1e-5 && (back_lon - lon).abs() < 1e-5);
+        }
     }
 
-    if let Some(progress_bar) = progress_bar {
-        progress_bar.set_position(completed_units as u64);
-    }
-
-    let ratio = completed_units as f64 / total_units as f64;
-    let percent = (ratio * 100.0).round().clamp(0.0, 100.0) as usize;
-    let bucket = percent / 2;
-    let previous = last_bucket.load(Ordering::Relaxed);
-
-    if bucket > previous
-        && last_bucket
-            .compare_exchange(previous, bucket, Ordering::Relaxed, Ordering::Relaxed)
-            .is_ok()
-    {
-        let gui_progress = 16.0 + ratio * 3.5;
-        emit_gui_progress_update(
-            gui_progress,
-            &format!("Smoothing DHM terrain... {percent}%"),
+    #[test]
+    fn retry_policy_backs_off_within_and_pauses_between_rounds() {
+        let retry = RetryPolicy {
+            round_pause: Duration::from_secs(10),
+            ..RetryPolicy::default()
+        };
+        let delays: Vec<u64> = [(1, 1), (1, 2), (1, 3), (2, 1), (2, 2)]
+            .iter()
+            .map(|&(round, attempt)| retry.delay_before(round, attempt).as_secs())
+            .collect();
+        assert_eq!(delays, vec![0, 2, 4, 10, 2]);
+    }
+
+    #[test]
+    fn requests_stop_at_the_first_answer_or_lasting_failure() {
+        let once = RetryPolicy {
+            attempts: 1,
+            rounds: 1,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(once.run("DHM WCS", || Attempt::Done(7)), Ok(7));
+
+        let mut tries = 0;
+        let failed = RetryPolicy::default().run::<()>("DHM WCS", || {
+            tries += 1;
+            Attempt::Fail("status 403".to_string())
+        });
+        assert_eq!(failed, Err("status 403".to_string()));
+        assert_eq!(tries, 1);
+
+        let exhausted = once.run::<()>("ArcticDEM", || Attempt::Retry("status 503".to_string()));
+        assert_eq!(
+            exhausted,
+            Err("ArcticDEM failed after 1 rounds of 1 attempts: status 503".to_string())
+        );
+    }
+
+    #[test]
+    fn crop_cuts_the_bbox_window_and_pads_outside_coverage() {
+        // 4x3 raster of 10m pixels, top-left corner at (1000, 2000)
+        let raster = DecodedRaster {
+            width: 4,
+            height: 3,
+            values: (0..12).map(|v| v as f32).collect(),
+        };
+        let georef = GeoTransform {
+            origin_x: 1000.0,
+            origin_y: 2000.0,
+            pixel_width: 10.0,
+            pixel_height: 10.0,
+        };
+
+        let crop = crop_to_utm_bbox(&raster, &georef, (1010.0, 1980.0, 1030.0, 2000.0)).unwrap();
+        assert_eq!((crop.width, crop.height), (2, 2));
+        assert_eq!(crop.values, vec![1.0, 2.0, 5.0, 6.0]);
+
+        // Extends 1 pixel past the right edge
+        let crop = crop_to_utm_bbox(&raster, &georef, (1030.0, 1990.0, 1050.0, 2000.0)).unwrap();
+        assert_eq!(crop.values[0], 3.0);
+        assert!(crop.values[1].is_nan());
+
+        assert!(crop_to_utm_bbox(&raster, &georef, (0.0, 0.0, 10.0, 10.0)).is_err());
+    }
+
+    #[test]
+    fn only_sea_flats_open_to_the_edge_are_carved() {
+        // A harbour reaching the west edge and an enclosed low polder behind a dike
+        let grid = vec![
+            vec![0.0, 0.1, 2.0, 2.0, 2.0, 2.0],
+            vec![0.0, 0.2, 0.1, 2.0, -1.0, 2.0],
+            vec![5.0, 5.0, 5.0, 2.0, 2.0, 2.0],
+        ];
+        let mask = sea_flat_mask(&grid);
+        assert_eq!(
+            mask,
+            vec![
+                vec![true, true, false, false, false, false],
+                vec![true, true, true, false, false, false],
+                vec![false, false, false, false, false, false],
+            ]
         );
+    }
 
-        if !is_running_with_gui() && percent > 0 && percent < 100 && percent.is_multiple_of(10) {
-            println!("Smoothing DHM terrain... {percent}%");
+    #[test]
+    fn decodes_tiled_deflate_geotiff() {
+        let raster = decode_geotiff(include_bytes!("../tests/dhm/tiled_deflate_f32.tif")).unwrap();
+        assert_eq!((raster.width, raster.height), (20, 18));
+        assert_eq!(raster.values.len(), 20 * 18);
+        // Pixels in the second tile column and row, where edge tiles are padded
+        for (x, y) in [(0, 0), (17, 3), (5, 16), (19, 17)] {
+            let expected = 0.5 * x as f32 + 0.25 * y as f32;
+            assert_eq!(raster.values[y * 20 + x], expected, "pixel {x},{y}");
         }
     }
-}
 
-fn dhm_gaussian_blur(grid: &[Vec<f64>], sigma: f64) -> Vec<Vec<f64>> {
-    let h = grid.len();
-    if h == 0 {
-        return vec![];
+    #[test]
+    fn decodes_lzw_strips_with_predictor_and_gdal_nodata() {
+        let raster =
+            decode_geotiff(include_bytes!("../tests/dhm/strips_lzw_predictor_i16.tif")).unwrap();
+        assert_eq!((raster.width, raster.height), (12, 10));
+        assert!(raster.values[0].is_nan());
+        // Rows from the second and last strip
+        assert_eq!(raster.values[5 * 12 + 7], (7 * 2 + 5 * 3) as f32);
+        assert_eq!(raster.values[9 * 12 + 11], (11 * 2 + 9 * 3) as f32);
     }
-    let w = grid[0].len();
 
-    let radius = (sigma * 3.0).ceil() as usize;
-    let kernel_size = radius * 2 + 1;
-    let mut kernel = vec![0.0f64; kernel_size];
-    let mut sum = 0.0;
-    for i in 0..kernel_size {
-        let x = i as f64 - radius as f64;
-        let val = (-x * x / (2.0 * sigma * sigma)).exp();
-        kernel[i] = val;
-        sum += val;
+    #[test]
+    fn decodes_first_band_of_interleaved_strips() {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        // 64x200 RGB image; the encoder splits it into ~8 KB strips
+        let (width, height) = (64u32, 200u32);
+        let data: Vec<u8> = (0..width * height)
+            .flat_map(|i| [i as u8, 200, 201])
+            .collect();
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        TiffEncoder::new(&mut bytes)
+            .unwrap()
+            .write_image::<colortype::RGB8>(width, height, &data)
+            .unwrap();
+
+        let raster = decode_geotiff(bytes.get_ref()).unwrap();
+        assert_eq!((raster.width, raster.height), (64, 200));
+        let expected: Vec<f32> = (0..width * height).map(|i| i as u8 as f32).collect();
+        assert_eq!(raster.values, expected);
     }
-    for value in &mut kernel {
-        *value /= sum;
+
+    /// 4x1 raster rising 10m per pixel towards +X.
+    fn ramp_raster() -> DecodedRaster {
+        DecodedRaster {
+            width: 4,
+            height: 1,
+            values: vec![0.0, 10.0, 20.0, 30.0],
+        }
     }
 
-    let total_units = h + w;
-    let completed_units = AtomicUsize::new(0);
-    let last_progress_bucket = AtomicUsize::new(0);
-    let progress_bar = if is_running_with_gui() {
-        None
-    } else {
-        let progress_bar = ProgressBar::new(total_units as u64);
-        progress_bar.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:45.cyan/blue}] {pos}/{len} smoothing units ({percent}%)")
-                .unwrap()
-                .progress_chars("#> "),
+    #[test]
+    fn nearest_resampling_terraces_and_bilinear_does_not() {
+        let raster = ramp_raster();
+
+        let nearest = resample_raster_to_grid(&raster, 8, 1, ResampleMethod::Nearest);
+        assert_eq!(
+            nearest[0],
+            vec![0.0, 0.0, 10.0, 10.0, 20.0, 20.0, 30.0, 30.0]
         );
-        progress_bar.set_message("Smoothing DHM terrain");
-        Some(progress_bar)
-    };
 
-    let temp: Vec<Vec<f64>> = grid
-        .par_iter()
-        .map(|row| {
-            let mut temp_row = vec![0.0f64; w];
-            for (x, value) in temp_row.iter_mut().enumerate() {
-                let mut acc = 0.0;
-                for (k, kernel_value) in kernel.iter().enumerate() {
-                    let sx = (x as isize + k as isize - radius as isize).clamp(0, w as isize - 1)
-                        as usize;
-                    acc += row[sx] * kernel_value;
-                }
-                *value = acc;
-            }
+        let bilinear = resample_raster_to_grid(&raster, 8, 1, ResampleMethod::Bilinear);
+        assert_eq!(bilinear[0][2], 7.5);
+        assert_eq!(bilinear[0][3], 12.5);
+        assert!(bilinear[0].windows(2).all(|w| w[0] <= w[1]));
 
-            let completed = completed_units.fetch_add(1, Ordering::Relaxed) + 1;
-            emit_dhm_smoothing_progress(
-                completed,
-                total_units,
-                &last_progress_bucket,
-                progress_bar.as_ref(),
-            );
-            temp_row
-        })
-        .collect();
+        // Catmull-Rom reproduces linear data exactly away from the edges
+        let bicubic = resample_raster_to_grid(&raster, 8, 1, ResampleMethod::Bicubic);
+        assert!((bicubic[0][3] - 12.5).abs() < 1e-9);
+    }
 
-    let blurred_columns: Vec<Vec<f64>> = (0..w)
-        .into_par_iter()
-        .map(|x| {
-            let mut blurred_column = vec![0.0f64; h];
-            for (y, value) in blurred_column.iter_mut().enumerate() {
-                let mut acc = 0.0;
-                for (k, kernel_value) in kernel.iter().enumerate() {
-                    let sy = (y as isize + k as isize - radius as isize).clamp(0, h as isize - 1)
-                        as usize;
-                    acc += temp[sy][x] * kernel_value;
-                }
-                *value = acc;
-            }
+    #[test]
+    fn bilinear_ignores_nodata_neighbours() {
+        let raster = DecodedRaster {
+            width: 2,
+            height: 1,
+            values: vec![5.0, DHM_NODATA as f32],
+        };
+        let grid = resample_raster_to_grid(&raster, 4, 1, ResampleMethod::Bilinear);
+        assert_eq!(grid[0][..3], [5.0, 5.0, 5.0]);
+        assert!(grid[0][3].is_nan());
+    }
 
-            let completed = completed_units.fetch_add(1, Ordering::Relaxed) + 1;
-            emit_dhm_smoothing_progress(
-                completed,
-                total_units,
-                &last_progress_bucket,
-                progress_bar.as_ref(),
-            );
-            blurred_column
-        })
-        .collect();
+    #[test]
+    fn inland_nodata_is_interpolated_from_neighbours() {
+        let mut grid = vec![vec![20.0; 5]; 5];
+        grid[2][2] = f64::NAN;
+        grid[2][3] = f64::NAN;
 
-    if let Some(progress_bar) = &progress_bar {
-        progress_bar.finish_with_message("Smoothing DHM terrain... done");
+        let (land, sea) = fill_nodata_cells(&mut grid);
+
+        assert_eq!((land, sea), (2, 0));
+        assert!((grid[2][2] - 20.0).abs() < 1e-9);
+        assert!((grid[2][3] - 20.0).abs() < 1e-9);
     }
 
-    let mut result = vec![vec![0.0f64; w]; h];
-    for (x, column) in blurred_columns.into_iter().enumerate() {
-        for (y, value) in column.into_iter().enumerate() {
-            result[y][x] = value;
+    #[test]
+    fn edge_nodata_next_to_sea_level_becomes_sea() {
+        let mut grid = vec![vec![0.2, 0.3, 5.0, 8.0]; 3];
+        for row in grid.iter_mut() {
+            row[0] = f64::NAN;
         }
+
+        let (land, sea) = fill_nodata_cells(&mut grid);
+
+        assert_eq!((land, sea), (0, 3));
+        assert!(grid.iter().all(|row| row[0] == 0.0));
     }
 
-    result
+    #[test]
+    fn edge_nodata_next_to_high_ground_is_not_sea() {
+        let mut grid = vec![vec![30.0, 31.0, 32.0]; 3];
+        for row in grid.iter_mut() {
+            row[2] = f64::NAN;
+        }
+
+        let (land, sea) = fill_nodata_cells(&mut grid);
+
+        assert_eq!((land, sea), (3, 0));
+        assert!(grid.iter().all(|row| row[2] > 29.0));
+    }
+
+    #[test]
+    fn large_bboxes_get_a_coarser_resolution() {
+        // Fits one request: one pixel per block as before
+        assert_eq!(auto_resolution((1500.0, 1200.0), 1500, 1.0), None);
+        // Kilometre-scale bbox: 1.6m stays within the pixel budget
+        assert_eq!(auto_resolution((5000.0, 4000.0), 5000, 1.0), Some(1.6));
+        // Municipality-scale bbox falls back to 3.2m
+        assert_eq!(auto_resolution((20000.0, 15000.0), 20000, 1.0), Some(3.2));
+        // Never finer than a block at small scales
+        assert_eq!(auto_resolution((20000.0, 15000.0), 5000, 4.0), Some(4.0));
+    }
+
+    #[test]
+    fn raster_tiles_cover_the_raster_within_the_request_cap() {
+        let tiles = raster_tiles(5000, 2048);
+        assert_eq!(
+            tiles,
+            vec![
+                (0, 0, 2048, 2048),
+                (2048, 0, 2048, 2048),
+                (4096, 0, 904, 2048)
+            ]
+        );
+        assert_eq!(raster_tiles(800, 600), vec![(0, 0, 800, 600)]);
+    }
+
+    #[test]
+    fn sheer_drops_to_the_sea_are_kept_as_cliffs() {
+        // Sea, a narrow beach and a 30m chalk plateau
+        let grid: Vec<Vec<f64>> = (0..10)
+            .map(|_| {
+                (0..40)
+                    .map(|x| match x {
+                        0..=4 => 0.0,
+                        5..=7 => 1.0,
+                        _ => 30.0,
+                    })
+                    .collect()
+            })
+            .collect();
+        let (mask, unsmoothed) = coastal_cliff_mask(&grid, &sea_flat_mask(&grid)).expect("cliff");
+        assert!(mask[5][8]);
+        assert!(!mask[5][2], "the sea is not a cliff");
+        assert!(!mask[5][35], "too far inland");
+
+        let mut smoothed = vec![vec![15.0; 40]; 10];
+        RestoreUnsmoothed {
+            cells: unsmoothed,
+            blend: 4,
+        }
+        .apply(&mut smoothed);
+        assert_eq!(smoothed[5][8], 30.0);
+        assert_eq!(smoothed[5][6], 1.0);
+
+        let gentle: Vec<Vec<f64>> = (0..10)
+            .map(|_| (0..40).map(|x| x as f64 * 0.5).collect())
+            .collect();
+        assert!(coastal_cliff_mask(&gentle, &sea_flat_mask(&gentle)).is_none());
+
+        // A polder below sea level behind a dike is not sea, so no cliff rises from it
+        let polder: Vec<Vec<f64>> = (0..10)
+            .map(|_| {
+                (0..40)
+                    .map(|x| match x {
+                        0 => 2.0,
+                        1..=4 => -2.0,
+                        _ => 30.0,
+                    })
+                    .collect()
+            })
+            .collect();
+        assert!(coastal_cliff_mask(&polder, &sea_flat_mask(&polder)).is_none());
+    }
 }
@@ -0,0 +1,157 @@
+//! Processing of accessibility features (`--accessibility`).
+//!
+//! This module renders an accessibility layer on top of the regular generation:
+//! - `tactile_paving=yes` on kerbs - yellow tactile strips, on kerb nodes and kerb ways
+//! - `entrance=*` + `wheelchair=yes` - slab ramps where the ground drops one block at the door
+//! - `amenity=parking_space` + `parking_space=disabled` - blue bays with a white marker
+
+use crate::args::Args;
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::floodfill_cache::FloodFillCache;
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
+use crate::world_editor::WorldEditor;
+
+/// Block used for tactile paving strips
+const TACTILE_PAVING: Block = YELLOW_CONCRETE;
+/// Paving blocks placed by other elements that the accessibility layer may replace
+const REPLACEABLE_PAVING: [Block; 6] = [
+    GRAY_CONCRETE,
+    LIGHT_GRAY_CONCRETE,
+    BLACK_CONCRETE,
+    SMOOTH_STONE,
+    STONE_BRICKS,
+    GRASS_BLOCK,
+];
+
+const NEIGHBOURS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+pub fn generate_accessibility(
+    editor: &mut WorldEditor,
+    element: &ProcessedElement,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+) {
+    let tags = element.tags();
+
+    if tags.get("tactile_paving").is_some_and(|v| v == "yes") && is_kerb(element) {
+        match element {
+            ProcessedElement::Node(node) => generate_tactile_node(editor, node),
+            ProcessedElement::Way(way) => generate_tactile_way(editor, way),
+            ProcessedElement::Relation(_) => {}
+        }
+    }
+
+    if let ProcessedElement::Node(node) = element {
+        if tags.contains_key("entrance") && tags.get("wheelchair").is_some_and(|v| v == "yes") {
+            generate_entrance_ramp(editor, node);
+        }
+    }
+
+    if tags.get("amenity").is_some_and(|v| v == "parking_space")
+        && tags.get("parking_space").is_some_and(|v| v == "disabled")
+    {
+        match element {
+            ProcessedElement::Node(node) => generate_disabled_bay_node(editor, node),
+            ProcessedElement::Way(way) => {
+                generate_disabled_bay_area(editor, way, args, flood_fill_cache)
+            }
+            ProcessedElement::Relation(_) => {}
+        }
+    }
+}
+
+fn is_kerb(element: &ProcessedElement) -> bool {
+    let tags = element.tags();
+    tags.get("barrier").is_some_and(|v| v == "kerb") || tags.contains_key("kerb")
+}
+
+/// Tactile paving around a kerb node (e.g. a lowered kerb at a crossing)
+fn generate_tactile_node(editor: &mut WorldEditor, node: &ProcessedNode) {
+    editor.set_block(TACTILE_PAVING, node.x, 0, node.z, None, Some(&[WATER]));
+    for (dx, dz) in NEIGHBOURS {
+        editor.set_block(
+            TACTILE_PAVING,
+            node.x + dx,
+            0,
+            node.z + dz,
+            Some(&REPLACEABLE_PAVING),
+            None,
+        );
+    }
+}
+
+/// Tactile paving strip along a kerb way
+fn generate_tactile_way(editor: &mut WorldEditor, way: &ProcessedWay) {
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            editor.set_block(TACTILE_PAVING, x, 0, z, None, Some(&[WATER]));
+        }
+    }
+}
+
+/// Fills a one-block drop in front of an accessible entrance with a slab, so the door
+/// can be reached without a step.
+fn generate_entrance_ramp(editor: &mut WorldEditor, node: &ProcessedNode) {
+    let door_y = editor.get_ground_level(node.x, node.z);
+    for (dx, dz) in NEIGHBOURS {
+        let (x, z) = (node.x + dx, node.z + dz);
+        if editor.get_ground_level(x, z) == door_y - 1 {
+            editor.set_block_absolute(SMOOTH_STONE_SLAB, x, door_y, z, None, None);
+        }
+    }
+}
+
+/// A single disabled bay mapped as a node: 3x5 blue bay with a white centre marker
+fn generate_disabled_bay_node(editor: &mut WorldEditor, node: &ProcessedNode) {
+    for dx in -1..=1 {
+        for dz in -2..=2 {
+            editor.set_block(
+                BLUE_CONCRETE,
+                node.x + dx,
+                0,
+                node.z + dz,
+                Some(&REPLACEABLE_PAVING),
+                None,
+            );
+        }
+    }
+    editor.set_block(
+        WHITE_CONCRETE,
+        node.x,
+        0,
+        node.z,
+        Some(&[BLUE_CONCRETE]),
+        None,
+    );
+}
+
+/// A disabled bay mapped as an area: blue surface with a white marker at its centre
+fn generate_disabled_bay_area(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+) {
+    let area = flood_fill_cache.get_or_compute(way, args.timeout.as_ref());
+    if area.is_empty() {
+        return;
+    }
+
+    for &(x, z) in &area {
+        editor.set_block(BLUE_CONCRETE, x, 0, z, Some(&REPLACEABLE_PAVING), None);
+    }
+
+    let count = area.len() as i64;
+    let (sum_x, sum_z) = area.iter().fold((0i64, 0i64), |(sx, sz), &(x, z)| {
+        (sx + x as i64, sz + z as i64)
+    });
+    editor.set_block(
+        WHITE_CONCRETE,
+        (sum_x / count) as i32,
+        0,
+        (sum_z / count) as i32,
+        Some(&[BLUE_CONCRETE]),
+        None,
+    );
+}
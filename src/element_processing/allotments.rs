@@ -0,0 +1,444 @@
+//! Danish allotment gardens (kolonihaver).
+//!
+//! An allotment site is a grid of small hedged plots in rows along narrow gravel paths.
+//! Each plot has a gate in its front hedge onto the path, a garden path lined with flowers
+//! up to a tiny painted hut at the back, a patch of vegetable beds beside the hut and lawn
+//! on the rest. The layout is on a fixed grid, so sites split across tiles line up.
+//!
+//! Where the plots are mapped (`allotments=plot`), they are laid out from their own outlines
+//! instead and the grid only fills the rest of the site. A plot with a mapped hut or shed
+//! keeps it and gets no generated hut.
+
+use crate::args::Args;
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::deterministic_rng::coord_rng;
+use crate::element_processing::plot_grid::{lay_ground, PlotGrid};
+use crate::floodfill_cache::{BuildingFootprintBitmap, CoordinateBitmap, FloodFillCache};
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use rand::prelude::IndexedRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// Width of the paths between the rows of plots
+const PATH_WIDTH: i32 = 2;
+/// Width of a plot along its path, including the hedge on one side
+const PLOT_WIDTH: i32 = 12;
+/// Depth of a plot from the front hedge to the back hedge, both included
+const PLOT_DEPTH: i32 = 14;
+/// Plots in rows, each row behind its path
+const PLOTS: PlotGrid = PlotGrid::new((PLOT_WIDTH, PATH_WIDTH + PLOT_DEPTH), (0, 0));
+/// Width and depth of a hut, walls included
+const HUT_WIDTH: i32 = 4;
+const HUT_DEPTH: i32 = 3;
+/// Clipped hedge around the plots
+const HEDGE: Block = OAK_LEAVES;
+/// Paths, gates and the terrace in front of the hut
+const PATH: Block = GRAVEL;
+/// Paint of the huts
+const HUT_COLOURS: [Block; 5] = [
+    RED_TERRACOTTA,
+    WHITE_TERRACOTTA,
+    YELLOW_TERRACOTTA,
+    LIGHT_BLUE_TERRACOTTA,
+    SPRUCE_PLANKS,
+];
+/// Flowers along the garden paths
+const FLOWERS: [Block; 4] = [RED_FLOWER, WHITE_FLOWER, YELLOW_FLOWER, BLUE_FLOWER];
+/// Vegetables grown in the beds
+const VEGETABLES: [Block; 3] = [CARROTS, POTATOES, BEETROOTS];
+
+/// What a block of an allotment site is part of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot {
+    Path,
+    Hedge,
+    /// Opening in the front hedge of a plot
+    Gate,
+    /// Inside a plot, `column` blocks across from its side hedge and `depth` blocks back
+    /// from its front hedge; the plot's corner is at `plot`
+    Garden {
+        plot: (i32, i32),
+        column: i32,
+        depth: i32,
+    },
+}
+
+fn slot(x: i32, z: i32) -> Slot {
+    let cell = PLOTS.cell(x, z);
+    if cell.depth < PATH_WIDTH {
+        return Slot::Path;
+    }
+    let (column, depth) = (cell.column, cell.depth - PATH_WIDTH);
+    match depth {
+        0 if column == PLOT_WIDTH / 2 => Slot::Gate,
+        0 => Slot::Hedge,
+        _ if column == 0 || depth == PLOT_DEPTH - 1 => Slot::Hedge,
+        _ => Slot::Garden {
+            plot: (cell.plot.0, cell.plot.1 + PATH_WIDTH),
+            column,
+            depth,
+        },
+    }
+}
+
+/// What a block inside a plot is used for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Feature {
+    GardenPath,
+    Flowers,
+    Lawn,
+    Vegetables,
+    HutWall,
+    HutWindow,
+    HutDoor,
+    HutFloor,
+}
+
+/// Lays out the inside of a plot, with its hut in the left or the right back corner
+fn feature(column: i32, depth: i32, hut_left: bool) -> Feature {
+    let middle = PLOT_WIDTH / 2;
+    let hut_front = PLOT_DEPTH - 1 - HUT_DEPTH;
+    let hut_columns = if hut_left {
+        1..=HUT_WIDTH
+    } else {
+        PLOT_WIDTH - HUT_WIDTH..=PLOT_WIDTH - 1
+    };
+    // The door is in the front wall, next to the corner nearest the garden path
+    let hut_door = if hut_left {
+        HUT_WIDTH - 1
+    } else {
+        PLOT_WIDTH - HUT_WIDTH + 1
+    };
+
+    if depth >= hut_front && hut_columns.contains(&column) {
+        let corner = column == *hut_columns.start() || column == *hut_columns.end();
+        return match depth {
+            _ if depth == hut_front && column == hut_door => Feature::HutDoor,
+            _ if corner => Feature::HutWall,
+            _ if depth == hut_front => Feature::HutWindow,
+            _ if depth == PLOT_DEPTH - 2 => Feature::HutWall,
+            _ => Feature::HutFloor,
+        };
+    }
+    if depth >= hut_front {
+        return Feature::Vegetables;
+    }
+    if depth == hut_front - 1 || column == middle {
+        // Up the middle to the terrace along the front of the hut
+        return Feature::GardenPath;
+    }
+    if (column - middle).abs() == 1 {
+        return Feature::Flowers;
+    }
+    Feature::Lawn
+}
+
+/// Whether a way is a single mapped allotment plot
+pub fn is_mapped_plot(way: &ProcessedWay) -> bool {
+    way.tags.get("allotments").is_some_and(|v| v == "plot")
+}
+
+/// Whether a mapped building stands anywhere inside the grid plot with its corner at `plot`
+fn plot_has_building(footprints: &BuildingFootprintBitmap, plot: (i32, i32)) -> bool {
+    (1..PLOT_WIDTH).any(|column| {
+        (1..PLOT_DEPTH - 1).any(|depth| footprints.contains(plot.0 + column, plot.1 + depth))
+    })
+}
+
+/// Lays out an allotment site (`landuse=allotments`) in rows of hedged plots with huts
+/// along narrow gravel paths, and hedges it in with gaps where the paths run out. The
+/// mapped plots in `mapped_plots` are left to [`generate_mapped_plot`].
+pub fn generate_allotments(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+    building_footprints: &BuildingFootprintBitmap,
+    mapped_plots: &CoordinateBitmap,
+) {
+    let area = flood_fill_cache.get_or_compute(way, args.timeout.as_ref());
+    let mut built_on: HashMap<(i32, i32), bool> = HashMap::new();
+
+    for (x, z) in area {
+        if mapped_plots.contains(x, z) {
+            continue;
+        }
+        let (plot, column, depth) = match slot(x, z) {
+            Slot::Path | Slot::Gate => {
+                editor.set_block(PATH, x, 0, z, None, None);
+                continue;
+            }
+            Slot::Hedge => {
+                if lay_ground(editor, GRASS_BLOCK, x, z) {
+                    editor.set_block(HEDGE, x, 1, z, None, None);
+                }
+                continue;
+            }
+            Slot::Garden {
+                plot,
+                column,
+                depth,
+            } => (plot, column, depth),
+        };
+
+        let mut rng = coord_rng(plot.0, plot.1, way.id);
+        let hut_left = rng.random_bool(0.5);
+        let paint = *HUT_COLOURS.choose(&mut rng).unwrap_or(&SPRUCE_PLANKS);
+        let vegetable = *VEGETABLES.choose(&mut rng).unwrap_or(&CARROTS);
+
+        let mut feature = feature(column, depth, hut_left);
+        let has_building = *built_on
+            .entry(plot)
+            .or_insert_with(|| plot_has_building(building_footprints, plot));
+        if has_building && is_hut(feature) {
+            feature = Feature::Lawn;
+        }
+        let hut = is_hut(feature);
+        let ground = match feature {
+            _ if hut => OAK_PLANKS,
+            Feature::GardenPath => PATH,
+            Feature::Vegetables => FARMLAND,
+            _ => GRASS_BLOCK,
+        };
+        if !lay_ground(editor, ground, x, z) {
+            continue;
+        }
+
+        match feature {
+            Feature::GardenPath | Feature::Lawn | Feature::HutFloor => {}
+            Feature::Flowers => {
+                let flower = *FLOWERS
+                    .choose(&mut coord_rng(x, z, way.id))
+                    .unwrap_or(&RED_FLOWER);
+                editor.set_block(flower, x, 1, z, None, None);
+            }
+            // Every third row of the beds is left as a walk between them
+            Feature::Vegetables if depth % 3 == 0 => {}
+            Feature::Vegetables => editor.set_block(vegetable, x, 1, z, None, None),
+            Feature::HutWall => {
+                editor.set_block(paint, x, 1, z, None, None);
+                editor.set_block(paint, x, 2, z, None, None);
+            }
+            Feature::HutWindow => {
+                editor.set_block(paint, x, 1, z, None, None);
+                editor.set_block(GLASS_PANE, x, 2, z, None, None);
+            }
+            Feature::HutDoor => {
+                editor.set_block(OAK_DOOR, x, 1, z, None, None);
+                editor.set_block(OAK_DOOR_UPPER, x, 2, z, None, None);
+            }
+        }
+        if hut {
+            // Flat roof of tarred felt
+            editor.set_block(BLACK_TERRACOTTA, x, 3, z, None, None);
+        }
+    }
+
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            if slot(x, z) != Slot::Path && !mapped_plots.contains(x, z) {
+                editor.set_block(HEDGE, x, 1, z, None, None);
+            }
+        }
+    }
+}
+
+fn is_hut(feature: Feature) -> bool {
+    matches!(
+        feature,
+        Feature::HutWall | Feature::HutWindow | Feature::HutDoor | Feature::HutFloor
+    )
+}
+
+/// Corner of a hut fitted into a back corner of a mapped plot, the corner farthest from its
+/// gate that the whole hut fits into
+fn fit_hut(area: &HashSet<(i32, i32)>, gate: (i32, i32)) -> Option<(i32, i32)> {
+    let min_x = area.iter().map(|&(x, _)| x).min()?;
+    let max_x = area.iter().map(|&(x, _)| x).max()?;
+    let min_z = area.iter().map(|&(_, z)| z).min()?;
+    let max_z = area.iter().map(|&(_, z)| z).max()?;
+    // Inside the hedge, which runs along the outline
+    let corners = [
+        (min_x + 1, min_z + 1),
+        (max_x - HUT_WIDTH, min_z + 1),
+        (min_x + 1, max_z - HUT_DEPTH),
+        (max_x - HUT_WIDTH, max_z - HUT_DEPTH),
+    ];
+    corners
+        .into_iter()
+        .filter(|&(x0, z0)| {
+            (x0..x0 + HUT_WIDTH).all(|x| (z0..z0 + HUT_DEPTH).all(|z| area.contains(&(x, z))))
+        })
+        .max_by_key(|&(x0, z0)| {
+            let (dx, dz) = (x0 + HUT_WIDTH / 2 - gate.0, z0 + HUT_DEPTH / 2 - gate.1);
+            dx * dx + dz * dz
+        })
+}
+
+/// Lays out a mapped allotment plot (`allotments=plot`) from its outline: a hedge with a
+/// gate in the middle of its longest side, lawn with flowers along the hedge, and a hut
+/// with vegetable beds in front of it unless a hut is mapped in the plot
+pub fn generate_mapped_plot(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+    building_footprints: &BuildingFootprintBitmap,
+) {
+    if way.nodes.len() < 3 {
+        return;
+    }
+    let area: HashSet<(i32, i32)> = flood_fill_cache
+        .get_or_compute(way, args.timeout.as_ref())
+        .into_iter()
+        .collect();
+    let longest = way
+        .nodes
+        .windows(2)
+        .max_by_key(|pair| (pair[1].x - pair[0].x).pow(2) + (pair[1].z - pair[0].z).pow(2));
+    let gate = longest.map_or((way.nodes[0].x, way.nodes[0].z), |pair| {
+        ((pair[0].x + pair[1].x) / 2, (pair[0].z + pair[1].z) / 2)
+    });
+
+    let mut rng = coord_rng(gate.0, gate.1, way.id);
+    let paint = *HUT_COLOURS.choose(&mut rng).unwrap_or(&SPRUCE_PLANKS);
+    let vegetable = *VEGETABLES.choose(&mut rng).unwrap_or(&CARROTS);
+    let has_building = area
+        .iter()
+        .any(|&(x, z)| building_footprints.contains(x, z));
+    let hut = if has_building {
+        None
+    } else {
+        fit_hut(&area, gate)
+    };
+
+    let mut hedge: HashSet<(i32, i32)> = HashSet::new();
+    for pair in way.nodes.windows(2) {
+        hedge.extend(
+            bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z)
+                .into_iter()
+                .map(|(x, _, z)| (x, z)),
+        );
+    }
+    for &(x, z) in &hedge {
+        if (x - gate.0).abs() <= 1 && (z - gate.1).abs() <= 1 {
+            editor.set_block(PATH, x, 0, z, None, None);
+        } else if lay_ground(editor, GRASS_BLOCK, x, z) {
+            editor.set_block(HEDGE, x, 1, z, None, None);
+        }
+    }
+
+    for &(x, z) in &area {
+        if hedge.contains(&(x, z)) {
+            continue;
+        }
+        if let Some((x0, z0)) = hut {
+            let (dx, dz) = (x - x0, z - z0);
+            if (0..HUT_WIDTH).contains(&dx) && (0..HUT_DEPTH).contains(&dz) {
+                // The door faces the gate
+                let front = if gate.1 < z0 { 0 } else { HUT_DEPTH - 1 };
+                let wall = dx == 0 || dx == HUT_WIDTH - 1 || dz == 0 || dz == HUT_DEPTH - 1;
+                if !lay_ground(editor, OAK_PLANKS, x, z) {
+                    continue;
+                }
+                if dz == front && dx == HUT_WIDTH / 2 {
+                    editor.set_block(OAK_DOOR, x, 1, z, None, None);
+                    editor.set_block(OAK_DOOR_UPPER, x, 2, z, None, None);
+                } else if wall {
+                    editor.set_block(paint, x, 1, z, None, None);
+                    let window = dz == front && !(dx == 0 || dx == HUT_WIDTH - 1);
+                    let upper = if window { GLASS_PANE } else { paint };
+                    editor.set_block(upper, x, 2, z, None, None);
+                }
+                editor.set_block(BLACK_TERRACOTTA, x, 3, z, None, None);
+                continue;
+            }
+            // Vegetable beds in the strip along the hut, every third row a walk
+            let beside = (-2..HUT_WIDTH + 2).contains(&dx) && (-3..HUT_DEPTH + 3).contains(&dz);
+            if beside {
+                if lay_ground(editor, FARMLAND, x, z) && dz.rem_euclid(3) != 0 {
+                    editor.set_block(vegetable, x, 1, z, None, None);
+                }
+                continue;
+            }
+        }
+        if !lay_ground(editor, GRASS_BLOCK, x, z) {
+            continue;
+        }
+        let by_hedge = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .iter()
+            .any(|&(dx, dz)| hedge.contains(&(x + dx, z + dz)));
+        if by_hedge && coord_rng(x, z, way.id).random_bool(0.5) {
+            let flower = *FLOWERS
+                .choose(&mut coord_rng(x, z, way.id))
+                .unwrap_or(&RED_FLOWER);
+            editor.set_block(flower, x, 1, z, None, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate_system::cartesian::XZBBox;
+
+    #[test]
+    fn rows_of_plots_open_onto_their_paths() {
+        assert_eq!(slot(5, 0), Slot::Path);
+        assert_eq!(slot(5, PATH_WIDTH + PLOT_DEPTH + 1), Slot::Path);
+        assert_eq!(slot(PLOT_WIDTH / 2, PATH_WIDTH), Slot::Gate);
+        assert_eq!(slot(3, PATH_WIDTH), Slot::Hedge);
+        assert_eq!(slot(PLOT_WIDTH, 5), Slot::Hedge);
+        assert_eq!(slot(3, PATH_WIDTH + PLOT_DEPTH - 1), Slot::Hedge);
+        assert_eq!(
+            slot(-9, 5),
+            Slot::Garden {
+                plot: (-12, PATH_WIDTH),
+                column: 3,
+                depth: 3
+            }
+        );
+    }
+
+    #[test]
+    fn garden_path_leads_to_the_hut_door() {
+        let middle = PLOT_WIDTH / 2;
+        assert_eq!(feature(middle, 1, true), Feature::GardenPath);
+        assert_eq!(feature(middle - 1, 4, true), Feature::Flowers);
+        assert_eq!(feature(2, 4, true), Feature::Lawn);
+        // The terrace runs along the front of the hut, past its door
+        assert_eq!(feature(2, 9, true), Feature::GardenPath);
+        assert_eq!(feature(3, 10, true), Feature::HutDoor);
+        assert_eq!(feature(2, 10, true), Feature::HutWindow);
+        assert_eq!(feature(1, 10, true), Feature::HutWall);
+        assert_eq!(feature(2, 11, true), Feature::HutFloor);
+        assert_eq!(feature(2, 12, true), Feature::HutWall);
+        assert_eq!(feature(8, 11, true), Feature::Vegetables);
+        assert_eq!(feature(9, 10, false), Feature::HutDoor);
+        assert_eq!(feature(2, 11, false), Feature::Vegetables);
+    }
+
+    #[test]
+    fn mapped_huts_replace_the_generated_ones() {
+        let bbox = XZBBox::rect_from_xz_lengths(64.0, 64.0).unwrap();
+        let mut footprints = BuildingFootprintBitmap::new(&bbox);
+        assert!(!plot_has_building(&footprints, (0, PATH_WIDTH)));
+        footprints.set(3, PATH_WIDTH + 11);
+        assert!(plot_has_building(&footprints, (0, PATH_WIDTH)));
+        assert!(!plot_has_building(&footprints, (PLOT_WIDTH, PATH_WIDTH)));
+    }
+
+    #[test]
+    fn huts_fit_in_the_corner_away_from_the_gate() {
+        let area: HashSet<(i32, i32)> =
+            (0..10).flat_map(|x| (0..12).map(move |z| (x, z))).collect();
+        // Gate in the front hedge, the hut at the back
+        let (_, z0) = fit_hut(&area, (5, 0)).unwrap();
+        assert_eq!(z0, 11 - HUT_DEPTH);
+        // Too small for a hut inside its hedge
+        let tiny: HashSet<(i32, i32)> = (0..4).flat_map(|x| (0..4).map(move |z| (x, z))).collect();
+        assert_eq!(fit_hut(&tiny, (2, 0)), None);
+    }
+}
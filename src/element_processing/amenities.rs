@@ -3,6 +3,7 @@ use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
 use crate::coordinate_system::cartesian::XZPoint;
 use crate::deterministic_rng::element_rng;
+use crate::element_processing::{cemeteries, churches, ferries, parking, street_details};
 use crate::floodfill::flood_fill_area; // Needed for inline amenity flood fills
 use crate::floodfill_cache::FloodFillCache;
 use crate::osm_parser::ProcessedElement;
@@ -146,7 +147,31 @@ pub fn generate_amenities(
                     }
                 }
             }
+            "ferry_terminal" => {
+                // Terminals mapped as a building are drawn as the building
+                if let ProcessedElement::Node(node) = element {
+                    ferries::generate_terminal(editor, node.x, node.z);
+                }
+            }
+            "grave_yard" => {
+                if let ProcessedElement::Way(way) = element {
+                    cemeteries::generate_cemetery(editor, way, args, flood_fill_cache, None);
+                    churches::generate_churchyard_wall(editor, way);
+                }
+            }
             "shelter" => {
+                // Waiting shelters mapped as a node by the stop
+                if let ProcessedElement::Node(node) = element {
+                    if node
+                        .tags
+                        .get("shelter_type")
+                        .is_some_and(|t| t == "public_transport")
+                    {
+                        street_details::generate_bus_shelter(editor, node.x, node.z);
+                        return;
+                    }
+                }
+
                 let roof_block: Block = STONE_BRICK_SLAB;
 
                 // Use pre-computed flood fill from cache
@@ -169,141 +194,55 @@ pub fn generate_amenities(
                     editor.set_block(roof_block, *x, 5, *z, None, None);
                 }
             }
-            "parking" | "fountain" => {
-                // Process parking or fountain areas
+            "parking" => {
+                parking::generate_parking(editor, element, args, flood_fill_cache);
+            }
+            "fountain" => {
                 let mut previous_node: Option<XZPoint> = None;
-                let mut corner_addup: (i32, i32, i32) = (0, 0, 0);
                 let mut current_amenity: Vec<(i32, i32)> = vec![];
 
-                let block_type = match amenity_type.as_str() {
-                    "fountain" => WATER,
-                    "parking" => GRAY_CONCRETE,
-                    _ => GRAY_CONCRETE,
-                };
-
                 for node in element.nodes() {
                     let pt: XZPoint = node.xz();
 
                     if let Some(prev) = previous_node {
-                        // Create borders for fountain or parking area
+                        // Basin edge with a decorative border around it
                         let bresenham_points: Vec<(i32, i32, i32)> =
                             bresenham_line(prev.x, 0, prev.z, pt.x, 0, pt.z);
                         for (bx, _, bz) in bresenham_points {
-                            editor.set_block(block_type, bx, 0, bz, Some(&[BLACK_CONCRETE]), None);
-
-                            // Decorative border around fountains
-                            if amenity_type == "fountain" {
-                                for dx in [-1, 0, 1].iter() {
-                                    for dz in [-1, 0, 1].iter() {
-                                        if (*dx, *dz) != (0, 0) {
-                                            editor.set_block(
-                                                LIGHT_GRAY_CONCRETE,
-                                                bx + dx,
-                                                0,
-                                                bz + dz,
-                                                None,
-                                                None,
-                                            );
-                                        }
+                            editor.set_block(WATER, bx, 0, bz, Some(&[BLACK_CONCRETE]), None);
+                            for dx in [-1, 0, 1].iter() {
+                                for dz in [-1, 0, 1].iter() {
+                                    if (*dx, *dz) != (0, 0) {
+                                        editor.set_block(
+                                            LIGHT_GRAY_CONCRETE,
+                                            bx + dx,
+                                            0,
+                                            bz + dz,
+                                            None,
+                                            None,
+                                        );
                                     }
                                 }
                             }
-
                             current_amenity.push((node.x, node.z));
-                            corner_addup.0 += node.x;
-                            corner_addup.1 += node.z;
-                            corner_addup.2 += 1;
                         }
                     }
                     previous_node = Some(pt);
                 }
 
-                // Flood-fill the interior area for parking or fountains
-                if corner_addup.2 > 0 {
-                    let polygon_coords: Vec<(i32, i32)> = current_amenity.to_vec();
+                // Flood-fill the basin
+                if !current_amenity.is_empty() {
                     let flood_area: Vec<(i32, i32)> =
-                        flood_fill_area(&polygon_coords, args.timeout.as_ref());
-
+                        flood_fill_area(&current_amenity, args.timeout.as_ref());
                     for (x, z) in flood_area {
                         editor.set_block(
-                            block_type,
+                            WATER,
                             x,
                             0,
                             z,
                             Some(&[BLACK_CONCRETE, GRAY_CONCRETE]),
                             None,
                         );
-
-                        // Enhanced parking space markings
-                        if amenity_type == "parking" {
-                            // Create defined parking spaces with realistic layout
-                            let space_width = 4; // Width of each parking space
-                            let space_length = 6; // Length of each parking space
-                            let lane_width = 5; // Width of driving lanes
-
-                            // Calculate which "zone" this coordinate falls into
-                            let zone_x = x / space_width;
-                            let zone_z = z / (space_length + lane_width);
-                            let local_x = x % space_width;
-                            let local_z = z % (space_length + lane_width);
-
-                            // Create parking space boundaries (only within parking areas, not in driving lanes)
-                            if local_z < space_length {
-                                // We're in a parking space area, not in the driving lane
-                                if local_x == 0 {
-                                    // Vertical parking space lines (only on the left edge)
-                                    editor.set_block(
-                                        LIGHT_GRAY_CONCRETE,
-                                        x,
-                                        0,
-                                        z,
-                                        Some(&[BLACK_CONCRETE, GRAY_CONCRETE]),
-                                        None,
-                                    );
-                                } else if local_z == 0 {
-                                    // Horizontal parking space lines (only on the top edge)
-                                    editor.set_block(
-                                        LIGHT_GRAY_CONCRETE,
-                                        x,
-                                        0,
-                                        z,
-                                        Some(&[BLACK_CONCRETE, GRAY_CONCRETE]),
-                                        None,
-                                    );
-                                }
-                            } else if local_z == space_length {
-                                // Bottom edge of parking spaces (border with driving lane)
-                                editor.set_block(
-                                    LIGHT_GRAY_CONCRETE,
-                                    x,
-                                    0,
-                                    z,
-                                    Some(&[BLACK_CONCRETE, GRAY_CONCRETE]),
-                                    None,
-                                );
-                            } else if local_z > space_length && local_z < space_length + lane_width
-                            {
-                                // Driving lane - use darker concrete
-                                editor.set_block(
-                                    BLACK_CONCRETE,
-                                    x,
-                                    0,
-                                    z,
-                                    Some(&[GRAY_CONCRETE]),
-                                    None,
-                                );
-                            }
-
-                            // Add light posts at parking space outline corners
-                            if local_x == 0 && local_z == 0 && zone_x % 3 == 0 && zone_z % 2 == 0 {
-                                // Light posts at regular intervals on parking space corners
-                                editor.set_block(COBBLESTONE_WALL, x, 1, z, None, None);
-                                for dy in 2..=4 {
-                                    editor.set_block(OAK_FENCE, x, dy, z, None, None);
-                                }
-                                editor.set_block(GLOWSTONE, x, 5, z, None, None);
-                            }
-                        }
                     }
                 }
             }
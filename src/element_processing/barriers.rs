@@ -1,112 +1,127 @@
+use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
 use crate::osm_parser::{ProcessedElement, ProcessedNode};
 use crate::world_editor::WorldEditor;
+use std::collections::HashMap;
 
-pub fn generate_barriers(editor: &mut WorldEditor, element: &ProcessedElement) {
-    // Default values
-    let mut barrier_material: Block = COBBLESTONE_WALL;
-    let mut barrier_height: i32 = 2;
+/// Blocks between the posts of a wire fence
+const POST_SPACING: usize = 4;
 
-    match element.tags().get("barrier").map(|s| s.as_str()) {
-        Some("bollard") => {
-            barrier_material = COBBLESTONE_WALL;
-            barrier_height = 1;
-        }
-        Some("kerb") => {
-            // Ignore kerbs
-            return;
-        }
-        Some("hedge") => {
-            barrier_material = OAK_LEAVES;
-            barrier_height = 2;
-        }
-        Some("fence") => {
-            // Handle fence sub-types
-            match element.tags().get("fence_type").map(|s| s.as_str()) {
-                Some("railing" | "bars" | "krest") => {
-                    barrier_material = STONE_BRICK_WALL;
-                    barrier_height = 1;
-                }
-                Some(
-                    "chain_link" | "metal" | "wire" | "barbed_wire" | "corrugated_metal"
-                    | "electric" | "metal_bars",
-                ) => {
-                    barrier_material = STONE_BRICK_WALL; // IRON_BARS
-                    barrier_height = 2;
-                }
-                Some("slatted" | "paling") => {
-                    barrier_material = OAK_FENCE;
-                    barrier_height = 1;
-                }
-                Some("wood" | "split_rail" | "panel" | "pole") => {
-                    barrier_material = OAK_FENCE;
-                    barrier_height = 2;
-                }
-                Some("concrete" | "stone") => {
-                    barrier_material = STONE_BRICK_WALL;
-                    barrier_height = 2;
-                }
-                Some("glass") => {
-                    barrier_material = GLASS;
-                    barrier_height = 1;
-                }
-                _ => {}
-            }
-        }
-        Some("wall") => {
-            barrier_material = STONE_BRICK_WALL;
-            barrier_height = 3;
+/// How a barrier way is built
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BarrierStyle {
+    body: Block,
+    /// Posts every `POST_SPACING` blocks, with the body strung between them
+    posts: Option<Block>,
+    /// Coping laid along the top of a wall
+    cap: Option<Block>,
+    /// Height in meters when the way has no `height` tag
+    default_height_m: f64,
+}
+
+impl BarrierStyle {
+    const fn new(body: Block, default_height_m: f64) -> Self {
+        Self {
+            body,
+            posts: None,
+            cap: None,
+            default_height_m,
         }
-        _ => {}
     }
-    // Tagged material takes priority over inferred
-    if let Some(barrier_mat) = element.tags().get("material") {
-        if barrier_mat == "brick" {
-            barrier_material = BRICK;
-        }
-        if barrier_mat == "concrete" {
-            barrier_material = LIGHT_GRAY_CONCRETE;
-        }
-        if barrier_mat == "metal" {
-            barrier_material = STONE_BRICK_WALL;
-        }
+}
+
+/// Wall of the given `material` or `wall` type, topped with a matching coping
+fn wall_style(material: Option<&str>) -> BarrierStyle {
+    let (body, cap) = match material {
+        Some("brick") => (BRICK, Some(BRICK_SLAB)),
+        Some("concrete" | "reinforced_concrete") => (LIGHT_GRAY_CONCRETE, Some(SMOOTH_STONE_SLAB)),
+        Some("wood") => (OAK_PLANKS, Some(OAK_SLAB)),
+        Some("metal") => (IRON_BARS, None),
+        // Danish stone dikes are piled field stones, overgrown with moss
+        Some("dry_stone" | "field_stone") => (MOSSY_COBBLESTONE, None),
+        _ => (STONE_BRICK_WALL, Some(STONE_BRICK_SLAB)),
+    };
+    BarrierStyle {
+        cap,
+        ..BarrierStyle::new(body, 2.0)
     }
+}
 
-    if let ProcessedElement::Way(way) = element {
-        // Determine wall height
-        let wall_height: i32 = element
-            .tags()
-            .get("height")
-            .and_then(|height: &String| height.parse::<f32>().ok())
-            .map(|height: f32| height.round() as i32)
-            .unwrap_or(barrier_height)
-            .max(2); // Minimum height of 2
-
-        // Process nodes to create the barrier wall
-        for i in 1..way.nodes.len() {
-            let prev: &crate::osm_parser::ProcessedNode = &way.nodes[i - 1];
-            let x1: i32 = prev.x;
-            let z1: i32 = prev.z;
-
-            let cur: &crate::osm_parser::ProcessedNode = &way.nodes[i];
-            let x2: i32 = cur.x;
-            let z2: i32 = cur.z;
-
-            // Generate the line of coordinates between the two nodes
-            let bresenham_points: Vec<(i32, i32, i32)> = bresenham_line(x1, 0, z1, x2, 0, z2);
-
-            for (bx, _, bz) in bresenham_points {
-                // Build the barrier wall to the specified height
-                for y in 1..=wall_height {
-                    editor.set_block(barrier_material, bx, y, bz, None, None);
-                }
-
-                // Add an optional top to the barrier if the height is more than 1
-                if wall_height > 1 {
-                    editor.set_block(STONE_BRICK_SLAB, bx, wall_height + 1, bz, None, None);
-                }
+/// Style of a fence from its `fence_type`, or its `material` when the type says nothing
+fn fence_style(tags: &HashMap<String, String>) -> BarrierStyle {
+    match tags.get("fence_type").map(String::as_str) {
+        Some("railing" | "bars" | "krest" | "metal_bars") => BarrierStyle::new(IRON_BARS, 1.0),
+        Some("chain_link" | "metal" | "corrugated_metal") => BarrierStyle::new(IRON_BARS, 2.0),
+        Some("wire" | "barbed_wire" | "electric") => BarrierStyle {
+            posts: Some(OAK_FENCE),
+            ..BarrierStyle::new(IRON_BARS, 1.0)
+        },
+        Some("slatted" | "paling") => BarrierStyle::new(OAK_FENCE, 1.0),
+        Some("wood" | "split_rail" | "panel" | "pole") => BarrierStyle::new(OAK_FENCE, 1.5),
+        Some("concrete" | "stone") => BarrierStyle::new(STONE_BRICK_WALL, 2.0),
+        Some("glass") => BarrierStyle::new(GLASS, 1.0),
+        _ => match tags.get("material").map(String::as_str) {
+            Some("metal" | "steel") => BarrierStyle::new(IRON_BARS, 1.5),
+            Some("concrete" | "stone" | "brick") => BarrierStyle::new(STONE_BRICK_WALL, 1.5),
+            _ => BarrierStyle::new(OAK_FENCE, 1.5),
+        },
+    }
+}
+
+/// How to build a barrier way, or `None` for barriers that are not drawn
+fn barrier_style(tags: &HashMap<String, String>) -> Option<BarrierStyle> {
+    let style = match tags.get("barrier").map(String::as_str) {
+        Some("kerb") => return None,
+        Some("bollard") => BarrierStyle::new(COBBLESTONE_WALL, 1.0),
+        Some("hedge") => BarrierStyle::new(OAK_LEAVES, 1.5),
+        Some("fence") => fence_style(tags),
+        Some("wall" | "city_wall" | "retaining_wall") => wall_style(
+            tags.get("material")
+                .or_else(|| tags.get("wall"))
+                .map(String::as_str),
+        ),
+        _ => BarrierStyle::new(COBBLESTONE_WALL, 2.0),
+    };
+    Some(style)
+}
+
+/// Height of a barrier in blocks, from its `height` tag in meters ("1.5" or "1.5 m") or
+/// else its style's default
+fn barrier_height(tags: &HashMap<String, String>, default_height_m: f64, scale: f64) -> i32 {
+    let meters = tags
+        .get("height")
+        .and_then(|h| h.trim().trim_end_matches('m').trim().parse::<f64>().ok())
+        .filter(|h| *h > 0.0)
+        .unwrap_or(default_height_m);
+    ((meters * scale).round() as i32).max(1)
+}
+
+/// Draws a barrier way: hedges in leaves, fences by their `fence_type` and walls in the
+/// stone, brick or concrete of their `material`, as tall as their `height` tag
+pub fn generate_barriers(editor: &mut WorldEditor, element: &ProcessedElement, args: &Args) {
+    let ProcessedElement::Way(way) = element else {
+        return;
+    };
+    let Some(style) = barrier_style(&way.tags) else {
+        return;
+    };
+    let height = barrier_height(&way.tags, style.default_height_m, args.scale);
+
+    let mut along = 0;
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            let block = match style.posts {
+                Some(post) if along % POST_SPACING == 0 => post,
+                _ => style.body,
+            };
+            for y in 1..=height {
+                editor.set_block(block, x, y, z, None, None);
             }
+            if let Some(cap) = style.cap {
+                editor.set_block(cap, x, height + 1, z, None, None);
+            }
+            along += 1;
         }
     }
 }
@@ -170,3 +185,38 @@ pub fn generate_barrier_nodes(editor: &mut WorldEditor<'_>, node: &ProcessedNode
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn styles_follow_fence_type_and_wall_material() {
+        let hedge = barrier_style(&tags(&[("barrier", "hedge")])).unwrap();
+        assert_eq!(hedge.body, OAK_LEAVES);
+        assert_eq!(hedge.cap, None);
+
+        let wire = barrier_style(&tags(&[("barrier", "fence"), ("fence_type", "wire")])).unwrap();
+        assert_eq!((wire.body, wire.posts), (IRON_BARS, Some(OAK_FENCE)));
+        let chain_link =
+            barrier_style(&tags(&[("barrier", "fence"), ("fence_type", "chain_link")])).unwrap();
+        assert_eq!(chain_link.posts, None);
+
+        let brick = barrier_style(&tags(&[("barrier", "wall"), ("material", "brick")])).unwrap();
+        assert_eq!((brick.body, brick.cap), (BRICK, Some(BRICK_SLAB)));
+        let dike = barrier_style(&tags(&[("barrier", "wall"), ("wall", "dry_stone")])).unwrap();
+        assert_eq!(dike.body, MOSSY_COBBLESTONE);
+
+        assert_eq!(barrier_style(&tags(&[("barrier", "kerb")])), None);
+    }
+
+    #[test]
+    fn height_tag_is_scaled_from_meters() {
+        assert_eq!(barrier_height(&tags(&[("height", "3")]), 1.5, 1.0), 3);
+        assert_eq!(barrier_height(&tags(&[("height", "1.2 m")]), 1.5, 1.0), 1);
+        assert_eq!(barrier_height(&tags(&[("height", "4")]), 1.5, 0.5), 2);
+        assert_eq!(barrier_height(&tags(&[("height", "tall")]), 1.5, 1.0), 2);
+        assert_eq!(barrier_height(&tags(&[]), 1.0, 0.2), 1);
+    }
+}
@@ -1,10 +1,10 @@
 use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
-use crate::clipping::clip_way_to_bbox;
 use crate::colors::color_text_to_rgb_tuple;
 use crate::coordinate_system::cartesian::XZPoint;
 use crate::deterministic_rng::{coord_rng, element_rng};
+use crate::element_processing::churches;
 use crate::element_processing::historic;
 use crate::element_processing::subprocessor::buildings_interior::generate_building_interior;
 use crate::floodfill_cache::FloodFillCache;
@@ -13,17 +13,22 @@ use crate::world_editor::WorldEditor;
 use fastnbt::Value;
 use rand::Rng;
 use std::collections::{HashMap, HashSet};
+use std::f64::consts::FRAC_PI_2;
 use std::time::Duration;
 
 /// Enum representing different roof types
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub(crate) enum RoofType {
-    Gabled,    // Two sloping sides meeting at a ridge
-    Hipped, // All sides slope downwards to walls (including Half-hipped, Gambrel, Mansard variations)
-    Skillion, // Single sloping surface
-    Pyramidal, // All sides come to a point at the top
-    Dome,   // Rounded, hemispherical structure
-    Flat,   // Default flat roof
+pub enum RoofType {
+    Gabled,     // Two sloping sides meeting at a ridge
+    Hipped,     // All sides slope downwards to walls
+    HalfHipped, // Gabled, with the top of the gable ends hipped
+    Gambrel,    // Gabled with a steep lower and a shallow upper pitch on each side
+    Mansard,    // Hipped with a steep lower and a shallow upper pitch on each side
+    Skillion,   // Single sloping surface
+    Pyramidal,  // All sides come to a point at the top
+    Dome,       // Rounded, hemispherical structure
+    Onion,      // Bulb swelling out above its base and tapering to a point
+    Flat,       // Default flat roof
 }
 
 #[derive(Clone)]
@@ -113,6 +118,9 @@ const RELIGIOUS_WALL_OPTIONS: [Block; 8] = [
     END_STONE_BRICKS,
 ];
 
+/// Wall blocks for Danish village churches (whitewashed)
+const CHURCH_WALL_OPTIONS: [Block; 2] = [WHITE_CONCRETE, WHITE_TERRACOTTA];
+
 /// Wall blocks suitable for institutional buildings (formal, clean)
 const INSTITUTIONAL_WALL_OPTIONS: [Block; 8] = [
     WHITE_CONCRETE,
@@ -155,6 +163,12 @@ const GARAGE_WALL_OPTIONS: [Block; 3] = [BRICK, STONE_BRICKS, POLISHED_ANDESITE]
 /// Wall blocks for sheds (wooden)
 const SHED_WALL_OPTIONS: [Block; 1] = [OAK_LOG];
 
+/// Buildings at least this tall (in blocks) get a lightning rod on their highest point
+const LIGHTNING_ROD_MIN_HEIGHT: i32 = 32;
+
+/// How far above the top of the walls the roof's highest point is searched for
+const ROOF_SEARCH_HEIGHT: i32 = 48;
+
 /// Wall blocks for greenhouses (glass variants)
 const GREENHOUSE_WALL_OPTIONS: [Block; 4] = [
     GLASS,
@@ -189,6 +203,7 @@ pub enum BuildingCategory {
     School,    // Schools, kindergartens, colleges
     Hospital,  // Healthcare buildings
     Religious, // Churches, mosques, temples, etc.
+    Church,    // Village churches: whitewashed nave with a west tower
 
     // Special types
     TallBuilding,     // Tall buildings (>7 floors or >28m)
@@ -252,6 +267,9 @@ impl BuildingCategory {
         let is_religious_amenity =
             element.tags.get("amenity").map(|s| s.as_str()) == Some("place_of_worship");
 
+        if churches::is_village_church(element) {
+            return BuildingCategory::Church;
+        }
         if is_religious_building || is_religious_amenity {
             return BuildingCategory::Religious;
         }
@@ -544,6 +562,20 @@ impl BuildingStylePreset {
         }
     }
 
+    /// Preset for village churches: a gabled nave with tall windows, the tower is added on top
+    pub fn church() -> Self {
+        Self {
+            use_vertical_windows: Some(true),
+            use_accent_roof_line: Some(false),
+            use_accent_lines: Some(false),
+            use_vertical_accent: Some(false),
+            roof_type: Some(RoofType::Gabled),
+            generate_roof: Some(true),
+            has_chimney: Some(false),
+            ..Default::default()
+        }
+    }
+
     /// Preset for towers (man_made=tower) — solid stone walls, no windows
     pub fn tower() -> Self {
         Self {
@@ -629,6 +661,7 @@ impl BuildingStylePreset {
             BuildingCategory::School => Self::school(),
             BuildingCategory::Hospital => Self::hospital(),
             BuildingCategory::Religious => Self::religious(),
+            BuildingCategory::Church => Self::church(),
             BuildingCategory::Historic => Self::historic(),
             BuildingCategory::Tower => Self::tower(),
             BuildingCategory::Garage => Self::garage(),
@@ -816,7 +849,14 @@ impl BuildingStyle {
                     | "villa"
                     | "yes"
             );
-            let suitable_roof = matches!(roof_type, RoofType::Gabled | RoofType::Hipped);
+            let suitable_roof = matches!(
+                roof_type,
+                RoofType::Gabled
+                    | RoofType::Hipped
+                    | RoofType::HalfHipped
+                    | RoofType::Gambrel
+                    | RoofType::Mansard
+            );
             let suitable_size = (30..=400).contains(&footprint_size);
 
             is_residential && suitable_roof && suitable_size && rng.random_bool(0.55)
@@ -865,6 +905,7 @@ struct BuildingConfig {
     window_block: Block,
     accent_block: Block,
     roof_block: Option<Block>,
+    roof_height: Option<i32>,
     use_vertical_windows: bool,
     use_horizontal_windows: bool,
     use_accent_roof_line: bool,
@@ -1014,6 +1055,9 @@ fn get_wall_block_for_category(category: BuildingCategory, rng: &mut impl Rng) -
         BuildingCategory::Religious => {
             RELIGIOUS_WALL_OPTIONS[rng.random_range(0..RELIGIOUS_WALL_OPTIONS.len())]
         }
+        BuildingCategory::Church => {
+            CHURCH_WALL_OPTIONS[rng.random_range(0..CHURCH_WALL_OPTIONS.len())]
+        }
         BuildingCategory::School | BuildingCategory::Hospital => {
             INSTITUTIONAL_WALL_OPTIONS[rng.random_range(0..INSTITUTIONAL_WALL_OPTIONS.len())]
         }
@@ -1347,7 +1391,13 @@ fn generate_roof_only_structure(
         .unwrap_or(RoofType::Flat);
 
     match roof_type {
-        RoofType::Dome | RoofType::Hipped | RoofType::Pyramidal => {
+        RoofType::Dome
+        | RoofType::Hipped
+        | RoofType::HalfHipped
+        | RoofType::Gambrel
+        | RoofType::Mansard
+        | RoofType::Pyramidal
+        | RoofType::Onion => {
             // Standalone roof parts with curved or sloped shapes are rendered
             // as domes.  Without supporting walls, the dome approximation
             // produces the best visual result for shell-like roof structures.
@@ -1371,8 +1421,13 @@ fn generate_roof_only_structure(
                     base_height: start_y_offset,
                     abs_terrain_offset,
                     roof_block,
+                    roof_height: None,
                 };
-                generate_dome_roof(editor, cached_floor_area, &config);
+                if roof_type == RoofType::Onion {
+                    generate_onion_roof(editor, cached_floor_area, &config);
+                } else {
+                    generate_dome_roof(editor, cached_floor_area, &config);
+                }
             }
         }
         _ => {
@@ -2272,14 +2327,47 @@ fn calculate_roof_peak_height(
 fn parse_roof_type(roof_shape: &str) -> RoofType {
     match roof_shape {
         "gabled" => RoofType::Gabled,
-        "hipped" | "half-hipped" | "gambrel" | "mansard" | "round" => RoofType::Hipped,
+        "hipped" | "round" => RoofType::Hipped,
+        "half-hipped" => RoofType::HalfHipped,
+        "gambrel" => RoofType::Gambrel,
+        "mansard" => RoofType::Mansard,
         "skillion" => RoofType::Skillion,
         "pyramidal" => RoofType::Pyramidal,
-        "dome" | "onion" | "cone" | "circular" | "spherical" => RoofType::Dome,
+        "onion" => RoofType::Onion,
+        "dome" | "cone" | "circular" | "spherical" => RoofType::Dome,
         _ => RoofType::Flat,
     }
 }
 
+/// Roof rise in blocks from the roof:height tag (meters)
+fn parse_roof_height(element: &ProcessedWay, scale_factor: f64) -> Option<i32> {
+    let height = element
+        .tags
+        .get("roof:height")?
+        .trim_end_matches('m')
+        .trim()
+        .parse::<f64>()
+        .ok()?;
+    (height > 0.0).then(|| ((height * scale_factor).round() as i32).max(1))
+}
+
+/// Direction a roof:direction tag points at, in degrees clockwise from north: the way
+/// a skillion roof faces, downhill. Accepts degrees or compass points such as "NE".
+fn parse_roof_direction(direction: &str) -> Option<f64> {
+    const COMPASS: [&str; 16] = [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW",
+        "NW", "NNW",
+    ];
+    let direction = direction.trim();
+    if let Ok(degrees) = direction.parse::<f64>() {
+        return Some(degrees.rem_euclid(360.0));
+    }
+    COMPASS
+        .iter()
+        .position(|point| point.eq_ignore_ascii_case(direction))
+        .map(|index| index as f64 * 22.5)
+}
+
 /// Checks if building type qualifies for automatic gabled roof
 fn qualifies_for_auto_gabled_roof(building_type: &str) -> bool {
     matches!(
@@ -2442,7 +2530,7 @@ pub fn generate_buildings(
     // Resolve style with deterministic RNG
     let mut rng = element_rng(element.id);
     let has_multiple_floors = building_height > 6;
-    let style = BuildingStyle::resolve(
+    let mut style = BuildingStyle::resolve(
         &preset,
         element,
         building_type,
@@ -2452,6 +2540,23 @@ pub fn generate_buildings(
         &mut rng,
     );
 
+    // Near the edge of the world buildings are often clipped: keep them simple, without interiors
+    // and with flat roofs, so cut-off rooms and half roofs don't show
+    let near_edge = args.edge_margin > 0
+        && element
+            .nodes
+            .iter()
+            .any(|node| editor.edge_distance(node.x, node.z) < args.edge_margin);
+    if near_edge {
+        style.roof_type = RoofType::Flat;
+        style.has_chimney = false;
+    }
+
+    // Village churches are roofed in red tile or lead
+    if category == BuildingCategory::Church {
+        style.roof_block = Some(churches::roof_block(element));
+    }
+
     // Detect abandoned buildings
     let is_abandoned_building = element
         .tags
@@ -2471,6 +2576,7 @@ pub fn generate_buildings(
         window_block: style.window_block,
         accent_block: style.accent_block,
         roof_block: style.roof_block,
+        roof_height: parse_roof_height(element, scale_factor),
         use_vertical_windows: style.use_vertical_windows,
         use_horizontal_windows: style.use_horizontal_windows,
         use_accent_roof_line: style.use_accent_roof_line,
@@ -2525,7 +2631,7 @@ pub fn generate_buildings(
         );
 
         // Generate interior features
-        if args.interior {
+        if args.interior && !near_edge {
             let skip_interior = matches!(
                 building_type,
                 "garage" | "shed" | "parking" | "roof" | "bridge"
@@ -2584,12 +2690,17 @@ fn generate_building_roof(
         style.roof_type,
         roof_area,
         config.abs_terrain_offset,
+        config.roof_height,
     );
 
     // Add chimney if style says so
     if style.has_chimney {
-        let roof_peak_height =
-            calculate_roof_peak_height(bounds, config.start_y_offset, config.building_height);
+        let roof_peak_height = match config.roof_height {
+            Some(rise) => config.start_y_offset + config.building_height + rise,
+            None => {
+                calculate_roof_peak_height(bounds, config.start_y_offset, config.building_height)
+            }
+        };
         generate_chimney(
             editor,
             roof_area,
@@ -2632,6 +2743,57 @@ fn generate_building_roof(
     if category == BuildingCategory::Hospital && style.roof_type == RoofType::Flat {
         generate_hospital_helipad(editor, element, roof_area, config);
     }
+
+    // West tower of village churches, built through the nave roof
+    if category == BuildingCategory::Church && style.roof_type != RoofType::Flat {
+        churches::generate_church_tower(
+            editor,
+            element,
+            roof_area,
+            config.start_y_offset,
+            config.building_height,
+            config.abs_terrain_offset,
+            config.wall_block,
+        );
+    }
+
+    // Lightning rod on the highest point of tall buildings, churches and towers
+    if config.building_height >= LIGHTNING_ROD_MIN_HEIGHT
+        || matches!(
+            category,
+            BuildingCategory::Religious | BuildingCategory::Church | BuildingCategory::Tower
+        )
+    {
+        generate_lightning_rod(editor, roof_area, config);
+    }
+}
+
+/// Places a lightning rod on the highest block of the finished roof: the tip of a spire,
+/// the ridge of a gabled roof or the parapet of a flat one. Roofs that already carry a rod
+/// (e.g. on an antenna mast) are left alone.
+fn generate_lightning_rod(
+    editor: &mut WorldEditor,
+    roof_area: &[(i32, i32)],
+    config: &BuildingConfig,
+) {
+    let wall_top = config.start_y_offset + config.building_height + config.abs_terrain_offset;
+    let mut highest: Option<(i32, i32, i32)> = None;
+    for &(x, z) in roof_area {
+        let floor = highest.map_or(wall_top, |(_, y, _)| y + 1);
+        if let Some(y) = (floor..=wall_top + ROOF_SEARCH_HEIGHT)
+            .rev()
+            .find(|&y| editor.block_at_absolute(x, y, z))
+        {
+            highest = Some((x, y, z));
+        }
+    }
+    let Some((x, y, z)) = highest else {
+        return;
+    };
+    if editor.check_for_block_absolute(x, y, z, Some(&[LIGHTNING_ROD]), None) {
+        return;
+    }
+    editor.set_block_absolute(LIGHTNING_ROD, x, y + 1, z, None, None);
 }
 
 fn multiply_scale(value: i32, scale_factor: f64) -> i32 {
@@ -3126,6 +3288,8 @@ struct RoofConfig {
     base_height: i32,
     abs_terrain_offset: i32,
     roof_block: Block,
+    /// Rise of the roof in blocks from roof:height, replacing the shape's default
+    roof_height: Option<i32>,
 }
 
 impl RoofConfig {
@@ -3138,6 +3302,7 @@ impl RoofConfig {
         wall_block: Block,
         accent_block: Block,
         abs_terrain_offset: i32,
+        roof_height: Option<i32>,
     ) -> Self {
         // Calculate bounds from the actual roof area (floor + walls)
         let (min_x, max_x, min_z, max_z) = roof_area.iter().fold(
@@ -3174,9 +3339,15 @@ impl RoofConfig {
             base_height,
             abs_terrain_offset,
             roof_block,
+            roof_height,
         }
     }
 
+    /// The roof's rise in blocks: roof:height if tagged, else the shape's default
+    fn rise(&self, default: i32) -> i32 {
+        self.roof_height.unwrap_or(default).max(1)
+    }
+
     fn width(&self) -> i32 {
         self.max_x - self.min_x
     }
@@ -3309,9 +3480,12 @@ fn generate_gabled_roof(
     };
 
     // Calculate roof height boost, but limit it to max_distance so the slope
-    // is at most 1 block per row (creates a proper diagonal line)
+    // is at most 1 block per row (creates a proper diagonal line). A tagged
+    // roof:height is kept as it is.
     let raw_roof_height_boost = (3.0 + (config.building_size() as f64 * 0.15).ln().max(1.0)) as i32;
-    let roof_height_boost = raw_roof_height_boost.min(max_distance);
+    let roof_height_boost = config
+        .roof_height
+        .map_or(raw_roof_height_boost.min(max_distance), |rise| rise.max(1));
     let roof_peak_height = config.base_height + roof_height_boost;
 
     // Calculate roof heights only for positions in the actual footprint
@@ -3506,53 +3680,205 @@ fn generate_hipped_roof_rectangular(
     }
 
     let stair_block_material = get_stair_block_for_material(config.roof_block);
-    let min_x = config.min_x;
-    let max_x = config.max_x;
-    let min_z = config.min_z;
-    let max_z = config.max_z;
 
     // For stair direction, determine which edge the point is closest to
     place_roof_blocks_with_stairs(editor, floor_area, &roof_heights, config, |x, z, _| {
-        let dist_from_min_x = x - min_x;
-        let dist_from_max_x = max_x - x;
-        let dist_from_min_z = z - min_z;
-        let dist_from_max_z = max_z - z;
+        create_stair_with_properties(
+            stair_block_material,
+            inward_stair_facing(x, z, config),
+            StairShape::Straight,
+        )
+    });
+}
 
-        // Find which edge is closest
-        let min_dist = dist_from_min_x
-            .min(dist_from_max_x)
-            .min(dist_from_min_z)
-            .min(dist_from_max_z);
+/// Stair facing up a hipped slope: away from the edge of the roof bounds the point is
+/// closest to, toward the center
+fn inward_stair_facing(x: i32, z: i32, config: &RoofConfig) -> StairFacing {
+    let dist_from_min_x = x - config.min_x;
+    let dist_from_max_x = config.max_x - x;
+    let dist_from_min_z = z - config.min_z;
+    let dist_from_max_z = config.max_z - z;
+
+    // Find which edge is closest
+    let min_dist = dist_from_min_x
+        .min(dist_from_max_x)
+        .min(dist_from_min_z)
+        .min(dist_from_max_z);
+
+    if dist_from_min_x == min_dist {
+        // Closest to west edge, stair faces east (toward center)
+        StairFacing::East
+    } else if dist_from_max_x == min_dist {
+        // Closest to east edge, stair faces west
+        StairFacing::West
+    } else if dist_from_min_z == min_dist {
+        // Closest to north edge, stair faces south
+        StairFacing::South
+    } else {
+        // Closest to south edge, stair faces north
+        StairFacing::North
+    }
+}
 
-        if dist_from_min_x == min_dist {
-            // Closest to west edge, stair faces east (toward center)
-            create_stair_with_properties(
-                stair_block_material,
-                StairFacing::East,
-                StairShape::Straight,
-            )
-        } else if dist_from_max_x == min_dist {
-            // Closest to east edge, stair faces west
-            create_stair_with_properties(
-                stair_block_material,
-                StairFacing::West,
-                StairShape::Straight,
-            )
-        } else if dist_from_min_z == min_dist {
-            // Closest to north edge, stair faces south
-            create_stair_with_properties(
-                stair_block_material,
-                StairFacing::South,
-                StairShape::Straight,
-            )
+/// Share of the rise reached at `t` of the run from the eave (0) to the ridge (1) on a
+/// roof with two pitches per side: a steep lower pitch reaching `break_rise` of the rise
+/// at `break_run` of the run, then a shallower upper pitch.
+fn two_pitch_profile(t: f64, break_run: f64, break_rise: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    if t < break_run {
+        t / break_run * break_rise
+    } else {
+        break_rise + (t - break_run) / (1.0 - break_run) * (1.0 - break_rise)
+    }
+}
+
+/// Distances of a point from the ridge line and from the nearer gable end of a roof whose
+/// ridge runs through the center along x or z
+fn ridge_distances(x: i32, z: i32, config: &RoofConfig, ridge_axis_is_x: bool) -> (i32, i32) {
+    if ridge_axis_is_x {
+        (
+            (z - config.center_z).abs(),
+            (x - config.min_x).min(config.max_x - x),
+        )
+    } else {
+        (
+            (x - config.center_x).abs(),
+            (z - config.min_z).min(config.max_z - z),
+        )
+    }
+}
+
+/// Stair facing up a gabled slope, toward the ridge
+fn ridgeward_stair_facing(
+    x: i32,
+    z: i32,
+    config: &RoofConfig,
+    ridge_axis_is_x: bool,
+) -> StairFacing {
+    if ridge_axis_is_x {
+        if z < config.center_z {
+            StairFacing::South
         } else {
-            // Closest to south edge, stair faces north
-            create_stair_with_properties(
-                stair_block_material,
-                StairFacing::North,
-                StairShape::Straight,
-            )
+            StairFacing::North
         }
+    } else if x < config.center_x {
+        StairFacing::East
+    } else {
+        StairFacing::West
+    }
+}
+
+/// Generates a half-hipped roof: a gabled roof whose gable ends are hipped over the top
+/// half of the rise, at the pitch of the sides
+fn generate_half_hipped_roof(
+    editor: &mut WorldEditor,
+    floor_area: &[(i32, i32)],
+    config: &RoofConfig,
+    ridge_axis_is_x: bool,
+) {
+    let half_span = if ridge_axis_is_x {
+        config.length() / 2
+    } else {
+        config.width() / 2
+    }
+    .max(1);
+    let rise = config.rise(half_span.clamp(3, 8));
+    let pitch = rise as f64 / half_span as f64;
+    let gable_end_rise = (rise - rise / 2) as f64;
+
+    let mut roof_heights = HashMap::new();
+    let mut facings = HashMap::new();
+    for &(x, z) in floor_area {
+        let (from_ridge, from_end) = ridge_distances(x, z, config, ridge_axis_is_x);
+        let side = rise as f64 - from_ridge as f64 * pitch;
+        let hip = gable_end_rise + from_end as f64 * pitch;
+
+        let facing = if side <= hip {
+            ridgeward_stair_facing(x, z, config, ridge_axis_is_x)
+        } else if ridge_axis_is_x {
+            // Up the hip, away from the nearer gable end
+            if x - config.min_x < config.max_x - x {
+                StairFacing::East
+            } else {
+                StairFacing::West
+            }
+        } else if z - config.min_z < config.max_z - z {
+            StairFacing::South
+        } else {
+            StairFacing::North
+        };
+
+        let roof_height = config.base_height + side.min(hip).max(0.0) as i32;
+        roof_heights.insert((x, z), roof_height);
+        facings.insert((x, z), facing);
+    }
+
+    let stair_block_material = get_stair_block_for_material(config.roof_block);
+    place_roof_blocks_with_stairs(editor, floor_area, &roof_heights, config, |x, z, _| {
+        create_stair_with_properties(stair_block_material, facings[&(x, z)], StairShape::Straight)
+    });
+}
+
+/// Generates a gambrel roof: a barn roof, gabled with a steep lower and a shallow upper
+/// pitch on each side
+fn generate_gambrel_roof(
+    editor: &mut WorldEditor,
+    floor_area: &[(i32, i32)],
+    config: &RoofConfig,
+    ridge_axis_is_x: bool,
+) {
+    let half_span = if ridge_axis_is_x {
+        config.length() / 2
+    } else {
+        config.width() / 2
+    }
+    .max(1);
+    let rise = config.rise((config.building_size() / 3).clamp(4, 9));
+
+    let mut roof_heights = HashMap::new();
+    for &(x, z) in floor_area {
+        let (from_ridge, _) = ridge_distances(x, z, config, ridge_axis_is_x);
+        let t = 1.0 - from_ridge as f64 / half_span as f64;
+        let roof_height =
+            config.base_height + (two_pitch_profile(t, 0.35, 0.65) * rise as f64) as i32;
+        roof_heights.insert((x, z), roof_height);
+    }
+
+    let stair_block_material = get_stair_block_for_material(config.roof_block);
+    place_roof_blocks_with_stairs(editor, floor_area, &roof_heights, config, |x, z, _| {
+        create_stair_with_properties(
+            stair_block_material,
+            ridgeward_stair_facing(x, z, config, ridge_axis_is_x),
+            StairShape::Straight,
+        )
+    });
+}
+
+/// Generates a mansard roof: hipped, with a steep lower pitch on all four sides rising
+/// almost the whole way and a nearly flat top
+fn generate_mansard_roof(editor: &mut WorldEditor, floor_area: &[(i32, i32)], config: &RoofConfig) {
+    let max_dist_to_edge = (config.width().min(config.length()) / 2).max(1);
+    let rise = config.rise(if config.building_size() > 20 { 7 } else { 5 });
+
+    let mut roof_heights = HashMap::new();
+    for &(x, z) in floor_area {
+        let min_dist_to_edge = (x - config.min_x)
+            .min(config.max_x - x)
+            .min(z - config.min_z)
+            .min(config.max_z - z);
+        let t = min_dist_to_edge as f64 / max_dist_to_edge as f64;
+        let roof_height =
+            config.base_height + (two_pitch_profile(t, 0.25, 0.8) * rise as f64) as i32;
+        roof_heights.insert((x, z), roof_height);
+    }
+
+    let stair_block_material = get_stair_block_for_material(config.roof_block);
+    place_roof_blocks_with_stairs(editor, floor_area, &roof_heights, config, |x, z, _| {
+        create_stair_with_properties(
+            stair_block_material,
+            inward_stair_facing(x, z, config),
+            StairShape::Straight,
+        )
     });
 }
 
@@ -3637,30 +3963,53 @@ fn generate_hipped_roof_square(
     });
 }
 
-/// Generates a skillion (mono-pitch) roof
+/// Generates a skillion (mono-pitch) roof facing `direction` (degrees clockwise from
+/// north, downhill), west by default
 fn generate_skillion_roof(
     editor: &mut WorldEditor,
     floor_area: &[(i32, i32)],
     config: &RoofConfig,
+    direction: Option<f64>,
 ) {
-    let width = config.width().max(1);
-    let max_roof_height = (config.building_size() / 3).clamp(4, 10);
+    let max_roof_height = config.rise((config.building_size() / 3).clamp(4, 10));
+
+    // Uphill unit vector, against the direction the roof faces (north is -z)
+    // Rounded, so cardinal directions project exactly onto the axes
+    let radians = direction.unwrap_or(270.0).to_radians();
+    let snap = |v: f64| (v * 1e6).round() / 1e6;
+    let (uphill_x, uphill_z) = (snap(-radians.sin()), snap(radians.cos()));
+    let uphill = |x: i32, z: i32| x as f64 * uphill_x + z as f64 * uphill_z;
+    let corners = [
+        uphill(config.min_x, config.min_z),
+        uphill(config.min_x, config.max_z),
+        uphill(config.max_x, config.min_z),
+        uphill(config.max_x, config.max_z),
+    ];
+    let low = corners.iter().copied().fold(f64::INFINITY, f64::min);
+    let span = (corners.iter().copied().fold(f64::NEG_INFINITY, f64::max) - low).max(1.0);
 
     let mut roof_heights = HashMap::new();
     for &(x, z) in floor_area {
-        let slope_progress = (x - config.min_x) as f64 / width as f64;
+        let slope_progress = ((uphill(x, z) - low) / span).clamp(0.0, 1.0);
         let roof_height = config.base_height + (slope_progress * max_roof_height as f64) as i32;
         roof_heights.insert((x, z), roof_height);
     }
 
     let stair_block_material = get_stair_block_for_material(config.roof_block);
+    let facing = if uphill_x.abs() >= uphill_z.abs() {
+        if uphill_x > 0.0 {
+            StairFacing::East
+        } else {
+            StairFacing::West
+        }
+    } else if uphill_z > 0.0 {
+        StairFacing::South
+    } else {
+        StairFacing::North
+    };
 
     place_roof_blocks_with_stairs(editor, floor_area, &roof_heights, config, |_, _, _| {
-        create_stair_with_properties(
-            stair_block_material,
-            StairFacing::East,
-            StairShape::Straight,
-        )
+        create_stair_with_properties(stair_block_material, facing, StairShape::Straight)
     });
 }
 
@@ -3670,7 +4019,7 @@ fn generate_pyramidal_roof(
     floor_area: &[(i32, i32)],
     config: &RoofConfig,
 ) {
-    let peak_height = config.base_height + (config.building_size() / 3).clamp(3, 8);
+    let peak_height = config.base_height + config.rise((config.building_size() / 3).clamp(3, 8));
     let max_distance = (config.width() / 2).max(config.length() / 2) as f64;
 
     let mut roof_heights = HashMap::new();
@@ -3853,6 +4202,7 @@ fn determine_pyramidal_stair_block(
 /// Generates a dome roof
 fn generate_dome_roof(editor: &mut WorldEditor, floor_area: &[(i32, i32)], config: &RoofConfig) {
     let radius = (config.building_size() / 2) as f64;
+    let rise = config.roof_height.map_or(radius * 0.8, f64::from);
     // Use empty blacklist to allow overwriting wall/ceiling blocks
     let replace_any: &[Block] = &[];
 
@@ -3862,7 +4212,7 @@ fn generate_dome_roof(editor: &mut WorldEditor, floor_area: &[(i32, i32)], confi
         let normalized_distance = (distance_from_center.sqrt() / radius).min(1.0);
 
         let height_factor = (1.0 - normalized_distance * normalized_distance).sqrt();
-        let surface_height = config.base_height + (height_factor * (radius * 0.8)) as i32;
+        let surface_height = config.base_height + (height_factor * rise) as i32;
 
         for y in config.base_height..=surface_height {
             editor.set_block_absolute(
@@ -3877,6 +4227,42 @@ fn generate_dome_roof(editor: &mut WorldEditor, floor_area: &[(i32, i32)], confi
     }
 }
 
+/// Generates an onion dome: a bulb swelling out just above its base and tapering to a point
+fn generate_onion_roof(editor: &mut WorldEditor, floor_area: &[(i32, i32)], config: &RoofConfig) {
+    let radius = (config.building_size() as f64 / 2.0).max(1.0);
+    let rise = config.rise((radius * 1.6).round() as i32);
+    // Use empty blacklist to allow overwriting wall/ceiling blocks
+    let replace_any: &[Block] = &[];
+
+    // Radius of the bulb at a share of its height: widest a third of the way up
+    let bulb_radius = |u: f64| {
+        if u < 0.35 {
+            radius * (0.8 + 0.2 * (u / 0.35 * FRAC_PI_2).sin())
+        } else {
+            radius * ((u - 0.35) / 0.65 * FRAC_PI_2).cos().max(0.0).powf(1.5)
+        }
+    };
+
+    for &(x, z) in floor_area {
+        let distance_from_center =
+            (((x - config.center_x).pow(2) + (z - config.center_z).pow(2)) as f64).sqrt();
+
+        for y in 0..=rise {
+            // The center column always reaches the tip
+            if distance_from_center <= bulb_radius(y as f64 / rise as f64).max(0.5) {
+                editor.set_block_absolute(
+                    config.roof_block,
+                    x,
+                    config.base_height + y + config.abs_terrain_offset,
+                    z,
+                    None,
+                    Some(replace_any),
+                );
+            }
+        }
+    }
+}
+
 /// Unified function to generate various roof types
 #[allow(clippy::too_many_arguments)]
 #[inline]
@@ -3892,6 +4278,7 @@ fn generate_roof(
     roof_type: RoofType,
     roof_area: &[(i32, i32)],
     abs_terrain_offset: i32,
+    roof_height: Option<i32>,
 ) {
     if roof_area.is_empty() {
         return;
@@ -3905,6 +4292,7 @@ fn generate_roof(
         wall_block,
         accent_block,
         abs_terrain_offset,
+        roof_height,
     );
 
     // If a preset specifies a dedicated roof block, use it instead of
@@ -3914,6 +4302,12 @@ fn generate_roof(
     }
 
     let roof_orientation = element.tags.get("roof:orientation").map(|s| s.as_str());
+    // The ridge runs along the longer side unless roof:orientation says across
+    let width_is_longer = config.width() >= config.length();
+    let ridge_axis_is_x = match roof_orientation {
+        Some(o) if o.eq_ignore_ascii_case("across") => !width_is_longer,
+        _ => width_is_longer,
+    };
 
     // For flat roofs, also honour the override so preset flat-roof
     // materials (e.g. greenhouse smooth-stone slab) are respected.
@@ -3937,14 +4331,8 @@ fn generate_roof(
         RoofType::Hipped => {
             let is_rectangular = (config.width() as f64 / config.length() as f64 > 1.3)
                 || (config.length() as f64 / config.width() as f64 > 1.3);
-            let width_is_longer = config.width() >= config.length();
-            let ridge_axis_is_x = match roof_orientation {
-                Some(o) if o.eq_ignore_ascii_case("along") => width_is_longer,
-                Some(o) if o.eq_ignore_ascii_case("across") => !width_is_longer,
-                _ => width_is_longer,
-            };
             let roof_peak_height =
-                config.base_height + if config.building_size() > 20 { 7 } else { 5 };
+                config.base_height + config.rise(if config.building_size() > 20 { 7 } else { 5 });
 
             if is_rectangular {
                 generate_hipped_roof_rectangular(
@@ -3959,8 +4347,24 @@ fn generate_roof(
             }
         }
 
+        RoofType::HalfHipped => {
+            generate_half_hipped_roof(editor, roof_area, &config, ridge_axis_is_x);
+        }
+
+        RoofType::Gambrel => {
+            generate_gambrel_roof(editor, roof_area, &config, ridge_axis_is_x);
+        }
+
+        RoofType::Mansard => {
+            generate_mansard_roof(editor, roof_area, &config);
+        }
+
         RoofType::Skillion => {
-            generate_skillion_roof(editor, roof_area, &config);
+            let direction = element
+                .tags
+                .get("roof:direction")
+                .and_then(|d| parse_roof_direction(d));
+            generate_skillion_roof(editor, roof_area, &config, direction);
         }
 
         RoofType::Pyramidal => {
@@ -3970,6 +4374,10 @@ fn generate_roof(
         RoofType::Dome => {
             generate_dome_roof(editor, roof_area, &config);
         }
+
+        RoofType::Onion => {
+            generate_onion_roof(editor, roof_area, &config);
+        }
     }
 }
 
@@ -4024,97 +4432,14 @@ pub fn generate_building_from_relation(
             .any(|m| m.role == ProcessedMemberRole::Part);
 
     if !has_parts {
-        // Collect outer member node lists and merge open segments into closed rings.
+        // Merge the open outer segments into closed rings and clip them to the world.
         // Multipolygon relations commonly split the outline across many short way
         // segments that share endpoints. Without merging, each segment is processed
         // individually, producing degenerate polygons and empty flood fills (only
         // wall outlines, no filled floors/ceilings/roofs).
-        let mut outer_rings: Vec<Vec<ProcessedNode>> = relation
-            .members
-            .iter()
-            .filter(|m| m.role == ProcessedMemberRole::Outer)
-            .map(|m| m.way.nodes.clone())
-            .collect();
-
-        super::merge_way_segments(&mut outer_rings);
-
-        // Clip assembled rings to the world bounding box.  Because member ways
-        // were kept unclipped during parsing (to allow ring assembly), the
-        // merged rings may extend beyond the requested area.  Clipping prevents
-        // oversized flood fills and unnecessary block placement.
-        outer_rings = outer_rings
-            .into_iter()
-            .map(|ring| clip_way_to_bbox(&ring, xzbbox))
-            .filter(|ring| ring.len() >= 4)
-            .collect();
-
-        // Close rings that are nearly closed (endpoints within 1 block)
-        for ring in &mut outer_rings {
-            if ring.len() >= 3 {
-                let first = &ring[0];
-                let last = ring.last().unwrap();
-                if first.id != last.id {
-                    let dx = (first.x - last.x).abs();
-                    let dz = (first.z - last.z).abs();
-                    if dx <= 1 && dz <= 1 {
-                        let close_node = ring[0].clone();
-                        ring.push(close_node);
-                    }
-                }
-            }
-        }
-
-        // Discard rings that are still open or too small
-        outer_rings.retain(|ring| {
-            if ring.len() < 4 {
-                return false;
-            }
-            let first = &ring[0];
-            let last = ring.last().unwrap();
-            first.id == last.id || ((first.x - last.x).abs() <= 1 && (first.z - last.z).abs() <= 1)
-        });
-
-        // Collect and assemble inner rings for courtyards/holes.
-        let mut inner_rings: Vec<Vec<ProcessedNode>> = relation
-            .members
-            .iter()
-            .filter(|m| m.role == ProcessedMemberRole::Inner)
-            .map(|m| m.way.nodes.clone())
-            .collect();
-
-        super::merge_way_segments(&mut inner_rings);
-
-        inner_rings = inner_rings
-            .into_iter()
-            .map(|ring| clip_way_to_bbox(&ring, xzbbox))
-            .filter(|ring| ring.len() >= 4)
-            .collect();
-
-        // Close rings that are nearly closed (endpoints within 1 block)
-        for ring in &mut inner_rings {
-            if ring.len() >= 3 {
-                let first = &ring[0];
-                let last = ring.last().unwrap();
-                if first.id != last.id {
-                    let dx = (first.x - last.x).abs();
-                    let dz = (first.z - last.z).abs();
-                    if dx <= 1 && dz <= 1 {
-                        let close_node = ring[0].clone();
-                        ring.push(close_node);
-                    }
-                }
-            }
-        }
-
-        // Discard rings that are still open or too small
-        inner_rings.retain(|ring| {
-            if ring.len() < 4 {
-                return false;
-            }
-            let first = &ring[0];
-            let last = ring.last().unwrap();
-            first.id == last.id || ((first.x - last.x).abs() <= 1 && (first.z - last.z).abs() <= 1)
-        });
+        let outer_rings = super::assemble_rings(relation, ProcessedMemberRole::Outer, xzbbox);
+        // Inner rings are the courtyards/holes
+        let inner_rings = super::assemble_rings(relation, ProcessedMemberRole::Inner, xzbbox);
 
         let hole_polygons: Option<Vec<HolePolygon>> = if inner_rings.is_empty() {
             None
@@ -4239,3 +4564,38 @@ fn generate_bridge(
         editor.set_block_absolute(floor_block, x, floor_y, z, None, None);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roof_shapes_map_to_their_generators() {
+        assert_eq!(parse_roof_type("half-hipped"), RoofType::HalfHipped);
+        assert_eq!(parse_roof_type("gambrel"), RoofType::Gambrel);
+        assert_eq!(parse_roof_type("mansard"), RoofType::Mansard);
+        assert_eq!(parse_roof_type("onion"), RoofType::Onion);
+        assert_eq!(parse_roof_type("cone"), RoofType::Dome);
+        assert_eq!(parse_roof_type("sawtooth"), RoofType::Flat);
+    }
+
+    #[test]
+    fn roof_directions_accept_degrees_and_compass_points() {
+        assert_eq!(parse_roof_direction("90"), Some(90.0));
+        assert_eq!(parse_roof_direction("-90"), Some(270.0));
+        assert_eq!(parse_roof_direction("SW"), Some(225.0));
+        assert_eq!(parse_roof_direction("nne"), Some(22.5));
+        assert_eq!(parse_roof_direction("uphill"), None);
+    }
+
+    #[test]
+    fn two_pitch_roofs_break_between_eave_and_ridge() {
+        assert_eq!(two_pitch_profile(0.0, 0.25, 0.8), 0.0);
+        assert!((two_pitch_profile(0.25, 0.25, 0.8) - 0.8).abs() < 1e-9);
+        assert!((two_pitch_profile(1.0, 0.25, 0.8) - 1.0).abs() < 1e-9);
+        // The lower pitch is the steeper one
+        let lower = two_pitch_profile(0.1, 0.25, 0.8) / 0.1;
+        let upper = (two_pitch_profile(0.9, 0.25, 0.8) - 0.8) / 0.65;
+        assert!(lower > upper);
+    }
+}
@@ -0,0 +1,172 @@
+//! Danish cemeteries (kirkegårde).
+//!
+//! A Danish cemetery is laid out in sections between gravel paths lined with trees. Each
+//! section holds rows of family plots, every plot framed by a low clipped hedge with an
+//! opening onto the narrow gravel walk in front of its row, a headstone at the back and
+//! flowers on the grave. The layout is on a fixed grid, so cemeteries split across tiles
+//! line up. Chapels and other buildings already on the ground are left alone.
+
+use crate::args::Args;
+use crate::block_definitions::*;
+use crate::deterministic_rng::coord_rng;
+use crate::element_processing::plot_grid::{lay_ground, PlotGrid};
+use crate::element_processing::tree::{Tree, TreeType};
+use crate::floodfill_cache::{BuildingFootprintBitmap, FloodFillCache};
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use rand::prelude::IndexedRandom;
+use rand::Rng;
+
+/// Sections between the main paths, in both directions
+const SECTION: i32 = 24;
+const SECTIONS: PlotGrid = PlotGrid::new((SECTION, SECTION), (0, 0));
+/// Width of the main paths around the sections
+const PATH_WIDTH: i32 = 2;
+/// Width of a plot along its row, including the hedge on one side
+const PLOT_WIDTH: i32 = 5;
+/// Rows of plots: the walk, the front hedge, four blocks of grave and the back hedge
+const ROW_DEPTH: i32 = 7;
+/// Clipped hedge around the plots
+const HEDGE: Block = OAK_LEAVES;
+/// Paths and walks
+const PATH: Block = GRAVEL;
+/// Stones that headstones are cut from
+const HEADSTONES: [Block; 5] = [
+    POLISHED_ANDESITE,
+    POLISHED_GRANITE,
+    POLISHED_DIORITE,
+    POLISHED_BLACKSTONE,
+    STONE,
+];
+/// Flowers planted on the graves
+const GRAVE_FLOWERS: [Block; 4] = [RED_FLOWER, WHITE_FLOWER, YELLOW_FLOWER, BLUE_FLOWER];
+/// Chance of a plot being vacant, kept as lawn inside its hedge
+const VACANT_SHARE: f64 = 0.2;
+/// Chance of a grave having a stone laid flat instead of a headstone
+const LYING_STONE_SHARE: f64 = 0.3;
+
+/// What a block of a cemetery is part of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot {
+    Path,
+    /// Tree at the corner of a section, where two paths meet
+    Tree,
+    Walk,
+    Hedge,
+    /// Opening in the front hedge of a plot
+    Gate,
+    /// Inside a plot, `depth` blocks back from the front hedge; the plot's corner is at
+    /// `plot`
+    Grave {
+        plot: (i32, i32),
+        depth: i32,
+    },
+}
+
+fn slot(x: i32, z: i32) -> Slot {
+    let section = SECTIONS.cell(x, z);
+    let (sx, sz) = (section.column, section.depth);
+    if sx < PATH_WIDTH || sz < PATH_WIDTH {
+        return Slot::Path;
+    }
+    if sx == PATH_WIDTH && sz == PATH_WIDTH {
+        return Slot::Tree;
+    }
+    // Rows of plots fill the section inside its paths
+    let plots = PlotGrid::new(
+        (PLOT_WIDTH, ROW_DEPTH),
+        (section.plot.0 + PATH_WIDTH, section.plot.1 + PATH_WIDTH),
+    );
+    let cell = plots.cell(x, z);
+    let (column, row) = (cell.column, cell.depth);
+    match row {
+        0 => Slot::Walk,
+        _ if column == 0 || row == ROW_DEPTH - 1 => Slot::Hedge,
+        1 if column == PLOT_WIDTH / 2 => Slot::Gate,
+        1 => Slot::Hedge,
+        _ => Slot::Grave {
+            plot: cell.plot,
+            depth: row - 2,
+        },
+    }
+}
+
+/// Lays out a cemetery (`landuse=cemetery` or `amenity=grave_yard`) in sections of hedged
+/// grave plots between gravel paths, with trees where the paths meet
+pub fn generate_cemetery(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+    building_footprints: Option<&BuildingFootprintBitmap>,
+) {
+    let area = flood_fill_cache.get_or_compute(way, args.timeout.as_ref());
+
+    for (x, z) in area {
+        let slot = slot(x, z);
+        let ground = match slot {
+            Slot::Path | Slot::Walk | Slot::Gate => PATH,
+            _ => GRASS_BLOCK,
+        };
+        if !lay_ground(editor, ground, x, z) {
+            continue;
+        }
+
+        match slot {
+            Slot::Path | Slot::Walk | Slot::Gate => {}
+            Slot::Tree => {
+                Tree::create_of_type(editor, (x, 1, z), TreeType::Oak, building_footprints);
+            }
+            Slot::Hedge => editor.set_block(HEDGE, x, 1, z, None, None),
+            Slot::Grave { plot, depth } => {
+                let mut rng = coord_rng(plot.0, plot.1, way.id);
+                if rng.random_bool(VACANT_SHARE) {
+                    continue;
+                }
+                let stone = *HEADSTONES.choose(&mut rng).unwrap_or(&STONE);
+                let lying = rng.random_bool(LYING_STONE_SHARE);
+                let middle = x - plot.0 == PLOT_WIDTH / 2;
+                match depth {
+                    // Headstone at the back of the grave, facing the walk
+                    3 if middle && !lying => editor.set_block(stone, x, 1, z, None, None),
+                    2 if middle && lying => editor.set_block(STONE_BRICK_SLAB, x, 1, z, None, None),
+                    1 | 2 => {
+                        if coord_rng(x, z, way.id).random_bool(0.5) {
+                            let flower = *GRAVE_FLOWERS.choose(&mut rng).unwrap_or(&RED_FLOWER);
+                            editor.set_block(flower, x, 1, z, None, None);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_hold_rows_of_hedged_plots() {
+        assert_eq!(slot(0, 5), Slot::Path);
+        assert_eq!(slot(SECTION + 1, 5), Slot::Path);
+        assert_eq!(slot(PATH_WIDTH, PATH_WIDTH), Slot::Tree);
+
+        // The first row of plots behind its walk, starting at (2, 2)
+        assert_eq!(slot(5, 2), Slot::Walk);
+        assert_eq!(slot(7, 3), Slot::Hedge);
+        assert_eq!(slot(4, 3), Slot::Gate);
+        assert_eq!(slot(5, 3), Slot::Hedge);
+        assert_eq!(
+            slot(4, 7),
+            Slot::Grave {
+                plot: (2, 2),
+                depth: 3
+            }
+        );
+        assert_eq!(slot(4, 8), Slot::Hedge);
+        // The next row starts with its walk
+        assert_eq!(slot(4, 9), Slot::Walk);
+    }
+}
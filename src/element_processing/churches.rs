@@ -0,0 +1,417 @@
+//! Danish village churches.
+//!
+//! A village church is a whitewashed nave under a red tile or lead roof, with a square tower
+//! at its west end and a churchyard behind a low fieldstone dike. The nave goes through the
+//! regular building pipeline in the `Church` style; this module adds the tower on top of it,
+//! unless the tower is mapped as a building of its own, and the dike along
+//! `amenity=grave_yard` outlines.
+
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::deterministic_rng::{coord_rng, element_rng};
+use crate::osm_parser::{ProcessedElement, ProcessedWay};
+use crate::world_editor::WorldEditor;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// Red tile roof of the nave
+const TILE_ROOF: Block = BRICK;
+/// Lead roof of the nave and the tower spire
+const LEAD_ROOF: Block = POLISHED_ANDESITE;
+/// Narrowest nave, in blocks, that gets a tower
+const MIN_NAVE_WIDTH: i32 = 5;
+/// Widest tower, in blocks
+const MAX_TOWER_SIDE: i32 = 9;
+/// How far the tower rises above the ridge of the nave
+const TOWER_ABOVE_RIDGE: i32 = 6;
+/// Height of the sound holes at the top of the tower
+const BELFRY_HEIGHT: i32 = 3;
+/// Largest footprint of a village church, in square blocks; larger churches are town
+/// churches and cathedrals
+const MAX_FOOTPRINT: f64 = 900.0;
+/// Tag set on churches whose tower is mapped as a building of its own
+const MAPPED_TOWER_KEY: &str = "arnis:mapped_tower";
+
+/// Footprint of a church tower
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TowerFootprint {
+    min_x: i32,
+    min_z: i32,
+    side: i32,
+}
+
+impl TowerFootprint {
+    fn max_x(&self) -> i32 {
+        self.min_x + self.side - 1
+    }
+
+    fn max_z(&self) -> i32 {
+        self.min_z + self.side - 1
+    }
+
+    /// Blocks from (x, z) to the nearest side of the tower
+    fn inset(&self, x: i32, z: i32) -> i32 {
+        (x - self.min_x)
+            .min(self.max_x() - x)
+            .min(z - self.min_z)
+            .min(self.max_z() - z)
+    }
+}
+
+/// Whether a building is a church that gets the village church treatment. Cathedrals,
+/// large or domed churches and churches mapped in building parts keep the generic
+/// religious style.
+pub fn is_village_church(element: &ProcessedWay) -> bool {
+    if element.tags.contains_key("building:part")
+        || footprint_area(element) > MAX_FOOTPRINT
+        || element
+            .tags
+            .get("roof:shape")
+            .is_some_and(|shape| matches!(shape.as_str(), "dome" | "onion"))
+    {
+        return false;
+    }
+    match element.tags.get("building").map(|s| s.as_str()) {
+        Some("church" | "chapel") => true,
+        Some("cathedral") | None => false,
+        Some(_) => {
+            element.tags.get("amenity").map(|s| s.as_str()) == Some("place_of_worship")
+                && element.tags.get("religion").map(|s| s.as_str()) == Some("christian")
+        }
+    }
+}
+
+/// Area of a way's outline in square blocks
+fn footprint_area(element: &ProcessedWay) -> f64 {
+    let twice_area: i64 = element
+        .nodes
+        .windows(2)
+        .map(|pair| pair[0].x as i64 * pair[1].z as i64 - pair[1].x as i64 * pair[0].z as i64)
+        .sum();
+    twice_area.abs() as f64 / 2.0
+}
+
+/// Whether a building is a church tower or belfry mapped on its own
+fn is_mapped_tower(tags: &HashMap<String, String>) -> bool {
+    matches!(
+        tags.get("building").map(String::as_str),
+        Some("tower" | "bell_tower" | "belfry")
+    ) || tags.get("tower:type").is_some_and(|v| v == "bell_tower")
+}
+
+/// Marks the village churches that touch a tower mapped as its own building, so they are
+/// not given a second one.
+pub fn mark_mapped_towers(elements: &mut [ProcessedElement]) {
+    let bounds = |way: &ProcessedWay| {
+        let xs = way.nodes.iter().map(|node| node.x);
+        let zs = way.nodes.iter().map(|node| node.z);
+        Some((xs.clone().min()?, zs.clone().min()?, xs.max()?, zs.max()?))
+    };
+    let towers: Vec<(i32, i32, i32, i32)> = elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Way(way) if is_mapped_tower(&way.tags) => bounds(way),
+            _ => None,
+        })
+        .collect();
+    if towers.is_empty() {
+        return;
+    }
+    for element in elements.iter_mut() {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        if !is_village_church(way) {
+            continue;
+        }
+        let Some((min_x, min_z, max_x, max_z)) = bounds(way) else {
+            continue;
+        };
+        let touches = towers.iter().any(|&(tx0, tz0, tx1, tz1)| {
+            tx0 <= max_x + 1 && tx1 >= min_x - 1 && tz0 <= max_z + 1 && tz1 >= min_z - 1
+        });
+        if touches {
+            way.tags
+                .insert(MAPPED_TOWER_KEY.to_string(), "yes".to_string());
+        }
+    }
+}
+
+/// Roof block of the nave: lead when roof:material or roof:colour says so, red tile otherwise
+pub fn roof_block(element: &ProcessedWay) -> Block {
+    let is_lead = element
+        .tags
+        .get("roof:material")
+        .is_some_and(|m| matches!(m.as_str(), "lead" | "metal"))
+        || element
+            .tags
+            .get("roof:colour")
+            .is_some_and(|c| matches!(c.as_str(), "grey" | "gray" | "silver"));
+    if is_lead {
+        LEAD_ROOF
+    } else {
+        TILE_ROOF
+    }
+}
+
+/// A square tower as wide as the nave at its west end, or its north end when the nave runs
+/// north-south. Naves too narrow or too short to carry one get none.
+fn tower_footprint(roof_area: &[(i32, i32)]) -> Option<TowerFootprint> {
+    let min_x = roof_area.iter().map(|&(x, _)| x).min()?;
+    let max_x = roof_area.iter().map(|&(x, _)| x).max()?;
+    let min_z = roof_area.iter().map(|&(_, z)| z).min()?;
+    let max_z = roof_area.iter().map(|&(_, z)| z).max()?;
+    let (width, length) = (max_x - min_x + 1, max_z - min_z + 1);
+
+    let nave_width = width.min(length);
+    if nave_width < MIN_NAVE_WIDTH || width.max(length) * 2 < nave_width * 3 {
+        return None;
+    }
+    let side = nave_width.min(MAX_TOWER_SIDE);
+    let inset = (nave_width - side) / 2;
+    Some(if width >= length {
+        TowerFootprint {
+            min_x,
+            min_z: min_z + inset,
+            side,
+        }
+    } else {
+        TowerFootprint {
+            min_x: min_x + inset,
+            min_z,
+            side,
+        }
+    })
+}
+
+/// Builds the west tower of a village church through the roof of its nave: whitewashed
+/// walls with sound holes below the top, crowned by battlements or a lead spire.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_church_tower(
+    editor: &mut WorldEditor,
+    element: &ProcessedWay,
+    roof_area: &[(i32, i32)],
+    start_y_offset: i32,
+    building_height: i32,
+    abs_terrain_offset: i32,
+    wall_block: Block,
+) {
+    if element.tags.contains_key(MAPPED_TOWER_KEY) {
+        return;
+    }
+    let Some(tower) = tower_footprint(roof_area) else {
+        return;
+    };
+    let area: HashSet<(i32, i32)> = roof_area.iter().copied().collect();
+    let cells: Vec<(i32, i32)> = (tower.min_x..=tower.max_x())
+        .flat_map(|x| (tower.min_z..=tower.max_z()).map(move |z| (x, z)))
+        .filter(|cell| area.contains(cell))
+        .collect();
+
+    let wall_top = start_y_offset + building_height;
+    // Gabled nave roofs rise at most half the width of the nave
+    let top = wall_top + tower.side / 2 + TOWER_ABOVE_RIDGE;
+    let belfry_floor = top - BELFRY_HEIGHT - 1;
+    let center = (tower.min_x + tower.side / 2, tower.min_z + tower.side / 2);
+    // The tower replaces whatever the nave roof put inside its footprint
+    let replace_any: &[Block] = &[];
+
+    for &(x, z) in &cells {
+        let on_wall = tower.inset(x, z) == 0;
+        for y in (start_y_offset + 1)..=top {
+            let block = if on_wall {
+                let sound_hole = y > belfry_floor && y < top && (x == center.0 || z == center.1);
+                if sound_hole {
+                    AIR
+                } else {
+                    wall_block
+                }
+            } else if y == wall_top || y == belfry_floor {
+                OAK_PLANKS
+            } else if y > wall_top {
+                AIR
+            } else {
+                continue;
+            };
+            editor.set_block_absolute(block, x, y + abs_terrain_offset, z, None, Some(replace_any));
+        }
+    }
+
+    let mut rng = element_rng(element.id);
+    if rng.random_bool(0.5) {
+        // Battlements: every other block along the top of the walls
+        for &(x, z) in &cells {
+            if tower.inset(x, z) == 0 && (x + z) % 2 == 0 {
+                editor.set_block_absolute(
+                    wall_block,
+                    x,
+                    top + 1 + abs_terrain_offset,
+                    z,
+                    None,
+                    Some(replace_any),
+                );
+            }
+        }
+    } else {
+        generate_spire(editor, &tower, &cells, top, abs_terrain_offset);
+    }
+}
+
+/// A lead spire narrowing by one block every two layers from the top of the tower walls
+fn generate_spire(
+    editor: &mut WorldEditor,
+    tower: &TowerFootprint,
+    cells: &[(i32, i32)],
+    top: i32,
+    abs_terrain_offset: i32,
+) {
+    let replace_any: &[Block] = &[];
+    let max_inset = (tower.side - 1) / 2;
+    for layer in 0.. {
+        let inset = layer / 2;
+        if inset > max_inset {
+            break;
+        }
+        let y = top + 1 + layer;
+        for &(x, z) in cells {
+            // Hollow rings, except at the tip
+            let cell_inset = tower.inset(x, z);
+            if cell_inset == inset || (inset == max_inset && cell_inset >= inset) {
+                editor.set_block_absolute(
+                    LEAD_ROOF,
+                    x,
+                    y + abs_terrain_offset,
+                    z,
+                    None,
+                    Some(replace_any),
+                );
+            }
+        }
+    }
+}
+
+/// Draws the fieldstone dike of a churchyard along its outline: mossy and plain cobblestone
+/// two blocks high, capped with stone brick slabs.
+pub fn generate_churchyard_wall(editor: &mut WorldEditor, element: &ProcessedWay) {
+    for pair in element.nodes.windows(2) {
+        let (prev, cur) = (&pair[0], &pair[1]);
+        for (bx, _, bz) in bresenham_line(prev.x, 0, prev.z, cur.x, 0, cur.z) {
+            let mut rng = coord_rng(bx, bz, element.id);
+            for y in 1..=2 {
+                let stone = if rng.random_bool(0.3) {
+                    MOSSY_COBBLESTONE
+                } else {
+                    COBBLESTONE
+                };
+                editor.set_block(stone, bx, y, bz, None, None);
+            }
+            editor.set_block(STONE_BRICK_SLAB, bx, 3, bz, None, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_parser::ProcessedNode;
+    use crate::test_utilities::tags;
+
+    fn church(pairs: &[(&str, &str)]) -> ProcessedWay {
+        ProcessedWay {
+            id: 1,
+            nodes: vec![ProcessedNode {
+                id: 1,
+                tags: HashMap::new(),
+                x: 0,
+                z: 0,
+                exact: None,
+            }],
+            tags: tags(pairs),
+        }
+    }
+
+    /// A closed rectangular outline from (x, z) to (x + width, z + length)
+    fn outline(id: u64, (x, z): (i32, i32), (width, length): (i32, i32)) -> Vec<ProcessedNode> {
+        [(0, 0), (width, 0), (width, length), (0, length), (0, 0)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(dx, dz))| ProcessedNode {
+                id: id * 10 + i as u64,
+                tags: HashMap::new(),
+                x: x + dx,
+                z: z + dz,
+                exact: None,
+            })
+            .collect()
+    }
+
+    fn rectangle(width: i32, length: i32) -> Vec<(i32, i32)> {
+        (0..width)
+            .flat_map(|x| (0..length).map(move |z| (x, z)))
+            .collect()
+    }
+
+    #[test]
+    fn village_churches_are_told_apart_from_cathedrals_and_parts() {
+        assert!(is_village_church(&church(&[("building", "church")])));
+        assert!(is_village_church(&church(&[
+            ("building", "yes"),
+            ("amenity", "place_of_worship"),
+            ("religion", "christian"),
+        ])));
+        assert!(!is_village_church(&church(&[("building", "cathedral")])));
+        assert!(!is_village_church(&church(&[
+            ("building", "church"),
+            ("building:part", "yes"),
+        ])));
+        assert!(!is_village_church(&church(&[
+            ("building", "church"),
+            ("roof:shape", "dome"),
+        ])));
+        let mut town_church = church(&[("building", "church")]);
+        town_church.nodes = outline(1, (0, 0), (60, 25));
+        assert!(!is_village_church(&town_church));
+        assert_eq!(roof_block(&church(&[("roof:material", "lead")])), LEAD_ROOF);
+        assert_eq!(roof_block(&church(&[])), TILE_ROOF);
+    }
+
+    #[test]
+    fn churches_next_to_a_mapped_tower_get_none_of_their_own() {
+        let way = |id, pairs: &[(&str, &str)], corner, size| {
+            ProcessedElement::Way(ProcessedWay {
+                id,
+                nodes: outline(id, corner, size),
+                tags: tags(pairs),
+            })
+        };
+        let mut elements = vec![
+            way(1, &[("building", "church")], (10, 0), (24, 8)),
+            way(2, &[("building", "tower")], (2, 0), (8, 8)),
+            way(3, &[("building", "church")], (100, 0), (24, 8)),
+        ];
+        mark_mapped_towers(&mut elements);
+        assert!(elements[0].tags().contains_key(MAPPED_TOWER_KEY));
+        assert!(!elements[2].tags().contains_key(MAPPED_TOWER_KEY));
+    }
+
+    #[test]
+    fn towers_stand_at_the_west_end_of_long_naves() {
+        let tower = tower_footprint(&rectangle(24, 7)).unwrap();
+        assert_eq!(
+            tower,
+            TowerFootprint {
+                min_x: 0,
+                min_z: 0,
+                side: 7
+            }
+        );
+
+        // Wide naves get a tower narrower than themselves, centered on the nave
+        let tower = tower_footprint(&rectangle(11, 30)).unwrap();
+        assert_eq!((tower.min_x, tower.min_z, tower.side), (1, 0, 9));
+
+        // Square or tiny footprints get none
+        assert!(tower_footprint(&rectangle(10, 10)).is_none());
+        assert!(tower_footprint(&rectangle(12, 4)).is_none());
+    }
+}
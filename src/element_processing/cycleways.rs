@@ -0,0 +1,197 @@
+//! Danish cycle infrastructure: cycle tracks and lanes along roads, and cycleways.
+//!
+//! Most Danish roads in towns have a cycle track (cykelsti) on each side, behind a raised
+//! curb between the carriageway and the pavement; others have a cycle lane painted off the
+//! carriageway with a broad white line. Roads tagged `cycleway=track` or `cycleway=lane`,
+//! or the `:left`, `:right` and `:both` forms, get a strip of cycle surface along the
+//! tagged sides, behind a curb or a line. Where a side road joins, the strip crosses it in
+//! blue, as Danish cycle crossings are painted. Separately mapped `highway=cycleway` ways
+//! are drawn in the same surface, and in blue where tagged as a crossing.
+
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::element_processing::highways::HighwayConnectivityMap;
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use std::collections::HashMap;
+
+/// Surface of cycle tracks, lanes and cycleways
+pub const CYCLE_SURFACE: Block = POLISHED_BLACKSTONE;
+/// Blue paint of cycle crossings
+pub const CYCLE_CROSSING: Block = BLUE_CONCRETE;
+/// Curb between the carriageway and a cycle track, and its top a half block above the road
+const CURB: Block = SMOOTH_STONE;
+const CURB_TOP: Block = SMOOTH_STONE_SLAB;
+/// Line between the carriageway and a cycle lane
+const LANE_LINE: Block = WHITE_CONCRETE;
+/// Width of a cycle track or lane
+const CYCLE_WIDTH: i32 = 2;
+/// How far either side of a junction the cycle crossing reaches
+const CROSSING_REACH: i32 = 4;
+/// Road surfaces a cycle crossing is painted over
+const ROADS: &[Block] = &[BLACK_CONCRETE, GRAY_CONCRETE];
+
+/// How cyclists are kept apart from the traffic on one side of a road
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleSide {
+    /// Behind a raised curb
+    Track,
+    /// Behind a painted line
+    Lane,
+}
+
+impl CycleSide {
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "track" | "opposite_track" => Some(Self::Track),
+            "lane" | "opposite_lane" => Some(Self::Lane),
+            _ => None,
+        }
+    }
+
+    fn separator(self) -> Block {
+        match self {
+            Self::Track => CURB,
+            Self::Lane => LANE_LINE,
+        }
+    }
+}
+
+/// The cycle tracks or lanes on the left and right of a road, from its `cycleway` tags.
+/// The more specific `:left` and `:right` tags win over `:both` and plain `cycleway`.
+pub fn cycle_sides(tags: &HashMap<String, String>) -> (Option<CycleSide>, Option<CycleSide>) {
+    let side = |suffix: &str| {
+        [
+            format!("cycleway:{suffix}"),
+            "cycleway:both".into(),
+            "cycleway".into(),
+        ]
+        .iter()
+        .find_map(|key| tags.get(key))
+        .and_then(|value| CycleSide::from_value(value))
+    };
+    (side("left"), side("right"))
+}
+
+/// Whether a `highway=cycleway` way crosses a road
+pub fn is_cycle_crossing(tags: &HashMap<String, String>) -> bool {
+    tags.get("cycleway").is_some_and(|v| v == "crossing")
+        || tags.get("crossing").is_some_and(|v| v != "no")
+}
+
+/// Block of a cycle strip `offset` blocks out from the road edge, on a side road's crossing
+/// or not. Offset 0 is the curb or line; crossings carry the blue across it.
+fn strip_block(side: CycleSide, offset: i32, crossing: bool) -> Block {
+    match (offset, crossing) {
+        (_, true) => CYCLE_CROSSING,
+        (0, false) => side.separator(),
+        _ => CYCLE_SURFACE,
+    }
+}
+
+/// The nodes of a way where other highways end on it. The way's own ends are in the map
+/// too, so they only count where another highway ends there as well.
+fn junctions(way: &ProcessedWay, highway_connectivity: &HighwayConnectivityMap) -> Vec<(i32, i32)> {
+    let last = way.nodes.len().saturating_sub(1);
+    way.nodes
+        .iter()
+        .enumerate()
+        .filter(|&(index, node)| {
+            let own_ends = usize::from(index == 0 || index == last);
+            highway_connectivity
+                .get(&(node.x, node.z))
+                .is_some_and(|layers| layers.len() > own_ends)
+        })
+        .map(|(_, node)| (node.x, node.z))
+        .collect()
+}
+
+/// Draws the cycle tracks or lanes along a ground-level road `half_width` blocks either side
+/// of its middle, crossing the side roads that join it in blue
+pub fn generate_cycle_sides(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    half_width: i32,
+    highway_connectivity: &HighwayConnectivityMap,
+) {
+    let (left, right) = cycle_sides(&way.tags);
+    if left.is_none() && right.is_none() {
+        return;
+    }
+    let junctions = junctions(way, highway_connectivity);
+    for pair in way.nodes.windows(2) {
+        // From the unsnapped positions, so the strips run parallel to the road as mapped
+        let ((x0, z0), (x1, z1)) = (pair[0].position(), pair[1].position());
+        let (dx, dz) = (x1 - x0, z1 - z0);
+        let length = dx.hypot(dz);
+        if length == 0.0 {
+            continue;
+        }
+        // Right of the way direction, with z pointing south
+        let (right_x, right_z) = (-dz / length, dx / length);
+
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            let at_junction = junctions.iter().any(|&(jx, jz)| {
+                (jx - x).pow(2) + (jz - z).pow(2) <= CROSSING_REACH * CROSSING_REACH
+            });
+            for (side, sign) in [(left, -1.0), (right, 1.0)] {
+                let Some(side) = side else {
+                    continue;
+                };
+                // Half steps, so diagonal strips leave no gaps
+                for step in 0..=2 * CYCLE_WIDTH + 1 {
+                    let distance = (half_width + 1) as f64 + step as f64 / 2.0;
+                    let bx = x + (sign * right_x * distance).round() as i32;
+                    let bz = z + (sign * right_z * distance).round() as i32;
+                    // Crossings are painted over the side road's surface
+                    let crossing = at_junction && editor.check_for_block(bx, 0, bz, Some(ROADS));
+                    let block = strip_block(side, step / 2, crossing);
+                    let whitelist = if crossing { Some(ROADS) } else { None };
+                    editor.set_block(block, bx, 0, bz, whitelist, None);
+                    if block == CURB && editor.check_for_block(bx, 0, bz, Some(&[CURB])) {
+                        editor.set_block(CURB_TOP, bx, 1, bz, None, None);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn reads_cycle_tracks_and_lanes_per_side() {
+        let track = Some(CycleSide::Track);
+        let lane = Some(CycleSide::Lane);
+        assert_eq!(cycle_sides(&tags(&[("cycleway", "track")])), (track, track));
+        assert_eq!(
+            cycle_sides(&tags(&[("cycleway:both", "lane"), ("cycleway:left", "no")])),
+            (None, lane)
+        );
+        assert_eq!(
+            cycle_sides(&tags(&[("cycleway:right", "track")])),
+            (None, track)
+        );
+        assert_eq!(
+            cycle_sides(&tags(&[("cycleway", "lane"), ("cycleway:left", "track")])),
+            (track, lane)
+        );
+        assert_eq!(
+            cycle_sides(&tags(&[("cycleway", "separate")])),
+            (None, None)
+        );
+        assert!(is_cycle_crossing(&tags(&[("cycleway", "crossing")])));
+        assert!(!is_cycle_crossing(&tags(&[("surface", "asphalt")])));
+    }
+
+    #[test]
+    fn strips_sit_behind_a_curb_or_line_and_cross_side_roads_in_blue() {
+        assert_eq!(strip_block(CycleSide::Track, 0, false), CURB);
+        assert_eq!(strip_block(CycleSide::Lane, 0, false), LANE_LINE);
+        assert_eq!(strip_block(CycleSide::Track, 2, false), CYCLE_SURFACE);
+        assert_eq!(strip_block(CycleSide::Track, 0, true), CYCLE_CROSSING);
+    }
+}
@@ -0,0 +1,278 @@
+//! Ferry routes (`route=ferry`) and ferry terminals (`amenity=ferry_terminal`).
+//!
+//! Ferry routes are marked with lane buoys where they cross open water, red to port and
+//! green to starboard heading out from the start of the way, as the IALA region A buoyage
+//! around Denmark has it. At each end of a route, where it leaves the quay, a ramp runs out
+//! over the water to a roll-on roll-off ferry lying at its berth with its bow door open.
+//! The sea is only filled in after the other elements, so routes are placed after it.
+//! Terminals mapped as a node get a small waiting hall.
+
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+
+/// Blocks between the buoys along a ferry route
+const BUOY_SPACING: usize = 32;
+/// Distance of the buoys either side of the route
+const LANE_HALF_WIDTH: i32 = 8;
+/// How far along a route from its end the quay edge is looked for
+const BERTH_SEARCH: usize = 40;
+/// Length of the ramp from the quay to the ferry's bow
+const RAMP_LENGTH: i32 = 3;
+/// Half-width of the ramp
+const RAMP_HALF_WIDTH: i32 = 2;
+/// Length and half-beam of the ferry
+const FERRY_LENGTH: i32 = 30;
+const FERRY_HALF_BEAM: i32 = 5;
+/// Blocks over which the stern narrows
+const STERN_TAPER: i32 = 4;
+/// Hull below the waterline and the band along it
+const HULL: Block = BLUE_CONCRETE;
+const HULL_BAND: Block = WHITE_CONCRETE;
+/// Car deck
+const CAR_DECK: Block = GRAY_CONCRETE;
+/// Passenger decks above the cars
+const SUPERSTRUCTURE: Block = WHITE_CONCRETE;
+/// Funnel in the shipping line's colours
+const FUNNEL: Block = RED_CONCRETE;
+
+/// The blocks along a way, in order
+fn route_points(way: &ProcessedWay) -> Vec<(i32, i32)> {
+    let mut points: Vec<(i32, i32)> = Vec::new();
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            if points.last() != Some(&(x, z)) {
+                points.push((x, z));
+            }
+        }
+    }
+    points
+}
+
+/// Index of the first point over water, coming from the quay at the start of `water`. A
+/// route starting out on the water has no quay to berth at.
+fn berth_index(water: &[bool]) -> Option<usize> {
+    let first_water = water.iter().take(BERTH_SEARCH).position(|&wet| wet)?;
+    (first_water > 0).then_some(first_water)
+}
+
+/// Half-beam of the ferry `along` blocks from its bow, narrowing towards the stern
+fn hull_half_width(along: i32) -> i32 {
+    let from_stern = FERRY_LENGTH - 1 - along;
+    if from_stern < STERN_TAPER {
+        FERRY_HALF_BEAM - (STERN_TAPER - from_stern) / 2
+    } else {
+        FERRY_HALF_BEAM
+    }
+}
+
+/// Block of the ferry at `along` from its bow, `across` from its centre line and `y` above
+/// the water. The bow faces the ramp with its door open; the passenger decks sit over the
+/// after part of the car deck with the funnel on top.
+fn ferry_block(along: i32, across: i32, y: i32) -> Option<Block> {
+    if !(0..FERRY_LENGTH).contains(&along) || across.abs() > hull_half_width(along) {
+        return None;
+    }
+    let side = across.abs() == hull_half_width(along);
+    let superstructure = (FERRY_LENGTH / 3..FERRY_LENGTH - STERN_TAPER).contains(&along)
+        && across.abs() < FERRY_HALF_BEAM;
+    let outline = across.abs() == FERRY_HALF_BEAM - 1
+        || along == FERRY_LENGTH / 3
+        || along == FERRY_LENGTH - STERN_TAPER - 1;
+    match y {
+        -1 => Some(HULL),
+        0 => Some(HULL_BAND),
+        1 => Some(CAR_DECK),
+        // Bulwarks along the car deck, open at the bow door
+        2 if side && along > 0 => Some(SUPERSTRUCTURE),
+        3..=5 if superstructure && outline => Some(if y == 4 { GLASS } else { SUPERSTRUCTURE }),
+        6 if superstructure => Some(SUPERSTRUCTURE),
+        7..=8 if along == FERRY_LENGTH * 2 / 3 && across == 0 => Some(FUNNEL),
+        _ => None,
+    }
+}
+
+/// A berth at a quay: where the ramp starts, and which way it runs out over the water
+struct Berth {
+    x: i32,
+    z: i32,
+    dir_x: f64,
+    dir_z: f64,
+}
+
+impl Berth {
+    /// Position of (`x`, `z`) along the berth and across it, rounded to blocks
+    fn local(&self, x: i32, z: i32) -> (i32, i32) {
+        let (dx, dz) = ((x - self.x) as f64, (z - self.z) as f64);
+        let along = dx * self.dir_x + dz * self.dir_z;
+        let across = -dx * self.dir_z + dz * self.dir_x;
+        (along.round() as i32, across.round() as i32)
+    }
+
+    /// Every block within `reach` blocks of the berth, with its position along and across
+    fn cells(&self, reach: i32) -> Vec<(i32, i32, i32, i32)> {
+        let mut cells = Vec::new();
+        for x in self.x - reach..=self.x + reach {
+            for z in self.z - reach..=self.z + reach {
+                let (along, across) = self.local(x, z);
+                cells.push((x, z, along, across));
+            }
+        }
+        cells
+    }
+}
+
+/// Builds a ramp out from the quay where a route leaves it, and a ferry lying at the end of
+/// the ramp if it fits on the water there
+fn generate_berth(editor: &mut WorldEditor, points: &[(i32, i32)]) -> bool {
+    let water: Vec<bool> = points
+        .iter()
+        .take(BERTH_SEARCH)
+        .map(|&(x, z)| editor.check_for_block(x, 0, z, Some(&[WATER])))
+        .collect();
+    let Some(index) = berth_index(&water) else {
+        return false;
+    };
+    let (x, z) = points[index];
+    let (ahead_x, ahead_z) = points[(index + FERRY_LENGTH as usize).min(points.len() - 1)];
+    let (dx, dz) = ((ahead_x - x) as f64, (ahead_z - z) as f64);
+    let length = dx.hypot(dz);
+    if length == 0.0 {
+        return false;
+    }
+    let berth = Berth {
+        x,
+        z,
+        dir_x: dx / length,
+        dir_z: dz / length,
+    };
+    let cells = berth.cells(RAMP_LENGTH + FERRY_LENGTH + FERRY_HALF_BEAM);
+
+    for &(x, z, along, across) in &cells {
+        if (0..RAMP_LENGTH).contains(&along) && across.abs() <= RAMP_HALF_WIDTH {
+            editor.set_block(STONE_BRICKS, x, 0, z, Some(&[WATER]), None);
+            if along == RAMP_LENGTH - 1 {
+                editor.set_block(SMOOTH_STONE_SLAB, x, 1, z, None, None);
+            }
+        }
+    }
+
+    // The ferry only berths where the whole of it lies on the water
+    let afloat = cells.iter().all(|&(x, z, along, across)| {
+        ferry_block(along - RAMP_LENGTH, across, 0).is_none()
+            || editor.check_for_block(x, 0, z, Some(&[WATER]))
+    });
+    if !afloat {
+        return false;
+    }
+    for &(x, z, along, across) in &cells {
+        for y in -1..=8 {
+            if let Some(block) = ferry_block(along - RAMP_LENGTH, across, y) {
+                editor.set_block(block, x, y, z, Some(&[WATER]), None);
+            }
+        }
+    }
+    true
+}
+
+/// Lane buoys either side of a ferry route, wherever they fall on open water
+fn place_buoys(editor: &mut WorldEditor, way: &ProcessedWay) {
+    let mut distance = 0;
+    for pair in way.nodes.windows(2) {
+        let (dx, dz) = (
+            (pair[1].x - pair[0].x) as f64,
+            (pair[1].z - pair[0].z) as f64,
+        );
+        let length = dx.hypot(dz);
+        if length == 0.0 {
+            continue;
+        }
+        // Port is left of the way direction
+        let offset_x = (dz / length * LANE_HALF_WIDTH as f64).round() as i32;
+        let offset_z = (-dx / length * LANE_HALF_WIDTH as f64).round() as i32;
+
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            distance += 1;
+            if distance % BUOY_SPACING != 0 {
+                continue;
+            }
+            for (buoy_x, buoy_z, buoy) in [
+                (x + offset_x, z + offset_z, RED_WOOL),
+                (x - offset_x, z - offset_z, GREEN_WOOL),
+            ] {
+                if editor.check_for_block(buoy_x, 0, buoy_z, Some(&[WATER])) {
+                    editor.set_block(buoy, buoy_x, 0, buoy_z, Some(&[WATER]), None);
+                    editor.set_block(buoy, buoy_x, 1, buoy_z, None, None);
+                }
+            }
+        }
+    }
+}
+
+/// Marks ferry routes with buoys and berths a ferry at each end of them. Returns how many
+/// ferries were berthed.
+pub fn generate_ferries(editor: &mut WorldEditor, routes: &[ProcessedWay]) -> usize {
+    let mut berthed = 0;
+    for way in routes {
+        place_buoys(editor, way);
+        let mut points = route_points(way);
+        if points.len() < 2 {
+            continue;
+        }
+        berthed += usize::from(generate_berth(editor, &points));
+        points.reverse();
+        berthed += usize::from(generate_berth(editor, &points));
+    }
+    berthed
+}
+
+/// A small waiting hall for a ferry terminal mapped as a node
+pub fn generate_terminal(editor: &mut WorldEditor, x: i32, z: i32) {
+    let (half_x, half_z) = (4, 3);
+    for dx in -half_x..=half_x {
+        for dz in -half_z..=half_z {
+            let wall = dx.abs() == half_x || dz.abs() == half_z;
+            editor.set_block(LIGHT_GRAY_CONCRETE, x + dx, 4, z + dz, None, None);
+            // Doorway in the middle of the south front
+            if !wall || (dx == 0 && dz == half_z) {
+                continue;
+            }
+            // Windows along the front and back
+            let window = dx.abs() < half_x && dz.abs() == half_z;
+            for y in 1..=3 {
+                let block = if y == 2 && window {
+                    GLASS_PANE
+                } else {
+                    SUPERSTRUCTURE
+                };
+                editor.set_block(block, x + dx, y, z + dz, None, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ferries_berth_where_the_route_leaves_the_quay() {
+        assert_eq!(berth_index(&[false, false, true, true]), Some(2));
+        // Routes starting out on the water and routes that never reach it have no berth
+        assert_eq!(berth_index(&[true, true]), None);
+        assert_eq!(berth_index(&[false; 3]), None);
+    }
+
+    #[test]
+    fn ferry_opens_its_bow_onto_the_ramp() {
+        assert_eq!(ferry_block(0, FERRY_HALF_BEAM, 2), None);
+        assert_eq!(ferry_block(0, 0, 1), Some(CAR_DECK));
+        assert_eq!(ferry_block(1, FERRY_HALF_BEAM, 2), Some(SUPERSTRUCTURE));
+        assert_eq!(ferry_block(FERRY_LENGTH, 0, 1), None);
+        // The stern narrows
+        assert_eq!(hull_half_width(FERRY_LENGTH - 1), FERRY_HALF_BEAM - 2);
+        assert_eq!(ferry_block(FERRY_LENGTH - 1, FERRY_HALF_BEAM, 0), None);
+        assert_eq!(ferry_block(FERRY_LENGTH * 2 / 3, 0, 8), Some(FUNNEL));
+    }
+}
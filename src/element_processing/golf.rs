@@ -0,0 +1,127 @@
+//! Features of golf courses mapped with `golf=*`.
+//!
+//! The course itself (`leisure=golf_course`) is plain grass; its holes are mapped as
+//! separate areas on top of it. Fairways are mown in stripes, greens are smooth with a flag
+//! in the hole, tees are levelled boxes and bunkers are sand hollows a block below the turf.
+//! Features are laid over the course grass, whichever of the two is processed first.
+
+use crate::args::Args;
+use crate::block_definitions::*;
+use crate::deterministic_rng::coord_rng;
+use crate::floodfill_cache::FloodFillCache;
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Putting green
+const GREEN: Block = LIME_CONCRETE;
+/// Tee box
+const TEE: Block = MOSS_BLOCK;
+/// Every other band of a mown fairway
+const MOWN_STRIPE: Block = MOSS_BLOCK;
+/// Width of the mowing stripes across a fairway
+const STRIPE_WIDTH: i32 = 4;
+/// Height of the flag pole on a green
+const FLAG_HEIGHT: i32 = 3;
+/// Chance of a tuft of long grass on a block of rough
+const ROUGH_GRASS_SHARE: f64 = 0.3;
+
+/// Whether a fairway block lies in a darker mowing stripe. The stripes run diagonally, so
+/// they show on fairways in every direction.
+fn in_mown_stripe(x: i32, z: i32) -> bool {
+    (x + z).div_euclid(STRIPE_WIDTH).rem_euclid(2) == 1
+}
+
+/// Block of `area` closest to its centroid, where the flag of a green stands
+fn flag_site(area: &[(i32, i32)]) -> Option<(i32, i32)> {
+    if area.is_empty() {
+        return None;
+    }
+    let count = area.len() as i64;
+    let cx = area.iter().map(|&(x, _)| x as i64).sum::<i64>() / count;
+    let cz = area.iter().map(|&(_, z)| z as i64).sum::<i64>() / count;
+    area.iter()
+        .copied()
+        .min_by_key(|&(x, z)| (x as i64 - cx).pow(2) + (z as i64 - cz).pow(2))
+}
+
+/// Whether a way is one of the golf areas laid here. Other `golf=*` features, such as
+/// water hazards and the lines of holes, are drawn by their other tags or not at all.
+pub fn is_golf_area(tags: &HashMap<String, String>) -> bool {
+    tags.get("golf")
+        .is_some_and(|g| matches!(g.as_str(), "fairway" | "green" | "tee" | "bunker" | "rough"))
+}
+
+/// Lays a `golf=fairway`, `green`, `tee`, `bunker` or `rough` area
+pub fn generate_golf(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+) {
+    if !is_golf_area(&way.tags) {
+        return;
+    }
+    let feature = way.tags["golf"].as_str();
+    let area = flood_fill_cache.get_or_compute(way, args.timeout.as_ref());
+
+    for &(x, z) in &area {
+        match feature {
+            "fairway" => {
+                let turf = if in_mown_stripe(x, z) {
+                    MOWN_STRIPE
+                } else {
+                    GRASS_BLOCK
+                };
+                editor.set_block(turf, x, 0, z, Some(&[GRASS_BLOCK]), None);
+            }
+            "green" => editor.set_block(GREEN, x, 0, z, Some(&[GRASS_BLOCK]), None),
+            "tee" => editor.set_block(TEE, x, 0, z, Some(&[GRASS_BLOCK]), None),
+            "bunker" => {
+                // Kept open so the ground pass does not fill the hollow
+                editor.set_block(CAVE_AIR, x, 0, z, Some(&[GRASS_BLOCK]), None);
+                editor.set_block(SAND, x, -1, z, Some(&[GRASS_BLOCK, DIRT]), None);
+            }
+            _ => {
+                editor.set_block(GRASS_BLOCK, x, 0, z, None, None);
+                if coord_rng(x, z, way.id).random_bool(ROUGH_GRASS_SHARE) {
+                    editor.set_block(TALL_GRASS_BOTTOM, x, 1, z, None, None);
+                    editor.set_block(TALL_GRASS_TOP, x, 2, z, None, None);
+                }
+            }
+        }
+    }
+
+    if feature == "green" {
+        if let Some((x, z)) = flag_site(&area) {
+            for y in 1..=FLAG_HEIGHT {
+                editor.set_block(IRON_BARS, x, y, z, None, None);
+            }
+            editor.set_block(RED_WOOL, x + 1, FLAG_HEIGHT, z, None, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_stands_in_the_middle_of_the_green() {
+        let green: Vec<(i32, i32)> = (0..5).flat_map(|x| (0..3).map(move |z| (x, z))).collect();
+        assert_eq!(flag_site(&green), Some((2, 1)));
+        // On a crescent the flag stays on the green
+        let crescent = [(0, 0), (1, 0), (2, 0), (0, 1), (0, 2), (1, 2), (2, 2)];
+        assert!(crescent.contains(&flag_site(&crescent).unwrap()));
+        assert_eq!(flag_site(&[]), None);
+    }
+
+    #[test]
+    fn mowing_stripes_alternate() {
+        assert!(!in_mown_stripe(0, 0));
+        assert!(in_mown_stripe(STRIPE_WIDTH, 0));
+        assert!(!in_mown_stripe(STRIPE_WIDTH, STRIPE_WIDTH));
+        assert!(in_mown_stripe(-1, 0));
+    }
+}
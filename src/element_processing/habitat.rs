@@ -0,0 +1,100 @@
+//! Bird habitat props along brackish coasts and lagoons.
+//!
+//! - Reed fringes along the shore of brackish water (lagoons, tidal and salt water)
+//! - Nesting islets (`place=islet`) with tall grass and birds; chickens and parrots stand in
+//!   for the gulls and terns Minecraft does not have
+//! - Observation hides at `leisure=bird_hide` nodes: a small spruce hut with a viewing slit
+
+use crate::args::Args;
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::deterministic_rng::{coord_rng, element_rng};
+use crate::floodfill_cache::FloodFillCache;
+use crate::osm_parser::{ProcessedNode, ProcessedWay};
+use crate::world_editor::WorldEditor;
+use rand::prelude::IndexedRandom;
+use rand::Rng;
+
+/// Share of shore blocks that grow reeds
+const REED_CHANCE: f64 = 0.6;
+/// One bird per this many islet blocks, capped at `MAX_ISLET_BIRDS`
+const ISLET_BLOCKS_PER_BIRD: usize = 40;
+const MAX_ISLET_BIRDS: usize = 6;
+
+/// Lines the outer shore of a brackish water body with reeds rooted in mud.
+pub fn generate_reed_fringe(
+    editor: &mut WorldEditor,
+    rings: &[Vec<ProcessedNode>],
+    element_id: u64,
+) {
+    for ring in rings {
+        for pair in ring.windows(2) {
+            for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+                if !editor.check_for_block(x, 0, z, Some(&[WATER]))
+                    || !coord_rng(x, z, element_id).random_bool(REED_CHANCE)
+                {
+                    continue;
+                }
+                editor.set_block(MUD, x, 0, z, Some(&[WATER]), None);
+                editor.set_block(TALL_GRASS_BOTTOM, x, 1, z, None, None);
+                editor.set_block(TALL_GRASS_TOP, x, 2, z, None, None);
+            }
+        }
+    }
+}
+
+/// Tall grass and a few nesting birds on a small island.
+pub fn generate_nesting_islet(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+) {
+    let area = flood_fill_cache.get_or_compute(way, args.timeout.as_ref());
+    if area.is_empty() {
+        return;
+    }
+
+    let mut rng = element_rng(way.id);
+    for &(x, z) in &area {
+        if rng.random_bool(0.4) {
+            editor.set_block(GRASS, x, 1, z, None, None);
+        }
+    }
+
+    let birds = (area.len() / ISLET_BLOCKS_PER_BIRD).clamp(1, MAX_ISLET_BIRDS);
+    for (i, &(x, z)) in area.choose_multiple(&mut rng, birds).enumerate() {
+        let bird = if i % 3 == 2 {
+            "minecraft:parrot"
+        } else {
+            "minecraft:chicken"
+        };
+        editor.add_entity(bird, x, 1, z, None);
+    }
+}
+
+/// A 5x5 spruce hide with a bench and a viewing slit facing north, entered from the south.
+pub fn generate_bird_hide(editor: &mut WorldEditor, node: &ProcessedNode) {
+    for dx in -2i32..=2 {
+        for dz in -2i32..=2 {
+            let (x, z) = (node.x + dx, node.z + dz);
+            editor.set_block(SPRUCE_PLANKS, x, 0, z, None, None);
+            editor.set_block(OAK_SLAB, x, 4, z, None, None);
+
+            let corner = dx.abs() == 2 && dz.abs() == 2;
+            let wall = dx.abs() == 2 || dz.abs() == 2;
+            for y in 1..=3 {
+                let slit = dz == -2 && dx.abs() <= 1 && y == 2;
+                let door = dz == 2 && dx == 0 && y <= 2;
+                if corner {
+                    editor.set_block(SPRUCE_LOG, x, y, z, None, None);
+                } else if wall && !slit && !door {
+                    editor.set_block(SPRUCE_PLANKS, x, y, z, None, None);
+                }
+            }
+        }
+    }
+    for dx in -1..=1 {
+        editor.set_block(OAK_SLAB, node.x + dx, 1, node.z - 1, None, None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Harbours and marinas.
+//!
+//! Small harbours line the Danish coast: a basin dredged deeper than the water around it,
+//! stone quays dropping straight into it, piers on piles and a marina of moored sailboats.
+//! Harbour basins are dug `HARBOUR_DEPTH` blocks deep by the water pass; quay walls and
+//! piles reach down through the water to its bed, and mooring bollards stand along the
+//! quays and the edges of piers.
+
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::deterministic_rng::coord_rng;
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// Blocks of water in harbour basins and docks
+pub const HARBOUR_DEPTH: i32 = 3;
+/// Blocks between mooring bollards along quays and piers
+pub const BOLLARD_SPACING: usize = 8;
+/// Deepest a quay wall or pile reaches through the water
+const MAX_FOUNDATION_DEPTH: i32 = 8;
+/// Mooring bollard
+const BOLLARD: Block = COBBLESTONE_WALL;
+/// Top course of a quay wall
+const QUAY_COPING: Block = SMOOTH_STONE;
+/// Face of a quay wall
+const QUAY_WALL: Block = STONE_BRICKS;
+/// Length of a moored sailboat, bow to stern
+const BOAT_LENGTH: i32 = 5;
+/// Beam of a moored sailboat
+const BOAT_BEAM: i32 = 2;
+/// Blocks from one berth to the next along a row, leaving a gap between the hulls
+const BERTH_PITCH: i32 = BOAT_BEAM + 1;
+/// Blocks from one row of berths to the next, leaving a fairway between them
+const ROW_PITCH: i32 = BOAT_LENGTH + 3;
+/// Chance of a berth having a boat in it
+const OCCUPIED_SHARE: f64 = 0.7;
+/// Height of a sailboat's mast above the water
+const MAST_HEIGHT: i32 = 7;
+
+/// Whether a pier or quay is built of concrete or stone rather than timber
+pub fn is_concrete(tags: &HashMap<String, String>) -> bool {
+    ["material", "surface"].iter().any(|key| {
+        tags.get(*key).is_some_and(|v| {
+            matches!(
+                v.as_str(),
+                "concrete" | "stone" | "paved" | "asphalt" | "reinforced_concrete"
+            )
+        })
+    })
+}
+
+/// Sets `top` at the ground or water surface at (x, z) and `body` below it for as long as
+/// there is water, so walls and piles stand on the bed
+pub fn sink_foundation(editor: &mut WorldEditor, top: Block, body: Block, x: i32, z: i32) {
+    editor.set_block(top, x, 0, z, Some(&[WATER]), None);
+    for y in (-MAX_FOUNDATION_DEPTH..0).rev() {
+        if !editor.check_for_block(x, y, z, Some(&[WATER])) {
+            break;
+        }
+        editor.set_block(body, x, y, z, Some(&[WATER]), None);
+    }
+}
+
+/// A mooring bollard standing `y` blocks above the ground
+pub fn place_bollard(editor: &mut WorldEditor, x: i32, y: i32, z: i32) {
+    editor.set_block(BOLLARD, x, y, z, None, None);
+}
+
+/// Draws a quay (`man_made=quay`) along its way: a stone wall from the quayside down to the
+/// harbour bed with bollards along its top
+pub fn generate_quay(editor: &mut WorldEditor, way: &ProcessedWay) {
+    let mut along = 0;
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            sink_foundation(editor, QUAY_COPING, QUAY_WALL, x, z);
+            if along % BOLLARD_SPACING == BOLLARD_SPACING / 2 {
+                place_bollard(editor, x, 1, z);
+            }
+            along += 1;
+        }
+    }
+}
+
+/// Corners of the berths that fit wholly on `water`. Berths lie on a fixed grid, so boats
+/// in neighbouring marinas and tiles line up.
+fn berths(water: &HashSet<(i32, i32)>) -> Vec<(i32, i32)> {
+    let mut berths: Vec<(i32, i32)> = water
+        .iter()
+        .copied()
+        .filter(|&(x, z)| x.rem_euclid(BERTH_PITCH) == 0 && z.rem_euclid(ROW_PITCH) == 0)
+        .filter(|&(x, z)| {
+            (0..BOAT_BEAM).all(|dx| (0..BOAT_LENGTH).all(|dz| water.contains(&(x + dx, z + dz))))
+        })
+        .collect();
+    berths.sort_unstable();
+    berths
+}
+
+/// A sailboat moored at the berth with its corner at (x, z), bow to the south: a white hull
+/// at the waterline, a cabin, and a mast with its mainsail set
+fn place_sailboat(editor: &mut WorldEditor, x: i32, z: i32, hull: Block) {
+    for dx in 0..BOAT_BEAM {
+        for dz in 0..BOAT_LENGTH {
+            editor.set_block(hull, x + dx, 0, z + dz, Some(&[WATER]), None);
+        }
+        editor.set_block(OAK_SLAB, x + dx, 1, z, None, None);
+        for dz in 1..=2 {
+            editor.set_block(WHITE_CONCRETE, x + dx, 1, z + dz, None, None);
+        }
+    }
+    for y in 1..=MAST_HEIGHT {
+        editor.set_block(IRON_BARS, x, y, z + 3, None, None);
+    }
+    // The mainsail rises aft of the mast over the cabin, narrowing towards the stern
+    for (dz, top) in [(1, MAST_HEIGHT - 3), (2, MAST_HEIGHT - 1)] {
+        for y in 2..=top {
+            editor.set_block(WHITE_WOOL, x, y, z + dz, None, None);
+        }
+    }
+}
+
+/// Builds a marina (`leisure=marina`) once the sea is in: the land of the boat yard is paved
+/// as hardstanding and sailboats are moored on the water in rows across its basin
+pub fn generate_marina(editor: &mut WorldEditor, area: &[(i32, i32)], element_id: u64) {
+    let (water, land): (HashSet<(i32, i32)>, Vec<(i32, i32)>) = area
+        .iter()
+        .copied()
+        .partition(|&(x, z)| editor.check_for_block(x, 0, z, Some(&[WATER])));
+    for (x, z) in land {
+        editor.set_block(GRAY_CONCRETE, x, 0, z, Some(&[GRASS_BLOCK]), None);
+    }
+    for (x, z) in berths(&water) {
+        let mut rng = coord_rng(x, z, element_id);
+        if !rng.random_bool(OCCUPIED_SHARE) {
+            continue;
+        }
+        let hull = if rng.random_bool(0.2) {
+            BLUE_CONCRETE
+        } else {
+            WHITE_CONCRETE
+        };
+        place_sailboat(editor, x, z, hull);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn berths_fit_inside_the_basin() {
+        let basin: HashSet<(i32, i32)> =
+            (0..10).flat_map(|x| (0..20).map(move |z| (x, z))).collect();
+        let found = berths(&basin);
+        // Three berths across the basin, two rows along it; the third row would run out of
+        // water before the bow
+        assert_eq!(found, vec![(0, 0), (0, 8), (3, 0), (3, 8), (6, 0), (6, 8)]);
+        assert!(berths(&HashSet::from([(0, 0), (1, 0)])).is_empty());
+    }
+
+    #[test]
+    fn concrete_piers_are_told_apart_from_timber_ones() {
+        assert!(is_concrete(&tags(&[("material", "concrete")])));
+        assert!(is_concrete(&tags(&[("surface", "paved")])));
+        assert!(!is_concrete(&tags(&[("material", "wood")])));
+        assert!(!is_concrete(&tags(&[])));
+    }
+}
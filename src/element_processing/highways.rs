@@ -2,16 +2,37 @@ use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
 use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
+use crate::element_processing::cycleways;
+use crate::element_processing::plazas;
+use crate::element_processing::steps;
+use crate::element_processing::street_details;
+use crate::element_processing::tunnels;
+use crate::element_processing::waterways::NAVIGABLE_CLEARANCE;
 use crate::floodfill_cache::{CoordinateBitmap, FloodFillCache};
 use crate::osm_parser::{ProcessedElement, ProcessedWay};
+use crate::world_editor::sign_text::{fill_lines, sign_line, wrap_words};
 use crate::world_editor::WorldEditor;
+use colored::Colorize;
 use std::collections::HashMap;
 
+/// A highway ending at a node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HighwayEnd {
+    /// Layer of the highway, with negative layers counted as ground level
+    pub layer: i32,
+    /// Whether the highway is a road for traffic rather than a footpath
+    pub road: bool,
+}
+
 /// Type alias for highway connectivity map
-pub type HighwayConnectivityMap = HashMap<(i32, i32), Vec<i32>>;
+pub type HighwayConnectivityMap = HashMap<(i32, i32), Vec<HighwayEnd>>;
 
 /// Minimum terrain dip (in blocks) below max endpoint elevation to classify a bridge as valley-spanning
-const VALLEY_BRIDGE_THRESHOLD: i32 = 7;
+const VALLEY_BRIDGE_THRESHOLD: i32 = 3;
+/// Blocks of clearance per layer between a level bridge deck and the valley floor or water
+const BRIDGE_CLEARANCE: i32 = 5;
+/// Bridge ramps rise one block per this many blocks
+const BRIDGE_RAMP_RUN: usize = 2;
 
 /// Generates highways with elevation support based on layer tags and connectivity analysis
 pub fn generate_highways(
@@ -20,6 +41,7 @@ pub fn generate_highways(
     args: &Args,
     highway_connectivity: &HighwayConnectivityMap,
     flood_fill_cache: &FloodFillCache,
+    navigable_channels: Option<&CoordinateBitmap>,
 ) {
     generate_highways_internal(
         editor,
@@ -27,16 +49,17 @@ pub fn generate_highways(
         args,
         highway_connectivity,
         flood_fill_cache,
+        navigable_channels,
     );
 }
 
 /// Build a connectivity map for highway endpoints to determine where slopes are needed.
 pub fn build_highway_connectivity_map(elements: &[ProcessedElement]) -> HighwayConnectivityMap {
-    let mut connectivity_map: HashMap<(i32, i32), Vec<i32>> = HashMap::new();
+    let mut connectivity_map: HighwayConnectivityMap = HashMap::new();
 
     for element in elements {
         if let ProcessedElement::Way(way) = element {
-            if way.tags.contains_key("highway") {
+            if let Some(highway_type) = way.tags.get("highway") {
                 let layer_value = way
                     .tags
                     .get("layer")
@@ -45,6 +68,10 @@ pub fn build_highway_connectivity_map(elements: &[ProcessedElement]) -> HighwayC
 
                 // Treat negative layers as ground level (0) for connectivity
                 let layer_value = if layer_value < 0 { 0 } else { layer_value };
+                let end = HighwayEnd {
+                    layer: layer_value,
+                    road: !is_footpath(highway_type),
+                };
 
                 // Add connectivity for start and end nodes
                 if !way.nodes.is_empty() {
@@ -54,14 +81,8 @@ pub fn build_highway_connectivity_map(elements: &[ProcessedElement]) -> HighwayC
                     let start_coord = (start_node.x, start_node.z);
                     let end_coord = (end_node.x, end_node.z);
 
-                    connectivity_map
-                        .entry(start_coord)
-                        .or_default()
-                        .push(layer_value);
-                    connectivity_map
-                        .entry(end_coord)
-                        .or_default()
-                        .push(layer_value);
+                    connectivity_map.entry(start_coord).or_default().push(end);
+                    connectivity_map.entry(end_coord).or_default().push(end);
                 }
             }
         }
@@ -74,6 +95,7 @@ pub fn collect_ground_highway_mask(
     elements: &[ProcessedElement],
     xzbbox: &XZBBox,
     scale: f64,
+    mapped_widths: bool,
 ) -> CoordinateBitmap {
     let mut mask = CoordinateBitmap::new(xzbbox);
 
@@ -86,7 +108,7 @@ pub fn collect_ground_highway_mask(
             continue;
         }
 
-        let Some(mut radius) = highway_mask_radius(&way.tags, scale) else {
+        let Some(mut radius) = highway_mask_radius(&way.tags, scale, mapped_widths) else {
             continue;
         };
 
@@ -114,12 +136,12 @@ pub fn collect_ground_highway_mask(
     mask
 }
 
-fn is_ground_level_highway(tags: &HashMap<String, String>) -> bool {
+pub(crate) fn is_ground_level_highway(tags: &HashMap<String, String>) -> bool {
     if tags.get("indoor").is_some_and(|v| v == "yes") {
         return false;
     }
 
-    if tags.get("bridge").is_some_and(|v| v != "no") {
+    if tags.get("bridge").is_some_and(|v| v != "no") || tunnels::is_tunnel(tags) {
         return false;
     }
 
@@ -141,10 +163,17 @@ fn is_ground_level_highway(tags: &HashMap<String, String>) -> bool {
         .is_some_and(|layer| layer > 0 || layer < 0)
 }
 
-fn highway_mask_radius(tags: &HashMap<String, String>, scale: f64) -> Option<i32> {
+/// Half-width in blocks of a highway; with `mapped_widths` (`--geodanmark-roads`) a mapped
+/// `width` overrides the default for the road class.
+pub(crate) fn highway_mask_radius(
+    tags: &HashMap<String, String>,
+    scale: f64,
+    mapped_widths: bool,
+) -> Option<i32> {
     let highway_type = tags.get("highway")?;
     let mut block_range = match highway_type.as_str() {
-        "footway" | "pedestrian" | "path" | "track" | "steps" => 1,
+        "footway" | "path" | "track" | "steps" | "cycleway" => 1,
+        "pedestrian" => 2,
         "motorway" | "primary" | "trunk" => 5,
         "secondary" => 4,
         "service" => 2,
@@ -163,6 +192,9 @@ fn highway_mask_radius(tags: &HashMap<String, String>, scale: f64) -> Option<i32
             }
         }
     };
+    if let Some(range) = tagged_half_width(tags).filter(|_| mapped_widths) {
+        block_range = range;
+    }
 
     if scale < 1.0 {
         block_range = ((block_range as f64) * scale).floor() as i32;
@@ -171,25 +203,41 @@ fn highway_mask_radius(tags: &HashMap<String, String>, scale: f64) -> Option<i32
     Some(block_range.max(1))
 }
 
+/// Half-width in blocks, at scale 1, of a road whose `width` tag gives its width in meters
+fn tagged_half_width(tags: &HashMap<String, String>) -> Option<i32> {
+    let width: f64 = tags
+        .get("width")?
+        .trim_end_matches('m')
+        .trim()
+        .parse()
+        .ok()?;
+    (width > 0.0).then(|| ((width - 1.0) / 2.0).round().clamp(1.0, 12.0) as i32)
+}
+
+/// Material of a road with an unpaved `surface`; paved roads keep their class's block
+fn unpaved_surface_block(surface: &str) -> Option<Block> {
+    match surface {
+        "unpaved" | "gravel" | "fine_gravel" | "compacted" => Some(GRAVEL),
+        "dirt" | "ground" | "earth" => Some(DIRT_PATH),
+        "sand" => Some(SAND),
+        _ => None,
+    }
+}
+
 /// Internal function that generates highways with connectivity context for elevation handling
 fn generate_highways_internal(
     editor: &mut WorldEditor,
     element: &ProcessedElement,
     args: &Args,
-    highway_connectivity: &HashMap<(i32, i32), Vec<i32>>, // Maps node coordinates to list of layers that connect to this node
+    highway_connectivity: &HighwayConnectivityMap, // Highways ending at each node
     flood_fill_cache: &FloodFillCache,
+    navigable_channels: Option<&CoordinateBitmap>,
 ) {
     if let Some(highway_type) = element.tags().get("highway") {
         if highway_type == "street_lamp" {
             // Handle street lamps
             if let ProcessedElement::Node(first_node) = element {
-                let x: i32 = first_node.x;
-                let z: i32 = first_node.z;
-                editor.set_block(COBBLESTONE_WALL, x, 1, z, None, None);
-                for dy in 2..=4 {
-                    editor.set_block(OAK_FENCE, x, dy, z, None, None);
-                }
-                editor.set_block(GLOWSTONE, x, 5, z, None, None);
+                street_details::generate_lamp_post(editor, first_node.x, first_node.z);
             }
         } else if highway_type == "crossing" {
             // Handle traffic signals for crossings
@@ -209,6 +257,10 @@ fn generate_highways_internal(
                     }
                 }
             }
+        } else if highway_type == "mini_roundabout" {
+            if let ProcessedElement::Node(node) = element {
+                street_details::generate_mini_roundabout(editor, node.x, node.z);
+            }
         } else if highway_type == "bus_stop" {
             // Handle bus stops
             if let ProcessedElement::Node(node) = element {
@@ -220,6 +272,17 @@ fn generate_highways_internal(
 
                 editor.set_block(WHITE_WOOL, x, 4, z, None, None);
                 editor.set_block(WHITE_WOOL, x + 1, 4, z, None, None);
+
+                if node.tags.get("shelter").is_some_and(|v| v == "yes") {
+                    street_details::generate_bus_shelter(editor, x, z);
+                }
+                if let Some(route_ref) = node.tags.get("route_ref") {
+                    let name = sign_line(node.tags.get("name").map_or("", String::as_str));
+                    // Route numbers ("1A;5C;350S") over the last two lines
+                    let routes = route_ref.split(';').map(str::trim);
+                    let [line3, line4, ..] = fill_lines(wrap_words(routes, 2));
+                    editor.set_sign(name, "Bus".to_string(), line3, line4, x, 1, z - 1, 0);
+                }
             }
         } else if element
             .tags()
@@ -253,8 +316,22 @@ fn generate_highways_internal(
             let filled_area: Vec<(i32, i32)> =
                 flood_fill_cache.get_or_compute(way, args.timeout.as_ref());
 
-            for (x, z) in filled_area {
-                editor.set_block(surface_block, x, 0, z, None, None);
+            // Pedestrian squares are paved in a pattern chosen by their surface
+            let surface = element.tags().get("surface").map(String::as_str);
+            let paving = if highway_type == "pedestrian" {
+                plazas::Paving::from_surface(surface)
+            } else {
+                None
+            };
+            for &(x, z) in &filled_area {
+                let block = match paving {
+                    Some(paving) => paving.block(x, z),
+                    None => surface_block,
+                };
+                editor.set_block(block, x, 0, z, None, None);
+            }
+            if let Some(paving) = paving {
+                plazas::generate_planters(editor, &filled_area, paving);
             }
         } else {
             let mut previous_node: Option<(i32, i32)> = None;
@@ -271,6 +348,7 @@ fn generate_highways_internal(
             // generate elevated decks or support pillars).
             let is_indoor = element.tags().get("indoor").is_some_and(|v| v == "yes");
             let is_bridge = !is_indoor && element.tags().get("bridge").is_some_and(|v| v != "no");
+            let is_tunnel = !is_indoor && !is_bridge && tunnels::is_tunnel(element.tags());
 
             // Parse the layer value for elevation calculation
             let mut layer_value = element
@@ -278,6 +356,8 @@ fn generate_highways_internal(
                 .get("layer")
                 .and_then(|layer| layer.parse::<i32>().ok())
                 .unwrap_or(0);
+            // Tunnels run one layer below ground unless tagged deeper
+            let tunnel_layers_down = (-layer_value).max(1);
 
             // Treat negative layers as ground level (0)
             if layer_value < 0 {
@@ -297,16 +377,34 @@ fn generate_highways_internal(
                 }
             }
 
+            // Pedestrian streets are paved in a pattern unless unpaved or overlaid
+            let mut paving = None;
+
             // Determine block type and range based on highway type
             match highway_type.as_str() {
-                "footway" | "pedestrian" => {
+                "footway" => {
                     block_type = GRAY_CONCRETE;
                     block_range = 1;
                 }
+                "pedestrian" => {
+                    block_type = GRAY_CONCRETE;
+                    block_range = 2;
+                    paving = plazas::Paving::from_surface(
+                        element.tags().get("surface").map(String::as_str),
+                    );
+                }
                 "path" => {
                     block_type = DIRT_PATH;
                     block_range = 1;
                 }
+                "cycleway" => {
+                    block_type = if cycleways::is_cycle_crossing(element.tags()) {
+                        cycleways::CYCLE_CROSSING
+                    } else {
+                        cycleways::CYCLE_SURFACE
+                    };
+                    block_range = 1;
+                }
                 "motorway" | "primary" | "trunk" => {
                     block_range = 5;
                     add_stripe = true;
@@ -336,8 +434,7 @@ fn generate_highways_internal(
                     block_range = 1;
                 }
                 "steps" => {
-                    //TODO: Add correct stairs respecting height, step_count, etc.
-                    block_type = GRAY_CONCRETE;
+                    block_type = STONE_BRICKS;
                     block_range = 1;
                 }
 
@@ -356,6 +453,34 @@ fn generate_highways_internal(
                 }
             }
 
+            // With --geodanmark-roads, unpaved roads get their own material and no lane
+            // markings, and a mapped width overrides the default for the road class
+            if let Some(surface) = element
+                .tags()
+                .get("surface")
+                .filter(|_| args.geodanmark_roads)
+                .and_then(|surface| unpaved_surface_block(surface))
+            {
+                block_type = surface;
+                add_stripe = false;
+                add_outline = false;
+                paving = None;
+            }
+            if let Some(range) = tagged_half_width(element.tags()).filter(|_| args.geodanmark_roads)
+            {
+                block_range = range;
+            }
+
+            // Thematic overlay colours replace the regular surface
+            if let Some(colour) = args
+                .overlay
+                .as_ref()
+                .and_then(|overlay| overlay.colour_for(element.tags()))
+            {
+                block_type = colour;
+                paving = None;
+            }
+
             let ProcessedElement::Way(way) = element else {
                 return;
             };
@@ -364,6 +489,21 @@ fn generate_highways_internal(
                 block_range = ((block_range as f64) * scale_factor).floor() as i32;
             }
 
+            // Steps climb the terrain as a flight of stairs, and so do footpaths too steep
+            // to walk up. The flight is their surface; lamps and the other details along
+            // the way follow as usual.
+            let mut flight = false;
+            if layer_value == 0 && !is_bridge && !is_tunnel {
+                let footpath = matches!(highway_type.as_str(), "footway" | "path" | "pedestrian");
+                let points = steps::flight_points(editor, way);
+                if highway_type == "steps" || (footpath && steps::is_steep(&points)) {
+                    let handrails = highway_type == "steps"
+                        && element.tags().get("handrail").is_none_or(|v| v != "no");
+                    steps::generate_steps(editor, &points, block_type, block_range, handrails);
+                    flight = true;
+                }
+            }
+
             // Calculate elevation based on layer
             const LAYER_HEIGHT_STEP: i32 = 6; // Each layer is 6 blocks higher/lower
             let base_elevation = layer_value * LAYER_HEIGHT_STEP;
@@ -381,62 +521,91 @@ fn generate_highways_internal(
             // Calculate total way length for slope distribution (needed before valley bridge check)
             let total_way_length = calculate_way_length(way);
 
-            // For bridges: detect if this spans a valley by checking terrain profile
-            // A valley bridge has terrain that dips significantly below the endpoints
-            // Skip valley detection entirely if terrain is disabled (no valleys in flat terrain)
-            // Skip very short bridges (< 25 blocks) as they're unlikely to span significant valleys
+            // For bridges: sample the terrain along the whole deck. A bridge whose ground
+            // dips below its banks (valley, river, strait) gets a level deck instead of
+            // following the terrain down. Skipped when terrain is disabled.
+            // Skip very short bridges (< 25 blocks), they are ramped like overpasses
             let terrain_enabled = editor
                 .get_ground()
                 .map(|g| g.elevation_enabled)
                 .unwrap_or(false);
 
-            let (is_valley_bridge, bridge_deck_y) =
-                if is_bridge && terrain_enabled && way.nodes.len() >= 2 && total_way_length >= 25 {
-                    let start_node = &way.nodes[0];
-                    let end_node = &way.nodes[way.nodes.len() - 1];
-                    let start_y = editor.get_ground_level(start_node.x, start_node.z);
-                    let end_y = editor.get_ground_level(end_node.x, end_node.z);
-                    let max_endpoint_y = start_y.max(end_y);
-
-                    // Sample terrain at middle nodes only (excluding endpoints we already have)
-                    // This avoids redundant get_ground_level() calls
-                    let middle_nodes = &way.nodes[1..way.nodes.len().saturating_sub(1)];
-                    let sampled_min = if middle_nodes.is_empty() {
-                        // No middle nodes, just use endpoints
-                        start_y.min(end_y)
-                    } else {
-                        // Sample up to 3 middle points (5 total with endpoints) for performance
-                        // Valleys are wide terrain features, so sparse sampling is sufficient
-                        let sample_count = middle_nodes.len().min(3);
-                        let step = if sample_count > 1 {
-                            (middle_nodes.len() - 1) / (sample_count - 1)
-                        } else {
-                            1
-                        };
-
-                        middle_nodes
-                            .iter()
-                            .step_by(step.max(1))
-                            .map(|node| editor.get_ground_level(node.x, node.z))
-                            .min()
-                            .unwrap_or(max_endpoint_y)
-                    };
-
-                    // Include endpoint elevations in the minimum calculation
-                    let min_terrain_y = sampled_min.min(start_y).min(end_y);
-
-                    // If ANY sampled point along the bridge is significantly lower than the max endpoint,
-                    // treat as valley bridge
-                    let is_valley = min_terrain_y < max_endpoint_y - VALLEY_BRIDGE_THRESHOLD;
-
-                    if is_valley {
-                        (true, max_endpoint_y)
-                    } else {
-                        (false, 0)
-                    }
+            // Bridges over a navigable channel (--navigable-waterways) always get a level
+            // deck, whatever their length, so boats pass underneath
+            let deck_points: Vec<(i32, i32)> = if (is_bridge || is_tunnel) && way.nodes.len() >= 2 {
+                way.nodes
+                    .windows(2)
+                    .flat_map(|pair| {
+                        bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z)
+                    })
+                    .map(|(x, _, z)| (x, z))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let channel_water: Vec<i32> = navigable_channels
+                .map(|channels| {
+                    deck_points
+                        .iter()
+                        .filter(|&&(x, z)| channels.contains(x, z))
+                        .map(|&(x, z)| editor.get_ground_level(x, z))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            let bridge_profile: Vec<i32> =
+                if !channel_water.is_empty() || (terrain_enabled && total_way_length >= 25) {
+                    deck_points
+                        .iter()
+                        .map(|&(x, z)| editor.get_ground_level(x, z))
+                        .collect()
                 } else {
-                    (false, 0)
+                    Vec::new()
                 };
+            let sea_level_y = editor.get_ground().and_then(|g| g.sea_level_y());
+            let bridge_deck_y = match channel_water.iter().max() {
+                Some(&water_y) => Some(navigable_bridge_deck(
+                    &bridge_profile,
+                    layer_value,
+                    sea_level_y,
+                    water_y,
+                )),
+                None => level_bridge_deck(&bridge_profile, layer_value, sea_level_y),
+            };
+
+            // Tunnels dip under the ground between their portals, whatever the terrain. The
+            // parser keeps tunnels whole, so the ramps count from portals outside the tile,
+            // where the ground is not known.
+            let tunnel_ground: Vec<Option<i32>> = if is_tunnel {
+                deck_points
+                    .iter()
+                    .map(|&(x, z)| editor.in_tile(x, z).then(|| editor.get_ground_level(x, z)))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let tunnel_road: Option<Vec<Option<i32>>> = (!tunnel_ground.is_empty())
+                .then(|| tunnels::tunnel_road_profile(&tunnel_ground, tunnel_layers_down));
+            if let Some(deck_y) = bridge_deck_y.filter(|_| !channel_water.is_empty()) {
+                let lowest_headroom = bridge_profile
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| {
+                        let (x, z) = deck_points[i];
+                        navigable_channels.is_some_and(|channels| channels.contains(x, z))
+                    })
+                    .map(|(i, &ground_y)| ramped_deck_y(deck_y, &bridge_profile, i) - ground_y)
+                    .min();
+                if lowest_headroom.is_some_and(|headroom| headroom < NAVIGABLE_CLEARANCE) {
+                    eprintln!(
+                        "{} Bridge {} is too short to ramp up to {} blocks of headroom over the navigable channel",
+                        "Warning:".yellow().bold(),
+                        way.id,
+                        NAVIGABLE_CLEARANCE
+                    );
+                }
+            }
+            let mut deck_position = 0;
 
             // Check if this is a short isolated elevated segment (layer > 0), if so, treat as ground level
             let is_short_isolated_elevated =
@@ -452,6 +621,14 @@ fn generate_highways_internal(
 
             let slope_length = (total_way_length as f32 * 0.35).clamp(15.0, 50.0) as usize; // 35% of way length, max 50 blocks, min 15 blocks
 
+            let is_ground_level =
+                effective_elevation == 0 && bridge_deck_y.is_none() && tunnel_road.is_none();
+
+            // Lamp posts along one side of lit roads, every LAMP_SPACING_M meters
+            let mut lamps = (is_ground_level && highway_type != "steps")
+                .then(|| LampSpacing::for_road(element.tags(), scale_factor))
+                .flatten();
+
             // Iterate over nodes to create the highway
             let mut segment_index = 0;
             let total_segments = way.nodes.len() - 1;
@@ -476,13 +653,25 @@ fn generate_highways_internal(
 
                     for (point_index, (x, _, z)) in bresenham_points.iter().enumerate() {
                         // Calculate Y elevation for this point
-                        // For valley bridges: use fixed deck height (max of endpoints) to stay level
+                        // For valley bridges: use the level deck height to stay level
                         // For overpasses and regular roads: use terrain-relative elevation with slopes
-                        let (current_y, use_absolute_y) = if is_valley_bridge {
-                            // Valley bridge deck is level at the maximum endpoint elevation
-                            // Don't add base_elevation - the layer tag indicates it's above water/road,
-                            // not that it should be higher than the terrain endpoints
-                            (bridge_deck_y, true)
+                        let mut tunnel_section = None;
+                        let (current_y, use_absolute_y) = if let Some(road) = &tunnel_road {
+                            deck_position += 1;
+                            let i = deck_position - 1;
+                            let Some(road_y) = road[i] else {
+                                continue;
+                            };
+                            tunnel_section = Some(tunnels::tunnel_section(&tunnel_ground, road, i));
+                            (road_y, true)
+                        } else if let Some(deck_y) = bridge_deck_y {
+                            // Valley bridge deck is level, ramping up from the banks where
+                            // it clears the ground under it
+                            deck_position += 1;
+                            (
+                                ramped_deck_y(deck_y, &bridge_profile, deck_position - 1),
+                                true,
+                            )
                         } else {
                             // Regular road or overpass: use terrain-relative calculation with ramps
                             let y = calculate_point_elevation(
@@ -498,20 +687,67 @@ fn generate_highways_internal(
                             (y, false)
                         };
 
-                        // Draw the road surface for the entire width
-                        for dx in -block_range..=block_range {
-                            for dz in -block_range..=block_range {
-                                let set_x: i32 = x + dx;
-                                let set_z: i32 = z + dz;
-
-                                // Zebra crossing logic
-                                if highway_type == "footway"
-                                    && element.tags().get("footway")
-                                        == Some(&"crossing".to_string())
-                                {
-                                    let is_horizontal: bool = (x2 - x1).abs() >= (z2 - z1).abs();
-                                    if is_horizontal {
-                                        if set_x % 2 < 1 {
+                        // Draw the road surface for the entire width, unless the way is a
+                        // flight of steps
+                        if !flight {
+                            for dx in -block_range..=block_range {
+                                for dz in -block_range..=block_range {
+                                    let set_x: i32 = x + dx;
+                                    let set_z: i32 = z + dz;
+
+                                    let surface_block = match paving {
+                                        Some(paving) => paving.block(set_x, set_z),
+                                        None => block_type,
+                                    };
+
+                                    // Zebra crossing logic
+                                    if highway_type == "footway"
+                                        && element.tags().get("footway")
+                                            == Some(&"crossing".to_string())
+                                    {
+                                        let is_horizontal: bool =
+                                            (x2 - x1).abs() >= (z2 - z1).abs();
+                                        if is_horizontal {
+                                            if set_x % 2 < 1 {
+                                                if use_absolute_y {
+                                                    editor.set_block_absolute(
+                                                        WHITE_CONCRETE,
+                                                        set_x,
+                                                        current_y,
+                                                        set_z,
+                                                        Some(&[BLACK_CONCRETE]),
+                                                        None,
+                                                    );
+                                                } else {
+                                                    editor.set_block(
+                                                        WHITE_CONCRETE,
+                                                        set_x,
+                                                        current_y,
+                                                        set_z,
+                                                        Some(&[BLACK_CONCRETE]),
+                                                        None,
+                                                    );
+                                                }
+                                            } else if use_absolute_y {
+                                                editor.set_block_absolute(
+                                                    BLACK_CONCRETE,
+                                                    set_x,
+                                                    current_y,
+                                                    set_z,
+                                                    None,
+                                                    None,
+                                                );
+                                            } else {
+                                                editor.set_block(
+                                                    BLACK_CONCRETE,
+                                                    set_x,
+                                                    current_y,
+                                                    set_z,
+                                                    None,
+                                                    None,
+                                                );
+                                            }
+                                        } else if set_z % 2 < 1 {
                                             if use_absolute_y {
                                                 editor.set_block_absolute(
                                                     WHITE_CONCRETE,
@@ -550,112 +786,129 @@ fn generate_highways_internal(
                                                 None,
                                             );
                                         }
-                                    } else if set_z % 2 < 1 {
-                                        if use_absolute_y {
-                                            editor.set_block_absolute(
-                                                WHITE_CONCRETE,
-                                                set_x,
-                                                current_y,
-                                                set_z,
-                                                Some(&[BLACK_CONCRETE]),
-                                                None,
-                                            );
-                                        } else {
-                                            editor.set_block(
-                                                WHITE_CONCRETE,
-                                                set_x,
-                                                current_y,
-                                                set_z,
-                                                Some(&[BLACK_CONCRETE]),
-                                                None,
-                                            );
-                                        }
                                     } else if use_absolute_y {
                                         editor.set_block_absolute(
-                                            BLACK_CONCRETE,
+                                            surface_block,
                                             set_x,
                                             current_y,
                                             set_z,
                                             None,
-                                            None,
+                                            Some(&[
+                                                BLACK_CONCRETE,
+                                                WHITE_CONCRETE,
+                                                cycleways::CYCLE_CROSSING,
+                                            ]),
                                         );
                                     } else {
                                         editor.set_block(
-                                            BLACK_CONCRETE,
+                                            surface_block,
                                             set_x,
                                             current_y,
                                             set_z,
                                             None,
-                                            None,
+                                            Some(&[
+                                                BLACK_CONCRETE,
+                                                WHITE_CONCRETE,
+                                                cycleways::CYCLE_CROSSING,
+                                            ]),
                                         );
                                     }
-                                } else if use_absolute_y {
-                                    editor.set_block_absolute(
-                                        block_type,
-                                        set_x,
-                                        current_y,
-                                        set_z,
-                                        None,
-                                        Some(&[BLACK_CONCRETE, WHITE_CONCRETE]),
-                                    );
-                                } else {
-                                    editor.set_block(
-                                        block_type,
-                                        set_x,
-                                        current_y,
-                                        set_z,
-                                        None,
-                                        Some(&[BLACK_CONCRETE, WHITE_CONCRETE]),
-                                    );
-                                }
 
-                                // Add stone brick foundation underneath elevated highways/bridges for thickness
-                                if (effective_elevation > 0 || use_absolute_y) && current_y > 0 {
-                                    // Add 1 layer of stone bricks underneath the highway surface
-                                    if use_absolute_y {
-                                        editor.set_block_absolute(
-                                            STONE_BRICKS,
-                                            set_x,
-                                            current_y - 1,
-                                            set_z,
-                                            None,
-                                            None,
-                                        );
-                                    } else {
-                                        editor.set_block(
-                                            STONE_BRICKS,
-                                            set_x,
-                                            current_y - 1,
-                                            set_z,
-                                            None,
-                                            None,
+                                    // Clear the bore or cutting above tunnel roads
+                                    if let Some(section) = tunnel_section {
+                                        tunnels::carve_tunnel_column(
+                                            editor, set_x, set_z, current_y, section,
                                         );
                                     }
-                                }
 
-                                // Add support pillars for elevated highways/bridges
-                                if (effective_elevation != 0 || use_absolute_y) && current_y > 0 {
-                                    if use_absolute_y {
-                                        add_highway_support_pillar_absolute(
-                                            editor,
-                                            set_x,
-                                            current_y,
-                                            set_z,
-                                            dx,
-                                            dz,
-                                            block_range,
-                                        );
-                                    } else {
-                                        add_highway_support_pillar(
-                                            editor,
-                                            set_x,
-                                            current_y,
-                                            set_z,
-                                            dx,
-                                            dz,
-                                            block_range,
-                                        );
+                                    // Add stone brick foundation underneath elevated highways/bridges for thickness
+                                    if (effective_elevation > 0 || use_absolute_y) && current_y > 0
+                                    {
+                                        // Add 1 layer of stone bricks underneath the highway surface
+                                        if use_absolute_y {
+                                            editor.set_block_absolute(
+                                                STONE_BRICKS,
+                                                set_x,
+                                                current_y - 1,
+                                                set_z,
+                                                None,
+                                                None,
+                                            );
+                                        } else {
+                                            editor.set_block(
+                                                STONE_BRICKS,
+                                                set_x,
+                                                current_y - 1,
+                                                set_z,
+                                                None,
+                                                None,
+                                            );
+                                        }
                                     }
+
+                                    // Add support pillars for elevated highways/bridges, except in
+                                    // a navigable channel
+                                    let in_channel = navigable_channels
+                                        .is_some_and(|channels| channels.contains(set_x, set_z));
+                                    if (effective_elevation != 0 || use_absolute_y)
+                                        && current_y > 0
+                                        && !in_channel
+                                    {
+                                        if use_absolute_y {
+                                            add_highway_support_pillar_absolute(
+                                                editor,
+                                                set_x,
+                                                current_y,
+                                                set_z,
+                                                dx,
+                                                dz,
+                                                block_range,
+                                            );
+                                        } else {
+                                            add_highway_support_pillar(
+                                                editor,
+                                                set_x,
+                                                current_y,
+                                                set_z,
+                                                dx,
+                                                dz,
+                                                block_range,
+                                            );
+                                        }
+                                    }
+                                }
+                            }
+                        }
+
+                        // Railings along both edges of raised bridge decks
+                        if is_bridge && (use_absolute_y || effective_elevation > 0) {
+                            let (rail_dx, rail_dz) = if (x2 - x1).abs() >= (z2 - z1).abs() {
+                                (0, block_range + 1)
+                            } else {
+                                // The outline of multi-lane roads widens them across x
+                                (block_range + 1 + i32::from(add_outline), 0)
+                            };
+                            for side in [-1, 1] {
+                                add_bridge_railing(
+                                    editor,
+                                    x + side * rail_dx,
+                                    current_y,
+                                    z + side * rail_dz,
+                                    use_absolute_y,
+                                );
+                            }
+                        }
+
+                        if let Some(lamps) = lamps.as_mut() {
+                            if lamps.step() {
+                                let (side_x, side_z) = if (x2 - x1).abs() >= (z2 - z1).abs() {
+                                    (*x, z + block_range + 1)
+                                } else {
+                                    (x + block_range + 1 + i32::from(add_outline), *z)
+                                };
+                                if street_details::lamp_post_fits(editor, side_x, side_z) {
+                                    street_details::generate_lamp_post(editor, side_x, side_z);
+                                    lamps.placed();
                                 }
                             }
                         }
@@ -750,7 +1003,121 @@ fn generate_highways_internal(
                 }
                 previous_node = Some((node.x, node.z));
             }
+
+            // Crossings and traffic calming across roads, and the islands of roundabouts
+            if is_ground_level && !is_footpath(highway_type) {
+                for (i, node) in way.nodes.iter().enumerate() {
+                    let Some(detail) = street_details::road_node_detail(&node.tags) else {
+                        continue;
+                    };
+                    let neighbour = way
+                        .nodes
+                        .get(i + 1)
+                        .or_else(|| i.checked_sub(1).map(|j| &way.nodes[j]));
+                    let Some(neighbour) = neighbour else {
+                        continue;
+                    };
+                    let along_x = (neighbour.x - node.x).abs() >= (neighbour.z - node.z).abs();
+                    street_details::generate_road_node_detail(
+                        editor,
+                        detail,
+                        node.x,
+                        node.z,
+                        along_x,
+                        block_range,
+                    );
+                }
+                if street_details::is_roundabout(element.tags()) {
+                    street_details::generate_roundabout_island(editor, way, block_range);
+                }
+            }
+
+            // Bollards close pedestrian streets to traffic where they meet a road
+            if highway_type == "pedestrian" && is_ground_level {
+                plazas::generate_street_bollards(editor, way, block_range, highway_connectivity);
+            }
+
+            // End ground-level roads that run off the edge of the world at a barrier
+            if args.edge_margin > 0 && is_ground_level {
+                for end in [&way.nodes[0], &way.nodes[way.nodes.len() - 1]] {
+                    cap_road_at_edge(editor, end.x, end.z, block_range);
+                }
+            }
+
+            // Cycle tracks and lanes along ground-level roads
+            if is_ground_level {
+                cycleways::generate_cycle_sides(editor, way, block_range, highway_connectivity);
+            }
+        }
+    }
+}
+
+/// Ways for people on foot or bike, which cross roads rather than carry their markings
+/// Counts the blocks along a lit road since its last lamp post
+#[derive(Debug, PartialEq)]
+struct LampSpacing {
+    spacing: usize,
+    since_lamp: usize,
+}
+
+impl LampSpacing {
+    /// Lamp posts every `LAMP_SPACING_M` meters, at least 8 blocks apart, on `lit=yes` roads
+    fn for_road(tags: &HashMap<String, String>, scale: f64) -> Option<Self> {
+        tags.get("lit").is_some_and(|v| v == "yes").then(|| Self {
+            spacing: ((street_details::LAMP_SPACING_M * scale).round() as usize).max(8),
+            since_lamp: 0,
+        })
+    }
+
+    /// Steps one block along the road and returns whether a lamp post is due there. A post
+    /// that does not fit is tried again on the next block.
+    fn step(&mut self) -> bool {
+        self.since_lamp += 1;
+        self.since_lamp >= self.spacing
+    }
+
+    fn placed(&mut self) {
+        self.since_lamp = 0;
+    }
+}
+
+fn is_footpath(highway_type: &str) -> bool {
+    matches!(
+        highway_type,
+        "footway" | "path" | "cycleway" | "steps" | "bridleway" | "pedestrian"
+    )
+}
+
+/// Places a barrier across a road end lying on the edge of the world, along the edge and as
+/// wide as the road. Ends on the edge of a tile inside the world carry on in the next tile.
+fn cap_road_at_edge(editor: &mut WorldEditor, x: i32, z: i32, block_range: i32) {
+    if editor.edge_distance(x, z) != 0 {
+        return;
+    }
+    let world = editor.world_xzbbox();
+    let on_x_edge = x == world.min_x() || x == world.max_x();
+    for offset in -block_range..=block_range {
+        let (bx, bz) = if on_x_edge {
+            (x, z + offset)
+        } else {
+            (x + offset, z)
+        };
+        editor.set_block(STONE_BRICK_WALL, bx, 1, bz, None, None);
+    }
+}
+
+/// Railing post on the edge of a bridge deck, standing on a deck block of its own. Decks
+/// still at ground level, where the bridge meets the road, get none.
+fn add_bridge_railing(editor: &mut WorldEditor, x: i32, deck_y: i32, z: i32, use_absolute_y: bool) {
+    if use_absolute_y {
+        if deck_y <= editor.get_ground_level(x, z) {
+            return;
         }
+        editor.set_block_absolute(STONE_BRICKS, x, deck_y, z, None, None);
+        editor.set_block_absolute(STONE_BRICK_WALL, x, deck_y + 1, z, None, None);
+    } else if deck_y > 0 {
+        editor.set_block(STONE_BRICKS, x, deck_y, z, None, None);
+        editor.set_block(STONE_BRICK_WALL, x, deck_y + 1, z, None, None);
     }
 }
 
@@ -758,7 +1125,7 @@ fn generate_highways_internal(
 fn should_add_slope_at_node(
     node: &crate::osm_parser::ProcessedNode,
     current_layer: i32,
-    highway_connectivity: &HashMap<(i32, i32), Vec<i32>>,
+    highway_connectivity: &HighwayConnectivityMap,
 ) -> bool {
     let node_coord = (node.x, node.z);
 
@@ -772,7 +1139,7 @@ fn should_add_slope_at_node(
         // Count how many ways are at the same layer as current way
         let same_layer_count = connected_layers
             .iter()
-            .filter(|&&layer| layer == current_layer)
+            .filter(|end| end.layer == current_layer)
             .count();
 
         // If this is the only way at this layer connecting to this node, we need a slope
@@ -789,6 +1156,53 @@ fn should_add_slope_at_node(
     }
 }
 
+/// Level deck height for a bridge from the terrain profile along it, or `None` when the
+/// ground does not dip below the banks and the bridge can follow the terrain.
+///
+/// The deck sits at the higher bank, raised to keep `BRIDGE_CLEARANCE` blocks per layer
+/// above the lowest point underneath. Over the sea the water surface counts as that lowest
+/// point, so a sloped sea floor does not pull the deck down into the water.
+fn level_bridge_deck(profile: &[i32], layer: i32, sea_level_y: Option<i32>) -> Option<i32> {
+    let (&start_y, &end_y) = (profile.first()?, profile.last()?);
+    let bank_y = start_y.max(end_y);
+    let lowest = profile.iter().copied().min()?;
+    if lowest >= bank_y - VALLEY_BRIDGE_THRESHOLD {
+        return None;
+    }
+    let floor = sea_level_y.map_or(lowest, |sly| lowest.max(sly));
+    Some(bank_y.max(floor + BRIDGE_CLEARANCE * layer.max(1)))
+}
+
+/// Level deck height for a bridge over a navigable channel: the usual level deck, raised
+/// to leave `NAVIGABLE_CLEARANCE` blocks of headroom above the channel's water at `water_y`.
+fn navigable_bridge_deck(
+    profile: &[i32],
+    layer: i32,
+    sea_level_y: Option<i32>,
+    water_y: i32,
+) -> i32 {
+    let bank_y = profile
+        .first()
+        .zip(profile.last())
+        .map_or(water_y, |(&start_y, &end_y)| start_y.max(end_y));
+    level_bridge_deck(profile, layer, sea_level_y)
+        .unwrap_or(bank_y)
+        .max(water_y + NAVIGABLE_CLEARANCE)
+}
+
+/// Deck height at `position` along a level bridge: the deck, ramped down to meet the
+/// ground at both ends
+fn ramped_deck_y(deck_y: i32, profile: &[i32], position: usize) -> i32 {
+    let (Some(&start_y), Some(&end_y)) = (profile.first(), profile.last()) else {
+        return deck_y;
+    };
+    let from_end = profile.len().saturating_sub(position + 1);
+    let rise = |bank_y: i32, distance: usize| bank_y + (distance / BRIDGE_RAMP_RUN) as i32;
+    deck_y
+        .min(rise(start_y, position))
+        .min(rise(end_y, from_end))
+}
+
 /// Helper function to calculate the total length of a way in blocks
 fn calculate_way_length(way: &ProcessedWay) -> usize {
     let mut total_length = 0;
@@ -979,9 +1393,10 @@ pub fn generate_aeroway(editor: &mut WorldEditor, way: &ProcessedWay, args: &Arg
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utilities::tags;
     use std::collections::HashMap;
 
-    fn highway_way(id: u64, coords: &[(i32, i32)], tags: &[(&str, &str)]) -> ProcessedElement {
+    fn highway_way(id: u64, coords: &[(i32, i32)], pairs: &[(&str, &str)]) -> ProcessedElement {
         ProcessedElement::Way(ProcessedWay {
             id,
             nodes: coords
@@ -992,15 +1407,39 @@ mod tests {
                     tags: HashMap::new(),
                     x: *x,
                     z: *z,
+                    exact: None,
                 })
                 .collect(),
-            tags: tags
-                .iter()
-                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
-                .collect(),
+            tags: tags(pairs),
         })
     }
 
+    #[test]
+    fn lamps_stand_at_even_spacing_on_lit_roads() {
+        assert_eq!(LampSpacing::for_road(&tags(&[("lit", "no")]), 1.0), None);
+        // Never closer than 8 blocks, however small the scale
+        assert_eq!(
+            LampSpacing::for_road(&tags(&[("lit", "yes")]), 0.1).map(|lamps| lamps.spacing),
+            Some(8)
+        );
+
+        let mut lamps = LampSpacing::for_road(&tags(&[("lit", "yes")]), 1.0).unwrap();
+        // Due on every block from the 30th on while none is placed
+        let due: Vec<usize> = (1..=70).filter(|_| lamps.step()).collect();
+        assert_eq!(due.len(), 41);
+
+        let mut lamps = LampSpacing::for_road(&tags(&[("lit", "yes")]), 1.0).unwrap();
+        let mut placed = Vec::new();
+        for block in 1..=70 {
+            // The first post is blocked on its block and goes up on the next
+            if lamps.step() && block != 30 {
+                lamps.placed();
+                placed.push(block);
+            }
+        }
+        assert_eq!(placed, vec![31, 61]);
+    }
+
     #[test]
     fn ground_highway_mask_covers_surface_roads() {
         let bbox = XZBBox::rect_from_xz_lengths(20.0, 20.0).unwrap();
@@ -1010,7 +1449,7 @@ mod tests {
             &[("highway", "service")],
         )];
 
-        let mask = collect_ground_highway_mask(&elements, &bbox, 1.0);
+        let mask = collect_ground_highway_mask(&elements, &bbox, 1.0, false);
 
         assert!(mask.contains(10, 10));
         assert!(mask.contains(10, 12));
@@ -1026,8 +1465,77 @@ mod tests {
             &[("highway", "service"), ("bridge", "yes")],
         )];
 
-        let mask = collect_ground_highway_mask(&elements, &bbox, 1.0);
+        let mask = collect_ground_highway_mask(&elements, &bbox, 1.0, false);
+
+        assert!(!mask.contains(10, 10));
+    }
+
+    #[test]
+    fn ground_highway_mask_skips_tunnels() {
+        let bbox = XZBBox::rect_from_xz_lengths(20.0, 20.0).unwrap();
+        let elements = vec![highway_way(
+            3,
+            &[(5, 10), (15, 10)],
+            &[("highway", "service"), ("tunnel", "yes")],
+        )];
+
+        let mask = collect_ground_highway_mask(&elements, &bbox, 1.0, false);
 
         assert!(!mask.contains(10, 10));
     }
+
+    #[test]
+    fn mapped_width_overrides_the_road_class() {
+        let primary = tags(&[("highway", "primary"), ("width", "7")]);
+        assert_eq!(
+            highway_mask_radius(&tags(&[("highway", "primary")]), 1.0, true),
+            Some(5)
+        );
+        assert_eq!(highway_mask_radius(&primary, 1.0, true), Some(3));
+        assert_eq!(
+            highway_mask_radius(
+                &tags(&[("highway", "residential"), ("width", "4.5 m")]),
+                1.0,
+                true
+            ),
+            Some(2)
+        );
+        // Without --geodanmark-roads the class decides
+        assert_eq!(highway_mask_radius(&primary, 1.0, false), Some(5));
+        assert_eq!(unpaved_surface_block("asphalt"), None);
+        assert_eq!(unpaved_surface_block("compacted"), Some(GRAVEL));
+    }
+
+    #[test]
+    fn bridges_keep_a_level_deck_over_dips() {
+        // Gentle ground: the bridge follows the terrain
+        assert_eq!(level_bridge_deck(&[10, 9, 9, 10], 1, None), None);
+
+        // River valley: level with the higher bank
+        let valley = [20, 18, 5, 4, 6, 19];
+        assert_eq!(level_bridge_deck(&valley, 1, None), Some(20));
+
+        // Strait between low banks: clearance over the water, not the sea floor
+        let strait = [2, 1, -8, -12, -9, 1, 2];
+        let deck = level_bridge_deck(&strait, 2, Some(0)).unwrap();
+        assert_eq!(deck, 2 * BRIDGE_CLEARANCE);
+        let ramp: Vec<i32> = (0..strait.len())
+            .map(|i| ramped_deck_y(deck, &strait, i))
+            .collect();
+        assert_eq!(ramp, vec![2, 2, 3, 3, 3, 2, 2]);
+    }
+
+    #[test]
+    fn bridges_leave_headroom_over_navigable_channels() {
+        // Flat canal banks: the deck rises above the water for boats
+        let canal = [3, 3, 3, 3, 3];
+        assert_eq!(
+            navigable_bridge_deck(&canal, 1, None, 3),
+            3 + NAVIGABLE_CLEARANCE
+        );
+
+        // A valley deck that is already high enough stays where it is
+        let valley = [20, 18, 5, 4, 6, 19];
+        assert_eq!(navigable_bridge_deck(&valley, 1, None, 4), 20);
+    }
 }
@@ -2,12 +2,16 @@
 //!
 //! This module handles historic OSM elements including:
 //! - `historic=memorial` - Memorials, monuments, and commemorative structures
+//! - `historic=tumulus` - Burial mounds, as grass-covered domes
+//! - `historic=archaeological_site` + `site_type=megalith` - Dolmens and passage graves
+//! - `historic=rune_stone` - Rune stones with a sign giving their name
 
 use crate::args::Args;
 use crate::block_definitions::*;
 use crate::deterministic_rng::element_rng;
 use crate::floodfill_cache::FloodFillCache;
 use crate::osm_parser::{ProcessedNode, ProcessedWay};
+use crate::world_editor::sign_text::{fill_lines, wrap_words};
 use crate::world_editor::WorldEditor;
 use rand::Rng;
 
@@ -31,6 +35,13 @@ pub fn generate_historic(editor: &mut WorldEditor, node: &ProcessedNode) {
             "memorial" => generate_memorial(editor, node),
             "monument" => generate_monument(editor, node),
             "wayside_cross" => generate_wayside_cross(editor, node),
+            "tumulus" => generate_burial_mound(editor, node),
+            "archaeological_site"
+                if node.tags.get("site_type").is_some_and(|t| t == "megalith") =>
+            {
+                generate_dolmen(editor, node)
+            }
+            "rune_stone" => generate_rune_stone(editor, node),
             _ => {}
         }
     }
@@ -189,6 +200,66 @@ fn generate_wayside_cross(editor: &mut WorldEditor, node: &ProcessedNode) {
     generate_cross(editor, x, z, 4);
 }
 
+/// Generate a burial mound: a grass-covered dome of earth, sized from the `diameter` and
+/// `height` tags in meters. Most Danish round barrows are 10-20 m across and 1-4 m high.
+fn generate_burial_mound(editor: &mut WorldEditor, node: &ProcessedNode) {
+    let meters = |key: &str| {
+        node.tags
+            .get(key)
+            .and_then(|v| v.trim_end_matches('m').trim().parse::<f64>().ok())
+    };
+    let radius = meters("diameter").map_or(7.0, |d| d / 2.0).clamp(3.0, 20.0);
+    let height = meters("height")
+        .unwrap_or(radius / 3.0 + 1.0)
+        .clamp(1.0, 10.0);
+
+    let r = radius.ceil() as i32;
+    for dx in -r..=r {
+        for dz in -r..=r {
+            let d = ((dx * dx + dz * dz) as f64).sqrt() / radius;
+            if d > 1.0 {
+                continue;
+            }
+            let top = (height * (1.0 - d * d).sqrt()).round() as i32;
+            for y in 1..top {
+                editor.set_block(DIRT, node.x + dx, y, node.z + dz, None, None);
+            }
+            if top >= 1 {
+                editor.set_block(GRASS_BLOCK, node.x + dx, top, node.z + dz, None, None);
+            }
+        }
+    }
+}
+
+/// Generate a dolmen: three upright granite boulders under a capstone, the chamber open to
+/// the south
+fn generate_dolmen(editor: &mut WorldEditor, node: &ProcessedNode) {
+    let (x, z) = (node.x, node.z);
+    for (dx, dz) in [(-1, 0), (1, 0), (0, -1)] {
+        editor.set_block(GRANITE, x + dx, 1, z + dz, None, None);
+        editor.set_block(ANDESITE, x + dx, 2, z + dz, None, None);
+    }
+    for dx in -1..=1 {
+        for dz in -1..=1 {
+            if dx == 0 || dz == 0 {
+                editor.set_block(GRANITE, x + dx, 3, z + dz, None, None);
+            }
+        }
+    }
+}
+
+/// Generate a rune stone: an upright granite stone with a sign in front giving its name
+fn generate_rune_stone(editor: &mut WorldEditor, node: &ProcessedNode) {
+    let (x, z) = (node.x, node.z);
+    editor.set_block(GRANITE, x, 1, z, None, None);
+    editor.set_block(POLISHED_GRANITE, x, 2, z, None, None);
+    editor.set_block(GRANITE, x, 3, z, None, None);
+
+    let name = node.tags.get("name").map_or("Runesten", String::as_str);
+    let [line1, line2, line3, line4] = fill_lines(wrap_words(name.split_whitespace(), 4));
+    editor.set_sign(line1, line2, line3, line4, x, 1, z + 1, 0);
+}
+
 /// Helper function to generate a cross structure
 fn generate_cross(editor: &mut WorldEditor, x: i32, z: i32, height: i32) {
     // Base
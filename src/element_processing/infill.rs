@@ -0,0 +1,131 @@
+//! Synthetic street-block infill for urban areas without mapped buildings (`--urban-infill`).
+//!
+//! There is no orthophoto or BBR source to tell where unmapped buildings stand, so OSM
+//! landuse stands in: residential, commercial and retail areas that contain (almost) no
+//! building footprints get generic perimeter blocks along their street edges around an open
+//! courtyard, as Danish city blocks are built. The massing is synthetic, every infilled area
+//! is listed in the manifest and counted in the generation summary.
+
+use crate::args::Args;
+use crate::block_definitions::*;
+use crate::deterministic_rng::element_rng;
+use crate::floodfill_cache::{BuildingFootprintBitmap, CoordinateBitmap, FloodFillCache};
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use rand::prelude::IndexedRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Areas smaller than this (blocks) are left alone
+const MIN_INFILL_AREA: usize = 600;
+/// Areas where mapped footprints already cover more than this share are not infilled
+const MAX_FOOTPRINT_SHARE: f64 = 0.02;
+/// Distance kept free along the area edge, where the street runs
+const STREET_SETBACK: i32 = 3;
+/// Depth of the perimeter block from street facade to courtyard facade
+const BLOCK_DEPTH: i32 = 10;
+const FLOOR_HEIGHT: i32 = 3;
+
+const FACADES: [Block; 4] = [
+    BRICK,
+    WHITE_TERRACOTTA,
+    LIGHT_GRAY_TERRACOTTA,
+    ORANGE_TERRACOTTA,
+];
+
+/// Distance of every area cell from the area edge, up to `max_distance`
+fn edge_distances(area: &[(i32, i32)], max_distance: i32) -> HashMap<(i32, i32), i32> {
+    let cells: HashSet<(i32, i32)> = area.iter().copied().collect();
+    let neighbours = |(x, z): (i32, i32)| [(x + 1, z), (x - 1, z), (x, z + 1), (x, z - 1)];
+
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    for &cell in area {
+        if neighbours(cell).iter().any(|n| !cells.contains(n)) {
+            distances.insert(cell, 0);
+            queue.push_back(cell);
+        }
+    }
+    while let Some(cell) = queue.pop_front() {
+        let distance = distances[&cell];
+        if distance >= max_distance {
+            continue;
+        }
+        for next in neighbours(cell) {
+            if cells.contains(&next) && !distances.contains_key(&next) {
+                distances.insert(next, distance + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+/// Fills an urban landuse area without mapped buildings with perimeter-block massing.
+/// Returns whether the area was infilled.
+pub fn generate_perimeter_blocks(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+    building_footprints: &BuildingFootprintBitmap,
+    road_mask: &CoordinateBitmap,
+) -> bool {
+    let urban = matches!(
+        way.tags.get("landuse").map(String::as_str),
+        Some("residential" | "commercial" | "retail")
+    );
+    if !urban {
+        return false;
+    }
+    let area = flood_fill_cache.get_or_compute(way, args.timeout.as_ref());
+    if area.len() < MIN_INFILL_AREA {
+        return false;
+    }
+    let mapped = building_footprints.count_contained(area.iter());
+    if mapped as f64 > area.len() as f64 * MAX_FOOTPRINT_SHARE {
+        return false;
+    }
+
+    let mut rng = element_rng(way.id);
+    let facade = *FACADES.choose(&mut rng).unwrap_or(&BRICK);
+    let height = FLOOR_HEIGHT * rng.random_range(3..=5);
+    let back = STREET_SETBACK + BLOCK_DEPTH - 1;
+
+    for ((x, z), distance) in edge_distances(&area, back) {
+        if distance < STREET_SETBACK || road_mask.contains(x, z) {
+            continue;
+        }
+        if distance == STREET_SETBACK || distance == back {
+            for y in 1..=height {
+                let window = y % FLOOR_HEIGHT == 2 && (x + z) % 3 != 0;
+                editor.set_block(if window { GLASS } else { facade }, x, y, z, None, None);
+            }
+        }
+        editor.set_block(SMOOTH_STONE, x, height + 1, z, None, None);
+    }
+
+    crate::manifest::record_synthetic(&format!(
+        "perimeter-block infill in landuse way {} ({} blocks)",
+        way.id,
+        area.len()
+    ));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_grow_inwards_from_the_edge() {
+        let area: Vec<(i32, i32)> = (0..7).flat_map(|x| (0..5).map(move |z| (x, z))).collect();
+        let distances = edge_distances(&area, 10);
+        assert_eq!(distances[&(0, 2)], 0);
+        assert_eq!(distances[&(1, 1)], 1);
+        assert_eq!(distances[&(3, 2)], 2);
+
+        let capped = edge_distances(&area, 1);
+        assert!(!capped.contains_key(&(3, 2)));
+    }
+}
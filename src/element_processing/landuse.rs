@@ -1,32 +1,112 @@
 use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
+use crate::data_sources::is_jutland;
 use crate::deterministic_rng::element_rng;
-use crate::element_processing::tree::{Tree, TreeType};
-use crate::floodfill_cache::{BuildingFootprintBitmap, FloodFillCache};
-use crate::osm_parser::{ProcessedMemberRole, ProcessedRelation, ProcessedWay};
+use crate::element_processing::tree::{ForestMix, Tree, TreeType};
+use crate::element_processing::{allotments, cemeteries};
+use crate::enrichment::{self, crops};
+use crate::floodfill_cache::{BuildingFootprintBitmap, CoordinateBitmap, FloodFillCache};
+use crate::osm_parser::{ProcessedRelation, ProcessedWay};
 use crate::world_editor::WorldEditor;
 use rand::prelude::IndexedRandom;
 use rand::Rng;
 
+/// Plants a block of a field with its crop from the field parcel map (`--field-crops`).
+/// Winter cereals are ripe and golden by the time spring cereals are still green.
+fn place_field_crop(
+    editor: &mut WorldEditor,
+    crop: &str,
+    season: Option<&String>,
+    x: i32,
+    z: i32,
+    rng: &mut impl Rng,
+) {
+    let on_farmland = editor.check_for_block(x, 0, z, Some(&[FARMLAND]));
+    match crop {
+        "rape" => {
+            let flower = if rng.random_bool(0.75) {
+                YELLOW_FLOWER
+            } else {
+                GRASS
+            };
+            editor.set_block(flower, x, 1, z, None, None);
+        }
+        "maize" => {
+            if rng.random_bool(0.8) {
+                editor.set_block(TALL_GRASS_BOTTOM, x, 1, z, None, None);
+                editor.set_block(TALL_GRASS_TOP, x, 2, z, None, None);
+            }
+        }
+        "grass" => {
+            if rng.random_bool(0.3) {
+                editor.set_block(GRASS, x, 1, z, None, None);
+            }
+        }
+        _ if !on_farmland => {}
+        "potato" => editor.set_block(POTATOES, x, 1, z, None, None),
+        "carrot" => editor.set_block(CARROTS, x, 1, z, None, None),
+        "sugar_beet" | "beet" => editor.set_block(BEETROOTS, x, 1, z, None, None),
+        _ => {
+            let wheat = if season.is_some_and(|s| s == "spring") {
+                WHEAT_YOUNG
+            } else {
+                WHEAT
+            };
+            editor.set_block(wheat, x, 1, z, None, None);
+        }
+    }
+}
+
 pub fn generate_landuse(
     editor: &mut WorldEditor,
     element: &ProcessedWay,
     args: &Args,
     flood_fill_cache: &FloodFillCache,
     building_footprints: &BuildingFootprintBitmap,
+    allotment_plots: &CoordinateBitmap,
 ) {
     // Determine block type based on landuse tag
     let binding: String = "".to_string();
     let landuse_tag: &String = element.tags.get("landuse").unwrap_or(&binding);
 
+    if landuse_tag == "cemetery" {
+        cemeteries::generate_cemetery(
+            editor,
+            element,
+            args,
+            flood_fill_cache,
+            Some(building_footprints),
+        );
+        generate_cemetery_fence(editor, element);
+        return;
+    }
+
+    if landuse_tag == "allotments" {
+        allotments::generate_allotments(
+            editor,
+            element,
+            args,
+            flood_fill_cache,
+            building_footprints,
+            allotment_plots,
+        );
+        return;
+    }
+
     // Use deterministic RNG seeded by element ID for consistent results across region boundaries
     let mut rng = element_rng(element.id);
 
+    // Only fields from the parcel map are planted with their crop; OSM farmland keeps the mix
+    let crop = enrichment::is_from(&element.tags, crops::SOURCE)
+        .then(|| element.tags.get("crop"))
+        .flatten()
+        .map(String::as_str);
     let block_type = match landuse_tag.as_str() {
         "greenfield" | "meadow" | "grass" | "orchard" | "forest" => GRASS_BLOCK,
+        // Rape, maize and grass leys grow on grass; the rest on tilled soil
+        "farmland" if matches!(crop, Some("rape" | "maize" | "grass")) => GRASS_BLOCK,
         "farmland" => FARMLAND,
-        "cemetery" => PODZOL,
         "construction" => COARSE_DIRT,
         "traffic_island" => STONE_BLOCK_SLAB,
         "residential" => {
@@ -60,28 +140,7 @@ pub fn generate_landuse(
     let floor_area: Vec<(i32, i32)> =
         flood_fill_cache.get_or_compute(element, args.timeout.as_ref());
 
-    let trees_ok_to_generate: Vec<TreeType> = {
-        let mut trees: Vec<TreeType> = vec![];
-        if let Some(leaf_type) = element.tags.get("leaf_type") {
-            match leaf_type.as_str() {
-                "broadleaved" => {
-                    trees.push(TreeType::Oak);
-                    trees.push(TreeType::Birch);
-                }
-                "needleleaved" => trees.push(TreeType::Spruce),
-                _ => {
-                    trees.push(TreeType::Oak);
-                    trees.push(TreeType::Spruce);
-                    trees.push(TreeType::Birch);
-                }
-            }
-        } else {
-            trees.push(TreeType::Oak);
-            trees.push(TreeType::Spruce);
-            trees.push(TreeType::Birch);
-        }
-        trees
-    };
+    let forest = ForestMix::from_tags(&element.tags, is_jutland(&args.bbox), args.forest_types);
 
     for (x, z) in floor_area {
         // Apply per-block randomness for certain landuse types
@@ -133,47 +192,17 @@ pub fn generate_landuse(
 
         // Add specific features for different landuse types
         match landuse_tag.as_str() {
-            "cemetery" => {
-                if (x % 3 == 0) && (z % 3 == 0) {
-                    let random_choice: i32 = rng.random_range(0..100);
-                    if random_choice < 15 {
-                        // Place graves
-                        if editor.check_for_block(x, 0, z, Some(&[PODZOL])) {
-                            if rng.random_bool(0.5) {
-                                editor.set_block(COBBLESTONE, x - 1, 1, z, None, None);
-                                editor.set_block(STONE_BRICK_SLAB, x - 1, 2, z, None, None);
-                                editor.set_block(STONE_BRICK_SLAB, x, 1, z, None, None);
-                                editor.set_block(STONE_BRICK_SLAB, x + 1, 1, z, None, None);
-                            } else {
-                                editor.set_block(COBBLESTONE, x, 1, z - 1, None, None);
-                                editor.set_block(STONE_BRICK_SLAB, x, 2, z - 1, None, None);
-                                editor.set_block(STONE_BRICK_SLAB, x, 1, z, None, None);
-                                editor.set_block(STONE_BRICK_SLAB, x, 1, z + 1, None, None);
-                            }
-                        }
-                    } else if random_choice < 30 {
-                        if editor.check_for_block(x, 0, z, Some(&[PODZOL])) {
-                            editor.set_block(RED_FLOWER, x, 1, z, None, None);
-                        }
-                    } else if random_choice < 33 {
-                        Tree::create(editor, (x, 1, z), Some(building_footprints));
-                    } else if random_choice < 35 {
-                        editor.set_block(OAK_LEAVES, x, 1, z, None, None);
-                    } else if random_choice < 37 {
-                        editor.set_block(FERN, x, 1, z, None, None);
-                    } else if random_choice < 41 {
-                        editor.set_block(LARGE_FERN_LOWER, x, 1, z, None, None);
-                        editor.set_block(LARGE_FERN_UPPER, x, 2, z, None, None);
-                    }
-                }
+            "forest"
+                if forest.plantation_spacing.is_some()
+                    && editor.check_for_block(x, 0, z, Some(&[GRASS_BLOCK])) =>
+            {
+                forest.plant_plantation_cell(editor, (x, z), &mut rng, building_footprints);
             }
             "forest" => {
                 if editor.check_for_block(x, 0, z, Some(&[GRASS_BLOCK])) {
                     let random_choice: i32 = rng.random_range(0..30);
                     if random_choice == 20 {
-                        let tree_type = *trees_ok_to_generate
-                            .choose(&mut rng)
-                            .unwrap_or(&TreeType::Oak);
+                        let tree_type = *forest.trees.choose(&mut rng).unwrap_or(&TreeType::Oak);
                         Tree::create_of_type(
                             editor,
                             (x, 1, z),
@@ -212,6 +241,15 @@ pub fn generate_landuse(
                         } else {
                             editor.set_block(OAK_LEAVES, x, 1, z, None, Some(&[SPONGE]));
                         }
+                    } else if let Some(crop) = crop {
+                        place_field_crop(
+                            editor,
+                            crop,
+                            element.tags.get("crop:season"),
+                            x,
+                            z,
+                            &mut rng,
+                        );
                     } else {
                         // Set crops only if the block below is farmland
                         if editor.check_for_block(x, 0, z, Some(&[FARMLAND])) {
@@ -368,11 +406,6 @@ pub fn generate_landuse(
             _ => {}
         }
     }
-
-    // Generate a stone brick wall fence around cemeteries
-    if landuse_tag == "cemetery" {
-        generate_cemetery_fence(editor, element);
-    }
 }
 
 /// Draws a stone-brick wall fence (with slab cap) along the outline of a
@@ -396,28 +429,18 @@ pub fn generate_landuse_from_relation(
     args: &Args,
     flood_fill_cache: &FloodFillCache,
     building_footprints: &BuildingFootprintBitmap,
+    allotment_plots: &CoordinateBitmap,
 ) {
     if rel.tags.contains_key("landuse") {
-        // Process each outer member way individually using cached flood fill.
-        // We intentionally do not combine all outer nodes into one mega-way,
-        // because that creates a nonsensical polygon spanning the whole relation
-        // extent, misses the flood fill cache, and can cause multi-GB allocations.
-        for member in &rel.members {
-            if member.role == ProcessedMemberRole::Outer {
-                // Use relation tags so the member inherits the relation's landuse=* type
-                let way_with_rel_tags = ProcessedWay {
-                    id: member.way.id,
-                    nodes: member.way.nodes.clone(),
-                    tags: rel.tags.clone(),
-                };
-                generate_landuse(
-                    editor,
-                    &way_with_rel_tags,
-                    args,
-                    flood_fill_cache,
-                    building_footprints,
-                );
-            }
+        for ring in flood_fill_cache.multipolygon_rings(rel.id) {
+            generate_landuse(
+                editor,
+                ring,
+                args,
+                flood_fill_cache,
+                building_footprints,
+                allotment_plots,
+            );
         }
     }
 }
@@ -2,9 +2,10 @@ use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
 use crate::deterministic_rng::element_rng;
+use crate::element_processing::pitches;
 use crate::element_processing::tree::Tree;
 use crate::floodfill_cache::{BuildingFootprintBitmap, FloodFillCache};
-use crate::osm_parser::{ProcessedMemberRole, ProcessedRelation, ProcessedWay};
+use crate::osm_parser::{ProcessedRelation, ProcessedWay};
 use crate::world_editor::WorldEditor;
 use rand::Rng;
 
@@ -16,6 +17,10 @@ pub fn generate_leisure(
     building_footprints: &BuildingFootprintBitmap,
 ) {
     if let Some(leisure_type) = element.tags.get("leisure") {
+        // Marinas are built once the sea is in, by `harbours::generate_marina`
+        if leisure_type == "marina" {
+            return;
+        }
         let mut previous_node: Option<(i32, i32)> = None;
         let mut corner_addup: (i32, i32, i32) = (0, 0, 0);
         let mut current_leisure: Vec<(i32, i32)> = vec![];
@@ -26,7 +31,11 @@ pub fn generate_leisure(
                 GRASS_BLOCK
             }
             "schoolyard" => BLACK_CONCRETE,
-            "playground" | "recreation_ground" | "pitch" | "beach_resort" | "dog_park" => {
+            "pitch" | "track" if !element.tags.contains_key("surface") => {
+                pitches::sport_surface(&element.tags)
+            }
+            "playground" | "recreation_ground" | "pitch" | "track" | "beach_resort"
+            | "dog_park" => {
                 if let Some(surface) = element.tags.get("surface") {
                     match surface.as_str() {
                         "clay" => TERRACOTTA,
@@ -86,6 +95,14 @@ pub fn generate_leisure(
             let filled_area: Vec<(i32, i32)> =
                 flood_fill_cache.get_or_compute(element, args.timeout.as_ref());
 
+            if matches!(leisure_type.as_str(), "pitch" | "track") {
+                // Surface first, so the markings are painted over it
+                for &(x, z) in &filled_area {
+                    editor.set_block(block_type, x, 0, z, Some(&[GRASS_BLOCK]), None);
+                }
+                pitches::generate_pitch(editor, element, &filled_area, block_type);
+            }
+
             // Use deterministic RNG seeded by element ID for consistent results across region boundaries
             let mut rng = element_rng(element.id);
 
@@ -185,26 +202,8 @@ pub fn generate_leisure_from_relation(
     building_footprints: &BuildingFootprintBitmap,
 ) {
     if rel.tags.get("leisure") == Some(&"park".to_string()) {
-        // Process each outer member way individually using cached flood fill.
-        // We intentionally do not combine all outer nodes into one mega-way,
-        // because that creates a nonsensical polygon spanning the whole relation
-        // extent, misses the flood fill cache, and can cause multi-GB allocations.
-        for member in &rel.members {
-            if member.role == ProcessedMemberRole::Outer {
-                // Use relation tags so the member inherits the relation's leisure=* type
-                let way_with_rel_tags = ProcessedWay {
-                    id: member.way.id,
-                    nodes: member.way.nodes.clone(),
-                    tags: rel.tags.clone(),
-                };
-                generate_leisure(
-                    editor,
-                    &way_with_rel_tags,
-                    args,
-                    flood_fill_cache,
-                    building_footprints,
-                );
-            }
+        for ring in flood_fill_cache.multipolygon_rings(rel.id) {
+            generate_leisure(editor, ring, args, flood_fill_cache, building_footprints);
         }
     }
 }
@@ -1,10 +1,43 @@
 use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
-use crate::osm_parser::{ProcessedElement, ProcessedNode};
+use crate::element_processing::{harbours, mills};
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
 use crate::world_editor::WorldEditor;
+use std::collections::HashMap;
 
-pub fn generate_man_made(editor: &mut WorldEditor, element: &ProcessedElement, _args: &Args) {
+/// Whether a storm surge is running (`--flood-scenario`), which decides if flood barriers
+/// and harbour gates are shut
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum FloodScenario {
+    /// Normal water level, gates stand open for shipping
+    #[default]
+    Normal,
+    /// Gates closed against a storm surge
+    StormSurge,
+}
+
+/// Blocks between the towers of a flood barrier
+const FLOOD_GATE_SPAN: usize = 12;
+/// Closed gates stand this many blocks above sea level
+const FLOOD_GATE_HEIGHT: i32 = 4;
+/// Height (meters) of a lighthouse without a `height` tag
+const DEFAULT_LIGHTHOUSE_HEIGHT: f64 = 20.0;
+/// Lowest lighthouse tower, in blocks, below its gallery
+const MIN_LIGHTHOUSE_HEIGHT: i32 = 8;
+/// Radius of a lighthouse tower
+const LIGHTHOUSE_RADIUS: i32 = 2;
+/// Height of each coloured band of a lighthouse tower
+const LIGHTHOUSE_BAND: i32 = 3;
+
+/// Generate a man_made structure. `mill_streams` are the waterways a watermill may stand
+/// on, see [`mills::is_mill_stream`].
+pub fn generate_man_made(
+    editor: &mut WorldEditor,
+    element: &ProcessedElement,
+    args: &Args,
+    mill_streams: &[ProcessedWay],
+) {
     // Skip if 'layer' or 'level' is negative in the tags
     if let Some(layer) = element.tags().get("layer") {
         if layer.parse::<i32>().unwrap_or(0) < 0 {
@@ -21,17 +54,67 @@ pub fn generate_man_made(editor: &mut WorldEditor, element: &ProcessedElement, _
     if let Some(man_made_type) = element.tags().get("man_made") {
         match man_made_type.as_str() {
             "pier" => generate_pier(editor, element),
+            "quay" => {
+                if let ProcessedElement::Way(way) = element {
+                    harbours::generate_quay(editor, way);
+                }
+            }
+            "breakwater" | "groyne" => generate_breakwater(editor, element),
+            "lighthouse" => {
+                if let Some((x, z)) = centre(element) {
+                    generate_lighthouse(editor, x, z, element.tags(), args.scale);
+                }
+            }
+            "windmill" => {
+                if let Some((x, z)) = centre(element) {
+                    mills::generate_windmill(editor, x, z, element.tags(), args.scale);
+                }
+            }
+            "watermill" => {
+                if let Some((x, z)) = centre(element) {
+                    mills::generate_watermill(editor, x, z, mill_streams);
+                }
+            }
             "antenna" => generate_antenna(editor, element),
             "chimney" => generate_chimney(editor, element),
             "water_well" => generate_water_well(editor, element),
             "water_tower" => generate_water_tower(editor, element),
             "mast" => generate_antenna(editor, element),
+            "storm_surge_barrier" | "flood_gate" => {
+                generate_flood_barrier(editor, element, args.flood_scenario)
+            }
             _ => {} // Unknown man_made type, ignore
         }
     }
 }
 
-/// Generate a pier structure with OAK_SLAB planks and OAK_LOG support pillars
+/// Whether a structure mapped with a `building` tag is drawn here rather than as a plain
+/// building
+pub fn is_building_structure(tags: &HashMap<String, String>) -> bool {
+    matches!(
+        tags.get("man_made").map(String::as_str),
+        Some("lighthouse" | "windmill" | "watermill")
+    )
+}
+
+/// Middle of a structure mapped as a node or by its outline. The closing node of an outline
+/// repeats its first one and is only counted once.
+fn centre(element: &ProcessedElement) -> Option<(i32, i32)> {
+    let mut nodes: Vec<&ProcessedNode> = element.nodes().collect();
+    if nodes.len() > 1 && nodes.first().map(|n| n.id) == nodes.last().map(|n| n.id) {
+        nodes.pop();
+    }
+    let count = nodes.len() as i32;
+    (count > 0).then(|| {
+        (
+            nodes.iter().map(|n| n.x).sum::<i32>() / count,
+            nodes.iter().map(|n| n.z).sum::<i32>() / count,
+        )
+    })
+}
+
+/// Generate a pier: a deck of oak slabs, or stone slabs for concrete piers, on piles driven
+/// down to the bed, with mooring bollards along its edges
 fn generate_pier(editor: &mut WorldEditor, element: &ProcessedElement) {
     if let ProcessedElement::Way(way) = element {
         let nodes = &way.nodes;
@@ -48,6 +131,11 @@ fn generate_pier(editor: &mut WorldEditor, element: &ProcessedElement) {
 
         let pier_height = 1; // Pier deck height above ground
         let support_spacing = 4; // Support pillars every 4 blocks
+        let (deck_block, pile_block) = if harbours::is_concrete(element.tags()) {
+            (SMOOTH_STONE_SLAB, STONE_BRICKS)
+        } else {
+            (OAK_SLAB, OAK_LOG)
+        };
 
         // Generate the pier walkway using bresenham line algorithm
         for i in 0..nodes.len() - 1 {
@@ -62,26 +150,180 @@ fn generate_pier(editor: &mut WorldEditor, element: &ProcessedElement) {
                 let half_width = pier_width / 2;
                 for x in (center_x - half_width)..=(center_x + half_width) {
                     for z in (center_z - half_width)..=(center_z + half_width) {
-                        editor.set_block(OAK_SLAB, x, pier_height, z, None, None);
+                        editor.set_block(deck_block, x, pier_height, z, None, None);
                     }
                 }
 
-                // Add support pillars every few blocks
+                // Place support pillars at the edges of the pier
+                let support_positions = [
+                    (center_x - half_width, *center_z), // Left side
+                    (center_x + half_width, *center_z), // Right side
+                ];
+
+                // Add support pillars every few blocks, down through the water
                 if index % support_spacing == 0 {
-                    let half_width = pier_width / 2;
+                    for (pillar_x, pillar_z) in support_positions {
+                        harbours::sink_foundation(
+                            editor, pile_block, pile_block, pillar_x, pillar_z,
+                        );
+                    }
+                }
 
-                    // Place support pillars at the edges of the pier
-                    let support_positions = [
-                        (center_x - half_width, center_z), // Left side
-                        (center_x + half_width, center_z), // Right side
-                    ];
+                // Mooring bollards on the deck edges, between the piles
+                if index % harbours::BOLLARD_SPACING == harbours::BOLLARD_SPACING / 2 {
+                    for (bollard_x, bollard_z) in support_positions {
+                        harbours::place_bollard(editor, bollard_x, pier_height + 1, bollard_z);
+                    }
+                }
+            }
+        }
+    }
+}
 
-                    for (pillar_x, pillar_z) in support_positions {
-                        // Support pillars going down from pier level
-                        editor.set_block(OAK_LOG, pillar_x, 0, *pillar_z, None, None);
+/// Generate a breakwater or groyne: a bank of rough stone along the way, or around the
+/// outline of a mole mapped as an area, piled up from the bed and rising a block above the
+/// ground and the water
+fn generate_breakwater(editor: &mut WorldEditor, element: &ProcessedElement) {
+    let ProcessedElement::Way(way) = element else {
+        return;
+    };
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            for (dx, dz) in [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let crest = if (dx, dz) == (0, 0) {
+                    STONE
+                } else {
+                    COBBLESTONE
+                };
+                harbours::sink_foundation(editor, COBBLESTONE, COBBLESTONE, x + dx, z + dz);
+                editor.set_block(COBBLESTONE, x + dx, 0, z + dz, None, Some(&[BEDROCK]));
+                editor.set_block(crest, x + dx, 1, z + dz, None, None);
+            }
+        }
+    }
+}
+
+/// Colours of the bands of a lighthouse tower from its `colour` tag, such as "white" or
+/// "red;white". Towers without one are banded red and white.
+fn lighthouse_bands(tags: &HashMap<String, String>) -> [Block; 2] {
+    let colours: Vec<Block> = tags
+        .get("colour")
+        .or_else(|| tags.get("building:colour"))
+        .into_iter()
+        .flat_map(|c| c.split([';', ',']))
+        .filter_map(|c| match c.trim() {
+            "white" => Some(WHITE_CONCRETE),
+            "red" => Some(RED_CONCRETE),
+            "black" => Some(BLACK_CONCRETE),
+            "grey" | "gray" => Some(LIGHT_GRAY_CONCRETE),
+            "yellow" => Some(YELLOW_CONCRETE),
+            "green" => Some(GREEN_CONCRETE),
+            _ => None,
+        })
+        .collect();
+    match colours.as_slice() {
+        [] => [RED_CONCRETE, WHITE_CONCRETE],
+        [single] => [*single, *single],
+        [first, second, ..] => [*first, *second],
+    }
+}
+
+/// Generate a lighthouse: a round tower in coloured bands as tall as its `height` tag,
+/// with a railed gallery around a glazed lantern room and its light on top
+fn generate_lighthouse(
+    editor: &mut WorldEditor,
+    x: i32,
+    z: i32,
+    tags: &HashMap<String, String>,
+    scale: f64,
+) {
+    let meters = tags
+        .get("height")
+        .and_then(|h| h.trim().trim_end_matches('m').trim().parse::<f64>().ok())
+        .unwrap_or(DEFAULT_LIGHTHOUSE_HEIGHT);
+    let top = ((meters * scale).round() as i32).max(MIN_LIGHTHOUSE_HEIGHT);
+    let bands = lighthouse_bands(tags);
+    let radius = LIGHTHOUSE_RADIUS;
+    let within = |dx: i32, dz: i32, r: i32| dx * dx + dz * dz <= r * r + r;
+
+    for dx in -radius - 1..=radius + 1 {
+        for dz in -radius - 1..=radius + 1 {
+            let (bx, bz) = (x + dx, z + dz);
+            if within(dx, dz, radius) {
+                // The wall of the tower, hollow inside
+                if !within(dx, dz, radius - 1) {
+                    for y in 1..top {
+                        let band = bands[((y - 1) / LIGHTHOUSE_BAND % 2) as usize];
+                        editor.set_block(band, bx, y, bz, None, None);
                     }
                 }
+                editor.set_block(SMOOTH_STONE, bx, top, bz, None, None);
+            } else if within(dx, dz, radius + 1) {
+                // Gallery around the lantern
+                editor.set_block(SMOOTH_STONE_SLAB, bx, top, bz, None, None);
+                editor.set_block(IRON_BARS, bx, top + 1, bz, None, None);
+            }
+        }
+    }
+
+    // Lantern room: glazing around the light, under a dark roof
+    for dx in -1..=1 {
+        for dz in -1..=1 {
+            for y in top + 1..=top + 2 {
+                let block = if (dx, dz) == (0, 0) {
+                    SEA_LANTERN
+                } else {
+                    GLASS
+                };
+                editor.set_block(block, x + dx, y, z + dz, None, None);
+            }
+            editor.set_block(BLACK_CONCRETE, x + dx, top + 3, z + dz, None, None);
+        }
+    }
+    editor.set_block(LIGHTNING_ROD, x, top + 4, z, None, None);
+}
+
+/// Generate a movable flood barrier across a harbour entrance: stone towers every
+/// `FLOOD_GATE_SPAN` blocks with iron gates between them. The gates are shut down to the
+/// sea bed in a storm surge and raised clear of the water otherwise.
+fn generate_flood_barrier(
+    editor: &mut WorldEditor,
+    element: &ProcessedElement,
+    scenario: FloodScenario,
+) {
+    let ProcessedElement::Way(way) = element else {
+        return;
+    };
+    let points: Vec<(i32, i32)> = way
+        .nodes
+        .windows(2)
+        .flat_map(|pair| bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z))
+        .map(|(x, _, z)| (x, z))
+        .collect();
+    let last = points.len().saturating_sub(1);
+    let sea_level_y = editor.get_ground().and_then(|ground| ground.sea_level_y());
+
+    for (index, &(x, z)) in points.iter().enumerate() {
+        let bed_y = editor.get_ground_level(x, z);
+        // Water stands at sea level on DHM terrain and at ground level otherwise
+        let gate_top = sea_level_y.unwrap_or(bed_y).max(bed_y) + FLOOD_GATE_HEIGHT;
+
+        if index % FLOOD_GATE_SPAN == 0 || index == last {
+            for y in bed_y..=gate_top + 2 {
+                editor.set_block_absolute(STONE_BRICKS, x, y, z, Some(&[WATER]), None);
             }
+            editor.set_block_absolute(STONE_BRICK_SLAB, x, gate_top + 3, z, None, None);
+            continue;
+        }
+
+        // Walkway beam between the towers
+        editor.set_block_absolute(SMOOTH_STONE, x, gate_top + 2, z, None, None);
+        let gate = match scenario {
+            FloodScenario::StormSurge => bed_y + 1..=gate_top,
+            FloodScenario::Normal => gate_top..=gate_top + 1,
+        };
+        for y in gate {
+            editor.set_block_absolute(IRON_BLOCK, x, y, z, Some(&[WATER]), None);
         }
     }
 }
@@ -240,11 +482,19 @@ fn generate_water_tower(editor: &mut WorldEditor, element: &ProcessedElement) {
 }
 
 /// Generate man_made structures for node elements
-pub fn generate_man_made_nodes(editor: &mut WorldEditor, node: &ProcessedNode) {
+pub fn generate_man_made_nodes(
+    editor: &mut WorldEditor,
+    node: &ProcessedNode,
+    scale: f64,
+    mill_streams: &[ProcessedWay],
+) {
     if let Some(man_made_type) = node.tags.get("man_made") {
         let element = ProcessedElement::Node(node.clone());
 
         match man_made_type.as_str() {
+            "lighthouse" => generate_lighthouse(editor, node.x, node.z, &node.tags, scale),
+            "windmill" => mills::generate_windmill(editor, node.x, node.z, &node.tags, scale),
+            "watermill" => mills::generate_watermill(editor, node.x, node.z, mill_streams),
             "antenna" => generate_antenna(editor, &element),
             "chimney" => generate_chimney(editor, &element),
             "water_well" => generate_water_well(editor, &element),
@@ -254,3 +504,49 @@ pub fn generate_man_made_nodes(editor: &mut WorldEditor, node: &ProcessedNode) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lighthouse_bands_follow_the_colour_tag() {
+        let tags = |colour: &str| HashMap::from([("colour".to_string(), colour.to_string())]);
+        assert_eq!(
+            lighthouse_bands(&HashMap::new()),
+            [RED_CONCRETE, WHITE_CONCRETE]
+        );
+        assert_eq!(lighthouse_bands(&tags("white")), [WHITE_CONCRETE; 2]);
+        assert_eq!(
+            lighthouse_bands(&tags("black;white")),
+            [BLACK_CONCRETE, WHITE_CONCRETE]
+        );
+        assert_eq!(
+            lighthouse_bands(&tags("brick")),
+            [RED_CONCRETE, WHITE_CONCRETE]
+        );
+    }
+
+    #[test]
+    fn outlines_count_their_closing_node_once() {
+        let node = |id, x, z| ProcessedNode {
+            id,
+            tags: HashMap::new(),
+            x,
+            z,
+            exact: None,
+        };
+        let outline = ProcessedElement::Way(ProcessedWay {
+            id: 1,
+            nodes: vec![
+                node(1, 0, 0),
+                node(2, 10, 0),
+                node(3, 10, 10),
+                node(4, 0, 10),
+                node(1, 0, 0),
+            ],
+            tags: HashMap::new(),
+        });
+        assert_eq!(centre(&outline), Some((5, 5)));
+    }
+}
@@ -0,0 +1,290 @@
+//! Traditional Danish mills (`man_made=windmill` and `man_made=watermill`).
+//!
+//! Danish windmills are mostly smock mills (hollændermøller): a whitewashed base of one or
+//! more storeys with a stage running around its top, an octagonal tarred timber smock
+//! tapering above it and a cap that turns the sails into the wind. The four lattice sails
+//! are drawn in an X, as if caught mid-turn, on the west face of the cap, where the
+//! prevailing wind comes from, with the fantail behind. Watermills are a mill house by
+//! their stream with an undershot wheel on the side facing it, and a mill pond dug when no
+//! water is mapped beside them. Modern turbines are `power=generator` and drawn by the power module.
+
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use std::collections::HashMap;
+
+/// Height (meters) of a windmill to the top of its cap without a `height` tag
+const DEFAULT_WINDMILL_HEIGHT: f64 = 18.0;
+/// Lowest windmill drawn, in blocks
+const MIN_WINDMILL_HEIGHT: i32 = 12;
+/// Radius of the base of a smock mill
+const BASE_RADIUS: i32 = 4;
+/// Radius of the top of the smock, under the cap
+const TOP_RADIUS: i32 = 2;
+/// Whitewashed base
+const MILL_BASE: Block = WHITE_CONCRETE;
+/// Tarred timber of the smock and the cap
+const SMOCK: Block = DARK_OAK_PLANKS;
+/// Sail cloth spread on the lattice
+const SAIL_CLOTH: Block = WHITE_WOOL;
+/// Radius of a watermill's wheel
+const WHEEL_RADIUS: i32 = 3;
+/// Radius of a mill pond
+const POND_RADIUS: i32 = 6;
+/// How far around a watermill water counts as its stream or pond
+const WATER_SEARCH: i32 = 8;
+/// How far from a watermill a mapped waterway counts as the stream that turns its wheel
+const STREAM_SEARCH: i32 = 40;
+
+/// Whether a way is a waterway a watermill can stand on
+pub fn is_mill_stream(way: &ProcessedWay) -> bool {
+    matches!(
+        way.tags.get("waterway").map(String::as_str),
+        Some("river" | "stream" | "brook" | "canal" | "ditch" | "drain" | "mill_race")
+    )
+}
+
+/// Direction, as a unit step along X or Z, from (`x`, `z`) to the nearest of `streams`
+/// within [`STREAM_SEARCH`] blocks
+fn stream_side(x: i32, z: i32, streams: &[ProcessedWay]) -> Option<(i32, i32)> {
+    let (dx, dz) = streams
+        .iter()
+        .flat_map(|way| way.nodes.windows(2))
+        .flat_map(|pair| bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z))
+        .map(|(sx, _, sz)| (sx - x, sz - z))
+        .filter(|&(dx, dz)| dx.abs().max(dz.abs()) <= STREAM_SEARCH)
+        .min_by_key(|&(dx, dz)| dx * dx + dz * dz)?;
+    Some(match (dx, dz) {
+        // A stream running through the mill house turns a wheel on its east side
+        (0, 0) => (1, 0),
+        _ if dx.abs() >= dz.abs() => (dx.signum(), 0),
+        _ => (0, dz.signum()),
+    })
+}
+
+/// Whether (dx, dz) lies within the octagon of radius `r`
+fn in_octagon(dx: i32, dz: i32, r: i32) -> bool {
+    dx.abs().max(dz.abs()) <= r && dx.abs() + dz.abs() <= r + r / 2
+}
+
+/// Ends of the four sails, as (y, z) offsets from the hub, in an X so the mill looks to be
+/// turning. Sails reach `length` blocks out but stop two blocks above the ground.
+fn sail_tips(hub_y: i32, length: i32) -> [(i32, i32); 4] {
+    let reach = ((length as f64 / std::f64::consts::SQRT_2).round() as i32).min(hub_y - 2);
+    [
+        (reach, reach),
+        (reach, -reach),
+        (-reach, -reach),
+        (-reach, reach),
+    ]
+}
+
+/// Generate a smock mill as tall as its `height` tag
+pub fn generate_windmill(
+    editor: &mut WorldEditor,
+    x: i32,
+    z: i32,
+    tags: &HashMap<String, String>,
+    scale: f64,
+) {
+    let meters = tags
+        .get("height")
+        .and_then(|h| h.trim().trim_end_matches('m').trim().parse::<f64>().ok())
+        .unwrap_or(DEFAULT_WINDMILL_HEIGHT);
+    let height = ((meters * scale).round() as i32).max(MIN_WINDMILL_HEIGHT);
+    let stage_y = height / 3;
+    let cap_y = height - 2;
+
+    // Base and smock, hollow inside, tapering from the stage up to the cap
+    for y in 1..cap_y {
+        let (block, radius) = if y <= stage_y {
+            (MILL_BASE, BASE_RADIUS)
+        } else {
+            let progress = (y - stage_y) as f64 / (cap_y - stage_y) as f64;
+            let radius = BASE_RADIUS as f64 - (BASE_RADIUS - TOP_RADIUS) as f64 * progress;
+            (SMOCK, radius.round() as i32)
+        };
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                if in_octagon(dx, dz, radius) && !in_octagon(dx, dz, radius - 1) {
+                    editor.set_block(block, x + dx, y, z + dz, None, None);
+                }
+            }
+        }
+    }
+
+    // Stage around the top of the base, with a railing
+    let stage = BASE_RADIUS + 2;
+    for dx in -stage..=stage {
+        for dz in -stage..=stage {
+            if in_octagon(dx, dz, stage) && !in_octagon(dx, dz, BASE_RADIUS) {
+                editor.set_block(OAK_SLAB_TOP, x + dx, stage_y, z + dz, None, None);
+                if !in_octagon(dx, dz, stage - 1) {
+                    editor.set_block(OAK_FENCE, x + dx, stage_y + 1, z + dz, None, None);
+                }
+            }
+        }
+    }
+
+    // Cap, narrowing to a ridge
+    for (y, radius) in [(cap_y, TOP_RADIUS), (cap_y + 1, TOP_RADIUS - 1)] {
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                if in_octagon(dx, dz, radius) {
+                    editor.set_block(SMOCK, x + dx, y, z + dz, None, None);
+                }
+            }
+        }
+    }
+    editor.set_block(SMOCK, x, height, z, None, None);
+
+    // Windshaft out of the west face of the cap, with the sails on its end
+    let hub_x = x - TOP_RADIUS - 2;
+    let hub_y = cap_y + 1;
+    for shaft_x in hub_x..x - TOP_RADIUS + 1 {
+        editor.set_block(DARK_OAK_LOG, shaft_x, hub_y, z, None, None);
+    }
+    for (tip_y, tip_z) in sail_tips(hub_y, height * 11 / 20) {
+        let (dy, dz) = (tip_y.signum(), tip_z.signum());
+        let stock = bresenham_line(0, hub_y, z, 0, hub_y + tip_y, z + tip_z);
+        let span = stock.len();
+        for (index, &(_, y, sz)) in stock.iter().enumerate().skip(1) {
+            editor.set_block(OAK_FENCE, hub_x, y, sz, None, None);
+            // Cloth on the trailing side of the outer part of each sail
+            if index * 3 >= span {
+                editor.set_block(SAIL_CLOTH, hub_x, y - dz, sz + dy, None, None);
+            }
+        }
+    }
+
+    // Fantail on the east side of the cap, turning it into the wind
+    let tail_x = x + TOP_RADIUS + 2;
+    for post_x in x + TOP_RADIUS..tail_x {
+        editor.set_block(OAK_FENCE, post_x, cap_y, z, None, None);
+    }
+    for offset in -2..=2 {
+        editor.set_block(OAK_FENCE, tail_x, cap_y + offset, z, None, None);
+        editor.set_block(OAK_FENCE, tail_x + offset, cap_y, z, None, None);
+    }
+}
+
+/// Generate a watermill: a whitewashed mill house under a tiled roof, its wheel turning in
+/// a race on the side facing the nearest of `streams` (east without one), and a mill pond
+/// north of it unless water is already mapped nearby
+pub fn generate_watermill(editor: &mut WorldEditor, x: i32, z: i32, streams: &[ProcessedWay]) {
+    let stream = stream_side(x, z, streams);
+    let water_nearby = stream.is_some()
+        || (-WATER_SEARCH..=WATER_SEARCH).any(|dx| {
+            (-WATER_SEARCH..=WATER_SEARCH)
+                .any(|dz| editor.check_for_block(x + dx, 0, z + dz, Some(&[WATER])))
+        });
+
+    // Mill house, seven by nine blocks
+    let (half_x, half_z) = (3, 4);
+    for dx in -half_x..=half_x {
+        for dz in -half_z..=half_z {
+            let wall = dx.abs() == half_x || dz.abs() == half_z;
+            for y in 1..=4 {
+                if wall {
+                    editor.set_block(MILL_BASE, x + dx, y, z + dz, None, None);
+                }
+            }
+            // Gabled roof, its ridge running north-south
+            let rise = half_x + 1 - dx.abs();
+            for y in 5..5 + rise {
+                let block = if y == 4 + rise { BRICK } else { SMOCK };
+                editor.set_block(block, x + dx, y, z + dz, None, None);
+            }
+        }
+    }
+
+    // Wheel race along the wall facing the stream, and the wheel in it. `along` runs along
+    // the wall, so the wheel turns in the plane of the wall.
+    let (side_x, side_z) = stream.unwrap_or((1, 0));
+    let (wheel_x, wheel_z) = (x + side_x * (half_x + 1), z + side_z * (half_z + 1));
+    let along = |offset: i32| {
+        if side_x != 0 {
+            (wheel_x, wheel_z + offset)
+        } else {
+            (wheel_x + offset, wheel_z)
+        }
+    };
+    for offset in -WHEEL_RADIUS - 1..=WHEEL_RADIUS + 1 {
+        let (rx, rz) = along(offset);
+        for y in -1..=0 {
+            editor.set_block(WATER, rx, y, rz, None, Some(&[BEDROCK]));
+        }
+    }
+    let hub_y = 2;
+    for dy in -WHEEL_RADIUS..=WHEEL_RADIUS {
+        for offset in -WHEEL_RADIUS..=WHEEL_RADIUS {
+            let distance = ((dy * dy + offset * offset) as f64).sqrt().round() as i32;
+            let spoke = dy == 0 || offset == 0;
+            if distance == WHEEL_RADIUS || (spoke && distance < WHEEL_RADIUS) {
+                let (wx, wz) = along(offset);
+                editor.set_block(OAK_PLANKS, wx, hub_y + dy, wz, Some(&[WATER]), None);
+            }
+        }
+    }
+
+    if water_nearby {
+        return;
+    }
+    let pond_z = z - half_z - POND_RADIUS - 2;
+    for dx in -POND_RADIUS..=POND_RADIUS {
+        for dz in -POND_RADIUS..=POND_RADIUS {
+            if dx * dx + dz * dz > POND_RADIUS * POND_RADIUS {
+                continue;
+            }
+            editor.set_block(WATER, x + dx, 0, pond_z + dz, None, Some(&[BEDROCK]));
+            editor.set_block(WATER, x + dx, -1, pond_z + dz, None, Some(&[BEDROCK]));
+            editor.set_block(DIRT, x + dx, -2, pond_z + dz, None, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn octagons_cut_their_corners() {
+        assert!(in_octagon(4, 0, 4));
+        assert!(in_octagon(2, 4, 4));
+        assert!(!in_octagon(4, 4, 4));
+        assert!(!in_octagon(5, 0, 4));
+    }
+
+    #[test]
+    fn watermill_wheels_face_the_nearest_stream() {
+        let node = |id, x, z| crate::osm_parser::ProcessedNode {
+            id,
+            tags: HashMap::new(),
+            x,
+            z,
+            exact: None,
+        };
+        let stream = |id, from, to| ProcessedWay {
+            id,
+            nodes: vec![from, to],
+            tags: HashMap::from([("waterway".to_string(), "stream".to_string())]),
+        };
+        let streams = [
+            // West of the mill, running north-south
+            stream(1, node(1, -10, -30), node(2, -10, 30)),
+            // Further away to the south
+            stream(2, node(3, -30, 25), node(4, 30, 25)),
+        ];
+        assert_eq!(stream_side(0, 0, &streams), Some((-1, 0)));
+        assert_eq!(stream_side(0, 20, &streams), Some((0, 1)));
+        assert_eq!(stream_side(200, 0, &streams), None);
+    }
+
+    #[test]
+    fn sails_stop_above_the_ground() {
+        assert_eq!(sail_tips(20, 10)[0], (7, 7));
+        // A short mill's sails are cut off two blocks above the ground
+        assert_eq!(sail_tips(8, 20)[2], (-6, -6));
+    }
+}
@@ -1,26 +1,46 @@
+pub mod accessibility;
 pub mod advertising;
+pub mod allotments;
 pub mod amenities;
 pub mod barriers;
 pub mod bridges;
 pub mod buildings;
+pub mod cemeteries;
+pub mod churches;
+pub mod cycleways;
 pub mod doors;
 pub mod emergency;
+pub mod ferries;
+pub mod golf;
+pub mod habitat;
+pub mod harbours;
 pub mod highways;
 pub mod historic;
+pub mod infill;
 pub mod landuse;
 pub mod leisure;
 pub mod man_made;
+pub mod mills;
 pub mod natural;
 pub mod oceans;
+pub mod parking;
+pub mod pitches;
+pub mod plazas;
+pub mod plot_grid;
 pub mod power;
 pub mod railways;
+pub mod steps;
+pub mod street_details;
 pub mod subprocessor;
 pub mod tourisms;
 pub mod tree;
+pub mod tunnels;
 pub mod water_areas;
 pub mod waterways;
 
-use crate::osm_parser::ProcessedNode;
+use crate::clipping::clip_way_to_bbox;
+use crate::coordinate_system::cartesian::XZBBox;
+use crate::osm_parser::{ProcessedMemberRole, ProcessedNode, ProcessedRelation};
 
 /// Merges way segments that share endpoints into closed rings.
 /// Used by water_areas.rs and boundaries.rs for assembling relation members.
@@ -121,3 +141,43 @@ pub fn merge_way_segments(rings: &mut Vec<Vec<ProcessedNode>>) {
         merge_way_segments(rings);
     }
 }
+
+/// Assembles the closed rings of a multipolygon relation's members with the given role:
+/// open segments are merged, the rings clipped to the world and closed when their ends
+/// are within a block of each other, and rings still open or too small are dropped.
+pub fn assemble_rings(
+    relation: &ProcessedRelation,
+    role: ProcessedMemberRole,
+    xzbbox: &XZBBox,
+) -> Vec<Vec<ProcessedNode>> {
+    let mut rings: Vec<Vec<ProcessedNode>> = relation
+        .members
+        .iter()
+        .filter(|m| m.role == role)
+        .map(|m| m.way.nodes.clone())
+        .collect();
+
+    merge_way_segments(&mut rings);
+
+    // Member ways are kept unclipped during parsing to allow ring assembly, so the
+    // merged rings may extend beyond the requested area. Clipping prevents oversized
+    // flood fills and unnecessary block placement.
+    let mut rings: Vec<Vec<ProcessedNode>> = rings
+        .into_iter()
+        .map(|ring| clip_way_to_bbox(&ring, xzbbox))
+        .filter(|ring| ring.len() >= 4)
+        .collect();
+
+    let nearly_closed = |ring: &[ProcessedNode]| {
+        let (first, last) = (&ring[0], &ring[ring.len() - 1]);
+        first.id == last.id || ((first.x - last.x).abs() <= 1 && (first.z - last.z).abs() <= 1)
+    };
+    for ring in &mut rings {
+        if ring[0].id != ring[ring.len() - 1].id && nearly_closed(ring) {
+            let close_node = ring[0].clone();
+            ring.push(close_node);
+        }
+    }
+    rings.retain(|ring| nearly_closed(ring));
+    rings
+}
@@ -1,13 +1,101 @@
 use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
+use crate::data_sources::is_jutland;
 use crate::deterministic_rng::element_rng;
-use crate::element_processing::tree::{Tree, TreeType};
+use crate::element_processing::tree::{ForestMix, Tree, TreeType};
 use crate::floodfill_cache::{BuildingFootprintBitmap, FloodFillCache};
-use crate::osm_parser::{ProcessedElement, ProcessedMemberRole, ProcessedRelation, ProcessedWay};
+use crate::osm_parser::{ProcessedElement, ProcessedRelation, ProcessedWay};
 use crate::world_editor::WorldEditor;
 use rand::{prelude::IndexedRandom, Rng};
 
+/// Water level on tidal flats (`--tide`)
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Tide {
+    /// Flats flooded with a block of shallow water, up to sea level on DHM terrain
+    High,
+    /// Exposed mud cut by meandering tidal channels
+    Low,
+}
+
+/// Tidal channels (priels) run roughly north-south, this far apart
+const TIDAL_CHANNEL_SPACING: f64 = 40.0;
+const TIDAL_CHANNEL_WIDTH: f64 = 3.0;
+
+/// Whether a block of a tidal flat lies in one of the meandering channels left at low tide
+fn is_tidal_channel(x: i32, z: i32) -> bool {
+    let z = z as f64;
+    let meander = (z / 23.0).sin() * 9.0 + (z / 61.0).sin() * 17.0;
+    (x as f64 + meander).rem_euclid(TIDAL_CHANNEL_SPACING) < TIDAL_CHANNEL_WIDTH
+}
+
+/// One block of a `wetland=tidalflat` area at the selected tide
+fn generate_tidal_flat(editor: &mut WorldEditor, x: i32, z: i32, tide: Tide) {
+    if tide == Tide::Low && !is_tidal_channel(x, z) {
+        return;
+    }
+    editor.set_block(WATER, x, 0, z, Some(&[MUD]), None);
+    editor.set_block(MUD, x, -1, z, None, None);
+
+    // At high tide the flats are part of the sea: flood them up to sea level
+    let sea_level_y = editor.get_ground().and_then(|ground| ground.sea_level_y());
+    if let (Tide::High, Some(sly)) = (tide, sea_level_y) {
+        for y in (editor.get_ground_level(x, z) + 1)..=sly {
+            editor.set_block_absolute(WATER, x, y, z, None, None);
+        }
+    }
+}
+
+/// Meters between the trees of a tree row
+const TREE_ROW_SPACING_M: f64 = 8.0;
+/// Closest the trees of a row are planted, in blocks, so their crowns stay apart
+const MIN_TREE_ROW_SPACING: usize = 3;
+/// Surfaces a tree row never plants on: roads and paths it runs beside, and water
+const TREE_ROW_SKIPPED: [Block; 6] = [
+    BLACK_CONCRETE,
+    WHITE_CONCRETE,
+    GRAY_CONCRETE,
+    LIGHT_GRAY_CONCRETE,
+    DIRT_PATH,
+    WATER,
+];
+
+/// Blocks between the trees of a tree row at `scale`
+fn tree_row_spacing(scale: f64) -> usize {
+    ((TREE_ROW_SPACING_M * scale).round() as usize).max(MIN_TREE_ROW_SPACING)
+}
+
+/// Plants the trees of a `natural=tree_row` evenly spaced along its way, such as the limes
+/// of an allé or the trees around a churchyard. A row is planted with one species.
+fn generate_tree_row(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    scale: f64,
+    building_footprints: &BuildingFootprintBitmap,
+) {
+    let mut rng = element_rng(way.id);
+    let tree_type = *TreeType::candidates(&way.tags)
+        .choose(&mut rng)
+        .unwrap_or(&TreeType::Oak);
+    let spacing = tree_row_spacing(scale);
+
+    let mut along = 0;
+    let mut previous: Option<(i32, i32)> = None;
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            // Consecutive segments share their end node
+            if previous == Some((x, z)) {
+                continue;
+            }
+            previous = Some((x, z));
+            if along % spacing == 0 && !editor.check_for_block(x, 0, z, Some(&TREE_ROW_SKIPPED)) {
+                Tree::create_of_type(editor, (x, 1, z), tree_type, Some(building_footprints));
+            }
+            along += 1;
+        }
+    }
+}
+
 pub fn generate_natural(
     editor: &mut WorldEditor,
     element: &ProcessedElement,
@@ -21,59 +109,7 @@ pub fn generate_natural(
                 let x: i32 = node.x;
                 let z: i32 = node.z;
 
-                let mut trees_ok_to_generate: Vec<TreeType> = vec![];
-                if let Some(species) = element.tags().get("species") {
-                    if species.contains("Betula") {
-                        trees_ok_to_generate.push(TreeType::Birch);
-                    }
-                    if species.contains("Quercus") {
-                        trees_ok_to_generate.push(TreeType::Oak);
-                    }
-                    if species.contains("Picea") {
-                        trees_ok_to_generate.push(TreeType::Spruce);
-                    }
-                } else if let Some(genus_wikidata) = element.tags().get("genus:wikidata") {
-                    match genus_wikidata.as_str() {
-                        "Q12004" => trees_ok_to_generate.push(TreeType::Birch),
-                        "Q26782" => trees_ok_to_generate.push(TreeType::Oak),
-                        "Q25243" => trees_ok_to_generate.push(TreeType::Spruce),
-                        _ => {
-                            trees_ok_to_generate.push(TreeType::Oak);
-                            trees_ok_to_generate.push(TreeType::Spruce);
-                            trees_ok_to_generate.push(TreeType::Birch);
-                        }
-                    }
-                } else if let Some(genus) = element.tags().get("genus") {
-                    match genus.as_str() {
-                        "Betula" => trees_ok_to_generate.push(TreeType::Birch),
-                        "Quercus" => trees_ok_to_generate.push(TreeType::Oak),
-                        "Picea" => trees_ok_to_generate.push(TreeType::Spruce),
-                        _ => trees_ok_to_generate.push(TreeType::Oak),
-                    }
-                } else if let Some(leaf_type) = element.tags().get("leaf_type") {
-                    match leaf_type.as_str() {
-                        "broadleaved" => {
-                            trees_ok_to_generate.push(TreeType::Oak);
-                            trees_ok_to_generate.push(TreeType::Birch);
-                        }
-                        "needleleaved" => trees_ok_to_generate.push(TreeType::Spruce),
-                        _ => {
-                            trees_ok_to_generate.push(TreeType::Oak);
-                            trees_ok_to_generate.push(TreeType::Spruce);
-                            trees_ok_to_generate.push(TreeType::Birch);
-                        }
-                    }
-                } else {
-                    trees_ok_to_generate.push(TreeType::Oak);
-                    trees_ok_to_generate.push(TreeType::Spruce);
-                    trees_ok_to_generate.push(TreeType::Birch);
-                }
-
-                if trees_ok_to_generate.is_empty() {
-                    trees_ok_to_generate.push(TreeType::Oak);
-                    trees_ok_to_generate.push(TreeType::Spruce);
-                    trees_ok_to_generate.push(TreeType::Birch);
-                }
+                let trees_ok_to_generate = TreeType::candidates(element.tags());
 
                 let mut rng = element_rng(element.id());
                 let tree_type = *trees_ok_to_generate
@@ -82,6 +118,10 @@ pub fn generate_natural(
 
                 Tree::create_of_type(editor, (x, 1, z), tree_type, Some(building_footprints));
             }
+        } else if natural_type == "tree_row" {
+            if let ProcessedElement::Way(way) = element {
+                generate_tree_row(editor, way, args.scale, building_footprints);
+            }
         } else {
             let mut previous_node: Option<(i32, i32)> = None;
             let mut corner_addup: (i32, i32, i32) = (0, 0, 0);
@@ -90,7 +130,7 @@ pub fn generate_natural(
 
             // Determine block type based on natural tag
             let block_type: Block = match natural_type.as_str() {
-                "scrub" | "grassland" | "wood" | "heath" | "tree_row" => GRASS_BLOCK,
+                "scrub" | "grassland" | "wood" | "heath" => GRASS_BLOCK,
                 "sand" | "dune" => SAND,
                 "beach" | "shoal" => {
                     let surface = element.tags().get("natural").unwrap_or(&binding);
@@ -140,28 +180,8 @@ pub fn generate_natural(
                 let filled_area: Vec<(i32, i32)> =
                     flood_fill_cache.get_or_compute(way, args.timeout.as_ref());
 
-                let trees_ok_to_generate: Vec<TreeType> = {
-                    let mut trees: Vec<TreeType> = vec![];
-                    if let Some(leaf_type) = element.tags().get("leaf_type") {
-                        match leaf_type.as_str() {
-                            "broadleaved" => {
-                                trees.push(TreeType::Oak);
-                                trees.push(TreeType::Birch);
-                            }
-                            "needleleaved" => trees.push(TreeType::Spruce),
-                            _ => {
-                                trees.push(TreeType::Oak);
-                                trees.push(TreeType::Spruce);
-                                trees.push(TreeType::Birch);
-                            }
-                        }
-                    } else {
-                        trees.push(TreeType::Oak);
-                        trees.push(TreeType::Spruce);
-                        trees.push(TreeType::Birch);
-                    }
-                    trees
-                };
+                let forest =
+                    ForestMix::from_tags(element.tags(), is_jutland(&args.bbox), args.forest_types);
 
                 // Use deterministic RNG seeded by element ID for consistent results across region boundaries
                 let mut rng = element_rng(way.id);
@@ -240,15 +260,25 @@ pub fn generate_natural(
                                 }
                             }
                         }
-                        "tree_row" | "wood" => {
+                        "wood"
+                            if forest.plantation_spacing.is_some()
+                                && editor.check_for_block(x, 0, z, Some(&[GRASS_BLOCK])) =>
+                        {
+                            forest.plant_plantation_cell(
+                                editor,
+                                (x, z),
+                                &mut rng,
+                                building_footprints,
+                            );
+                        }
+                        "wood" => {
                             if !editor.check_for_block(x, 0, z, Some(&[GRASS_BLOCK])) {
                                 continue;
                             }
                             let random_choice: i32 = rng.random_range(0..30);
                             if random_choice == 0 {
-                                let tree_type = *trees_ok_to_generate
-                                    .choose(&mut rng)
-                                    .unwrap_or(&TreeType::Oak);
+                                let tree_type =
+                                    *forest.trees.choose(&mut rng).unwrap_or(&TreeType::Oak);
                                 Tree::create_of_type(
                                     editor,
                                     (x, 1, z),
@@ -281,6 +311,12 @@ pub fn generate_natural(
                         }
                         "wetland" => {
                             if let Some(wetland_type) = element.tags().get("wetland") {
+                                if let (Some(tide), "tidalflat") =
+                                    (args.tide, wetland_type.as_str())
+                                {
+                                    generate_tidal_flat(editor, x, z, tide);
+                                    continue;
+                                }
                                 // Wetland without water blocks
                                 if matches!(wetland_type.as_str(), "wet_meadow" | "fen") {
                                     if rng.random_bool(0.3) {
@@ -551,26 +587,14 @@ pub fn generate_natural_from_relation(
     building_footprints: &BuildingFootprintBitmap,
 ) {
     if rel.tags.contains_key("natural") {
-        // Process each outer member way individually using cached flood fill.
-        // We intentionally do not combine all outer nodes into one mega-way,
-        // because that creates a nonsensical polygon spanning the whole relation
-        // extent, misses the flood fill cache, and can cause multi-GB allocations.
-        for member in &rel.members {
-            if member.role == ProcessedMemberRole::Outer {
-                // Use relation tags so the member inherits the relation's natural=* type
-                let way_with_rel_tags = ProcessedWay {
-                    id: member.way.id,
-                    nodes: member.way.nodes.clone(),
-                    tags: rel.tags.clone(),
-                };
-                generate_natural(
-                    editor,
-                    &ProcessedElement::Way(way_with_rel_tags),
-                    args,
-                    flood_fill_cache,
-                    building_footprints,
-                );
-            }
+        for ring in flood_fill_cache.multipolygon_rings(rel.id) {
+            generate_natural(
+                editor,
+                &ProcessedElement::Way(ring.clone()),
+                args,
+                flood_fill_cache,
+                building_footprints,
+            );
         }
     }
 }
@@ -23,6 +23,7 @@ pub fn generate_oceans(
     building_footprints: &CoordinateBitmap,
     explicit_water_mask: &CoordinateBitmap,
     urban_lookup: &UrbanGroundLookup,
+    sea: &mut CoordinateBitmap,
 ) {
     let polygons = build_ocean_polygons(
         coastlines,
@@ -37,7 +38,7 @@ pub fn generate_oceans(
         return;
     }
 
-    fill_water_polygons(editor, &polygons.outers, &polygons.inners);
+    fill_water_polygons(editor, &polygons.outers, &polygons.inners, sea);
 }
 
 fn build_ocean_polygons(
@@ -480,6 +481,7 @@ mod tests {
                     tags: HashMap::new(),
                     x: *x,
                     z: *z,
+                    exact: None,
                 })
                 .collect(),
         }
@@ -0,0 +1,290 @@
+//! Surface car parks for `amenity=parking` areas.
+//!
+//! A car park is laid in asphalt and split into rows of painted bays, two rows back to back
+//! between driving aisles. Service roads mapped into the car park are already on the ground
+//! when it is processed; they are kept as its aisles and the rows are lined up along them.
+//! Car parks without one get their aisles along their longer side. Some of the bays hold a
+//! parked car.
+
+use crate::args::Args;
+use crate::block_definitions::*;
+use crate::deterministic_rng::coord_rng;
+use crate::element_processing::plot_grid::PlotGrid;
+use crate::element_processing::street_details::generate_lamp_post;
+use crate::floodfill_cache::FloodFillCache;
+use crate::osm_parser::ProcessedElement;
+use crate::world_editor::WorldEditor;
+use rand::prelude::IndexedRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// Surface of the car park
+const ASPHALT: Block = BLACK_CONCRETE;
+/// Bay markings
+const PAINT: Block = WHITE_CONCRETE;
+/// Road surfaces already on the ground that are kept as aisles
+const ROAD_SURFACES: [Block; 4] = [
+    BLACK_CONCRETE,
+    WHITE_CONCRETE,
+    GRAY_CONCRETE,
+    LIGHT_GRAY_CONCRETE,
+];
+/// Width of a bay along the aisle, including its line
+const BAY_WIDTH: i32 = 3;
+/// Depth of a bay from the aisle
+const BAY_DEPTH: i32 = 5;
+/// Width of a driving aisle
+const AISLE_WIDTH: i32 = 5;
+/// Aisle and the two rows of bays behind it
+const ROW_PERIOD: i32 = AISLE_WIDTH + 2 * BAY_DEPTH;
+/// Bays between the lamp posts along the middle of a double row
+const LAMP_EVERY_BAYS: i32 = 5;
+/// Chance of a bay holding a parked car
+const PARKED_SHARE: f64 = 0.4;
+/// Smallest scale at which bays are marked; below it cars no longer fit the map
+const MIN_BAY_SCALE: f64 = 0.5;
+/// Body colours of parked cars
+const CAR_COLOURS: [Block; 6] = [
+    WHITE_CONCRETE,
+    BLACK_CONCRETE,
+    GRAY_CONCRETE,
+    LIGHT_GRAY_CONCRETE,
+    RED_CONCRETE,
+    BLUE_CONCRETE,
+];
+
+/// What a block of a car park is part of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot {
+    Aisle,
+    /// Line between two bays
+    Line,
+    /// Inside the bay `bay` of row `row`, `depth` blocks from the aisle it opens onto
+    Bay {
+        row: i32,
+        bay: i32,
+        depth: i32,
+    },
+}
+
+/// Orientation and position of the rows of a car park
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    /// Whether the aisles run along the x axis
+    along_x: bool,
+    /// Across coordinate where an aisle starts
+    aisle_start: i32,
+}
+
+impl Layout {
+    /// Lines the aisles up with the access roads inside the car park, or runs them along its
+    /// longer side from a row of bays at its edge when there are none
+    fn new(area: &[(i32, i32)], roads: &HashSet<(i32, i32)>) -> Option<Self> {
+        let extent = |cells: &mut dyn Iterator<Item = (i32, i32)>| {
+            cells.fold(None, |acc: Option<(i32, i32, i32, i32)>, (x, z)| {
+                Some(match acc {
+                    None => (x, x, z, z),
+                    Some((x0, x1, z0, z1)) => (x0.min(x), x1.max(x), z0.min(z), z1.max(z)),
+                })
+            })
+        };
+        if let Some((min_x, max_x, min_z, max_z)) = extent(&mut roads.iter().copied()) {
+            let along_x = max_x - min_x >= max_z - min_z;
+            let mut across: Vec<i32> = roads
+                .iter()
+                .map(|&(x, z)| if along_x { z } else { x })
+                .collect();
+            across.sort_unstable();
+            let middle = across[across.len() / 2];
+            return Some(Self {
+                along_x,
+                aisle_start: middle - AISLE_WIDTH / 2,
+            });
+        }
+        let (min_x, max_x, min_z, max_z) = extent(&mut area.iter().copied())?;
+        let along_x = max_x - min_x >= max_z - min_z;
+        let edge = if along_x { min_z } else { min_x };
+        Some(Self {
+            along_x,
+            aisle_start: edge + BAY_DEPTH,
+        })
+    }
+
+    fn slot(&self, x: i32, z: i32) -> Slot {
+        let (along, across) = if self.along_x { (x, z) } else { (z, x) };
+        // Bays along the aisle, pairs of rows across the aisles
+        let cell =
+            PlotGrid::new((BAY_WIDTH, ROW_PERIOD), (0, self.aisle_start)).cell(along, across);
+        let within = cell.depth;
+        if within < AISLE_WIDTH {
+            return Slot::Aisle;
+        }
+        if cell.column == 0 {
+            return Slot::Line;
+        }
+        // Behind each aisle a row opens onto it, and behind that one a row opening onto
+        // the next aisle
+        let behind = within - AISLE_WIDTH;
+        let (row_in_pair, depth) = if behind < BAY_DEPTH {
+            (0, behind)
+        } else {
+            (1, 2 * BAY_DEPTH - 1 - behind)
+        };
+        Slot::Bay {
+            row: cell.index.1 * 2 + row_in_pair,
+            bay: cell.index.0,
+            depth,
+        }
+    }
+}
+
+/// Paves an `amenity=parking` area, marks its bays and parks cars in some of them.
+/// Underground and multi-storey car parks are left to their buildings.
+pub fn generate_parking(
+    editor: &mut WorldEditor,
+    element: &ProcessedElement,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+) {
+    if element
+        .tags()
+        .get("parking")
+        .is_some_and(|p| matches!(p.as_str(), "underground" | "multi-storey"))
+    {
+        return;
+    }
+    let area = flood_fill_cache.get_or_compute_element(element, args.timeout.as_ref());
+    let roads: HashSet<(i32, i32)> = area
+        .iter()
+        .copied()
+        .filter(|&(x, z)| editor.check_for_block(x, 0, z, Some(&ROAD_SURFACES)))
+        .collect();
+
+    for &(x, z) in &area {
+        if !roads.contains(&(x, z)) {
+            editor.set_block(ASPHALT, x, 0, z, None, None);
+        }
+    }
+    if args.scale < MIN_BAY_SCALE {
+        return;
+    }
+    let Some(layout) = Layout::new(&area, &roads) else {
+        return;
+    };
+
+    // Only bays lying wholly inside the car park and off its roads are used
+    let mut bays: HashMap<(i32, i32), Vec<(i32, i32, i32)>> = HashMap::new();
+    for &(x, z) in &area {
+        if roads.contains(&(x, z)) {
+            continue;
+        }
+        match layout.slot(x, z) {
+            Slot::Aisle => {}
+            Slot::Line => {
+                editor.set_block(PAINT, x, 0, z, Some(&[ASPHALT]), None);
+            }
+            Slot::Bay { row, bay, depth } => {
+                bays.entry((row, bay)).or_default().push((x, z, depth));
+            }
+        }
+    }
+
+    let bay_cells = ((BAY_WIDTH - 1) * BAY_DEPTH) as usize;
+    for (&(row, bay), cells) in &bays {
+        if cells.len() < bay_cells {
+            continue;
+        }
+        // Lamps stand on the line at the back of every few bays of the second row of a pair
+        if row.rem_euclid(2) == 1 && bay.rem_euclid(LAMP_EVERY_BAYS) == 0 {
+            if let Some(&(x, z, _)) = cells.iter().find(|&&(_, _, d)| d == BAY_DEPTH - 1) {
+                let (lamp_x, lamp_z) = if layout.along_x {
+                    (x - (x.rem_euclid(BAY_WIDTH)), z)
+                } else {
+                    (x, z - (z.rem_euclid(BAY_WIDTH)))
+                };
+                generate_lamp_post(editor, lamp_x, lamp_z);
+            }
+        }
+
+        let mut rng = coord_rng(row, bay, element.id());
+        if !rng.random_bool(PARKED_SHARE) {
+            continue;
+        }
+        let colour = *CAR_COLOURS.choose(&mut rng).unwrap_or(&WHITE_CONCRETE);
+        // Parked nose first, with its back a block in from the aisle
+        for &(x, z, depth) in cells {
+            if !(1..BAY_DEPTH).contains(&depth) {
+                continue;
+            }
+            editor.set_block(colour, x, 1, z, None, None);
+            if depth == 2 || depth == 3 {
+                editor.set_block(GLASS, x, 2, z, None, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle(width: i32, length: i32) -> Vec<(i32, i32)> {
+        (0..width)
+            .flat_map(|x| (0..length).map(move |z| (x, z)))
+            .collect()
+    }
+
+    #[test]
+    fn rows_open_onto_aisles_along_the_longer_side() {
+        let area = rectangle(40, 20);
+        let layout = Layout::new(&area, &HashSet::new()).unwrap();
+        assert_eq!(
+            layout,
+            Layout {
+                along_x: true,
+                aisle_start: BAY_DEPTH
+            }
+        );
+        // A row of bays at the edge opens onto the first aisle
+        assert_eq!(
+            layout.slot(1, 0),
+            Slot::Bay {
+                row: -1,
+                bay: 0,
+                depth: BAY_DEPTH - 1
+            }
+        );
+        assert_eq!(layout.slot(3, 0), Slot::Line);
+        assert_eq!(layout.slot(7, BAY_DEPTH), Slot::Aisle);
+        assert_eq!(
+            layout.slot(7, BAY_DEPTH + AISLE_WIDTH),
+            Slot::Bay {
+                row: 0,
+                bay: 2,
+                depth: 0
+            }
+        );
+        assert_eq!(
+            layout.slot(7, BAY_DEPTH + ROW_PERIOD - 1),
+            Slot::Bay {
+                row: 1,
+                bay: 2,
+                depth: 0
+            }
+        );
+    }
+
+    #[test]
+    fn aisles_follow_the_access_road() {
+        let area = rectangle(40, 20);
+        // A service road running north-south through the middle of the car park
+        let roads: HashSet<(i32, i32)> = (0..20)
+            .flat_map(|z| (18..=22).map(move |x| (x, z)))
+            .collect();
+        let layout = Layout::new(&area, &roads).unwrap();
+        assert!(!layout.along_x);
+        assert!(roads.iter().all(|&(x, z)| layout.slot(x, z) == Slot::Aisle));
+        assert!(matches!(layout.slot(23, 1), Slot::Bay { depth: 0, .. }));
+        assert!(matches!(layout.slot(17, 1), Slot::Bay { depth: 0, .. }));
+    }
+}
@@ -0,0 +1,340 @@
+//! Line markings and equipment of sports pitches (`leisure=pitch`) by their `sport` tag.
+//!
+//! A pitch is mapped as a rectangle, often not aligned with the map. Its markings are laid
+//! out in the frame of the smallest rectangle around the outline, and scaled to its size
+//! rather than to the regulation dimensions, so they always fill the pitch: football
+//! pitches get their halfway line, centre circle, penalty areas and goals, tennis courts
+//! their service boxes and a net, and athletics grounds a tartan track of lanes around a
+//! grass infield.
+
+use crate::block_definitions::*;
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use std::collections::HashMap;
+
+/// Painted lines
+const PAINT: Block = WHITE_CONCRETE;
+/// Athletics track surface
+const TARTAN: Block = RED_TERRACOTTA;
+/// Goal nets and the tennis net
+const NET: Block = COBWEB;
+/// Lanes on an athletics track
+const TRACK_LANES: i32 = 4;
+/// Width of a lane, not counting the line between it and the next
+const LANE_WIDTH: i32 = 2;
+/// Width of an athletics track across all its lanes and lines
+const TRACK_WIDTH: i32 = TRACK_LANES * (LANE_WIDTH + 1);
+
+/// Sports with their own markings
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sport {
+    Football,
+    Tennis,
+    Athletics,
+}
+
+impl Sport {
+    fn from_tags(tags: &HashMap<String, String>) -> Option<Self> {
+        // `sport` may list several sports; the first one is marked
+        let sport = tags.get("sport")?.split(';').next()?.trim();
+        match sport {
+            "soccer" | "football" => Some(Self::Football),
+            "tennis" => Some(Self::Tennis),
+            "athletics" | "running" => Some(Self::Athletics),
+            _ => None,
+        }
+    }
+}
+
+/// Surface of a pitch without a `surface` tag, from its sport. Danish tennis courts are
+/// mostly clay.
+pub fn sport_surface(tags: &HashMap<String, String>) -> Block {
+    match Sport::from_tags(tags) {
+        Some(Sport::Football | Sport::Athletics) => GRASS_BLOCK,
+        Some(Sport::Tennis) => TERRACOTTA,
+        None => GREEN_STAINED_HARDENED_CLAY,
+    }
+}
+
+/// The smallest rectangle around a pitch outline, with its long side as the length
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frame {
+    center: (f64, f64),
+    /// Unit vector along the length of the pitch
+    along: (f64, f64),
+    half_length: f64,
+    half_width: f64,
+}
+
+impl Frame {
+    /// Fits the rectangle to the outline by trying the direction of each of its edges
+    fn fit(outline: &[(i32, i32)]) -> Option<Self> {
+        let mut best: Option<(f64, Frame)> = None;
+        for pair in outline.windows(2) {
+            let (dx, dz) = (
+                (pair[1].0 - pair[0].0) as f64,
+                (pair[1].1 - pair[0].1) as f64,
+            );
+            let length = dx.hypot(dz);
+            if length == 0.0 {
+                continue;
+            }
+            let (ux, uz) = (dx / length, dz / length);
+            let (mut min_a, mut max_a, mut min_c, mut max_c) =
+                (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+            for &(x, z) in outline {
+                let (a, c) = (
+                    x as f64 * ux + z as f64 * uz,
+                    -(x as f64) * uz + z as f64 * ux,
+                );
+                min_a = min_a.min(a);
+                max_a = max_a.max(a);
+                min_c = min_c.min(c);
+                max_c = max_c.max(c);
+            }
+            let area = (max_a - min_a) * (max_c - min_c);
+            if best
+                .as_ref()
+                .is_some_and(|(best_area, _)| *best_area <= area)
+            {
+                continue;
+            }
+            // Back from edge coordinates to the world
+            let (mid_a, mid_c) = ((min_a + max_a) / 2.0, (min_c + max_c) / 2.0);
+            let center = (mid_a * ux - mid_c * uz, mid_a * uz + mid_c * ux);
+            let (extent_a, extent_c) = (max_a - min_a, max_c - min_c);
+            let frame = if extent_a >= extent_c {
+                Frame {
+                    center,
+                    along: (ux, uz),
+                    half_length: extent_a / 2.0,
+                    half_width: extent_c / 2.0,
+                }
+            } else {
+                Frame {
+                    center,
+                    along: (-uz, ux),
+                    half_length: extent_c / 2.0,
+                    half_width: extent_a / 2.0,
+                }
+            };
+            best = Some((area, frame));
+        }
+        best.map(|(_, frame)| frame)
+    }
+
+    /// Position of a block along and across the pitch from its centre
+    fn local(&self, x: i32, z: i32) -> (f64, f64) {
+        let (dx, dz) = (x as f64 - self.center.0, z as f64 - self.center.1);
+        let (ux, uz) = self.along;
+        (dx * ux + dz * uz, -dx * uz + dz * ux)
+    }
+
+    /// Block at `along` and `across` from the centre of the pitch
+    fn world(&self, along: f64, across: f64) -> (i32, i32) {
+        let (ux, uz) = self.along;
+        (
+            (self.center.0 + along * ux - across * uz).round() as i32,
+            (self.center.1 + along * uz + across * ux).round() as i32,
+        )
+    }
+}
+
+/// Whether `value` lies on a line at `at`, one block wide
+fn on_line(value: f64, at: f64) -> bool {
+    (value - at).abs() < 0.5
+}
+
+/// Whether a block at (a, c) in the frame lies on the boundary of the pitch
+fn on_boundary(frame: &Frame, a: f64, c: f64) -> bool {
+    a.abs() > frame.half_length - 1.0 || c.abs() > frame.half_width - 1.0
+}
+
+/// Marking of a football pitch at (a, c), in proportion to the 105 by 68 m regulation
+/// pitch: halfway line, centre circle and penalty areas
+fn football_line(frame: &Frame, a: f64, c: f64) -> bool {
+    let (length, width) = (frame.half_length, frame.half_width);
+    let circle = 0.087 * 2.0 * length;
+    let box_depth = 0.157 * 2.0 * length;
+    let box_half_width = 0.3 * 2.0 * width;
+    let in_box_span = c.abs() <= box_half_width;
+    on_boundary(frame, a, c)
+        || on_line(a, 0.0)
+        || on_line(a.hypot(c), circle)
+        || (in_box_span && on_line(a.abs(), length - box_depth))
+        || (a.abs() >= length - box_depth && on_line(c.abs(), box_half_width))
+}
+
+/// Marking of a tennis court at (a, c), in proportion to the 23.77 by 10.97 m doubles
+/// court: singles sidelines, service lines and the centre service line
+fn tennis_line(frame: &Frame, a: f64, c: f64) -> bool {
+    let (length, width) = (frame.half_length, frame.half_width);
+    let singles = 0.75 * width;
+    let service = 0.54 * length;
+    on_boundary(frame, a, c)
+        || on_line(c.abs(), singles)
+        || (c.abs() <= singles && on_line(a.abs(), service))
+        || (a.abs() <= service && on_line(c, 0.0))
+}
+
+/// Part of an athletics ground at (a, c): a stadium-shaped track of lanes along the edge
+/// with semicircular bends, around the infield
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TrackPart {
+    /// Corner outside the track or the infield inside it
+    Off,
+    Lane,
+    Line,
+}
+
+fn track_part(frame: &Frame, a: f64, c: f64) -> TrackPart {
+    let radius = frame.half_width;
+    let straight = (frame.half_length - radius).max(0.0);
+    // Distance from the spine along the middle of the straights
+    let from_spine = if a.abs() <= straight {
+        c.abs()
+    } else {
+        (a.abs() - straight).hypot(c)
+    };
+    let inward = radius - from_spine;
+    if !(0.0..TRACK_WIDTH as f64).contains(&inward) {
+        return TrackPart::Off;
+    }
+    // The finish line crosses the home straight at its end
+    if c > 0.0 && a.abs() <= straight && on_line(a, straight) {
+        return TrackPart::Line;
+    }
+    if (inward as i32) % (LANE_WIDTH + 1) == LANE_WIDTH {
+        TrackPart::Line
+    } else {
+        TrackPart::Lane
+    }
+}
+
+/// A football goal on the end line at `along`, facing the centre: posts and a crossbar
+/// with the net behind
+fn place_goal(editor: &mut WorldEditor, frame: &Frame, along: f64, half_mouth: f64) {
+    let back = along + along.signum();
+    let mut c = -half_mouth;
+    while c <= half_mouth {
+        let (x, z) = frame.world(along, c);
+        let (nx, nz) = frame.world(back, c);
+        let post = c == -half_mouth || c + 1.0 > half_mouth;
+        for y in 1..=2 {
+            if post {
+                editor.set_block(PAINT, x, y, z, None, None);
+            }
+            editor.set_block(NET, nx, y, nz, None, None);
+        }
+        editor.set_block(PAINT, x, 3, z, None, None);
+        c += 1.0;
+    }
+}
+
+/// A tennis net across the middle of the court, between posts outside the sidelines
+fn place_net(editor: &mut WorldEditor, frame: &Frame) {
+    let mut c = -frame.half_width;
+    while c <= frame.half_width {
+        let (x, z) = frame.world(0.0, c);
+        let edge = c == -frame.half_width || c + 1.0 > frame.half_width;
+        editor.set_block(if edge { OAK_FENCE } else { NET }, x, 1, z, None, None);
+        c += 1.0;
+    }
+}
+
+/// Marks a pitch whose `area` has been laid in `surface`, and sets up its goals or net.
+/// Pitches for other sports keep their plain surface.
+pub fn generate_pitch(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    area: &[(i32, i32)],
+    surface: Block,
+) {
+    let Some(sport) = Sport::from_tags(&way.tags) else {
+        return;
+    };
+    let outline: Vec<(i32, i32)> = way.nodes.iter().map(|n| (n.x, n.z)).collect();
+    let Some(frame) = Frame::fit(&outline) else {
+        return;
+    };
+    // Too small to mark
+    if frame.half_width < 3.0 {
+        return;
+    }
+
+    for &(x, z) in area {
+        let (a, c) = frame.local(x, z);
+        let block = match sport {
+            Sport::Football => football_line(&frame, a, c).then_some(PAINT),
+            Sport::Tennis => tennis_line(&frame, a, c).then_some(PAINT),
+            Sport::Athletics => match track_part(&frame, a, c) {
+                TrackPart::Off => None,
+                TrackPart::Lane => Some(TARTAN),
+                TrackPart::Line => Some(PAINT),
+            },
+        };
+        if let Some(block) = block {
+            editor.set_block(block, x, 0, z, Some(&[surface]), None);
+        }
+    }
+
+    match sport {
+        Sport::Football => {
+            // Goals a tenth of the width of the pitch, at least three blocks wide
+            let half_mouth = (0.054 * 2.0 * frame.half_width).round().max(1.0);
+            let end = frame.half_length.floor() - 1.0;
+            place_goal(editor, &frame, end, half_mouth);
+            place_goal(editor, &frame, -end, half_mouth);
+        }
+        Sport::Tennis => place_net(editor, &frame),
+        Sport::Athletics => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_fits_a_rotated_pitch() {
+        // A 20 by 10 rectangle turned to run north-east
+        let outline = [(0, 0), (12, 16), (4, 22), (-8, 6), (0, 0)];
+        let frame = Frame::fit(&outline).unwrap();
+        assert!((frame.half_length - 10.0).abs() < 1e-9);
+        assert!((frame.half_width - 5.0).abs() < 1e-9);
+        assert_eq!(frame.world(0.0, 0.0), (2, 11));
+        let (a, c) = frame.local(12, 16);
+        assert!((a.abs() - 10.0).abs() < 1e-9 && (c.abs() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn track_lanes_follow_the_bends() {
+        let frame = Frame {
+            center: (0.0, 0.0),
+            along: (1.0, 0.0),
+            half_length: 60.0,
+            half_width: 30.0,
+        };
+        // Along the straight, lanes and lines alternate inward from the edge
+        assert_eq!(track_part(&frame, 0.0, 29.5), TrackPart::Lane);
+        assert_eq!(track_part(&frame, 0.0, 27.5), TrackPart::Line);
+        assert_eq!(track_part(&frame, 0.0, 10.0), TrackPart::Off);
+        // Past the straight the track bends around, leaving the corners off
+        assert_eq!(track_part(&frame, 59.5, 0.0), TrackPart::Lane);
+        assert_eq!(track_part(&frame, 59.0, 29.0), TrackPart::Off);
+        // The finish line crosses the home straight
+        assert_eq!(track_part(&frame, 30.0, 29.5), TrackPart::Line);
+    }
+
+    #[test]
+    fn sport_tag_chooses_the_markings() {
+        let tags = |sport: &str| HashMap::from([("sport".to_string(), sport.to_string())]);
+        assert_eq!(
+            Sport::from_tags(&tags("soccer;tennis")),
+            Some(Sport::Football)
+        );
+        assert_eq!(Sport::from_tags(&tags("athletics")), Some(Sport::Athletics));
+        assert_eq!(Sport::from_tags(&tags("boules")), None);
+        assert_eq!(sport_surface(&tags("tennis")), TERRACOTTA);
+    }
+}
@@ -0,0 +1,185 @@
+//! Pedestrian streets and squares (`highway=pedestrian`), paved like a Danish gågade.
+//!
+//! Pedestrian streets and squares are laid in a pattern chosen by their `surface`: without
+//! one, in granite setts and pale flagstones with bands of brick running through them, so
+//! they read as places to walk rather than roads. Bollards close pedestrian streets to
+//! traffic where they meet a road, and squares get raised planters on a regular grid.
+
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::element_processing::highways::HighwayConnectivityMap;
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use std::collections::HashSet;
+
+/// Blocks between the bands of brick across the paving
+const BAND_SPACING: i32 = 8;
+/// Setts and flagstones between the brick bands, alternating in squares of this size
+const TILE_SIZE: i32 = 2;
+/// Blocks between the planters on a square, every other gap between the brick bands
+const PLANTER_SPACING: i32 = 2 * BAND_SPACING;
+/// Distance from the outer ring of a planter to its centre
+const PLANTER_RADIUS: i32 = 2;
+/// How far in from the end of a pedestrian street its bollards stand
+const BOLLARD_SETBACK: usize = 4;
+/// Bollard closing a pedestrian street to traffic
+const BOLLARD: Block = COBBLESTONE_WALL;
+
+/// Pattern a pedestrian street or square is paved in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Paving {
+    /// Setts and flagstones with brick bands, for streets without a mapped surface
+    Gaagade,
+    /// Setts in staggered rows
+    Setts,
+    /// Flagstones in alternating squares between bands of setts
+    PavingStones,
+    /// Brick with bands of granite
+    Bricks,
+}
+
+impl Paving {
+    /// The pattern for a `surface` value, or `None` for surfaces laid plain (asphalt,
+    /// concrete, gravel, ...)
+    pub fn from_surface(surface: Option<&str>) -> Option<Self> {
+        match surface {
+            None | Some("paved") => Some(Paving::Gaagade),
+            Some("sett" | "cobblestone" | "unhewn_cobblestone") => Some(Paving::Setts),
+            Some("paving_stones") => Some(Paving::PavingStones),
+            Some("bricks") => Some(Paving::Bricks),
+            Some(_) => None,
+        }
+    }
+
+    /// Paving block at (`x`, `z`)
+    pub fn block(self, x: i32, z: i32) -> Block {
+        let band = x.rem_euclid(BAND_SPACING) == 0 || z.rem_euclid(BAND_SPACING) == 0;
+        let tile = (x.div_euclid(TILE_SIZE) + z.div_euclid(TILE_SIZE)).rem_euclid(2) == 0;
+        match self {
+            Paving::Gaagade if band => BRICK,
+            Paving::Gaagade if tile => STONE_BRICKS,
+            Paving::Gaagade => POLISHED_ANDESITE,
+            Paving::Setts if (x + 2 * z).rem_euclid(3) == 0 => CRACKED_STONE_BRICKS,
+            Paving::Setts => STONE_BRICKS,
+            Paving::PavingStones if band => STONE_BRICKS,
+            Paving::PavingStones if tile => POLISHED_ANDESITE,
+            Paving::PavingStones => SMOOTH_STONE,
+            Paving::Bricks if band => POLISHED_GRANITE,
+            Paving::Bricks => BRICK,
+        }
+    }
+}
+
+/// Whether a planter of a square is centred on (`x`, `z`). The planters sit in the middle of
+/// the squares between the brick bands.
+fn is_planter_centre(x: i32, z: i32) -> bool {
+    let offset = BAND_SPACING / 2;
+    x.rem_euclid(PLANTER_SPACING) == offset && z.rem_euclid(PLANTER_SPACING) == offset
+}
+
+/// Raised planters, each a bed of flowers around a shrub within a low stone rim, on the
+/// paved parts of a square where the whole planter fits
+pub fn generate_planters(editor: &mut WorldEditor, area: &[(i32, i32)], paving: Paving) {
+    let cells: HashSet<(i32, i32)> = area.iter().copied().collect();
+    let span = -PLANTER_RADIUS..=PLANTER_RADIUS;
+    for &(cx, cz) in area {
+        if !is_planter_centre(cx, cz) {
+            continue;
+        }
+        let fits = span.clone().all(|dx| {
+            span.clone().all(|dz| {
+                let (x, z) = (cx + dx, cz + dz);
+                cells.contains(&(x, z))
+                    && editor.check_for_block(x, 0, z, Some(&[paving.block(x, z)]))
+            })
+        });
+        if !fits {
+            continue;
+        }
+
+        for dx in span.clone() {
+            for dz in span.clone() {
+                let (x, z) = (cx + dx, cz + dz);
+                if dx.abs() == PLANTER_RADIUS || dz.abs() == PLANTER_RADIUS {
+                    editor.set_block(STONE_BRICK_SLAB, x, 1, z, None, None);
+                    continue;
+                }
+                editor.set_block(GRASS_BLOCK, x, 0, z, Some(&[paving.block(x, z)]), None);
+                let plant = match (dx, dz) {
+                    (0, 0) => OAK_LEAVES,
+                    _ if (dx + dz).rem_euclid(2) == 0 => RED_FLOWER,
+                    _ => YELLOW_FLOWER,
+                };
+                editor.set_block(plant, x, 1, z, None, None);
+            }
+        }
+        editor.set_block(OAK_LEAVES, cx, 2, cz, None, None);
+    }
+}
+
+/// A row of bollards across each end of a pedestrian street that meets a road, `half_width`
+/// blocks either side of its middle and set back a little from the road. Ends that carry on
+/// as footways or other pedestrian streets stay open.
+pub fn generate_street_bollards(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    half_width: i32,
+    highway_connectivity: &HighwayConnectivityMap,
+) {
+    let count = way.nodes.len();
+    if count < 2 {
+        return;
+    }
+    for (end, next) in [(0, 1), (count - 1, count - 2)] {
+        let (from, to) = (&way.nodes[end], &way.nodes[next]);
+        let meets_road = highway_connectivity
+            .get(&(from.x, from.z))
+            .is_some_and(|ends| ends.iter().any(|highway| highway.road));
+        if !meets_road {
+            continue;
+        }
+        let line = bresenham_line(from.x, 0, from.z, to.x, 0, to.z);
+        let Some(&(x, _, z)) = line.get(BOLLARD_SETBACK) else {
+            continue;
+        };
+        let along_x = (to.x - from.x).abs() >= (to.z - from.z).abs();
+        // Every other block, so people and bikes pass between them
+        for offset in (-half_width..=half_width).filter(|offset| offset % 2 == 0) {
+            let (bx, bz) = if along_x {
+                (x, z + offset)
+            } else {
+                (x + offset, z)
+            };
+            editor.set_block(BOLLARD, bx, 1, bz, None, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paving_alternates_between_brick_bands() {
+        let paving = Paving::Gaagade;
+        assert_eq!(paving.block(0, 5), BRICK);
+        assert_eq!(paving.block(-8, 3), BRICK);
+        assert_eq!(paving.block(1, 1), STONE_BRICKS);
+        assert_eq!(paving.block(2, 1), POLISHED_ANDESITE);
+        assert_eq!(paving.block(-1, 1), POLISHED_ANDESITE);
+        // Planters stand clear of the brick bands
+        assert!(is_planter_centre(4, 20));
+        assert!(!is_planter_centre(12, 20));
+        let rim = PLANTER_RADIUS;
+        assert!((4 - rim..=4 + rim).all(|x| paving.block(x, 20 - rim) != BRICK));
+    }
+
+    #[test]
+    fn the_surface_chooses_the_pattern() {
+        assert_eq!(Paving::from_surface(None), Some(Paving::Gaagade));
+        assert_eq!(Paving::from_surface(Some("sett")), Some(Paving::Setts));
+        assert_eq!(Paving::from_surface(Some("asphalt")), None);
+        assert_eq!(Paving::Bricks.block(1, 1), BRICK);
+        assert_eq!(Paving::PavingStones.block(0, 3), STONE_BRICKS);
+    }
+}
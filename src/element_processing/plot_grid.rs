@@ -0,0 +1,79 @@
+//! Fixed grids of plots that cemeteries, allotment sites and car parks are laid out on.
+//!
+//! The grids are anchored to world coordinates rather than to the outline of an area, so
+//! every block finds its place on its own and areas split across tiles line up.
+
+use crate::block_definitions::Block;
+use crate::world_editor::WorldEditor;
+
+/// Plots repeating every `pitch` blocks along x and z, with a plot's corner at `origin`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlotGrid {
+    pub pitch: (i32, i32),
+    pub origin: (i32, i32),
+}
+
+/// Where a block lies in a [`PlotGrid`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlotCell {
+    /// Plot counted from the origin, along x and z
+    pub index: (i32, i32),
+    /// Corner of the plot
+    pub plot: (i32, i32),
+    /// Blocks into the plot along x
+    pub column: i32,
+    /// Blocks into the plot along z
+    pub depth: i32,
+}
+
+impl PlotGrid {
+    pub const fn new(pitch: (i32, i32), origin: (i32, i32)) -> Self {
+        Self { pitch, origin }
+    }
+
+    pub fn cell(&self, x: i32, z: i32) -> PlotCell {
+        let (dx, dz) = (x - self.origin.0, z - self.origin.1);
+        let (column, depth) = (dx.rem_euclid(self.pitch.0), dz.rem_euclid(self.pitch.1));
+        PlotCell {
+            index: (dx.div_euclid(self.pitch.0), dz.div_euclid(self.pitch.1)),
+            plot: (x - column, z - depth),
+            column,
+            depth,
+        }
+    }
+}
+
+/// Lays `ground` at (x, z) and returns whether it is there to build on. Buildings, roads
+/// and other features already there keep their ground.
+pub fn lay_ground(editor: &mut WorldEditor, ground: Block, x: i32, z: i32) -> bool {
+    editor.set_block(ground, x, 0, z, None, None);
+    editor.check_for_block(x, 0, z, Some(&[ground]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_count_from_the_origin_on_both_sides() {
+        let grid = PlotGrid::new((5, 7), (2, 2));
+        assert_eq!(
+            grid.cell(4, 8),
+            PlotCell {
+                index: (0, 0),
+                plot: (2, 2),
+                column: 2,
+                depth: 6
+            }
+        );
+        assert_eq!(
+            grid.cell(1, -6),
+            PlotCell {
+                index: (-1, -2),
+                plot: (-3, -12),
+                column: 4,
+                depth: 6
+            }
+        );
+    }
+}
@@ -3,15 +3,38 @@
 //! This module handles power-related OSM elements including:
 //! - `power=tower` - Large electricity pylons
 //! - `power=pole` - Smaller wooden/concrete poles
-//! - `power=line` - Power lines connecting towers/poles
+//! - `power=line` - Power lines connecting towers/poles, hanging from their insulators
+//! - `power=substation` - Fenced gravel yards with transformers
+//! - `power=generator` + `generator:source=wind` - Wind turbines, scaled by hub height and
+//!   rotor diameter
 
+use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
+use crate::floodfill_cache::FloodFillCache;
 use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
 use crate::world_editor::WorldEditor;
-
-/// Generate power infrastructure from way elements (power lines)
-pub fn generate_power(editor: &mut WorldEditor, element: &ProcessedElement) {
+use std::collections::{HashMap, HashSet};
+
+/// Pylon and pole heights (meters) when their nodes have no `height` tag
+const DEFAULT_TOWER_HEIGHT: f64 = 25.0;
+const DEFAULT_POLE_HEIGHT: f64 = 10.0;
+/// Reach of a pylon's upper cross-arms, whose insulators carry the conductors
+const TOWER_ARM_LENGTH: i32 = 5;
+/// Reach of a pole's cross-arm to either side
+const POLE_ARM_LENGTH: i32 = 2;
+/// Blocks between the transformers of a substation, on a grid
+const TRANSFORMER_SPACING: i32 = 8;
+/// Blocks a transformer keeps from the substation fence
+const TRANSFORMER_CLEARANCE: i32 = 2;
+
+/// Generate power infrastructure from way elements (power lines and substations)
+pub fn generate_power(
+    editor: &mut WorldEditor,
+    element: &ProcessedElement,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+) {
     // Skip if 'layer' or 'level' is negative in the tags
     if let Some(layer) = element.tags().get("layer") {
         if layer.parse::<i32>().unwrap_or(0) < 0 {
@@ -47,18 +70,28 @@ pub fn generate_power(editor: &mut WorldEditor, element: &ProcessedElement) {
         match power_type.as_str() {
             "line" | "minor_line" => {
                 if let ProcessedElement::Way(way) = element {
-                    generate_power_line(editor, way);
+                    generate_power_line(editor, way, args.scale);
                 }
             }
-            "tower" => generate_power_tower(editor, element),
-            "pole" => generate_power_pole(editor, element),
+            "substation" => {
+                if let ProcessedElement::Way(way) = element {
+                    generate_substation(editor, way, args, flood_fill_cache);
+                }
+            }
+            "tower" => generate_power_tower(editor, element, args.scale),
+            "pole" => generate_power_pole(editor, element, args.scale),
             _ => {}
         }
     }
 }
 
+/// Hub height and rotor diameter (meters) of turbines without them in their tags: a typical
+/// 3 MW land turbine
+const DEFAULT_HUB_HEIGHT: f64 = 80.0;
+const DEFAULT_ROTOR_DIAMETER: f64 = 90.0;
+
 /// Generate power infrastructure from node elements
-pub fn generate_power_nodes(editor: &mut WorldEditor, node: &ProcessedNode) {
+pub fn generate_power_nodes(editor: &mut WorldEditor, node: &ProcessedNode, scale: f64) {
     // Skip if 'layer' or 'level' is negative in the tags
     if let Some(layer) = node.tags.get("layer") {
         if layer.parse::<i32>().unwrap_or(0) < 0 {
@@ -87,35 +120,106 @@ pub fn generate_power_nodes(editor: &mut WorldEditor, node: &ProcessedNode) {
 
     if let Some(power_type) = node.tags.get("power") {
         match power_type.as_str() {
-            "tower" => generate_power_tower_from_node(editor, node),
-            "pole" => generate_power_pole_from_node(editor, node),
+            "tower" => generate_power_tower_from_node(editor, node, scale),
+            "pole" => generate_power_pole_from_node(editor, node, scale),
+            "generator"
+                if node
+                    .tags
+                    .get("generator:source")
+                    .is_some_and(|s| s == "wind") =>
+            {
+                generate_wind_turbine(editor, node, scale)
+            }
             _ => {}
         }
     }
 }
 
+/// Meters in a length tag such as "80", "80 m" or "80.5m"
+fn meters(value: &str) -> Option<f64> {
+    let number: String = value
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    number.parse().ok().filter(|m: &f64| *m > 0.0)
+}
+
+/// Generate a wind turbine from its `height:hub` and `rotor:diameter` tags
+///
+/// A white tower up to the nacelle, with three blades in the north-south plane: Danish
+/// turbines mostly face the prevailing westerly wind.
+fn generate_wind_turbine(editor: &mut WorldEditor, node: &ProcessedNode, scale: f64) {
+    let tag = |key: &str| node.tags.get(key).and_then(|v| meters(v));
+    let rotor_diameter = tag("rotor:diameter").unwrap_or(DEFAULT_ROTOR_DIAMETER);
+    let hub_height = tag("height:hub")
+        .or_else(|| tag("height").map(|h| h - rotor_diameter / 2.0))
+        .filter(|h| *h > rotor_diameter / 2.0)
+        .unwrap_or(DEFAULT_HUB_HEIGHT.max(rotor_diameter / 2.0 + 10.0));
+    let hub_y = ((hub_height * scale).round() as i32).max(8);
+    let radius = ((rotor_diameter / 2.0 * scale).round() as i32).clamp(3, hub_y - 3);
+    let (x, z) = (node.x, node.z);
+
+    // Tower, wider at the foot
+    for y in 1..hub_y {
+        editor.set_block(WHITE_CONCRETE, x, y, z, None, None);
+        if y <= hub_y / 4 {
+            for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                editor.set_block(WHITE_CONCRETE, x + dx, y, z + dz, None, None);
+            }
+        }
+    }
+
+    // Nacelle, reaching back east from the rotor
+    for dx in 0..=3 {
+        for dy in 0..=1 {
+            editor.set_block(SMOOTH_QUARTZ, x + dx, hub_y + dy, z, None, None);
+        }
+    }
+
+    // Rotor hub and blades, pointing up and down to either side
+    let rotor_x = x - 1;
+    editor.set_block(IRON_BLOCK, rotor_x, hub_y, z, None, None);
+    for angle in [90.0_f64, 210.0, 330.0] {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let tip_z = z + (cos * radius as f64).round() as i32;
+        let tip_y = hub_y + (sin * radius as f64).round() as i32;
+        for (_, by, bz) in bresenham_line(0, hub_y, z, 0, tip_y, tip_z) {
+            editor.set_block(WHITE_CONCRETE, rotor_x, by, bz, None, None);
+        }
+    }
+}
+
+/// Height in blocks of a pylon from its `height` tag
+fn tower_height(tags: &HashMap<String, String>, scale: f64) -> i32 {
+    let height = tags
+        .get("height")
+        .and_then(|h| meters(h))
+        .unwrap_or(DEFAULT_TOWER_HEIGHT);
+    ((height * scale).round() as i32).clamp(15, 40)
+}
+
+/// Height in blocks of a power pole from its `height` tag
+fn pole_height(tags: &HashMap<String, String>, scale: f64) -> i32 {
+    let height = tags
+        .get("height")
+        .and_then(|h| meters(h))
+        .unwrap_or(DEFAULT_POLE_HEIGHT);
+    ((height * scale).round() as i32).clamp(6, 15)
+}
+
 /// Generate a high-voltage transmission tower (pylon) from a ProcessedElement
-fn generate_power_tower(editor: &mut WorldEditor, element: &ProcessedElement) {
+fn generate_power_tower(editor: &mut WorldEditor, element: &ProcessedElement, scale: f64) {
     let Some(first_node) = element.nodes().next() else {
         return;
     };
-    let height = element
-        .tags()
-        .get("height")
-        .and_then(|h| h.parse::<i32>().ok())
-        .unwrap_or(25)
-        .clamp(15, 40);
+    let height = tower_height(element.tags(), scale);
     generate_power_tower_impl(editor, first_node.x, first_node.z, height);
 }
 
 /// Generate a high-voltage transmission tower (pylon) from a ProcessedNode
-fn generate_power_tower_from_node(editor: &mut WorldEditor, node: &ProcessedNode) {
-    let height = node
-        .tags
-        .get("height")
-        .and_then(|h| h.parse::<i32>().ok())
-        .unwrap_or(25)
-        .clamp(15, 40);
+fn generate_power_tower_from_node(editor: &mut WorldEditor, node: &ProcessedNode, scale: f64) {
+    let height = tower_height(&node.tags, scale);
     generate_power_tower_impl(editor, node.x, node.z, height);
 }
 
@@ -128,7 +232,7 @@ fn generate_power_tower_impl(editor: &mut WorldEditor, x: i32, z: i32, height: i
     let base_width = 3; // Half-width at base (so 7x7 footprint)
     let top_width = 1; // Half-width at top (so 3x3)
     let arm_height = height - 4; // Height where arms extend
-    let arm_length = 5; // How far arms extend horizontally
+    let arm_length = TOWER_ARM_LENGTH; // How far arms extend horizontally
 
     // Build the four corner legs with tapering
     for y in 1..=height {
@@ -232,16 +336,11 @@ fn generate_power_tower_impl(editor: &mut WorldEditor, x: i32, z: i32, height: i
 }
 
 /// Generate a wooden/concrete power pole from a ProcessedElement
-fn generate_power_pole(editor: &mut WorldEditor, element: &ProcessedElement) {
+fn generate_power_pole(editor: &mut WorldEditor, element: &ProcessedElement, scale: f64) {
     let Some(first_node) = element.nodes().next() else {
         return;
     };
-    let height = element
-        .tags()
-        .get("height")
-        .and_then(|h| h.parse::<i32>().ok())
-        .unwrap_or(10)
-        .clamp(6, 15);
+    let height = pole_height(element.tags(), scale);
     let pole_material = element
         .tags()
         .get("material")
@@ -251,13 +350,8 @@ fn generate_power_pole(editor: &mut WorldEditor, element: &ProcessedElement) {
 }
 
 /// Generate a wooden/concrete power pole from a ProcessedNode
-fn generate_power_pole_from_node(editor: &mut WorldEditor, node: &ProcessedNode) {
-    let height = node
-        .tags
-        .get("height")
-        .and_then(|h| h.parse::<i32>().ok())
-        .unwrap_or(10)
-        .clamp(6, 15);
+fn generate_power_pole_from_node(editor: &mut WorldEditor, node: &ProcessedNode, scale: f64) {
+    let height = pole_height(&node.tags, scale);
     let pole_material = node
         .tags
         .get("material")
@@ -288,7 +382,7 @@ fn generate_power_pole_impl(
     }
 
     // Cross-arm at top (perpendicular beam for wires)
-    let arm_length = 2;
+    let arm_length = POLE_ARM_LENGTH;
     for dx in -arm_length..=arm_length {
         editor.set_block(OAK_FENCE, x + dx, height, z, None, None);
     }
@@ -299,11 +393,30 @@ fn generate_power_pole_impl(
     editor.set_block(END_ROD, x, height + 1, z, None, None); // Center insulator
 }
 
+/// Height at which the conductors hang at a node of a power line: from the insulators
+/// under a pylon's upper arms or on top of a pole, or `default` between supports
+fn attachment_height(node: &ProcessedNode, default: i32, scale: f64) -> i32 {
+    match node.tags.get("power").map(String::as_str) {
+        Some("tower") => tower_height(&node.tags, scale) - 5,
+        Some("pole") => pole_height(&node.tags, scale) + 1,
+        _ => default,
+    }
+}
+
+/// Height of a conductor a fraction `t` along a span between attachments at `start` and
+/// `end`, sagging up to `max_sag` at mid-span (a parabola approximating the catenary)
+fn wire_height(start: i32, end: i32, t: f64, max_sag: i32) -> i32 {
+    let level = start as f64 + (end - start) as f64 * t;
+    let sag = 4.0 * max_sag as f64 * t * (1.0 - t);
+    // Keep the wire clear of the ground
+    ((level - sag).round() as i32).max(3)
+}
+
 /// Generate power lines connecting towers/poles
 ///
-/// Creates a catenary-like curve (simplified) between nodes to simulate
-/// the natural sag of power cables.
-fn generate_power_line(editor: &mut WorldEditor, way: &ProcessedWay) {
+/// Each span hangs from the insulators of the supports at its ends and sags between them.
+/// Spans between pylons carry a conductor from each end of the cross-arms.
+fn generate_power_line(editor: &mut WorldEditor, way: &ProcessedWay, scale: f64) {
     if way.nodes.len() < 2 {
         return;
     }
@@ -327,9 +440,8 @@ fn generate_power_line(editor: &mut WorldEditor, way: &ProcessedWay) {
         .unwrap_or(15);
 
     // Process consecutive node pairs
-    for i in 1..way.nodes.len() {
-        let start = &way.nodes[i - 1];
-        let end = &way.nodes[i];
+    for pair in way.nodes.windows(2) {
+        let (start, end) = (&pair[0], &pair[1]);
 
         // Calculate distance between nodes
         let dx = (end.x - start.x) as f64;
@@ -338,48 +450,137 @@ fn generate_power_line(editor: &mut WorldEditor, way: &ProcessedWay) {
 
         // Calculate sag based on span length (longer spans = more sag)
         let max_sag = (distance / 15.0).clamp(1.0, 6.0) as i32;
+        let start_y = attachment_height(start, base_height, scale);
+        let end_y = attachment_height(end, base_height, scale);
 
         // Determine chain orientation based on line direction
         // If the line runs more along X-axis, use CHAIN_X; if more along Z-axis, use CHAIN_Z
-        let chain_block = if dx.abs() >= dz.abs() {
-            CHAIN_X // Line runs primarily along X-axis
+        let along_x = dx.abs() >= dz.abs();
+        let chain_block = if along_x { CHAIN_X } else { CHAIN_Z };
+
+        // Conductors side by side across the line: from the arm ends of pylons, or three
+        // phases close together on high-voltage lines without mapped pylons
+        let on_tower = |node: &ProcessedNode| node.tags.get("power").is_some_and(|p| p == "tower");
+        let offsets: &[i32] = if on_tower(start) || on_tower(end) {
+            &[-TOWER_ARM_LENGTH, TOWER_ARM_LENGTH]
+        } else if base_height >= 18 {
+            &[-1, 0, 1]
         } else {
-            CHAIN_Z // Line runs primarily along Z-axis
+            &[0]
         };
 
         // Generate points along the line using Bresenham
         let line_points = bresenham_line(start.x, 0, start.z, end.x, 0, end.z);
+        // Use len-1 as denominator so last point reaches t=1.0
+        let denom = (line_points.len().saturating_sub(1)).max(1) as f64;
 
         for (idx, (lx, _, lz)) in line_points.iter().enumerate() {
-            // Calculate position along the span (0.0 to 1.0)
-            // Use len-1 as denominator so last point reaches t=1.0
-            let denom = (line_points.len().saturating_sub(1)).max(1) as f64;
-            let t = idx as f64 / denom;
-
-            // Catenary approximation: sag is maximum at center, zero at ends
-            // Using parabola: sag = 4 * max_sag * t * (1 - t)
-            let sag = (4.0 * max_sag as f64 * t * (1.0 - t)) as i32;
-
-            // Ensure wire doesn't go underground (minimum height of 3 blocks above ground)
-            let wire_y = (base_height - sag).max(3);
-
-            // Place the wire block (chain aligned with line direction)
-            editor.set_block(chain_block, *lx, wire_y, *lz, None, None);
-
-            // For high voltage lines, add parallel wires offset to sides
-            if base_height >= 18 {
-                // Three-phase power: 3 parallel lines
-                // Offset perpendicular to the line direction
-                if dx.abs() >= dz.abs() {
-                    // Line runs along X, offset in Z
-                    editor.set_block(chain_block, *lx, wire_y, *lz + 1, None, None);
-                    editor.set_block(chain_block, *lx, wire_y, *lz - 1, None, None);
+            let wire_y = wire_height(start_y, end_y, idx as f64 / denom, max_sag);
+            for &offset in offsets {
+                let (wx, wz) = if along_x {
+                    (*lx, *lz + offset)
                 } else {
-                    // Line runs along Z, offset in X
-                    editor.set_block(chain_block, *lx + 1, wire_y, *lz, None, None);
-                    editor.set_block(chain_block, *lx - 1, wire_y, *lz, None, None);
-                }
+                    (*lx + offset, *lz)
+                };
+                editor.set_block(chain_block, wx, wire_y, wz, None, None);
+            }
+        }
+    }
+}
+
+/// Transformer sites of a substation: grid points with room for a transformer clear of the
+/// fence
+fn transformer_sites(yard: &HashSet<(i32, i32)>) -> Vec<(i32, i32)> {
+    let reach = 1 + TRANSFORMER_CLEARANCE;
+    let mut sites: Vec<(i32, i32)> = yard
+        .iter()
+        .copied()
+        .filter(|&(x, z)| {
+            x.rem_euclid(TRANSFORMER_SPACING) == 0 && z.rem_euclid(TRANSFORMER_SPACING) == 0
+        })
+        .filter(|&(x, z)| {
+            (-reach..=reach).all(|dx| (-reach..=reach).all(|dz| yard.contains(&(x + dx, z + dz))))
+        })
+        .collect();
+    sites.sort_unstable();
+    sites
+}
+
+/// Generate a substation: a gravel yard behind a chain-link fence, with transformers
+fn generate_substation(
+    editor: &mut WorldEditor,
+    way: &ProcessedWay,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+) {
+    let yard: HashSet<(i32, i32)> = flood_fill_cache
+        .get_or_compute(way, args.timeout.as_ref())
+        .into_iter()
+        .collect();
+    for &(x, z) in &yard {
+        editor.set_block(GRAVEL, x, 0, z, None, None);
+    }
+
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            for y in 1..=2 {
+                editor.set_block(IRON_BARS, x, y, z, None, None);
+            }
+        }
+    }
+
+    // Transformer tanks on a concrete plinth with their bushings on top
+    for (x, z) in transformer_sites(&yard) {
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                editor.set_block(
+                    LIGHT_GRAY_CONCRETE,
+                    x + dx,
+                    0,
+                    z + dz,
+                    Some(&[GRAVEL]),
+                    None,
+                );
+                editor.set_block(GRAY_CONCRETE, x + dx, 1, z + dz, None, None);
+                editor.set_block(GRAY_CONCRETE, x + dx, 2, z + dz, None, None);
             }
+            editor.set_block(END_ROD, x + dx, 3, z, None, None);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wires_hang_between_their_attachments() {
+        assert_eq!(wire_height(20, 20, 0.0, 4), 20);
+        assert_eq!(wire_height(20, 20, 0.5, 4), 16);
+        assert_eq!(wire_height(20, 10, 1.0, 4), 10);
+        // A long span between low poles does not touch the ground
+        assert_eq!(wire_height(6, 6, 0.5, 6), 3);
+    }
+
+    #[test]
+    fn conductors_hang_from_the_insulators() {
+        let node = |power: &str| ProcessedNode {
+            id: 1,
+            tags: HashMap::from([("power".to_string(), power.to_string())]),
+            x: 0,
+            z: 0,
+            exact: None,
+        };
+        // Pylons carry the wires 5 below the top, under their arms; poles on top
+        assert_eq!(attachment_height(&node("tower"), 15, 1.0), 20);
+        assert_eq!(attachment_height(&node("pole"), 15, 1.0), 11);
+        assert_eq!(attachment_height(&node("portal"), 15, 1.0), 15);
+    }
+
+    #[test]
+    fn transformers_keep_clear_of_the_fence() {
+        let yard: HashSet<(i32, i32)> =
+            (0..20).flat_map(|x| (0..12).map(move |z| (x, z))).collect();
+        assert_eq!(transformer_sites(&yard), vec![(8, 8), (16, 8)]);
+    }
+}
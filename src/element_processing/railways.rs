@@ -1,10 +1,170 @@
+use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
-use crate::osm_parser::ProcessedWay;
+use crate::coordinate_system::cartesian::XZPoint;
+use crate::floodfill::flood_fill_area;
+use crate::osm_parser::{ProcessedElement, ProcessedWay};
 use crate::world_editor::WorldEditor;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Blocks of track between powered boosters on minecart transit lines
+const BOOSTER_SPACING: usize = 32;
+/// Maximum distance in blocks between a station or platform and its stop on the track
+const STATION_RADIUS: i32 = 8;
+/// Number of unpowered powered rails a cart brakes on at a stop
+const STOP_LENGTH: usize = 3;
+/// Blocks of track per sleeper
+const SLEEPER_SPACING: usize = 2;
+/// Meters between the masts of an overhead contact line
+const CATENARY_SPAN_M: f64 = 60.0;
+/// Fewest blocks between catenary masts, however small the scale
+const MIN_MAST_SPACING: usize = 8;
+/// Height of the contact wire above the ground
+const CONTACT_WIRE_Y: i32 = 5;
+/// Blocks from the middle of the track to its catenary masts
+const MAST_OFFSET: i32 = 2;
+/// Steel of catenary masts, wires and platform shelters
+const STEEL: Block = IRON_BARS;
+/// Length of the shelter in the middle of an uncovered platform
+const SHELTER_LENGTH: usize = 8;
+/// Shortest platform that gets a shelter when it is not covered along its length
+const MIN_SHELTERED_PLATFORM: usize = 16;
+/// Blocks between the posts holding up a platform roof
+const SHELTER_POST_SPACING: usize = 4;
+/// Ground blocks a catenary mast may not stand on: other tracks and roads
+const MAST_BLOCKERS: [Block; 5] = [
+    GRAVEL,
+    BLACK_CONCRETE,
+    GRAY_CONCRETE,
+    LIGHT_GRAY_CONCRETE,
+    WHITE_CONCRETE,
+];
+
+/// Collects the station and platform positions minecart transit lines stop at
+/// (`--minecart-transit`): station/halt/stop nodes and the centre of platform ways.
+pub fn collect_transit_stations(elements: &[ProcessedElement]) -> Vec<(i32, i32)> {
+    elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Node(node)
+                if node
+                    .tags
+                    .get("railway")
+                    .is_some_and(|v| matches!(v.as_str(), "station" | "halt" | "stop")) =>
+            {
+                Some((node.x, node.z))
+            }
+            ProcessedElement::Way(way)
+                if !way.nodes.is_empty()
+                    && way.tags.get("railway").is_some_and(|v| v == "platform") =>
+            {
+                let count = way.nodes.len() as i64;
+                let (sum_x, sum_z) = way.nodes.iter().fold((0i64, 0i64), |(sx, sz), node| {
+                    (sx + node.x as i64, sz + node.z as i64)
+                });
+                Some(((sum_x / count) as i32, (sum_z / count) as i32))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Index of the nearest station within `STATION_RADIUS` that has no stop on this line yet
+fn nearby_station(
+    stations: &[(i32, i32)],
+    x: i32,
+    z: i32,
+    served: &HashSet<usize>,
+) -> Option<usize> {
+    stations
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !served.contains(i))
+        .map(|(i, &(sx, sz))| (i, (sx - x).pow(2) + (sz - z).pow(2)))
+        .filter(|&(_, dist_sq)| dist_sq <= STATION_RADIUS * STATION_RADIUS)
+        .min_by_key(|&(_, dist_sq)| dist_sq)
+        .map(|(i, _)| i)
+}
+
+/// Powered (booster) and unpowered (stop) rail variants of a straight rail
+fn powered_rail_variants(rail: Block) -> Option<(Block, Block)> {
+    match rail {
+        RAIL_NORTH_SOUTH => Some((POWERED_RAIL_NORTH_SOUTH, POWERED_RAIL_NORTH_SOUTH_OFF)),
+        RAIL_EAST_WEST => Some((POWERED_RAIL_EAST_WEST, POWERED_RAIL_EAST_WEST_OFF)),
+        _ => None,
+    }
+}
+
+/// Start of a station stop: a button beside the track powers the stop rails for
+/// departure, and an empty minecart waits on the first rail.
+fn place_station_stop(editor: &mut WorldEditor, x: i32, z: i32, rail: Block) {
+    let (button_x, button_z) = if rail == RAIL_NORTH_SOUTH {
+        (x + 1, z)
+    } else {
+        (x, z + 1)
+    };
+    editor.set_block(SMOOTH_STONE, button_x, 0, button_z, None, None);
+    editor.set_block(STONE_BUTTON, button_x, 1, button_z, None, None);
+    editor.add_entity("minecraft:minecart", x, 1, z, None);
+}
 
-pub fn generate_railways(editor: &mut WorldEditor, element: &ProcessedWay) {
+/// Sleepers of a kind of railway: concrete on main and light rail lines, timber on heritage
+/// and narrow gauge lines. Tracks running in the street, like trams, have none and no
+/// ballast either.
+fn sleeper_block(railway_type: &str) -> Option<Block> {
+    match railway_type {
+        "rail" | "light_rail" => Some(SMOOTH_STONE),
+        "preserved" | "narrow_gauge" | "disused" | "miniature" => Some(SPRUCE_LOG),
+        _ => None,
+    }
+}
+
+/// Whether a line is powered from an overhead contact line, like the main lines and the
+/// S-tog network
+fn has_catenary(tags: &HashMap<String, String>) -> bool {
+    tags.get("electrified").is_some_and(|e| e == "contact_line")
+}
+
+/// Whether a platform belongs to the Copenhagen S-tog network
+fn is_s_tog(tags: &HashMap<String, String>) -> bool {
+    ["network", "operator"]
+        .iter()
+        .any(|key| tags.get(*key).is_some_and(|v| v.contains("S-tog")))
+}
+
+/// Unit step across a segment running from `a` to `b`
+fn across_segment(a: XZPoint, b: XZPoint) -> (i32, i32) {
+    if (b.x - a.x).abs() >= (b.z - a.z).abs() {
+        (0, 1)
+    } else {
+        (1, 0)
+    }
+}
+
+/// A catenary mast beside the track with its arm reaching over the contact wire
+fn place_catenary_mast(editor: &mut WorldEditor, x: i32, z: i32, across: (i32, i32)) {
+    let (mast_x, mast_z) = (x + across.0 * MAST_OFFSET, z + across.1 * MAST_OFFSET);
+    if editor.check_for_block(mast_x, 0, mast_z, Some(&MAST_BLOCKERS)) {
+        return;
+    }
+    for y in 1..=CONTACT_WIRE_Y + 1 {
+        editor.set_block(STEEL, mast_x, y, mast_z, None, None);
+    }
+    for offset in 0..MAST_OFFSET {
+        let (arm_x, arm_z) = (x + across.0 * offset, z + across.1 * offset);
+        editor.set_block(STEEL, arm_x, CONTACT_WIRE_Y + 1, arm_z, None, None);
+    }
+}
+
+pub fn generate_railways(
+    editor: &mut WorldEditor,
+    element: &ProcessedWay,
+    args: &Args,
+    transit_stations: Option<&[(i32, i32)]>,
+) {
     if let Some(railway_type) = element.tags.get("railway") {
+        // Station outlines are areas, their tracks and platforms are mapped on their own
         if [
             "proposed",
             "abandoned",
@@ -12,12 +172,18 @@ pub fn generate_railways(editor: &mut WorldEditor, element: &ProcessedWay) {
             "construction",
             "razed",
             "turntable",
+            "station",
         ]
         .contains(&railway_type.as_str())
         {
             return;
         }
 
+        if railway_type == "platform" {
+            generate_platform(editor, element, args);
+            return;
+        }
+
         if let Some(subway) = element.tags.get("subway") {
             if subway == "yes" {
                 return;
@@ -30,17 +196,36 @@ pub fn generate_railways(editor: &mut WorldEditor, element: &ProcessedWay) {
             }
         }
 
+        // Minecart transit runs on passenger lines only
+        let transit_stations = transit_stations.filter(|_| {
+            matches!(
+                railway_type.as_str(),
+                "rail" | "light_rail" | "narrow_gauge" | "preserved"
+            )
+        });
+
+        let sleeper = sleeper_block(railway_type);
+        let mast_spacing = has_catenary(&element.tags)
+            .then(|| ((CATENARY_SPAN_M * args.scale).round() as usize).max(MIN_MAST_SPACING));
+
+        // Minecart transit state along the whole way
+        let mut since_booster = 0;
+        let mut stop_remaining = 0;
+        let mut served_stations = HashSet::new();
+        // Blocks of track laid so far, for sleepers and masts
+        let mut along = 0;
+
         for i in 1..element.nodes.len() {
             let prev_node = element.nodes[i - 1].xz();
             let cur_node = element.nodes[i].xz();
+            let across = across_segment(prev_node, cur_node);
 
             let points = bresenham_line(prev_node.x, 0, prev_node.z, cur_node.x, 0, cur_node.z);
             let smoothed_points = smooth_diagonal_rails(&points);
 
             for j in 0..smoothed_points.len() {
                 let (bx, _, bz) = smoothed_points[j];
-
-                editor.set_block(GRAVEL, bx, 0, bz, None, None);
+                along += 1;
 
                 let prev = if j > 0 {
                     Some(smoothed_points[j - 1])
@@ -53,22 +238,143 @@ pub fn generate_railways(editor: &mut WorldEditor, element: &ProcessedWay) {
                     None
                 };
 
-                let rail_block = determine_rail_direction(
+                let mut rail_block = determine_rail_direction(
                     (bx, bz),
                     prev.map(|(x, _, z)| (x, z)),
                     next.map(|(x, _, z)| (x, z)),
                 );
+                let mut bed_block = GRAVEL;
+
+                // Powered rails only exist as straight pieces
+                if let (Some(stations), Some((booster, stop))) =
+                    (transit_stations, powered_rail_variants(rail_block))
+                {
+                    since_booster += 1;
+                    if stop_remaining > 0 {
+                        stop_remaining -= 1;
+                        rail_block = stop;
+                    } else if let Some(station) = nearby_station(stations, bx, bz, &served_stations)
+                    {
+                        served_stations.insert(station);
+                        place_station_stop(editor, bx, bz, rail_block);
+                        stop_remaining = STOP_LENGTH - 1;
+                        since_booster = 0;
+                        rail_block = stop;
+                    } else if since_booster >= BOOSTER_SPACING {
+                        // Redstone block underneath keeps the booster powered
+                        since_booster = 0;
+                        bed_block = REDSTONE_BLOCK;
+                        rail_block = booster;
+                    }
+                }
+
+                // Ballast bed a block wider than the track on both sides, crossed by a sleeper
+                // every few blocks
+                if let Some(sleeper) = sleeper {
+                    let on_sleeper = along % SLEEPER_SPACING == 0;
+                    if on_sleeper && bed_block == GRAVEL {
+                        bed_block = sleeper;
+                    }
+                    for side in [-1, 1] {
+                        let (sx, sz) = (bx + across.0 * side, bz + across.1 * side);
+                        if on_sleeper {
+                            editor.set_block(sleeper, sx, 0, sz, Some(&[GRAVEL]), None);
+                        } else {
+                            editor.set_block(GRAVEL, sx, 0, sz, None, None);
+                        }
+                    }
+                }
 
+                editor.set_block(bed_block, bx, 0, bz, None, None);
                 editor.set_block(rail_block, bx, 1, bz, None, None);
 
-                if bx % 4 == 0 {
-                    editor.set_block(OAK_LOG, bx, 0, bz, None, None);
+                if let Some(spacing) = mast_spacing {
+                    editor.set_block(STEEL, bx, CONTACT_WIRE_Y, bz, None, None);
+                    if along % spacing == 0 {
+                        place_catenary_mast(editor, bx, bz, across);
+                    }
                 }
             }
         }
     }
 }
 
+/// Blocks along a linear platform under its roof: all of it when the platform is covered,
+/// otherwise a shelter in its middle if it is long enough for one
+fn shelter_span(length: usize, covered: bool) -> Range<usize> {
+    if covered {
+        0..length
+    } else if length < MIN_SHELTERED_PLATFORM {
+        0..0
+    } else {
+        let start = (length - SHELTER_LENGTH) / 2;
+        start..start + SHELTER_LENGTH
+    }
+}
+
+/// Raised platform along a platform way, with a yellow safety line on both long edges of
+/// linear platforms. Area platforms are paved edge to edge. Linear platforms get a roof on
+/// posts, and S-tog platforms the red S sign at their start.
+fn generate_platform(editor: &mut WorldEditor, element: &ProcessedWay, args: &Args) {
+    let closed = element.nodes.len() > 2
+        && element.nodes.first().map(|n| n.id) == element.nodes.last().map(|n| n.id);
+    if closed {
+        let outline: Vec<(i32, i32)> = element.nodes.iter().map(|n| (n.x, n.z)).collect();
+        for (x, z) in flood_fill_area(&outline, args.timeout.as_ref()) {
+            editor.set_block(SMOOTH_STONE, x, 1, z, None, None);
+        }
+    }
+    let mut points = Vec::new();
+    for pair in element.nodes.windows(2) {
+        let (a, b) = (pair[0].xz(), pair[1].xz());
+        // Across the platform, perpendicular to the way
+        let across = across_segment(a, b);
+        for (x, _, z) in bresenham_line(a.x, 0, a.z, b.x, 0, b.z) {
+            for offset in -1..=1 {
+                let block = if !closed && offset != 0 {
+                    YELLOW_CONCRETE
+                } else {
+                    SMOOTH_STONE
+                };
+                let (px, pz) = (x + across.0 * offset, z + across.1 * offset);
+                editor.set_block(block, px, 1, pz, None, None);
+            }
+            points.push((x, z, across));
+        }
+    }
+    if closed {
+        return;
+    }
+
+    let covered = ["covered", "shelter"]
+        .iter()
+        .any(|key| element.tags.get(*key).is_some_and(|v| v == "yes"));
+    let span = shelter_span(points.len(), covered);
+    for (i, &(x, z, across)) in points.iter().enumerate() {
+        if !span.contains(&i) {
+            continue;
+        }
+        if (i - span.start) % SHELTER_POST_SPACING == 0 {
+            for y in 2..=4 {
+                editor.set_block(STEEL, x, y, z, None, None);
+            }
+        }
+        for offset in -1..=1 {
+            let (rx, rz) = (x + across.0 * offset, z + across.1 * offset);
+            editor.set_block(SMOOTH_STONE_SLAB, rx, 5, rz, None, None);
+        }
+    }
+
+    if is_s_tog(&element.tags) {
+        if let Some(&(x, z, _)) = points.first() {
+            for y in 2..=3 {
+                editor.set_block(STEEL, x, y, z, None, None);
+            }
+            editor.set_block(RED_CONCRETE, x, 4, z, None, None);
+        }
+    }
+}
+
 fn smooth_diagonal_rails(points: &[(i32, i32, i32)]) -> Vec<(i32, i32, i32)> {
     let mut smoothed = Vec::new();
 
@@ -242,3 +548,53 @@ pub fn generate_roller_coaster(editor: &mut WorldEditor, element: &ProcessedWay)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::{node, tags};
+    use std::collections::HashMap;
+
+    #[test]
+    fn track_details_follow_the_kind_of_line() {
+        assert_eq!(sleeper_block("rail"), Some(SMOOTH_STONE));
+        assert_eq!(sleeper_block("preserved"), Some(SPRUCE_LOG));
+        assert_eq!(sleeper_block("tram"), None);
+        assert!(has_catenary(&tags(&[
+            ("railway", "rail"),
+            ("electrified", "contact_line"),
+            ("voltage", "1650"),
+        ])));
+        assert!(!has_catenary(&tags(&[("electrified", "rail")])));
+        assert!(is_s_tog(&tags(&[("operator", "DSB S-tog")])));
+        assert!(!is_s_tog(&tags(&[("operator", "DSB")])));
+    }
+
+    #[test]
+    fn platforms_are_sheltered_in_the_middle_or_along_their_cover() {
+        assert_eq!(shelter_span(40, false), 16..24);
+        assert_eq!(shelter_span(40, true), 0..40);
+        assert!(shelter_span(10, false).is_empty());
+    }
+
+    #[test]
+    fn transit_stops_at_stations_and_platform_centres() {
+        let elements = vec![
+            ProcessedElement::Node(node(1, 5, 5, &[("railway", "station")])),
+            ProcessedElement::Node(node(2, 9, 9, &[("railway", "level_crossing")])),
+            ProcessedElement::Way(ProcessedWay {
+                id: 3,
+                nodes: vec![node(4, 10, 0, &[]), node(5, 20, 0, &[])],
+                tags: HashMap::from([("railway".to_string(), "platform".to_string())]),
+            }),
+        ];
+
+        let stations = collect_transit_stations(&elements);
+        assert_eq!(stations, vec![(5, 5), (15, 0)]);
+
+        let mut served = HashSet::new();
+        assert_eq!(nearby_station(&stations, 14, 3, &served), Some(1));
+        served.insert(1);
+        assert_eq!(nearby_station(&stations, 14, 3, &served), None);
+    }
+}
@@ -0,0 +1,180 @@
+//! Steps (`highway=steps`), and footpaths too steep to walk, as flights of stairs.
+//!
+//! The flight follows the terrain along the way but never rises more than one block per
+//! block walked. Where the ground is steeper it cuts into the hillside rather than stand
+//! out from it, and every rise is a stair block facing uphill, so the way can be walked
+//! without jumping. Steps have handrails on both sides unless tagged `handrail=no`.
+
+use crate::block_definitions::*;
+use crate::bresenham::bresenham_line;
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+
+/// Handrails along both sides of a flight of steps
+const HANDRAIL: Block = IRON_BARS;
+/// Blocks over which a footpath has to keep climbing more than a block per block before it
+/// is terraced, so a single bank or kerb along it is stepped over instead
+const STEEP_RUN: usize = 4;
+
+/// A point along a way: its position and the ground level there
+pub type FlightPoint = (i32, i32, i32);
+
+/// The blocks along a way, in order, with the ground level at each
+pub fn flight_points(editor: &WorldEditor, way: &ProcessedWay) -> Vec<FlightPoint> {
+    let mut points: Vec<FlightPoint> = Vec::new();
+    for pair in way.nodes.windows(2) {
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            // Segments share their end nodes
+            if points.last().is_some_and(|&(px, pz, _)| (px, pz) == (x, z)) {
+                continue;
+            }
+            points.push((x, z, editor.get_ground_level(x, z)));
+        }
+    }
+    points
+}
+
+/// Whether the ground along a footpath climbs more than a block per block over `STEEP_RUN`
+/// blocks anywhere, too steep to walk up without steps
+pub fn is_steep(points: &[FlightPoint]) -> bool {
+    points
+        .windows(STEEP_RUN + 1)
+        .any(|run| (run[STEEP_RUN].2 - run[0].2).abs() > STEEP_RUN as i32)
+}
+
+/// Height of the flight at each point: the highest that rises at most a block per block and
+/// stays at or below the ground everywhere
+fn flight_profile(ground: &[i32]) -> Vec<i32> {
+    let mut profile = ground.to_vec();
+    for i in 1..profile.len() {
+        profile[i] = profile[i].min(profile[i - 1] + 1);
+    }
+    for i in (0..profile.len().saturating_sub(1)).rev() {
+        profile[i] = profile[i].min(profile[i + 1] + 1);
+    }
+    profile
+}
+
+/// Which way a stair climbing by (`dx`, `dz`) faces: towards the top of the flight
+fn uphill_facing(dx: i32, dz: i32) -> StairFacing {
+    if dx.abs() >= dz.abs() {
+        if dx > 0 {
+            StairFacing::East
+        } else {
+            StairFacing::West
+        }
+    } else if dz > 0 {
+        StairFacing::South
+    } else {
+        StairFacing::North
+    }
+}
+
+/// Stair block for steps of the given surface; earthen paths get timber steps
+fn stair_block(surface: Block) -> Block {
+    match surface {
+        DIRT_PATH | DIRT | COARSE_DIRT | GRAVEL | GRASS_BLOCK | SAND => OAK_STAIRS,
+        _ => get_stair_block_for_material(surface),
+    }
+}
+
+/// Builds a flight of steps `half_width` blocks either side of the points, in `surface`
+/// with matching stairs, with handrails along both sides if asked for
+pub fn generate_steps(
+    editor: &mut WorldEditor,
+    points: &[FlightPoint],
+    surface: Block,
+    half_width: i32,
+    handrails: bool,
+) {
+    let ground: Vec<i32> = points.iter().map(|&(_, _, y)| y).collect();
+    let profile = flight_profile(&ground);
+    let stair = stair_block(surface);
+
+    for (i, &(x, z, _)) in points.iter().enumerate() {
+        let y = profile[i];
+        // A point above the one before or after it is a riser, climbed from the lower one
+        let lower_neighbour = [i.checked_sub(1), Some(i + 1)]
+            .into_iter()
+            .flatten()
+            .filter(|&j| j < points.len())
+            .find(|&j| profile[j] < y);
+        let facing = lower_neighbour.map(|j| uphill_facing(x - points[j].0, z - points[j].1));
+
+        // The flight runs across the way's main direction
+        let (prev, next) = (
+            points[i.saturating_sub(1)],
+            points[(i + 1).min(points.len() - 1)],
+        );
+        let along_x = (next.0 - prev.0).abs() >= (next.1 - prev.1).abs();
+        let (across_x, across_z) = if along_x { (0, 1) } else { (1, 0) };
+
+        for offset in -half_width..=half_width {
+            let (bx, bz) = (x + offset * across_x, z + offset * across_z);
+            match facing {
+                Some(facing) => editor.set_block_with_properties_absolute(
+                    create_stair_with_properties(stair, facing, StairShape::Straight),
+                    bx,
+                    y,
+                    bz,
+                    None,
+                    None,
+                ),
+                None => editor.set_block_absolute(surface, bx, y, bz, None, None),
+            }
+            // Cut the flight into the hillside, keeping the cutting open when the ground is
+            // filled in
+            for clear_y in y + 1..=editor.get_ground_level(bx, bz) {
+                editor.set_block_absolute(CAVE_AIR, bx, clear_y, bz, None, None);
+            }
+        }
+
+        if handrails {
+            for side in [-1, 1] {
+                let (rx, rz) = (
+                    x + side * (half_width + 1) * across_x,
+                    z + side * (half_width + 1) * across_z,
+                );
+                editor.set_block_absolute(surface, rx, y, rz, None, None);
+                editor.set_block_absolute(HANDRAIL, rx, y + 1, rz, None, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flights_cut_into_slopes_too_steep_to_walk() {
+        // A bank rising three blocks in one is cut back to a block per block
+        assert_eq!(flight_profile(&[0, 0, 0, 3, 3, 3]), vec![0, 0, 0, 1, 2, 3]);
+        // Gentle slopes and dips are followed as they are
+        assert_eq!(flight_profile(&[5, 4, 3, 3, 4]), vec![5, 4, 3, 3, 4]);
+        // A single spike is levelled off
+        assert_eq!(flight_profile(&[2, 6, 2]), vec![2, 3, 2]);
+        assert!(flight_profile(&[]).is_empty());
+
+        // Only a slope kept up over several blocks is too steep to walk
+        let path = |ground: &[i32]| -> Vec<FlightPoint> {
+            ground
+                .iter()
+                .enumerate()
+                .map(|(x, &y)| (x as i32, 0, y))
+                .collect()
+        };
+        assert!(is_steep(&path(&[0, 2, 4, 6, 8])));
+        assert!(is_steep(&path(&[9, 7, 5, 3, 1, 1])));
+        assert!(!is_steep(&path(&[0, 0, 2, 2, 2, 2])));
+        assert!(!is_steep(&path(&[0, 1, 2, 3, 4, 5])));
+        assert!(!is_steep(&path(&[0, 3])));
+    }
+
+    #[test]
+    fn stairs_face_up_the_flight() {
+        assert_eq!(uphill_facing(1, 0), StairFacing::East);
+        assert_eq!(uphill_facing(-1, 1), StairFacing::West);
+        assert_eq!(uphill_facing(0, -1), StairFacing::North);
+    }
+}
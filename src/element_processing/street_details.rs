@@ -0,0 +1,305 @@
+//! Street details and road furniture.
+//!
+//! Zebra crossings, speed bumps, raised tables, kerb buildouts and pedestrian islands are
+//! mapped as tagged nodes on the road way; they are drawn across the road at the node once
+//! the road is laid, so the road's direction is known. Roundabouts get a grassed central
+//! island behind a kerb, with a tree when there is room for one, and mini roundabouts a
+//! painted disc. Lit roads get lamp posts along their side, and bus stops and public
+//! transport shelters a glass shelter.
+
+use crate::block_definitions::*;
+use crate::element_processing::tree::{Tree, TreeType};
+use crate::osm_parser::{ProcessedNode, ProcessedWay};
+use crate::world_editor::WorldEditor;
+use std::collections::HashMap;
+
+/// Meters between the lamp posts along a lit road
+pub const LAMP_SPACING_M: f64 = 30.0;
+/// Largest central island drawn; bigger rings are rarely round and mapped with landuse
+const MAX_ISLAND_RADIUS: i32 = 30;
+/// Smallest central island that gets a tree instead of a bush
+const MIN_TREE_ISLAND_RADIUS: i32 = 4;
+/// Road surfaces a detail may be painted over
+const ROAD_SURFACES: [Block; 4] = [
+    BLACK_CONCRETE,
+    WHITE_CONCRETE,
+    GRAY_CONCRETE,
+    LIGHT_GRAY_CONCRETE,
+];
+
+/// A detail drawn across the road at a tagged node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoadNodeDetail {
+    /// Marked crossing: white bars along the road
+    Zebra,
+    /// Speed bump or cushion: a low ridge across the road
+    Bump,
+    /// Raised table, often at a crossing: a brick plateau across the road
+    Table,
+    /// Choker or chicane: the kerb bulges into both sides of the road
+    Buildout,
+    /// Pedestrian refuge in the middle of the road
+    Island,
+}
+
+/// Detail of a road node from its `highway=crossing` or `traffic_calming` tags
+pub fn road_node_detail(tags: &HashMap<String, String>) -> Option<RoadNodeDetail> {
+    if let Some(calming) = tags.get("traffic_calming") {
+        return match calming.as_str() {
+            "bump" | "hump" | "cushion" | "yes" => Some(RoadNodeDetail::Bump),
+            "table" => Some(RoadNodeDetail::Table),
+            "choker" | "chicane" => Some(RoadNodeDetail::Buildout),
+            "island" => Some(RoadNodeDetail::Island),
+            _ => None,
+        };
+    }
+    if tags.get("highway").map(|h| h.as_str()) != Some("crossing") {
+        return None;
+    }
+    let marked = tags.get("crossing").is_some_and(|c| {
+        matches!(
+            c.as_str(),
+            "zebra" | "marked" | "uncontrolled" | "traffic_signals"
+        )
+    }) || tags.get("crossing_ref").is_some_and(|r| r == "zebra");
+    marked.then_some(RoadNodeDetail::Zebra)
+}
+
+pub fn is_roundabout(tags: &HashMap<String, String>) -> bool {
+    tags.get("junction")
+        .is_some_and(|j| j == "roundabout" || j == "circular")
+}
+
+/// Draws `detail` across a ground-level road at (x, z). `along_x` tells whether the road
+/// runs mostly along the x axis; `half_width` is the half width of the road surface.
+pub fn generate_road_node_detail(
+    editor: &mut WorldEditor,
+    detail: RoadNodeDetail,
+    x: i32,
+    z: i32,
+    along_x: bool,
+    half_width: i32,
+) {
+    // (along, across) offsets to world coordinates
+    let at = |along: i32, across: i32| {
+        if along_x {
+            (x + along, z + across)
+        } else {
+            (x + across, z + along)
+        }
+    };
+    let across = -half_width..=half_width;
+
+    match detail {
+        RoadNodeDetail::Zebra => {
+            for along in -1..=1 {
+                for c in across.clone() {
+                    let (bx, bz) = at(along, c);
+                    let paint = if c.rem_euclid(2) == 0 {
+                        WHITE_CONCRETE
+                    } else {
+                        BLACK_CONCRETE
+                    };
+                    editor.set_block(paint, bx, 0, bz, Some(&ROAD_SURFACES), None);
+                }
+            }
+        }
+        RoadNodeDetail::Bump => {
+            for c in across {
+                let (bx, bz) = at(0, c);
+                editor.set_block(SMOOTH_STONE_SLAB, bx, 1, bz, None, None);
+            }
+        }
+        RoadNodeDetail::Table => {
+            for along in -2..=2 {
+                for c in across.clone() {
+                    let (bx, bz) = at(along, c);
+                    editor.set_block(BRICK_SLAB, bx, 1, bz, None, None);
+                }
+            }
+        }
+        RoadNodeDetail::Buildout => {
+            // Keep at least one lane open between the bulges
+            if half_width < 2 {
+                return;
+            }
+            for side in [-half_width, half_width] {
+                for along in -1..=1 {
+                    let (bx, bz) = at(along, side);
+                    editor.set_block(SMOOTH_STONE, bx, 0, bz, Some(&ROAD_SURFACES), None);
+                    let kerb = if along == 0 {
+                        OAK_LEAVES
+                    } else {
+                        SMOOTH_STONE_SLAB
+                    };
+                    editor.set_block(kerb, bx, 1, bz, None, None);
+                }
+            }
+        }
+        RoadNodeDetail::Island => {
+            for along in -2..=2 {
+                let (bx, bz) = at(along, 0);
+                editor.set_block(SMOOTH_STONE_SLAB, bx, 1, bz, None, None);
+            }
+        }
+    }
+}
+
+/// Distance from the origin to the segment from `a` to `b`
+fn distance_to_segment(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dz) = (b.0 - a.0, b.1 - a.1);
+    let length_sq = dx * dx + dz * dz;
+    let t = if length_sq > 0.0 {
+        (-(a.0 * dx + a.1 * dz) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a.0 + t * dx).hypot(a.1 + t * dz)
+}
+
+/// Center and radius of the central island of a closed roundabout ring drawn `half_width`
+/// blocks to either side: the largest circle around the centroid of its nodes that clears
+/// the road
+fn island_of(way: &ProcessedWay, half_width: i32) -> Option<(i32, i32, i32)> {
+    let nodes = &way.nodes;
+    if nodes.len() < 4 || nodes.first().map(|n| n.id) != nodes.last().map(|n| n.id) {
+        return None;
+    }
+    let ring = &nodes[..nodes.len() - 1];
+    let count = ring.len() as i64;
+    let cx = (ring.iter().map(|n| n.x as i64).sum::<i64>() / count) as i32;
+    let cz = (ring.iter().map(|n| n.z as i64).sum::<i64>() / count) as i32;
+    let inner = nodes
+        .windows(2)
+        .map(|pair| {
+            let offset = |n: &ProcessedNode| ((n.x - cx) as f64, (n.z - cz) as f64);
+            distance_to_segment(offset(&pair[0]), offset(&pair[1]))
+        })
+        .fold(f64::INFINITY, f64::min);
+    let radius = (inner as i32 - half_width - 1).min(MAX_ISLAND_RADIUS);
+    (radius >= 1).then_some((cx, cz, radius))
+}
+
+/// Grasses the central island of a ground-level roundabout behind a kerb and plants a tree
+/// or bush in its middle.
+pub fn generate_roundabout_island(editor: &mut WorldEditor, way: &ProcessedWay, half_width: i32) {
+    let Some((cx, cz, radius)) = island_of(way, half_width) else {
+        return;
+    };
+    for dx in -radius..=radius {
+        for dz in -radius..=radius {
+            let distance_sq = dx * dx + dz * dz;
+            if distance_sq > radius * radius {
+                continue;
+            }
+            let (x, z) = (cx + dx, cz + dz);
+            editor.set_block(GRASS_BLOCK, x, 0, z, None, Some(&ROAD_SURFACES));
+            if distance_sq > (radius - 1) * (radius - 1) {
+                editor.set_block(SMOOTH_STONE_SLAB, x, 1, z, None, None);
+            }
+        }
+    }
+    if radius >= MIN_TREE_ISLAND_RADIUS {
+        Tree::create_of_type(editor, (cx, 1, cz), TreeType::Oak, None);
+    } else if radius >= 2 {
+        editor.set_block(OAK_LEAVES, cx, 1, cz, None, None);
+    }
+}
+
+/// Paints the white disc of a mini roundabout on the road
+pub fn generate_mini_roundabout(editor: &mut WorldEditor, x: i32, z: i32) {
+    for dx in -1..=1 {
+        for dz in -1..=1 {
+            editor.set_block(
+                WHITE_CONCRETE,
+                x + dx,
+                0,
+                z + dz,
+                Some(&ROAD_SURFACES),
+                None,
+            );
+        }
+    }
+}
+
+/// A street lamp: a pole on a low base with the light on top
+pub fn generate_lamp_post(editor: &mut WorldEditor, x: i32, z: i32) {
+    editor.set_block(COBBLESTONE_WALL, x, 1, z, None, None);
+    for dy in 2..=4 {
+        editor.set_block(OAK_FENCE, x, dy, z, None, None);
+    }
+    editor.set_block(GLOWSTONE, x, 5, z, None, None);
+}
+
+/// Whether a lamp post fits at (x, z) beside a road: not on the surface of another road
+pub fn lamp_post_fits(editor: &WorldEditor, x: i32, z: i32) -> bool {
+    !editor.check_for_block(x, 0, z, Some(&ROAD_SURFACES))
+}
+
+/// Small glass-walled shelter with a bench next to a bus stop pole
+pub fn generate_bus_shelter(editor: &mut WorldEditor, x: i32, z: i32) {
+    for dx in 1..=3 {
+        for dy in 1..=2 {
+            editor.set_block(GLASS_PANE, x + dx, dy, z + 1, None, None);
+        }
+        editor.set_block(SMOOTH_STONE_SLAB, x + dx, 3, z, None, None);
+        editor.set_block(SMOOTH_STONE_SLAB, x + dx, 3, z + 1, None, None);
+        editor.set_block(OAK_SLAB, x + dx, 1, z, None, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn node_tags_choose_the_detail() {
+        assert_eq!(
+            road_node_detail(&tags(&[("highway", "crossing"), ("crossing", "zebra")])),
+            Some(RoadNodeDetail::Zebra)
+        );
+        assert_eq!(
+            road_node_detail(&tags(&[("highway", "crossing"), ("crossing", "unmarked")])),
+            None
+        );
+        assert_eq!(
+            road_node_detail(&tags(&[("traffic_calming", "hump")])),
+            Some(RoadNodeDetail::Bump)
+        );
+        assert_eq!(
+            road_node_detail(&tags(&[("traffic_calming", "choker")])),
+            Some(RoadNodeDetail::Buildout)
+        );
+        assert_eq!(road_node_detail(&tags(&[("highway", "stop")])), None);
+        assert!(is_roundabout(&tags(&[("junction", "roundabout")])));
+    }
+
+    #[test]
+    fn islands_fill_closed_rings_inside_the_road() {
+        let corners = [(10, 0), (0, 10), (-10, 0), (0, -10), (10, 0)];
+        let node = |i: usize, (x, z): (i32, i32)| ProcessedNode {
+            id: if i == corners.len() - 1 { 0 } else { i as u64 },
+            tags: HashMap::new(),
+            x,
+            z,
+            exact: None,
+        };
+        let mut way = ProcessedWay {
+            id: 1,
+            tags: tags(&[("highway", "primary"), ("junction", "roundabout")]),
+            nodes: corners
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| node(i, c))
+                .collect(),
+        };
+        // The edges of the diamond pass 7 blocks from its center
+        assert_eq!(island_of(&way, 2), Some((0, 0, 4)));
+        assert_eq!(island_of(&way, 6), None);
+
+        // Open ways have no island
+        way.nodes.pop();
+        assert_eq!(island_of(&way, 2), None);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::block_definitions::*;
+use crate::enrichment::bbr;
 use crate::world_editor::WorldEditor;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// Interior layout for building ground floors (1st layer above floor)
 #[rustfmt::skip]
@@ -270,10 +271,147 @@ pub fn get_interior_block(c: char, is_layer2: bool, wall_block: Block) -> Option
         'd' => Some(CHISELLED_BOOKSHELF_WEST),  // Chiseled Bookshelf West
         'M' => Some(DAMAGED_ANVIL),             // Damaged Anvil
         'Q' => Some(SCAFFOLDING),               // Scaffolding
+        'K' => Some(OAK_SLAB_TOP),              // Desk or counter top
+        'X' => Some(BARREL),                    // Shop shelf
+        'V' => Some(BLACK_CONCRETE),            // Blackboard
+        'Y' => Some(QUARTZ_BLOCK),              // Altar
+        'I' => Some(LECTERN),                   // Lectern
+        'R' => Some(RED_CARPET),                // Red Carpet
         _ => None,                              // Default case for unknown characters
     }
 }
 
+/// Which way a seat faces, for the seat characters of the layouts: `n`, `e`, `s` and `w`
+/// are oak stairs with their backs to the north, east, south and west
+fn seat_facing(c: char) -> Option<StairFacing> {
+    match c {
+        'n' => Some(StairFacing::North),
+        'e' => Some(StairFacing::East),
+        's' => Some(StairFacing::South),
+        'w' => Some(StairFacing::West),
+        _ => None,
+    }
+}
+
+/// Buildings furnished for what they are used for rather than with the generic rooms
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InteriorKind {
+    Shop,
+    Office,
+    Church,
+    School,
+}
+
+impl InteriorKind {
+    /// Picks the furnishing BBR's usage code maps to (`bbr:furnishing`, added from BBR)
+    /// when the building has one, and otherwise from its OSM `building`, `shop`, `office`
+    /// and `amenity` tags
+    fn from_tags(tags: &HashMap<String, String>) -> Option<Self> {
+        if let Some(furnishing) = tags.get(bbr::FURNISHING_TAG) {
+            return match furnishing.as_str() {
+                "shop" => Some(InteriorKind::Shop),
+                "office" => Some(InteriorKind::Office),
+                "church" => Some(InteriorKind::Church),
+                "school" => Some(InteriorKind::School),
+                _ => None,
+            };
+        }
+
+        let building = tags.get("building").map(String::as_str).unwrap_or("yes");
+        let amenity = tags.get("amenity").map(String::as_str);
+        match building {
+            "retail" | "supermarket" | "kiosk" | "shop" => Some(InteriorKind::Shop),
+            "office" => Some(InteriorKind::Office),
+            "church" | "cathedral" | "chapel" => Some(InteriorKind::Church),
+            "school" | "college" | "university" => Some(InteriorKind::School),
+            _ if amenity == Some("place_of_worship") => Some(InteriorKind::Church),
+            _ if matches!(amenity, Some("school" | "college" | "university")) => {
+                Some(InteriorKind::School)
+            }
+            _ if tags.contains_key("shop") => Some(InteriorKind::Shop),
+            _ if tags.contains_key("office") => Some(InteriorKind::Office),
+            _ => None,
+        }
+    }
+}
+
+/// Length of a classroom, from the wall with its blackboard to the next classroom
+const CLASSROOM_DEPTH: i32 = 9;
+/// Distance between the doors of neighbouring classrooms
+const CLASSROOM_DOOR_SPACING: i32 = 12;
+
+/// Layout characters (first and second layer) at (`lx`, `lz`) from the corner of the
+/// furnished area of a floor, `width` by `depth` blocks, or `None` where a building of
+/// this kind has the generic rooms on this floor
+fn furnished_cell(
+    kind: InteriorKind,
+    floor_index: usize,
+    lx: i32,
+    lz: i32,
+    width: i32,
+    depth: i32,
+) -> Option<(char, char)> {
+    match kind {
+        // Flats above the shop
+        InteriorKind::Shop if floor_index > 0 => None,
+        InteriorKind::Shop => Some(match lz {
+            // Counter inside the front
+            0 if lx < 4 => ('K', ' '),
+            0 | 1 => (' ', ' '),
+            // Shelves in rows, with an aisle across every ninth row and along the back
+            _ if lz == depth - 1 || (lz - 2) % 9 == 8 => (' ', ' '),
+            _ if lx % 3 == 2 => ('X', 'B'),
+            _ => (' ', ' '),
+        }),
+        // Desks facing each other in pairs, with a walkway around each pair
+        InteriorKind::Office => Some(match (lx % 5, lz % 4) {
+            (1..=3, 1) => ('K', ' '),
+            (1 | 3, 0) => ('n', ' '),
+            (1 | 3, 2) => ('s', ' '),
+            _ => (' ', ' '),
+        }),
+        // Only the nave is furnished
+        InteriorKind::Church if floor_index > 0 => Some((' ', ' ')),
+        InteriorKind::Church => {
+            // The altar is at the end of the long axis, which is the east end of a church
+            // built east to west
+            let along_x = width >= depth;
+            let (along, across, length, span) = if along_x {
+                (lx, lz, width, depth)
+            } else {
+                (lz, lx, depth, width)
+            };
+            let aisle = across == span / 2;
+            let pew = if along_x { 'w' } else { 'n' };
+            Some(match along {
+                _ if along == length - 2 && (across - span / 2).abs() <= 1 => ('Y', ' '),
+                // Altar rail with an opening at the aisle
+                _ if along == length - 4 && !aisle => ('U', ' '),
+                _ if along == length - 5 && across == 0 => ('I', ' '),
+                _ if along > length - 5 => (' ', ' '),
+                _ if aisle => ('R', ' '),
+                _ if along % 2 == 1 && across > 0 && across < span - 1 => (pew, ' '),
+                _ => (' ', ' '),
+            })
+        }
+        // Classrooms, each with a blackboard on its front wall and rows of pupils' desks
+        // facing it
+        InteriorKind::School => {
+            let (column, row) = (lx % CLASSROOM_DOOR_SPACING, lz % CLASSROOM_DEPTH);
+            Some(match row {
+                0 if column == 1 => ('D', 'D'),
+                0 if (4..=8).contains(&column) => ('W', 'V'),
+                0 => ('W', 'W'),
+                1 if column == 6 => ('K', ' '),
+                1 | 2 => (' ', ' '),
+                _ if column % 3 == 0 => (' ', ' '),
+                _ if row % 2 == 1 => ('K', ' '),
+                _ => ('s', ' '),
+            })
+        }
+    }
+}
+
 /// Generates interior layouts inside buildings at each floor level
 #[allow(clippy::too_many_arguments)]
 pub fn generate_building_interior(
@@ -310,6 +448,15 @@ pub fn generate_building_interior(
     let interior_max_x = max_x - buffer;
     let interior_max_z = max_z - buffer;
 
+    // With --interiors, shops, offices, churches and schools are furnished for their use
+    let kind = if is_abandoned_building || !args.interiors {
+        None
+    } else {
+        InteriorKind::from_tags(&element.tags)
+    };
+    let furnished_width = interior_max_x - interior_min_x + 1;
+    let furnished_depth = interior_max_z - interior_min_z + 1;
+
     // Generate interiors for each floor
     for (floor_index, &floor_y) in floor_levels.iter().enumerate() {
         // Store wall and door positions for this floor to extend them to the ceiling
@@ -375,11 +522,32 @@ pub fn generate_building_interior(
                     % pattern_height;
 
                 // Access the pattern arrays safely
-                let cell1 = layer1[pattern_z as usize][pattern_x as usize];
-                let cell2 = layer2[pattern_z as usize][pattern_x as usize];
+                let furnished = kind.and_then(|kind| {
+                    furnished_cell(
+                        kind,
+                        floor_index,
+                        x - interior_min_x,
+                        z - interior_min_z,
+                        furnished_width,
+                        furnished_depth,
+                    )
+                });
+                let (cell1, cell2) = furnished.unwrap_or((
+                    layer1[pattern_z as usize][pattern_x as usize],
+                    layer2[pattern_z as usize][pattern_x as usize],
+                ));
 
                 // Place first layer blocks
-                if let Some(block) = get_interior_block(cell1, false, wall_block) {
+                if let Some(facing) = seat_facing(cell1) {
+                    editor.set_block_with_properties_absolute(
+                        create_stair_with_properties(OAK_STAIRS, facing, StairShape::Straight),
+                        x,
+                        floor_y + y_offset + abs_terrain_offset,
+                        z,
+                        None,
+                        None,
+                    );
+                } else if let Some(block) = get_interior_block(cell1, false, wall_block) {
                     editor.set_block_absolute(
                         block,
                         x,
@@ -428,3 +596,45 @@ pub fn generate_building_interior(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn bbr_furnishing_decides_over_osm_tags() {
+        let shop = tags(&[("building", "retail"), ("bbr:furnishing", "office")]);
+        assert_eq!(InteriorKind::from_tags(&shop), Some(InteriorKind::Office));
+        let school = tags(&[("building", "yes"), ("bbr:furnishing", "school")]);
+        assert_eq!(InteriorKind::from_tags(&school), Some(InteriorKind::School));
+        let house = tags(&[("building", "office"), ("bbr:furnishing", "rooms")]);
+        assert_eq!(InteriorKind::from_tags(&house), None);
+
+        let church = tags(&[("building", "yes"), ("amenity", "place_of_worship")]);
+        assert_eq!(InteriorKind::from_tags(&church), Some(InteriorKind::Church));
+        let bakery = tags(&[("building", "yes"), ("shop", "bakery")]);
+        assert_eq!(InteriorKind::from_tags(&bakery), Some(InteriorKind::Shop));
+        assert_eq!(
+            InteriorKind::from_tags(&tags(&[("building", "house")])),
+            None
+        );
+    }
+
+    #[test]
+    fn church_pews_face_the_altar() {
+        let (width, depth) = (20, 9);
+        let cell = |lx, lz| furnished_cell(InteriorKind::Church, 0, lx, lz, width, depth);
+        assert_eq!(cell(18, 4), Some(('Y', ' ')));
+        assert_eq!(cell(16, 2), Some(('U', ' ')));
+        assert_eq!(cell(16, 4), Some((' ', ' ')));
+        assert_eq!(cell(3, 2), Some(('w', ' ')));
+        assert_eq!(cell(3, 4), Some(('R', ' ')));
+        assert_eq!(cell(3, 0), Some((' ', ' ')));
+        // Upper floors of a shop keep the generic rooms
+        assert_eq!(
+            furnished_cell(InteriorKind::Shop, 1, 2, 2, width, depth),
+            None
+        );
+    }
+}
@@ -3,6 +3,7 @@ use crate::deterministic_rng::coord_rng;
 use crate::floodfill_cache::BuildingFootprintBitmap;
 use crate::world_editor::WorldEditor;
 use rand::Rng;
+use std::collections::HashMap;
 
 type Coord = (i32, i32, i32);
 
@@ -110,6 +111,15 @@ const ACACIA_LEAVES_FILL: [(Coord, Coord); 5] = [
     ((0, 8, 0), (0, 9, 0)),
 ];
 
+/// Beech: tall, smooth grey trunk under a broad, dense crown, leaves from y=5 to y=11
+const BEECH_LEAVES_FILL: [(Coord, Coord); 5] = [
+    ((-1, 5, 0), (-1, 11, 0)),
+    ((1, 5, 0), (1, 11, 0)),
+    ((0, 5, -1), (0, 11, -1)),
+    ((0, 5, 1), (0, 11, 1)),
+    ((0, 11, 0), (0, 12, 0)),
+];
+
 //////////////////////////////////////////////////
 
 /// Helper function to set blocks in various patterns.
@@ -119,7 +129,7 @@ fn round(editor: &mut WorldEditor, material: Block, (x, y, z): Coord, block_patt
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TreeType {
     Oak,
     Spruce,
@@ -127,6 +137,124 @@ pub enum TreeType {
     DarkOak,
     Jungle,
     Acacia,
+    Beech,
+}
+
+impl TreeType {
+    /// Trees a single tree or the trees of a tree row may be drawn as: its genus from the
+    /// `genus` tag, the first word of `species` or `genus:wikidata`, or else its `leaf_type`.
+    /// Limes and horse chestnuts, the trees of Danish allés, get the dense dark oak crown.
+    pub fn candidates(tags: &HashMap<String, String>) -> Vec<TreeType> {
+        use TreeType::*;
+        let genus = tags
+            .get("genus")
+            .map(String::as_str)
+            .or_else(|| {
+                tags.get("species")
+                    .and_then(|s| s.split_whitespace().next())
+            })
+            .or_else(|| match tags.get("genus:wikidata").map(String::as_str) {
+                Some("Q12004") => Some("Betula"),
+                Some("Q26782") => Some("Quercus"),
+                Some("Q25243") => Some("Picea"),
+                _ => None,
+            });
+        match genus {
+            Some("Betula" | "Populus" | "Salix" | "Alnus" | "Sorbus") => return vec![Birch],
+            Some("Quercus" | "Acer" | "Fraxinus" | "Ulmus" | "Platanus" | "Castanea") => {
+                return vec![Oak]
+            }
+            Some("Fagus" | "Carpinus") => return vec![Beech],
+            Some("Tilia" | "Aesculus") => return vec![DarkOak],
+            Some("Picea" | "Pinus" | "Abies" | "Larix" | "Pseudotsuga" | "Taxus" | "Thuja") => {
+                return vec![Spruce]
+            }
+            _ => {}
+        }
+        match tags.get("leaf_type").map(String::as_str) {
+            Some("broadleaved") => vec![Oak, Beech, Birch],
+            Some("needleleaved") => vec![Spruce],
+            _ => vec![Oak, Spruce, Birch],
+        }
+    }
+}
+
+/// Trees of a forest polygon and how they are planted
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForestMix {
+    pub trees: Vec<TreeType>,
+    /// Plantations are planted in a grid with this spacing instead of scattered
+    pub plantation_spacing: Option<i32>,
+}
+
+/// Spacing of the rows in conifer plantations
+const PLANTATION_SPACING: i32 = 4;
+
+impl ForestMix {
+    /// Trees for a forest's tags. With `forest_types` (`--forest-types`) they follow the main
+    /// species (`genus`, as set from the Miljøstyrelsen forest types) or else the OSM
+    /// `leaf_type`: Danish deciduous woods are mostly beech with oak, and conifer woods in
+    /// Jutland are spruce plantations. Without it, only `leaf_type` picks the trees.
+    pub fn from_tags(tags: &HashMap<String, String>, jutland: bool, forest_types: bool) -> Self {
+        use TreeType::*;
+        if !forest_types {
+            let trees = match tags.get("leaf_type").map(String::as_str) {
+                Some("broadleaved") => vec![Oak, Birch],
+                Some("needleleaved") => vec![Spruce],
+                _ => vec![Oak, Spruce, Birch],
+            };
+            return Self {
+                trees,
+                plantation_spacing: None,
+            };
+        }
+        let conifers = |trees: Vec<TreeType>| Self {
+            trees,
+            plantation_spacing: jutland.then_some(PLANTATION_SPACING),
+        };
+        let scattered = |trees: Vec<TreeType>| Self {
+            trees,
+            plantation_spacing: None,
+        };
+        match tags.get("genus").map(String::as_str) {
+            Some("Fagus") => return scattered(vec![Beech, Beech, Beech, Oak]),
+            Some("Quercus") => return scattered(vec![Oak, Oak, Oak, Birch]),
+            Some("Betula") => return scattered(vec![Birch]),
+            Some("Picea" | "Pinus" | "Abies" | "Larix") => return conifers(vec![Spruce]),
+            _ => {}
+        }
+        match tags.get("leaf_type").map(String::as_str) {
+            Some("broadleaved") => scattered(vec![Beech, Beech, Oak, Birch]),
+            Some("needleleaved") => conifers(vec![Spruce]),
+            Some(_) => scattered(vec![Beech, Oak, Spruce, Birch]),
+            None => scattered(vec![Oak, Spruce, Birch]),
+        }
+    }
+
+    /// Whether a plantation row has a tree at (`x`, `z`)
+    pub fn plants_at(&self, x: i32, z: i32) -> bool {
+        self.plantation_spacing
+            .is_some_and(|spacing| x.rem_euclid(spacing) == 0 && z.rem_euclid(spacing) == 0)
+    }
+
+    /// Plants one grass cell of a plantation: trees in rows on needle-strewn ground with the
+    /// odd fern between them.
+    pub fn plant_plantation_cell(
+        &self,
+        editor: &mut WorldEditor,
+        (x, z): (i32, i32),
+        rng: &mut impl Rng,
+        building_footprints: &BuildingFootprintBitmap,
+    ) {
+        if self.plants_at(x, z) {
+            let tree_type = self.trees.first().copied().unwrap_or(TreeType::Spruce);
+            Tree::create_of_type(editor, (x, 1, z), tree_type, Some(building_footprints));
+        } else if rng.random_range(0..3) == 0 {
+            editor.set_block(PODZOL, x, 0, z, Some(&[GRASS_BLOCK]), None);
+        } else if rng.random_range(0..12) == 0 {
+            editor.set_block(FERN, x, 1, z, None, None);
+        }
+    }
 }
 
 // TODO what should be moved in, and what should be referenced?
@@ -299,6 +427,19 @@ impl Tree<'_> {
                     (6..=7).rev().collect(),
                 ],
             },
+
+            TreeType::Beech => Self {
+                // Grey trunk, crown reaching wide from halfway up
+                log_block: ACACIA_LOG,
+                log_height: 10,
+                leaves_block: OAK_LEAVES,
+                leaves_fill: &BEECH_LEAVES_FILL,
+                round_ranges: [
+                    (5..=10).rev().collect(),
+                    (6..=10).rev().collect(),
+                    (7..=9).rev().collect(),
+                ],
+            },
         } // match
     } // fn get_tree
 
@@ -449,3 +590,63 @@ impl Tree<'_> {
         ]
     }
 } // impl Tree
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn forest_mix_follows_species_then_leaf_type() {
+        let beech = ForestMix::from_tags(&tags(&[("genus", "Fagus")]), true, true);
+        assert_eq!(beech.trees[0], TreeType::Beech);
+        assert_eq!(beech.plantation_spacing, None);
+
+        let spruce = ForestMix::from_tags(&tags(&[("leaf_type", "needleleaved")]), true, true);
+        assert_eq!(spruce.plantation_spacing, Some(PLANTATION_SPACING));
+        assert!(spruce.plants_at(8, -4));
+        assert!(!spruce.plants_at(8, -3));
+        let zealand = ForestMix::from_tags(&tags(&[("leaf_type", "needleleaved")]), false, true);
+        assert_eq!(zealand.plantation_spacing, None);
+
+        assert_eq!(ForestMix::from_tags(&tags(&[]), true, true).trees.len(), 3);
+    }
+
+    #[test]
+    fn forest_mix_without_forest_types_follows_leaf_type() {
+        use TreeType::*;
+        let broadleaved = tags(&[("genus", "Fagus"), ("leaf_type", "broadleaved")]);
+        let mix = ForestMix::from_tags(&broadleaved, true, false);
+        assert_eq!(mix.trees, vec![Oak, Birch]);
+
+        let needleleaved =
+            ForestMix::from_tags(&tags(&[("leaf_type", "needleleaved")]), true, false);
+        assert_eq!(needleleaved.trees, vec![Spruce]);
+        assert_eq!(needleleaved.plantation_spacing, None);
+    }
+
+    #[test]
+    fn single_trees_follow_genus_species_then_leaf_type() {
+        use TreeType::*;
+        assert_eq!(
+            TreeType::candidates(&tags(&[("species", "Tilia cordata")])),
+            vec![DarkOak]
+        );
+        assert_eq!(
+            TreeType::candidates(&tags(&[("genus", "Fagus"), ("species", "Quercus robur")])),
+            vec![Beech]
+        );
+        assert_eq!(
+            TreeType::candidates(&tags(&[("genus:wikidata", "Q12004")])),
+            vec![Birch]
+        );
+        assert_eq!(
+            TreeType::candidates(&tags(&[
+                ("species", "Sequoia"),
+                ("leaf_type", "needleleaved")
+            ])),
+            vec![Spruce]
+        );
+        assert_eq!(TreeType::candidates(&tags(&[])), vec![Oak, Spruce, Birch]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Tunnels for ways tagged `tunnel=yes`.
+//!
+//! The road of a tunnel dips from its portals to a level under the ground and runs through a
+//! bore lined with stone bricks. Where the ground above is too low to cover the bore, the
+//! road runs in an open cutting instead. The bore is cleared with cave air, so the ground
+//! pass, which only fills empty blocks, does not fill it back in.
+
+use crate::block_definitions::*;
+use crate::world_editor::WorldEditor;
+use std::collections::HashMap;
+
+/// Clear height of the bore above the road
+pub const BORE_HEIGHT: i32 = 4;
+/// Depth of the road under its portals per layer below ground
+const LAYER_DEPTH: i32 = 6;
+/// Tunnel ramps descend one block per this many blocks
+const RAMP_RUN: usize = 2;
+/// Walls and ceiling of the bore, and the headwalls of its portals
+const LINING: Block = STONE_BRICKS;
+
+/// Where along a tunnel a point of its road lies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TunnelSection {
+    /// Too little ground above for a bore: the road runs in an open trench
+    Cutting,
+    /// First or last covered point, where the bore meets the cutting
+    Portal,
+    /// Under the ground
+    Bore,
+}
+
+pub fn is_tunnel(tags: &HashMap<String, String>) -> bool {
+    tags.get("tunnel").is_some_and(|v| v == "yes")
+}
+
+/// Road heights along a tunnel from the ground heights along the whole way, `None` where it
+/// runs outside the tile. The road dips `LAYER_DEPTH` blocks per layer below ground, ramping
+/// down from the portals at both ends of the way, and runs straight between the ends of
+/// each stretch inside the tile. Where a stretch ends at the tile edge rather than at a
+/// portal, the road lies as deep under the ground there as the ramp has brought it, so
+/// neighbouring tiles meet at the same height. It never rises above the ground.
+pub fn tunnel_road_profile(ground: &[Option<i32>], layers_down: i32) -> Vec<Option<i32>> {
+    let last = ground.len().saturating_sub(1);
+    let depth = LAYER_DEPTH * layers_down.max(1);
+    let dip = |i: usize| depth.min((i.min(last - i) / RAMP_RUN) as i32);
+    let mut road = vec![None; ground.len()];
+    let mut start = 0;
+    while start < ground.len() {
+        let Some(start_y) = ground[start] else {
+            start += 1;
+            continue;
+        };
+        let end = (start..ground.len())
+            .take_while(|&i| ground[i].is_some())
+            .last()
+            .unwrap_or(start);
+        let end_y = ground[end].unwrap_or(start_y);
+        for i in start..=end {
+            let straight = if end == start {
+                start_y
+            } else {
+                start_y + (end_y - start_y) * (i - start) as i32 / (end - start) as i32
+            };
+            let ground_y = ground[i].unwrap_or(straight);
+            road[i] = Some((straight - dip(i)).min(ground_y));
+        }
+        start = end + 1;
+    }
+    road
+}
+
+/// Section of the tunnel at point `i` of its road, which lies in the tile. Points outside
+/// the tile count as covered, so the tile edge is no portal.
+pub fn tunnel_section(ground: &[Option<i32>], road: &[Option<i32>], i: usize) -> TunnelSection {
+    let covered = |i: usize| match (ground[i], road[i]) {
+        (Some(ground_y), Some(road_y)) => ground_y > road_y + BORE_HEIGHT,
+        _ => true,
+    };
+    if !covered(i) {
+        TunnelSection::Cutting
+    } else if i == 0 || i + 1 == road.len() || !covered(i - 1) || !covered(i + 1) {
+        TunnelSection::Portal
+    } else {
+        TunnelSection::Bore
+    }
+}
+
+/// Clears the column above the road at (x, z) and lines it. Lining only goes where nothing
+/// is placed yet, so the cleared columns next to it stay open.
+pub fn carve_tunnel_column(
+    editor: &mut WorldEditor,
+    x: i32,
+    z: i32,
+    road_y: i32,
+    section: TunnelSection,
+) {
+    let ground_y = editor.get_ground_level(x, z);
+    let clear_top = match section {
+        TunnelSection::Cutting => ground_y,
+        TunnelSection::Portal | TunnelSection::Bore => road_y + BORE_HEIGHT,
+    };
+    if clear_top <= road_y {
+        return;
+    }
+    let replace_any: &[Block] = &[];
+    for y in (road_y + 1)..=clear_top {
+        editor.set_block_absolute(CAVE_AIR, x, y, z, None, Some(replace_any));
+    }
+
+    for (nx, nz) in [(x + 1, z), (x - 1, z), (x, z + 1), (x, z - 1)] {
+        for y in road_y..=clear_top {
+            editor.set_block_absolute(LINING, nx, y, nz, None, None);
+        }
+    }
+
+    // Ceiling of the bore; portals get a headwall up to the ground above
+    let ceiling_top = match section {
+        TunnelSection::Cutting => return,
+        TunnelSection::Portal => ground_y,
+        TunnelSection::Bore => road_y + BORE_HEIGHT + 1,
+    };
+    for y in (road_y + BORE_HEIGHT + 1)..=ceiling_top {
+        editor.set_block_absolute(LINING, x, y, z, None, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inside(ground: &[i32]) -> Vec<Option<i32>> {
+        ground.iter().copied().map(Some).collect()
+    }
+
+    #[test]
+    fn tunnels_ramp_down_between_their_portals() {
+        let flat = inside(&[10; 40]);
+        let road = tunnel_road_profile(&flat, 1);
+        assert_eq!(road[0], Some(10));
+        assert_eq!(road[39], Some(10));
+        assert_eq!(road[2], Some(9));
+        assert_eq!(*road.iter().flatten().min().unwrap(), 10 - LAYER_DEPTH);
+
+        // Through a hill the road runs straight from portal to portal
+        let hill = [5, 8, 20, 30, 30, 20, 8, 5];
+        let road = tunnel_road_profile(&inside(&hill), 1);
+        assert!(road
+            .iter()
+            .zip(&hill)
+            .all(|(road_y, ground_y)| road_y.unwrap() <= *ground_y));
+        assert_eq!(road, inside(&[5, 5, 4, 4, 4, 4, 5, 5]));
+    }
+
+    #[test]
+    fn portals_sit_where_the_bore_meets_the_cutting() {
+        let hill = inside(&[5, 8, 20, 30, 30, 20, 8, 5]);
+        let road = tunnel_road_profile(&hill, 1);
+        let sections: Vec<TunnelSection> = (0..hill.len())
+            .map(|i| tunnel_section(&hill, &road, i))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![
+                TunnelSection::Cutting,
+                TunnelSection::Cutting,
+                TunnelSection::Portal,
+                TunnelSection::Bore,
+                TunnelSection::Bore,
+                TunnelSection::Portal,
+                TunnelSection::Cutting,
+                TunnelSection::Cutting,
+            ]
+        );
+    }
+
+    #[test]
+    fn tiles_meet_under_the_ground_at_their_edge() {
+        let ground: Vec<i32> = (0..40).map(|i| 10 + i / 4).collect();
+        let seam = 15;
+        let west: Vec<Option<i32>> = (0..40).map(|i| (i < seam).then(|| ground[i])).collect();
+        let east: Vec<Option<i32>> = (0..40).map(|i| (i >= seam).then(|| ground[i])).collect();
+        let west_road = tunnel_road_profile(&west, 1);
+        let east_road = tunnel_road_profile(&east, 1);
+
+        // Both sides of the seam lie as deep as the ramps from the portals have reached
+        assert_eq!(west_road[0], Some(ground[0]));
+        assert_eq!(west_road[seam - 1], Some(ground[seam - 1] - LAYER_DEPTH));
+        assert_eq!(east_road[seam], Some(ground[seam] - LAYER_DEPTH));
+        assert_eq!(east_road[39], Some(ground[39]));
+        assert!(west_road[seam..].iter().all(Option::is_none));
+
+        // The bore runs on through the seam instead of opening into a cutting
+        assert_eq!(
+            tunnel_section(&west, &west_road, seam - 1),
+            TunnelSection::Bore
+        );
+        assert_eq!(tunnel_section(&east, &east_road, seam), TunnelSection::Bore);
+    }
+}
@@ -1,10 +1,90 @@
 use crate::clipping::clip_water_ring_to_bbox;
+use crate::element_processing::harbours::HARBOUR_DEPTH;
 use crate::{
-    block_definitions::WATER,
+    block_definitions::*,
     coordinate_system::cartesian::{XZBBox, XZPoint},
+    floodfill_cache::CoordinateBitmap,
     osm_parser::{ProcessedMemberRole, ProcessedNode, ProcessedRelation, ProcessedWay},
     world_editor::WorldEditor,
 };
+use std::collections::HashMap;
+
+/// Water deeper than this (meters, from `depth`/`maxdepth`) gets a darker bed
+const DEEP_WATER_M: f64 = 8.0;
+
+/// Visual character of a water body. Water itself cannot be tinted per block, so the
+/// character shows through the bed block directly under the surface.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WaterQuality {
+    /// Clear lakes and chalk-bottomed fjords: pale floor, turquoise look
+    Clear,
+    /// Brackish lagoons and tidal water: grey-green clay floor
+    Brackish,
+    /// Harbour basins, docks and canals: dark mud floor
+    Murky,
+}
+
+impl WaterQuality {
+    /// Returns the quality tagged on a water polygon, or `None` for plain water.
+    fn from_tags(tags: &HashMap<String, String>) -> Option<Self> {
+        let tag = |key: &str| tags.get(key).map(String::as_str);
+        if matches!(
+            tag("water"),
+            Some("harbour" | "basin" | "dock" | "canal" | "moat" | "wastewater")
+        ) || tags.contains_key("harbour")
+            || tag("landuse") == Some("basin")
+        {
+            Some(Self::Murky)
+        } else if matches!(tag("water"), Some("lagoon" | "salt_pool"))
+            || tag("salt") == Some("yes")
+            || tag("tidal") == Some("yes")
+        {
+            Some(Self::Brackish)
+        } else if matches!(
+            tag("water"),
+            Some("lake" | "reservoir" | "fjord" | "oxbow" | "spring")
+        ) || tag("natural") == Some("bay")
+        {
+            Some(Self::Clear)
+        } else {
+            None
+        }
+    }
+
+    fn bed(self, depth: Option<f64>) -> Block {
+        let deep = depth.is_some_and(|d| d >= DEEP_WATER_M);
+        match (self, deep) {
+            (Self::Clear, false) => DIORITE,
+            (Self::Clear, true) => LIGHT_BLUE_TERRACOTTA,
+            (Self::Brackish, false) => CLAY,
+            (Self::Brackish, true) => GRAVEL,
+            (Self::Murky, _) => MUD,
+        }
+    }
+}
+
+/// Bed block for a water polygon, chosen from its tags and tagged depth
+fn water_bed(tags: &HashMap<String, String>) -> Option<Block> {
+    let depth = ["depth", "maxdepth"]
+        .iter()
+        .filter_map(|key| tags.get(*key))
+        .find_map(|v| v.trim().trim_end_matches('m').trim().parse::<f64>().ok());
+    WaterQuality::from_tags(tags).map(|quality| quality.bed(depth))
+}
+
+/// Blocks of water above the bed: harbour basins and docks are dredged for ships, other
+/// water is a single layer
+fn water_depth(tags: &HashMap<String, String>) -> i32 {
+    let is_harbour = tags
+        .get("water")
+        .is_some_and(|w| matches!(w.as_str(), "harbour" | "dock"))
+        || tags.contains_key("harbour");
+    if is_harbour {
+        HARBOUR_DEPTH
+    } else {
+        1
+    }
+}
 
 pub fn generate_water_area_from_way(
     editor: &mut WorldEditor,
@@ -17,7 +97,16 @@ pub fn generate_water_area_from_way(
         return;
     }
 
-    generate_water_areas(editor, &outers, &[]);
+    generate_water_areas(
+        editor,
+        &outers,
+        &[],
+        water_bed(&element.tags),
+        water_depth(&element.tags),
+    );
+    if WaterQuality::from_tags(&element.tags) == Some(WaterQuality::Brackish) {
+        super::habitat::generate_reed_fringe(editor, &outers, element.id);
+    }
 }
 
 pub fn generate_water_areas_from_relation(
@@ -116,13 +205,24 @@ pub fn generate_water_areas_from_relation(
         return;
     }
 
-    generate_water_areas(editor, &outers, &inners);
+    generate_water_areas(
+        editor,
+        &outers,
+        &inners,
+        water_bed(&element.tags),
+        water_depth(&element.tags),
+    );
+    if WaterQuality::from_tags(&element.tags) == Some(WaterQuality::Brackish) {
+        super::habitat::generate_reed_fringe(editor, &outers, element.id);
+    }
 }
 
 fn generate_water_areas(
     editor: &mut WorldEditor,
     outers: &[Vec<ProcessedNode>],
     inners: &[Vec<ProcessedNode>],
+    bed: Option<Block>,
+    depth: i32,
 ) {
     let outers_xz: Vec<Vec<XZPoint>> = outers
         .iter()
@@ -133,13 +233,28 @@ fn generate_water_areas(
         .map(|x| x.iter().map(|y| y.xz()).collect::<Vec<_>>())
         .collect();
 
-    fill_water_polygons(editor, &outers_xz, &inners_xz);
+    fill_water_polygons_with_bed(editor, &outers_xz, &inners_xz, bed, depth, None);
 }
 
+/// Fills the sea, marking the blocks it covers in `sea`
 pub(crate) fn fill_water_polygons(
     editor: &mut WorldEditor,
     outers: &[Vec<XZPoint>],
     inners: &[Vec<XZPoint>],
+    sea: &mut CoordinateBitmap,
+) {
+    fill_water_polygons_with_bed(editor, outers, inners, None, 1, Some(sea));
+}
+
+/// Fills water polygons `depth` blocks deep, optionally laying `bed` directly under the water
+/// and marking the filled blocks in `filled`.
+fn fill_water_polygons_with_bed(
+    editor: &mut WorldEditor,
+    outers: &[Vec<XZPoint>],
+    inners: &[Vec<XZPoint>],
+    bed: Option<Block>,
+    depth: i32,
+    filled: Option<&mut CoordinateBitmap>,
 ) {
     // Calculate polygon bounding box to limit fill area
     let mut poly_min_x = i32::MAX;
@@ -169,7 +284,9 @@ pub(crate) fn fill_water_polygons(
     let max_x = poly_max_x.min(world_max_x);
     let max_z = poly_max_z.min(world_max_z);
 
-    scanline_fill_water(min_x, min_z, max_x, max_z, outers, inners, editor);
+    scanline_fill_water(
+        min_x, min_z, max_x, max_z, outers, inners, bed, depth, editor, filled,
+    );
 }
 
 /// Verifies all rings are properly closed (first node matches last).
@@ -398,7 +515,10 @@ fn scanline_fill_water(
     max_z: i32,
     outers: &[Vec<XZPoint>],
     inners: &[Vec<XZPoint>],
+    bed: Option<Block>,
+    depth: i32,
     editor: &mut WorldEditor,
+    mut filled: Option<&mut CoordinateBitmap>,
 ) {
     // Collect edges per outer ring so we can union their spans correctly,
     // even if multiple outer rings happen to overlap (invalid OSM, but
@@ -406,6 +526,7 @@ fn scanline_fill_water(
     let outer_edge_groups: Vec<Vec<ScanlineEdge>> =
         outers.iter().map(|ring| collect_ring_edges(ring)).collect();
     let inner_edges = collect_all_ring_edges(inners);
+    let replace_any: &[Block] = &[];
 
     for z in min_z..=max_z {
         let z_f = z as f64;
@@ -436,7 +557,45 @@ fn scanline_fill_water(
         for (start, end) in fill_spans {
             for x in start..=end {
                 editor.set_block(WATER, x, 0, z, None, None);
+                // Deeper water is dug out of whatever ground is already there
+                for y in (1 - depth)..0 {
+                    editor.set_block(WATER, x, y, z, None, Some(replace_any));
+                }
+                if let Some(bed) = bed {
+                    editor.set_block(bed, x, -depth, z, None, Some(&[WATER]));
+                }
+                if let Some(filled) = filled.as_deref_mut() {
+                    filled.set(x, z);
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn water_bed_follows_tags_and_depth() {
+        assert_eq!(water_bed(&tags(&[("water", "harbour")])), Some(MUD));
+        assert_eq!(water_bed(&tags(&[("water", "lagoon")])), Some(CLAY));
+        assert_eq!(
+            water_bed(&tags(&[("natural", "bay"), ("depth", "12 m")])),
+            Some(LIGHT_BLUE_TERRACOTTA)
+        );
+        assert_eq!(water_bed(&tags(&[("water", "lake")])), Some(DIORITE));
+        assert_eq!(water_bed(&tags(&[("natural", "water")])), None);
+    }
+
+    #[test]
+    fn harbour_basins_are_deeper_than_other_water() {
+        assert_eq!(water_depth(&tags(&[("water", "harbour")])), HARBOUR_DEPTH);
+        assert_eq!(
+            water_depth(&tags(&[("natural", "water"), ("harbour", "yes")])),
+            HARBOUR_DEPTH
+        );
+        assert_eq!(water_depth(&tags(&[("water", "lake")])), 1);
+    }
+}
@@ -1,21 +1,64 @@
+use crate::args::Args;
 use crate::block_definitions::*;
 use crate::bresenham::bresenham_line;
-use crate::osm_parser::ProcessedWay;
+use crate::coordinate_system::cartesian::XZBBox;
+use crate::deterministic_rng::coord_rng;
+use crate::element_processing::tree::{Tree, TreeType};
+use crate::floodfill::flood_fill_area;
+use crate::floodfill_cache::CoordinateBitmap;
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
 use crate::world_editor::WorldEditor;
+use fastnbt::Value;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
 
-pub fn generate_waterways(editor: &mut WorldEditor, element: &ProcessedWay) {
-    if let Some(waterway_type) = element.tags.get("waterway") {
-        let (mut waterway_width, waterway_depth) = get_waterway_dimensions(waterway_type);
+/// Narrowest navigable channel, leaving room to turn a boat
+const NAVIGABLE_MIN_WIDTH: i32 = 5;
+/// Shallowest navigable channel, so boats never scrape the bed
+const NAVIGABLE_MIN_DEPTH: i32 = 2;
+/// Blocks between lane markers along a navigable channel
+const LANE_MARKER_SPACING: usize = 24;
+/// Headroom kept between the water and a bridge deck over a navigable channel
+pub const NAVIGABLE_CLEARANCE: i32 = 4;
+/// Levels of flowing water a river's current steps through before starting over; 7 is the
+/// thinnest flowing water
+const CURRENT_LEVELS: usize = 7;
+/// Deepest a channel is cut below its banks before the water steps up instead
+const MAX_CARVE: i32 = 4;
+/// Ground a channel may be cut through: terrain and plants, not roads or buildings
+const CARVABLE: [Block; 12] = [
+    WATER,
+    DIRT,
+    COARSE_DIRT,
+    GRASS_BLOCK,
+    PODZOL,
+    SAND,
+    GRAVEL,
+    CLAY,
+    MUD,
+    GRASS,
+    TALL_GRASS_BOTTOM,
+    TALL_GRASS_TOP,
+];
+/// Bank ground nothing is planted on
+const UNPLANTABLE: [Block; 6] = [
+    WATER,
+    CAVE_AIR,
+    BLACK_CONCRETE,
+    GRAY_CONCRETE,
+    LIGHT_GRAY_CONCRETE,
+    WHITE_CONCRETE,
+];
 
-        // Check for custom width in tags
-        if let Some(width_str) = element.tags.get("width") {
-            waterway_width = width_str.parse::<i32>().unwrap_or_else(|_| {
-                width_str
-                    .parse::<f32>()
-                    .map(|f: f32| f as i32)
-                    .unwrap_or(waterway_width)
-            });
-        }
+pub fn generate_waterways(
+    editor: &mut WorldEditor,
+    element: &ProcessedWay,
+    args: &Args,
+    navigable_channels: Option<&CoordinateBitmap>,
+) {
+    if let Some(waterway_type) = element.tags.get("waterway") {
+        let (_, mut waterway_depth) = get_waterway_dimensions(waterway_type);
+        let mut waterway_width = waterway_width(&element.tags, args.scale);
 
         // Skip layers below the ground level
         if matches!(
@@ -25,30 +68,356 @@ pub fn generate_waterways(editor: &mut WorldEditor, element: &ProcessedWay) {
             return;
         }
 
-        // Process consecutive node pairs to create waterways
-        // Use windows(2) to avoid connecting last node back to first
-        for nodes_pair in element.nodes.windows(2) {
-            let prev_node = nodes_pair[0].xz();
-            let current_node = nodes_pair[1].xz();
-
-            // Draw a line between the current and previous node
-            let bresenham_points: Vec<(i32, i32, i32)> = bresenham_line(
-                prev_node.x,
-                0,
-                prev_node.z,
-                current_node.x,
-                0,
-                current_node.z,
+        // Navigable waterways (--navigable-waterways) get a channel a boat fits through,
+        // kept clear of vegetation and marked with lane buoys
+        let navigable = navigable_channels.is_some() && is_navigable_waterway(&element.tags);
+        if navigable {
+            waterway_width = waterway_width.max(NAVIGABLE_MIN_WIDTH);
+            waterway_depth = waterway_depth.max(NAVIGABLE_MIN_DEPTH);
+        }
+        let half_width = waterway_width / 2;
+
+        // Points along the way, which runs downstream. Use windows(2) to avoid connecting
+        // last node back to first
+        let mut points: Vec<(i32, i32)> = element
+            .nodes
+            .windows(2)
+            .flat_map(|pair| bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z))
+            .map(|(x, _, z)| (x, z))
+            .collect();
+        points.dedup();
+
+        // The water lies level across the channel at its lowest bank and never flows uphill.
+        // The parser keeps waterways whole, so the level is carried over the stretches
+        // outside the tile and meets the neighbouring tile's at the seam.
+        let banks = lowest_banks(editor, &points, half_width + 1);
+        let surface = water_surface_profile(&banks);
+
+        for (&(bx, bz), &surface_y) in points.iter().zip(&surface) {
+            let Some(surface_y) = surface_y else {
+                continue;
+            };
+            // Create water channel with proper depth and sloped banks
+            create_water_channel(editor, bx, bz, waterway_width, waterway_depth, surface_y);
+            if navigable {
+                clear_channel_surface(editor, bx, bz, waterway_width);
+            }
+        }
+
+        // A current down the middle of navigable rivers: flowing water whose level drops
+        // downstream, so boats drift with the river. The source water either side keeps it
+        // from draining away.
+        if navigable && waterway_type == "river" {
+            for (i, (&(bx, bz), &surface_y)) in points.iter().zip(&surface).enumerate() {
+                let Some(surface_y) = surface_y else {
+                    continue;
+                };
+                let level = 1 + i % CURRENT_LEVELS;
+                editor.set_block_with_properties_absolute(
+                    flowing_water(level),
+                    bx,
+                    surface_y,
+                    bz,
+                    Some(&[WATER]),
+                    None,
+                );
+            }
+        }
+
+        // Wide rivers are often mapped with a water area beyond their channel as well, so
+        // only streams get trees on their banks
+        if matches!(waterway_type.as_str(), "river" | "stream" | "brook") {
+            let trees = waterway_type != "river";
+            let in_tile: Vec<(i32, i32)> = points
+                .iter()
+                .zip(&surface)
+                .filter(|(_, surface_y)| surface_y.is_some())
+                .map(|(&point, _)| point)
+                .collect();
+            plant_banks(editor, &in_tile, half_width + 2, trees, element.id);
+        }
+
+        if navigable {
+            place_lane_markers(editor, element, half_width);
+        }
+    }
+}
+
+/// Width of a waterway in blocks: its `width` tag in meters, or the usual width of its kind
+fn waterway_width(tags: &HashMap<String, String>, scale: f64) -> i32 {
+    let (class_width, _) = tags
+        .get("waterway")
+        .map_or((4, 2), |w| get_waterway_dimensions(w));
+    let meters = tags
+        .get("width")
+        .and_then(|w| w.trim().trim_end_matches('m').trim().parse::<f64>().ok())
+        .unwrap_or(class_width as f64);
+    ((meters * scale).round() as i32).max(1)
+}
+
+/// Lowest ground within `reach` blocks of each point along a waterway, or `None` outside
+/// the tile, where the ground is not known. The ground is looked up across the way at each
+/// point and a running minimum over the points either side takes in the ground along it,
+/// so a point costs a line of lookups rather than a square.
+fn lowest_banks(editor: &WorldEditor, points: &[(i32, i32)], reach: i32) -> Vec<Option<i32>> {
+    let across: Vec<Option<i32>> = points
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, z))| {
+            if !editor.in_tile(x, z) {
+                return None;
+            }
+            let (prev, next) = (
+                points[i.saturating_sub(1)],
+                points[(i + 1).min(points.len() - 1)],
             );
+            let along_x = (next.0 - prev.0).abs() >= (next.1 - prev.1).abs();
+            let (across_x, across_z) = if along_x { (0, 1) } else { (1, 0) };
+            (-reach..=reach)
+                .map(|offset| editor.get_ground_level(x + offset * across_x, z + offset * across_z))
+                .min()
+        })
+        .collect();
+    running_min(&across, reach as usize)
+}
+
+/// The least of the known values within `reach` places of each known value, in one pass:
+/// the queue holds the places still in reach whose values no later one has undercut.
+fn running_min(values: &[Option<i32>], reach: usize) -> Vec<Option<i32>> {
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    let mut ahead = 0;
+    (0..values.len())
+        .map(|i| {
+            while ahead < values.len() && ahead <= i + reach {
+                if values[ahead].is_some() {
+                    while queue.back().is_some_and(|&j| values[j] >= values[ahead]) {
+                        queue.pop_back();
+                    }
+                    queue.push_back(ahead);
+                }
+                ahead += 1;
+            }
+            while queue.front().is_some_and(|&j| j + reach < i) {
+                queue.pop_front();
+            }
+            values[i].and(queue.front().and_then(|&j| values[j]))
+        })
+        .collect()
+}
+
+/// Water level along a waterway from the lowest bank at each point downstream. The water
+/// never rises along its course, except where the banks climb more than `MAX_CARVE` blocks
+/// above it: there it steps up rather than cutting a gorge. Where the bank is not known the
+/// level runs on unchanged, and the point has none.
+fn water_surface_profile(banks: &[Option<i32>]) -> Vec<Option<i32>> {
+    let mut level = i32::MAX;
+    banks
+        .iter()
+        .map(|bank| {
+            bank.map(|bank| {
+                level = level.min(bank).max(bank - MAX_CARVE);
+                level
+            })
+        })
+        .collect()
+}
 
-            for (bx, _, bz) in bresenham_points {
-                // Create water channel with proper depth and sloped banks
-                create_water_channel(editor, bx, bz, waterway_width, waterway_depth);
+/// Reeds, ferns and, with `trees`, the odd birch along the banks of natural watercourses,
+/// `reach` blocks from the middle of the channel
+fn plant_banks(
+    editor: &mut WorldEditor,
+    points: &[(i32, i32)],
+    reach: i32,
+    trees: bool,
+    element_id: u64,
+) {
+    for &(cx, cz) in points {
+        for dx in -reach..=reach {
+            for dz in -reach..=reach {
+                if dx.abs().max(dz.abs()) != reach {
+                    continue;
+                }
+                let (x, z) = (cx + dx, cz + dz);
+                if editor.check_for_block(x, 0, z, Some(&UNPLANTABLE)) {
+                    continue;
+                }
+                match coord_rng(x, z, element_id).random_range(0..100) {
+                    0 if trees => Tree::create_of_type(editor, (x, 1, z), TreeType::Birch, None),
+                    1..25 => {
+                        editor.set_block(TALL_GRASS_BOTTOM, x, 1, z, None, None);
+                        editor.set_block(TALL_GRASS_TOP, x, 2, z, None, None);
+                    }
+                    25..35 => editor.set_block(FERN, x, 1, z, None, None),
+                    _ => {}
+                }
             }
         }
     }
 }
 
+/// Whether boats can travel a waterway: rivers, canals and fairways unless tagged
+/// `boat=no`, and any other waterway tagged for boats or with a CEMT class
+pub fn is_navigable_waterway(tags: &HashMap<String, String>) -> bool {
+    let Some(waterway_type) = tags.get("waterway") else {
+        return false;
+    };
+    let tagged_for = |key: &str| tags.get(key).map(|v| v.as_str());
+    if tagged_for("boat") == Some("no") {
+        return false;
+    }
+    matches!(waterway_type.as_str(), "river" | "canal" | "fairway")
+        || matches!(tagged_for("boat"), Some("yes") | Some("designated"))
+        || matches!(tagged_for("motorboat"), Some("yes") | Some("designated"))
+        || tags.contains_key("CEMT")
+}
+
+/// Flowing water of the given level (1 to 7), thinning as the level rises
+fn flowing_water(level: usize) -> BlockWithProperties {
+    let properties = HashMap::from([("level".to_string(), Value::String(level.to_string()))]);
+    BlockWithProperties::new(WATER, Some(Value::Compound(properties)))
+}
+
+/// Collects the water surface cells of the navigable waterways and harbour basins
+/// (--navigable-waterways), which bridges keep clear of pillars and span with headroom for
+/// boats, so the way in from the sea stays open
+pub fn collect_navigable_channels(
+    elements: &[ProcessedElement],
+    xzbbox: &XZBBox,
+    scale: f64,
+) -> CoordinateBitmap {
+    let mut mask = CoordinateBitmap::new(xzbbox);
+
+    for element in elements {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        if way.tags.get("water").is_some_and(|w| w == "harbour") {
+            let outline: Vec<(i32, i32)> = way.nodes.iter().map(|n| (n.x, n.z)).collect();
+            for (x, z) in flood_fill_area(&outline, None) {
+                mask.set(x, z);
+            }
+            continue;
+        }
+        if !is_navigable_waterway(&way.tags) {
+            continue;
+        }
+
+        let half_width = waterway_width(&way.tags, scale).max(NAVIGABLE_MIN_WIDTH) / 2;
+
+        for pair in way.nodes.windows(2) {
+            for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+                for dx in -half_width..=half_width {
+                    for dz in -half_width..=half_width {
+                        mask.set(x + dx, z + dz);
+                    }
+                }
+            }
+        }
+    }
+
+    mask
+}
+
+/// Clears reeds and crops from the water surface of a navigable channel, so nothing
+/// stops a boat
+fn clear_channel_surface(editor: &mut WorldEditor, center_x: i32, center_z: i32, width: i32) {
+    const OBSTACLES: [Block; 6] = [
+        GRASS,
+        TALL_GRASS_BOTTOM,
+        TALL_GRASS_TOP,
+        WHEAT,
+        CARROTS,
+        POTATOES,
+    ];
+    let half_width = width / 2;
+    for x in (center_x - half_width)..=(center_x + half_width) {
+        for z in (center_z - half_width)..=(center_z + half_width) {
+            for y in 1..=2 {
+                if editor.check_for_block(x, y, z, Some(&OBSTACLES)) {
+                    editor.set_block(AIR, x, y, z, Some(&OBSTACLES), None);
+                }
+            }
+        }
+    }
+}
+
+/// Places lane buoys on both edges of a navigable channel. Following IALA region A, a
+/// boat heading upstream, against the way direction, keeps red to port and green to
+/// starboard.
+fn place_lane_markers(editor: &mut WorldEditor, element: &ProcessedWay, half_width: i32) {
+    let mut distance = 0;
+    for pair in element.nodes.windows(2) {
+        let (dx, dz) = (
+            (pair[1].x - pair[0].x) as f64,
+            (pair[1].z - pair[0].z) as f64,
+        );
+        let length = dx.hypot(dz);
+        if length == 0.0 {
+            continue;
+        }
+        // Right of the way direction, i.e. the port side heading upstream
+        let (right_x, right_z) = (-dz / length, dx / length);
+        let offset_x = (right_x * half_width as f64).round() as i32;
+        let offset_z = (right_z * half_width as f64).round() as i32;
+
+        for (x, _, z) in bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z) {
+            distance += 1;
+            if distance % LANE_MARKER_SPACING != 0 {
+                continue;
+            }
+            for (marker_x, marker_z, marker) in [
+                (x + offset_x, z + offset_z, RED_WOOL),
+                (x - offset_x, z - offset_z, GREEN_WOOL),
+            ] {
+                if let Some(y) = water_surface_y(editor, marker_x, marker_z) {
+                    editor.set_block(marker, marker_x, y, marker_z, Some(&[WATER]), None);
+                    editor.set_block(marker, marker_x, y + 1, marker_z, Some(&[CAVE_AIR]), None);
+                }
+            }
+        }
+    }
+}
+
+/// Height of the water surface at (x, z) relative to the ground, if there is water. The
+/// surface lies below the ground where a channel is cut in.
+fn water_surface_y(editor: &WorldEditor, x: i32, z: i32) -> Option<i32> {
+    (-MAX_CARVE..=0)
+        .rev()
+        .find(|&y| editor.check_for_block(x, y, z, Some(&[WATER])))
+}
+
+/// Places a lateral buoy or beacon mapped with `seamark:type` (--navigable-waterways), the
+/// marks that lead boats through harbour entrances and along fairways. In Danish waters
+/// (IALA region A) port marks are red and starboard marks green; the mapped colour wins.
+pub fn generate_lateral_mark(editor: &mut WorldEditor, node: &ProcessedNode) {
+    let Some(kind @ ("buoy_lateral" | "beacon_lateral")) =
+        node.tags.get("seamark:type").map(String::as_str)
+    else {
+        return;
+    };
+    let tag = |key: &str| node.tags.get(&format!("seamark:{kind}:{key}"));
+    let colour = tag("colour").and_then(|colours| colours.split(';').next());
+    let block = match (colour, tag("category").map(String::as_str)) {
+        (Some("red"), _) | (None, Some("port" | "preferred_channel_starboard")) => RED_WOOL,
+        (Some("green"), _) | (None, Some("starboard" | "preferred_channel_port")) => GREEN_WOOL,
+        _ => return,
+    };
+    if kind == "buoy_lateral" {
+        // Buoys float on the water, with a light on top
+        if let Some(y) = water_surface_y(editor, node.x, node.z) {
+            editor.set_block(block, node.x, y, node.z, Some(&[WATER]), None);
+            editor.set_block(block, node.x, y + 1, node.z, None, None);
+            editor.set_block(SEA_LANTERN, node.x, y + 2, node.z, None, None);
+        }
+    } else {
+        // Beacons stand on the breakwater or the sea bed, three blocks tall
+        for y in 1..=3 {
+            editor.set_block(block, node.x, y, node.z, Some(&[WATER]), None);
+        }
+        editor.set_block(SEA_LANTERN, node.x, 4, node.z, None, None);
+    }
+}
+
 /// Determines width and depth based on waterway type
 fn get_waterway_dimensions(waterway_type: &str) -> (i32, i32) {
     match waterway_type {
@@ -64,13 +433,15 @@ fn get_waterway_dimensions(waterway_type: &str) -> (i32, i32) {
     }
 }
 
-/// Creates a water channel with proper depth and sloped banks
+/// Creates a water channel with proper depth and sloped banks, its water surface at
+/// `surface_y`. Banks above the water are cut down to it.
 fn create_water_channel(
     editor: &mut WorldEditor,
     center_x: i32,
     center_z: i32,
     width: i32,
     depth: i32,
+    surface_y: i32,
 ) {
     let half_width = width / 2;
 
@@ -80,36 +451,110 @@ fn create_water_channel(
             let dz = (z - center_z).abs();
             let distance_from_center = dx.max(dz);
 
-            if distance_from_center <= half_width {
+            let water_depth = if distance_from_center <= half_width {
                 // Main water channel
-                for y in (1 - depth)..=0 {
-                    editor.set_block(WATER, x, y, z, None, None);
-                }
-
-                // Place one layer of dirt below the water channel
-                editor.set_block(DIRT, x, -depth, z, None, None);
-
-                // Clear vegetation above the water
-                editor.set_block(AIR, x, 1, z, Some(&[GRASS, WHEAT, CARROTS, POTATOES]), None);
+                depth
             } else if distance_from_center == half_width + 1 && depth > 1 {
-                // Create sloped banks (one block interval slopes)
-                let slope_depth = (depth - 1).max(1);
-                for y in (1 - slope_depth)..=0 {
-                    if y == 0 {
-                        // Surface level - place water or air
-                        editor.set_block(WATER, x, y, z, None, None);
-                    } else {
-                        // Below surface - dig out for slope
-                        editor.set_block(AIR, x, y, z, None, None);
-                    }
-                }
-
-                // Place one layer of dirt below the sloped areas
-                editor.set_block(DIRT, x, -slope_depth, z, None, None);
+                // Sloped banks (one block interval slopes)
+                (depth - 1).max(1)
+            } else {
+                continue;
+            };
 
-                // Clear vegetation above sloped areas
-                editor.set_block(AIR, x, 1, z, Some(&[GRASS, WHEAT, CARROTS, POTATOES]), None);
+            // Cave air keeps the cut open when the ground is filled in later
+            let ground_y = editor.get_ground_level(x, z);
+            for y in (surface_y + 1)..=ground_y {
+                editor.set_block_absolute(CAVE_AIR, x, y, z, Some(&CARVABLE), None);
+            }
+            for y in (surface_y - water_depth + 1)..=surface_y {
+                editor.set_block_absolute(WATER, x, y, z, Some(&[DIRT, CAVE_AIR]), None);
             }
+
+            // Place one layer of dirt below the water
+            editor.set_block_absolute(DIRT, x, surface_y - water_depth, z, None, None);
+
+            // Clear vegetation above the water
+            editor.set_block_absolute(
+                AIR,
+                x,
+                ground_y.max(surface_y) + 1,
+                z,
+                Some(&[GRASS, WHEAT, CARROTS, POTATOES]),
+                None,
+            );
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn boats_travel_rivers_canals_and_tagged_waterways() {
+        assert!(is_navigable_waterway(&tags(&[("waterway", "river")])));
+        assert!(is_navigable_waterway(&tags(&[("waterway", "fairway")])));
+        assert!(!is_navigable_waterway(&tags(&[("waterway", "stream")])));
+        assert!(is_navigable_waterway(&tags(&[
+            ("waterway", "stream"),
+            ("boat", "yes")
+        ])));
+        assert!(!is_navigable_waterway(&tags(&[
+            ("waterway", "canal"),
+            ("boat", "no")
+        ])));
+        assert!(!is_navigable_waterway(&tags(&[("natural", "water")])));
+    }
+
+    #[test]
+    fn the_current_thins_downstream() {
+        let level = |i: usize| {
+            let properties = HashMap::from([("level".to_string(), Value::String(i.to_string()))]);
+            Some(Value::Compound(properties))
+        };
+        let water = |i: usize| flowing_water(1 + i % CURRENT_LEVELS);
+        assert_eq!(water(0).properties, level(1));
+        assert_eq!(water(6).properties, level(7));
+        assert_eq!(water(7).properties, level(1));
+    }
+
+    #[test]
+    fn widths_come_from_the_width_tag_or_the_kind_of_waterway() {
+        assert_eq!(waterway_width(&tags(&[("waterway", "river")]), 1.0), 8);
+        assert_eq!(
+            waterway_width(&tags(&[("waterway", "river"), ("width", "25 m")]), 1.0),
+            25
+        );
+        assert_eq!(
+            waterway_width(&tags(&[("waterway", "stream"), ("width", "1.6")]), 0.5),
+            1
+        );
+        assert_eq!(waterway_width(&tags(&[("waterway", "drain")]), 0.3), 1);
+    }
+
+    #[test]
+    fn water_never_flows_uphill_but_steps_up_out_of_gorges() {
+        let known = |banks: &[i32]| banks.iter().copied().map(Some).collect::<Vec<_>>();
+        assert_eq!(
+            water_surface_profile(&known(&[10, 9, 11, 8, 8, 20])),
+            known(&[10, 9, 9, 8, 8, 16])
+        );
+        // Upstream of the tile the level is carried on, not started again
+        assert_eq!(
+            water_surface_profile(&[Some(6), None, None, Some(9), Some(5)]),
+            vec![Some(6), None, None, Some(6), Some(5)]
+        );
+    }
+
+    #[test]
+    fn banks_are_the_lowest_ground_within_reach_along_the_way() {
+        let values = [Some(5), Some(3), Some(7), None, Some(8), Some(9), Some(1)];
+        assert_eq!(
+            running_min(&values, 1),
+            vec![Some(3), Some(3), Some(3), None, Some(8), Some(1), Some(1)]
+        );
+        assert_eq!(running_min(&values, 0), values.to_vec());
+        assert!(running_min(&[], 2).is_empty());
+    }
+}
@@ -1,15 +1,22 @@
 #[cfg(feature = "gui")]
 use crate::telemetry::{send_log, LogLevel};
 use crate::{
+    args::Args,
     coordinate_system::{geographic::LLBBox, transformation::geo_distance},
     progress::emit_gui_progress_update,
 };
+use colored::Colorize;
 use image::Rgb;
 use rayon::prelude::*;
 use std::path::{Path, PathBuf};
 
 /// Maximum Y coordinate in Minecraft (build height limit)
 const MAX_Y: i32 = 319;
+/// Blocks kept free below the build limit for buildings, trees and other structures
+const TERRAIN_HEIGHT_BUFFER: i32 = 15;
+/// Blocks between the ground level and the default sea level in absolute mode, so land below
+/// sea level such as Lammefjorden (-7m) keeps its shape
+const DEFAULT_SEA_LEVEL_DEPTH: i32 = 16;
 /// AWS S3 Terrarium tiles endpoint (no API key required)
 const AWS_TERRARIUM_URL: &str =
     "https://s3.amazonaws.com/elevation-tiles-prod/terrarium/{z}/{x}/{y}.png";
@@ -22,7 +29,72 @@ const MAX_ZOOM: u8 = 15;
 /// Maximum concurrent tile downloads to be respectful to AWS
 const MAX_CONCURRENT_DOWNLOADS: usize = 8;
 /// Maximum age for cached tiles in days before they are cleaned up
-const TILE_CACHE_MAX_AGE_DAYS: u64 = 7;
+pub const TILE_CACHE_MAX_AGE_DAYS: u64 = 7;
+/// Directory the downloaded elevation tiles are cached in
+pub const TILE_CACHE_DIR: &str = "./arnis-tile-cache";
+
+/// How real-world elevation is mapped onto Minecraft Y.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ElevationMode {
+    /// Lowest point of the area at the ground level, compressed only to fit the build limit
+    #[default]
+    Relative,
+    /// Real meters above sea level at a fixed meters-per-block, sea level at a fixed Y
+    Absolute,
+}
+
+/// Vertical mapping settings shared by all elevation sources
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeightMapping {
+    pub mode: ElevationMode,
+    pub ground_level: i32,
+    pub meters_per_block: f64,
+    /// Minecraft Y of 0m elevation in absolute mode
+    pub sea_level_y: i32,
+    /// Vertical exaggeration applied on top of either mode (1.0 = none)
+    pub exaggeration: f64,
+}
+
+impl HeightMapping {
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            mode: args.elevation_mode,
+            ground_level: args.ground_level,
+            meters_per_block: args.meters_per_block,
+            sea_level_y: args
+                .sea_level_y
+                .unwrap_or(args.ground_level + DEFAULT_SEA_LEVEL_DEPTH),
+            exaggeration: args.terrain_exaggeration,
+        }
+    }
+
+    /// Minecraft Y of a real elevation in absolute mode, clamped to the usable Y range
+    pub fn absolute_y(&self, meters: f64) -> i32 {
+        ((self.sea_level_y as f64 + self.absolute_blocks(meters)).round() as i32)
+            .clamp(self.ground_level, MAX_Y - TERRAIN_HEIGHT_BUFFER)
+    }
+
+    fn absolute_blocks(&self, meters: f64) -> f64 {
+        meters * self.exaggeration / self.meters_per_block
+    }
+
+    /// Warns when absolute terrain does not fit between the ground level and the build limit
+    pub fn warn_if_clipped(&self, min_meters: f64, max_meters: f64) {
+        let top = self.sea_level_y as f64 + self.absolute_blocks(max_meters);
+        let bottom = self.sea_level_y as f64 + self.absolute_blocks(min_meters);
+        if top > (MAX_Y - TERRAIN_HEIGHT_BUFFER) as f64 || bottom < self.ground_level as f64 {
+            eprintln!(
+                "{} Absolute elevation {:.1}m to {:.1}m does not fit the Y range {} to {}; \
+                 terrain is clipped. Adjust --meters-per-block or --sea-level-y.",
+                "Warning:".yellow().bold(),
+                min_meters,
+                max_meters,
+                self.ground_level,
+                MAX_Y - TERRAIN_HEIGHT_BUFFER
+            );
+        }
+    }
+}
 
 /// Holds processed elevation data and metadata
 #[derive(Clone)]
@@ -37,6 +109,12 @@ pub struct ElevationData {
     /// When set, areas at or below this level should be filled with water.
     /// Only set by DHM (high-res Danish terrain) which has accurate absolute elevation.
     pub(crate) sea_level_y: Option<i32>,
+    /// Cells carved into open water by `--carve-sea`, row-major like `heights`
+    pub(crate) carved_sea: Option<Vec<Vec<bool>>>,
+    /// Cells of coastal cliff faces found in the DHM, row-major like `heights`
+    pub(crate) coastal_cliffs: Option<Vec<Vec<bool>>>,
+    /// Cells the DEM measured, rather than nodata filled in, row-major like `heights`
+    pub(crate) measured: Option<Vec<Vec<bool>>>,
 }
 
 /// RGB image buffer type for elevation tiles
@@ -48,7 +126,7 @@ type TileDownloadResult = Result<((u32, u32), TileImage), String>;
 /// Only deletes .png files within the arnis-tile-cache directory that are older than TILE_CACHE_MAX_AGE_DAYS.
 /// This function is safe and will not delete files outside the cache directory or fail on errors.
 pub fn cleanup_old_cached_tiles() {
-    let tile_cache_dir = PathBuf::from("./arnis-tile-cache");
+    let tile_cache_dir = PathBuf::from(TILE_CACHE_DIR);
 
     if !tile_cache_dir.exists() || !tile_cache_dir.is_dir() {
         return; // Nothing to clean up
@@ -126,6 +204,46 @@ pub fn cleanup_old_cached_tiles() {
     }
 }
 
+/// Lists the cached tile files (z{zoom}_x{x}_y{y}.png) in the tile cache directory.
+fn cached_tile_files() -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(TILE_CACHE_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|name| name.ends_with(".png") && name.starts_with('z'))
+        })
+        .collect()
+}
+
+/// Returns the number of cached elevation tiles and their total size in bytes.
+pub fn cached_tiles_usage() -> (usize, u64) {
+    let files = cached_tile_files();
+    let bytes = files
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    (files.len(), bytes)
+}
+
+/// Deletes all cached elevation tiles regardless of age.
+/// Returns the number of deleted tiles.
+pub fn clear_cached_tiles() -> usize {
+    cached_tile_files()
+        .iter()
+        .filter(|path| std::fs::remove_file(path).is_ok())
+        .count()
+}
+
 /// Calculates appropriate zoom level for the given bounding box
 fn calculate_zoom_level(bbox: &LLBBox) -> u8 {
     let lat_diff: f64 = (bbox.max().lat() - bbox.min().lat()).abs();
@@ -271,8 +389,9 @@ fn fetch_or_load_tile(
 pub fn fetch_elevation_data(
     bbox: &LLBBox,
     scale: f64,
-    ground_level: i32,
+    mapping: &HeightMapping,
 ) -> Result<ElevationData, Box<dyn std::error::Error>> {
+    let ground_level = mapping.ground_level;
     let (base_scale_z, base_scale_x) = geo_distance(bbox.min(), bbox.max());
 
     // Apply same floor() and scale operations as CoordTransformer.llbbox_to_xzbbox()
@@ -291,7 +410,7 @@ pub fn fetch_elevation_data(
     let mut height_grid: Vec<Vec<f64>> = vec![vec![f64::NAN; grid_width]; grid_height];
     let mut extreme_values_found = Vec::new(); // Track extreme values for debugging
 
-    let tile_cache_dir = PathBuf::from("./arnis-tile-cache");
+    let tile_cache_dir = PathBuf::from(TILE_CACHE_DIR);
     if !tile_cache_dir.exists() {
         std::fs::create_dir_all(&tile_cache_dir)?;
     }
@@ -480,17 +599,27 @@ pub fn fetch_elevation_data(
     // Realistic height scaling: 1 meter of real elevation = scale blocks in Minecraft
     // At scale=1.0, 1 meter = 1 block (realistic 1:1 mapping)
     // At scale=2.0, 1 meter = 2 blocks (exaggerated for larger worlds)
-    let ideal_scaled_range: f64 = height_range * scale;
+    // --terrain-exaggeration stretches this further, e.g. to make flat moraine landscapes visible
+    let ideal_scaled_range: f64 = height_range * scale * mapping.exaggeration;
 
     // Calculate available Y range in Minecraft (from ground_level to MAX_Y)
     // Leave a buffer at the top for buildings, trees, and other structures
-    const TERRAIN_HEIGHT_BUFFER: i32 = 15;
     let available_y_range: f64 = (MAX_Y - TERRAIN_HEIGHT_BUFFER - ground_level) as f64;
 
     // Determine final height scale:
     // - Use realistic 1:1 (times scale) if terrain fits within Minecraft limits
     // - Only compress if the terrain would exceed the build height
-    let scaled_range: f64 = if ideal_scaled_range <= available_y_range {
+    let scaled_range: f64 = if mapping.mode == ElevationMode::Absolute {
+        eprintln!(
+            "Absolute elevation: {:.1}m to {:.1}m, 1 block = {:.2}m, sea level at Y={}",
+            min_height,
+            max_height,
+            mapping.meters_per_block / mapping.exaggeration,
+            mapping.sea_level_y
+        );
+        mapping.warn_if_clipped(min_height, max_height);
+        ideal_scaled_range
+    } else if ideal_scaled_range <= available_y_range {
         // Terrain fits! Use realistic scaling
         eprintln!(
             "Realistic elevation: {:.1}m range fits in {} available blocks",
@@ -518,6 +647,9 @@ pub fn fetch_elevation_data(
         .map(|row| {
             row.iter()
                 .map(|&h| {
+                    if mapping.mode == ElevationMode::Absolute {
+                        return mapping.absolute_y(h);
+                    }
                     // Calculate relative position within the elevation range (0.0 to 1.0)
                     let relative_height: f64 = if height_range > 0.0 {
                         (h - min_height) / height_range
@@ -549,6 +681,9 @@ pub fn fetch_elevation_data(
         width: grid_width,
         height: grid_height,
         sea_level_y: None,
+        carved_sea: None,
+        coastal_cliffs: None,
+        measured: None,
     })
 }
 
@@ -747,6 +882,7 @@ fn filter_elevation_outliers(height_grid: &mut [Vec<f64>]) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::Parser;
 
     #[test]
     fn test_terrarium_height_decoding() {
@@ -777,6 +913,50 @@ mod tests {
         assert_eq!(height, -100.0);
     }
 
+    #[test]
+    fn land_below_sea_level_keeps_its_shape_by_default() {
+        let args = Args::parse_from([
+            "arnis",
+            "--output-dir",
+            ".",
+            "--bbox",
+            "55.7,11.4,55.8,11.5",
+            "--elevation-mode",
+            "absolute",
+        ]);
+        let mapping = HeightMapping::from_args(&args);
+        assert!(mapping.absolute_y(-7.0) > args.ground_level);
+        assert!(mapping.absolute_y(-7.0) < mapping.absolute_y(0.0));
+    }
+
+    #[test]
+    fn test_absolute_height_mapping() {
+        let mapping = HeightMapping {
+            mode: ElevationMode::Absolute,
+            ground_level: -62,
+            meters_per_block: 1.0,
+            sea_level_y: 0,
+            exaggeration: 1.0,
+        };
+        // Himmelbjerget, 147m
+        assert_eq!(mapping.absolute_y(147.0), 147);
+        assert_eq!(mapping.absolute_y(-3.0), -3);
+        assert_eq!(mapping.absolute_y(-500.0), -62);
+        assert_eq!(mapping.absolute_y(2000.0), MAX_Y - TERRAIN_HEIGHT_BUFFER);
+
+        let mapping = HeightMapping {
+            meters_per_block: 2.0,
+            ..mapping
+        };
+        assert_eq!(mapping.absolute_y(147.0), 74);
+
+        let mapping = HeightMapping {
+            exaggeration: 3.0,
+            ..mapping
+        };
+        assert_eq!(mapping.absolute_y(10.0), 15);
+    }
+
     #[test]
     fn test_aws_url_generation() {
         let url = AWS_TERRARIUM_URL
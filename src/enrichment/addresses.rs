@@ -0,0 +1,356 @@
+//! Street-name and house-number signs from DAWA, the Danish address register (`--addresses`).
+//!
+//! The access addresses in the bbox are fetched from the free DAWA API on Dataforsyningen
+//! and added to the elements as address nodes (`addr:street`/`addr:housenumber`), so they
+//! follow any map transformation like the OSM data. After the elements are processed, every
+//! address gets a sign with its house number at the edge of the nearest street, on the side
+//! of its building, and every intersection of named streets gets a sign listing the streets
+//! that meet there. Streets without a `name` in OSM take the street name of the nearest
+//! address.
+
+use crate::bresenham::bresenham_line;
+use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, PointIndex, ADDRESS_IDS};
+use crate::floodfill_cache::CoordinateBitmap;
+use crate::osm_parser::{ProcessedElement, ProcessedNode};
+use crate::world_editor::sign_text::sign_line;
+use crate::world_editor::WorldEditor;
+use std::collections::{BTreeSet, HashMap};
+
+const DAWA_URL: &str = "https://api.dataforsyningen.dk/adgangsadresser";
+/// Source of the address nodes
+const SOURCE: &str = "DAWA";
+/// How far (blocks) an unnamed street may be from an address to take its street name, and
+/// an address from a street to get its sign there
+const STREET_MATCH_RADIUS: i32 = 30;
+/// How far (blocks) a sign may be moved to find a free spot off roads and buildings
+const SIGN_SEARCH_RADIUS: i32 = 6;
+
+/// Highway types that get street-name signs at their intersections
+const STREET_TYPES: &[&str] = &[
+    "primary",
+    "secondary",
+    "tertiary",
+    "residential",
+    "living_street",
+    "unclassified",
+    "pedestrian",
+];
+
+/// An access address from DAWA
+#[derive(Clone, Debug, PartialEq)]
+pub struct Address {
+    pub street: String,
+    pub house_number: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Fetches the access addresses inside the bbox from DAWA.
+pub fn fetch_addresses(bbox: &LLBBox) -> Result<Vec<Address>, String> {
+    println!("Fetching addresses from DAWA...");
+    let (min, max) = (bbox.min(), bbox.max());
+    let polygon = format!(
+        "[[[{0},{1}],[{2},{1}],[{2},{3}],[{0},{3}],[{0},{1}]]]",
+        min.lng(),
+        min.lat(),
+        max.lng(),
+        max.lat()
+    );
+    let url = format!("{DAWA_URL}?polygon={polygon}&srid=4326&struktur=mini");
+    let bytes = enrichment::download(SOURCE, "addresses", &url, &url)?;
+    parse_addresses(&bytes)
+}
+
+/// Parses a DAWA `struktur=mini` response; `x`/`y` are longitude/latitude.
+fn parse_addresses(bytes: &[u8]) -> Result<Vec<Address>, String> {
+    let json: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid DAWA response: {e}"))?;
+    let entries = json
+        .as_array()
+        .ok_or_else(|| "Invalid DAWA response: expected a list of addresses".to_string())?;
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            Some(Address {
+                street: entry.get("vejnavn")?.as_str()?.to_string(),
+                house_number: entry.get("husnr")?.as_str()?.to_string(),
+                lon: entry.get("x")?.as_f64()?,
+                lat: entry.get("y")?.as_f64()?,
+            })
+        })
+        .collect())
+}
+
+/// Address nodes for the addresses inside the world.
+pub fn address_nodes(
+    addresses: &[Address],
+    transformer: &CoordTransformer,
+    xzbbox: &XZBBox,
+) -> Vec<ProcessedElement> {
+    addresses
+        .iter()
+        .enumerate()
+        .filter_map(|(i, address)| {
+            let point = transformer.transform_point(LLPoint::new(address.lat, address.lon).ok()?);
+            if !xzbbox.contains(&point) {
+                return None;
+            }
+            let tags = HashMap::from([
+                ("addr:street".to_string(), address.street.clone()),
+                ("addr:housenumber".to_string(), address.house_number.clone()),
+                enrichment::source_tag(SOURCE),
+            ]);
+            Some(ProcessedElement::Node(ProcessedNode {
+                id: ADDRESS_IDS + i as u64,
+                tags,
+                x: point.x,
+                z: point.z,
+                exact: None,
+            }))
+        })
+        .collect()
+}
+
+/// A sign to place: position and its four lines of text. House-number signs stand at the
+/// street in front of their address, on the side the address lies, at `toward`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sign {
+    pub x: i32,
+    pub z: i32,
+    pub toward: Option<(i32, i32)>,
+    pub lines: [String; 4],
+}
+
+/// Whether a way is a street that gets signs
+fn is_street(tags: &HashMap<String, String>) -> bool {
+    tags.get("highway")
+        .is_some_and(|highway| STREET_TYPES.contains(&highway.as_str()))
+        && tags.get("area").is_none_or(|area| area != "yes")
+}
+
+/// Plans house-number signs for the DAWA address nodes and street-name signs for the
+/// intersections of named streets. Must run before the elements are consumed.
+pub fn plan_signs(elements: &[ProcessedElement]) -> Vec<Sign> {
+    let mut addresses: PointIndex<&str> = PointIndex::new(STREET_MATCH_RADIUS);
+    let mut street_points: PointIndex<()> = PointIndex::new(STREET_MATCH_RADIUS);
+    for element in elements {
+        match element {
+            ProcessedElement::Node(node) if enrichment::is_from(&node.tags, SOURCE) => {
+                addresses.insert(node.x, node.z, &node.tags["addr:street"]);
+            }
+            ProcessedElement::Way(way) if is_street(&way.tags) => {
+                for pair in way.nodes.windows(2) {
+                    for (x, _, z) in
+                        bresenham_line(pair[0].x, 0, pair[0].z, pair[1].x, 0, pair[1].z)
+                    {
+                        street_points.insert(x, z, ());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut signs: Vec<Sign> = elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Node(node) if enrichment::is_from(&node.tags, SOURCE) => Some(node),
+            _ => None,
+        })
+        .map(|node| {
+            // At the nearest street, or beside the building where no street is near
+            let (x, z, toward) = match street_points.nearest(node.x, node.z) {
+                Some((x, z, _)) => (x, z, Some((node.x, node.z))),
+                None => (node.x, node.z, None),
+            };
+            Sign {
+                x,
+                z,
+                toward,
+                lines: [
+                    sign_line(&node.tags["addr:housenumber"]),
+                    sign_line(&node.tags["addr:street"]),
+                    String::new(),
+                    String::new(),
+                ],
+            }
+        })
+        .collect();
+
+    // Street names meeting at each highway node
+    let mut streets_at: HashMap<u64, (XZPoint, BTreeSet<String>)> = HashMap::new();
+    for element in elements {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        if !is_street(&way.tags) || way.nodes.is_empty() {
+            continue;
+        }
+        let middle = &way.nodes[way.nodes.len() / 2];
+        let Some(name) = way.tags.get("name").cloned().or_else(|| {
+            addresses
+                .nearest(middle.x, middle.z)
+                .map(|(_, _, street)| street.to_string())
+        }) else {
+            continue;
+        };
+        for node in &way.nodes {
+            streets_at
+                .entry(node.id)
+                .or_insert_with(|| (node.xz(), BTreeSet::new()))
+                .1
+                .insert(name.clone());
+        }
+    }
+
+    let mut intersections: Vec<(XZPoint, BTreeSet<String>)> = streets_at
+        .into_values()
+        .filter(|(_, names)| names.len() >= 2)
+        .collect();
+    intersections.sort_by_key(|(point, _)| (point.x, point.z));
+    for (point, names) in intersections {
+        let mut lines: [String; 4] = Default::default();
+        for (line, name) in lines.iter_mut().zip(&names) {
+            *line = sign_line(name);
+        }
+        signs.push(Sign {
+            x: point.x,
+            z: point.z,
+            toward: None,
+            lines,
+        });
+    }
+    signs
+}
+
+/// Places the planned signs at the nearest free spot off roads and buildings.
+/// Returns the number of signs placed.
+pub fn place_signs(
+    editor: &mut WorldEditor,
+    signs: Vec<Sign>,
+    road_mask: &CoordinateBitmap,
+    building_footprints: &CoordinateBitmap,
+) -> usize {
+    let (min_x, min_z) = editor.get_min_coords();
+    let (max_x, max_z) = editor.get_max_coords();
+    let mut placed = 0;
+    for sign in signs {
+        let is_free = |x: i32, z: i32| {
+            (min_x..=max_x).contains(&x)
+                && (min_z..=max_z).contains(&z)
+                && !road_mask.contains(x, z)
+                && !building_footprints.contains(x, z)
+                && !editor.block_at(x, 1, z)
+        };
+        // House numbers go at the street's edge, walking from the street to the address
+        let at_street = sign.toward.and_then(|(to_x, to_z)| {
+            bresenham_line(sign.x, 0, sign.z, to_x, 0, to_z)
+                .into_iter()
+                .map(|(x, _, z)| (x, z))
+                .find(|&(x, z)| is_free(x, z))
+        });
+        let Some((x, z)) = at_street.or_else(|| {
+            let (x, z) = sign.toward.unwrap_or((sign.x, sign.z));
+            free_spot(x, z, is_free)
+        }) else {
+            continue;
+        };
+        let [line1, line2, line3, line4] = sign.lines;
+        editor.set_sign(line1, line2, line3, line4, x, 1, z, 0);
+        placed += 1;
+    }
+    placed
+}
+
+/// Nearest position to (x, z), ring by ring up to `SIGN_SEARCH_RADIUS`, that is free.
+pub(crate) fn free_spot(x: i32, z: i32, is_free: impl Fn(i32, i32) -> bool) -> Option<(i32, i32)> {
+    (0..=SIGN_SEARCH_RADIUS).find_map(|radius| {
+        (-radius..=radius)
+            .flat_map(|dx| (-radius..=radius).map(move |dz| (dx, dz)))
+            .filter(|&(dx, dz)| dx.abs().max(dz.abs()) == radius)
+            .map(|(dx, dz)| (x + dx, z + dz))
+            .find(|&(x, z)| is_free(x, z))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_parser::ProcessedWay;
+    use crate::test_utilities::{node, tags};
+
+    fn street(id: u64, nodes: Vec<ProcessedNode>, name: Option<&str>) -> ProcessedElement {
+        let mut tags = HashMap::from([("highway".to_string(), "residential".to_string())]);
+        if let Some(name) = name {
+            tags.insert("name".to_string(), name.to_string());
+        }
+        ProcessedElement::Way(ProcessedWay { id, nodes, tags })
+    }
+
+    #[test]
+    fn parses_mini_addresses() {
+        let body = br#"[{"vejnavn":"Strandvejen","husnr":"12A","x":12.57,"y":55.68},
+                        {"vejnavn":"Uden nummer","x":12.5,"y":55.6}]"#;
+        let addresses = parse_addresses(body).unwrap();
+        assert_eq!(
+            addresses,
+            vec![Address {
+                street: "Strandvejen".to_string(),
+                house_number: "12A".to_string(),
+                lat: 55.68,
+                lon: 12.57,
+            }]
+        );
+        assert!(parse_addresses(b"{}").is_err());
+    }
+
+    #[test]
+    fn signs_name_intersections_from_osm_or_the_nearest_address() {
+        let crossing = node(10, 50, 50, &[]);
+        let elements = vec![
+            street(
+                1,
+                vec![node(1, 0, 50, &[]), crossing.clone(), node(2, 100, 50, &[])],
+                Some("Vestergade"),
+            ),
+            // Unnamed in OSM: takes the street of the address next to it
+            street(
+                2,
+                vec![node(3, 50, 0, &[]), crossing.clone(), node(4, 50, 100, &[])],
+                None,
+            ),
+            ProcessedElement::Node(node(
+                ADDRESS_IDS,
+                53,
+                48,
+                &[
+                    ("addr:street", "Kirkestræde"),
+                    ("addr:housenumber", "3"),
+                    (enrichment::SOURCE_KEY, SOURCE),
+                ],
+            )),
+        ];
+
+        let signs = plan_signs(&elements);
+        assert_eq!(signs.len(), 2);
+        assert_eq!(signs[0].lines[0], "3");
+        // The house number stands at the nearest street, facing the address
+        assert_eq!((signs[0].x, signs[0].z), (53, 50));
+        assert_eq!(signs[0].toward, Some((53, 48)));
+        assert_eq!((signs[1].x, signs[1].z), (50, 50));
+        assert_eq!(signs[1].lines[..2], ["Kirkestræde", "Vestergade"]);
+    }
+
+    #[test]
+    fn free_spot_searches_outwards() {
+        assert_eq!(free_spot(0, 0, |_, _| true), Some((0, 0)));
+        assert_eq!(
+            free_spot(0, 0, |x, z| x.abs() >= 2 && z == 0),
+            Some((-2, 0))
+        );
+        assert_eq!(free_spot(0, 0, |_, _| false), None);
+    }
+}
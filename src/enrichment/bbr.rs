@@ -0,0 +1,528 @@
+//! Building usages from BBR, the Danish building register (`--interiors`).
+//!
+//! OSM rarely says what a building is used for beyond `building=yes`, while BBR registers
+//! the usage of every building (`byg021BygningensAnvendelse`) together with a point inside
+//! its footprint (`byg404Koordinat`). The buildings in the bbox are fetched from the BBR
+//! service on Datafordeler, and every OSM building whose footprint holds a BBR point gets
+//! its usage code as `bbr:anvendelse` and the furnishing the code maps to as
+//! `bbr:furnishing`, which the interiors are furnished by. The wall material and roofing
+//! codes fill in `building:material` and `roof:material` where OSM has none. The code
+//! tables are in [`crate::bbr_codes`].
+//!
+//! The buildings of a bbox are kept in a cache with the latest registration time seen. A
+//! later run over the same bbox, such as a server regenerating its map, only downloads the
+//! records registered since then and merges them into the cache: changed buildings replace
+//! their old record, and buildings no longer standing are dropped. Only the download is
+//! incremental; the world is still enriched and generated from every cached building.
+
+use crate::bbr_codes::{CodeField, CodeTables};
+use crate::coordinate_system::cartesian::XZBBox;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::dhm::{utm32n_to_wgs84, wgs84_to_utm32n};
+use crate::enrichment::{self, nature::ring_contains};
+use crate::floodfill_cache::CoordinateBitmap;
+use crate::history;
+use crate::osm_parser::{ProcessedElement, ProcessedWay};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const BBR_URL: &str = "https://services.datafordeler.dk/BBR/BBRPublic/1/rest/bygning";
+/// Tag the usage code is stored in
+pub const USAGE_TAG: &str = "bbr:anvendelse";
+/// Tag the furnishing the usage code maps to is stored in, one of
+/// [`crate::bbr_codes::FURNISHINGS`]
+pub const FURNISHING_TAG: &str = "bbr:furnishing";
+/// Buildings per page of the BBR response
+const PAGE_SIZE: usize = 1000;
+/// Pages fetched at most, so a bbox over a whole city stays a bounded download
+const MAX_PAGES: usize = 100;
+/// BBR status of a building that stands (`Bygning opført`)
+const STATUS_BUILT: &str = "6";
+/// Cache directory used when the platform has no cache directory
+const FALLBACK_CACHE_DIR: &str = "./arnis-bbr-cache";
+
+/// A registered building: its BBR id, usage code, wall material and roofing codes where
+/// registered, and a point inside it as (lat, lng)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BbrBuilding {
+    pub id: String,
+    pub usage: u16,
+    pub wall_material: Option<u16>,
+    pub roof_material: Option<u16>,
+    pub point: (f64, f64),
+}
+
+/// A building record as BBR returns it. Records of buildings that were demolished or are
+/// only planned have `standing` false, and may lack a usage or position.
+#[derive(Clone, Debug, PartialEq)]
+struct BbrRecord {
+    id: String,
+    standing: bool,
+    usage: Option<u16>,
+    wall_material: Option<u16>,
+    roof_material: Option<u16>,
+    point: Option<(f64, f64)>,
+    /// Registration time (`registreringFra`)
+    registered: Option<DateTime<FixedOffset>>,
+}
+
+/// The standing buildings of a bbox as of the latest registration time seen
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct BbrCache {
+    synced_until: Option<DateTime<FixedOffset>>,
+    buildings: Vec<BbrBuilding>,
+}
+
+impl BbrCache {
+    /// Merges newer records in the order they were registered: each replaces the building
+    /// with its id, and buildings that no longer stand are dropped. Returns the number of
+    /// buildings changed or dropped.
+    fn merge(&mut self, mut records: Vec<BbrRecord>) -> usize {
+        // A building registered more than once since the last run ends up as its latest
+        // version, whatever order BBR returned them in
+        records.sort_by_key(|record| record.registered);
+        let mut buildings: HashMap<String, BbrBuilding> = self
+            .buildings
+            .drain(..)
+            .map(|building| (building.id.clone(), building))
+            .collect();
+        let mut changed = 0;
+        for record in records {
+            if record.registered > self.synced_until {
+                self.synced_until = record.registered;
+            }
+            let removed = buildings.remove(&record.id).is_some();
+            match (record.standing, record.usage, record.point) {
+                (true, Some(usage), Some(point)) => {
+                    let building = BbrBuilding {
+                        id: record.id.clone(),
+                        usage,
+                        wall_material: record.wall_material,
+                        roof_material: record.roof_material,
+                        point,
+                    };
+                    buildings.insert(record.id, building);
+                    changed += 1;
+                }
+                _ => changed += usize::from(removed),
+            }
+        }
+        self.buildings = buildings.into_values().collect();
+        // By id, so footprints holding several buildings pick the same one on every run
+        self.buildings.sort_by(|a, b| a.id.cmp(&b.id));
+        changed
+    }
+}
+
+/// Cache file of the buildings of `bbox`
+fn cache_path(bbox: &LLBBox) -> PathBuf {
+    let dir = dirs::cache_dir()
+        .map(|dir| dir.join("arnis").join("bbr"))
+        .unwrap_or_else(|| PathBuf::from(FALLBACK_CACHE_DIR));
+    dir.join(format!(
+        "bbr_{:.5}_{:.5}_{:.5}_{:.5}.json",
+        bbox.min().lat(),
+        bbox.min().lng(),
+        bbox.max().lat(),
+        bbox.max().lng()
+    ))
+}
+
+fn read_cache(path: &Path) -> Option<BbrCache> {
+    serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+}
+
+fn write_cache(path: &Path, cache: &BbrCache) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create BBR cache: {e}"))?;
+    }
+    let json = serde_json::to_vec(cache).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write BBR cache: {e}"))
+}
+
+/// Fetches the standing buildings inside the bbox from BBR. With a cache of the bbox from
+/// an earlier run, only the records registered since are fetched and merged into it. A
+/// fetch cut short at [`MAX_PAGES`] is used for this run but not cached, as the records
+/// left out would never be fetched again.
+pub fn fetch_buildings(bbox: &LLBBox, token: &str) -> Result<Vec<BbrBuilding>, String> {
+    let path = cache_path(bbox);
+    let mut cache = read_cache(&path).unwrap_or_default();
+    let synced_until = cache.synced_until;
+    let (records, truncated) = match synced_until {
+        Some(since) => {
+            println!("Fetching building usages registered in BBR since {since}...");
+            fetch_records(bbox, token, Some(since))?
+        }
+        None => {
+            println!("Fetching building usages from BBR...");
+            fetch_records(bbox, token, None)?
+        }
+    };
+    let changed = cache.merge(records);
+    if synced_until.is_some() {
+        println!("BBR: {changed} buildings changed since the last run");
+    }
+    if truncated {
+        cache.synced_until = synced_until;
+    } else if let Err(e) = write_cache(&path, &cache) {
+        eprintln!("BBR: {e}; the next run fetches every building again.");
+    }
+    Ok(cache.buildings)
+}
+
+/// Fetches the building records inside the bbox page by page: the standing buildings, or
+/// with `since` every record registered after it, demolitions included. Also returns
+/// whether the fetch stopped at [`MAX_PAGES`] with records left.
+fn fetch_records(
+    bbox: &LLBBox,
+    token: &str,
+    since: Option<DateTime<FixedOffset>>,
+) -> Result<(Vec<BbrRecord>, bool), String> {
+    let (min_e, min_n) = wgs84_to_utm32n(bbox.min().lat(), bbox.min().lng());
+    let (max_e, max_n) = wgs84_to_utm32n(bbox.max().lat(), bbox.max().lng());
+    let polygon = format!(
+        "POLYGON(({min_e} {min_n},{max_e} {min_n},{max_e} {max_n},{min_e} {max_n},{min_e} {min_n}))"
+    );
+    let filter = match since {
+        // A `+` in the time's UTC offset would read as a space
+        Some(since) => format!(
+            "RegistreringstidFra={}",
+            since.to_rfc3339().replace('+', "%2B")
+        ),
+        None => format!("Status={STATUS_BUILT}"),
+    };
+
+    let mut records = Vec::new();
+    for page in 1..=MAX_PAGES {
+        let request =
+            format!("Format=JSON&{filter}&Polygon={polygon}&PageSize={PAGE_SIZE}&Page={page}");
+        let bytes = enrichment::download(
+            "BBR",
+            "bbr-buildings",
+            &format!("{BBR_URL}?{request}&apiKey={token}"),
+            &format!("{BBR_URL}?{request}"),
+        )?;
+        let parsed = parse_records(&bytes)?;
+        let full_page = parsed.len() == PAGE_SIZE;
+        records.extend(parsed);
+        if !full_page {
+            return Ok((records, false));
+        }
+    }
+    eprintln!(
+        "BBR: more than {} buildings in the bbox; the rest keep their OSM tags.",
+        MAX_PAGES * PAGE_SIZE
+    );
+    Ok((records, true))
+}
+
+/// Parses a page of BBR building records. Positions are WKT points in UTM32N; records
+/// without an id are skipped.
+fn parse_records(bytes: &[u8]) -> Result<Vec<BbrRecord>, String> {
+    let json: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid BBR response: {e}"))?;
+    let records = json
+        .as_array()
+        .ok_or_else(|| "Invalid BBR response: expected a list of buildings".to_string())?;
+    Ok(records
+        .iter()
+        .filter_map(|record| {
+            let text = |key: &str| record.get(key).and_then(|v| v.as_str()).map(str::trim);
+            let code = |key: &str| text(key).and_then(|code| code.parse().ok());
+            Some(BbrRecord {
+                id: text("id_lokalId")?.to_string(),
+                standing: text("status") == Some(STATUS_BUILT),
+                usage: code("byg021BygningensAnvendelse"),
+                wall_material: code("byg032YdervæggensMateriale"),
+                roof_material: code("byg033Tagdækningsmateriale"),
+                point: text("byg404Koordinat")
+                    .and_then(wkt_point)
+                    .map(|(easting, northing)| utm32n_to_wgs84(easting, northing)),
+                registered: text("registreringFra")
+                    .and_then(|time| DateTime::parse_from_rfc3339(time).ok()),
+            })
+        })
+        .collect())
+}
+
+/// Easting and northing of a WKT `POINT(x y)`
+fn wkt_point(wkt: &str) -> Option<(f64, f64)> {
+    let inner = wkt.trim().strip_prefix("POINT")?.trim();
+    let inner = inner.strip_prefix('(')?.strip_suffix(')')?;
+    let mut parts = inner.split_whitespace().map(str::parse::<f64>);
+    Some((parts.next()?.ok()?, parts.next()?.ok()?))
+}
+
+/// Closed outline of a building way and its extent (min x, max x, min z, max z)
+fn footprint(way: &ProcessedWay) -> Option<(Vec<(i32, i32)>, (i32, i32, i32, i32))> {
+    if !way.tags.contains_key("building") {
+        return None;
+    }
+    let ring: Vec<(i32, i32)> = way.nodes.iter().map(|node| (node.x, node.z)).collect();
+    if ring.len() < 4 || ring.first() != ring.last() {
+        return None;
+    }
+    let (min_x, max_x) = ring.iter().fold((i32::MAX, i32::MIN), |(lo, hi), &(x, _)| {
+        (lo.min(x), hi.max(x))
+    });
+    let (min_z, max_z) = ring.iter().fold((i32::MAX, i32::MIN), |(lo, hi), &(_, z)| {
+        (lo.min(z), hi.max(z))
+    });
+    Some((ring, (min_x, max_x, min_z, max_z)))
+}
+
+/// The footprints of the OSM buildings in `xzbbox`, and the part of them given a BBR usage,
+/// for the coverage map
+pub fn matched_footprints(
+    elements: &[ProcessedElement],
+    xzbbox: &XZBBox,
+) -> (CoordinateBitmap, CoordinateBitmap) {
+    let mut footprints = CoordinateBitmap::new(xzbbox);
+    let mut matched = CoordinateBitmap::new(xzbbox);
+    for element in elements {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        let Some((ring, (min_x, max_x, min_z, max_z))) = footprint(way) else {
+            continue;
+        };
+        let is_matched = way.tags.contains_key(USAGE_TAG);
+        for x in min_x.max(xzbbox.min_x())..=max_x.min(xzbbox.max_x()) {
+            for z in min_z.max(xzbbox.min_z())..=max_z.min(xzbbox.max_z()) {
+                if ring_contains(&ring, x as f64, z as f64) {
+                    footprints.set(x, z);
+                    if is_matched {
+                        matched.set(x, z);
+                    }
+                }
+            }
+        }
+    }
+    (footprints, matched)
+}
+
+/// Tags the OSM buildings holding a BBR point with its usage code, the furnishing the code
+/// maps to and the wall and roof materials OSM does not tag, mapped with `codes`. Buildings
+/// that already have a usage code are left alone. Where a footprint holds several
+/// registered buildings, such as a terrace, the one with the lowest BBR id decides. Codes
+/// without a mapping are counted in the run history. Returns the number of buildings
+/// tagged.
+pub fn apply_building_data(
+    elements: &mut [ProcessedElement],
+    buildings: &[BbrBuilding],
+    transformer: &CoordTransformer,
+    codes: &CodeTables,
+) -> usize {
+    // Points sorted along x, so each footprint only looks at the points within its extent
+    let mut points: Vec<(i32, i32, &BbrBuilding)> = buildings
+        .iter()
+        .filter_map(|building| {
+            let (lat, lng) = building.point;
+            let point = transformer.transform_point(LLPoint::new(lat, lng).ok()?);
+            Some((point.x, point.z, building))
+        })
+        .collect();
+    points.sort_by_key(|&(x, _, _)| x);
+
+    let mut tagged = 0;
+    for element in elements.iter_mut() {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        if way.tags.contains_key(USAGE_TAG) {
+            continue;
+        }
+        let Some((ring, (min_x, max_x, min_z, max_z))) = footprint(way) else {
+            continue;
+        };
+        let first = points.partition_point(|&(x, _, _)| x < min_x);
+        let Some(&(_, _, building)) = points[first..]
+            .iter()
+            .take_while(|&&(x, _, _)| x <= max_x)
+            .find(|&&(x, z, _)| {
+                (min_z..=max_z).contains(&z) && ring_contains(&ring, x as f64, z as f64)
+            })
+        else {
+            continue;
+        };
+
+        way.tags
+            .insert(USAGE_TAG.to_string(), building.usage.to_string());
+        let fields = [
+            (CodeField::Usage, Some(building.usage), FURNISHING_TAG),
+            (
+                CodeField::WallMaterial,
+                building.wall_material,
+                "building:material",
+            ),
+            (
+                CodeField::RoofMaterial,
+                building.roof_material,
+                "roof:material",
+            ),
+        ];
+        for (field, code, key) in fields {
+            let Some(code) = code else {
+                continue;
+            };
+            match codes.value(field, code) {
+                Some(value) => {
+                    way.tags
+                        .entry(key.to_string())
+                        .or_insert_with(|| value.to_string());
+                }
+                None => history::record_counted(&field.unmapped_stat(code), [("way", way.id)]),
+            }
+        }
+        tagged += 1;
+    }
+    tagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_parser::{ProcessedNode, ProcessedWay};
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn parses_usage_position_and_status() {
+        let body = r#"[
+            {"id_lokalId": "a", "status": "6", "byg021BygningensAnvendelse": "322",
+             "byg032YdervæggensMateriale": "1", "byg404Koordinat": "POINT(575000.5 6223000.25)",
+             "registreringFra": "2024-03-01T10:00:00.123+01:00"},
+            {"id_lokalId": "b", "status": "10", "byg021BygningensAnvendelse": "120"},
+            {"byg404Koordinat": "POINT(575000 6223000)"}
+        ]"#;
+        let records = parse_records(body.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].standing, records[0].usage), (true, Some(322)));
+        assert_eq!(
+            (records[0].wall_material, records[0].roof_material),
+            (Some(1), None)
+        );
+        let (lat, lng) = records[0].point.unwrap();
+        assert!((56.0..56.3).contains(&lat) && (10.0..10.4).contains(&lng));
+        assert_eq!(
+            records[0].registered,
+            DateTime::parse_from_rfc3339("2024-03-01T09:00:00.123Z").ok()
+        );
+        assert!(!records[1].standing && records[1].point.is_none());
+        assert!(parse_records(b"{}").is_err());
+        assert_eq!(wkt_point("POINT (1.5 2)"), Some((1.5, 2.0)));
+    }
+
+    #[test]
+    fn newer_records_replace_and_drop_cached_buildings() {
+        let time = |time: &str| DateTime::parse_from_rfc3339(time).unwrap();
+        let record = |id: &str, standing, usage, registered: &str| BbrRecord {
+            id: id.to_string(),
+            standing,
+            usage: Some(usage),
+            wall_material: None,
+            roof_material: None,
+            point: Some((56.0, 10.0)),
+            registered: Some(time(registered)),
+        };
+        let mut cache = BbrCache::default();
+        cache.merge(vec![
+            record("a", true, 120, "2024-01-01T00:00:00Z"),
+            record("b", true, 322, "2024-02-01T00:00:00Z"),
+            record("c", true, 414, "2024-01-15T00:00:00Z"),
+        ]);
+        assert_eq!(cache.synced_until, Some(time("2024-02-01T00:00:00Z")));
+
+        // "a" becomes a shop, "b" is demolished and "d" is built. The versions of "a" are
+        // returned newest first, and times are compared as instants, not as text.
+        let changed = cache.merge(vec![
+            record("a", true, 322, "2024-03-01T00:00:00.5Z"),
+            record("a", true, 130, "2024-03-01T00:00:00Z"),
+            record("b", false, 322, "2024-03-02T00:00:00Z"),
+            record("d", true, 421, "2024-03-03T00:00:00Z"),
+            record("c", true, 414, "2024-03-03T01:30:00+02:00"),
+        ]);
+        assert_eq!(changed, 5);
+        let usages: Vec<(&str, u16)> = cache
+            .buildings
+            .iter()
+            .map(|building| (building.id.as_str(), building.usage))
+            .collect();
+        assert_eq!(usages, [("a", 322), ("c", 414), ("d", 421)]);
+        assert_eq!(cache.synced_until, Some(time("2024-03-03T00:00:00Z")));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bbr.json");
+        write_cache(&path, &cache).unwrap();
+        assert_eq!(read_cache(&path), Some(cache));
+    }
+
+    #[test]
+    fn buildings_take_the_usage_of_the_point_inside() {
+        let bbox = LLBBox::new(56.0, 10.0, 56.01, 10.01).unwrap();
+        let (transformer, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&bbox, 1.0).unwrap();
+        let point = transformer.transform_point(LLPoint::new(56.005, 10.005).unwrap());
+        let square = |id: u64, x: i32, pairs: &[(&str, &str)]| {
+            let z = point.z;
+            let corners = [
+                (x - 5, z - 5),
+                (x + 5, z - 5),
+                (x + 5, z + 5),
+                (x - 5, z + 5),
+            ];
+            ProcessedElement::Way(ProcessedWay {
+                id,
+                nodes: corners
+                    .into_iter()
+                    .chain([corners[0]])
+                    .enumerate()
+                    .map(|(i, (x, z))| ProcessedNode {
+                        id: id * 10 + (i % 4) as u64,
+                        tags: Default::default(),
+                        x,
+                        z,
+                        exact: None,
+                    })
+                    .collect(),
+                tags: tags(pairs),
+            })
+        };
+        let mut elements = vec![
+            square(
+                1,
+                point.x,
+                &[("building", "yes"), ("roof:material", "slate")],
+            ),
+            square(2, point.x + 50, &[("building", "yes")]),
+            square(3, point.x, &[("building", "yes"), (USAGE_TAG, "120")]),
+        ];
+        let buildings = [BbrBuilding {
+            id: "a".to_string(),
+            usage: 322,
+            wall_material: Some(1),
+            roof_material: Some(5),
+            point: (56.005, 10.005),
+        }];
+
+        let codes = CodeTables::built_in();
+        assert_eq!(
+            apply_building_data(&mut elements, &buildings, &transformer, &codes),
+            1
+        );
+        let shop = elements[0].tags();
+        assert_eq!(shop.get(USAGE_TAG).unwrap(), "322");
+        assert_eq!(shop.get(FURNISHING_TAG).unwrap(), "shop");
+        assert_eq!(shop.get("building:material").unwrap(), "brick");
+        // OSM's own roof material stays
+        assert_eq!(shop.get("roof:material").unwrap(), "slate");
+        assert!(elements[1].tags().get(USAGE_TAG).is_none());
+        assert_eq!(elements[2].tags().get(USAGE_TAG).unwrap(), "120");
+
+        // The second square is left without a usage
+        let (footprints, matched) = matched_footprints(&elements[..2], &xzbbox);
+        assert!(footprints.contains(point.x + 50, point.z) && matched.contains(point.x, point.z));
+        assert!(!matched.contains(point.x + 50, point.z));
+    }
+}
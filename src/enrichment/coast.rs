@@ -0,0 +1,268 @@
+//! Precise coastline from GeoDanmark (`--geodanmark-coast`).
+//!
+//! OSM coastlines in Denmark are sometimes traced coarsely, cutting off small harbour
+//! basins or running straight across breakwaters. GeoDanmark maps the coast to within a
+//! metre. The coastline, harbour basins and breakwaters (moles) in the bbox are fetched from
+//! the GeoDanmark WFS on Dataforsyningen, and the coastline replaces the OSM coastline ways,
+//! so the ocean fill decides land and sea from it block by block. Harbour basins are added
+//! as harbour water and breakwaters as `man_made=breakwater`. GeoDanmark digitises the coast
+//! with the land on the left, as OSM does.
+
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, SyntheticIds, COAST_IDS};
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
+use std::collections::HashMap;
+
+const GEODANMARK_WFS_URL: &str = "https://api.dataforsyningen.dk/GeoDanmark60_NOHIST_GML3_DAF";
+/// Source of the coast ways
+const SOURCE: &str = "GeoDanmark";
+
+/// Kind of a GeoDanmark coast feature
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoastKind {
+    Coastline,
+    HarbourBasin,
+    Breakwater,
+}
+
+impl CoastKind {
+    /// GeoDanmark layer of the kind
+    fn layer(self) -> &'static str {
+        match self {
+            CoastKind::Coastline => "gdk60:Kyst",
+            CoastKind::HarbourBasin => "gdk60:Havnebassin",
+            CoastKind::Breakwater => "gdk60:Bygvaerk",
+        }
+    }
+
+    /// OSM tags rendering the kind
+    fn osm_tags(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            CoastKind::Coastline => &[("natural", "coastline")],
+            CoastKind::HarbourBasin => &[("natural", "water"), ("water", "harbour")],
+            CoastKind::Breakwater => &[("man_made", "breakwater")],
+        }
+    }
+}
+
+/// A coast feature: its kind and its line, or the outer ring of a polygon, as (lat, lng)
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoastFeature {
+    pub kind: CoastKind,
+    pub line: Vec<(f64, f64)>,
+}
+
+/// Fetches the coastline, harbour basins and breakwaters inside the bbox from GeoDanmark.
+pub fn fetch_coast(bbox: &LLBBox, token: &str) -> Result<Vec<CoastFeature>, String> {
+    println!("Fetching the GeoDanmark coastline...");
+    let (min, max) = (bbox.min(), bbox.max());
+
+    let mut features = Vec::new();
+    for kind in [
+        CoastKind::Coastline,
+        CoastKind::HarbourBasin,
+        CoastKind::Breakwater,
+    ] {
+        let request = format!(
+            "service=WFS&version=2.0.0&request=GetFeature&typeNames={}\
+             &outputFormat=application/json&srsName=EPSG:4326\
+             &bbox={},{},{},{},urn:ogc:def:crs:EPSG::4326",
+            kind.layer(),
+            min.lat(),
+            min.lng(),
+            max.lat(),
+            max.lng()
+        );
+        let bytes = enrichment::download(
+            SOURCE,
+            "geodanmark-coast",
+            &format!("{GEODANMARK_WFS_URL}?{request}&token={token}"),
+            &format!("GeoDanmark60_NOHIST_GML3_DAF {request}"),
+        )?;
+        features.extend(parse_coast(kind, &bytes)?);
+    }
+    Ok(features)
+}
+
+/// Parses a GeoJSON feature collection of one layer. Of the structures, only moles and
+/// breakwaters are kept.
+fn parse_coast(kind: CoastKind, bytes: &[u8]) -> Result<Vec<CoastFeature>, String> {
+    let mut coast = Vec::new();
+    for feature in enrichment::geojson_features(bytes, SOURCE)? {
+        if kind == CoastKind::Breakwater {
+            let structure = feature
+                .get("properties")
+                .and_then(|p| p.get("bygvaerkstype"))
+                .and_then(|t| t.as_str())
+                .unwrap_or_default()
+                .to_lowercase();
+            if !structure.contains("mole") && !structure.contains("bølgebryder") {
+                continue;
+            }
+        }
+        let Some(geometry) = feature.get("geometry") else {
+            continue;
+        };
+        let Some(coordinates) = geometry.get("coordinates") else {
+            continue;
+        };
+        let Some(parts) = coordinates.as_array() else {
+            continue;
+        };
+        let lines: Vec<&serde_json::Value> = match geometry.get("type").and_then(|t| t.as_str()) {
+            Some("LineString") => vec![coordinates],
+            Some("MultiLineString") => parts.iter().collect(),
+            Some("Polygon") => parts.first().into_iter().collect(),
+            Some("MultiPolygon") => parts
+                .iter()
+                .filter_map(|polygon| polygon.as_array()?.first())
+                .collect(),
+            _ => continue,
+        };
+        coast.extend(
+            lines
+                .into_iter()
+                .filter_map(enrichment::geojson_line)
+                .filter(|line| line.len() >= 2)
+                .map(|line| CoastFeature { kind, line }),
+        );
+    }
+    Ok(coast)
+}
+
+/// Adds the coast features to the elements as ways. When GeoDanmark has coastline in the
+/// bbox, the OSM coastline ways are removed first. Returns the number of ways added and of
+/// OSM coastline ways replaced.
+pub fn apply_coast(
+    elements: &mut Vec<ProcessedElement>,
+    features: &[CoastFeature],
+    transformer: &CoordTransformer,
+) -> (usize, usize) {
+    let mut replaced = 0;
+    if features.iter().any(|f| f.kind == CoastKind::Coastline) {
+        let before = elements.len();
+        elements.retain(|element| {
+            !matches!(element, ProcessedElement::Way(way)
+                if way.tags.get("natural").is_some_and(|v| v == "coastline"))
+        });
+        replaced = before - elements.len();
+    }
+
+    let mut ids = SyntheticIds::new(COAST_IDS);
+    let mut added = 0;
+    for feature in features {
+        let mut nodes: Vec<ProcessedNode> = feature
+            .line
+            .iter()
+            .filter_map(|&(lat, lng)| {
+                let point = transformer.transform_point(LLPoint::new(lat, lng).ok()?);
+                Some(ProcessedNode {
+                    id: ids.next_id(),
+                    tags: HashMap::new(),
+                    x: point.x,
+                    z: point.z,
+                    exact: None,
+                })
+            })
+            .collect();
+        nodes.dedup_by_key(|node| (node.x, node.z));
+        if nodes.len() < 2 {
+            continue;
+        }
+        // Close rings on their first node, as OSM areas are
+        let closed = feature.line.first() == feature.line.last();
+        if closed {
+            nodes.pop();
+            if nodes.len() < 3 {
+                continue;
+            }
+            nodes.push(nodes[0].clone());
+        }
+
+        let mut tags: HashMap<String, String> = feature
+            .kind
+            .osm_tags()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        tags.extend([enrichment::source_tag(SOURCE)]);
+        elements.push(ProcessedElement::Way(ProcessedWay {
+            id: ids.next_id(),
+            nodes,
+            tags,
+        }));
+        added += 1;
+    }
+    (added, replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_coast_layers() {
+        let coastline = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{},
+             "geometry":{"type":"LineString","coordinates":[[10.0,56.0],[10.01,56.0]]}}]}"#;
+        let features = parse_coast(CoastKind::Coastline, coastline.as_bytes()).unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].line[1], (56.0, 10.01));
+
+        let structures = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{"bygvaerkstype":"Mole"},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[10.0,56.0],[10.001,56.0],[10.001,56.001],[10.0,56.0]]]}},
+            {"type":"Feature","properties":{"bygvaerkstype":"Bro"},
+             "geometry":{"type":"LineString","coordinates":[[10.0,56.0],[10.01,56.0]]}}]}"#;
+        let features = parse_coast(CoastKind::Breakwater, structures.as_bytes()).unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].line.len(), 4);
+    }
+
+    #[test]
+    fn replaces_osm_coastline() {
+        let bbox = LLBBox::new(56.0, 10.0, 56.01, 10.01).unwrap();
+        let (transformer, _) = CoordTransformer::llbbox_to_xzbbox(&bbox, 1.0).unwrap();
+        let osm_way = |id, key: &str, value: &str| {
+            ProcessedElement::Way(ProcessedWay {
+                id,
+                nodes: vec![],
+                tags: HashMap::from([(key.to_string(), value.to_string())]),
+            })
+        };
+        let mut elements = vec![
+            osm_way(1, "natural", "coastline"),
+            osm_way(2, "highway", "residential"),
+        ];
+        let features = [
+            CoastFeature {
+                kind: CoastKind::Coastline,
+                line: vec![(56.005, 10.0), (56.005, 10.01)],
+            },
+            CoastFeature {
+                kind: CoastKind::HarbourBasin,
+                line: vec![
+                    (56.001, 10.001),
+                    (56.001, 10.003),
+                    (56.003, 10.003),
+                    (56.001, 10.001),
+                ],
+            },
+        ];
+
+        assert_eq!(apply_coast(&mut elements, &features, &transformer), (2, 1));
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0].id(), 2);
+        assert_eq!(elements[1].tags().get("natural").unwrap(), "coastline");
+        let ProcessedElement::Way(basin) = &elements[2] else {
+            panic!("harbour basin is not a way");
+        };
+        assert_eq!(
+            basin.nodes.first().unwrap().id,
+            basin.nodes.last().unwrap().id
+        );
+        assert_eq!(basin.tags.get("water").unwrap(), "harbour");
+    }
+}
@@ -0,0 +1,209 @@
+//! Field crops from the national field parcel map, Markkort (`--field-crops`).
+//!
+//! Landbrugsstyrelsen publishes every farmer's field (mark) with the crop declared for the
+//! year. The fields in the bbox are fetched from the Markkort WFS and added to the elements
+//! as `landuse=farmland` ways with a `crop` tag, so each field is planted with its actual
+//! crop instead of the generic mix: wheat and barley, yellow flowering rape, tall maize,
+//! potatoes, beets and grass leys.
+
+use crate::coordinate_system::geographic::LLBBox;
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, SyntheticIds, FIELD_IDS};
+use crate::osm_parser::{ProcessedElement, ProcessedWay};
+use std::collections::HashMap;
+
+const MARKKORT_WFS_URL: &str = "https://geodata.fvm.dk/geoserver/ows";
+/// Source of the field ways
+pub const SOURCE: &str = "Markkort";
+/// Prefix of the WFS layer names; each crop year is its own layer, e.g. `Marker:Marker_2024`
+const LAYER_PREFIX: &str = "Marker:Marker_";
+
+/// Crop of a field, as the OSM `crop` value and, for cereals, the sowing season
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub osm: &'static str,
+    pub season: Option<&'static str>,
+}
+
+/// Crop of a Markkort `afgroede` name, e.g. "Vinterhvede", "Vårbyg", "Silomajs"
+pub fn crop_of(name: &str) -> Option<Crop> {
+    let name = name.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| name.contains(word));
+    let crop = |osm| Some(Crop { osm, season: None });
+    if has(&["raps"]) {
+        crop("rape")
+    } else if has(&["majs"]) {
+        crop("maize")
+    } else if has(&["kartof"]) {
+        crop("potato")
+    } else if has(&["gulerød"]) {
+        crop("carrot")
+    } else if has(&["roer"]) {
+        crop("sugar_beet")
+    } else if has(&["hvede", "byg", "rug", "havre", "triticale", "spelt"]) {
+        Some(Crop {
+            osm: "cereal",
+            season: Some(if name.starts_with("vår") {
+                "spring"
+            } else {
+                "winter"
+            }),
+        })
+    } else if has(&["græs", "kløver"]) {
+        crop("grass")
+    } else {
+        None
+    }
+}
+
+/// Markkort layer of the crop `year`, or of the latest year the WFS lists
+fn markkort_layer(year: Option<u16>) -> Result<String, String> {
+    if let Some(year) = year {
+        return Ok(format!("{LAYER_PREFIX}{year}"));
+    }
+    let url = format!("{MARKKORT_WFS_URL}?service=WFS&version=2.0.0&request=GetCapabilities");
+    let bytes = enrichment::download(SOURCE, "field-crops-capabilities", &url, &url)?;
+    let year = latest_year(&String::from_utf8_lossy(&bytes))
+        .ok_or_else(|| format!("{SOURCE} lists no crop years"))?;
+    println!("Using the {SOURCE} crop year {year}");
+    Ok(format!("{LAYER_PREFIX}{year}"))
+}
+
+/// The latest crop year among the layers named in a WFS capabilities document
+fn latest_year(capabilities: &str) -> Option<u16> {
+    capabilities
+        .split(LAYER_PREFIX)
+        .skip(1)
+        .filter_map(|rest| rest.get(..4)?.parse().ok())
+        .max()
+}
+
+/// A field with its crop and outer ring as (lat, lng)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub crop: Crop,
+    pub ring: Vec<(f64, f64)>,
+}
+
+/// Fetches the fields inside the bbox from Markkort, with their crops of `year` or of the
+/// latest year published.
+pub fn fetch_fields(bbox: &LLBBox, year: Option<u16>) -> Result<Vec<Field>, String> {
+    println!("Fetching field crops from Markkort...");
+    let layer = markkort_layer(year)?;
+    let (min, max) = (bbox.min(), bbox.max());
+    let url = format!(
+        "{MARKKORT_WFS_URL}?service=WFS&version=2.0.0&request=GetFeature&typeNames={}\
+         &outputFormat=application/json&srsName=EPSG:4326\
+         &bbox={},{},{},{},urn:ogc:def:crs:EPSG::4326",
+        layer,
+        min.lat(),
+        min.lng(),
+        max.lat(),
+        max.lng()
+    );
+    let bytes = enrichment::download(SOURCE, "field-crops", &url, &url)?;
+    parse_fields(&bytes)
+}
+
+/// Parses a GeoJSON feature collection of fields; fields with unknown crops are skipped.
+fn parse_fields(bytes: &[u8]) -> Result<Vec<Field>, String> {
+    let mut fields = Vec::new();
+    for feature in enrichment::geojson_features(bytes, SOURCE)? {
+        let properties = feature.get("properties");
+        let Some(crop) = properties
+            .and_then(|p| p.get("Afgroede").or_else(|| p.get("afgroede")))
+            .and_then(|name| name.as_str())
+            .and_then(crop_of)
+        else {
+            continue;
+        };
+        let Some(geometry) = feature.get("geometry") else {
+            continue;
+        };
+        fields.extend(
+            enrichment::outer_rings(geometry)
+                .into_iter()
+                .map(|ring| Field { crop, ring }),
+        );
+    }
+    Ok(fields)
+}
+
+/// Farmland ways for the fields, projected like the OSM data.
+pub fn field_ways(fields: &[Field], transformer: &CoordTransformer) -> Vec<ProcessedElement> {
+    let mut ids = SyntheticIds::new(FIELD_IDS);
+    fields
+        .iter()
+        .filter_map(|field| {
+            let nodes = enrichment::project_ring(&field.ring, transformer, &mut ids)?;
+            let mut tags = HashMap::from([
+                ("landuse".to_string(), "farmland".to_string()),
+                ("crop".to_string(), field.crop.osm.to_string()),
+                enrichment::source_tag(SOURCE),
+            ]);
+            if let Some(season) = field.crop.season {
+                tags.insert("crop:season".to_string(), season.to_string());
+            }
+            Some(ProcessedElement::Way(ProcessedWay {
+                id: ids.next_id(),
+                nodes,
+                tags,
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_markkort_crop_names() {
+        assert_eq!(crop_of("Vinterraps").map(|c| c.osm), Some("rape"));
+        assert_eq!(crop_of("Majs til modenhed").map(|c| c.osm), Some("maize"));
+        assert_eq!(
+            crop_of("Kartofler, stivelses-").map(|c| c.osm),
+            Some("potato")
+        );
+        assert_eq!(
+            crop_of("Sukkerroer til fabrik").map(|c| c.osm),
+            Some("sugar_beet")
+        );
+        assert_eq!(
+            crop_of("Vinterhvede"),
+            Some(Crop {
+                osm: "cereal",
+                season: Some("winter")
+            })
+        );
+        assert_eq!(crop_of("Vårbyg").and_then(|c| c.season), Some("spring"));
+        assert_eq!(
+            crop_of("Permanent græs, normalt udbytte").map(|c| c.osm),
+            Some("grass")
+        );
+        assert_eq!(crop_of("Juletræer"), None);
+    }
+
+    #[test]
+    fn finds_the_latest_crop_year() {
+        let capabilities = "<Name>Marker:Marker_2022</Name><Name>Marker:Marker_2024</Name>\
+                            <Name>Marker:Marker_2023</Name><Name>Marker:Marker_x</Name>";
+        assert_eq!(latest_year(capabilities), Some(2024));
+        assert_eq!(latest_year("<Name>Blokke:Blokke</Name>"), None);
+    }
+
+    #[test]
+    fn parses_fields() {
+        let body = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{"Afgroede":"Vårbyg"},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[9.0,56.0],[9.01,56.0],[9.01,56.01],[9.0,56.0]]]}},
+            {"type":"Feature","properties":{"Afgroede":"Juletræer"},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[9.1,56.0],[9.11,56.0],[9.11,56.01],[9.1,56.0]]]}}]}"#;
+        let fields = parse_fields(body.as_bytes()).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].crop.season, Some("spring"));
+        assert_eq!(fields[0].ring[1], (56.0, 9.01));
+    }
+}
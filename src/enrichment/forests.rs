@@ -0,0 +1,188 @@
+//! Forest types from the Miljøstyrelsen forest map (`--forest-types`).
+//!
+//! Most OSM woods in Denmark carry no `leaf_type`, so every forest would get the same
+//! generic tree mix. Miljøstyrelsen maps each forest as deciduous, coniferous or mixed, with
+//! its main tree species. The forest types in the bbox are fetched from the MiljøGIS WFS and
+//! copied onto the OSM forest and wood polygons they cover as `leaf_type` and `genus`, so
+//! beech woods, oak woods and spruce plantations each get their own trees. Polygons that
+//! already have a `leaf_type` in OSM keep it.
+
+use crate::coordinate_system::cartesian::XZPoint;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, nature::ring_contains};
+use crate::osm_parser::ProcessedElement;
+
+const MILJOEGIS_WFS_URL: &str = "https://wfs2-miljoegis.mim.dk/skovdata/ows";
+/// Source of the forest types
+const SOURCE: &str = "Miljøstyrelsen forest map";
+
+/// Type of a mapped forest, as OSM `leaf_type` and the `genus` of its main species
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForestType {
+    pub leaf_type: &'static str,
+    pub genus: Option<&'static str>,
+}
+
+impl ForestType {
+    /// Forest type of the `skovtype` ("Løvskov", "Nåleskov", "Blandskov") and
+    /// `hovedtraeart` (main species, e.g. "Bøg", "Rødgran") attributes
+    pub fn from_names(kind: &str, species: Option<&str>) -> Option<Self> {
+        let leaf_type = match kind.trim().to_lowercase().as_str() {
+            "løvskov" => "broadleaved",
+            "nåleskov" => "needleleaved",
+            "blandskov" => "mixed",
+            _ => return None,
+        };
+        let genus = species.and_then(|species| {
+            let species = species.to_lowercase();
+            [
+                ("bøg", "Fagus"),
+                ("eg", "Quercus"),
+                ("birk", "Betula"),
+                ("gran", "Picea"),
+                ("fyr", "Pinus"),
+                ("lærk", "Larix"),
+            ]
+            .iter()
+            .find(|(name, _)| species.ends_with(name))
+            .map(|&(_, genus)| genus)
+        });
+        Some(Self { leaf_type, genus })
+    }
+}
+
+/// A mapped forest: its type and the outer ring of each of its polygons as (lat, lng)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForestArea {
+    pub kind: ForestType,
+    pub rings: Vec<Vec<(f64, f64)>>,
+}
+
+/// Fetches the forest types inside the bbox from the Miljøstyrelsen forest map.
+pub fn fetch_forest_types(bbox: &LLBBox) -> Result<Vec<ForestArea>, String> {
+    println!("Fetching forest types from Miljøstyrelsen...");
+    let (min, max) = (bbox.min(), bbox.max());
+    let url = format!(
+        "{MILJOEGIS_WFS_URL}?service=WFS&version=2.0.0&request=GetFeature\
+         &typeNames=skovdata:skovtyper&outputFormat=application/json&srsName=EPSG:4326\
+         &bbox={},{},{},{},urn:ogc:def:crs:EPSG::4326",
+        min.lat(),
+        min.lng(),
+        max.lat(),
+        max.lng()
+    );
+    let bytes = enrichment::download(SOURCE, "forest-types", &url, &url)?;
+    parse_forest_types(&bytes)
+}
+
+/// Parses a GeoJSON feature collection of forests; forests of unknown type are skipped.
+fn parse_forest_types(bytes: &[u8]) -> Result<Vec<ForestArea>, String> {
+    Ok(enrichment::geojson_features(bytes, SOURCE)?
+        .iter()
+        .filter_map(|feature| {
+            let properties = feature.get("properties")?;
+            let kind = ForestType::from_names(
+                properties.get("skovtype")?.as_str()?,
+                properties.get("hovedtraeart").and_then(|s| s.as_str()),
+            )?;
+            let rings = enrichment::outer_rings(feature.get("geometry")?);
+            (!rings.is_empty()).then_some(ForestArea { kind, rings })
+        })
+        .collect())
+}
+
+/// Copies the forest types onto the OSM forest and wood ways whose centroid lies in a mapped
+/// forest, unless they have a `leaf_type` of their own. Returns the number of ways typed.
+pub fn apply_forest_types(
+    elements: &mut [ProcessedElement],
+    areas: &[ForestArea],
+    transformer: &CoordTransformer,
+) -> usize {
+    let projected: Vec<(ForestType, Vec<(i32, i32)>)> = areas
+        .iter()
+        .flat_map(|area| {
+            area.rings.iter().map(|ring| {
+                let points = ring
+                    .iter()
+                    .filter_map(|&(lat, lng)| {
+                        let XZPoint { x, z } =
+                            transformer.transform_point(LLPoint::new(lat, lng).ok()?);
+                        Some((x, z))
+                    })
+                    .collect();
+                (area.kind, points)
+            })
+        })
+        .collect();
+
+    let mut typed = 0;
+    for element in elements {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        let is_forest = way.tags.get("landuse").is_some_and(|v| v == "forest")
+            || way.tags.get("natural").is_some_and(|v| v == "wood");
+        if !is_forest || way.tags.contains_key("leaf_type") || way.nodes.len() < 3 {
+            continue;
+        }
+        let n = way.nodes.len() as f64;
+        let cx = way.nodes.iter().map(|node| node.x as f64).sum::<f64>() / n;
+        let cz = way.nodes.iter().map(|node| node.z as f64).sum::<f64>() / n;
+        let Some((kind, _)) = projected
+            .iter()
+            .find(|(_, ring)| ring.len() >= 3 && ring_contains(ring, cx, cz))
+        else {
+            continue;
+        };
+        way.tags
+            .insert("leaf_type".to_string(), kind.leaf_type.to_string());
+        if let Some(genus) = kind.genus {
+            way.tags
+                .entry("genus".to_string())
+                .or_insert_with(|| genus.to_string());
+        }
+        typed += 1;
+    }
+    typed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_forest_types_and_species() {
+        assert_eq!(
+            ForestType::from_names("Løvskov", Some("Bøg")),
+            Some(ForestType {
+                leaf_type: "broadleaved",
+                genus: Some("Fagus")
+            })
+        );
+        assert_eq!(
+            ForestType::from_names("Nåleskov", Some("Sitkagran")).and_then(|t| t.genus),
+            Some("Picea")
+        );
+        assert_eq!(
+            ForestType::from_names("Blandskov", None).map(|t| t.leaf_type),
+            Some("mixed")
+        );
+        assert_eq!(ForestType::from_names("Ukendt", Some("Bøg")), None);
+    }
+
+    #[test]
+    fn parses_forest_types() {
+        let body = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{"skovtype":"Nåleskov","hovedtraeart":"Rødgran"},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[9.0,56.0],[9.01,56.0],[9.01,56.01],[9.0,56.0]]]}},
+            {"type":"Feature","properties":{"skovtype":"Ukendt"},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[9.1,56.0],[9.11,56.0],[9.11,56.01],[9.1,56.0]]]}}]}"#;
+        let areas = parse_forest_types(body.as_bytes()).unwrap();
+        assert_eq!(areas.len(), 1);
+        assert_eq!(areas[0].kind.genus, Some("Picea"));
+        assert_eq!(areas[0].rings[0][1], (56.0, 9.01));
+    }
+}
@@ -0,0 +1,354 @@
+//! Land cover for unmapped countryside from CORINE Land Cover (`--land-cover`).
+//!
+//! Large rural gaps without any OSM landuse would otherwise become one even lawn. The
+//! CORINE Land Cover polygons in the bbox, mapped from satellite images across Europe at a
+//! coarse 100 m, are fetched from the EEA map service page by page and become `land_cover`
+//! ways. They are kept apart from the elements and only fill the blocks the elements left
+//! empty, once the sea is in. Where `--ortho` is on, each
+//! block's orthophoto class refines the coarse class: bare soil in arable land is a field
+//! ploughed for spring sowing, bare soil in a wood a felled clearing, dark pixels in a
+//! pasture heath.
+
+use crate::args::Args;
+use crate::block_definitions::*;
+use crate::clipping::clip_way_to_bbox;
+use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
+use crate::coordinate_system::geographic::LLBBox;
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::deterministic_rng::coord_rng;
+use crate::element_processing::tree::Tree;
+use crate::enrichment::ortho::{GroundClass, OrthoGround};
+use crate::enrichment::{self, SyntheticIds, LAND_COVER_IDS};
+use crate::floodfill_cache::{BuildingFootprintBitmap, FloodFillCache};
+use crate::osm_parser::ProcessedWay;
+use crate::world_editor::WorldEditor;
+use rand::Rng;
+use std::collections::HashMap;
+
+const CORINE_URL: &str =
+    "https://image.discomap.eea.europa.eu/arcgis/rest/services/Corine/CLC2018_WM/MapServer/0/query";
+/// Tag carrying the class of a land cover way
+const COVER_TAG: &str = "land_cover";
+/// Source of the land cover ways
+const SOURCE: &str = "CORINE Land Cover";
+/// Polygons asked for per request. The service sends at most 2000 and flags the rest as
+/// left out, so larger bboxes are fetched page by page.
+const PAGE_SIZE: usize = 1000;
+/// Rows across a field left unsown as tramlines for the sprayer
+const TRAMLINE_SPACING: i32 = 12;
+
+/// What unmapped countryside is covered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LandCover {
+    /// Arable land with a crop of cereals
+    Crops,
+    /// Arable land ploughed and not yet sown
+    Ploughed,
+    Meadow,
+    Forest,
+    Heath,
+}
+
+impl LandCover {
+    /// Cover of a CORINE level 3 class, e.g. "211" for non-irrigated arable land. Built-up
+    /// land, water, beaches and bare rock give `None` and are left to the default ground.
+    pub fn from_corine(code: &str) -> Option<Self> {
+        match code.trim() {
+            "211" | "212" | "213" | "241" | "242" | "243" => Some(Self::Crops),
+            "231" | "244" | "321" | "411" => Some(Self::Meadow),
+            "311" | "312" | "313" | "324" => Some(Self::Forest),
+            "322" | "323" | "412" => Some(Self::Heath),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Crops => "crops",
+            Self::Ploughed => "ploughed",
+            Self::Meadow => "meadow",
+            Self::Forest => "forest",
+            Self::Heath => "heath",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Crops,
+            Self::Ploughed,
+            Self::Meadow,
+            Self::Forest,
+            Self::Heath,
+        ]
+        .into_iter()
+        .find(|cover| cover.name() == name)
+    }
+
+    /// The cover of one block, refined by its orthophoto class. The spring photo shows
+    /// winter crops as green as grass, so only bare soil and heath tell against the coarse
+    /// class.
+    pub fn refine(self, class: Option<GroundClass>) -> Self {
+        match (self, class) {
+            (Self::Crops | Self::Meadow, Some(GroundClass::BareSoil)) => Self::Ploughed,
+            (Self::Meadow, Some(GroundClass::Heath)) => Self::Heath,
+            (
+                Self::Forest,
+                Some(GroundClass::BareSoil | GroundClass::Sand | GroundClass::Gravel),
+            ) => Self::Meadow,
+            (Self::Heath, Some(GroundClass::Grass)) => Self::Meadow,
+            _ => self,
+        }
+    }
+}
+
+/// A CORINE polygon: its cover and outer ring as (lat, lng)
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverArea {
+    pub cover: LandCover,
+    pub ring: Vec<(f64, f64)>,
+}
+
+/// One response of the map service
+struct CoverPage {
+    areas: Vec<CoverArea>,
+    /// Features in the response, including those with no cover
+    features: usize,
+    /// Whether the service left out features past this page
+    more: bool,
+}
+
+/// Fetches the CORINE Land Cover polygons inside the bbox from the EEA map service.
+pub fn fetch_land_cover(bbox: &LLBBox) -> Result<Vec<CoverArea>, String> {
+    println!("Fetching land cover from CORINE...");
+    let (min, max) = (bbox.min(), bbox.max());
+    let mut areas = Vec::new();
+    let mut offset = 0;
+    loop {
+        let url = format!(
+            "{CORINE_URL}?geometry={},{},{},{}&geometryType=esriGeometryEnvelope&inSR=4326\
+             &spatialRel=esriSpatialRelIntersects&outFields=Code_18&outSR=4326\
+             &orderByFields=OBJECTID&resultOffset={offset}&resultRecordCount={PAGE_SIZE}\
+             &f=geojson",
+            min.lng(),
+            min.lat(),
+            max.lng(),
+            max.lat()
+        );
+        let bytes = enrichment::download("CORINE", "land-cover", &url, &url)?;
+        let page = parse_cover_page(&bytes)?;
+        areas.extend(page.areas);
+        if !page.more || page.features == 0 {
+            return Ok(areas);
+        }
+        offset += page.features;
+    }
+}
+
+/// Parses a GeoJSON feature collection of CORINE polygons; classes with no cover are
+/// skipped.
+fn parse_cover_page(bytes: &[u8]) -> Result<CoverPage, String> {
+    let json: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid CORINE response: {e}"))?;
+    // The flag sits at the top of the collection or in its properties, by server version
+    let more = [
+        json.get("exceededTransferLimit"),
+        json.pointer("/properties/exceededTransferLimit"),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|flag| flag.as_bool() == Some(true));
+    let features = enrichment::geojson_features(bytes, "CORINE")?;
+    let count = features.len();
+
+    let mut areas = Vec::new();
+    for feature in features {
+        let Some(cover) = feature
+            .get("properties")
+            .and_then(|p| p.get("Code_18"))
+            .and_then(|code| code.as_str())
+            .and_then(LandCover::from_corine)
+        else {
+            continue;
+        };
+        let Some(geometry) = feature.get("geometry") else {
+            continue;
+        };
+        areas.extend(
+            enrichment::outer_rings(geometry)
+                .into_iter()
+                .map(|ring| CoverArea { cover, ring }),
+        );
+    }
+    Ok(CoverPage {
+        areas,
+        features: count,
+        more,
+    })
+}
+
+/// Land cover ways for the polygons, projected like the OSM data and clipped to the bbox,
+/// as CORINE polygons often reach far beyond it.
+pub fn cover_ways(
+    areas: &[CoverArea],
+    transformer: &CoordTransformer,
+    xzbbox: &XZBBox,
+) -> Vec<ProcessedWay> {
+    let mut ids = SyntheticIds::new(LAND_COVER_IDS);
+    areas
+        .iter()
+        .filter_map(|area| {
+            let nodes = enrichment::project_ring(&area.ring, transformer, &mut ids)?;
+            let nodes = clip_way_to_bbox(&nodes, xzbbox);
+            if nodes.len() < 4 {
+                return None;
+            }
+
+            let tags = HashMap::from([
+                (COVER_TAG.to_string(), area.cover.name().to_string()),
+                enrichment::source_tag(SOURCE),
+            ]);
+            Some(ProcessedWay {
+                id: ids.next_id(),
+                nodes,
+                tags,
+            })
+        })
+        .collect()
+}
+
+/// Covers one empty block with its ground and what grows on it
+fn place_cover(
+    editor: &mut WorldEditor,
+    cover: LandCover,
+    x: i32,
+    z: i32,
+    rng: &mut impl Rng,
+    building_footprints: &BuildingFootprintBitmap,
+) {
+    let ground = match cover {
+        LandCover::Crops | LandCover::Ploughed => FARMLAND,
+        LandCover::Meadow | LandCover::Forest => GRASS_BLOCK,
+        LandCover::Heath => PODZOL,
+    };
+    editor.set_block(ground, x, 0, z, None, None);
+
+    match cover {
+        LandCover::Ploughed => {}
+        LandCover::Crops if z.rem_euclid(TRAMLINE_SPACING) == 0 => {}
+        LandCover::Crops => editor.set_block(WHEAT, x, 1, z, None, None),
+        LandCover::Meadow => match rng.random_range(0..100) {
+            0 => editor.set_block(RED_FLOWER, x, 1, z, None, None),
+            1 => editor.set_block(YELLOW_FLOWER, x, 1, z, None, None),
+            2..=40 => editor.set_block(GRASS, x, 1, z, None, None),
+            _ => {}
+        },
+        LandCover::Forest => match rng.random_range(0..30) {
+            0 => Tree::create(editor, (x, 1, z), Some(building_footprints)),
+            1 => editor.set_block(FERN, x, 1, z, None, None),
+            2..=8 => editor.set_block(GRASS, x, 1, z, None, None),
+            _ => {}
+        },
+        LandCover::Heath => match rng.random_range(0..100) {
+            0..=2 => editor.set_block(OAK_LEAVES, x, 1, z, None, None),
+            3..=10 => editor.set_block(DEAD_BUSH, x, 1, z, None, None),
+            11..=30 => editor.set_block(GRASS, x, 1, z, None, None),
+            _ => {}
+        },
+    }
+}
+
+/// Covers the blocks of the land cover ways that nothing else was placed on, refined by the
+/// orthophoto where there is one. `origin` is the corner of the orthophoto's grid. Returns
+/// how many blocks were covered.
+pub fn generate_land_cover(
+    editor: &mut WorldEditor,
+    ways: &[ProcessedWay],
+    ortho: Option<&OrthoGround>,
+    origin: XZPoint,
+    args: &Args,
+    flood_fill_cache: &FloodFillCache,
+    building_footprints: &BuildingFootprintBitmap,
+) -> usize {
+    let mut covered = 0;
+    for way in ways {
+        let Some(cover) = way
+            .tags
+            .get(COVER_TAG)
+            .and_then(|name| LandCover::from_name(name))
+        else {
+            continue;
+        };
+        for (x, z) in flood_fill_cache.get_or_compute(way, args.timeout.as_ref()) {
+            if editor.block_at(x, 0, z) {
+                continue;
+            }
+            let class = ortho.and_then(|ortho| ortho.class_at(x - origin.x, z - origin.z));
+            let mut rng = coord_rng(x, z, way.id);
+            place_cover(
+                editor,
+                cover.refine(class),
+                x,
+                z,
+                &mut rng,
+                building_footprints,
+            );
+            covered += 1;
+        }
+    }
+    covered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_corine_classes_and_refines_them_by_the_orthophoto() {
+        assert_eq!(LandCover::from_corine("211"), Some(LandCover::Crops));
+        assert_eq!(LandCover::from_corine("231"), Some(LandCover::Meadow));
+        assert_eq!(LandCover::from_corine("312"), Some(LandCover::Forest));
+        assert_eq!(LandCover::from_corine("322"), Some(LandCover::Heath));
+        assert_eq!(LandCover::from_corine("112"), None);
+        assert_eq!(LandCover::from_corine("512"), None);
+
+        let crops = LandCover::Crops;
+        assert_eq!(crops.refine(None), LandCover::Crops);
+        assert_eq!(crops.refine(Some(GroundClass::Grass)), LandCover::Crops);
+        assert_eq!(
+            crops.refine(Some(GroundClass::BareSoil)),
+            LandCover::Ploughed
+        );
+        assert_eq!(
+            LandCover::Forest.refine(Some(GroundClass::BareSoil)),
+            LandCover::Meadow
+        );
+        assert_eq!(
+            LandCover::Meadow.refine(Some(GroundClass::Heath)),
+            LandCover::Heath
+        );
+        for cover in [LandCover::Crops, LandCover::Ploughed, LandCover::Heath] {
+            assert_eq!(LandCover::from_name(cover.name()), Some(cover));
+        }
+    }
+
+    #[test]
+    fn parses_cover_areas() {
+        let body = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{"Code_18":"211"},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[9.0,56.0],[9.01,56.0],[9.01,56.01],[9.0,56.0]]]}},
+            {"type":"Feature","properties":{"Code_18":"112"},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[9.1,56.0],[9.11,56.0],[9.11,56.01],[9.1,56.0]]]}}]}"#;
+        let page = parse_cover_page(body.as_bytes()).unwrap();
+        assert_eq!(page.features, 2);
+        assert!(!page.more);
+        assert_eq!(page.areas.len(), 1);
+        assert_eq!(page.areas[0].cover, LandCover::Crops);
+        assert_eq!(page.areas[0].ring[1], (56.0, 9.01));
+
+        let truncated = r#"{"type":"FeatureCollection","features":[],
+            "properties":{"exceededTransferLimit":true}}"#;
+        assert!(parse_cover_page(truncated.as_bytes()).unwrap().more);
+    }
+}
@@ -0,0 +1,366 @@
+//! Data from sources other than OpenStreetMap that enrich the generated world.
+
+pub mod addresses;
+pub mod bbr;
+pub mod coast;
+pub mod crops;
+pub mod forests;
+pub mod land_cover;
+pub mod monuments;
+pub mod nature;
+pub mod ortho;
+pub mod place_names;
+pub mod roads;
+pub mod transit;
+pub mod wind_turbines;
+
+use crate::args::Args;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::osm_parser::ProcessedNode;
+use crate::task_graph::{Pending, TaskGraph};
+use once_cell::sync::Lazy;
+use reqwest::blocking::Client;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Tag marking the elements an enrichment added, with the source they came from as value.
+/// OSM's own `source` tag only says what a mapper traced from, so it is left to OSM.
+pub const SOURCE_KEY: &str = "arnis:source";
+
+/// First synthetic element ID of each source. Each range lies clear of OSM IDs and of the
+/// other sources.
+pub const LAND_COVER_IDS: u64 = 0x7ff4_0000_0000_0000;
+pub const EXCLUSION_ZONE_IDS: u64 = 0x7ff5_0000_0000_0000;
+pub const ROAD_IDS: u64 = 0x7ff6_0000_0000_0000;
+pub const MONUMENT_IDS: u64 = 0x7ff7_0000_0000_0000;
+pub const COAST_IDS: u64 = 0x7ff8_0000_0000_0000;
+pub const TURBINE_IDS: u64 = 0x7ff9_0000_0000_0000;
+pub const TRANSIT_IDS: u64 = 0x7ffa_0000_0000_0000;
+pub const FIELD_IDS: u64 = 0x7ffb_0000_0000_0000;
+pub const NATURE_IDS: u64 = 0x7ffc_0000_0000_0000;
+pub const BOUNDARY_IDS: u64 = 0x7ffd_0000_0000_0000;
+pub const PLACE_IDS: u64 = 0x7ffe_0000_0000_0000;
+pub const ADDRESS_IDS: u64 = 0x7fff_0000_0000_0000;
+
+/// Hands out the synthetic element IDs of one source, counting up from its first ID
+pub struct SyntheticIds(u64);
+
+impl SyntheticIds {
+    pub fn new(first: u64) -> Self {
+        Self(first)
+    }
+
+    pub fn next_id(&mut self) -> u64 {
+        self.0 += 1;
+        self.0
+    }
+}
+
+/// The [`SOURCE_KEY`] tag of the elements from `source`
+pub fn source_tag(source: &str) -> (String, String) {
+    (SOURCE_KEY.to_string(), source.to_string())
+}
+
+/// Whether an element with these tags was added from `source`
+pub fn is_from(tags: &HashMap<String, String>, source: &str) -> bool {
+    tags.get(SOURCE_KEY).is_some_and(|value| value == source)
+}
+
+static CLIENT: Lazy<Result<Client, String>> = Lazy::new(|| {
+    Client::builder()
+        .timeout(Duration::from_secs(120))
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {e}"))
+});
+
+/// Downloads `url` from `source` and records the response in the manifest as input
+/// `input`. `recorded_url` is the URL as recorded, without any access token.
+pub fn download(
+    source: &str,
+    input: &str,
+    url: &str,
+    recorded_url: &str,
+) -> Result<Vec<u8>, String> {
+    let client = CLIENT.as_ref().map_err(Clone::clone)?;
+    // The URL may contain a token, so it is left out of errors
+    let resp = client
+        .get(url)
+        .send()
+        .map_err(|e| format!("{source} request failed: {}", e.without_url()))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!("{source} returned status {status}"));
+    }
+    let bytes = resp
+        .bytes()
+        .map_err(|e| format!("Failed to read {source} response: {}", e.without_url()))?;
+    crate::manifest::record_input(input, recorded_url, &bytes);
+    Ok(bytes.to_vec())
+}
+
+/// The features of a GeoJSON feature collection from `source`
+pub fn geojson_features(bytes: &[u8], source: &str) -> Result<Vec<serde_json::Value>, String> {
+    let mut json: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid {source} response: {e}"))?;
+    match json.get_mut("features").map(serde_json::Value::take) {
+        Some(serde_json::Value::Array(features)) => Ok(features),
+        _ => Err(format!(
+            "Invalid {source} response: expected a feature collection"
+        )),
+    }
+}
+
+/// A GeoJSON line or ring as (lat, lng)
+pub fn geojson_line(line: &serde_json::Value) -> Option<Vec<(f64, f64)>> {
+    line.as_array()?
+        .iter()
+        .map(|point| {
+            let point = point.as_array()?;
+            Some((point.get(1)?.as_f64()?, point.first()?.as_f64()?))
+        })
+        .collect()
+}
+
+/// The outer rings of a GeoJSON polygon or multipolygon, as (lat, lng). Other geometries
+/// have none.
+pub fn outer_rings(geometry: &serde_json::Value) -> Vec<Vec<(f64, f64)>> {
+    let Some(coordinates) = geometry.get("coordinates").and_then(|c| c.as_array()) else {
+        return Vec::new();
+    };
+    let rings: Vec<&serde_json::Value> = match geometry.get("type").and_then(|t| t.as_str()) {
+        Some("Polygon") => coordinates.first().into_iter().collect(),
+        Some("MultiPolygon") => coordinates
+            .iter()
+            .filter_map(|polygon| polygon.as_array()?.first())
+            .collect(),
+        _ => Vec::new(),
+    };
+    rings
+        .into_iter()
+        .filter_map(geojson_line)
+        .filter(|ring| ring.len() >= 3)
+        .collect()
+}
+
+/// Nodes for a ring of (lat, lng), projected like the OSM data and closed on their first
+/// node as OSM areas are. Rings of fewer than three points give `None`.
+pub fn project_ring(
+    ring: &[(f64, f64)],
+    transformer: &CoordTransformer,
+    ids: &mut SyntheticIds,
+) -> Option<Vec<ProcessedNode>> {
+    let mut nodes: Vec<ProcessedNode> = ring
+        .iter()
+        .filter_map(|&(lat, lng)| {
+            let llpoint = LLPoint::new(lat, lng).ok()?;
+            let point = transformer.transform_point(llpoint);
+            Some(ProcessedNode {
+                id: ids.next_id(),
+                tags: HashMap::new(),
+                x: point.x,
+                z: point.z,
+                exact: Some(transformer.project(llpoint)),
+            })
+        })
+        .collect();
+    if nodes.first().map(|n| (n.x, n.z)) == nodes.last().map(|n| (n.x, n.z)) {
+        nodes.pop();
+    }
+    if nodes.len() < 3 {
+        return None;
+    }
+    nodes.push(nodes[0].clone());
+    Some(nodes)
+}
+
+/// Items at block positions, bucketed by cells of a fixed size, for finding the nearest
+/// item to a point without checking them all
+pub struct PointIndex<T> {
+    cell: i32,
+    cells: HashMap<(i32, i32), Vec<(i32, i32, T)>>,
+}
+
+impl<T> PointIndex<T> {
+    /// An empty index for lookups up to `radius` blocks
+    pub fn new(radius: i32) -> Self {
+        Self {
+            cell: radius.max(1),
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell_of(&self, x: i32, z: i32) -> (i32, i32) {
+        (x.div_euclid(self.cell), z.div_euclid(self.cell))
+    }
+
+    pub fn insert(&mut self, x: i32, z: i32, item: T) {
+        let cell = self.cell_of(x, z);
+        self.cells.entry(cell).or_default().push((x, z, item));
+    }
+
+    /// The nearest item within the index radius of (`x`, `z`), with its position
+    pub fn nearest(&self, x: i32, z: i32) -> Option<(i32, i32, &T)> {
+        let (cx, cz) = self.cell_of(x, z);
+        let radius_sq = self.cell as i64 * self.cell as i64;
+        (-1..=1)
+            .flat_map(|dx| (-1..=1).map(move |dz| (cx + dx, cz + dz)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .map(|(ix, iz, item)| {
+                let (dx, dz) = ((ix - x) as i64, (iz - z) as i64);
+                (dx * dx + dz * dz, (*ix, *iz, item))
+            })
+            .filter(|&(distance_sq, _)| distance_sq <= radius_sq)
+            .min_by_key(|&(distance_sq, (ix, iz, _))| (distance_sq, ix, iz))
+            .map(|(_, found)| found)
+    }
+}
+
+/// The register downloads of a run. They only need the bbox, so they are added to the
+/// run's download graph next to the Overpass download and run alongside it, the terrain
+/// download and the OSM parse; each is joined when its data is applied to the elements.
+/// Downloads that were not requested, or whose register does not cover the bbox, are `None`.
+pub struct Prefetch {
+    pub addresses: Option<Pending<Vec<addresses::Address>>>,
+    pub buildings: Option<Pending<Vec<bbr::BbrBuilding>>>,
+    pub nature: Option<Pending<Vec<nature::NatureArea>>>,
+    pub crops: Option<Pending<Vec<crops::Field>>>,
+    pub forests: Option<Pending<Vec<forests::ForestArea>>>,
+    pub land_cover: Option<Pending<Vec<land_cover::CoverArea>>>,
+    pub coast: Option<Pending<Vec<coast::CoastFeature>>>,
+    pub roads: Option<Pending<Vec<roads::RoadCenterline>>>,
+    pub wind_turbines: Option<Pending<Vec<wind_turbines::Turbine>>>,
+    pub monuments: Option<Pending<Vec<monuments::Monument>>>,
+    pub place_names: Option<Pending<Vec<place_names::Place>>>,
+}
+
+impl Prefetch {
+    /// Adds the requested downloads for `bbox` to `graph`. `danish` tells whether the
+    /// Danish registers cover it.
+    pub fn add_to(graph: &mut TaskGraph, args: &Args, bbox: LLBBox, danish: bool) -> Self {
+        fn add<T: Send + 'static>(
+            graph: &mut TaskGraph,
+            name: &'static str,
+            requested: bool,
+            bbox: LLBBox,
+            fetch: fn(&LLBBox) -> Result<T, String>,
+        ) -> Option<Pending<T>> {
+            requested.then(|| graph.add(name, &[], move || fetch(&bbox)).1)
+        }
+        let year = args.field_crops_year;
+        let crops = (args.field_crops && danish).then(|| {
+            graph
+                .add("field-crops", &[], move || crops::fetch_fields(&bbox, year))
+                .1
+        });
+        let token = args.dhm_token.clone().filter(|_| danish);
+        let buildings = token.clone().filter(|_| args.interiors).map(|token| {
+            graph
+                .add("bbr", &[], move || bbr::fetch_buildings(&bbox, &token))
+                .1
+        });
+        // The coast and the roads both come from GeoDanmark on the same token, which is
+        // asked for one of them at a time
+        let coast_task = token
+            .clone()
+            .filter(|_| args.geodanmark_coast)
+            .map(|token| graph.add("coast", &[], move || coast::fetch_coast(&bbox, &token)));
+        let roads = token.filter(|_| args.geodanmark_roads).map(|token| {
+            let after: Vec<_> = coast_task.iter().map(|(task, _)| *task).collect();
+            graph
+                .add("roads", &after, move || roads::fetch_roads(&bbox, &token))
+                .1
+        });
+        Self {
+            addresses: add(
+                graph,
+                "addresses",
+                args.addresses && danish,
+                bbox,
+                addresses::fetch_addresses,
+            ),
+            buildings,
+            nature: add(
+                graph,
+                "protected-nature",
+                args.protected_nature && danish,
+                bbox,
+                nature::fetch_nature_areas,
+            ),
+            crops,
+            forests: add(
+                graph,
+                "forest-types",
+                args.forest_types && danish,
+                bbox,
+                forests::fetch_forest_types,
+            ),
+            // CORINE covers all of Europe, not just Denmark
+            land_cover: add(
+                graph,
+                "land-cover",
+                args.land_cover,
+                bbox,
+                land_cover::fetch_land_cover,
+            ),
+            coast: coast_task.map(|(_, pending)| pending),
+            roads,
+            wind_turbines: add(
+                graph,
+                "wind-turbines",
+                args.wind_turbines && danish,
+                bbox,
+                wind_turbines::fetch_turbines,
+            ),
+            monuments: add(
+                graph,
+                "ancient-monuments",
+                args.ancient_monuments && danish,
+                bbox,
+                monuments::fetch_monuments,
+            ),
+            place_names: add(
+                graph,
+                "place-names",
+                args.place_names && danish,
+                bbox,
+                place_names::fetch_places,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_outer_rings_of_polygons_only() {
+        let geometry = serde_json::json!({"type": "MultiPolygon", "coordinates": [
+            [[[12.0, 55.0], [12.1, 55.0], [12.1, 55.1], [12.0, 55.0]],
+             [[12.02, 55.02], [12.03, 55.02], [12.03, 55.03], [12.02, 55.02]]],
+            [[[13.0, 56.0], [13.1, 56.0]]]
+        ]});
+        let rings = outer_rings(&geometry);
+        assert_eq!(rings.len(), 1);
+        assert_eq!(rings[0][1], (55.0, 12.1));
+        assert!(
+            outer_rings(&serde_json::json!({"type": "Point", "coordinates": [12.0, 55.0]}))
+                .is_empty()
+        );
+        assert!(geojson_features(b"[]", "Test").is_err());
+        assert!(is_from(&HashMap::from([source_tag("DAWA")]), "DAWA"));
+    }
+
+    #[test]
+    fn point_index_finds_the_nearest_item_in_range() {
+        let mut index = PointIndex::new(10);
+        index.insert(0, 0, "a");
+        index.insert(12, 0, "b");
+        index.insert(-30, 0, "c");
+        assert_eq!(index.nearest(7, 0), Some((12, 0, &"b")));
+        assert_eq!(index.nearest(-3, 4), Some((0, 0, &"a")));
+        assert_eq!(index.nearest(-20, 0), None);
+    }
+}
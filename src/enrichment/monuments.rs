@@ -0,0 +1,218 @@
+//! Ancient monuments from Fund og Fortidsminder (`--ancient-monuments`).
+//!
+//! Denmark has tens of thousands of protected burial mounds, dolmens and rune stones, but
+//! OSM maps few of them. Slots- og Kulturstyrelsen registers every one in Fund og
+//! Fortidsminder. The monuments in the bbox are fetched from its WFS and added to the
+//! elements as `historic` nodes at their registered positions: burial mounds become
+//! grass-covered domes, dolmens and passage graves stone chambers, and rune stones upright
+//! stones with their name on a sign. Monuments OSM already maps nearby are left to OSM.
+
+use crate::coordinate_system::cartesian::XZPoint;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, MONUMENT_IDS};
+use crate::osm_parser::{ProcessedElement, ProcessedNode};
+use std::collections::HashMap;
+
+const FUND_OG_FORTIDSMINDER_WFS_URL: &str = "https://www.kulturarv.dk/geoserver/ows";
+/// Source of the monument nodes
+const SOURCE: &str = "Fund og Fortidsminder";
+/// Registered monuments this close (blocks) to an OSM monument of the same kind are skipped
+const MATCH_RADIUS: i64 = 10;
+
+/// Kind of an ancient monument
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonumentKind {
+    BurialMound,
+    Dolmen,
+    PassageGrave,
+    RuneStone,
+}
+
+impl MonumentKind {
+    /// Kind of an `anlaegsbetegnelse`, e.g. "Rundhøj", "Langdysse", "Jættestue", "Runesten"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.contains("runesten") {
+            Some(Self::RuneStone)
+        } else if name.contains("jættestue") {
+            Some(Self::PassageGrave)
+        } else if name.contains("dysse") {
+            Some(Self::Dolmen)
+        } else if name.contains("høj") {
+            Some(Self::BurialMound)
+        } else {
+            None
+        }
+    }
+
+    /// OSM tags rendering the kind
+    fn osm_tags(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::BurialMound => &[("historic", "tumulus")],
+            Self::Dolmen => &[
+                ("historic", "archaeological_site"),
+                ("site_type", "megalith"),
+                ("megalith_type", "dolmen"),
+            ],
+            Self::PassageGrave => &[
+                ("historic", "archaeological_site"),
+                ("site_type", "megalith"),
+                ("megalith_type", "passage_grave"),
+            ],
+            Self::RuneStone => &[("historic", "rune_stone")],
+        }
+    }
+}
+
+/// A registered monument
+#[derive(Clone, Debug, PartialEq)]
+pub struct Monument {
+    pub kind: MonumentKind,
+    pub name: Option<String>,
+    pub lat: f64,
+    pub lng: f64,
+}
+
+/// Fetches the burial mounds, dolmens and rune stones inside the bbox.
+pub fn fetch_monuments(bbox: &LLBBox) -> Result<Vec<Monument>, String> {
+    println!("Fetching ancient monuments from Fund og Fortidsminder...");
+    let (min, max) = (bbox.min(), bbox.max());
+    let url = format!(
+        "{FUND_OG_FORTIDSMINDER_WFS_URL}?service=WFS&version=2.0.0&request=GetFeature\
+         &typeNames=fundogfortidsminder:anlaeg_punkt&outputFormat=application/json\
+         &srsName=EPSG:4326&bbox={},{},{},{},urn:ogc:def:crs:EPSG::4326",
+        min.lat(),
+        min.lng(),
+        max.lat(),
+        max.lng()
+    );
+    let bytes = enrichment::download(SOURCE, "ancient-monuments", &url, &url)?;
+    parse_monuments(&bytes)
+}
+
+/// Parses a GeoJSON feature collection of monuments; other kinds of site are skipped.
+fn parse_monuments(bytes: &[u8]) -> Result<Vec<Monument>, String> {
+    Ok(enrichment::geojson_features(bytes, SOURCE)?
+        .iter()
+        .filter_map(|feature| {
+            let properties = feature.get("properties")?;
+            let kind = MonumentKind::from_name(properties.get("anlaegsbetegnelse")?.as_str()?)?;
+            let geometry = feature.get("geometry")?;
+            if geometry.get("type").and_then(|t| t.as_str()) != Some("Point") {
+                return None;
+            }
+            let point = geometry.get("coordinates")?.as_array()?;
+            Some(Monument {
+                kind,
+                name: properties
+                    .get("navn")
+                    .and_then(|name| name.as_str())
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string),
+                lat: point.get(1)?.as_f64()?,
+                lng: point.first()?.as_f64()?,
+            })
+        })
+        .collect())
+}
+
+/// Monument nodes for the registered monuments OSM does not map.
+pub fn monument_nodes(
+    monuments: &[Monument],
+    transformer: &CoordTransformer,
+    elements: &[ProcessedElement],
+) -> Vec<ProcessedElement> {
+    let mut added = Vec::new();
+    for (i, monument) in monuments.iter().enumerate() {
+        let Ok(point) = LLPoint::new(monument.lat, monument.lng) else {
+            continue;
+        };
+        let XZPoint { x, z } = transformer.transform_point(point);
+        let tags = monument.kind.osm_tags();
+
+        let mapped = elements.iter().any(|element| match element {
+            ProcessedElement::Node(node)
+                if node.tags.get("historic").map(String::as_str) == Some(tags[0].1) =>
+            {
+                let (dx, dz) = ((node.x - x) as i64, (node.z - z) as i64);
+                dx * dx + dz * dz <= MATCH_RADIUS * MATCH_RADIUS
+            }
+            _ => false,
+        });
+        if mapped {
+            continue;
+        }
+
+        let mut tags: HashMap<String, String> = tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        if let Some(name) = &monument.name {
+            tags.insert("name".to_string(), name.clone());
+        }
+        tags.extend([enrichment::source_tag(SOURCE)]);
+        added.push(ProcessedElement::Node(ProcessedNode {
+            id: MONUMENT_IDS + i as u64,
+            tags,
+            x,
+            z,
+            exact: None,
+        }));
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_monument_names() {
+        assert_eq!(
+            MonumentKind::from_name("Rundhøj"),
+            Some(MonumentKind::BurialMound)
+        );
+        assert_eq!(
+            MonumentKind::from_name("Langdysse"),
+            Some(MonumentKind::Dolmen)
+        );
+        assert_eq!(
+            MonumentKind::from_name("Jættestue"),
+            Some(MonumentKind::PassageGrave)
+        );
+        assert_eq!(
+            MonumentKind::from_name("Runesten"),
+            Some(MonumentKind::RuneStone)
+        );
+        assert_eq!(MonumentKind::from_name("Voldsted"), None);
+    }
+
+    #[test]
+    fn skips_monuments_mapped_in_osm() {
+        let body = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{"anlaegsbetegnelse":"Runesten","navn":"Jellingstenen"},
+             "geometry":{"type":"Point","coordinates":[9.005,56.005]}},
+            {"type":"Feature","properties":{"anlaegsbetegnelse":"Rundhøj","navn":""},
+             "geometry":{"type":"Point","coordinates":[9.002,56.002]}}]}"#;
+        let monuments = parse_monuments(body.as_bytes()).unwrap();
+        assert_eq!(monuments.len(), 2);
+        assert_eq!(monuments[1].name, None);
+
+        let bbox = LLBBox::new(56.0, 9.0, 56.01, 9.01).unwrap();
+        let (transformer, _) = CoordTransformer::llbbox_to_xzbbox(&bbox, 1.0).unwrap();
+        let XZPoint { x, z } = transformer.transform_point(LLPoint::new(56.005, 9.005).unwrap());
+        let osm = vec![ProcessedElement::Node(ProcessedNode {
+            id: 1,
+            tags: HashMap::from([("historic".to_string(), "rune_stone".to_string())]),
+            x: x + 3,
+            z,
+            exact: None,
+        })];
+
+        let nodes = monument_nodes(&monuments, &transformer, &osm);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].tags().get("historic").unwrap(), "tumulus");
+    }
+}
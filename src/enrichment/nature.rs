@@ -0,0 +1,212 @@
+//! Protected nature areas from Danmarks Miljøportal (`--protected-nature`).
+//!
+//! Heaths, meadows, bogs, lakes and salt marshes protected under §3 of the Danish nature
+//! protection act are mapped far more completely than in OSM, where many of them are only
+//! `landuse=grass` or not mapped at all. The registered §3 areas in the bbox are fetched
+//! from the Miljøportal WFS and added to the elements as ways with the equivalent OSM
+//! natural tags, so they get the matching surface and vegetation. Areas that OSM already
+//! maps as something other than grass are left to OSM.
+
+use crate::coordinate_system::geographic::LLBBox;
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, SyntheticIds, NATURE_IDS};
+use crate::osm_parser::{ProcessedElement, ProcessedWay};
+use std::collections::HashMap;
+
+const MILJOEPORTAL_WFS_URL: &str = "https://arealeditering-dist-geo.miljoeportal.dk/geoserver/ows";
+/// Source of the §3 ways
+const SOURCE: &str = "Danmarks Miljøportal";
+
+/// §3 nature type (`natyp_navn`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatureType {
+    Heath,
+    Meadow,
+    Bog,
+    Lake,
+    DryGrassland,
+    SaltMeadow,
+}
+
+impl NatureType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "hede" => Some(NatureType::Heath),
+            "eng" => Some(NatureType::Meadow),
+            "mose" => Some(NatureType::Bog),
+            "sø" => Some(NatureType::Lake),
+            "overdrev" => Some(NatureType::DryGrassland),
+            "strandeng" => Some(NatureType::SaltMeadow),
+            _ => None,
+        }
+    }
+
+    /// OSM tags rendering the nature type
+    fn osm_tags(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            NatureType::Heath => &[("natural", "heath")],
+            NatureType::Meadow => &[("natural", "wetland"), ("wetland", "wet_meadow")],
+            NatureType::Bog => &[("natural", "wetland"), ("wetland", "bog")],
+            NatureType::Lake => &[("natural", "water"), ("water", "lake")],
+            NatureType::DryGrassland => &[("natural", "grassland")],
+            NatureType::SaltMeadow => &[("natural", "wetland"), ("wetland", "saltmarsh")],
+        }
+    }
+}
+
+/// A protected nature area: its type and the outer ring of each of its polygons as (lat, lng)
+#[derive(Clone, Debug, PartialEq)]
+pub struct NatureArea {
+    pub kind: NatureType,
+    pub rings: Vec<Vec<(f64, f64)>>,
+}
+
+/// Fetches the §3 protected nature areas inside the bbox from Danmarks Miljøportal.
+pub fn fetch_nature_areas(bbox: &LLBBox) -> Result<Vec<NatureArea>, String> {
+    println!("Fetching §3 nature areas from Danmarks Miljøportal...");
+    let (min, max) = (bbox.min(), bbox.max());
+    let url = format!(
+        "{MILJOEPORTAL_WFS_URL}?service=WFS&version=2.0.0&request=GetFeature\
+         &typeNames=dai:bes_naturtyper&outputFormat=application/json&srsName=EPSG:4326\
+         &bbox={},{},{},{},urn:ogc:def:crs:EPSG::4326",
+        min.lat(),
+        min.lng(),
+        max.lat(),
+        max.lng()
+    );
+    let bytes = enrichment::download("Miljøportal", "protected-nature", &url, &url)?;
+    parse_nature_areas(&bytes)
+}
+
+/// Parses a GeoJSON feature collection of §3 areas; types other than the six known ones
+/// are skipped.
+fn parse_nature_areas(bytes: &[u8]) -> Result<Vec<NatureArea>, String> {
+    Ok(enrichment::geojson_features(bytes, "Miljøportal")?
+        .iter()
+        .filter_map(|feature| {
+            let name = feature.get("properties")?.get("natyp_navn")?.as_str()?;
+            let kind = NatureType::from_name(name)?;
+            let rings = enrichment::outer_rings(feature.get("geometry")?);
+            (!rings.is_empty()).then_some(NatureArea { kind, rings })
+        })
+        .collect())
+}
+
+/// Whether an OSM element already maps its area as something more specific than grass
+fn maps_more_than_grass(tags: &HashMap<String, String>) -> bool {
+    let is =
+        |key: &str, values: &[&str]| tags.get(key).is_some_and(|v| values.contains(&v.as_str()));
+    if is("landuse", &["grass", "meadow", "greenfield"]) || is("natural", &["grassland"]) {
+        return false;
+    }
+    [
+        "natural", "landuse", "leisure", "water", "amenity", "building",
+    ]
+    .iter()
+    .any(|key| tags.contains_key(*key))
+}
+
+/// Even-odd test of a point against a ring
+pub fn ring_contains(ring: &[(i32, i32)], x: f64, z: f64) -> bool {
+    let mut inside = false;
+    for (i, &(x1, z1)) in ring.iter().enumerate() {
+        let (x2, z2) = ring[(i + 1) % ring.len()];
+        let (x1, z1, x2, z2) = (x1 as f64, z1 as f64, x2 as f64, z2 as f64);
+        if (z1 <= z) != (z2 <= z) && x < x1 + (z - z1) / (z2 - z1) * (x2 - x1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Ways for the §3 areas that OSM maps only as grass or not at all, projected like the OSM
+/// data. `elements` are the OSM elements the areas are checked against.
+pub fn nature_ways(
+    areas: &[NatureArea],
+    transformer: &CoordTransformer,
+    elements: &[ProcessedElement],
+) -> Vec<ProcessedElement> {
+    let mapped: Vec<Vec<(i32, i32)>> = elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Way(way)
+                if way.nodes.len() >= 4
+                    && way.nodes.first().map(|n| n.id) == way.nodes.last().map(|n| n.id)
+                    && maps_more_than_grass(&way.tags) =>
+            {
+                Some(way.nodes.iter().map(|n| (n.x, n.z)).collect())
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut ids = SyntheticIds::new(NATURE_IDS);
+    let mut ways = Vec::new();
+    for area in areas {
+        for ring in &area.rings {
+            let Some(nodes) = enrichment::project_ring(ring, transformer, &mut ids) else {
+                continue;
+            };
+            let n = (nodes.len() - 1) as f64;
+            let cx = nodes[1..].iter().map(|p| p.x as f64).sum::<f64>() / n;
+            let cz = nodes[1..].iter().map(|p| p.z as f64).sum::<f64>() / n;
+            if mapped.iter().any(|ring| ring_contains(ring, cx, cz)) {
+                continue;
+            }
+
+            let mut tags: HashMap<String, String> = area
+                .kind
+                .osm_tags()
+                .iter()
+                .map(|&(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            tags.extend([enrichment::source_tag(SOURCE)]);
+            tags.insert("protect_class".to_string(), "§3".to_string());
+            ways.push(ProcessedElement::Way(ProcessedWay {
+                id: ids.next_id(),
+                nodes,
+                tags,
+            }));
+        }
+    }
+    ways
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn parses_known_nature_types() {
+        let body = br#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{"natyp_navn":"Hede"},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[10.0,56.0],[10.01,56.0],[10.01,56.01],[10.0,56.0]]]}},
+            {"type":"Feature","properties":{"natyp_navn":"Mose"},
+             "geometry":{"type":"MultiPolygon","coordinates":[
+                [[[10.1,56.0],[10.11,56.0],[10.11,56.01],[10.1,56.0]]],
+                [[[10.2,56.0],[10.21,56.0],[10.21,56.01],[10.2,56.0]]]]}},
+            {"type":"Feature","properties":{"natyp_navn":"Ukendt"},
+             "geometry":{"type":"Polygon","coordinates":[]}}]}"#;
+        let areas = parse_nature_areas(body).unwrap();
+        assert_eq!(areas.len(), 2);
+        assert_eq!(areas[0].kind, NatureType::Heath);
+        assert_eq!(areas[0].rings[0][1], (56.0, 10.01));
+        assert_eq!(areas[1].kind, NatureType::Bog);
+        assert_eq!(areas[1].rings.len(), 2);
+        assert!(parse_nature_areas(b"[]").is_err());
+    }
+
+    #[test]
+    fn only_grass_gives_way_to_protected_nature() {
+        assert!(!maps_more_than_grass(&tags(&[("landuse", "grass")])));
+        assert!(!maps_more_than_grass(&tags(&[("highway", "path")])));
+        assert!(maps_more_than_grass(&tags(&[("landuse", "farmland")])));
+        assert!(maps_more_than_grass(&tags(&[("natural", "wood")])));
+
+        let square = [(0, 0), (10, 0), (10, 10), (0, 10), (0, 0)];
+        assert!(ring_contains(&square, 5.0, 5.0));
+        assert!(!ring_contains(&square, 15.0, 5.0));
+    }
+}
@@ -0,0 +1,207 @@
+//! Ground materials from the GeoDanmark orthophoto (`--ortho`).
+//!
+//! Where OSM has no landuse, every natural cell would otherwise be grass. The spring
+//! orthophoto from Dataforsyningen is fetched for the bbox and each cell's pixel is
+//! classified by color: bare field soil, dark heath, sand and gravel lots replace the plain
+//! grass, so ploughed fields next to green ones, beaches and unmapped gravel pits show up.
+//! Mapped landuse and urban ground are left alone.
+
+use crate::block_definitions::*;
+use crate::coordinate_system::geographic::LLBBox;
+use image::Rgb;
+use reqwest::blocking::Client;
+use std::time::Duration;
+
+const ORTHO_WMS_URL: &str = "https://api.dataforsyningen.dk/orto_foraar_DAF";
+/// Largest image requested from the WMS; bigger worlds sample it more coarsely
+const MAX_REQUEST_PIXELS: usize = 2048;
+
+/// Ground kind of an orthophoto pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroundClass {
+    Grass,
+    /// Ploughed or freshly sown field
+    BareSoil,
+    /// Dark, brownish-green heath and scrub
+    Heath,
+    Sand,
+    Gravel,
+}
+
+impl GroundClass {
+    /// Surface and subsurface blocks for the class
+    pub fn blocks(self) -> (Block, Block) {
+        match self {
+            GroundClass::Grass => (GRASS_BLOCK, DIRT),
+            GroundClass::BareSoil => (COARSE_DIRT, DIRT),
+            GroundClass::Heath => (PODZOL, DIRT),
+            GroundClass::Sand => (SAND, SAND),
+            GroundClass::Gravel => (GRAVEL, GRAVEL),
+        }
+    }
+}
+
+/// Classifies a pixel by hue, saturation and brightness. Water, shadows and roofs (blue,
+/// very dark or very bright grey) give `None` and keep the default ground.
+pub fn classify(Rgb([r, g, b]): Rgb<u8>) -> Option<GroundClass> {
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let value = max;
+    let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+    if value < 0.15 || (b >= g && b > r) {
+        return None;
+    }
+    if saturation < 0.12 {
+        return (0.3..0.7).contains(&value).then_some(GroundClass::Gravel);
+    }
+    let hue = if max == r {
+        60.0 * ((g - b) / (max - min)).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / (max - min) + 2.0)
+    } else {
+        60.0 * ((r - g) / (max - min) + 4.0)
+    };
+    match hue {
+        h if (70.0..170.0).contains(&h) => Some(GroundClass::Grass),
+        h if (35.0..70.0).contains(&h) && value > 0.65 && saturation < 0.5 => {
+            Some(GroundClass::Sand)
+        }
+        h if (35.0..70.0).contains(&h) && value < 0.4 => Some(GroundClass::Heath),
+        h if (35.0..70.0).contains(&h) => Some(GroundClass::Grass),
+        h if !(35.0..330.0).contains(&h) => Some(GroundClass::BareSoil),
+        _ => None,
+    }
+}
+
+/// Classified orthophoto covering the terrain grid
+pub struct OrthoGround {
+    width: usize,
+    height: usize,
+    classes: Vec<Option<GroundClass>>,
+    /// Pixels the WMS left blank (pure black or white) outside the photo's coverage
+    blank: Vec<bool>,
+    /// Size of the terrain grid the image is sampled onto
+    grid: (usize, usize),
+}
+
+impl OrthoGround {
+    pub fn from_image(image: &image::RgbImage, grid: (usize, usize)) -> Self {
+        Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            classes: image.pixels().map(|&pixel| classify(pixel)).collect(),
+            blank: image
+                .pixels()
+                .map(|&Rgb(rgb)| rgb == [0; 3] || rgb == [255; 3])
+                .collect(),
+            grid,
+        }
+    }
+
+    /// Index of the pixel under grid cell (`x`, `z`), counted from the northwest corner
+    fn pixel(&self, x: i32, z: i32) -> Option<usize> {
+        let (grid_width, grid_height) = self.grid;
+        if x < 0 || z < 0 || x as usize >= grid_width || z as usize >= grid_height {
+            return None;
+        }
+        let px = x as usize * self.width / grid_width;
+        let pz = z as usize * self.height / grid_height;
+        Some(pz * self.width + px)
+    }
+
+    /// Class of the pixel under grid cell (`x`, `z`)
+    pub fn class_at(&self, x: i32, z: i32) -> Option<GroundClass> {
+        self.classes[self.pixel(x, z)?]
+    }
+
+    /// Whether the orthophoto covers grid cell (`x`, `z`)
+    pub fn covers(&self, x: i32, z: i32) -> bool {
+        self.pixel(x, z).is_some_and(|i| !self.blank[i])
+    }
+}
+
+/// Fetches the orthophoto for the bbox and classifies it for a grid of `grid` cells.
+pub fn fetch_ortho_ground(
+    bbox: &LLBBox,
+    token: &str,
+    grid: (usize, usize),
+) -> Result<OrthoGround, String> {
+    let image = fetch_ortho_image(bbox, token, grid)?;
+    Ok(OrthoGround::from_image(&image, grid))
+}
+
+/// Fetches the orthophoto for the bbox at `size` pixels, or smaller for sizes beyond what
+/// the WMS serves in one request.
+pub fn fetch_ortho_image(
+    bbox: &LLBBox,
+    token: &str,
+    size: (usize, usize),
+) -> Result<image::RgbImage, String> {
+    println!("Fetching GeoDanmark orthophoto...");
+    let (width, height) = (
+        size.0.clamp(1, MAX_REQUEST_PIXELS),
+        size.1.clamp(1, MAX_REQUEST_PIXELS),
+    );
+    // WMS 1.3.0 takes EPSG:4326 bboxes in lat/lng order
+    let request = format!(
+        "service=WMS&version=1.3.0&request=GetMap&layers=orto_foraar&styles=\
+         &crs=EPSG:4326&bbox={},{},{},{}&width={width}&height={height}&format=image/png",
+        bbox.min().lat(),
+        bbox.min().lng(),
+        bbox.max().lat(),
+        bbox.max().lng()
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(180))
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {e}"))?;
+    let resp = client
+        .get(format!("{ORTHO_WMS_URL}?{request}&token={token}"))
+        .send()
+        .map_err(|e| format!("Orthophoto request failed: {e}"))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!("Orthophoto WMS returned status {status}"));
+    }
+    let bytes = resp
+        .bytes()
+        .map_err(|e| format!("Failed to read orthophoto: {e}"))?;
+    if bytes.first() == Some(&b'<') {
+        let text = String::from_utf8_lossy(&bytes[..bytes.len().min(500)]);
+        return Err(format!("Orthophoto WMS returned error: {text}"));
+    }
+    crate::manifest::record_input("ortho", &format!("orto_foraar_DAF {request}"), &bytes);
+
+    Ok(image::load_from_memory(&bytes)
+        .map_err(|e| format!("Failed to decode orthophoto: {e}"))?
+        .to_rgb8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_typical_ground_colors() {
+        assert_eq!(classify(Rgb([70, 120, 50])), Some(GroundClass::Grass));
+        assert_eq!(classify(Rgb([130, 95, 70])), Some(GroundClass::BareSoil));
+        assert_eq!(classify(Rgb([225, 210, 170])), Some(GroundClass::Sand));
+        assert_eq!(classify(Rgb([140, 140, 135])), Some(GroundClass::Gravel));
+        assert_eq!(classify(Rgb([80, 75, 40])), Some(GroundClass::Heath));
+        assert_eq!(classify(Rgb([30, 60, 90])), None, "water");
+        assert_eq!(classify(Rgb([20, 20, 20])), None, "shadow");
+    }
+
+    #[test]
+    fn grid_cells_sample_the_scaled_image() {
+        let mut image = image::RgbImage::from_pixel(2, 2, Rgb([70, 120, 50]));
+        image.put_pixel(1, 1, Rgb([225, 210, 170]));
+        let ortho = OrthoGround::from_image(&image, (10, 10));
+        assert_eq!(ortho.class_at(0, 0), Some(GroundClass::Grass));
+        assert_eq!(ortho.class_at(9, 9), Some(GroundClass::Sand));
+        assert_eq!(ortho.class_at(4, 5), Some(GroundClass::Grass));
+        assert_eq!(ortho.class_at(10, 0), None);
+    }
+}
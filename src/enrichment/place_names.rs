@@ -0,0 +1,235 @@
+//! Place-name markers from Danske Stednavne, the Danish place-name register
+//! (`--place-names`).
+//!
+//! The named places in the bbox (towns, villages, districts, hills, lakes, ancient
+//! monuments and the like) are fetched from the DAWA steder API on Dataforsyningen and
+//! added to the elements as place-name nodes at the visual centre of each place, so they
+//! follow any map transformation like the OSM data. After the elements are processed,
+//! every place gets a marker: a post with a sign facing each direction, giving the name
+//! and the kind of place, so players can orient themselves in large builds.
+
+use crate::block_definitions::*;
+use crate::coordinate_system::cartesian::XZBBox;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::addresses::free_spot;
+use crate::enrichment::{self, PLACE_IDS};
+use crate::floodfill_cache::CoordinateBitmap;
+use crate::osm_parser::{ProcessedElement, ProcessedNode};
+use crate::world_editor::sign_text::{fill_lines, sign_line, wrap_words};
+use crate::world_editor::WorldEditor;
+use std::collections::HashMap;
+
+const STEDER_URL: &str = "https://api.dataforsyningen.dk/steder";
+/// Source of the place-name nodes
+const SOURCE: &str = "Danske Stednavne";
+/// Height of the marker post the signs stand around
+const POST_HEIGHT: i32 = 3;
+
+/// A named place from Danske Stednavne
+#[derive(Clone, Debug, PartialEq)]
+pub struct Place {
+    pub name: String,
+    /// `undertype` of the place, e.g. "by", "bydel", "sø"
+    pub kind: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Fetches the named places inside the bbox from Danske Stednavne.
+pub fn fetch_places(bbox: &LLBBox) -> Result<Vec<Place>, String> {
+    println!("Fetching place names from Danske Stednavne...");
+    let (min, max) = (bbox.min(), bbox.max());
+    let polygon = format!(
+        "[[[{0},{1}],[{2},{1}],[{2},{3}],[{0},{3}],[{0},{1}]]]",
+        min.lng(),
+        min.lat(),
+        max.lng(),
+        max.lat()
+    );
+    let url = format!("{STEDER_URL}?polygon={polygon}&srid=4326");
+    let bytes = enrichment::download("Stednavne", "place-names", &url, &url)?;
+    parse_places(&bytes)
+}
+
+/// Parses a steder response; `visueltcenter` is `[longitude, latitude]`.
+fn parse_places(bytes: &[u8]) -> Result<Vec<Place>, String> {
+    let json: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid Stednavne response: {e}"))?;
+    let entries = json
+        .as_array()
+        .ok_or_else(|| "Invalid Stednavne response: expected a list of places".to_string())?;
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let center = entry.get("visueltcenter")?.as_array()?;
+            Some(Place {
+                name: entry.get("primærtnavn")?.as_str()?.to_string(),
+                kind: entry
+                    .get("undertype")
+                    .and_then(|kind| kind.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                lon: center.first()?.as_f64()?,
+                lat: center.get(1)?.as_f64()?,
+            })
+        })
+        .collect())
+}
+
+/// Place-name nodes for the places inside the world.
+pub fn place_nodes(
+    places: &[Place],
+    transformer: &CoordTransformer,
+    xzbbox: &XZBBox,
+) -> Vec<ProcessedElement> {
+    places
+        .iter()
+        .enumerate()
+        .filter_map(|(i, place)| {
+            let point = transformer.transform_point(LLPoint::new(place.lat, place.lon).ok()?);
+            if !xzbbox.contains(&point) {
+                return None;
+            }
+            let tags = HashMap::from([
+                ("stednavn".to_string(), place.name.clone()),
+                ("stednavn:type".to_string(), place.kind.clone()),
+                enrichment::source_tag(SOURCE),
+            ]);
+            Some(ProcessedElement::Node(ProcessedNode {
+                id: PLACE_IDS + i as u64,
+                tags,
+                x: point.x,
+                z: point.z,
+                exact: None,
+            }))
+        })
+        .collect()
+}
+
+/// A place-name marker to build: position and the lines on each of its signs
+#[derive(Clone, Debug, PartialEq)]
+pub struct Marker {
+    pub x: i32,
+    pub z: i32,
+    pub lines: [String; 4],
+}
+
+/// Plans a marker for every place-name node. Must run before the elements are consumed.
+pub fn plan_markers(elements: &[ProcessedElement]) -> Vec<Marker> {
+    elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Node(node) if enrichment::is_from(&node.tags, SOURCE) => Some(node),
+            _ => None,
+        })
+        .map(|node| {
+            let mut lines = fill_lines(wrap_words(node.tags["stednavn"].split_whitespace(), 3));
+            let mut kind_line = sign_line(&node.tags["stednavn:type"]);
+            if let Some(first) = kind_line.chars().next() {
+                kind_line.replace_range(..first.len_utf8(), &first.to_uppercase().to_string());
+            }
+            lines[3] = kind_line;
+            Marker {
+                x: node.x,
+                z: node.z,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Builds the planned markers at the nearest free spot off roads and buildings.
+/// Returns the number of markers built.
+pub fn place_markers(
+    editor: &mut WorldEditor,
+    markers: Vec<Marker>,
+    road_mask: &CoordinateBitmap,
+    building_footprints: &CoordinateBitmap,
+) -> usize {
+    let (min_x, min_z) = editor.get_min_coords();
+    let (max_x, max_z) = editor.get_max_coords();
+    // Signs on each side of the post, with the rotation facing away from it
+    let sides = [((0, 1), 0), ((-1, 0), 4), ((0, -1), 8), ((1, 0), 12)];
+    let mut placed = 0;
+    for marker in markers {
+        let Some((x, z)) = free_spot(marker.x, marker.z, |x, z| {
+            std::iter::once((0, 0))
+                .chain(sides.iter().map(|&(offset, _)| offset))
+                .all(|(dx, dz)| {
+                    let (x, z) = (x + dx, z + dz);
+                    (min_x..=max_x).contains(&x)
+                        && (min_z..=max_z).contains(&z)
+                        && !road_mask.contains(x, z)
+                        && !building_footprints.contains(x, z)
+                        && !editor.block_at(x, 1, z)
+                })
+        }) else {
+            continue;
+        };
+        for y in 1..=POST_HEIGHT {
+            editor.set_block(SPRUCE_LOG, x, y, z, None, None);
+        }
+        for ((dx, dz), rotation) in sides {
+            let [line1, line2, line3, line4] = marker.lines.clone();
+            editor.set_sign(line1, line2, line3, line4, x + dx, 1, z + dz, rotation);
+        }
+        placed += 1;
+    }
+    placed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_places() {
+        let body = r#"[{"primærtnavn":"Odense","hovedtype":"Bebyggelse","undertype":"by",
+                        "visueltcenter":[10.39,55.40]},
+                       {"primærtnavn":"Uden center"}]"#;
+        let places = parse_places(body.as_bytes()).unwrap();
+        assert_eq!(
+            places,
+            vec![Place {
+                name: "Odense".to_string(),
+                kind: "by".to_string(),
+                lat: 55.40,
+                lon: 10.39,
+            }]
+        );
+        assert!(parse_places(b"{}").is_err());
+    }
+
+    #[test]
+    fn markers_wrap_long_names_and_name_the_kind() {
+        let node = ProcessedNode {
+            id: PLACE_IDS,
+            tags: HashMap::from([
+                (
+                    "stednavn".to_string(),
+                    "Store Dyrehave ved Hillerød".to_string(),
+                ),
+                ("stednavn:type".to_string(), "skov".to_string()),
+                enrichment::source_tag(SOURCE),
+            ]),
+            x: 4,
+            z: 7,
+            exact: None,
+        };
+        let markers = plan_markers(&[ProcessedElement::Node(node)]);
+        assert_eq!(
+            markers,
+            vec![Marker {
+                x: 4,
+                z: 7,
+                lines: [
+                    "Store Dyrehave".to_string(),
+                    "ved Hillerød".to_string(),
+                    String::new(),
+                    "Skov".to_string(),
+                ],
+            }]
+        );
+    }
+}
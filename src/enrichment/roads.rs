@@ -0,0 +1,477 @@
+//! Road attributes from the GeoDanmark road centerlines (`--geodanmark-roads`).
+//!
+//! OSM `width` and `lanes` tags are patchy, so most roads are drawn at the default width of
+//! their class. GeoDanmark maps every road centerline with its carriageway width, number of
+//! lanes and whether it is paved, and maps each carriageway of a divided road as a line of
+//! its own. The centerlines in the bbox are fetched from the GeoDanmark WFS on
+//! Dataforsyningen and matched to the OSM roads running along them: the roads get the
+//! width, lanes and surface they lack, and motorways and trunk roads that OSM maps as a
+//! single two-way line are replaced by GeoDanmark's separate carriageways.
+
+use crate::bresenham::bresenham_line;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, SyntheticIds, ROAD_IDS};
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
+use std::collections::{HashMap, HashSet};
+
+const GEODANMARK_WFS_URL: &str = "https://api.dataforsyningen.dk/GeoDanmark60_NOHIST_GML3_DAF";
+/// OSM road points within this many blocks of a centerline lie along it
+const MATCH_RADIUS: i32 = 4;
+/// Share of an OSM road's points that must lie along a centerline for the two to match
+const MATCH_SHARE: f64 = 0.6;
+/// Every how many blocks an OSM road is sampled for matching
+const SAMPLE_SPACING: usize = 3;
+
+/// OSM road classes that are matched to the centerlines
+const ROAD_CLASSES: &[&str] = &[
+    "motorway",
+    "trunk",
+    "primary",
+    "secondary",
+    "tertiary",
+    "unclassified",
+    "residential",
+    "living_street",
+    "service",
+    "motorway_link",
+    "trunk_link",
+    "primary_link",
+    "secondary_link",
+    "tertiary_link",
+];
+
+/// A GeoDanmark road centerline as (lat, lng), with its attributes where registered
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoadCenterline {
+    pub line: Vec<(f64, f64)>,
+    /// Carriageway width in meters
+    pub width: Option<f64>,
+    pub lanes: Option<u32>,
+    pub paved: Option<bool>,
+    /// Whether the line is one carriageway of a divided road
+    pub carriageway: bool,
+}
+
+/// Fetches the road centerlines inside the bbox from GeoDanmark.
+pub fn fetch_roads(bbox: &LLBBox, token: &str) -> Result<Vec<RoadCenterline>, String> {
+    println!("Fetching GeoDanmark road centerlines...");
+    let (min, max) = (bbox.min(), bbox.max());
+    let request = format!(
+        "service=WFS&version=2.0.0&request=GetFeature&typeNames=gdk60:Vejmidte\
+         &outputFormat=application/json&srsName=EPSG:4326\
+         &bbox={},{},{},{},urn:ogc:def:crs:EPSG::4326",
+        min.lat(),
+        min.lng(),
+        max.lat(),
+        max.lng()
+    );
+    let bytes = enrichment::download(
+        "GeoDanmark",
+        "geodanmark-roads",
+        &format!("{GEODANMARK_WFS_URL}?{request}&token={token}"),
+        &format!("GeoDanmark60_NOHIST_GML3_DAF {request}"),
+    )?;
+    parse_roads(&bytes)
+}
+
+/// Parses a GeoJSON feature collection of centerlines. `vejmidtetype` "Kørebane" marks a
+/// carriageway of a divided road; `overflade` is "Befæstet" (paved) or "Ubefæstet".
+fn parse_roads(bytes: &[u8]) -> Result<Vec<RoadCenterline>, String> {
+    // Numbers come as numbers or as strings with a decimal comma
+    let number = |value: &serde_json::Value| -> Option<f64> {
+        value
+            .as_f64()
+            .or_else(|| value.as_str()?.trim().replace(',', ".").parse().ok())
+            .filter(|n| *n > 0.0)
+    };
+
+    let mut roads = Vec::new();
+    for feature in enrichment::geojson_features(bytes, "GeoDanmark")? {
+        let Some(geometry) = feature.get("geometry") else {
+            continue;
+        };
+        let Some(coordinates) = geometry.get("coordinates") else {
+            continue;
+        };
+        let lines: Vec<&serde_json::Value> = match geometry.get("type").and_then(|t| t.as_str()) {
+            Some("LineString") => vec![coordinates],
+            Some("MultiLineString") => coordinates.as_array().into_iter().flatten().collect(),
+            _ => continue,
+        };
+
+        let properties = feature.get("properties");
+        let property = |key: &str| properties.and_then(|p| p.get(key));
+        let text = |key: &str| {
+            property(key)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_lowercase()
+        };
+        let overflade = text("overflade");
+        let paved = match overflade.as_str() {
+            "befæstet" => Some(true),
+            "ubefæstet" => Some(false),
+            _ => None,
+        };
+        let width = property("bredde").and_then(number);
+        let lanes = property("antal_spor")
+            .and_then(number)
+            .map(|lanes| lanes.round() as u32);
+        let carriageway = text("vejmidtetype").contains("kørebane");
+
+        roads.extend(
+            lines
+                .into_iter()
+                .filter_map(enrichment::geojson_line)
+                .filter(|line| line.len() >= 2)
+                .map(|line| RoadCenterline {
+                    line,
+                    width,
+                    lanes,
+                    paved,
+                    carriageway,
+                }),
+        );
+    }
+    Ok(roads)
+}
+
+/// Blocks along a polyline of block coordinates
+fn rasterize(points: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let mut blocks: Vec<(i32, i32)> = points
+        .windows(2)
+        .flat_map(|pair| bresenham_line(pair[0].0, 0, pair[0].1, pair[1].0, 0, pair[1].1))
+        .map(|(x, _, z)| (x, z))
+        .collect();
+    blocks.dedup();
+    blocks
+}
+
+/// Position on `line` nearest to `point`, as the index of the segment and how far along it
+fn nearest_position(line: &[(i32, i32)], (px, pz): (i32, i32)) -> (usize, f64) {
+    let (px, pz) = (px as f64, pz as f64);
+    let mut nearest = (0, 0.0, f64::MAX);
+    for (i, pair) in line.windows(2).enumerate() {
+        let (ax, az) = (pair[0].0 as f64, pair[0].1 as f64);
+        let (dx, dz) = (pair[1].0 as f64 - ax, pair[1].1 as f64 - az);
+        let length = dx * dx + dz * dz;
+        let t = if length > 0.0 {
+            (((px - ax) * dx + (pz - az) * dz) / length).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (ex, ez) = (ax + t * dx - px, az + t * dz - pz);
+        let distance = ex * ex + ez * ez;
+        if distance < nearest.2 {
+            nearest = (i, t, distance);
+        }
+    }
+    (nearest.0, nearest.1)
+}
+
+/// The part of `line` alongside the stretch from `start` to `end`, in the line's direction
+fn clip_line(line: &[(i32, i32)], start: (i32, i32), end: (i32, i32)) -> Vec<(i32, i32)> {
+    if line.len() < 2 {
+        return line.to_vec();
+    }
+    let (mut from, mut to) = (nearest_position(line, start), nearest_position(line, end));
+    if to.0 < from.0 || (to.0 == from.0 && to.1 < from.1) {
+        std::mem::swap(&mut from, &mut to);
+    }
+    let point_at = |(i, t): (usize, f64)| {
+        let (a, b) = (line[i], line[i + 1]);
+        (
+            (a.0 as f64 + t * (b.0 - a.0) as f64).round() as i32,
+            (a.1 as f64 + t * (b.1 - a.1) as f64).round() as i32,
+        )
+    };
+    let mut clipped = vec![point_at(from)];
+    clipped.extend_from_slice(&line[from.0 + 1..=to.0]);
+    clipped.push(point_at(to));
+    clipped.dedup();
+    clipped
+}
+
+/// A centerline block and the index of its line
+type LineBlock = (i32, i32, usize);
+
+/// Centerline blocks bucketed by cells of [`MATCH_RADIUS`], for finding the centerlines
+/// near a point
+struct CenterlineIndex {
+    cells: HashMap<(i32, i32), Vec<LineBlock>>,
+}
+
+impl CenterlineIndex {
+    fn new(lines: &[Vec<(i32, i32)>]) -> Self {
+        let mut cells: HashMap<(i32, i32), Vec<LineBlock>> = HashMap::new();
+        for (i, line) in lines.iter().enumerate() {
+            for (x, z) in rasterize(line) {
+                let cell = (x.div_euclid(MATCH_RADIUS), z.div_euclid(MATCH_RADIUS));
+                cells.entry(cell).or_default().push((x, z, i));
+            }
+        }
+        Self { cells }
+    }
+
+    /// Centerlines passing within [`MATCH_RADIUS`] of (`x`, `z`)
+    fn near(&self, x: i32, z: i32) -> HashSet<usize> {
+        let (cx, cz) = (x.div_euclid(MATCH_RADIUS), z.div_euclid(MATCH_RADIUS));
+        let mut near = HashSet::new();
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                for &(lx, lz, i) in self.cells.get(&(cx + dx, cz + dz)).into_iter().flatten() {
+                    let (ddx, ddz) = (lx - x, lz - z);
+                    if ddx * ddx + ddz * ddz <= MATCH_RADIUS * MATCH_RADIUS {
+                        near.insert(i);
+                    }
+                }
+            }
+        }
+        near
+    }
+}
+
+/// Matches the OSM roads in `elements` to the centerlines. Matched roads get the width,
+/// lanes and surface they are not tagged with, and two-way motorways and trunk roads that
+/// run along two or more carriageways are replaced by them. Returns the number of roads
+/// given attributes and of roads split into carriageways.
+pub fn apply_road_attributes(
+    elements: &mut Vec<ProcessedElement>,
+    roads: &[RoadCenterline],
+    transformer: &CoordTransformer,
+) -> (usize, usize) {
+    let lines: Vec<Vec<(i32, i32)>> = roads
+        .iter()
+        .map(|road| {
+            road.line
+                .iter()
+                .filter_map(|&(lat, lng)| {
+                    let point = transformer.transform_point(LLPoint::new(lat, lng).ok()?);
+                    Some((point.x, point.z))
+                })
+                .collect()
+        })
+        .collect();
+    let index = CenterlineIndex::new(&lines);
+
+    let mut attributed = 0;
+    let mut split: Vec<(usize, Vec<usize>)> = Vec::new();
+    let mut used = HashSet::new();
+    for (position, element) in elements.iter_mut().enumerate() {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        let Some(highway) = way.tags.get("highway").cloned() else {
+            continue;
+        };
+        if !ROAD_CLASSES.contains(&highway.as_str()) || way.tags.contains_key("area") {
+            continue;
+        }
+
+        let points: Vec<(i32, i32)> = way.nodes.iter().map(|node| (node.x, node.z)).collect();
+        let samples: Vec<(i32, i32)> = rasterize(&points)
+            .into_iter()
+            .step_by(SAMPLE_SPACING)
+            .collect();
+        if samples.is_empty() {
+            continue;
+        }
+        let mut votes: HashMap<usize, usize> = HashMap::new();
+        for &(x, z) in &samples {
+            for i in index.near(x, z) {
+                *votes.entry(i).or_default() += 1;
+            }
+        }
+        let mut matched: Vec<(usize, usize)> = votes
+            .into_iter()
+            .filter(|&(_, count)| count as f64 >= samples.len() as f64 * MATCH_SHARE)
+            .collect();
+        matched.sort_by_key(|&(i, count)| (std::cmp::Reverse(count), i));
+        let Some(&(best, _)) = matched.first() else {
+            continue;
+        };
+
+        let two_way = way.tags.get("oneway").is_none_or(|v| v == "no");
+        let carriageways: Vec<usize> = matched
+            .iter()
+            .map(|&(i, _)| i)
+            .filter(|i| roads[*i].carriageway && !used.contains(i))
+            .collect();
+        if two_way && matches!(highway.as_str(), "motorway" | "trunk") && carriageways.len() >= 2 {
+            used.extend(carriageways.iter().copied());
+            split.push((position, carriageways));
+            continue;
+        }
+
+        let road = &roads[best];
+        let attributes = [
+            ("width", road.width.map(|width| format!("{width}"))),
+            ("lanes", road.lanes.map(|lanes| lanes.to_string())),
+            (
+                "surface",
+                road.paved
+                    .map(|paved| if paved { "asphalt" } else { "gravel" }.to_string()),
+            ),
+        ];
+        let mut changed = false;
+        for (key, value) in attributes {
+            if let Some(value) = value.filter(|_| !way.tags.contains_key(key)) {
+                way.tags.insert(key.to_string(), value);
+                changed = true;
+            }
+        }
+        attributed += changed as usize;
+    }
+
+    // Replace the split roads by their carriageways, which keep the road's OSM tags and are
+    // cut to the stretch of the road
+    let mut ids = SyntheticIds::new(ROAD_IDS);
+    let mut carriageway_ways = Vec::new();
+    for (position, carriageways) in &split {
+        let ProcessedElement::Way(way) = &elements[*position] else {
+            continue;
+        };
+        let (Some(first), Some(last)) = (way.nodes.first(), way.nodes.last()) else {
+            continue;
+        };
+        for &i in carriageways {
+            let mut tags = way.tags.clone();
+            tags.insert("oneway".to_string(), "yes".to_string());
+            tags.remove("lanes");
+            if let Some(width) = roads[i].width {
+                tags.insert("width".to_string(), format!("{width}"));
+            }
+            if let Some(lanes) = roads[i].lanes {
+                tags.insert("lanes".to_string(), lanes.to_string());
+            }
+            let nodes = clip_line(&lines[i], (first.x, first.z), (last.x, last.z))
+                .into_iter()
+                .map(|(x, z)| ProcessedNode {
+                    id: ids.next_id(),
+                    tags: HashMap::new(),
+                    x,
+                    z,
+                    exact: None,
+                })
+                .collect();
+            carriageway_ways.push(ProcessedElement::Way(ProcessedWay {
+                id: ids.next_id(),
+                nodes,
+                tags,
+            }));
+        }
+    }
+    let replaced: HashSet<usize> = split.iter().map(|(position, _)| *position).collect();
+    let mut position = 0;
+    elements.retain(|_| {
+        position += 1;
+        !replaced.contains(&(position - 1))
+    });
+    elements.extend(carriageway_ways);
+    (attributed, split.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_centerline_attributes() {
+        let body = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{"vejmidtetype":"Kørebane","overflade":"Befæstet",
+             "bredde":"10,5","antal_spor":2},
+             "geometry":{"type":"LineString","coordinates":[[12.0,55.0],[12.01,55.0]]}},
+            {"type":"Feature","properties":{"vejmidtetype":"Vej","overflade":"Ubefæstet"},
+             "geometry":{"type":"MultiLineString","coordinates":[[[12.0,55.001],[12.01,55.001]]]}}]}"#;
+        let roads = parse_roads(body.as_bytes()).unwrap();
+        assert_eq!(roads.len(), 2);
+        assert_eq!(roads[0].width, Some(10.5));
+        assert_eq!(roads[0].lanes, Some(2));
+        assert!(roads[0].carriageway);
+        assert_eq!(roads[1].paved, Some(false));
+        assert!(!roads[1].carriageway);
+    }
+
+    #[test]
+    fn attributes_roads_and_splits_motorways() {
+        let bbox = LLBBox::new(55.0, 12.0, 55.01, 12.01).unwrap();
+        let (transformer, _) = CoordTransformer::llbbox_to_xzbbox(&bbox, 1.0).unwrap();
+        let to_xz = |lat, lng| {
+            let point = transformer.transform_point(LLPoint::new(lat, lng).unwrap());
+            (point.x, point.z)
+        };
+        let osm_way = |id, lat, highway: &str| {
+            let (start, end) = (to_xz(lat, 12.001), to_xz(lat, 12.009));
+            ProcessedElement::Way(ProcessedWay {
+                id,
+                nodes: [start, end]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(x, z))| ProcessedNode {
+                        id: id * 10 + i as u64,
+                        tags: HashMap::new(),
+                        x,
+                        z,
+                        exact: None,
+                    })
+                    .collect(),
+                tags: HashMap::from([("highway".to_string(), highway.to_string())]),
+            })
+        };
+        let centerline = |lat, carriageway| RoadCenterline {
+            line: vec![(lat, 12.0), (lat, 12.01)],
+            width: Some(7.0),
+            lanes: Some(2),
+            paved: Some(true),
+            carriageway,
+        };
+        // A residential road, and a motorway mapped as one line between its carriageways
+        let mut elements = vec![
+            osm_way(1, 55.002, "residential"),
+            osm_way(2, 55.006, "motorway"),
+        ];
+        let roads = [
+            centerline(55.002, false),
+            centerline(55.00598, true),
+            centerline(55.00602, true),
+        ];
+
+        assert_eq!(
+            apply_road_attributes(&mut elements, &roads, &transformer),
+            (1, 1)
+        );
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0].tags().get("width").unwrap(), "7");
+        assert_eq!(elements[0].tags().get("surface").unwrap(), "asphalt");
+        assert!(elements[1..]
+            .iter()
+            .all(|element| element.tags().get("oneway").unwrap() == "yes"
+                && element.tags().get("highway").unwrap() == "motorway"));
+        // The carriageways run as far as the motorway, not the whole centerline
+        let (start, end) = (to_xz(55.006, 12.001), to_xz(55.006, 12.009));
+        for element in &elements[1..] {
+            let ProcessedElement::Way(way) = element else {
+                panic!("carriageways are ways");
+            };
+            assert!(way
+                .nodes
+                .iter()
+                .all(|node| node.x >= start.0 && node.x <= end.0));
+        }
+    }
+
+    #[test]
+    fn clips_lines_to_a_stretch() {
+        let line = [(0, 0), (10, 0), (10, 10)];
+        assert_eq!(
+            clip_line(&line, (3, 2), (12, 6)),
+            vec![(3, 0), (10, 0), (10, 6)]
+        );
+        assert_eq!(
+            clip_line(&line, (12, 6), (3, 2)),
+            vec![(3, 0), (10, 0), (10, 6)]
+        );
+        assert_eq!(clip_line(&line, (2, 1), (7, -1)), vec![(2, 0), (7, 0)]);
+    }
+}
@@ -0,0 +1,412 @@
+//! Transit stops from a GTFS feed (`--gtfs`).
+//!
+//! A GTFS feed, such as Rejseplanen's national feed unzipped to a directory, lists every bus
+//! stop and station with the routes serving it. The stops in the bbox are read from it and
+//! matched with the OSM data: OSM bus stops get the route numbers of the nearest GTFS stop,
+//! stops OSM doesn't have are added, with a shelter where several routes call, and rail
+//! stops (S-train and regional trains) without a mapped platform get a platform along the
+//! nearest track.
+
+use crate::coordinate_system::cartesian::XZPoint;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, SyntheticIds, TRANSIT_IDS};
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Source of the added stops and platforms
+const SOURCE: &str = "GTFS";
+/// How far (blocks) an OSM bus stop may be from a GTFS stop to be taken as the same stop
+const BUS_MATCH_RADIUS: i32 = 20;
+/// Rail stops with an OSM platform or station this close (blocks) are already mapped
+const PLATFORM_MATCH_RADIUS: i32 = 60;
+/// How far (blocks) from a rail stop the track for its platform is searched
+const TRACK_SEARCH_RADIUS: i32 = 30;
+/// Length of an added platform and its distance from the track centre, in blocks
+const PLATFORM_LENGTH: f64 = 40.0;
+const PLATFORM_OFFSET: f64 = 3.0;
+/// Bus stops served by at least this many routes get a shelter
+const SHELTER_MIN_ROUTES: usize = 3;
+
+/// A stop or station from the feed, with the short names of the routes calling there
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransitStop {
+    pub name: String,
+    pub lat: f64,
+    pub lng: f64,
+    pub routes: BTreeSet<String>,
+    /// Served by trains rather than buses
+    pub rail: bool,
+}
+
+/// Whether a GTFS `route_type` (basic or extended) is a train
+fn is_rail_route(route_type: u32) -> bool {
+    route_type == 2 || (100..=117).contains(&route_type)
+}
+
+/// Splits a CSV line into fields, honouring double-quoted fields
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches(['\r', '\n']).chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// Calls `row` with each record of a GTFS table as a column name lookup
+fn read_table(
+    dir: &Path,
+    name: &str,
+    mut row: impl FnMut(&dyn Fn(&str) -> Option<String>),
+) -> Result<(), String> {
+    let path = dir.join(name);
+    let file = File::open(&path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let mut lines = BufReader::new(file).lines();
+    let header = match lines.next() {
+        Some(line) => line.map_err(|e| format!("Failed to read {}: {e}", path.display()))?,
+        None => return Ok(()),
+    };
+    let columns: HashMap<String, usize> = csv_fields(header.trim_start_matches('\u{feff}'))
+        .into_iter()
+        .enumerate()
+        .map(|(i, column)| (column.trim().to_string(), i))
+        .collect();
+    for line in lines {
+        let line = line.map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let fields = csv_fields(&line);
+        row(&|column: &str| {
+            columns
+                .get(column)
+                .and_then(|&i| fields.get(i))
+                .map(|value| value.trim().to_string())
+        });
+    }
+    Ok(())
+}
+
+/// Reads the stops inside the bbox and the routes serving them from a GTFS directory.
+pub fn read_gtfs_stops(dir: &Path, bbox: &LLBBox) -> Result<Vec<TransitStop>, String> {
+    println!("Reading GTFS stops from {}...", dir.display());
+    let stops_path = dir.join("stops.txt");
+    let stops_bytes = std::fs::read(&stops_path)
+        .map_err(|e| format!("Failed to read {}: {e}", stops_path.display()))?;
+    crate::manifest::record_input("gtfs", &stops_path.display().to_string(), &stops_bytes);
+
+    let mut stops: HashMap<String, TransitStop> = HashMap::new();
+    read_table(dir, "stops.txt", |get| {
+        let (Some(id), Some(lat), Some(lng)) = (
+            get("stop_id"),
+            get("stop_lat").and_then(|v| v.parse::<f64>().ok()),
+            get("stop_lon").and_then(|v| v.parse::<f64>().ok()),
+        ) else {
+            return;
+        };
+        // Stops and platforms only, not stations or entrances
+        if !matches!(get("location_type").as_deref(), None | Some("" | "0")) {
+            return;
+        }
+        let (min, max) = (bbox.min(), bbox.max());
+        if !(min.lat()..=max.lat()).contains(&lat) || !(min.lng()..=max.lng()).contains(&lng) {
+            return;
+        }
+        stops.insert(
+            id,
+            TransitStop {
+                name: get("stop_name").unwrap_or_default(),
+                lat,
+                lng,
+                routes: BTreeSet::new(),
+                rail: false,
+            },
+        );
+    })?;
+    if stops.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut routes: HashMap<String, (String, bool)> = HashMap::new();
+    read_table(dir, "routes.txt", |get| {
+        if let Some(id) = get("route_id") {
+            let name = get("route_short_name")
+                .filter(|name| !name.is_empty())
+                .or_else(|| get("route_long_name"))
+                .unwrap_or_default();
+            let rail = get("route_type")
+                .and_then(|v| v.parse::<u32>().ok())
+                .is_some_and(is_rail_route);
+            routes.insert(id, (name, rail));
+        }
+    })?;
+    let mut trip_routes: HashMap<String, String> = HashMap::new();
+    read_table(dir, "trips.txt", |get| {
+        if let (Some(trip), Some(route)) = (get("trip_id"), get("route_id")) {
+            trip_routes.insert(trip, route);
+        }
+    })?;
+
+    // stop_times is by far the largest table: only stops in the bbox are looked up
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    read_table(dir, "stop_times.txt", |get| {
+        let (Some(stop_id), Some(trip)) = (get("stop_id"), get("trip_id")) else {
+            return;
+        };
+        let Some(stop) = stops.get_mut(&stop_id) else {
+            return;
+        };
+        let Some(route_id) = trip_routes.get(&trip) else {
+            return;
+        };
+        if !seen.insert((stop_id, route_id.clone())) {
+            return;
+        }
+        if let Some((name, rail)) = routes.get(route_id) {
+            if !name.is_empty() {
+                stop.routes.insert(name.clone());
+            }
+            stop.rail |= rail;
+        }
+    })?;
+
+    let mut stops: Vec<TransitStop> = stops
+        .into_values()
+        .filter(|stop| !stop.routes.is_empty())
+        .collect();
+    stops.sort_by(|a, b| a.name.cmp(&b.name).then(a.lat.total_cmp(&b.lat)));
+    Ok(stops)
+}
+
+fn distance_sq(a: (i32, i32), b: (i32, i32)) -> i64 {
+    let (dx, dz) = ((a.0 - b.0) as i64, (a.1 - b.1) as i64);
+    dx * dx + dz * dz
+}
+
+/// Closest point on segment `a`-`b` to `p`, with the segment's unit direction
+fn closest_on_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> ((f64, f64), (f64, f64)) {
+    let (dx, dz) = (b.0 - a.0, b.1 - a.1);
+    let length_sq = dx * dx + dz * dz;
+    if length_sq == 0.0 {
+        return (a, (1.0, 0.0));
+    }
+    let t = (((p.0 - a.0) * dx + (p.1 - a.1) * dz) / length_sq).clamp(0.0, 1.0);
+    let length = length_sq.sqrt();
+    ((a.0 + t * dx, a.1 + t * dz), (dx / length, dz / length))
+}
+
+/// Matches the stops with the OSM data and returns the elements to add: route numbers are
+/// written onto matching OSM bus stops in `elements`, the rest become new bus stops, and
+/// rail stops without a platform get one along the nearest track.
+pub fn transit_elements(
+    stops: &[TransitStop],
+    transformer: &CoordTransformer,
+    elements: &mut [ProcessedElement],
+) -> Vec<ProcessedElement> {
+    let mut rail_stops: Vec<(i32, i32)> = Vec::new();
+    let mut tracks: Vec<Vec<(f64, f64)>> = Vec::new();
+    for element in elements.iter() {
+        let tags = element.tags();
+        let is_stop = matches!(
+            tags.get("railway").map(String::as_str),
+            Some("platform" | "station" | "halt")
+        ) || tags
+            .get("public_transport")
+            .is_some_and(|v| v == "platform")
+            && tags.get("train").is_some_and(|v| v == "yes");
+        match element {
+            ProcessedElement::Node(node) if is_stop => rail_stops.push((node.x, node.z)),
+            ProcessedElement::Way(way) if is_stop => {
+                rail_stops.extend(way.nodes.iter().map(|node| (node.x, node.z)))
+            }
+            ProcessedElement::Way(way)
+                if tags
+                    .get("railway")
+                    .is_some_and(|v| matches!(v.as_str(), "rail" | "light_rail")) =>
+            {
+                tracks.push(way.nodes.iter().map(|n| (n.x as f64, n.z as f64)).collect());
+            }
+            _ => {}
+        }
+    }
+
+    let mut ids = SyntheticIds::new(TRANSIT_IDS);
+    let mut added = Vec::new();
+    for stop in stops {
+        let Ok(point) = LLPoint::new(stop.lat, stop.lng) else {
+            continue;
+        };
+        let XZPoint { x, z } = transformer.transform_point(point);
+        let route_ref = stop.routes.iter().cloned().collect::<Vec<_>>().join(";");
+
+        if stop.rail {
+            let mapped = rail_stops
+                .iter()
+                .any(|&p| distance_sq(p, (x, z)) <= (PLATFORM_MATCH_RADIUS as i64).pow(2));
+            if mapped {
+                continue;
+            }
+            let p = (x as f64, z as f64);
+            let Some((on_track, (dx, dz))) = tracks
+                .iter()
+                .flat_map(|track| track.windows(2))
+                .map(|pair| closest_on_segment(p, pair[0], pair[1]))
+                .filter(|&(c, _)| {
+                    (c.0 - p.0).powi(2) + (c.1 - p.1).powi(2)
+                        <= (TRACK_SEARCH_RADIUS as f64).powi(2)
+                })
+                .min_by(|a, b| {
+                    let d = |c: (f64, f64)| (c.0 - p.0).powi(2) + (c.1 - p.1).powi(2);
+                    d(a.0).total_cmp(&d(b.0))
+                })
+            else {
+                continue;
+            };
+            // On the side of the track the stop lies on
+            let side = if (p.0 - on_track.0) * -dz + (p.1 - on_track.1) * dx >= 0.0 {
+                1.0
+            } else {
+                -1.0
+            };
+            let centre = (
+                on_track.0 - dz * PLATFORM_OFFSET * side,
+                on_track.1 + dx * PLATFORM_OFFSET * side,
+            );
+            let half = PLATFORM_LENGTH / 2.0;
+            let nodes = [-half, half]
+                .iter()
+                .map(|&t| ProcessedNode {
+                    id: ids.next_id(),
+                    tags: HashMap::new(),
+                    x: (centre.0 + dx * t).round() as i32,
+                    z: (centre.1 + dz * t).round() as i32,
+                    exact: None,
+                })
+                .collect();
+            added.push(ProcessedElement::Way(ProcessedWay {
+                id: ids.next_id(),
+                nodes,
+                tags: HashMap::from([
+                    ("railway".to_string(), "platform".to_string()),
+                    ("public_transport".to_string(), "platform".to_string()),
+                    ("train".to_string(), "yes".to_string()),
+                    ("name".to_string(), stop.name.clone()),
+                    ("route_ref".to_string(), route_ref),
+                    enrichment::source_tag(SOURCE),
+                ]),
+            }));
+            rail_stops.push((x, z));
+            continue;
+        }
+
+        let nearest = elements
+            .iter_mut()
+            .filter_map(|element| match element {
+                ProcessedElement::Node(node)
+                    if node.tags.get("highway").is_some_and(|v| v == "bus_stop") =>
+                {
+                    Some(node)
+                }
+                _ => None,
+            })
+            .filter(|node| {
+                distance_sq((node.x, node.z), (x, z)) <= (BUS_MATCH_RADIUS as i64).pow(2)
+            })
+            .min_by_key(|node| distance_sq((node.x, node.z), (x, z)));
+        if let Some(node) = nearest {
+            node.tags
+                .entry("route_ref".to_string())
+                .or_insert(route_ref);
+            continue;
+        }
+        let mut tags = HashMap::from([
+            ("highway".to_string(), "bus_stop".to_string()),
+            ("name".to_string(), stop.name.clone()),
+            ("route_ref".to_string(), route_ref),
+            enrichment::source_tag(SOURCE),
+        ]);
+        if stop.routes.len() >= SHELTER_MIN_ROUTES {
+            tags.insert("shelter".to_string(), "yes".to_string());
+        }
+        added.push(ProcessedElement::Node(ProcessedNode {
+            id: ids.next_id(),
+            tags,
+            x,
+            z,
+            exact: None,
+        }));
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn splits_quoted_csv_fields() {
+        assert_eq!(
+            csv_fields("1,\"Nørreport St., Spor 1\",55.68\r"),
+            vec!["1", "Nørreport St., Spor 1", "55.68"]
+        );
+        assert_eq!(csv_fields("a,\"say \"\"hi\"\"\",")[1], "say \"hi\"");
+        assert!(is_rail_route(109));
+        assert!(!is_rail_route(700));
+    }
+
+    #[test]
+    fn reads_stops_with_their_routes() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+        fs::write(
+            dir.join("stops.txt"),
+            "\u{feff}stop_id,stop_name,stop_lat,stop_lon,location_type\n\
+             1,Nørreport St.,55.6834,12.5717,0\n\
+             2,Nørreport,55.6834,12.5717,1\n\
+             3,Elsewhere,56.5,10.0,0\n\
+             4,Fiolstræde,55.6810,12.5723,\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("routes.txt"),
+            "route_id,route_short_name,route_long_name,route_type\n\
+             r1,A,,109\nr2,5C,,700\nr3,350S,,700\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("trips.txt"),
+            "route_id,service_id,trip_id\nr1,s,t1\nr2,s,t2\nr3,s,t3\nr2,s,t4\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("stop_times.txt"),
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             t1,08:00:00,08:00:00,1,1\nt2,08:01:00,08:01:00,4,1\n\
+             t3,08:02:00,08:02:00,4,2\nt4,09:01:00,09:01:00,4,1\n",
+        )
+        .unwrap();
+
+        let bbox = LLBBox::new(55.67, 12.56, 55.69, 12.58).unwrap();
+        let stops = read_gtfs_stops(dir, &bbox).unwrap();
+
+        assert_eq!(stops.len(), 2);
+        assert_eq!(stops[0].name, "Fiolstræde");
+        assert!(!stops[0].rail);
+        assert_eq!(
+            stops[0].routes.iter().collect::<Vec<_>>(),
+            vec!["350S", "5C"]
+        );
+        assert!(stops[1].rail);
+    }
+}
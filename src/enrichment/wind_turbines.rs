@@ -0,0 +1,189 @@
+//! Wind turbines from Energistyrelsen's turbine register (`--wind-turbines`).
+//!
+//! Energistyrelsen keeps master data for every wind turbine in Denmark, with its position,
+//! hub height and rotor diameter. The turbines in the bbox are fetched from the register's
+//! WFS: matching OSM generator nodes get the register's dimensions, and turbines OSM does
+//! not map are added as new generator nodes, so every turbine is built to its real size
+//! instead of the default model.
+
+use crate::coordinate_system::cartesian::XZPoint;
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, TURBINE_IDS};
+use crate::osm_parser::{ProcessedElement, ProcessedNode};
+use std::collections::HashMap;
+
+const ENS_WFS_URL: &str = "https://data.ens.dk/geoserver/ows";
+/// Source of the turbine nodes
+const SOURCE: &str = "Energistyrelsen";
+/// OSM generators within this many blocks of a registered turbine are taken to be it
+const MATCH_RADIUS: i64 = 30;
+
+/// A registered turbine: position and dimensions in meters
+#[derive(Clone, Debug, PartialEq)]
+pub struct Turbine {
+    pub lat: f64,
+    pub lng: f64,
+    pub hub_height: f64,
+    pub rotor_diameter: f64,
+}
+
+/// Fetches the turbines inside the bbox from the Energistyrelsen register.
+pub fn fetch_turbines(bbox: &LLBBox) -> Result<Vec<Turbine>, String> {
+    println!("Fetching wind turbines from Energistyrelsen...");
+    let (min, max) = (bbox.min(), bbox.max());
+    let url = format!(
+        "{ENS_WFS_URL}?service=WFS&version=2.0.0&request=GetFeature\
+         &typeNames=ens:vindmoeller&outputFormat=application/json&srsName=EPSG:4326\
+         &bbox={},{},{},{},urn:ogc:def:crs:EPSG::4326",
+        min.lat(),
+        min.lng(),
+        max.lat(),
+        max.lng()
+    );
+    let bytes = enrichment::download("Turbine register", "wind-turbines", &url, &url)?;
+    parse_turbines(&bytes)
+}
+
+/// Parses a GeoJSON feature collection of turbines; turbines without dimensions are skipped.
+fn parse_turbines(bytes: &[u8]) -> Result<Vec<Turbine>, String> {
+    // Dimensions come as numbers or as strings with a decimal comma
+    let meters = |value: &serde_json::Value| -> Option<f64> {
+        value
+            .as_f64()
+            .or_else(|| value.as_str()?.trim().replace(',', ".").parse().ok())
+            .filter(|m| *m > 0.0)
+    };
+    Ok(enrichment::geojson_features(bytes, "Turbine register")?
+        .iter()
+        .filter_map(|feature| {
+            let properties = feature.get("properties")?;
+            let geometry = feature.get("geometry")?;
+            if geometry.get("type").and_then(|t| t.as_str()) != Some("Point") {
+                return None;
+            }
+            let point = geometry.get("coordinates")?.as_array()?;
+            Some(Turbine {
+                lat: point.get(1)?.as_f64()?,
+                lng: point.first()?.as_f64()?,
+                hub_height: meters(properties.get("navhoejde")?)?,
+                rotor_diameter: meters(properties.get("rotordiameter")?)?,
+            })
+        })
+        .collect())
+}
+
+/// Writes the register's dimensions onto the OSM wind generators in `elements` and returns
+/// new generator nodes for the turbines OSM does not map.
+pub fn turbine_elements(
+    turbines: &[Turbine],
+    transformer: &CoordTransformer,
+    elements: &mut [ProcessedElement],
+) -> Vec<ProcessedElement> {
+    let mut added = Vec::new();
+    for (i, turbine) in turbines.iter().enumerate() {
+        let Ok(point) = LLPoint::new(turbine.lat, turbine.lng) else {
+            continue;
+        };
+        let XZPoint { x, z } = transformer.transform_point(point);
+        let dimensions = [
+            ("height:hub", turbine.hub_height),
+            ("rotor:diameter", turbine.rotor_diameter),
+        ];
+
+        let nearest = elements
+            .iter_mut()
+            .filter_map(|element| match element {
+                ProcessedElement::Node(node)
+                    if node.tags.get("power").is_some_and(|v| v == "generator")
+                        && node
+                            .tags
+                            .get("generator:source")
+                            .is_some_and(|v| v == "wind") =>
+                {
+                    let (dx, dz) = ((node.x - x) as i64, (node.z - z) as i64);
+                    Some((dx * dx + dz * dz, node))
+                }
+                _ => None,
+            })
+            .filter(|(distance_sq, _)| *distance_sq <= MATCH_RADIUS * MATCH_RADIUS)
+            .min_by_key(|(distance_sq, _)| *distance_sq);
+        if let Some((_, node)) = nearest {
+            for (key, meters) in dimensions {
+                node.tags.insert(key.to_string(), format!("{meters}"));
+            }
+            continue;
+        }
+
+        let mut tags = HashMap::from([
+            ("power".to_string(), "generator".to_string()),
+            ("generator:source".to_string(), "wind".to_string()),
+            ("generator:method".to_string(), "wind_turbine".to_string()),
+            enrichment::source_tag(SOURCE),
+        ]);
+        for (key, meters) in dimensions {
+            tags.insert(key.to_string(), format!("{meters}"));
+        }
+        added.push(ProcessedElement::Node(ProcessedNode {
+            id: TURBINE_IDS + i as u64,
+            tags,
+            x,
+            z,
+            exact: None,
+        }));
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_turbines() {
+        let body = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{"navhoejde":94.0,"rotordiameter":"112,5"},
+             "geometry":{"type":"Point","coordinates":[8.5,56.2]}},
+            {"type":"Feature","properties":{"navhoejde":null,"rotordiameter":44},
+             "geometry":{"type":"Point","coordinates":[8.6,56.2]}}]}"#;
+        let turbines = parse_turbines(body.as_bytes()).unwrap();
+        assert_eq!(
+            turbines,
+            vec![Turbine {
+                lat: 56.2,
+                lng: 8.5,
+                hub_height: 94.0,
+                rotor_diameter: 112.5
+            }]
+        );
+    }
+
+    #[test]
+    fn sizes_osm_turbines_and_adds_missing_ones() {
+        let bbox = LLBBox::new(56.0, 8.0, 56.01, 8.01).unwrap();
+        let (transformer, _) = CoordTransformer::llbbox_to_xzbbox(&bbox, 1.0).unwrap();
+        let turbine = |lat, lng| Turbine {
+            lat,
+            lng,
+            hub_height: 80.0,
+            rotor_diameter: 100.0,
+        };
+        let turbines = [turbine(56.005, 8.005), turbine(56.001, 8.001)];
+        let XZPoint { x, z } = transformer.transform_point(LLPoint::new(56.005, 8.005).unwrap());
+        let mut elements = vec![ProcessedElement::Node(ProcessedNode {
+            id: 1,
+            tags: HashMap::from([
+                ("power".to_string(), "generator".to_string()),
+                ("generator:source".to_string(), "wind".to_string()),
+            ]),
+            x: x + 5,
+            z,
+            exact: None,
+        })];
+
+        let added = turbine_elements(&turbines, &transformer, &mut elements);
+        assert_eq!(elements[0].tags().get("rotor:diameter").unwrap(), "100");
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].tags().get("height:hub").unwrap(), "80");
+    }
+}
@@ -0,0 +1,300 @@
+//! User-defined exclusion zones (`--exclusion-zones`, or polygons drawn in the GUI).
+//!
+//! An exclusion zone keeps part of the area free, e.g. for the player's own builds inside an
+//! otherwise generated city. Zones are GeoJSON polygons; a feature's `mode` property says
+//! what is left inside it: `terrain` (the default) builds the ground but nothing on it,
+//! `empty` builds nothing at all. Like the `--area` boundary, the zones are added to the
+//! elements as marker ways so they follow map transformations, and taken out again before
+//! processing to build the masks the world editor and the ground pass respect.
+
+use crate::area;
+use crate::coordinate_system::cartesian::XZBBox;
+use crate::coordinate_system::geographic::LLPoint;
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::enrichment::{self, SyntheticIds, EXCLUSION_ZONE_IDS};
+use crate::floodfill_cache::CoordinateBitmap;
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Tag of the marker ways carrying the zone rings through the pipeline; the value is the mode
+const ZONE_TAG: &str = "arnis:exclusion_zone";
+/// Tag grouping the rings of one zone, so its holes only cut into that zone
+const ZONE_ID_TAG: &str = "arnis:exclusion_zone:id";
+
+/// What is left inside an exclusion zone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExclusionMode {
+    /// The ground is built, no buildings, roads, trees or other features
+    TerrainOnly,
+    /// Nothing is built
+    Empty,
+}
+
+impl ExclusionMode {
+    fn name(self) -> &'static str {
+        match self {
+            ExclusionMode::TerrainOnly => "terrain",
+            ExclusionMode::Empty => "empty",
+        }
+    }
+}
+
+/// An exclusion zone: the rings of one polygon as (lat, lng), combined even-odd
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExclusionZone {
+    pub mode: ExclusionMode,
+    pub rings: Vec<Vec<(f64, f64)>>,
+}
+
+/// Projected rings of one zone: its ID, whether it is an `empty` zone and the rings
+type ZoneRings = (String, bool, Vec<Vec<(i32, i32)>>);
+
+/// Masks of the blocks inside the exclusion zones
+pub struct ExclusionMasks {
+    /// Blocks of all zones, where no features are placed
+    pub features: CoordinateBitmap,
+    /// Blocks of the `empty` zones, where no ground is built either
+    pub ground: CoordinateBitmap,
+}
+
+/// Reads the exclusion zones from a GeoJSON file.
+pub fn load_zones(path: &Path) -> Result<Vec<ExclusionZone>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read exclusion zones {}: {e}", path.display()))?;
+    crate::manifest::record_input("exclusion-zones", &path.display().to_string(), &bytes);
+    parse_zones(&bytes).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Parses exclusion zones from GeoJSON: a feature collection, a single feature or a bare
+/// geometry of polygons and multipolygons.
+pub fn parse_zones(bytes: &[u8]) -> Result<Vec<ExclusionZone>, String> {
+    let json: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid exclusion zones: {e}"))?;
+    let features: Vec<&serde_json::Value> = match json.get("features") {
+        Some(features) => features
+            .as_array()
+            .ok_or_else(|| "Invalid exclusion zones: features is not a list".to_string())?
+            .iter()
+            .collect(),
+        None => vec![&json],
+    };
+
+    let mut zones = Vec::new();
+    for feature in features {
+        let mode = match feature
+            .get("properties")
+            .and_then(|p| p.get("mode"))
+            .and_then(|m| m.as_str())
+        {
+            None | Some("terrain") => ExclusionMode::TerrainOnly,
+            Some("empty") => ExclusionMode::Empty,
+            Some(other) => {
+                return Err(format!(
+                    "Unknown exclusion zone mode '{other}' (expected terrain or empty)"
+                ))
+            }
+        };
+        let geometry = feature.get("geometry").unwrap_or(feature);
+        let coordinates = geometry.get("coordinates");
+        let polygons: Vec<&serde_json::Value> = match geometry.get("type").and_then(|t| t.as_str())
+        {
+            Some("Polygon") => coordinates.into_iter().collect(),
+            Some("MultiPolygon") => coordinates
+                .and_then(|c| c.as_array())
+                .map(|polygons| polygons.iter().collect())
+                .unwrap_or_default(),
+            other => return Err(format!("Unsupported exclusion zone geometry {other:?}")),
+        };
+        for polygon in polygons.into_iter().filter_map(|p| p.as_array()) {
+            let rings: Vec<Vec<(f64, f64)>> = polygon
+                .iter()
+                .filter_map(enrichment::geojson_line)
+                .filter(|ring| ring.len() >= 3)
+                .collect();
+            if !rings.is_empty() {
+                zones.push(ExclusionZone { mode, rings });
+            }
+        }
+    }
+    Ok(zones)
+}
+
+/// Marker ways for the zone rings, projected like the OSM data
+pub fn marker_ways(
+    zones: &[ExclusionZone],
+    transformer: &CoordTransformer,
+) -> Vec<ProcessedElement> {
+    let mut ids = SyntheticIds::new(EXCLUSION_ZONE_IDS);
+    let mut ways = Vec::new();
+    for (zone_index, zone) in zones.iter().enumerate() {
+        for ring in &zone.rings {
+            let nodes = ring
+                .iter()
+                .filter_map(|&(lat, lng)| {
+                    let point = transformer.transform_point(LLPoint::new(lat, lng).ok()?);
+                    Some(ProcessedNode {
+                        id: ids.next_id(),
+                        tags: HashMap::new(),
+                        x: point.x,
+                        z: point.z,
+                        exact: None,
+                    })
+                })
+                .collect();
+            ways.push(ProcessedElement::Way(ProcessedWay {
+                id: ids.next_id(),
+                nodes,
+                tags: HashMap::from([
+                    (ZONE_TAG.to_string(), zone.mode.name().to_string()),
+                    (ZONE_ID_TAG.to_string(), zone_index.to_string()),
+                ]),
+            }));
+        }
+    }
+    ways
+}
+
+/// Takes the zone marker ways out of the elements and rasterizes them into the masks of
+/// blocks inside the zones. Returns `None` when no zone is given.
+pub fn take_exclusion_masks(
+    elements: &mut Vec<ProcessedElement>,
+    xzbbox: &XZBBox,
+) -> Option<ExclusionMasks> {
+    let mut zones: Vec<ZoneRings> = Vec::new();
+    elements.retain(|element| match element {
+        ProcessedElement::Way(way) if way.tags.contains_key(ZONE_TAG) => {
+            let key = way.tags.get(ZONE_ID_TAG).cloned().unwrap_or_default();
+            let ring = way.nodes.iter().map(|n| (n.x, n.z)).collect();
+            match zones.iter_mut().find(|(id, _, _)| *id == key) {
+                Some((_, _, rings)) => rings.push(ring),
+                None => {
+                    let empty = way.tags[ZONE_TAG] == ExclusionMode::Empty.name();
+                    zones.push((key, empty, vec![ring]));
+                }
+            }
+            false
+        }
+        _ => true,
+    });
+    if zones.is_empty() {
+        return None;
+    }
+
+    let mut masks = ExclusionMasks {
+        features: CoordinateBitmap::new(xzbbox),
+        ground: CoordinateBitmap::new(xzbbox),
+    };
+    for (_, empty, rings) in &zones {
+        area::fill_rings(&mut masks.features, rings, xzbbox);
+        if *empty {
+            area::fill_rings(&mut masks.ground, rings, xzbbox);
+        }
+    }
+    Some(masks)
+}
+
+/// Whether an element lies wholly inside the exclusion zones, so nothing of it is built.
+/// Water and land use are kept wherever they lie: they shape the terrain the ground pass
+/// builds in the zones, and the editor's mask keeps anything else of them out.
+pub fn wholly_excluded(element: &ProcessedElement, mask: &CoordinateBitmap) -> bool {
+    if shapes_terrain(element.tags()) {
+        return false;
+    }
+    match element {
+        ProcessedElement::Relation(relation) => relation
+            .members
+            .iter()
+            .all(|member| member.way.nodes.iter().all(|n| mask.contains(n.x, n.z))),
+        _ => element.nodes().all(|n| mask.contains(n.x, n.z)),
+    }
+}
+
+/// Whether an element decides where the ground is water or what covers it
+fn shapes_terrain(tags: &HashMap<String, String>) -> bool {
+    tags.contains_key("landuse")
+        || tags.contains_key("water")
+        || tags.contains_key("waterway")
+        || tags
+            .get("natural")
+            .is_some_and(|v| matches!(v.as_str(), "water" | "bay" | "coastline" | "wetland"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate_system::geographic::LLBBox;
+
+    #[test]
+    fn parses_zone_modes() {
+        let body = br#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","properties":{},
+             "geometry":{"type":"Polygon","coordinates":[
+                [[10.0,55.0],[10.1,55.0],[10.1,55.1],[10.0,55.0]]]}},
+            {"type":"Feature","properties":{"mode":"empty"},
+             "geometry":{"type":"MultiPolygon","coordinates":[
+                [[[10.2,55.0],[10.3,55.0],[10.3,55.1],[10.2,55.0]]],
+                [[[10.4,55.0],[10.5,55.0],[10.5,55.1],[10.4,55.0]]]]}}]}"#;
+        let zones = parse_zones(body).unwrap();
+        assert_eq!(zones.len(), 3);
+        assert_eq!(zones[0].mode, ExclusionMode::TerrainOnly);
+        assert_eq!(zones[2].mode, ExclusionMode::Empty);
+        assert_eq!(zones[0].rings[0][1], (55.0, 10.1));
+
+        let unknown = br#"{"type":"Feature","properties":{"mode":"flat"},
+            "geometry":{"type":"Polygon","coordinates":[]}}"#;
+        assert!(parse_zones(unknown).is_err());
+    }
+
+    #[test]
+    fn masks_keep_the_ground_of_terrain_zones() {
+        let bbox = LLBBox::new(55.0, 10.0, 55.01, 10.01).unwrap();
+        let (transformer, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&bbox, 1.0).unwrap();
+        let square = |lat: f64, lng: f64| {
+            vec![
+                (lat, lng),
+                (lat, lng + 0.002),
+                (lat + 0.002, lng + 0.002),
+                (lat + 0.002, lng),
+            ]
+        };
+        let zones = [
+            ExclusionZone {
+                mode: ExclusionMode::TerrainOnly,
+                rings: vec![square(55.001, 10.001)],
+            },
+            ExclusionZone {
+                mode: ExclusionMode::Empty,
+                rings: vec![square(55.006, 10.006)],
+            },
+        ];
+        let mut elements = marker_ways(&zones, &transformer);
+        let masks = take_exclusion_masks(&mut elements, &xzbbox).unwrap();
+        assert!(elements.is_empty());
+
+        let terrain = transformer.transform_point(LLPoint::new(55.002, 10.002).unwrap());
+        let empty = transformer.transform_point(LLPoint::new(55.007, 10.007).unwrap());
+        assert!(masks.features.contains(terrain.x, terrain.z));
+        assert!(!masks.ground.contains(terrain.x, terrain.z));
+        assert!(masks.features.contains(empty.x, empty.z));
+        assert!(masks.ground.contains(empty.x, empty.z));
+        assert!(!masks.features.contains(0, 0));
+
+        // Of what lies inside a zone, only what shapes the terrain is kept
+        let around = [
+            (terrain.x - 2, terrain.z - 2),
+            (terrain.x + 2, terrain.z - 2),
+            (terrain.x + 2, terrain.z + 2),
+            (terrain.x - 2, terrain.z - 2),
+        ];
+        let inside = |tags: &[(&str, &str)]| {
+            wholly_excluded(
+                &crate::test_utilities::way(1, tags, &around),
+                &masks.features,
+            )
+        };
+        assert!(inside(&[("building", "house")]));
+        assert!(!inside(&[("natural", "water")]));
+        assert!(!inside(&[("landuse", "farmland")]));
+    }
+}
@@ -0,0 +1,823 @@
+//! Per-chunk feature index (`--feature-index`) and `arnis strip`.
+//!
+//! While generating, every element is classified into a feature class and the chunks its
+//! outline touches are recorded in `arnis-features.json` next to `level.dat`. The
+//! `strip` command later uses that index to remove a single feature class from an existing
+//! Java world, e.g. `arnis strip <world> --feature vegetation`, touching only the chunks
+//! the class was written into instead of re-running the whole generation. With
+//! `--regenerate-from`, the class is then copied in from a world generated for the same
+//! `--anchor` from newer data, which can cover just the area around a change.
+//!
+//! Stripping works on block names, so only classes with blocks of their own can be
+//! removed: vegetation (leaves, plants and the logs of trees) and railways (rails). The
+//! index records chunks, not blocks, so every such block in an indexed chunk goes, whatever
+//! placed it: hedges, garden planting, roller coasters and player builds included. A wood
+//! marks every chunk it spans.
+
+use crate::coordinate_system::cartesian::XZBBox;
+use crate::osm_parser::ProcessedElement;
+use fastanvil::Region;
+use fastnbt::{LongArray, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+/// File name of the index inside the world directory
+pub const INDEX_FILE: &str = "arnis-features.json";
+/// Blocks around an element's outline that also count as written (tree crowns, kerbs, ...)
+const OUTLINE_MARGIN: i32 = 8;
+/// Blocks per chunk section
+const SECTION_VOLUME: usize = 16 * 16 * 16;
+
+const PLANTS: &[&str] = &[
+    "short_grass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "dead_bush",
+    "poppy",
+    "dandelion",
+    "blue_orchid",
+    "azure_bluet",
+    "oxeye_daisy",
+    "cornflower",
+    "allium",
+    "red_tulip",
+    "white_tulip",
+    "pink_tulip",
+    "orange_tulip",
+    "lily_of_the_valley",
+    "sunflower",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "sweet_berry_bush",
+];
+
+const RAILS: &[&str] = &["rail", "powered_rail", "detector_rail", "activator_rail"];
+
+/// Class of generator output recorded in the index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeatureClass {
+    Vegetation,
+    Buildings,
+    Highways,
+    Railways,
+    Water,
+    Landuse,
+    Other,
+}
+
+impl FeatureClass {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Vegetation => "vegetation",
+            Self::Buildings => "buildings",
+            Self::Highways => "highways",
+            Self::Railways => "railways",
+            Self::Water => "water",
+            Self::Landuse => "landuse",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Feature classes `arnis strip` can remove: those with blocks of their own
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrippableClass {
+    /// Leaves, plants and the logs of trees
+    Vegetation,
+    /// Rails
+    Railways,
+}
+
+impl From<StrippableClass> for FeatureClass {
+    fn from(class: StrippableClass) -> Self {
+        match class {
+            StrippableClass::Vegetation => Self::Vegetation,
+            StrippableClass::Railways => Self::Railways,
+        }
+    }
+}
+
+/// Feature classes an element is generated as
+pub fn classify(element: &ProcessedElement) -> Vec<FeatureClass> {
+    let tags = element.tags();
+    let tag = |key: &str| tags.get(key).map(String::as_str);
+    let mut classes = Vec::new();
+
+    if tags.contains_key("building") || tags.contains_key("building:part") {
+        classes.push(FeatureClass::Buildings);
+    }
+    if tags.contains_key("highway") {
+        classes.push(FeatureClass::Highways);
+    }
+    if tags.contains_key("railway") || tags.contains_key("roller_coaster") {
+        classes.push(FeatureClass::Railways);
+    }
+    if tags.contains_key("waterway")
+        || tags.contains_key("water")
+        || tag("natural") == Some("water")
+    {
+        classes.push(FeatureClass::Water);
+    }
+    if let Some(landuse) = tag("landuse") {
+        classes.push(FeatureClass::Landuse);
+        if matches!(
+            landuse,
+            "forest" | "orchard" | "vineyard" | "meadow" | "grass" | "village_green"
+        ) {
+            classes.push(FeatureClass::Vegetation);
+        }
+    }
+    let green = matches!(
+        tag("natural"),
+        Some("tree" | "tree_row" | "wood" | "scrub" | "heath" | "grassland")
+    ) || matches!(tag("leisure"), Some("park" | "garden" | "nature_reserve"));
+    if green && !classes.contains(&FeatureClass::Vegetation) {
+        classes.push(FeatureClass::Vegetation);
+    }
+
+    if classes.is_empty() {
+        classes.push(FeatureClass::Other);
+    }
+    classes
+}
+
+/// Chunks of the world covered by an element's outline, widened by [`OUTLINE_MARGIN`].
+/// Waterways, tunnels and coastlines are kept whole beyond the world, so the outline is cut
+/// back to it.
+fn element_chunks(element: &ProcessedElement, xzbbox: &XZBBox) -> Vec<(i32, i32)> {
+    let points: Vec<(i32, i32)> = match element {
+        ProcessedElement::Node(node) => vec![(node.x, node.z)],
+        ProcessedElement::Way(way) => way.nodes.iter().map(|n| (n.x, n.z)).collect(),
+        ProcessedElement::Relation(rel) => rel
+            .members
+            .iter()
+            .flat_map(|m| m.way.nodes.iter().map(|n| (n.x, n.z)))
+            .collect(),
+    };
+    let Some(&(first_x, first_z)) = points.first() else {
+        return Vec::new();
+    };
+    let (mut min_x, mut min_z, mut max_x, mut max_z) = (first_x, first_z, first_x, first_z);
+    for &(x, z) in &points {
+        min_x = min_x.min(x);
+        min_z = min_z.min(z);
+        max_x = max_x.max(x);
+        max_z = max_z.max(z);
+    }
+
+    let chunk_range = |min: i32, max: i32, world_min: i32, world_max: i32| {
+        ((min - OUTLINE_MARGIN).max(world_min) >> 4)..=((max + OUTLINE_MARGIN).min(world_max) >> 4)
+    };
+    let chunks_z = chunk_range(min_z, max_z, xzbbox.min_z(), xzbbox.max_z());
+    chunk_range(min_x, max_x, xzbbox.min_x(), xzbbox.max_x())
+        .flat_map(|cx| chunks_z.clone().map(move |cz| (cx, cz)))
+        .collect()
+}
+
+/// Which chunks each feature class was written into.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeatureIndex {
+    pub chunks: BTreeMap<FeatureClass, BTreeSet<(i32, i32)>>,
+}
+
+impl FeatureIndex {
+    pub fn from_elements(elements: &[ProcessedElement], xzbbox: &XZBBox) -> Self {
+        let mut index = Self::default();
+        for element in elements {
+            let chunks = element_chunks(element, xzbbox);
+            for class in classify(element) {
+                index
+                    .chunks
+                    .entry(class)
+                    .or_default()
+                    .extend(chunks.iter().copied());
+            }
+        }
+        index
+    }
+
+    pub fn load(world_dir: &Path) -> Result<Self, String> {
+        let path = world_dir.join(INDEX_FILE);
+        let text = fs::read_to_string(&path).map_err(|e| {
+            format!(
+                "No feature index at {} (generate with --feature-index): {e}",
+                path.display()
+            )
+        })?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid feature index: {e}"))
+    }
+
+    fn save(&self, world_dir: &Path) -> Result<PathBuf, String> {
+        let path = world_dir.join(INDEX_FILE);
+        let text = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize feature index: {e}"))?;
+        fs::write(&path, text).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Writes the index into the world, merged with the index of previously generated tiles.
+    pub fn write(mut self, world_dir: &Path) -> Result<PathBuf, String> {
+        if let Ok(existing) = Self::load(world_dir) {
+            for (class, chunks) in existing.chunks {
+                self.chunks.entry(class).or_default().extend(chunks);
+            }
+        }
+        self.save(world_dir)
+    }
+}
+
+fn block_name(entry: &Value) -> &str {
+    let name = match entry {
+        Value::Compound(map) => match map.get("Name") {
+            Some(Value::String(name)) => name.as_str(),
+            _ => "",
+        },
+        _ => "",
+    };
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+fn air() -> Value {
+    Value::Compound(HashMap::from([(
+        "Name".to_string(),
+        Value::String("minecraft:air".to_string()),
+    )]))
+}
+
+/// Bits per block index in a section's packed `data`
+fn bits_per_block(palette_len: usize) -> usize {
+    (usize::BITS - palette_len.saturating_sub(1).leading_zeros()).max(4) as usize
+}
+
+/// Block states of one chunk section, unpacked to one palette index per block
+struct SectionBlocks {
+    palette: Vec<Value>,
+    indices: Vec<u16>,
+}
+
+impl SectionBlocks {
+    /// Unpacks a section's block states; `None` for sections without a palette, with too
+    /// little data or with indices beyond the palette
+    fn decode(block_states: &HashMap<String, Value>) -> Option<Self> {
+        let Some(Value::List(palette)) = block_states.get("palette") else {
+            return None;
+        };
+        let mut indices = vec![0u16; SECTION_VOLUME];
+        if let Some(Value::LongArray(data)) = block_states.get("data") {
+            let bits = bits_per_block(palette.len());
+            let per_long = 64 / bits;
+            let mask = (1u64 << bits) - 1;
+            for (i, index) in indices.iter_mut().enumerate() {
+                let long = *data.get(i / per_long)? as u64;
+                *index = ((long >> ((i % per_long) * bits)) & mask) as u16;
+                if *index as usize >= palette.len() {
+                    return None;
+                }
+            }
+        }
+        Some(Self {
+            palette: palette.clone(),
+            indices,
+        })
+    }
+
+    fn name(&self, i: usize) -> &str {
+        self.palette
+            .get(self.indices[i] as usize)
+            .map_or("", block_name)
+    }
+
+    /// Packs the blocks back, dropping palette entries that are no longer used
+    fn encode(&self, block_states: &mut HashMap<String, Value>) {
+        let mut remap = vec![None; self.palette.len()];
+        let mut palette = Vec::new();
+        for &index in &self.indices {
+            if remap[index as usize].is_none() {
+                remap[index as usize] = Some(palette.len() as u16);
+                palette.push(self.palette[index as usize].clone());
+            }
+        }
+
+        if palette.len() <= 1 {
+            block_states.remove("data");
+        } else {
+            let bits = bits_per_block(palette.len());
+            let per_long = 64 / bits;
+            let mut data = vec![0i64; SECTION_VOLUME.div_ceil(per_long)];
+            for (i, &index) in self.indices.iter().enumerate() {
+                let value = remap[index as usize].unwrap() as u64;
+                data[i / per_long] |= (value << ((i % per_long) * bits)) as i64;
+            }
+            block_states.insert("data".to_string(), Value::LongArray(LongArray::new(data)));
+        }
+        block_states.insert("palette".to_string(), Value::List(palette));
+    }
+
+    fn set_air(&mut self, i: usize) {
+        let air_index = match self.palette.iter().position(|p| block_name(p) == "air") {
+            Some(index) => index,
+            None => {
+                self.palette.push(air());
+                self.palette.len() - 1
+            }
+        };
+        self.indices[i] = air_index as u16;
+    }
+
+    /// Sets block `i` to the block state `entry`, adding it to the palette if new
+    fn set(&mut self, i: usize, entry: &Value) {
+        let index = match self.palette.iter().position(|p| p == entry) {
+            Some(index) => index,
+            None => {
+                self.palette.push(entry.clone());
+                self.palette.len() - 1
+            }
+        };
+        self.indices[i] = index as u16;
+    }
+}
+
+/// The compound holding a chunk's sections. Freshly generated chunks keep their data in
+/// the pre-1.18 `Level` compound, chunks saved by the game have it at the root.
+fn chunk_root(chunk: &mut Value) -> Option<&mut HashMap<String, Value>> {
+    let Value::Compound(root) = chunk else {
+        return None;
+    };
+    if matches!(root.get("Level"), Some(Value::Compound(_))) {
+        let Some(Value::Compound(level)) = root.get_mut("Level") else {
+            unreachable!()
+        };
+        return Some(level);
+    }
+    Some(root)
+}
+
+fn section_y(section: &Value) -> i32 {
+    match section {
+        Value::Compound(map) => match map.get("Y") {
+            Some(Value::Byte(y)) => *y as i32,
+            Some(Value::Int(y)) => *y,
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Sorts a chunk's sections bottom to top, so tree trunks can be followed across section
+/// borders, and unpacks their blocks
+fn decode_sections(sections: &mut [Value]) -> Vec<Option<SectionBlocks>> {
+    sections.sort_by_key(section_y);
+    sections
+        .iter()
+        .map(|s| match s {
+            Value::Compound(map) => match map.get("block_states") {
+                Some(Value::Compound(states)) => SectionBlocks::decode(states),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Packs the blocks back into a chunk's sections
+fn encode_sections(sections: &mut [Value], blocks: &[Option<SectionBlocks>]) {
+    for (section, blocks) in sections.iter_mut().zip(blocks) {
+        if let (Value::Compound(map), Some(blocks)) = (section, blocks) {
+            if let Some(Value::Compound(states)) = map.get_mut("block_states") {
+                blocks.encode(states);
+            }
+        }
+    }
+}
+
+/// The blocks of a feature class in a chunk's sections, as (section, index in the section)
+fn class_blocks(blocks: &[Option<SectionBlocks>], class: StrippableClass) -> Vec<(usize, usize)> {
+    let height = blocks.len() * 16;
+    let at = |y: usize, x: usize, z: usize| (y / 16, (y % 16) * 256 + z * 16 + x);
+    let name_at = |y: usize, x: usize, z: usize| {
+        let (section, i) = at(y, x, z);
+        blocks[section].as_ref().map_or("", |b| b.name(i))
+    };
+
+    let mut found = Vec::new();
+    for x in 0..16 {
+        for z in 0..16 {
+            let mut y = 0;
+            while y < height {
+                let name = name_at(y, x, z);
+                match class {
+                    StrippableClass::Vegetation if name.ends_with("_log") => {
+                        // Only trunks that carry a crown belong to a tree
+                        let mut top = y;
+                        while top < height && name_at(top, x, z).ends_with("_log") {
+                            top += 1;
+                        }
+                        if top < height && name_at(top, x, z).ends_with("_leaves") {
+                            found.extend((y..top).map(|log_y| at(log_y, x, z)));
+                        }
+                        y = top;
+                        continue;
+                    }
+                    StrippableClass::Vegetation
+                        if name.ends_with("_leaves") || PLANTS.contains(&name) =>
+                    {
+                        found.push(at(y, x, z))
+                    }
+                    StrippableClass::Railways if RAILS.contains(&name) => found.push(at(y, x, z)),
+                    _ => {}
+                }
+                y += 1;
+            }
+        }
+    }
+    found
+}
+
+/// Replaces the blocks of a feature class in one chunk with air. Returns the number of
+/// blocks removed.
+fn strip_chunk(chunk: &mut Value, class: StrippableClass) -> usize {
+    let Some(root) = chunk_root(chunk) else {
+        return 0;
+    };
+    let Some(Value::List(sections)) = root.get_mut("sections") else {
+        return 0;
+    };
+    let mut blocks = decode_sections(sections);
+    let found = class_blocks(&blocks, class);
+    if found.is_empty() {
+        return 0;
+    }
+    for &(section, i) in &found {
+        if let Some(blocks) = blocks[section].as_mut() {
+            blocks.set_air(i);
+        }
+    }
+    encode_sections(sections, &blocks);
+    // Let the game recompute lighting for the changed chunk
+    root.insert("isLightOn".to_string(), Value::Byte(0));
+    found.len()
+}
+
+/// Copies the blocks of a feature class from `source`, the same chunk of another world,
+/// into `chunk` where it has air. Returns the number of blocks copied.
+fn graft_chunk(chunk: &mut Value, source: &mut Value, class: StrippableClass) -> usize {
+    let (Some(root), Some(source_root)) = (chunk_root(chunk), chunk_root(source)) else {
+        return 0;
+    };
+    let (Some(Value::List(sections)), Some(Value::List(source_sections))) =
+        (root.get_mut("sections"), source_root.get_mut("sections"))
+    else {
+        return 0;
+    };
+    let source_blocks = decode_sections(source_sections);
+    let mut blocks = decode_sections(sections);
+
+    let mut copied = 0;
+    for (source_section, i) in class_blocks(&source_blocks, class) {
+        let y = section_y(&source_sections[source_section]);
+        let (Some(source_section), Some(section)) = (
+            source_blocks[source_section].as_ref(),
+            sections
+                .iter()
+                .position(|section| section_y(section) == y)
+                .and_then(|section| blocks[section].as_mut()),
+        ) else {
+            continue;
+        };
+        if section.name(i) == "air" {
+            section.set(
+                i,
+                &source_section.palette[source_section.indices[i] as usize],
+            );
+            copied += 1;
+        }
+    }
+    if copied > 0 {
+        encode_sections(sections, &blocks);
+        root.insert("isLightOn".to_string(), Value::Byte(0));
+    }
+    copied
+}
+
+/// Chunks grouped by region file, as chunk positions inside the region
+fn by_region(chunks: &BTreeSet<(i32, i32)>) -> BTreeMap<(i32, i32), Vec<(usize, usize)>> {
+    let mut regions: BTreeMap<(i32, i32), Vec<(usize, usize)>> = BTreeMap::new();
+    for &(cx, cz) in chunks {
+        regions
+            .entry((cx >> 5, cz >> 5))
+            .or_default()
+            .push(((cx & 31) as usize, (cz & 31) as usize));
+    }
+    regions
+}
+
+fn region_path(world_dir: &Path, (region_x, region_z): (i32, i32)) -> PathBuf {
+    world_dir
+        .join("region")
+        .join(format!("r.{region_x}.{region_z}.mca"))
+}
+
+fn read_chunk(
+    region: &mut Region<File>,
+    (x, z): (usize, usize),
+    path: &Path,
+) -> Result<Option<Value>, String> {
+    let Ok(Some(data)) = region.read_chunk(x, z) else {
+        return Ok(None);
+    };
+    fastnbt::from_bytes(&data)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse chunk in {}: {e}", path.display()))
+}
+
+fn write_chunk(
+    region: &mut Region<File>,
+    (x, z): (usize, usize),
+    chunk: &Value,
+    path: &Path,
+) -> Result<(), String> {
+    let bytes = fastnbt::to_bytes(chunk).map_err(|e| format!("Failed to serialize chunk: {e}"))?;
+    region
+        .write_chunk(x, z, &bytes)
+        .map_err(|e| format!("Failed to write chunk to {}: {e}", path.display()))
+}
+
+fn open_region(path: &Path, write: bool) -> Result<Option<Region<File>>, String> {
+    let Ok(file) = File::options().read(true).write(write).open(path) else {
+        return Ok(None);
+    };
+    Region::from_stream(file)
+        .map(Some)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))
+}
+
+/// Removes a feature class from a generated Java world, using its feature index to find
+/// the affected chunks. Returns the number of blocks removed.
+pub fn strip_feature(world_dir: &Path, class: StrippableClass) -> Result<usize, String> {
+    let feature = FeatureClass::from(class);
+    let mut index = FeatureIndex::load(world_dir)?;
+    let chunks = index.chunks.remove(&feature).unwrap_or_default();
+    if chunks.is_empty() {
+        println!("No {} recorded in the feature index", feature.name());
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for (region_xz, local_chunks) in by_region(&chunks) {
+        let path = region_path(world_dir, region_xz);
+        let Some(mut region) = open_region(&path, true)? else {
+            continue;
+        };
+        for local in local_chunks {
+            let Some(mut chunk) = read_chunk(&mut region, local, &path)? else {
+                continue;
+            };
+            let count = strip_chunk(&mut chunk, class);
+            if count > 0 {
+                write_chunk(&mut region, local, &chunk, &path)?;
+                removed += count;
+            }
+        }
+    }
+
+    index.save(world_dir)?;
+    Ok(removed)
+}
+
+/// Regenerates a feature class of a generated Java world from `source_dir`, a Java world
+/// generated with `--feature-index` for the same `--anchor` and scale from newer data, e.g.
+/// for a smaller bbox around the change. The class is stripped from the world, and the
+/// source's blocks of the class are copied into the chunks its index records, wherever the
+/// world has air there. Returns the number of blocks removed and copied.
+pub fn regenerate_feature(
+    world_dir: &Path,
+    source_dir: &Path,
+    class: StrippableClass,
+) -> Result<(usize, usize), String> {
+    let feature = FeatureClass::from(class);
+    let source_chunks = FeatureIndex::load(source_dir)?
+        .chunks
+        .remove(&feature)
+        .unwrap_or_default();
+    let removed = strip_feature(world_dir, class)?;
+
+    let mut copied = 0;
+    let mut grafted = BTreeSet::new();
+    for (region_xz, local_chunks) in by_region(&source_chunks) {
+        let source_path = region_path(source_dir, region_xz);
+        let path = region_path(world_dir, region_xz);
+        let (Some(mut source), Some(mut region)) =
+            (open_region(&source_path, false)?, open_region(&path, true)?)
+        else {
+            continue;
+        };
+        for local in local_chunks {
+            let (Some(mut source_chunk), Some(mut chunk)) = (
+                read_chunk(&mut source, local, &source_path)?,
+                read_chunk(&mut region, local, &path)?,
+            ) else {
+                continue;
+            };
+            let count = graft_chunk(&mut chunk, &mut source_chunk, class);
+            if count > 0 {
+                write_chunk(&mut region, local, &chunk, &path)?;
+                copied += count;
+                grafted.insert((
+                    region_xz.0 * 32 + local.0 as i32,
+                    region_xz.1 * 32 + local.1 as i32,
+                ));
+            }
+        }
+    }
+
+    // The class is now where it was copied to, so it can be stripped or regenerated again
+    let mut index = FeatureIndex::load(world_dir)?;
+    index.chunks.entry(feature).or_default().extend(grafted);
+    index.save(world_dir)?;
+    Ok((removed, copied))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(name: &str) -> Value {
+        Value::Compound(HashMap::from([(
+            "Name".to_string(),
+            Value::String(format!("minecraft:{name}")),
+        )]))
+    }
+
+    fn section(y: i8, palette: &[&str], indices: &[u16]) -> Value {
+        let mut states = HashMap::new();
+        SectionBlocks {
+            palette: palette.iter().map(|name| block(name)).collect(),
+            indices: indices.to_vec(),
+        }
+        .encode(&mut states);
+        Value::Compound(HashMap::from([
+            ("Y".to_string(), Value::Byte(y)),
+            ("block_states".to_string(), Value::Compound(states)),
+        ]))
+    }
+
+    fn names(chunk: &Value, section: usize) -> Vec<String> {
+        let Value::Compound(root) = chunk else {
+            unreachable!()
+        };
+        let Some(Value::List(sections)) = root.get("sections") else {
+            unreachable!()
+        };
+        let Value::Compound(map) = &sections[section] else {
+            unreachable!()
+        };
+        let Some(Value::Compound(states)) = map.get("block_states") else {
+            unreachable!()
+        };
+        let blocks = SectionBlocks::decode(states).unwrap();
+        (0..SECTION_VOLUME)
+            .map(|i| blocks.name(i).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sections_indexing_beyond_their_palette_are_left_alone() {
+        let mut indices = vec![0u16; SECTION_VOLUME];
+        indices[7] = 2;
+        indices[8] = 1;
+        let mut chunk = Value::Compound(HashMap::from([(
+            "sections".to_string(),
+            Value::List(vec![section(0, &["air", "oak_leaves", "stone"], &indices)]),
+        )]));
+        // Drop "stone" from the palette, leaving index 2 pointing past its end
+        let Value::Compound(root) = &mut chunk else {
+            unreachable!()
+        };
+        let Some(Value::List(sections)) = root.get_mut("sections") else {
+            unreachable!()
+        };
+        let Value::Compound(map) = &mut sections[0] else {
+            unreachable!()
+        };
+        let Some(Value::Compound(states)) = map.get_mut("block_states") else {
+            unreachable!()
+        };
+        let Some(Value::List(palette)) = states.get_mut("palette") else {
+            unreachable!()
+        };
+        palette.pop();
+        assert!(SectionBlocks::decode(states).is_none());
+        assert_eq!(strip_chunk(&mut chunk, StrippableClass::Vegetation), 0);
+    }
+
+    #[test]
+    fn records_chunks_per_feature_class() {
+        use crate::coordinate_system::cartesian::XZVector;
+        use crate::osm_parser::ProcessedNode;
+        let tree = ProcessedElement::Node(ProcessedNode {
+            id: 1,
+            tags: HashMap::from([("natural".to_string(), "tree".to_string())]),
+            x: 40,
+            z: -3,
+            exact: None,
+        });
+
+        let xzbbox =
+            XZBBox::rect_from_xz_lengths(100.0, 100.0).unwrap() - XZVector { dx: 50, dz: 50 };
+        let index = FeatureIndex::from_elements(&[tree], &xzbbox);
+        let chunks = &index.chunks[&FeatureClass::Vegetation];
+        assert!(chunks.contains(&(2, -1)));
+        assert!(chunks.contains(&(3, 0)));
+        assert_eq!(chunks.len(), 4);
+        assert!(!index.chunks.contains_key(&FeatureClass::Buildings));
+
+        // A river running on beyond the world is only indexed inside it
+        let river =
+            crate::test_utilities::way(2, &[("waterway", "river")], &[(-40, 0), (10_000, 0)]);
+        let index = FeatureIndex::from_elements(&[river], &xzbbox);
+        let chunks = &index.chunks[&FeatureClass::Water];
+        assert!(chunks.contains(&(3, 0)));
+        assert!(!chunks.contains(&(4, 0)));
+    }
+
+    #[test]
+    fn regenerates_the_class_from_another_world_where_there_is_air() {
+        // The source has a tree in column (0,0) and grass in column (1,0); the world has a
+        // player's stone block where the crown would go
+        let mut source_blocks = vec![0u16; SECTION_VOLUME];
+        source_blocks[0] = 1;
+        source_blocks[256] = 1;
+        source_blocks[2 * 256] = 2;
+        source_blocks[1] = 3;
+        let mut source = Value::Compound(HashMap::from([(
+            "sections".to_string(),
+            Value::List(vec![section(
+                0,
+                &["air", "oak_log", "oak_leaves", "short_grass"],
+                &source_blocks,
+            )]),
+        )]));
+        let mut world_blocks = vec![0u16; SECTION_VOLUME];
+        world_blocks[2 * 256] = 1;
+        let mut chunk = Value::Compound(HashMap::from([(
+            "sections".to_string(),
+            Value::List(vec![
+                section(1, &["air"], &[0; SECTION_VOLUME]),
+                section(0, &["air", "stone"], &world_blocks),
+            ]),
+        )]));
+
+        let copied = graft_chunk(&mut chunk, &mut source, StrippableClass::Vegetation);
+
+        assert_eq!(copied, 3);
+        let names = names(&chunk, 0);
+        assert_eq!(names[0], "oak_log");
+        assert_eq!(names[256], "oak_log");
+        assert_eq!(names[2 * 256], "stone");
+        assert_eq!(names[1], "short_grass");
+        assert!(matches!(&chunk, Value::Compound(root) if root.contains_key("isLightOn")));
+    }
+
+    #[test]
+    fn strips_trees_and_plants_but_keeps_other_logs() {
+        // Column (0,0): a trunk crossing into the next section, topped by leaves.
+        // Column (1,0): a log pillar without a crown. Column (2,0): grass on the ground.
+        let mut lower = vec![0u16; SECTION_VOLUME];
+        lower[0] = 1;
+        for y in 14..16 {
+            lower[y * 256] = 2;
+            lower[y * 256 + 1] = 2;
+        }
+        lower[2] = 1;
+        lower[256 + 2] = 3;
+        let mut upper = vec![0u16; SECTION_VOLUME];
+        upper[0] = 1;
+        upper[256] = 2;
+
+        // Sections deliberately out of order
+        let mut chunk = Value::Compound(HashMap::from([(
+            "sections".to_string(),
+            Value::List(vec![
+                section(1, &["air", "oak_log", "oak_leaves"], &upper),
+                section(0, &["air", "grass_block", "oak_log", "short_grass"], &lower),
+            ]),
+        )]));
+
+        let removed = strip_chunk(&mut chunk, StrippableClass::Vegetation);
+
+        // Trunk: 2 logs below and 1 log above the section border, plus the crown and grass
+        assert_eq!(removed, 5);
+        let lower = names(&chunk, 0);
+        assert_eq!(lower[0], "grass_block");
+        assert_eq!(lower[14 * 256], "air");
+        assert_eq!(lower[15 * 256 + 1], "oak_log");
+        assert_eq!(lower[256 + 2], "air");
+        let upper = names(&chunk, 1);
+        assert!(upper.iter().all(|name| name == "air"));
+    }
+}
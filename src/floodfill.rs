@@ -9,6 +9,13 @@ use std::time::{Duration, Instant};
 /// 25 million blocks ≈ 5000×5000; bitmap uses only ~3 MB at this size.
 const MAX_FLOOD_FILL_AREA: i64 = 25_000_000;
 
+/// A fill covering more than this multiple of its polygon's area has leaked
+const LEAK_AREA_RATIO: f64 = 2.0;
+/// Extra cells a fill may cover beyond the ratio, so tiny polygons are not flagged
+const LEAK_SLACK: f64 = 16.0;
+/// Most crossings split off a ring before the rest is left as it is
+const MAX_RING_SPLITS: usize = 64;
+
 /// A compact bitmap for visited-coordinate tracking during flood fill.
 ///
 /// Uses 1 bit per coordinate instead of ~48 bytes per entry in a `HashSet`.
@@ -101,6 +108,90 @@ pub fn flood_fill_area(
     }
 }
 
+/// Whether a fill of `filled` cells is far larger than the polygon it was computed for,
+/// the sign of a fill escaping a broken outline (the "ocean floods the town" bug).
+/// Rasterized outlines add about one cell per boundary block, so the perimeter is
+/// allowed on top of the area.
+pub fn fill_leaked(polygon_coords: &[(i32, i32)], filled: usize) -> bool {
+    if polygon_coords.len() < 3 {
+        return false;
+    }
+    let (twice_area, perimeter) = polygon_coords
+        .iter()
+        .zip(polygon_coords.iter().cycle().skip(1))
+        .fold((0i64, 0.0), |(area, length), (&(x0, z0), &(x1, z1))| {
+            let (dx, dz) = ((x1 - x0) as f64, (z1 - z0) as f64);
+            (
+                area + x0 as i64 * z1 as i64 - x1 as i64 * z0 as i64,
+                length + dx.hypot(dz),
+            )
+        });
+    let area = twice_area.unsigned_abs() as f64 / 2.0;
+    filled as f64 > area * LEAK_AREA_RATIO + perimeter + LEAK_SLACK
+}
+
+/// Point where segments a0-a1 and b0-b1 cross, if they do
+fn crossing(
+    (a0, a1): ((i32, i32), (i32, i32)),
+    (b0, b1): ((i32, i32), (i32, i32)),
+) -> Option<(i32, i32)> {
+    let (ax, az) = ((a1.0 - a0.0) as f64, (a1.1 - a0.1) as f64);
+    let (bx, bz) = ((b1.0 - b0.0) as f64, (b1.1 - b0.1) as f64);
+    let denominator = ax * bz - az * bx;
+    if denominator == 0.0 {
+        return None;
+    }
+    let (dx, dz) = ((b0.0 - a0.0) as f64, (b0.1 - a0.1) as f64);
+    let t = (dx * bz - dz * bx) / denominator;
+    let u = (dx * az - dz * ax) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then(|| {
+        (
+            (a0.0 as f64 + t * ax).round() as i32,
+            (a0.1 as f64 + t * az).round() as i32,
+        )
+    })
+}
+
+/// Splits a ring crossing itself, like a bow tie, into the simple rings it is made of, so
+/// each can be filled on its own. Rings are returned without their closing node.
+pub fn split_crossed_ring(polygon_coords: &[(i32, i32)]) -> Vec<Vec<(i32, i32)>> {
+    let mut ring = polygon_coords.to_vec();
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    let mut pending = vec![ring];
+    let mut rings = Vec::new();
+    while let Some(ring) = pending.pop() {
+        let n = ring.len();
+        let segment = |i: usize| (ring[i], ring[(i + 1) % n]);
+        // Segments that cross without sharing a node
+        let split = (0..n)
+            .flat_map(|i| (i + 2..n).map(move |j| (i, j)))
+            .filter(|&(i, j)| !(i == 0 && j == n - 1))
+            .find_map(|(i, j)| crossing(segment(i), segment(j)).map(|point| (i, j, point)));
+        match split {
+            Some((i, j, point)) if rings.len() + pending.len() < MAX_RING_SPLITS => {
+                let mut outer: Vec<(i32, i32)> = ring[..=i].to_vec();
+                outer.push(point);
+                outer.extend_from_slice(&ring[j + 1..]);
+                let mut loop_ring = vec![point];
+                loop_ring.extend_from_slice(&ring[i + 1..=j]);
+                for part in [outer, loop_ring] {
+                    let mut part: Vec<(i32, i32)> = part.into_iter().dedup().collect();
+                    if part.len() > 1 && part.first() == part.last() {
+                        part.pop();
+                    }
+                    if part.len() >= 3 {
+                        pending.push(part);
+                    }
+                }
+            }
+            _ => rings.push(ring),
+        }
+    }
+    rings
+}
+
 /// Optimized flood fill for larger polygons with multi-seed detection for complex shapes like U-shapes
 fn optimized_flood_fill_area(
     polygon_coords: &[(i32, i32)],
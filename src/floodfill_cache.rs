@@ -5,10 +5,15 @@
 //! sequential processing.
 
 use crate::coordinate_system::cartesian::XZBBox;
-use crate::floodfill::flood_fill_area;
-use crate::osm_parser::{ProcessedElement, ProcessedMemberRole, ProcessedRelation, ProcessedWay};
-use fnv::FnvHashMap;
+use crate::element_processing::assemble_rings;
+use crate::floodfill::{fill_leaked, flood_fill_area, split_crossed_ring};
+use crate::osm_parser::{
+    is_filled_multipolygon, ProcessedElement, ProcessedMemberRole, ProcessedRelation, ProcessedWay,
+};
+use colored::Colorize;
+use fnv::{FnvHashMap, FnvHashSet};
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::time::Duration;
 
 /// A memory-efficient bitmap for storing coordinates.
@@ -225,10 +230,16 @@ impl CoordinateBitmap {
 /// Type alias for building footprint bitmap (for backwards compatibility).
 pub type BuildingFootprintBitmap = CoordinateBitmap;
 
+/// Assembled rings of a multipolygon relation, each with its filled coordinates
+type RingFills = Vec<(ProcessedWay, Vec<(i32, i32)>)>;
+
 /// A cache of pre-computed flood fill results, keyed by element ID.
 pub struct FloodFillCache {
     /// Cached results: element_id -> filled coordinates
     way_cache: FnvHashMap<u64, Vec<(i32, i32)>>,
+    /// Assembled outer rings of the filled multipolygon relations, by relation ID. Their
+    /// fills, with the inner rings cut out, are cached under the ring IDs.
+    multipolygons: FnvHashMap<u64, Vec<ProcessedWay>>,
 }
 
 impl FloodFillCache {
@@ -236,13 +247,18 @@ impl FloodFillCache {
     pub fn new() -> Self {
         Self {
             way_cache: FnvHashMap::default(),
+            multipolygons: FnvHashMap::default(),
         }
     }
 
     /// Pre-computes flood fills for all elements that need them.
     ///
     /// This runs in parallel using Rayon, taking advantage of multiple CPU cores.
-    pub fn precompute(elements: &[ProcessedElement], timeout: Option<&Duration>) -> Self {
+    pub fn precompute(
+        elements: &[ProcessedElement],
+        xzbbox: &XZBBox,
+        timeout: Option<&Duration>,
+    ) -> Self {
         // Collect all ways that need flood fill
         let ways_needing_fill: Vec<&ProcessedWay> = elements
             .iter()
@@ -261,11 +277,17 @@ impl FloodFillCache {
         // Compute all way flood fills in parallel
         let way_results: Vec<(u64, Vec<(i32, i32)>)> = ways_needing_fill
             .par_iter()
-            .map(|way| {
-                let polygon_coords: Vec<(i32, i32)> =
-                    way.nodes.iter().map(|n| (n.x, n.z)).collect();
-                let filled = flood_fill_area(&polygon_coords, timeout);
-                (way.id, filled)
+            .map(|way| (way.id, Self::fill_way(way, timeout)))
+            .collect();
+
+        // Multipolygons are assembled into rings and their holes cut out of the fills
+        let multipolygon_results: Vec<(u64, RingFills)> = elements
+            .par_iter()
+            .filter_map(|el| match el {
+                ProcessedElement::Relation(rel) if is_filled_multipolygon(&rel.tags) => {
+                    Some((rel.id, Self::fill_multipolygon(rel, xzbbox, timeout)))
+                }
+                _ => None,
             })
             .collect();
 
@@ -274,10 +296,76 @@ impl FloodFillCache {
         for (id, filled) in way_results {
             cache.way_cache.insert(id, filled);
         }
+        for (id, rings) in multipolygon_results {
+            let mut ways = Vec::with_capacity(rings.len());
+            for (way, filled) in rings {
+                cache.way_cache.insert(way.id, filled);
+                ways.push(way);
+            }
+            cache.multipolygons.insert(id, ways);
+        }
 
         cache
     }
 
+    /// Fills each outer ring of a multipolygon, leaving out the blocks inside its inner rings.
+    /// The rings become ways with the relation's tags, so they are generated like closed
+    /// ways of the same kind.
+    fn fill_multipolygon(
+        rel: &ProcessedRelation,
+        xzbbox: &XZBBox,
+        timeout: Option<&Duration>,
+    ) -> RingFills {
+        let outers = assemble_rings(rel, ProcessedMemberRole::Outer, xzbbox);
+        if outers.is_empty() {
+            return Vec::new();
+        }
+        let mut holes: FnvHashSet<(i32, i32)> = FnvHashSet::default();
+        for (index, nodes) in assemble_rings(rel, ProcessedMemberRole::Inner, xzbbox)
+            .into_iter()
+            .enumerate()
+        {
+            let inner = ProcessedWay {
+                id: Self::ring_id(rel.id, 0x2000 | index),
+                nodes,
+                tags: Default::default(),
+            };
+            holes.extend(Self::fill_way(&inner, timeout));
+        }
+
+        outers
+            .into_iter()
+            .enumerate()
+            .map(|(index, nodes)| {
+                let way = ProcessedWay {
+                    id: Self::ring_id(rel.id, index),
+                    nodes,
+                    tags: rel.tags.clone(),
+                };
+                let mut filled = Self::fill_way(&way, timeout);
+                if !holes.is_empty() {
+                    filled.retain(|coord| !holes.contains(coord));
+                }
+                (way, filled)
+            })
+            .collect()
+    }
+
+    /// Synthetic ID of a multipolygon ring: bit 63 flags it, as for the rings of building
+    /// relations, and the 0x4000 slots keep it apart from those
+    fn ring_id(relation_id: u64, index: usize) -> u64 {
+        (1u64 << 63) | (relation_id << 16) | 0x4000 | (index as u64 & 0x3FFF)
+    }
+
+    /// The outer rings of a filled multipolygon relation, as ways with the relation's tags
+    /// whose cached fills leave its inner rings empty. Generating each ring on its own fills
+    /// the relation with the type of its tags, e.g. landuse=*, leisure=* or natural=*.
+    pub fn multipolygon_rings(&self, relation_id: u64) -> &[ProcessedWay] {
+        self.multipolygons
+            .get(&relation_id)
+            .map_or(&[], |rings| rings.as_slice())
+    }
+
     /// Gets cached flood fill result for a way, or computes it if not cached.
     ///
     /// Note: Combined ways created from relations (e.g., in `generate_natural_from_relation`)
@@ -295,9 +383,48 @@ impl FloodFillCache {
             cached.clone()
         } else {
             // Fallback: compute on demand for synthetic/combined ways from relations
-            let polygon_coords: Vec<(i32, i32)> = way.nodes.iter().map(|n| (n.x, n.z)).collect();
-            flood_fill_area(&polygon_coords, timeout)
+            Self::fill_way(way, timeout)
+        }
+    }
+
+    /// Flood fills a way's outline. An outline crossing itself, like a bow tie, is split
+    /// into its simple rings and each is filled. A fill that leaked far beyond its ring is
+    /// dropped with a warning naming the element, rather than flooding the surrounding map.
+    fn fill_way(way: &ProcessedWay, timeout: Option<&Duration>) -> Vec<(i32, i32)> {
+        let polygon_coords: Vec<(i32, i32)> = way.nodes.iter().map(|n| (n.x, n.z)).collect();
+        let rings = split_crossed_ring(&polygon_coords);
+        if rings.len() <= 1 {
+            return Self::fill_ring(way.id, &polygon_coords, timeout);
         }
+        let mut filled: Vec<(i32, i32)> = rings
+            .iter()
+            .flat_map(|ring| {
+                let mut closed = ring.clone();
+                closed.push(ring[0]);
+                Self::fill_ring(way.id, &closed, timeout)
+            })
+            .collect();
+        filled.sort_unstable();
+        filled.dedup();
+        filled
+    }
+
+    fn fill_ring(
+        id: u64,
+        polygon_coords: &[(i32, i32)],
+        timeout: Option<&Duration>,
+    ) -> Vec<(i32, i32)> {
+        let filled = flood_fill_area(polygon_coords, timeout);
+        if fill_leaked(polygon_coords, filled.len()) {
+            eprintln!(
+                "{} Flood fill of element {} covered {} blocks, far more than its outline; skipping the fill",
+                "Warning:".yellow().bold(),
+                id,
+                filled.len()
+            );
+            return Vec::new();
+        }
+        filled
     }
 
     /// Gets cached flood fill result for a ProcessedElement (Way only).
@@ -327,7 +454,8 @@ impl FloodFillCache {
     /// - amenity -> amenities::generate_amenities
     /// - natural (except tree) -> natural::generate_natural
     /// - highway with area=yes -> highways::generate_highways (area fill)
-    fn way_needs_flood_fill(way: &ProcessedWay) -> bool {
+    /// - allotments=plot -> allotments::generate_mapped_plot
+    pub(crate) fn way_needs_flood_fill(way: &ProcessedWay) -> bool {
         way.tags.contains_key("building")
             || way.tags.contains_key("building:part")
             || way.tags.contains_key("landuse")
@@ -343,6 +471,8 @@ impl FloodFillCache {
                 && way.tags.get("area").map(|v| v == "yes").unwrap_or(false))
             // Historic tomb polygons (e.g. tomb=pyramid)
             || way.tags.get("tomb").map(|v| v == "pyramid").unwrap_or(false)
+            // Mapped allotment plots, usually without a landuse tag of their own
+            || way.tags.get("allotments").map(|v| v == "plot").unwrap_or(false)
     }
 
     /// Collects all building footprint coordinates from the pre-computed cache.
@@ -426,6 +556,14 @@ impl FloodFillCache {
                                 }
                             }
                         }
+                        // Assembled multipolygon rings, with their holes left out
+                        for ring in self.multipolygon_rings(rel.id) {
+                            if let Some(cached) = self.way_cache.get(&ring.id) {
+                                for &(x, z) in cached {
+                                    dry_land.set(x, z);
+                                }
+                            }
+                        }
                     }
                 }
                 _ => {}
@@ -435,6 +573,71 @@ impl FloodFillCache {
         dry_land
     }
 
+    /// Collects the coordinates of mapped allotment plots (`allotments=plot`).
+    pub fn collect_allotment_plot_mask(
+        &self,
+        elements: &[ProcessedElement],
+        xzbbox: &XZBBox,
+    ) -> CoordinateBitmap {
+        let mut plots = CoordinateBitmap::new(xzbbox);
+
+        for element in elements {
+            if let ProcessedElement::Way(way) = element {
+                if way.tags.get("allotments").is_some_and(|v| v == "plot") {
+                    if let Some(cached) = self.way_cache.get(&way.id) {
+                        for &(x, z) in cached {
+                            plots.set(x, z);
+                        }
+                    }
+                }
+            }
+        }
+
+        plots
+    }
+
+    /// Collects the coordinates of fields, meadows and orchards, whose topsoil the plough
+    /// keeps deep (`--fillground`).
+    pub fn collect_ploughed_land_mask(
+        &self,
+        elements: &[ProcessedElement],
+        xzbbox: &XZBBox,
+    ) -> CoordinateBitmap {
+        let mut ploughed = CoordinateBitmap::new(xzbbox);
+        let is_ploughed = |tags: &HashMap<String, String>| {
+            tags.get("landuse").is_some_and(|v| {
+                matches!(
+                    v.as_str(),
+                    "farmland" | "meadow" | "orchard" | "vineyard" | "allotments"
+                )
+            })
+        };
+
+        for element in elements {
+            match element {
+                ProcessedElement::Way(way) if is_ploughed(&way.tags) => {
+                    if let Some(cached) = self.way_cache.get(&way.id) {
+                        for &(x, z) in cached {
+                            ploughed.set(x, z);
+                        }
+                    }
+                }
+                ProcessedElement::Relation(rel) if is_ploughed(&rel.tags) => {
+                    for ring in self.multipolygon_rings(rel.id) {
+                        if let Some(cached) = self.way_cache.get(&ring.id) {
+                            for &(x, z) in cached {
+                                ploughed.set(x, z);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        ploughed
+    }
+
     /// Collects coordinates for OSM features that are explicitly mapped as water.
     pub fn collect_explicit_water_mask(
         &self,
@@ -651,6 +854,13 @@ impl FloodFillCache {
             self.way_cache.remove(&id);
         }
     }
+
+    /// Removes the assembled rings of a multipolygon relation and their fills.
+    pub fn remove_multipolygon(&mut self, relation_id: u64) {
+        for ring in self.multipolygons.remove(&relation_id).unwrap_or_default() {
+            self.way_cache.remove(&ring.id);
+        }
+    }
 }
 
 impl Default for FloodFillCache {
@@ -681,6 +891,82 @@ mod tests {
         assert!(!FloodFillCache::way_contributes_to_dry_land(&way));
         assert!(!FloodFillCache::relation_contributes_to_dry_land(&rel));
     }
+
+    #[test]
+    fn fills_far_larger_than_their_outline_are_leaks() {
+        let square = [(0, 0), (20, 0), (20, 20), (0, 20), (0, 0)];
+        // The filled square with its outline cells
+        assert!(!fill_leaked(&square, 21 * 21));
+        assert!(fill_leaked(&square, 5000));
+
+        // The halves of a crossed outline cancel out, so a bbox-sized fill is caught
+        let bow_tie = [(0, 0), (40, 40), (40, 0), (0, 40), (0, 0)];
+        assert!(fill_leaked(&bow_tie, 41 * 41));
+
+        // Its halves are filled on their own instead
+        let mut halves = split_crossed_ring(&bow_tie);
+        halves.sort();
+        assert_eq!(
+            halves,
+            vec![
+                vec![(0, 0), (20, 20), (0, 40)],
+                vec![(20, 20), (40, 40), (40, 0)]
+            ]
+        );
+        assert_eq!(split_crossed_ring(&square).len(), 1);
+    }
+
+    #[test]
+    fn multipolygon_fills_leave_the_inner_rings_empty() {
+        use crate::osm_parser::{ProcessedMember, ProcessedNode};
+        use std::sync::Arc;
+
+        let way = |id: u64, points: &[(i32, i32)]| ProcessedMember {
+            role: if id < 20 {
+                ProcessedMemberRole::Outer
+            } else {
+                ProcessedMemberRole::Inner
+            },
+            way: Arc::new(ProcessedWay {
+                id,
+                nodes: points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(x, z))| ProcessedNode {
+                        id: id * 100 + i as u64,
+                        tags: HashMap::new(),
+                        x,
+                        z,
+                        exact: None,
+                    })
+                    .collect(),
+                tags: HashMap::new(),
+            }),
+        };
+        // The outline is split over two open ways; the inner ring is a hole in the meadow
+        let rel = ProcessedRelation {
+            id: 7,
+            tags: HashMap::from([
+                ("type".to_string(), "multipolygon".to_string()),
+                ("landuse".to_string(), "meadow".to_string()),
+            ]),
+            members: vec![
+                way(10, &[(0, 0), (20, 0), (20, 20)]),
+                way(11, &[(20, 20), (0, 20), (0, 0)]),
+                way(20, &[(8, 8), (12, 8), (12, 12), (8, 12), (8, 8)]),
+            ],
+        };
+        let xzbbox = XZBBox::rect_from_xz_lengths(100.0, 100.0).unwrap();
+        let cache = FloodFillCache::precompute(&[ProcessedElement::Relation(rel)], &xzbbox, None);
+
+        let rings = cache.multipolygon_rings(7);
+        assert_eq!(rings.len(), 1);
+        assert_eq!(rings[0].tags["landuse"], "meadow");
+        let filled = cache.get_or_compute(&rings[0], None);
+        assert!(filled.contains(&(4, 4)));
+        assert!(!filled.contains(&(10, 10)));
+        assert!(cache.multipolygon_rings(8).is_empty());
+    }
 }
 
 /// Configures the global Rayon thread pool with a CPU usage cap.
@@ -0,0 +1,334 @@
+//! The ground under the surface for `--fillground`.
+//!
+//! Instead of one slab of stone, each column gets topsoil as deep as its land cover keeps
+//! it, glacial till below that and bedrock at a depth set by the region: chalk in northern
+//! Jutland and on Møn, Danian limestone around Stevns, Faxe and Copenhagen, granite on
+//! Bornholm. Elsewhere in Denmark the till runs deep over plain stone. Erratic boulders sit
+//! in the till and flint runs in bands through the chalk and limestone.
+
+use crate::block_definitions::*;
+use crate::coordinate_system::geographic::LLBBox;
+use crate::data_processing::MIN_Y;
+use crate::data_sources::Realm;
+use crate::deterministic_rng::coord_rng;
+use crate::world_editor::WorldEditor;
+use rand::Rng;
+
+/// Salt for the chalk texture of coastal cliff faces and the chalk bedrock
+const CHALK_SALT: u64 = 0xc4a1_c000;
+/// Salt for the texture of the limestone bedrock
+const LIMESTONE_SALT: u64 = 0x1135_7000;
+/// Salt for the mix of clay, gravel and coarse dirt in the till
+const TILL_SALT: u64 = 0x7111_0000;
+/// Salt for the erratic boulders in the till
+const BOULDER_SALT: u64 = 0xb01d_e000;
+/// Every this many blocks a dark flint band runs through the chalk
+const FLINT_BAND_SPACING: i32 = 5;
+/// Flint lies in sparser bands in the limestone
+const LIMESTONE_FLINT_SPACING: i32 = 7;
+/// Blocks of bedrock under its top that get strata; below them it is one block, which keeps
+/// the fill fast and its chunk sections uniform
+const STRATA_DEPTH: i32 = 24;
+/// Depth of the weathered, brown top of the till
+const WEATHERED_TILL_DEPTH: i32 = 3;
+/// Side of the grid cells that hold at most one erratic boulder each
+const BOULDER_CELL: i32 = 16;
+/// Chance that a cell holds a boulder
+const BOULDER_CHANCE: f64 = 0.2;
+
+/// Bedrock under the glacial deposits of the region a world lies in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Geology {
+    /// Outside Denmark: the plain stone fill of before
+    Stone,
+    /// Deep till over stone, most of Denmark
+    Till,
+    /// Chalk close under the till, as in Himmerland, Thy, Mors and on Møn
+    Chalk,
+    /// Danian limestone close under the till, as around Stevns, Faxe and Copenhagen
+    Limestone,
+    /// Granite under a thin cover, Bornholm
+    Granite,
+}
+
+impl Geology {
+    /// Region of the bbox centre
+    pub fn of(bbox: &LLBBox) -> Self {
+        if Realm::of(bbox) != Realm::Denmark {
+            return Geology::Stone;
+        }
+        let lat = (bbox.min().lat() + bbox.max().lat()) / 2.0;
+        let lng = (bbox.min().lng() + bbox.max().lng()) / 2.0;
+        let within = |min_lat: f64, max_lat: f64, min_lng: f64, max_lng: f64| {
+            (min_lat..=max_lat).contains(&lat) && (min_lng..=max_lng).contains(&lng)
+        };
+        if lng > 14.6 {
+            Geology::Granite
+        } else if within(55.15, 55.45, 11.95, 12.5) || within(55.55, 55.8, 12.35, 12.7) {
+            Geology::Limestone
+        } else if within(54.9, 55.1, 12.05, 12.6) || within(56.55, 57.2, 8.2, 10.4) {
+            Geology::Chalk
+        } else {
+            Geology::Till
+        }
+    }
+
+    /// Depth of the top of the bedrock under the surface
+    fn bedrock_depth(self) -> i32 {
+        match self {
+            Geology::Stone => 3,
+            Geology::Granite => 5,
+            Geology::Chalk => 12,
+            Geology::Limestone => 15,
+            Geology::Till => 40,
+        }
+    }
+
+    /// Block of the bedrock at height `y`
+    fn bedrock_block(self, x: i32, y: i32, z: i32) -> Block {
+        match self {
+            Geology::Stone | Geology::Till => STONE,
+            Geology::Chalk => chalk_stratum(x, y, z),
+            Geology::Limestone => limestone_stratum(x, y, z),
+            Geology::Granite => {
+                if coord_rng(x, z, y as u64).random_bool(0.15) {
+                    DIORITE
+                } else {
+                    GRANITE
+                }
+            }
+        }
+    }
+
+    /// Bedrock under its strata
+    fn deep_block(self) -> Block {
+        match self {
+            Geology::Stone | Geology::Till => STONE,
+            Geology::Chalk => WHITE_CONCRETE,
+            Geology::Limestone => SMOOTH_SANDSTONE,
+            Geology::Granite => GRANITE,
+        }
+    }
+}
+
+/// What covers the ground of a column, which sets how deep its topsoil goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroundCover {
+    /// Built-up ground: a layer of gravel fill under the paving
+    Paved,
+    /// Fields, meadows and orchards with a deep plough layer
+    Ploughed,
+    Natural,
+}
+
+impl GroundCover {
+    /// Depth of the topsoil, counting the surface block
+    fn topsoil_depth(self) -> i32 {
+        match self {
+            GroundCover::Paved | GroundCover::Natural => 3,
+            GroundCover::Ploughed => 5,
+        }
+    }
+}
+
+/// Block of a coastal cliff face or chalk bedrock at height `y`: white chalk with dark flint
+/// bands, as in the strata of Møns Klint and Stevns Klint.
+pub fn chalk_stratum(x: i32, y: i32, z: i32) -> Block {
+    if y.rem_euclid(FLINT_BAND_SPACING) == 0 {
+        return if coord_rng(x, z, CHALK_SALT ^ 1).random_bool(0.7) {
+            GRAY_CONCRETE
+        } else {
+            POLISHED_ANDESITE
+        };
+    }
+    if coord_rng(x, z, CHALK_SALT ^ y as u64).random_bool(0.25) {
+        DIORITE
+    } else {
+        WHITE_CONCRETE
+    }
+}
+
+/// Block of the limestone bedrock at height `y`: yellowish limestone with flint nodules
+/// scattered along sparse bands
+fn limestone_stratum(x: i32, y: i32, z: i32) -> Block {
+    let mut rng = coord_rng(x, z, LIMESTONE_SALT ^ y as u64);
+    if y.rem_euclid(LIMESTONE_FLINT_SPACING) == 0 && rng.random_bool(0.35) {
+        GRAY_CONCRETE
+    } else if rng.random_bool(0.2) {
+        WHITE_TERRACOTTA
+    } else {
+        SMOOTH_SANDSTONE
+    }
+}
+
+/// Block of the till at `depth` below the surface: brown and gravelly where weathered,
+/// grey clay with lenses of gravel below
+fn till_block(x: i32, depth: i32, z: i32, weathered_bottom: i32) -> Block {
+    let roll: f64 = coord_rng(x, z, TILL_SALT ^ depth as u64).random();
+    if depth <= weathered_bottom {
+        if roll < 0.7 {
+            COARSE_DIRT
+        } else {
+            GRAVEL
+        }
+    } else if roll < 0.8 {
+        CLAY
+    } else if roll < 0.9 {
+        GRAVEL
+    } else {
+        COARSE_DIRT
+    }
+}
+
+/// A round erratic boulder in the till
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Boulder {
+    x: i32,
+    z: i32,
+    depth: i32,
+    radius: i32,
+    rock: Block,
+}
+
+impl Boulder {
+    fn contains(&self, x: i32, z: i32, depth: i32) -> bool {
+        let (dx, dz, dy) = (x - self.x, z - self.z, depth - self.depth);
+        dx * dx + dz * dz + dy * dy <= self.radius * self.radius + self.radius
+    }
+}
+
+/// Erratic boulder of the cell around (x, z), if any, lying between the two depths
+fn cell_boulder(x: i32, z: i32, min_depth: i32, max_depth: i32) -> Option<Boulder> {
+    let (cell_x, cell_z) = (x.div_euclid(BOULDER_CELL), z.div_euclid(BOULDER_CELL));
+    let mut rng = coord_rng(cell_x, cell_z, BOULDER_SALT);
+    if !rng.random_bool(BOULDER_CHANCE) {
+        return None;
+    }
+    let radius = rng.random_range(1..=2);
+    if max_depth - min_depth < radius * 2 {
+        return None;
+    }
+    Some(Boulder {
+        x: cell_x * BOULDER_CELL + rng.random_range(radius..BOULDER_CELL - radius),
+        z: cell_z * BOULDER_CELL + rng.random_range(radius..BOULDER_CELL - radius),
+        depth: rng.random_range(min_depth + radius..=max_depth - radius),
+        radius,
+        rock: if rng.random_bool(0.6) {
+            GRANITE
+        } else {
+            DIORITE
+        },
+    })
+}
+
+/// Block at `depth` below the surface of column (x, z), from the bottom of the topsoil down
+fn subsurface_block(
+    geology: Geology,
+    cover: GroundCover,
+    x: i32,
+    z: i32,
+    depth: i32,
+    y: i32,
+) -> Block {
+    let topsoil = cover.topsoil_depth();
+    let bedrock = geology.bedrock_depth().max(topsoil);
+    if depth < topsoil {
+        return if cover == GroundCover::Paved {
+            GRAVEL
+        } else {
+            DIRT
+        };
+    }
+    if depth >= bedrock {
+        return geology.bedrock_block(x, y, z);
+    }
+    if let Some(boulder) = cell_boulder(x, z, topsoil, bedrock - 1) {
+        if boulder.contains(x, z, depth) {
+            return boulder.rock;
+        }
+    }
+    till_block(x, depth, z, topsoil + WEATHERED_TILL_DEPTH - 1)
+}
+
+/// Fills the column (x, z) under the two subsurface blocks of the ground pass down to just
+/// above the bedrock layer of the world. Blocks placed by element processing are kept.
+pub fn fill_column(
+    editor: &mut WorldEditor,
+    geology: Geology,
+    cover: GroundCover,
+    x: i32,
+    z: i32,
+    ground_y: i32,
+) {
+    let strata_bottom =
+        (ground_y - geology.bedrock_depth().max(cover.topsoil_depth()) - STRATA_DEPTH)
+            .max(MIN_Y + 1);
+    for y in (strata_bottom..=ground_y - 3).rev() {
+        let block = subsurface_block(geology, cover, x, z, ground_y - y, y);
+        editor.set_block_if_absent_absolute(block, x, y, z);
+    }
+    editor.fill_column_absolute(
+        geology.deep_block(),
+        x,
+        z,
+        MIN_Y + 1,
+        strata_bottom - 1,
+        true,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbox(lat: f64, lng: f64) -> LLBBox {
+        LLBBox::new(lat - 0.01, lng - 0.01, lat + 0.01, lng + 0.01).unwrap()
+    }
+
+    #[test]
+    fn regions_get_their_bedrock() {
+        assert_eq!(Geology::of(&bbox(55.10, 14.90)), Geology::Granite);
+        assert_eq!(Geology::of(&bbox(55.29, 12.45)), Geology::Limestone);
+        assert_eq!(Geology::of(&bbox(55.68, 12.57)), Geology::Limestone);
+        assert_eq!(Geology::of(&bbox(54.97, 12.54)), Geology::Chalk);
+        assert_eq!(Geology::of(&bbox(57.05, 9.92)), Geology::Chalk);
+        assert_eq!(Geology::of(&bbox(56.16, 10.20)), Geology::Till);
+        assert_eq!(Geology::of(&bbox(59.91, 10.75)), Geology::Stone);
+    }
+
+    #[test]
+    fn columns_run_from_topsoil_through_till_to_bedrock() {
+        let column =
+            |cover, depth| subsurface_block(Geology::Chalk, cover, 3, 3, depth, 60 - depth);
+        assert_eq!(column(GroundCover::Ploughed, 4), DIRT);
+        assert_eq!(column(GroundCover::Paved, 3), GRAVEL);
+        assert!([COARSE_DIRT, GRAVEL, GRANITE, DIORITE].contains(&column(GroundCover::Natural, 4)));
+        let bedrock = column(GroundCover::Natural, 20);
+        assert!([WHITE_CONCRETE, DIORITE, GRAY_CONCRETE, POLISHED_ANDESITE].contains(&bedrock));
+
+        // Outside Denmark the stone starts right under the topsoil, as before
+        assert_eq!(
+            subsurface_block(Geology::Stone, GroundCover::Natural, 0, 0, 3, 0),
+            STONE
+        );
+    }
+
+    #[test]
+    fn boulders_stay_inside_their_cell_and_the_till() {
+        for cell in 0..200 {
+            let (x, z) = (cell * BOULDER_CELL + 5, -cell * BOULDER_CELL);
+            if let Some(boulder) = cell_boulder(x, z, 3, 39) {
+                assert_eq!(
+                    boulder.x.div_euclid(BOULDER_CELL),
+                    x.div_euclid(BOULDER_CELL)
+                );
+                assert_eq!(
+                    boulder.z.div_euclid(BOULDER_CELL),
+                    z.div_euclid(BOULDER_CELL)
+                );
+                assert!(boulder.depth - boulder.radius >= 3);
+                assert!(boulder.depth + boulder.radius <= 39);
+            }
+        }
+    }
+}
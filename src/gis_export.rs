@@ -0,0 +1,310 @@
+//! Export of generation layers as GIS files.
+//!
+//! Writes the parsed OSM elements, the computed road corridors and the terrain grid
+//! next to the generated world so the Minecraft output can be overlaid with the
+//! source data in QGIS/ArcGIS during planning workshops.
+//!
+//! Layers written to the export directory:
+//! - `elements.geojson` - every processed node, way and relation with its tags
+//! - `road_corridors.geojson` - ground-level highway corridors as polygons, using the
+//!   same widths as block placement
+//! - `terrain.asc` + `terrain.prj` - ESRI ASCII grid of the Minecraft ground Y per block
+
+use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::element_processing::highways::highway_mask_radius;
+use crate::ground::Ground;
+use crate::osm_parser::{ProcessedElement, ProcessedMemberRole, ProcessedNode, ProcessedWay};
+use colored::Colorize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// WKT for WGS84, written as `.prj` sidecar so GIS tools pick up the CRS of the grid.
+const WGS84_PRJ: &str = r#"GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563]],PRIMEM["Greenwich",0],UNIT["degree",0.0174532925199433]]"#;
+
+/// Writes all GIS layers for one generation job into `dir`.
+///
+/// Elements are expected in untransformed parser coordinates (before `transform_map`),
+/// so that the layers line up with the real-world geography.
+pub fn export_gis_layers(
+    dir: &Path,
+    elements: &[ProcessedElement],
+    ground: &Ground,
+    transformer: &CoordTransformer,
+    xzbbox: &XZBBox,
+    scale: f64,
+    mapped_widths: bool,
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create GIS export directory: {e}"))?;
+
+    let features: Vec<Value> = elements
+        .iter()
+        .filter_map(|element| element_feature(element, transformer))
+        .collect();
+    write_feature_collection(&dir.join("elements.geojson"), features)?;
+
+    let corridors: Vec<Value> = elements
+        .iter()
+        .filter_map(|element| match element {
+            ProcessedElement::Way(way) => {
+                road_corridor_feature(way, transformer, scale, mapped_widths)
+            }
+            _ => None,
+        })
+        .collect();
+    write_feature_collection(&dir.join("road_corridors.geojson"), corridors)?;
+
+    write_terrain_grid(dir, ground, transformer, xzbbox)?;
+
+    println!(
+        "GIS layers exported to: {}",
+        dir.display().to_string().bright_white().bold()
+    );
+    Ok(())
+}
+
+fn write_feature_collection(path: &Path, features: Vec<Value>) -> Result<(), String> {
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+
+    let file =
+        File::create(path).map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+    serde_json::to_writer(BufWriter::new(file), &collection)
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Converts a block coordinate to a GeoJSON `[lng, lat]` position at the block center.
+fn position(transformer: &CoordTransformer, x: i32, z: i32) -> Value {
+    let (lat, lng) = transformer.inverse_transform(x as f64 + 0.5, z as f64 + 0.5);
+    json!([lng, lat])
+}
+
+fn line_coordinates(transformer: &CoordTransformer, nodes: &[ProcessedNode]) -> Vec<Value> {
+    nodes
+        .iter()
+        .map(|node| position(transformer, node.x, node.z))
+        .collect()
+}
+
+fn is_closed(nodes: &[ProcessedNode]) -> bool {
+    nodes.len() >= 4 && nodes.first().map(|n| (n.x, n.z)) == nodes.last().map(|n| (n.x, n.z))
+}
+
+fn tag_properties(id: u64, kind: &str, tags: &HashMap<String, String>) -> Map<String, Value> {
+    let mut properties = Map::new();
+    properties.insert("osm_id".to_string(), json!(id));
+    properties.insert("osm_type".to_string(), json!(kind));
+    for (key, value) in tags {
+        properties.insert(key.clone(), json!(value));
+    }
+    properties
+}
+
+fn way_geometry(transformer: &CoordTransformer, way: &ProcessedWay) -> Option<Value> {
+    if way.nodes.len() < 2 {
+        return None;
+    }
+    let coordinates = line_coordinates(transformer, &way.nodes);
+    Some(if is_closed(&way.nodes) {
+        json!({ "type": "Polygon", "coordinates": [coordinates] })
+    } else {
+        json!({ "type": "LineString", "coordinates": coordinates })
+    })
+}
+
+pub(crate) fn element_feature(
+    element: &ProcessedElement,
+    transformer: &CoordTransformer,
+) -> Option<Value> {
+    let geometry = match element {
+        ProcessedElement::Node(node) => json!({
+            "type": "Point",
+            "coordinates": position(transformer, node.x, node.z),
+        }),
+        ProcessedElement::Way(way) => way_geometry(transformer, way)?,
+        ProcessedElement::Relation(rel) => {
+            // Relations are exported as the union of their member lines; the member roles
+            // are preserved as a property so outer/inner rings can be told apart.
+            let lines: Vec<Value> = rel
+                .members
+                .iter()
+                .filter(|member| member.way.nodes.len() >= 2)
+                .map(|member| Value::Array(line_coordinates(transformer, &member.way.nodes)))
+                .collect();
+            if lines.is_empty() {
+                return None;
+            }
+            json!({ "type": "MultiLineString", "coordinates": lines })
+        }
+    };
+
+    let mut properties = tag_properties(element.id(), element.kind(), element.tags());
+    if let ProcessedElement::Relation(rel) = element {
+        let roles: Vec<&str> = rel
+            .members
+            .iter()
+            .filter(|member| member.way.nodes.len() >= 2)
+            .map(|member| match member.role {
+                ProcessedMemberRole::Outer => "outer",
+                ProcessedMemberRole::Inner => "inner",
+                ProcessedMemberRole::Part => "part",
+            })
+            .collect();
+        properties.insert("member_roles".to_string(), json!(roles));
+    }
+
+    Some(json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": properties,
+    }))
+}
+
+/// Builds the drivable corridor of a highway as one rectangle per segment, using the
+/// same half-width that block placement uses for the ground highway mask.
+fn road_corridor_feature(
+    way: &ProcessedWay,
+    transformer: &CoordTransformer,
+    scale: f64,
+    mapped_widths: bool,
+) -> Option<Value> {
+    if way.nodes.len() < 2 || way.tags.get("area").is_some_and(|v| v == "yes") {
+        return None;
+    }
+    let radius = highway_mask_radius(&way.tags, scale, mapped_widths)? as f64 + 0.5;
+
+    let mut polygons: Vec<Value> = Vec::new();
+    for pair in way.nodes.windows(2) {
+        let (x1, z1) = (pair[0].x as f64 + 0.5, pair[0].z as f64 + 0.5);
+        let (x2, z2) = (pair[1].x as f64 + 0.5, pair[1].z as f64 + 0.5);
+        let (dx, dz) = (x2 - x1, z2 - z1);
+        let length = (dx * dx + dz * dz).sqrt();
+        if length == 0.0 {
+            continue;
+        }
+        let (nx, nz) = (-dz / length * radius, dx / length * radius);
+
+        let ring: Vec<Value> = [
+            (x1 + nx, z1 + nz),
+            (x2 + nx, z2 + nz),
+            (x2 - nx, z2 - nz),
+            (x1 - nx, z1 - nz),
+            (x1 + nx, z1 + nz),
+        ]
+        .iter()
+        .map(|&(x, z)| {
+            let (lat, lng) = transformer.inverse_transform(x, z);
+            json!([lng, lat])
+        })
+        .collect();
+        polygons.push(json!([ring]));
+    }
+
+    if polygons.is_empty() {
+        return None;
+    }
+
+    let mut properties = tag_properties(way.id, "way", &way.tags);
+    properties.insert("width_m".to_string(), json!(radius * 2.0 / scale));
+
+    Some(json!({
+        "type": "Feature",
+        "geometry": { "type": "MultiPolygon", "coordinates": polygons },
+        "properties": properties,
+    }))
+}
+
+/// Writes the ground level of every block as an ESRI ASCII grid in WGS84 degrees.
+fn write_terrain_grid(
+    dir: &Path,
+    ground: &Ground,
+    transformer: &CoordTransformer,
+    xzbbox: &XZBBox,
+) -> Result<(), String> {
+    let ncols = (xzbbox.max_x() - xzbbox.min_x() + 1).max(0) as usize;
+    let nrows = (xzbbox.max_z() - xzbbox.min_z() + 1).max(0) as usize;
+    if ncols == 0 || nrows == 0 {
+        return Ok(());
+    }
+
+    // Grid cells are not square in degrees, so use GDAL's DX/DY extension.
+    let (top_lat, left_lng) =
+        transformer.inverse_transform(xzbbox.min_x() as f64, xzbbox.min_z() as f64);
+    let (bottom_lat, right_lng) =
+        transformer.inverse_transform((xzbbox.max_x() + 1) as f64, (xzbbox.max_z() + 1) as f64);
+    let dx = (right_lng - left_lng) / ncols as f64;
+    let dy = (top_lat - bottom_lat) / nrows as f64;
+
+    let path = dir.join("terrain.asc");
+    let file =
+        File::create(&path).map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    let write_err = |e: std::io::Error| format!("Failed to write {}: {e}", path.display());
+
+    writeln!(out, "ncols {ncols}").map_err(write_err)?;
+    writeln!(out, "nrows {nrows}").map_err(write_err)?;
+    writeln!(out, "xllcorner {left_lng}").map_err(write_err)?;
+    writeln!(out, "yllcorner {bottom_lat}").map_err(write_err)?;
+    writeln!(out, "dx {dx}").map_err(write_err)?;
+    writeln!(out, "dy {dy}").map_err(write_err)?;
+    writeln!(out, "NODATA_value -9999").map_err(write_err)?;
+
+    // Rows run north to south, which matches increasing Minecraft Z.
+    let mut line = String::with_capacity(ncols * 4);
+    for z in 0..nrows as i32 {
+        line.clear();
+        for x in 0..ncols as i32 {
+            if x > 0 {
+                line.push(' ');
+            }
+            line.push_str(&ground.level(XZPoint::new(x, z)).to_string());
+        }
+        writeln!(out, "{line}").map_err(write_err)?;
+    }
+    out.flush().map_err(write_err)?;
+
+    std::fs::write(dir.join("terrain.prj"), WGS84_PRJ)
+        .map_err(|e| format!("Failed to write terrain.prj: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate_system::geographic::LLBBox;
+    use crate::test_utilities::node;
+
+    #[test]
+    fn closed_ways_export_as_polygons() {
+        let llbbox = LLBBox::new(55.0, 10.0, 55.01, 10.01).unwrap();
+        let (transformer, _) = CoordTransformer::llbbox_to_xzbbox(&llbbox, 1.0).unwrap();
+
+        let way = ProcessedElement::Way(ProcessedWay {
+            id: 7,
+            nodes: vec![
+                node(1, 0, 0, &[]),
+                node(2, 10, 0, &[]),
+                node(3, 10, 10, &[]),
+                node(1, 0, 0, &[]),
+            ],
+            tags: HashMap::from([("building".to_string(), "yes".to_string())]),
+        });
+
+        let feature = element_feature(&way, &transformer).unwrap();
+        assert_eq!(feature["geometry"]["type"], "Polygon");
+        assert_eq!(feature["properties"]["osm_id"], 7);
+        assert_eq!(feature["properties"]["building"], "yes");
+
+        let first = &feature["geometry"]["coordinates"][0][0];
+        let lng = first[0].as_f64().unwrap();
+        let lat = first[1].as_f64().unwrap();
+        assert!(
+            llbbox.contains(&crate::coordinate_system::geographic::LLPoint::new(lat, lng).unwrap())
+        );
+    }
+}
@@ -1,12 +1,18 @@
 use crate::args::Args;
-use crate::coordinate_system::{cartesian::XZPoint, geographic::LLBBox};
-use crate::elevation_data::{fetch_elevation_data, ElevationData};
-use crate::progress::emit_gui_progress_update;
+use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
+use crate::coordinate_system::geographic::LLBBox;
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::elevation_data::{fetch_elevation_data, ElevationData, HeightMapping};
+use crate::progress::{cancel_requested, emit_gui_progress_update};
 #[cfg(feature = "gui")]
 use crate::telemetry::{send_log, LogLevel};
 use colored::Colorize;
 use image::{Rgb, RgbImage};
 
+/// Distance in blocks to each side used for the slope central difference.
+/// Sampling over more than one block smooths out the 1-block Y steps of the height grid.
+const SLOPE_SAMPLE_DISTANCE: i32 = 2;
+
 /// Represents terrain data and elevation settings
 #[derive(Clone)]
 pub struct Ground {
@@ -24,8 +30,9 @@ impl Ground {
         }
     }
 
-    pub fn new_enabled(bbox: &LLBBox, scale: f64, ground_level: i32) -> Self {
-        match fetch_elevation_data(bbox, scale, ground_level) {
+    pub fn new_enabled(bbox: &LLBBox, scale: f64, mapping: &HeightMapping) -> Self {
+        let ground_level = mapping.ground_level;
+        match fetch_elevation_data(bbox, scale, mapping) {
             Ok(elevation_data) => Self {
                 elevation_enabled: true,
                 ground_level,
@@ -48,11 +55,105 @@ impl Ground {
         }
     }
 
+    /// Terrain from a grid of block heights, one cell per block
+    #[cfg(test)]
+    pub(crate) fn from_heights(heights: Vec<Vec<i32>>) -> Self {
+        Self {
+            elevation_enabled: true,
+            ground_level: 0,
+            elevation_data: Some(ElevationData {
+                width: heights.first().map_or(0, Vec::len),
+                height: heights.len(),
+                heights,
+                sea_level_y: None,
+                carved_sea: None,
+                coastal_cliffs: None,
+                measured: None,
+            }),
+        }
+    }
+
     /// Returns the Minecraft Y for sea level, if known (DHM terrain only).
     pub fn sea_level_y(&self) -> Option<i32> {
         self.elevation_data.as_ref().and_then(|d| d.sea_level_y)
     }
 
+    /// The elevation grid, for passes that work on whole borders (seam matching)
+    pub fn elevation_data_mut(&mut self) -> Option<&mut ElevationData> {
+        self.elevation_data.as_mut()
+    }
+
+    /// Resamples the terrain, fetched as an even lat/lng grid over `llbbox`, onto the blocks
+    /// of `xzbbox` as `transformer` projects them. The linear projection lines the two up
+    /// already, so this only changes the terrain under the sinusoidal one.
+    pub fn reproject(&mut self, llbbox: &LLBBox, transformer: &CoordTransformer, xzbbox: &XZBBox) {
+        if transformer.is_linear() {
+            return;
+        }
+        let Some(data) = self.elevation_data.as_ref() else {
+            return;
+        };
+        let width = (xzbbox.max_x() - xzbbox.min_x()).max(1) as usize;
+        let height = (xzbbox.max_z() - xzbbox.min_z()).max(1) as usize;
+        let (min, max) = (llbbox.min(), llbbox.max());
+        // Blocks beyond the bbox at their latitude take the terrain at its edge
+        let cell = |x: usize, z: usize| {
+            let (lat, lng) = transformer.inverse_transform(
+                (xzbbox.min_x() + x as i32) as f64 + 0.5,
+                (xzbbox.min_z() + z as i32) as f64 + 0.5,
+            );
+            let x_ratio = (lng - min.lng()) / (max.lng() - min.lng());
+            let z_ratio = (max.lat() - lat) / (max.lat() - min.lat());
+            Self::grid_cell(x_ratio.clamp(0.0, 1.0), z_ratio.clamp(0.0, 1.0), data)
+        };
+        let resample_mask = |mask: &Option<Vec<Vec<bool>>>| {
+            mask.as_ref()
+                .map(|mask| resample(mask, width, height, &cell))
+        };
+        let reprojected = ElevationData {
+            heights: resample(&data.heights, width, height, &cell),
+            width,
+            height,
+            sea_level_y: data.sea_level_y,
+            carved_sea: resample_mask(&data.carved_sea),
+            coastal_cliffs: resample_mask(&data.coastal_cliffs),
+            measured: resample_mask(&data.measured),
+        };
+        self.elevation_data = Some(reprojected);
+    }
+
+    /// Whether the terrain model carved open sea at the given coordinates (`--carve-sea`).
+    pub fn is_carved_sea(&self, coord: XZPoint) -> bool {
+        self.mask_at(coord, |data| data.carved_sea.as_ref())
+    }
+
+    /// Whether the given coordinates lie on a coastal cliff face found in the DHM.
+    pub fn is_coastal_cliff(&self, coord: XZPoint) -> bool {
+        self.mask_at(coord, |data| data.coastal_cliffs.as_ref())
+    }
+
+    /// Whether the DEM measured the given coordinates, rather than nodata being filled in.
+    /// Always false for terrain from the global tiles.
+    pub fn is_measured(&self, coord: XZPoint) -> bool {
+        self.mask_at(coord, |data| data.measured.as_ref())
+    }
+
+    fn mask_at(
+        &self,
+        coord: XZPoint,
+        mask: impl Fn(&ElevationData) -> Option<&Vec<Vec<bool>>>,
+    ) -> bool {
+        let Some(data) = self.elevation_data.as_ref() else {
+            return false;
+        };
+        let Some(mask) = mask(data) else {
+            return false;
+        };
+        let (x_ratio, z_ratio) = self.get_data_coordinates(coord, data);
+        let (x, z) = Self::grid_cell(x_ratio, z_ratio, data);
+        mask[z][x]
+    }
+
     /// Returns the ground level at the given coordinates
     #[inline(always)]
     pub fn level(&self, coord: XZPoint) -> i32 {
@@ -65,6 +166,43 @@ impl Ground {
         self.interpolate_height(x_ratio, z_ratio, data)
     }
 
+    /// Overrides the ground level at the given coordinates, for terrain passes that reshape
+    /// the ground before placement. Does nothing without elevation.
+    pub fn set_level(&mut self, coord: XZPoint, y: i32) {
+        if !self.elevation_enabled {
+            return;
+        }
+        let Some(data) = self.elevation_data.as_ref() else {
+            return;
+        };
+        let (x_ratio, z_ratio) = self.get_data_coordinates(coord, data);
+        let (x, z) = Self::grid_cell(x_ratio, z_ratio, data);
+        if let Some(data) = self.elevation_data.as_mut() {
+            data.heights[z][x] = y;
+        }
+    }
+
+    /// Returns the terrain slope in degrees (0 = flat) at the given coordinates,
+    /// or `None` when elevation is disabled.
+    pub fn slope(&self, coord: XZPoint) -> Option<f64> {
+        let (gx, gz) = self.gradient(coord)?;
+        Some(gx.hypot(gz).atan().to_degrees())
+    }
+
+    /// Height change per block along X and Z, from a central difference of the height grid.
+    fn gradient(&self, coord: XZPoint) -> Option<(f64, f64)> {
+        if !self.elevation_enabled || self.elevation_data.is_none() {
+            return None;
+        }
+        let d = SLOPE_SAMPLE_DISTANCE;
+        let height = |dx: i32, dz: i32| self.level(XZPoint::new(coord.x + dx, coord.z + dz)) as f64;
+        let span = (2 * d) as f64;
+        Some((
+            (height(d, 0) - height(-d, 0)) / span,
+            (height(0, d) - height(0, -d)) / span,
+        ))
+    }
+
     #[allow(unused)]
     #[inline(always)]
     pub fn min_level<I: Iterator<Item = XZPoint>>(&self, coords: I) -> Option<i32> {
@@ -94,9 +232,16 @@ impl Ground {
     /// Interpolates height value from the elevation grid
     #[inline(always)]
     fn interpolate_height(&self, x_ratio: f64, z_ratio: f64, data: &ElevationData) -> i32 {
+        let (x, z) = Self::grid_cell(x_ratio, z_ratio, data);
+        data.heights[z][x]
+    }
+
+    /// Grid cell nearest to the given data coordinates
+    #[inline(always)]
+    fn grid_cell(x_ratio: f64, z_ratio: f64, data: &ElevationData) -> (usize, usize) {
         let x: usize = ((x_ratio * (data.width - 1) as f64).round() as usize).min(data.width - 1);
         let z: usize = ((z_ratio * (data.height - 1) as f64).round() as usize).min(data.height - 1);
-        data.heights[z][x]
+        (x, z)
     }
 
     fn save_debug_image(&self, filename: &str) {
@@ -149,20 +294,108 @@ impl Ground {
     }
 }
 
+/// Grid of `width` x `height` cells, each taking the value of the cell of `grid` that `cell`
+/// picks for it
+fn resample<T: Copy>(
+    grid: &[Vec<T>],
+    width: usize,
+    height: usize,
+    cell: &impl Fn(usize, usize) -> (usize, usize),
+) -> Vec<Vec<T>> {
+    (0..height)
+        .map(|z| {
+            (0..width)
+                .map(|x| {
+                    let (x, z) = cell(x, z);
+                    grid[z][x]
+                })
+                .collect()
+        })
+        .collect()
+}
+
 pub fn generate_ground_data(args: &Args) -> Ground {
     if args.terrain {
         println!("{} Fetching elevation...", "[3/7]".bold());
         emit_gui_progress_update(14.0, "Fetching elevation...");
 
-        // Try DHM (high-res Danish terrain) first if token is available
-        if let Some(ref token) = args.dhm_token {
-            match crate::dhm::fetch_dhm_elevation(
-                &args.bbox,
-                args.scale,
-                args.ground_level,
-                token,
-                args.debug,
-            ) {
+        if args.bathymetry && args.dhm_token.is_none() && args.dem.is_none() {
+            eprintln!(
+                "{} --bathymetry needs DHM terrain (--dhm-token or --dem); the sea floor stays flat.",
+                "Warning:".yellow().bold()
+            );
+        }
+        if args.carve_sea && args.dhm_token.is_none() && args.dem.is_none() {
+            eprintln!(
+                "{} --carve-sea needs DHM terrain (--dhm-token or --dem); no sea is carved.",
+                "Warning:".yellow().bold()
+            );
+        }
+
+        let realm = crate::data_sources::Realm::of(&args.bbox);
+        if args.bathymetry && !realm.has_danish_data() {
+            eprintln!(
+                "{} --bathymetry only covers Danish waters; the sea floor stays flat.",
+                "Warning:".yellow().bold()
+            );
+        }
+        if args.dem.is_none() && args.dhm_token.is_some() && !realm.has_danish_data() {
+            eprintln!(
+                "{} DHM only covers Denmark, not {}; using {} terrain instead.",
+                "Warning:".yellow().bold(),
+                realm.name(),
+                if realm.has_arcticdem() {
+                    "ArcticDEM"
+                } else {
+                    "global"
+                }
+            );
+        }
+
+        // A local DEM takes precedence, then DHM (high-res Danish terrain) if a token is
+        // available, or ArcticDEM in Greenland and the Faroes
+        let bathymetry = crate::bathymetry::BathymetrySource::from_args(args);
+        let mapping = HeightMapping::from_args(args);
+        let options = crate::dhm::DemOptions {
+            resample: args.dhm_resample,
+            // The depth rasters are sampled on the Danish UTM grid
+            bathymetry: bathymetry.as_ref().filter(|_| realm.has_danish_data()),
+            carve_sea_flats: args.carve_sea,
+            retry: crate::dhm::RetryPolicy {
+                attempts: args.dhm_retries.max(1),
+                rounds: args.dhm_rounds.max(1),
+                round_pause: args.dhm_round_pause,
+                total_timeout: args.dhm_total_timeout,
+            },
+            resolution: args.dhm_resolution,
+            keep_measured: args.coverage_map || args.min_coverage.is_some(),
+        };
+        let dhm = if let Some(ref dem) = args.dem {
+            Some((
+                "Local DEM",
+                crate::dhm::load_dem_elevation(dem, &args.bbox, args.scale, &mapping, &options),
+            ))
+        } else if realm.has_arcticdem() {
+            Some((
+                "ArcticDEM terrain",
+                crate::dhm::fetch_arcticdem_elevation(&args.bbox, args.scale, &mapping, &options),
+            ))
+        } else {
+            args.dhm_token
+                .as_ref()
+                .filter(|_| realm.has_danish_data())
+                .map(|token| {
+                    (
+                        "DHM terrain",
+                        crate::dhm::fetch_dhm_elevation(
+                            &args.bbox, args.scale, &mapping, token, &options, args.debug,
+                        ),
+                    )
+                })
+        };
+
+        if let Some((source, result)) = dhm {
+            match result {
                 Ok(elevation_data) => {
                     let ground = Ground {
                         elevation_enabled: true,
@@ -174,16 +407,21 @@ pub fn generate_ground_data(args: &Args) -> Ground {
                     }
                     return ground;
                 }
+                // A cancelled generation is thrown away, so nothing else is downloaded for it
+                Err(_) if cancel_requested() => return Ground::new_flat(args.ground_level),
                 Err(e) => {
                     eprintln!(
-                        "{} DHM terrain failed: {e}. Falling back to default elevation.",
+                        "{} {source} failed: {e}. Falling back to default elevation.",
                         "Warning:".yellow().bold()
                     );
                 }
             }
         }
+        if cancel_requested() {
+            return Ground::new_flat(args.ground_level);
+        }
 
-        let ground = Ground::new_enabled(&args.bbox, args.scale, args.ground_level);
+        let ground = Ground::new_enabled(&args.bbox, args.scale, &mapping);
         if args.debug {
             ground.save_debug_image("elevation_debug");
         }
@@ -191,3 +429,65 @@ pub fn generate_ground_data(args: &Args) -> Ground {
     }
     Ground::new_flat(args.ground_level)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate_system::cartesian::XZVector;
+    use crate::world_editor::WorldEditor;
+
+    /// Terrain rising by one block per block towards +X (east).
+    fn ramp_ground(size: usize) -> Ground {
+        let heights = (0..size)
+            .map(|_| (0..size).map(|x| x as i32).collect())
+            .collect();
+        Ground::from_heights(heights)
+    }
+
+    #[test]
+    fn carved_sea_is_looked_up_in_grid_coordinates() {
+        let mut ground = ramp_ground(10);
+        // Open sea along the western three columns of the grid
+        let sea = (0..10).map(|_| (0..10).map(|x| x < 3).collect()).collect();
+        ground.elevation_data.as_mut().unwrap().carved_sea = Some(sea);
+
+        // A tile whose area starts 100 blocks east and south of the world origin
+        let mut tile = XZBBox::rect_from_xz_lengths(9.0, 9.0).unwrap();
+        tile += XZVector { dx: 100, dz: 100 };
+        let llbbox = LLBBox::new(0.0, 0.0, 1.0, 1.0).unwrap();
+        let mut editor = WorldEditor::new(std::env::temp_dir(), &tile, llbbox);
+        editor.set_ground(std::sync::Arc::new(ground));
+
+        assert!(editor.is_carved_sea(101, 105));
+        assert!(!editor.is_carved_sea(108, 105));
+    }
+
+    #[test]
+    fn slope_follows_the_terrain() {
+        let ground = ramp_ground(11);
+        let center = XZPoint::new(5, 5);
+
+        // The grid is sampled slightly coarser than 1:1, so only check the range
+        let slope = ground.slope(center).unwrap();
+        assert!((30.0..=50.0).contains(&slope), "slope {slope}");
+
+        assert_eq!(Ground::new_flat(-62).slope(center), None);
+    }
+
+    #[test]
+    fn arctic_terrain_narrows_towards_the_pole() {
+        let llbbox = LLBBox::new(60.0, -50.0, 70.0, -48.0).unwrap();
+        let (transformer, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&llbbox, 0.0001).unwrap();
+        assert!(!transformer.is_linear());
+        let mut ground = ramp_ground(11);
+        ground.reproject(&llbbox, &transformer, &xzbbox);
+
+        let heights = &ground.elevation_data.as_ref().unwrap().heights;
+        let (north, south) = (&heights[0], heights.last().unwrap());
+        // The bbox spans the whole south row, but nearly a third less of the north row, so its
+        // western blocks lie beyond the bbox and take the terrain at its edge
+        assert_eq!(south[1], 1);
+        assert_eq!(north[1], 0);
+        assert_eq!(north[north.len() / 2], 5);
+    }
+}
@@ -3,6 +3,7 @@ use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
 use crate::coordinate_system::geographic::{LLBBox, LLPoint};
 use crate::coordinate_system::transformation::CoordTransformer;
 use crate::data_processing::{self, GenerationOptions};
+use crate::exclusion;
 use crate::ground::{self, Ground};
 use crate::map_transformation;
 use crate::osm_parser;
@@ -11,6 +12,7 @@ use crate::retrieve_data;
 use crate::telemetry::{self, send_log, LogLevel};
 use crate::version_check;
 use crate::world_editor::WorldFormat;
+use clap::Parser;
 use colored::Colorize;
 use fastnbt::Value;
 use flate2::read::GzDecoder;
@@ -19,7 +21,7 @@ use log::LevelFilter;
 use rfd::FileDialog;
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::{env, fs, io::Write};
+use std::{env, fs, io, io::Write};
 use tauri_plugin_log::{Builder as LogBuilder, Target, TargetKind};
 
 /// Manages the session.lock file for a Minecraft world directory
@@ -110,9 +112,11 @@ pub fn run_gui() {
             gui_set_save_path,
             gui_pick_save_directory,
             gui_start_generation,
+            gui_cancel_generation,
             gui_get_version,
             gui_check_for_updates,
             gui_get_world_map_data,
+            gui_get_coverage_preview,
             gui_show_in_folder
         ])
         .setup(|app| {
@@ -701,6 +705,81 @@ struct WorldMapData {
     max_mc_z: i32,
 }
 
+/// Where the DHM terrain and the orthophoto have data for the selected area, drawn before
+/// anything is generated so a wrong token or missing coverage shows at once
+#[tauri::command]
+async fn gui_get_coverage_preview(
+    bbox_text: String,
+    world_scale: f64,
+    token: String,
+) -> Result<CoveragePreview, String> {
+    tauri::async_runtime::spawn_blocking(move || coverage_preview(&bbox_text, world_scale, &token))
+        .await
+        .map_err(|e| format!("Coverage preview failed: {e}"))?
+}
+
+fn coverage_preview(
+    bbox_text: &str,
+    world_scale: f64,
+    token: &str,
+) -> Result<CoveragePreview, String> {
+    let scale = world_scale.to_string();
+    let args = Args::try_parse_from([
+        "arnis",
+        "--bbox",
+        bbox_text,
+        "--scale",
+        &scale,
+        "--terrain",
+        "--ortho",
+        "--coverage-map",
+        "--dhm-token",
+        token,
+    ])
+    .map_err(|e| e.to_string())?;
+    let (image, coverage) = data_processing::coverage_preview(&args)?;
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode coverage map: {e}"))?;
+    let base64_image = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &png);
+    // Shown on the map like the world preview, without Minecraft coordinates to copy
+    Ok(CoveragePreview {
+        map: WorldMapData {
+            image_base64: format!("data:image/png;base64,{base64_image}"),
+            min_lat: args.bbox.min().lat(),
+            max_lat: args.bbox.max().lat(),
+            min_lon: args.bbox.min().lng(),
+            max_lon: args.bbox.max().lng(),
+            min_mc_x: 0,
+            max_mc_x: 0,
+            min_mc_z: 0,
+            max_mc_z: 0,
+        },
+        sources: coverage
+            .iter()
+            .map(|source| CoverageShare {
+                name: source.name.to_string(),
+                percent: source.percent(),
+            })
+            .collect(),
+    })
+}
+
+/// Coverage map of the selected area and the share each source covers
+#[derive(serde::Serialize)]
+struct CoveragePreview {
+    map: WorldMapData,
+    sources: Vec<CoverageShare>,
+}
+
+#[derive(serde::Serialize)]
+struct CoverageShare {
+    name: String,
+    percent: f64,
+}
+
 /// Opens the file with default application (Windows) or shows in file explorer (macOS/Linux)
 #[tauri::command]
 fn gui_show_in_folder(path: String) -> Result<(), String> {
@@ -752,6 +831,12 @@ fn gui_show_in_folder(path: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Asks a running generation to stop; long waits such as DHM retries end early.
+#[tauri::command]
+fn gui_cancel_generation() {
+    progress::request_cancel();
+}
+
 #[tauri::command]
 #[allow(clippy::too_many_arguments)]
 #[allow(unused_variables)]
@@ -770,13 +855,28 @@ fn gui_start_generation(
     spawn_point: Option<(f64, f64)>,
     telemetry_consent: bool,
     world_format: String,
+    exclusion_zones: Option<String>,
+    ortho_check_token: Option<String>,
 ) -> Result<(), String> {
     use progress::emit_gui_error;
     use LLBBox;
 
+    progress::reset_cancel();
     telemetry::set_telemetry_consent(telemetry_consent);
     telemetry::send_generation_click();
 
+    // Polygons drawn on the map as exclusion zones, as a GeoJSON feature collection
+    let exclusion_zones = match exclusion_zones.as_deref().map(str::as_bytes) {
+        Some(geojson) => match exclusion::parse_zones(geojson) {
+            Ok(zones) => zones,
+            Err(e) => {
+                emit_gui_error(&e);
+                return Err(e);
+            }
+        },
+        None => Vec::new(),
+    };
+
     if is_new_world && world_format != "bedrock" {
         let llbbox = match LLBBox::from_str(&bbox_text) {
             Ok(bbox) => bbox,
@@ -907,14 +1007,6 @@ fn gui_start_generation(
                 }
             };
 
-            let generation_options_base = GenerationOptions {
-                path: generation_path.clone(),
-                format: world_format,
-                level_name,
-                spawn_point: mc_spawn_point,
-                update_spawn_after_generation: true,
-            };
-
             let (full_xzbbox, job_tiles, full_transformer) = if world_format
                 == WorldFormat::JavaAnvil
             {
@@ -932,9 +1024,9 @@ fn gui_start_generation(
                     println!("{message}");
                 }
 
-                (plan.full_xzbbox.clone(), plan.tiles, Some(transformer))
+                (plan.full_xzbbox.clone(), plan.tiles, transformer)
             } else {
-                let (_, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&bbox, world_scale)
+                let (transformer, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&bbox, world_scale)
                     .map_err(|e| format!("Failed to create coordinate transformer: {e}"))?;
                 (
                     xzbbox.clone(),
@@ -944,10 +1036,21 @@ fn gui_start_generation(
                         index: 1,
                         total: 1,
                     }],
-                    None,
+                    transformer,
                 )
             };
 
+            let mut generation_options_base = GenerationOptions {
+                path: generation_path.clone(),
+                format: world_format,
+                level_name,
+                spawn_point: mc_spawn_point,
+                update_spawn_after_generation: true,
+                coverage_map: None,
+                world_xzbbox: full_xzbbox.clone(),
+                block_medians: None,
+            };
+
             let requires_tiling = world_format == WorldFormat::JavaAnvil && job_tiles.len() > 1;
             let output_path_for_args = if world_format == WorldFormat::JavaAnvil {
                 generation_path.clone()
@@ -956,24 +1059,103 @@ fn gui_start_generation(
             };
             let build_args = |job_bbox: LLBBox| Args {
                 bbox: job_bbox,
+                area: None,
+                area_boundary: None,
+                exclusion_zones: None,
                 file: None,
+                osm_file: None,
                 land_polygons: None,
+                no_history: false,
                 save_json_file: None,
                 path: Some(output_path_for_args.clone()),
                 bedrock: world_format == WorldFormat::BedrockMcWorld,
                 downloader: "requests".to_string(),
+                overpass_urls: Vec::new(),
                 scale: world_scale,
+                snap: crate::coordinate_system::snap::SnapMode::Floor,
                 ground_level,
+                elevation_mode: crate::elevation_data::ElevationMode::Relative,
+                meters_per_block: 1.0,
+                sea_level_y: None,
+                terrain_exaggeration: 1.0,
                 terrain: terrain_enabled,
                 interior: interior_enabled,
+                interiors: false,
+                bbr_codes: None,
+                edge_margin: 0,
                 roof: roof_enabled,
                 fillground: fillground_enabled,
                 city_boundaries: city_boundaries_enabled,
+                urban_infill: false,
+                addresses: false,
+                place_names: false,
+                ortho: false,
+                coverage_map: false,
+                min_coverage: None,
+                ortho_check: ortho_check_token.is_some(),
+                geodanmark_coast: false,
+                geodanmark_roads: false,
+                protected_nature: false,
+                field_crops: false,
+                field_crops_year: None,
+                forest_types: false,
+                land_cover: false,
+                wind_turbines: false,
+                ancient_monuments: false,
+                landmarks: None,
+                gtfs: None,
+                privacy_audit: false,
                 debug: false,
                 timeout: Some(std::time::Duration::from_secs(40)),
                 dhm_token: None,
+                dem: None,
+                dhm_resample: crate::dhm::ResampleMethod::Bilinear,
+                dhm_resolution: None,
+                dhm_retries: 3,
+                dhm_rounds: 5,
+                dhm_round_pause: std::time::Duration::from_secs(60),
+                dhm_total_timeout: None,
+                bathymetry: false,
+                bathymetry_token: None,
+                carve_sea: false,
+                tide: None,
+                flood_scenario: crate::element_processing::man_made::FloodScenario::Normal,
+                navigable_waterways: false,
+                anchor: None,
+                export_gis: None,
+                seam_export: None,
+                seam_from: Vec::new(),
+                accessibility: false,
+                school_mode: false,
+                overlay: None,
+                minecart_transit: false,
+                tour: false,
+                camera_path: false,
+                info_book: false,
+                time_of_day: None,
+                gamemode: None,
+                border: None,
+                showcase: false,
+                feature_index: false,
+                manifest: false,
             };
 
+            // Building heights are estimated from medians over the whole world, so a block
+            // cut by a tile seam gets one estimate
+            if requires_tiling && !skip_osm_objects {
+                match crate::height_estimation::world_medians(
+                    &build_args(bbox),
+                    &full_transformer,
+                    &full_xzbbox,
+                ) {
+                    Ok(medians) => generation_options_base.block_medians = Some(medians),
+                    Err(e) => eprintln!(
+                        "Building heights of the whole area unavailable: {e}. Heights are \
+                         estimated per tile."
+                    ),
+                }
+            }
+
             let mut spawn_y_after_generation =
                 if world_format == WorldFormat::JavaAnvil && !terrain_enabled {
                     Some(-61)
@@ -991,7 +1173,12 @@ fn gui_start_generation(
                     }
 
                     let args = build_args(tile.llbbox);
-                    let ground = ground::generate_ground_data(&args);
+                    let mut ground = ground::generate_ground_data(&args);
+                    ground.reproject(&tile.llbbox, &full_transformer, &tile.xzbbox);
+                    if progress::cancel_requested() {
+                        emit_gui_error("Generation cancelled");
+                        return Err("Generation cancelled".to_string());
+                    }
                     if world_format == WorldFormat::JavaAnvil {
                         if let Some((spawn_x, spawn_z)) = mc_spawn_point {
                             if tile.xzbbox.contains(&XZPoint::new(spawn_x, spawn_z)) {
@@ -1009,6 +1196,7 @@ fn gui_start_generation(
                     generation_options.update_spawn_after_generation = !requires_tiling;
 
                     data_processing::generate_world_with_options(
+                        Vec::new(),
                         Vec::new(),
                         tile.xzbbox.clone(),
                         tile.llbbox,
@@ -1026,26 +1214,28 @@ fn gui_start_generation(
                         println!("{tile_message}");
                     }
 
+                    let args = build_args(tile.llbbox);
                     let raw_data = retrieve_data::fetch_data_from_overpass(
                         tile.llbbox,
+                        retrieve_data::OptionalLayers::from_args(&args),
                         false,
                         "requests",
                         None,
                     )
                     .map_err(|e| e.to_string())?;
 
-                    let args = build_args(tile.llbbox);
                     let (mut parsed_elements, mut xzbbox) =
-                        if let Some(transformer) = &full_transformer {
-                            osm_parser::parse_osm_data_with_transformer(
-                                raw_data,
-                                transformer,
-                                tile.xzbbox.clone(),
-                                false,
-                            )
-                        } else {
-                            osm_parser::parse_osm_data(raw_data, tile.llbbox, world_scale, false)
-                        };
+                        osm_parser::parse_osm_data_with_transformer(
+                            raw_data,
+                            &full_transformer,
+                            tile.xzbbox.clone(),
+                            false,
+                        );
+                    if !exclusion_zones.is_empty() {
+                        // Projected like the OSM data of the tile
+                        parsed_elements
+                            .extend(exclusion::marker_ways(&exclusion_zones, &full_transformer));
+                    }
                     parsed_elements.sort_by(|el1, el2| {
                         let (el1_priority, el2_priority) =
                             (osm_parser::get_priority(el1), osm_parser::get_priority(el2));
@@ -1060,6 +1250,11 @@ fn gui_start_generation(
                     });
 
                     let mut ground = ground::generate_ground_data(&args);
+                    ground.reproject(&tile.llbbox, &full_transformer, &xzbbox);
+                    if progress::cancel_requested() {
+                        emit_gui_error("Generation cancelled");
+                        return Err("Generation cancelled".to_string());
+                    }
                     map_transformation::transform_map(
                         &mut parsed_elements,
                         &mut xzbbox,
@@ -1084,6 +1279,7 @@ fn gui_start_generation(
 
                     data_processing::generate_world_with_options(
                         parsed_elements,
+                        Vec::new(),
                         xzbbox,
                         tile.llbbox,
                         ground,
@@ -1109,8 +1305,17 @@ fn gui_start_generation(
             println!("{}", "Done! World generation completed.".green().bold());
 
             if world_format == WorldFormat::JavaAnvil {
-                let preview_info =
+                let mut preview_info =
                     data_processing::MapPreviewInfo::new(generation_path.clone(), &full_xzbbox);
+                // Checked against the orthophoto on the preview's map
+                preview_info.ortho_check = ortho_check_token.as_deref().and_then(|token| {
+                    data_processing::OrthoCheck::new(
+                        generation_path.join("arnis-ortho-check.png"),
+                        bbox,
+                        world_scale,
+                        Some(token),
+                    )
+                });
                 data_processing::start_map_preview_generation(preview_info);
             }
 
@@ -1018,6 +1018,7 @@ $(document).ready(function () {
     L.drawLocal.draw.toolbar.buttons = L.drawLocal.draw.toolbar.buttons || {};
     L.drawLocal.draw.toolbar.buttons.rectangle = 'Choose area';
     L.drawLocal.draw.toolbar.buttons.marker = 'Set spawnpoint';
+    L.drawLocal.draw.toolbar.buttons.polygon = 'Draw exclusion zone';
 
     // Initialize the FeatureGroup to store editable layers
     drawnItems = new L.FeatureGroup();
@@ -1050,7 +1051,18 @@ $(document).ready(function () {
                 repeatMode: false
             },
             polyline: false,
-            polygon: false,
+            // Exclusion zones: ground only, kept free of buildings, roads and trees
+            polygon: {
+                shapeOptions: {
+                    color: '#e0a030',
+                    opacity: 0.8,
+                    weight: 2,
+                    fillColor: '#e0a030',
+                    fillOpacity: 0.25
+                },
+                allowIntersection: false,
+                repeatMode: false
+            },
             circle: false,
             marker: {
                 icon: customMarkerIcon
@@ -1131,14 +1143,14 @@ $(document).ready(function () {
 
         drawnItems.addLayer(e.layer);
 
-        // Only update the bounds based on non-marker layers
-        if (e.layerType !== 'marker') {
+        // Only update the bounds based on the area, not markers or exclusion zones
+        if (e.layerType !== 'marker' && e.layerType !== 'polygon') {
             // Calculate bounds only from non-marker layers
             const nonMarkerBounds = new L.LatLngBounds();
             let hasNonMarkerLayers = false;
             
             drawnItems.eachLayer(function(layer) {
-                if (!(layer instanceof L.Marker)) {
+                if (!(layer instanceof L.Marker) && !isExclusionZone(layer)) {
                     hasNonMarkerLayers = true;
                     nonMarkerBounds.extend(layer.getBounds());
                 }
@@ -1318,3 +1330,21 @@ function getSpawnPointCoords() {
 
 // Expose the function to the parent window
 window.getSpawnPointCoords = getSpawnPointCoords;
+
+// Drawn polygons are exclusion zones; the area itself is a rectangle
+function isExclusionZone(layer) {
+    return layer instanceof L.Polygon && !(layer instanceof L.Rectangle);
+}
+
+// Expose the exclusion zones to the parent window as a GeoJSON feature collection
+function getExclusionZones() {
+    const features = [];
+    drawnItems.eachLayer(function(layer) {
+        if (isExclusionZone(layer)) {
+            features.push(layer.toGeoJSON());
+        }
+    });
+    return features.length > 0 ? JSON.stringify({ type: 'FeatureCollection', features: features }) : null;
+}
+
+window.getExclusionZones = getExclusionZones;
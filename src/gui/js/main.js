@@ -36,6 +36,8 @@ window.addEventListener("DOMContentLoaded", async () => {
   registerMessageEvent();
   window.createWorld = createWorld;
   window.startGeneration = startGeneration;
+  window.cancelGeneration = cancelGeneration;
+  window.previewCoverage = previewCoverage;
   setupProgressListener();
   await initSavePath();
   initSettings();
@@ -117,6 +119,9 @@ async function applyLocalization(localization) {
     "span[data-localize='city_boundaries']": "city_boundaries",
     "span[data-localize='map_theme']": "map_theme",
     "span[data-localize='save_path']": "save_path",
+    "span[data-localize='ortho_check']": "ortho_check",
+    "span[data-localize='coverage_preview']": "coverage_preview",
+    "button[data-localize='check_coverage']": "check_coverage",
     ".footer-link": "footer_text",
     "button[data-localize='license_and_credits']": "license_and_credits",
     "h2[data-localize='license_and_credits']": "license_and_credits",
@@ -868,6 +873,11 @@ async function startGeneration() {
         spawnPoint = [coords.lat, coords.lng];
       }
     }
+    // Polygons drawn on the map are kept free of generation
+    let exclusionZones = null;
+    if (mapFrame && mapFrame.contentWindow && mapFrame.contentWindow.getExclusionZones) {
+      exclusionZones = mapFrame.contentWindow.getExclusionZones();
+    }
 
     // Get generation mode from dropdown
     var generationMode = document.getElementById("generation-mode-select").value;
@@ -878,6 +888,8 @@ async function startGeneration() {
     var roof = document.getElementById("roof-toggle").checked;
     var fill_ground = document.getElementById("fillground-toggle").checked;
     var city_boundaries = document.getElementById("city-boundaries-toggle").checked;
+    // A Dataforsyningen token turns on the orthophoto check of the finished world
+    var ortho_check_token = document.getElementById("ortho-check-token").value.trim();
     var scale = parseFloat(document.getElementById("scale-value-slider").value);
     // var ground_level = parseInt(document.getElementById("ground-level").value, 10);
     // DEPRECATED: Ground level input removed from UI
@@ -904,7 +916,9 @@ async function startGeneration() {
         isNewWorld: true,
         spawnPoint: spawnPoint,
         telemetryConsent: telemetryConsent || false,
-        worldFormat: selectedWorldFormat
+        worldFormat: selectedWorldFormat,
+        exclusionZones: exclusionZones,
+        orthoCheckToken: ortho_check_token || null
     });
 
     console.log("Generation process started.");
@@ -915,6 +929,57 @@ async function startGeneration() {
   }
 }
 
+// Asks the backend to stop a running generation, e.g. while DHM downloads are retried
+async function cancelGeneration() {
+  if (generationButtonEnabled) {
+    return;
+  }
+  try {
+    await invoke("gui_cancel_generation");
+  } catch (error) {
+    console.error("Error cancelling generation:", error);
+  }
+}
+
+// Shows on the map where the DHM terrain and the orthophoto have data for the selected
+// area, before anything is generated, so a wrong token or missing coverage shows at once
+async function previewCoverage() {
+  const info = document.getElementById("coverage-preview-info");
+  var token = document.getElementById("ortho-check-token").value.trim();
+  if (!selectedBBox || selectedBBox == "0.000000 0.000000 0.000000 0.000000") {
+    const bboxSelectionInfo = document.getElementById('bbox-selection-info');
+    setBboxSelectionInfo(bboxSelectionInfo, "select_location_first", "#fa7878");
+    return;
+  }
+  if (!token) {
+    info.textContent = "Enter a Dataforsyningen token above first.";
+    return;
+  }
+
+  info.textContent = "Fetching terrain and orthophoto...";
+  try {
+    var scale = parseFloat(document.getElementById("scale-value-slider").value);
+    const preview = await invoke("gui_get_coverage_preview", {
+      bboxText: selectedBBox,
+      worldScale: scale,
+      token: token
+    });
+    info.textContent = preview.sources
+      .map(source => `${source.name} ${Math.round(source.percent)}%`)
+      .join(", ");
+    const mapFrame = document.querySelector('.map-container');
+    if (mapFrame && mapFrame.contentWindow) {
+      mapFrame.contentWindow.postMessage({
+        type: 'worldPreviewReady',
+        data: preview.map
+      }, '*');
+    }
+  } catch (error) {
+    console.error("Coverage preview failed:", error);
+    info.textContent = String(error);
+  }
+}
+
 // World preview overlay state
 let worldPreviewEnabled = false;
 let currentWorldMapData = null;
@@ -0,0 +1,471 @@
+//! Level estimates for buildings mapped without a height.
+//!
+//! Buildings with neither `height` nor `building:levels` all get the same default height,
+//! which leaves a flat skyline wherever coverage is patchy. The buildings mapped with a
+//! height in the same landuse polygon, roughly a city block, tell what is typical there:
+//! an unmapped building gets the median levels of its mapped neighbours instead. The
+//! medians are taken once over the whole world, so a block cut by a tile seam gets the same
+//! estimate on both sides of it.
+
+use crate::args::Args;
+use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::element_processing::assemble_rings;
+use crate::land_polygons::point_in_ring;
+use crate::osm_file;
+use crate::osm_parser::{self, ProcessedElement, ProcessedMemberRole, ProcessedNode};
+use crate::retrieve_data;
+use std::collections::HashMap;
+
+/// Mapped neighbours needed in a block before its median is trusted
+const MIN_NEIGHBOURS: usize = 3;
+/// Storey height used to turn a tagged height into levels
+const METERS_PER_LEVEL: f64 = 3.0;
+/// Size of the grid cells the blocks are indexed in, in blocks
+const INDEX_CELL: i32 = 64;
+/// Building types with a height of their own that the neighbours say nothing about
+const OWN_HEIGHT_TYPES: &[&str] = &[
+    "garage",
+    "garages",
+    "carport",
+    "shed",
+    "hut",
+    "cabin",
+    "kiosk",
+    "roof",
+    "greenhouse",
+    "service",
+    "toilets",
+    "bunker",
+    "transformer_tower",
+];
+
+/// The way or relation a landuse polygon was mapped as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BlockId {
+    Way(u64),
+    Relation(u64),
+}
+
+/// A landuse polygon: its outline and holes, and the bounds of its outline
+struct Block {
+    id: BlockId,
+    outer: Vec<Vec<XZPoint>>,
+    inner: Vec<Vec<XZPoint>>,
+    min: XZPoint,
+    max: XZPoint,
+}
+
+impl Block {
+    fn from_element(element: &ProcessedElement, xzbbox: &XZBBox) -> Option<Self> {
+        if !element.tags().contains_key("landuse") {
+            return None;
+        }
+        let ring = |nodes: &[ProcessedNode]| nodes.iter().map(|n| n.xz()).collect::<Vec<_>>();
+        let (id, outer, inner) = match element {
+            ProcessedElement::Way(way) => {
+                let outline = ring(&way.nodes);
+                if outline.len() < 4 || outline.first() != outline.last() {
+                    return None;
+                }
+                (BlockId::Way(way.id), vec![outline], Vec::new())
+            }
+            ProcessedElement::Relation(relation) => {
+                let rings = |role| {
+                    assemble_rings(relation, role, xzbbox)
+                        .iter()
+                        .map(|nodes| ring(nodes))
+                        .collect::<Vec<_>>()
+                };
+                let outer = rings(ProcessedMemberRole::Outer);
+                let inner = rings(ProcessedMemberRole::Inner);
+                (BlockId::Relation(relation.id), outer, inner)
+            }
+            ProcessedElement::Node(_) => return None,
+        };
+        let points = || outer.iter().flatten();
+        let min = XZPoint::new(points().map(|p| p.x).min()?, points().map(|p| p.z).min()?);
+        let max = XZPoint::new(points().map(|p| p.x).max()?, points().map(|p| p.z).max()?);
+        Some(Self {
+            id,
+            outer,
+            inner,
+            min,
+            max,
+        })
+    }
+
+    fn bounds_area(&self) -> i64 {
+        (self.max.x - self.min.x) as i64 * (self.max.z - self.min.z) as i64
+    }
+
+    fn contains(&self, point: XZPoint) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.z >= self.min.z
+            && point.z <= self.max.z
+            && self.outer.iter().any(|ring| point_in_ring(point, ring))
+            && !self.inner.iter().any(|ring| point_in_ring(point, ring))
+    }
+}
+
+/// The landuse polygons of the elements, indexed by grid cell
+struct Blocks {
+    blocks: Vec<Block>,
+    index: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Blocks {
+    fn new(elements: &[ProcessedElement], xzbbox: &XZBBox) -> Self {
+        let mut blocks: Vec<Block> = elements
+            .iter()
+            .filter_map(|element| Block::from_element(element, xzbbox))
+            .collect();
+        // Smallest first, so nested polygons win over the ones around them
+        blocks.sort_by_key(Block::bounds_area);
+
+        let mut index: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (i, block) in blocks.iter().enumerate() {
+            for cell_x in block.min.x.div_euclid(INDEX_CELL)..=block.max.x.div_euclid(INDEX_CELL) {
+                for cell_z in
+                    block.min.z.div_euclid(INDEX_CELL)..=block.max.z.div_euclid(INDEX_CELL)
+                {
+                    index.entry((cell_x, cell_z)).or_default().push(i);
+                }
+            }
+        }
+        Self { blocks, index }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The smallest block around a point
+    fn block_of(&self, point: XZPoint) -> Option<BlockId> {
+        let cell = (
+            point.x.div_euclid(INDEX_CELL),
+            point.z.div_euclid(INDEX_CELL),
+        );
+        self.index
+            .get(&cell)?
+            .iter()
+            .map(|&i| &self.blocks[i])
+            .find(|block| block.contains(point))
+            .map(|block| block.id)
+    }
+}
+
+/// Levels a building is mapped with, from building:levels or its height
+fn mapped_levels(tags: &HashMap<String, String>) -> Option<f64> {
+    if let Some(levels) = tags
+        .get("building:levels")
+        .and_then(|l| l.trim().parse::<f64>().ok())
+    {
+        return Some(levels);
+    }
+    tags.get("height")
+        .and_then(|h| h.trim_end_matches('m').trim().parse::<f64>().ok())
+        .map(|height| (height / METERS_PER_LEVEL).round().max(1.0))
+}
+
+fn is_estimable_building(tags: &HashMap<String, String>) -> bool {
+    tags.get("building")
+        .is_some_and(|b| !OWN_HEIGHT_TYPES.contains(&b.as_str()))
+}
+
+/// Centroid of a building way, or of the outer ways of a building relation
+fn centroid(element: &ProcessedElement) -> Option<XZPoint> {
+    let nodes: Vec<&ProcessedNode> = match element {
+        ProcessedElement::Way(way) => way.nodes.iter().collect(),
+        ProcessedElement::Relation(relation) => relation
+            .members
+            .iter()
+            .filter(|member| member.role == ProcessedMemberRole::Outer)
+            .flat_map(|member| member.way.nodes.iter())
+            .collect(),
+        ProcessedElement::Node(_) => return None,
+    };
+    let count = nodes.len() as i64;
+    if count == 0 {
+        return None;
+    }
+    let (sum_x, sum_z) = nodes.iter().fold((0i64, 0i64), |(sx, sz), n| {
+        (sx + n.x as i64, sz + n.z as i64)
+    });
+    Some(XZPoint::new((sum_x / count) as i32, (sum_z / count) as i32))
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Median levels of the landuse polygons with enough mapped buildings in them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockMedians(HashMap<BlockId, u32>);
+
+impl BlockMedians {
+    /// The medians of the blocks among `elements`. A building belongs to the smallest
+    /// polygon around its centroid. For a tiled run, `elements` are those of the whole
+    /// world and `xzbbox` is the world's.
+    pub fn of(elements: &[ProcessedElement], xzbbox: &XZBBox) -> Self {
+        let blocks = Blocks::new(elements, xzbbox);
+        if blocks.is_empty() {
+            return Self::default();
+        }
+        let mut mapped: HashMap<BlockId, Vec<f64>> = HashMap::new();
+        for element in elements {
+            if !is_estimable_building(element.tags()) {
+                continue;
+            }
+            let (Some(levels), Some(block)) = (
+                mapped_levels(element.tags()),
+                centroid(element).and_then(|point| blocks.block_of(point)),
+            ) else {
+                continue;
+            };
+            mapped.entry(block).or_default().push(levels);
+        }
+        let medians = mapped
+            .into_iter()
+            .filter(|(_, levels)| levels.len() >= MIN_NEIGHBOURS)
+            .map(|(block, mut levels)| (block, median(&mut levels).round().max(1.0) as u32))
+            .collect();
+        Self(medians)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The medians over the whole world of a tiled run, from the run's OSM file or extract, or
+/// else from the landuse areas and the buildings mapped with a height fetched from Overpass
+pub fn world_medians(
+    args: &Args,
+    transformer: &CoordTransformer,
+    world_xzbbox: &XZBBox,
+) -> Result<BlockMedians, String> {
+    let data = match (&args.file, &args.osm_file) {
+        (Some(file), _) => retrieve_data::fetch_data_from_file(file).map_err(|e| e.to_string())?,
+        (None, Some(osm_file)) => osm_file::read_osm_file(osm_file, &args.bbox, &args.bbox)?,
+        (None, None) => {
+            retrieve_data::fetch_building_heights_from_overpass(args.bbox, &args.downloader)
+                .map_err(|e| e.to_string())?
+        }
+    };
+    let (elements, _) =
+        osm_parser::parse_osm_data_with_transformer(data, transformer, world_xzbbox.clone(), false);
+    Ok(BlockMedians::of(&elements, world_xzbbox))
+}
+
+/// Tags buildings mapped without a height with the median `building:levels` of their
+/// landuse polygon. Returns the number of buildings estimated.
+pub fn estimate_building_levels(
+    elements: &mut [ProcessedElement],
+    medians: &BlockMedians,
+    xzbbox: &XZBBox,
+) -> usize {
+    if medians.is_empty() {
+        return 0;
+    }
+    let blocks = Blocks::new(elements, xzbbox);
+    let estimates: Vec<(usize, u32)> = elements
+        .iter()
+        .enumerate()
+        .filter(|(_, element)| {
+            is_estimable_building(element.tags()) && mapped_levels(element.tags()).is_none()
+        })
+        .filter_map(|(i, element)| {
+            let block = blocks.block_of(centroid(element)?)?;
+            Some((i, *medians.0.get(&block)?))
+        })
+        .collect();
+    for &(i, levels) in &estimates {
+        elements[i]
+            .tags_mut()
+            .insert("building:levels".to_string(), levels.to_string());
+    }
+    estimates.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_parser::{ProcessedMember, ProcessedRelation, ProcessedWay};
+    use crate::test_utilities::{tags, way};
+    use std::sync::Arc;
+
+    fn square(x: i32, z: i32, size: i32) -> Vec<(i32, i32)> {
+        vec![
+            (x, z),
+            (x + size, z),
+            (x + size, z + size),
+            (x, z + size),
+            (x, z),
+        ]
+    }
+
+    fn levels(element: &ProcessedElement) -> Option<&str> {
+        element.tags().get("building:levels").map(|l| l.as_str())
+    }
+
+    fn world() -> XZBBox {
+        XZBBox::rect_from_xz_lengths(1000.0, 1000.0).unwrap()
+    }
+
+    /// Estimates the levels with medians taken over the elements themselves
+    fn estimate(elements: &mut [ProcessedElement]) -> usize {
+        let medians = BlockMedians::of(elements, &world());
+        estimate_building_levels(elements, &medians, &world())
+    }
+
+    #[test]
+    fn unmapped_buildings_take_the_median_of_their_block() {
+        let mut elements = vec![
+            way(1, &[("landuse", "residential")], &square(0, 0, 100)),
+            way(
+                2,
+                &[("building", "apartments"), ("building:levels", "4")],
+                &square(5, 5, 10),
+            ),
+            way(
+                3,
+                &[("building", "apartments"), ("building:levels", "5")],
+                &square(25, 5, 10),
+            ),
+            way(
+                4,
+                &[("building", "yes"), ("height", "15 m")],
+                &square(45, 5, 10),
+            ),
+            way(5, &[("building", "yes")], &square(65, 5, 10)),
+            // Sheds keep their own height
+            way(6, &[("building", "shed")], &square(85, 5, 5)),
+            // Outside any block
+            way(7, &[("building", "yes")], &square(205, 5, 10)),
+        ];
+
+        assert_eq!(estimate(&mut elements), 1);
+        assert_eq!(levels(&elements[4]), Some("5"));
+        assert_eq!(levels(&elements[5]), None);
+        assert_eq!(levels(&elements[6]), None);
+    }
+
+    #[test]
+    fn blocks_with_few_mapped_buildings_are_left_alone() {
+        let mut elements = vec![
+            way(1, &[("landuse", "residential")], &square(0, 0, 100)),
+            way(
+                2,
+                &[("building", "house"), ("building:levels", "2")],
+                &square(5, 5, 10),
+            ),
+            way(3, &[("building", "house")], &square(25, 5, 10)),
+        ];
+
+        assert_eq!(estimate(&mut elements), 0);
+        assert_eq!(levels(&elements[2]), None);
+    }
+
+    #[test]
+    fn tiles_use_the_medians_of_the_whole_world() {
+        let mapped = |id, x| {
+            way(
+                id,
+                &[("building", "apartments"), ("building:levels", "6")],
+                &square(x, 5, 10),
+            )
+        };
+        let block = way(1, &[("landuse", "residential")], &square(0, 0, 100));
+        let unmapped = way(5, &[("building", "yes")], &square(5, 50, 10));
+        let whole_world = vec![
+            block.clone(),
+            mapped(2, 5),
+            mapped(3, 25),
+            mapped(4, 65),
+            unmapped.clone(),
+        ];
+        // The tile west of a seam at x = 40 holds only two of the mapped buildings
+        let mut tile = vec![block, mapped(2, 5), mapped(3, 25), unmapped];
+
+        let medians = BlockMedians::of(&whole_world, &world());
+        assert_eq!(estimate_building_levels(&mut tile, &medians, &world()), 1);
+        assert_eq!(levels(&tile[3]), Some("6"));
+    }
+
+    #[test]
+    fn relations_count_as_blocks_and_buildings() {
+        let member = |role, element: ProcessedElement| {
+            let ProcessedElement::Way(way) = element else {
+                unreachable!()
+            };
+            ProcessedMember {
+                role,
+                way: Arc::new(way),
+            }
+        };
+        let relation = |id, pairs: &[(&str, &str)], members| {
+            ProcessedElement::Relation(ProcessedRelation {
+                id,
+                tags: tags(pairs),
+                members,
+            })
+        };
+        let mut elements = vec![
+            relation(
+                1,
+                &[("type", "multipolygon"), ("landuse", "residential")],
+                vec![
+                    member(ProcessedMemberRole::Outer, way(10, &[], &square(0, 0, 100))),
+                    // A courtyard; the building in it belongs to no block
+                    member(
+                        ProcessedMemberRole::Inner,
+                        way(11, &[], &square(60, 60, 30)),
+                    ),
+                ],
+            ),
+            way(
+                2,
+                &[("building", "house"), ("building:levels", "2")],
+                &square(5, 5, 10),
+            ),
+            way(
+                3,
+                &[("building", "house"), ("building:levels", "2")],
+                &square(25, 5, 10),
+            ),
+            relation(
+                4,
+                &[
+                    ("type", "multipolygon"),
+                    ("building", "house"),
+                    ("height", "6"),
+                ],
+                vec![member(
+                    ProcessedMemberRole::Outer,
+                    way(12, &[], &square(45, 5, 10)),
+                )],
+            ),
+            relation(
+                5,
+                &[("type", "multipolygon"), ("building", "yes")],
+                vec![member(
+                    ProcessedMemberRole::Outer,
+                    way(13, &[], &square(5, 40, 10)),
+                )],
+            ),
+            way(6, &[("building", "yes")], &square(70, 70, 10)),
+        ];
+
+        assert_eq!(estimate(&mut elements), 1);
+        assert_eq!(levels(&elements[4]), Some("2"));
+        assert_eq!(levels(&elements[5]), None);
+    }
+}
@@ -0,0 +1,316 @@
+//! Local history of generation runs (`arnis history`).
+//!
+//! Each finished run appends a record to `history.jsonl` in the Arnis config directory
+//! (`~/.config/arnis` on Linux): when it ran, its settings as the checksum manifest lists
+//! them, how long its phases took and figures about its input and output. A tiled run is
+//! one record, and an element read by several of its tiles is counted once. Nothing leaves
+//! the machine. `arnis history list` shows the recorded runs and `arnis history diff <run1>
+//! <run2>` the options that differ between two runs next to the change in durations and
+//! statistics, so the effect of an option can be seen rather than guessed. `--no-history`
+//! leaves a run out.
+
+use crate::args::Args;
+use crate::osm_parser::ProcessedElement;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// History file where the system has no config directory, in the working directory
+const FALLBACK_HISTORY_FILE: &str = "./arnis-history.jsonl";
+
+/// Phase durations and statistics of the current run, summed over its tiles
+static DURATIONS: Mutex<BTreeMap<String, f64>> = Mutex::new(BTreeMap::new());
+static STATS: Mutex<BTreeMap<String, f64>> = Mutex::new(BTreeMap::new());
+/// Elements counted in each statistic of the current run, by kind and id, so an element
+/// that several tiles read counts once
+static COUNTED: Mutex<BTreeMap<String, HashSet<(&'static str, u64)>>> = Mutex::new(BTreeMap::new());
+
+/// The history file, in the Arnis config directory
+pub fn history_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join("arnis").join("history.jsonl"))
+        .unwrap_or_else(|| PathBuf::from(FALLBACK_HISTORY_FILE))
+}
+
+/// One recorded run
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Number of the run in the history, from 1
+    pub id: usize,
+    /// Start of the run, in seconds since the Unix epoch
+    pub started: u64,
+    pub arnis_version: String,
+    pub config: serde_json::Value,
+    /// Seconds spent per phase
+    pub durations: BTreeMap<String, f64>,
+    pub stats: BTreeMap<String, f64>,
+}
+
+/// Adds time spent in a phase of the current run.
+pub fn record_duration(phase: &str, duration: Duration) {
+    if let Ok(mut durations) = DURATIONS.lock() {
+        *durations.entry(phase.to_string()).or_default() += duration.as_secs_f64();
+    }
+}
+
+/// Adds to a statistic of the current run.
+pub fn record_stat(name: &str, value: f64) {
+    if let Ok(mut stats) = STATS.lock() {
+        *stats.entry(name.to_string()).or_default() += value;
+    }
+}
+
+/// Counts the elements in a statistic of the current run, each once however many tiles
+/// read it.
+pub fn record_elements<'a>(name: &str, elements: impl IntoIterator<Item = &'a ProcessedElement>) {
+    record_counted(
+        name,
+        elements
+            .into_iter()
+            .map(|element| (element.kind(), element.id())),
+    );
+}
+
+/// Counts elements, by kind (`node`, `way` or `relation`) and id, in a statistic of the
+/// current run, each once however many tiles read it.
+pub fn record_counted(name: &str, keys: impl IntoIterator<Item = (&'static str, u64)>) {
+    if let Ok(mut counted) = COUNTED.lock() {
+        counted.entry(name.to_string()).or_default().extend(keys);
+    }
+}
+
+/// The statistics of the current run gathered so far
+pub fn run_stats() -> BTreeMap<String, f64> {
+    let mut stats = STATS.lock().map(|s| s.clone()).unwrap_or_default();
+    if let Ok(counted) = COUNTED.lock() {
+        for (name, elements) in counted.iter() {
+            *stats.entry(name.clone()).or_default() += elements.len() as f64;
+        }
+    }
+    stats
+}
+
+/// Region files and bytes of a generated world: `region/*.mca` of a Java world, or the
+/// `.mcworld` file itself
+fn output_size(output: &Path) -> (usize, u64) {
+    if output.is_file() {
+        return (1, output.metadata().map_or(0, |m| m.len()));
+    }
+    let Ok(entries) = std::fs::read_dir(output.join("region")) else {
+        return (0, 0);
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "mca"))
+        .fold((0, 0), |(files, bytes), entry| {
+            (files + 1, bytes + entry.metadata().map_or(0, |m| m.len()))
+        })
+}
+
+/// Reads the recorded runs; a missing history is empty.
+pub fn load_runs(path: &Path) -> Result<Vec<RunRecord>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+    };
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("{} line {}: {e}", path.display(), i + 1))
+        })
+        .collect()
+}
+
+/// Appends a run to the history and returns its number.
+pub fn append_run(path: &Path, mut record: RunRecord) -> Result<usize, String> {
+    record.id = load_runs(path)?.last().map_or(1, |last| last.id + 1);
+    let line = serde_json::to_string(&record)
+        .map_err(|e| format!("Failed to serialize the run record: {e}"))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    writeln!(file, "{line}").map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(record.id)
+}
+
+/// Records the finished run with what was gathered during it.
+pub fn record_run(output: &Path, args: &Args, started: SystemTime) -> Result<usize, String> {
+    record_duration(
+        "total",
+        SystemTime::now()
+            .duration_since(started)
+            .unwrap_or_default(),
+    );
+    let (region_files, bytes) = output_size(output);
+    record_stat("region_files", region_files as f64);
+    record_stat("world_mb", bytes as f64 / (1024.0 * 1024.0));
+
+    let record = RunRecord {
+        id: 0,
+        started: started
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs()),
+        arnis_version: env!("CARGO_PKG_VERSION").to_string(),
+        config: crate::manifest::config_json(args),
+        durations: DURATIONS.lock().map(|d| d.clone()).unwrap_or_default(),
+        stats: run_stats(),
+    };
+    append_run(&history_path(), record)
+}
+
+/// Finds a run by its number, or `last` for the latest one.
+pub fn find_run<'a>(runs: &'a [RunRecord], selector: &str) -> Result<&'a RunRecord, String> {
+    let found = if selector == "last" {
+        runs.last()
+    } else {
+        let id: usize = selector
+            .parse()
+            .map_err(|_| format!("Invalid run '{selector}' (expected a run number or last)"))?;
+        runs.iter().find(|run| run.id == id)
+    };
+    found.ok_or_else(|| format!("No run {selector} in the history"))
+}
+
+fn format_change(before: Option<f64>, after: Option<f64>, unit: &str) -> String {
+    let value = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{v:.1}{unit}"));
+    let change = match (before, after) {
+        (Some(before), Some(after)) if before != 0.0 => {
+            format!(" ({:+.1}%)", (after - before) * 100.0 / before)
+        }
+        _ => String::new(),
+    };
+    format!("{} -> {}{change}", value(before), value(after))
+}
+
+/// Lines describing how run `b` differs from run `a`: the changed options, then the
+/// durations and statistics of both
+pub fn diff_runs(a: &RunRecord, b: &RunRecord) -> Vec<String> {
+    let mut lines = Vec::new();
+    let empty = serde_json::Map::new();
+    let (config_a, config_b) = (
+        a.config.as_object().unwrap_or(&empty),
+        b.config.as_object().unwrap_or(&empty),
+    );
+    let mut keys: Vec<&String> = config_a.keys().chain(config_b.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    let null = serde_json::Value::Null;
+    let changed: Vec<String> = keys
+        .into_iter()
+        .filter_map(|key| {
+            let (before, after) = (
+                config_a.get(key).unwrap_or(&null),
+                config_b.get(key).unwrap_or(&null),
+            );
+            (before != after).then(|| format!("  {key}: {before} -> {after}"))
+        })
+        .collect();
+    if a.arnis_version != b.arnis_version {
+        lines.push(format!(
+            "Arnis version: {} -> {}",
+            a.arnis_version, b.arnis_version
+        ));
+    }
+    if changed.is_empty() {
+        lines.push("Options: unchanged".to_string());
+    } else {
+        lines.push("Options:".to_string());
+        lines.extend(changed);
+    }
+
+    for (title, unit, before, after) in [
+        ("Durations:", " s", &a.durations, &b.durations),
+        ("Statistics:", "", &a.stats, &b.stats),
+    ] {
+        let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+        names.sort();
+        names.dedup();
+        if names.is_empty() {
+            continue;
+        }
+        lines.push(title.to_string());
+        for name in names {
+            lines.push(format!(
+                "  {name}: {}",
+                format_change(before.get(name).copied(), after.get(name).copied(), unit)
+            ));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities;
+    use serde_json::json;
+
+    fn run(config: serde_json::Value, total: f64, buildings: f64) -> RunRecord {
+        RunRecord {
+            id: 0,
+            started: 0,
+            arnis_version: "2.3.0".to_string(),
+            config,
+            durations: BTreeMap::from([("total".to_string(), total)]),
+            stats: BTreeMap::from([("buildings".to_string(), buildings)]),
+        }
+    }
+
+    #[test]
+    fn appends_numbered_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("arnis").join("history.jsonl");
+        assert!(load_runs(&path).unwrap().is_empty());
+        assert_eq!(append_run(&path, run(json!({}), 1.0, 1.0)).unwrap(), 1);
+        assert_eq!(append_run(&path, run(json!({}), 2.0, 1.0)).unwrap(), 2);
+
+        let runs = load_runs(&path).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(find_run(&runs, "last").unwrap().durations["total"], 2.0);
+        assert_eq!(find_run(&runs, "1").unwrap().id, 1);
+        assert!(find_run(&runs, "3").is_err());
+        assert!(find_run(&runs, "first").is_err());
+    }
+
+    #[test]
+    fn elements_read_by_several_tiles_count_once() {
+        let building = |id| test_utilities::way(id, &[("building", "yes")], &[(0, 0), (4, 4)]);
+        // Building 2 straddles the seam and is read by both tiles
+        let (west, east) = ([building(1), building(2)], [building(2), building(3)]);
+        record_elements("test_tiled_buildings", &west);
+        record_elements("test_tiled_buildings", &east);
+        record_counted("test_tiled_buildings", [("relation", 2)]);
+        assert_eq!(run_stats()["test_tiled_buildings"], 4.0);
+    }
+
+    #[test]
+    fn diff_lists_changed_options_and_their_effect() {
+        let a = run(json!({"scale": 1.0, "terrain": false}), 100.0, 200.0);
+        let b = run(json!({"scale": 1.0, "terrain": true}), 150.0, 200.0);
+        assert_eq!(
+            diff_runs(&a, &b),
+            [
+                "Options:",
+                "  terrain: false -> true",
+                "Durations:",
+                "  total: 100.0 s -> 150.0 s (+50.0%)",
+                "Statistics:",
+                "  buildings: 200.0 -> 200.0 (+0.0%)",
+            ]
+        );
+        assert_eq!(diff_runs(&a, &a)[0], "Options: unchanged");
+    }
+}
@@ -0,0 +1,358 @@
+//! In-game info book about the generated world (`--info-book`).
+//!
+//! Statistics are gathered from the elements and the terrain before generation: the area
+//! covered, the number of buildings, the longest named road and the highest point. After
+//! generation they are written into a book on a lectern at spawn, together with the run
+//! statistics kept for `arnis history`, the data sources used and their dates, the
+//! generation date and the credits the data licenses ask for.
+
+use crate::args::Args;
+use crate::block_definitions::{BlockWithProperties, LECTERN};
+use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
+use crate::coordinate_system::geographic::LLBBox;
+use crate::coordinate_system::transformation::geo_distance;
+use crate::data_sources::Realm;
+use crate::ground::Ground;
+use crate::osm_parser::ProcessedElement;
+use crate::world_editor::WorldEditor;
+use fastnbt::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Characters that fit on one book page
+const PAGE_LENGTH: usize = 240;
+
+/// Figures about the generated world for the info book
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorldStats {
+    pub area_km2: f64,
+    pub buildings: usize,
+    /// Name and length in meters of the longest named road
+    pub longest_road: Option<(String, f64)>,
+    /// Highest ground as (x, y, z)
+    pub highest_point: Option<(i32, i32, i32)>,
+    /// Statistics of the run, by their `arnis history` name
+    pub run: BTreeMap<String, f64>,
+}
+
+/// Gathers the statistics before the elements are consumed.
+pub fn collect_stats(
+    elements: &[ProcessedElement],
+    llbbox: &LLBBox,
+    xzbbox: &XZBBox,
+    ground: &Ground,
+    scale: f64,
+) -> WorldStats {
+    let (north_south, east_west) = geo_distance(llbbox.min(), llbbox.max());
+
+    let mut buildings = 0;
+    let mut road_lengths: HashMap<&str, f64> = HashMap::new();
+    for element in elements {
+        let tags = element.tags();
+        if tags.contains_key("building") && !matches!(element, ProcessedElement::Node(_)) {
+            buildings += 1;
+        }
+        if let (ProcessedElement::Way(way), true, Some(name)) =
+            (element, tags.contains_key("highway"), tags.get("name"))
+        {
+            let blocks: f64 = way
+                .nodes
+                .windows(2)
+                .map(|pair| {
+                    (((pair[1].x - pair[0].x) as f64).powi(2)
+                        + ((pair[1].z - pair[0].z) as f64).powi(2))
+                    .sqrt()
+                })
+                .sum();
+            *road_lengths.entry(name.as_str()).or_default() += blocks / scale;
+        }
+    }
+    let longest_road = road_lengths
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(name, meters)| (name.to_string(), meters));
+
+    let highest_point = ground.elevation_enabled.then(|| {
+        let mut highest = (xzbbox.min_x(), i32::MIN, xzbbox.min_z());
+        for x in 0..=(xzbbox.max_x() - xzbbox.min_x()) {
+            for z in 0..=(xzbbox.max_z() - xzbbox.min_z()) {
+                let y = ground.level(XZPoint::new(x, z));
+                if y > highest.1 {
+                    highest = (xzbbox.min_x() + x, y, xzbbox.min_z() + z);
+                }
+            }
+        }
+        highest
+    });
+
+    WorldStats {
+        area_km2: north_south * east_west / 1e6,
+        buildings,
+        longest_road,
+        highest_point,
+        run: BTreeMap::new(),
+    }
+}
+
+/// Datasets the world was generated from, by the options that were enabled
+pub fn data_sources(args: &Args) -> Vec<&'static str> {
+    let realm = Realm::of(&args.bbox);
+    let danish = realm.has_danish_data();
+    let mut sources = vec!["OpenStreetMap contributors (ODbL)"];
+    if args.terrain {
+        sources.push(if args.dem.is_some() {
+            "Local elevation model"
+        } else if realm == Realm::Greenland {
+            "ArcticDEM, Polar Geospatial Center"
+        } else if args.dhm_token.is_some() && danish {
+            "DHM, Danmarks Hoejdemodel (Dataforsyningen)"
+        } else {
+            "AWS Terrain Tiles"
+        });
+    }
+    let enrichments: [(bool, &'static str); 12] = [
+        (args.bathymetry && danish, "EMODnet / GST depth models"),
+        (args.addresses && danish, "DAWA, Danmarks Adresser"),
+        (args.place_names && danish, "Danske Stednavne"),
+        (
+            args.protected_nature && danish,
+            "Danmarks Miljoeportal (para. 3 nature)",
+        ),
+        (args.field_crops && danish, "Markkort, Landbrugsstyrelsen"),
+        (args.forest_types && danish, "Forest map, Miljoestyrelsen"),
+        (args.land_cover, "CORINE Land Cover (Copernicus)"),
+        (
+            args.wind_turbines && danish,
+            "Turbine register, Energistyrelsen",
+        ),
+        (
+            args.ancient_monuments && danish,
+            "Fund og Fortidsminder, Slots- og Kulturstyrelsen",
+        ),
+        (
+            args.geodanmark_coast && danish && args.dhm_token.is_some(),
+            "GeoDanmark coastline (Dataforsyningen)",
+        ),
+        (
+            args.geodanmark_roads && danish && args.dhm_token.is_some(),
+            "GeoDanmark road centerlines (Dataforsyningen)",
+        ),
+        (args.ortho && danish, "GeoDanmark orthophoto"),
+    ];
+    sources.extend(
+        enrichments
+            .into_iter()
+            .filter_map(|(used, name)| used.then_some(name)),
+    );
+    if args.gtfs.is_some() {
+        sources.push("GTFS transit feed");
+    }
+    sources
+}
+
+/// A time's date as YYYY-MM-DD (UTC)
+fn date_of(time: SystemTime) -> String {
+    let days = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| (d.as_secs() / 86_400) as i64)
+        .unwrap_or(0);
+    civil_date(days)
+}
+
+/// Today's date as YYYY-MM-DD (UTC)
+fn today() -> String {
+    date_of(SystemTime::now())
+}
+
+/// Date of the OSM data: when a local file was last changed, otherwise today, when it was
+/// downloaded
+fn osm_date(args: &Args) -> String {
+    args.osm_file
+        .as_deref()
+        .or(args.file.as_deref().map(Path::new))
+        .and_then(|path| path.metadata().and_then(|m| m.modified()).ok())
+        .map_or_else(today, date_of)
+}
+
+/// Calendar date of a day count since 1970-01-01
+pub fn civil_date(days: i64) -> String {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Book pages: the statistics, then the sources with the date of the OSM data and the
+/// credits, wrapped to fit the pages
+pub fn book_pages(
+    stats: &WorldStats,
+    sources: &[&str],
+    osm_date: &str,
+    generated: &str,
+) -> Vec<String> {
+    let mut summary = vec![
+        "This world".to_string(),
+        String::new(),
+        format!("Area: {:.2} km2", stats.area_km2),
+        format!("Buildings: {}", stats.buildings),
+    ];
+    if let Some((name, meters)) = &stats.longest_road {
+        summary.push(format!("Longest road: {name} ({meters:.0} m)"));
+    }
+    if let Some((x, y, z)) = stats.highest_point {
+        summary.push(format!("Highest point: Y {y} at {x}, {z}"));
+    }
+    summary.push(format!("Generated: {generated}"));
+    if !stats.run.is_empty() {
+        summary.push(String::new());
+        summary.extend(
+            stats
+                .run
+                .iter()
+                .map(|(name, value)| format!("{}: {value:.0}", name.replace('_', " "))),
+        );
+    }
+
+    let mut credits = vec!["Data sources".to_string(), String::new()];
+    credits.extend(sources.iter().map(|source| format!("- {source}")));
+    credits.push(format!("OSM data of {osm_date}"));
+    credits.push(String::new());
+    credits.push("Generated with Arnis".to_string());
+
+    [summary, credits]
+        .iter()
+        .flat_map(|lines| paginate(lines))
+        .collect()
+}
+
+/// Splits lines into pages of at most `PAGE_LENGTH` characters
+fn paginate(lines: &[String]) -> Vec<String> {
+    let mut pages = vec![String::new()];
+    for line in lines {
+        let page = pages.last_mut().unwrap();
+        if !page.is_empty() && page.chars().count() + line.chars().count() + 1 > PAGE_LENGTH {
+            pages.push(line.clone());
+        } else {
+            if !page.is_empty() {
+                page.push('\n');
+            }
+            page.push_str(line);
+        }
+    }
+    pages
+}
+
+/// Places a lectern with the info book at world coordinates (`x`, `z`).
+pub fn place_info_book(editor: &mut WorldEditor, x: i32, z: i32, mut pages: Vec<String>) {
+    crate::privacy::audit_lines("books", &mut pages);
+    let text = |text: &str| Value::String(serde_json::Value::from(text).to_string());
+    let page_values = pages
+        .iter()
+        .map(|page| Value::Compound(HashMap::from([("raw".to_string(), text(page))])))
+        .collect();
+    let content = HashMap::from([
+        (
+            "title".to_string(),
+            Value::Compound(HashMap::from([(
+                "raw".to_string(),
+                Value::String("About this world".to_string()),
+            )])),
+        ),
+        ("author".to_string(), Value::String("Arnis".to_string())),
+        ("resolved".to_string(), Value::Byte(1)),
+        ("pages".to_string(), Value::List(page_values)),
+    ]);
+    let book = HashMap::from([
+        (
+            "id".to_string(),
+            Value::String("minecraft:written_book".to_string()),
+        ),
+        ("count".to_string(), Value::Int(1)),
+        (
+            "components".to_string(),
+            Value::Compound(HashMap::from([(
+                "minecraft:written_book_content".to_string(),
+                Value::Compound(content),
+            )])),
+        ),
+    ]);
+    let data = HashMap::from([
+        ("Book".to_string(), Value::Compound(book)),
+        ("Page".to_string(), Value::Int(0)),
+    ]);
+
+    let properties = Value::Compound(HashMap::from([
+        ("facing".to_string(), Value::String("south".to_string())),
+        ("has_book".to_string(), Value::String("true".to_string())),
+        ("powered".to_string(), Value::String("false".to_string())),
+    ]));
+    editor.set_block_entity(
+        BlockWithProperties::new(LECTERN, Some(properties)),
+        x,
+        1,
+        z,
+        "minecraft:lectern",
+        data,
+    );
+}
+
+/// Writes today's info book onto a lectern at spawn
+pub fn write_info_book(
+    editor: &mut WorldEditor,
+    spawn: (i32, i32),
+    stats: &WorldStats,
+    args: &Args,
+) {
+    let stats = WorldStats {
+        run: crate::history::run_stats(),
+        ..stats.clone()
+    };
+    let pages = book_pages(&stats, &data_sources(args), &osm_date(args), &today());
+    place_info_book(editor, spawn.0, spawn.1, pages);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_list_stats_and_sources() {
+        let stats = WorldStats {
+            area_km2: 1.234,
+            buildings: 42,
+            longest_road: Some(("Vestergade".to_string(), 812.4)),
+            highest_point: Some((10, 87, -3)),
+            run: BTreeMap::from([("osm_elements".to_string(), 5120.0)]),
+        };
+        let pages = book_pages(
+            &stats,
+            &["OpenStreetMap contributors (ODbL)"],
+            "2026-10-01",
+            "2026-10-14",
+        );
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].contains("Area: 1.23 km2"));
+        assert!(pages[0].contains("Longest road: Vestergade (812 m)"));
+        assert!(pages[0].contains("Highest point: Y 87 at 10, -3"));
+        assert!(pages[0].contains("osm elements: 5120"));
+        assert!(pages[1].contains("- OpenStreetMap contributors (ODbL)"));
+        assert!(pages[1].contains("OSM data of 2026-10-01"));
+
+        let long: Vec<String> = (0..40).map(|i| format!("Line {i}")).collect();
+        assert!(paginate(&long).len() > 1);
+    }
+
+    #[test]
+    fn formats_civil_dates() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(20_740), "2026-10-14");
+        assert_eq!(civil_date(11_016), "2000-02-29");
+    }
+}
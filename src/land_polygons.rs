@@ -1,10 +1,11 @@
 use crate::clipping::clip_water_ring_to_bbox;
 use crate::coordinate_system::{
-    cartesian::{XZBBox, XZPoint},
+    cartesian::{XZBBox, XZPoint, XZVector},
     geographic::{LLBBox, LLPoint},
     transformation::CoordTransformer,
 };
 use crate::element_processing::water_areas::fill_water_polygons;
+use crate::floodfill_cache::CoordinateBitmap;
 use crate::osm_parser::ProcessedNode;
 use crate::world_editor::WorldEditor;
 use shapefile::{Point, PolygonRing, Shape, ShapeReader};
@@ -17,6 +18,7 @@ pub fn generate_oceans_from_land_polygons(
     llbbox: &LLBBox,
     xzbbox: &XZBBox,
     scale: f64,
+    sea: &mut CoordinateBitmap,
 ) -> Result<bool, String> {
     let polygons = load_external_water_polygons(dataset_path, llbbox, xzbbox, scale)?;
 
@@ -29,7 +31,7 @@ pub fn generate_oceans_from_land_polygons(
         polygons.outers.len(),
         polygons.inners.len()
     );
-    fill_water_polygons(editor, &polygons.outers, &polygons.inners);
+    fill_water_polygons(editor, &polygons.outers, &polygons.inners, sea);
     Ok(true)
 }
 
@@ -54,6 +56,11 @@ fn load_external_water_polygons(
     let shapefile_path = resolve_shapefile_path(dataset_path)?;
     let dataset_kind = detect_dataset_kind(&shapefile_path);
     let (transformer, _) = CoordTransformer::llbbox_to_xzbbox(llbbox, scale)?;
+    // The world may be translated (tiles, --anchor); the projection starts at 0,0
+    let origin = XZVector {
+        dx: xzbbox.min_x(),
+        dz: xzbbox.min_z(),
+    };
     let mut reader = ShapeReader::from_path(&shapefile_path)
         .map_err(|e| format!("Failed to open coastline polygon shapefile: {e}"))?;
 
@@ -82,12 +89,13 @@ fn load_external_water_polygons(
                     Ok(llpoint) => llpoint,
                     Err(_) => continue,
                 };
-                let xz = transformer.transform_point(llpoint);
+                let xz = transformer.transform_point(llpoint) + origin;
                 nodes.push(ProcessedNode {
                     id: record_id + idx as u64,
                     tags: HashMap::new(),
                     x: xz.x,
                     z: xz.z,
+                    exact: None,
                 });
             }
 
@@ -306,7 +314,7 @@ fn ring_touches_bbox(ring: &[XZPoint], xzbbox: &XZBBox) -> bool {
     })
 }
 
-fn point_in_ring(point: XZPoint, ring: &[XZPoint]) -> bool {
+pub(crate) fn point_in_ring(point: XZPoint, ring: &[XZPoint]) -> bool {
     if ring.len() < 3 {
         return false;
     }
@@ -403,7 +411,9 @@ mod dataset_tests {
         let tempdir = tempfile::tempdir().unwrap();
         let mut editor = WorldEditor::new(tempdir.path().to_path_buf(), &xzbbox, llbbox);
 
-        generate_oceans_from_land_polygons(&mut editor, dataset, &llbbox, &xzbbox, 1.0).unwrap();
+        let mut sea = CoordinateBitmap::new(&xzbbox);
+        generate_oceans_from_land_polygons(&mut editor, dataset, &llbbox, &xzbbox, 1.0, &mut sea)
+            .unwrap();
 
         for (lat, lng) in [
             (55.682, 12.615),
@@ -420,6 +430,7 @@ mod dataset_tests {
                 Some(&[crate::block_definitions::WATER]),
                 None,
             ));
+            assert!(sea.contains(sample.x, sample.z));
         }
     }
 }
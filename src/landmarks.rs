@@ -0,0 +1,610 @@
+//! Curated builds of famous landmarks (`--landmarks`).
+//!
+//! The generic generators turn Rundetårn into a round brick building and leave Den Lille
+//! Havfrue out altogether. The landmark registry (`assets/landmarks/registry.json`) maps
+//! famous Danish landmarks to builds contributed by the community, matched by Wikidata ID
+//! or OSM ID. The builds are Sponge schematics (`.schem`, as saved by WorldEdit) in the
+//! directory given to `--landmarks`, where a `registry.json` can add entries of its own.
+//! Landmarks are found in the OSM data before it is clipped to the tile, so each is
+//! centred on its whole footprint and built once, by the tile that holds its centre. Their
+//! elements are tagged before map transformations, so the build follows them, and are
+//! built from the schematic instead of by the generators. The builds are made at 1:1, so
+//! they are only placed at `--scale 1`.
+
+use crate::block_definitions::{Block, BlockWithProperties};
+use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
+use crate::coordinate_system::geographic::LLPoint;
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::osm_parser::{OsmData, ProcessedElement};
+use crate::world_editor::WorldEditor;
+use colored::Colorize;
+use fastnbt::{ByteArray, Value};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Tag marking elements built from a landmark prefab; the value is the schematic file
+pub const LANDMARK_TAG: &str = "arnis:landmark";
+/// Tag with the offset (dx,dz) of the build's centre from the element's first node, which
+/// moves with the element through map transformations
+const ANCHOR_TAG: &str = "arnis:landmark_anchor";
+const BUILT_IN_REGISTRY: &str = include_str!("../assets/landmarks/registry.json");
+/// Matches this close (meters) to an already matched element of the same landmark are the
+/// same landmark mapped twice, e.g. as a building and as a node
+const SAME_LANDMARK_RADIUS: f64 = 16.0;
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// A landmark of the registry and the schematic it is built from
+#[derive(Clone, Debug, Deserialize)]
+pub struct RegistryEntry {
+    pub name: String,
+    pub prefab: String,
+    #[serde(rename = "match")]
+    pub matcher: LandmarkMatch,
+}
+
+/// The IDs an element must have to be the landmark; all given IDs must match
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LandmarkMatch {
+    pub wikidata: Option<String>,
+    /// OSM ID as `node/123`, `way/123` or `relation/123`
+    pub osm: Option<String>,
+}
+
+impl LandmarkMatch {
+    fn is_specific(&self) -> bool {
+        self.wikidata.is_some() || self.osm.is_some()
+    }
+
+    fn matches(&self, kind: &str, id: u64, tags: Option<&HashMap<String, String>>) -> bool {
+        let wikidata = self
+            .wikidata
+            .as_ref()
+            .is_none_or(|wikidata| tags.and_then(|tags| tags.get("wikidata")) == Some(wikidata));
+        let osm = self
+            .osm
+            .as_ref()
+            .is_none_or(|osm| *osm == format!("{kind}/{id}"));
+        wikidata && osm
+    }
+}
+
+/// The built-in registry followed by the entries of `registry.json` in the prefab directory.
+pub fn load_registry(dir: &Path) -> Result<Vec<RegistryEntry>, String> {
+    let mut registry = parse_registry(BUILT_IN_REGISTRY)?;
+    let local = dir.join("registry.json");
+    if local.is_file() {
+        let text = std::fs::read_to_string(&local)
+            .map_err(|e| format!("Failed to read {}: {e}", local.display()))?;
+        registry.extend(parse_registry(&text).map_err(|e| format!("{}: {e}", local.display()))?);
+    }
+    Ok(registry)
+}
+
+fn parse_registry(text: &str) -> Result<Vec<RegistryEntry>, String> {
+    let registry: Vec<RegistryEntry> =
+        serde_json::from_str(text).map_err(|e| format!("Invalid landmark registry: {e}"))?;
+    if let Some(entry) = registry.iter().find(|entry| !entry.matcher.is_specific()) {
+        return Err(format!(
+            "Landmark '{}' needs a wikidata or osm match",
+            entry.name
+        ));
+    }
+    Ok(registry)
+}
+
+/// A landmark found in the OSM data: the element it is built in place of, other elements
+/// mapping the same landmark, and the centre (lat, lng) of its whole footprint
+#[derive(Clone, Debug, PartialEq)]
+pub struct LandmarkSite {
+    pub name: String,
+    prefab: String,
+    element: (String, u64),
+    duplicates: Vec<(String, u64)>,
+    centre: (f64, f64),
+}
+
+/// Bounds (min lat, min lng, max lat, max lng) of each node, way and relation of the raw,
+/// unclipped OSM data
+fn raw_bounds(data: &OsmData) -> HashMap<(&str, u64), (f64, f64, f64, f64)> {
+    let merge = |a: (f64, f64, f64, f64), b: (f64, f64, f64, f64)| {
+        (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+    };
+    let mut bounds = HashMap::new();
+    for element in data.elements().iter().filter(|e| e.r#type == "node") {
+        if let (Some(lat), Some(lng)) = (element.lat, element.lon) {
+            bounds.insert(("node", element.id), (lat, lng, lat, lng));
+        }
+    }
+    for element in data.elements().iter().filter(|e| e.r#type == "way") {
+        let way_bounds = element
+            .nodes
+            .iter()
+            .flatten()
+            .filter_map(|&node| bounds.get(&("node", node)).copied())
+            .reduce(merge);
+        if let Some(way_bounds) = way_bounds {
+            bounds.insert(("way", element.id), way_bounds);
+        }
+    }
+    for element in data.elements().iter().filter(|e| e.r#type == "relation") {
+        let relation_bounds = element
+            .members
+            .iter()
+            .filter_map(|member| bounds.get(&(member.r#type.as_str(), member.r#ref)).copied())
+            .reduce(merge);
+        if let Some(relation_bounds) = relation_bounds {
+            bounds.insert(("relation", element.id), relation_bounds);
+        }
+    }
+    bounds
+}
+
+/// Finds the registered landmarks whose schematic is in `dir` in the raw OSM data, before
+/// it is clipped to the tile. Where a landmark is mapped more than once, e.g. as a building
+/// and as a node, the largest mapping is built and the others are dropped.
+pub fn locate_landmarks(
+    data: &OsmData,
+    registry: &[RegistryEntry],
+    dir: &Path,
+) -> Vec<LandmarkSite> {
+    let bounds = raw_bounds(data);
+    let mut sites: Vec<LandmarkSite> = Vec::new();
+    for entry in registry {
+        if !dir.join(&entry.prefab).is_file() {
+            continue;
+        }
+        let mut matches: Vec<((String, u64), (f64, f64, f64, f64))> = data
+            .elements()
+            .iter()
+            .filter(|element| {
+                entry
+                    .matcher
+                    .matches(&element.r#type, element.id, element.tags.as_ref())
+            })
+            .filter_map(|element| {
+                let bounds = *bounds.get(&(element.r#type.as_str(), element.id))?;
+                Some(((element.r#type.clone(), element.id), bounds))
+            })
+            .collect();
+        // Largest footprint first
+        matches.sort_by(|(_, a), (_, b)| {
+            let area = |(min_lat, min_lng, max_lat, max_lng): &(f64, f64, f64, f64)| {
+                (max_lat - min_lat) * (max_lng - min_lng)
+            };
+            area(b).total_cmp(&area(a))
+        });
+
+        let first = sites.len();
+        for (element, (min_lat, min_lng, max_lat, max_lng)) in matches {
+            let centre = ((min_lat + max_lat) / 2.0, (min_lng + max_lng) / 2.0);
+            let same = sites[first..].iter_mut().find(|site| {
+                let dz = (site.centre.0 - centre.0) * METERS_PER_DEGREE;
+                let dx =
+                    (site.centre.1 - centre.1) * METERS_PER_DEGREE * centre.0.to_radians().cos();
+                dx.hypot(dz) <= SAME_LANDMARK_RADIUS
+            });
+            match same {
+                Some(site) => site.duplicates.push(element),
+                None => sites.push(LandmarkSite {
+                    name: entry.name.clone(),
+                    prefab: entry.prefab.clone(),
+                    element,
+                    duplicates: Vec::new(),
+                    centre,
+                }),
+            }
+        }
+    }
+    sites
+}
+
+/// Point of an element that its anchor is measured from
+fn reference_point(element: &ProcessedElement) -> Option<(i32, i32)> {
+    let node = match element {
+        ProcessedElement::Relation(relation) => relation.members.first()?.way.nodes.first(),
+        _ => element.nodes().next(),
+    }?;
+    Some((node.x, node.z))
+}
+
+/// Tags the elements of the landmarks whose centre lies in `xzbbox` to be built from their
+/// prefab, and drops the landmarks' other elements: duplicate mappings, and the parts of
+/// landmarks that another tile builds. Returns the names of the landmarks built here.
+pub fn tag_landmarks(
+    elements: &mut Vec<ProcessedElement>,
+    sites: &[LandmarkSite],
+    transformer: &CoordTransformer,
+    xzbbox: &XZBBox,
+) -> Vec<String> {
+    let mut built: HashMap<(&str, u64), (&LandmarkSite, XZPoint)> = HashMap::new();
+    let mut dropped: HashSet<(&str, u64)> = HashSet::new();
+    for site in sites {
+        let anchor = LLPoint::new(site.centre.0, site.centre.1)
+            .ok()
+            .map(|centre| transformer.transform_point(centre))
+            .filter(|anchor| xzbbox.contains(anchor));
+        let element = (site.element.0.as_str(), site.element.1);
+        match anchor {
+            Some(anchor) => {
+                built.insert(element, (site, anchor));
+            }
+            None => {
+                dropped.insert(element);
+            }
+        }
+        dropped.extend(
+            site.duplicates
+                .iter()
+                .map(|(kind, id)| (kind.as_str(), *id)),
+        );
+    }
+
+    let mut found = Vec::new();
+    elements.retain_mut(|element| {
+        let key = (element.kind(), element.id());
+        if let Some(&(site, anchor)) = built.get(&key) {
+            let Some((x, z)) = reference_point(element) else {
+                return true;
+            };
+            let tags = element.tags_mut();
+            tags.insert(LANDMARK_TAG.to_string(), site.prefab.clone());
+            tags.insert(
+                ANCHOR_TAG.to_string(),
+                format!("{},{}", anchor.x - x, anchor.z - z),
+            );
+            found.push(site.name.clone());
+            return true;
+        }
+        !dropped.contains(&key)
+    });
+    found
+}
+
+/// Block the build of a tagged element is centred on
+fn anchor(element: &ProcessedElement) -> Option<(i32, i32)> {
+    let (dx, dz) = element.tags().get(ANCHOR_TAG)?.split_once(',')?;
+    let (x, z) = reference_point(element)?;
+    Some((x + dx.parse::<i32>().ok()?, z + dz.parse::<i32>().ok()?))
+}
+
+/// Position of a block in a schematic (x, y, z) and its index in the palette
+type PrefabBlock = (i32, i32, i32, usize);
+
+/// A landmark build: its size and its blocks; air is left out
+pub struct Prefab {
+    pub width: i32,
+    pub length: i32,
+    palette: Vec<Option<BlockWithProperties>>,
+    blocks: Vec<PrefabBlock>,
+    /// Block names of the schematic Arnis has no block for; they are left out
+    pub unknown: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicBlocks {
+    palette: HashMap<String, i32>,
+    data: ByteArray,
+}
+
+/// A Sponge schematic, version 2 (palette and block data at the top) or 3 (under `Blocks`)
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Schematic {
+    width: i16,
+    height: i16,
+    length: i16,
+    palette: Option<HashMap<String, i32>>,
+    block_data: Option<ByteArray>,
+    blocks: Option<SchematicBlocks>,
+}
+
+/// Version 3 nests the schematic in the root compound
+#[derive(Deserialize)]
+struct SchematicRoot {
+    #[serde(rename = "Schematic")]
+    schematic: Option<Schematic>,
+}
+
+/// Block of a block state such as `minecraft:oak_stairs[facing=north,half=bottom]`; `None`
+/// for air and for blocks Arnis does not know
+fn block_state(state: &str) -> Result<Option<BlockWithProperties>, ()> {
+    let state = state.strip_prefix("minecraft:").unwrap_or(state);
+    let (name, properties) = match state.split_once('[') {
+        Some((name, properties)) => (name, properties.trim_end_matches(']')),
+        None => (state, ""),
+    };
+    if matches!(name, "air" | "cave_air" | "void_air") {
+        return Ok(None);
+    }
+    let block = Block::from_name(name).ok_or(())?;
+    let properties: HashMap<String, Value> = properties
+        .split(',')
+        .filter_map(|property| property.split_once('='))
+        .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+        .collect();
+    Ok(Some(if properties.is_empty() {
+        BlockWithProperties::simple(block)
+    } else {
+        BlockWithProperties::new(block, Some(Value::Compound(properties)))
+    }))
+}
+
+/// Parses a Sponge schematic, gzipped as WorldEdit saves it or not.
+pub fn parse_schematic(bytes: &[u8]) -> Result<Prefab, String> {
+    let mut nbt = Vec::new();
+    let nbt = match GzDecoder::new(bytes).read_to_end(&mut nbt) {
+        Ok(_) => &nbt[..],
+        Err(_) => bytes,
+    };
+    let schematic = match fastnbt::from_bytes::<SchematicRoot>(nbt) {
+        Ok(SchematicRoot {
+            schematic: Some(schematic),
+        }) => schematic,
+        _ => {
+            fastnbt::from_bytes::<Schematic>(nbt).map_err(|e| format!("Invalid schematic: {e}"))?
+        }
+    };
+    let (palette, data) = match (schematic.blocks, schematic.palette, schematic.block_data) {
+        (Some(blocks), _, _) => (blocks.palette, blocks.data),
+        (None, Some(palette), Some(data)) => (palette, data),
+        _ => return Err("Invalid schematic: no block palette".to_string()),
+    };
+
+    let mut states: Vec<Option<BlockWithProperties>> = vec![None; palette.len()];
+    let mut unknown = Vec::new();
+    for (state, index) in &palette {
+        let slot = states
+            .get_mut(*index as usize)
+            .ok_or_else(|| format!("Invalid schematic: palette index {index} out of range"))?;
+        match block_state(state) {
+            Ok(block) => *slot = block,
+            Err(()) => unknown.push(state.clone()),
+        }
+    }
+    unknown.sort();
+
+    let (width, height, length) = (
+        schematic.width as u16 as i32,
+        schematic.height as u16 as i32,
+        schematic.length as u16 as i32,
+    );
+    // Block data are palette indices as varints, x fastest, then z, then y
+    let mut blocks = Vec::new();
+    let (mut value, mut shift, mut position) = (0usize, 0, 0i32);
+    for &byte in data.iter() {
+        let byte = byte as u8;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 != 0 {
+            shift += 7;
+            if shift > 28 {
+                return Err("Invalid schematic: malformed block data".to_string());
+            }
+            continue;
+        }
+        if states.get(value).is_some_and(Option::is_some) {
+            blocks.push((
+                position % width,
+                position / (width * length),
+                (position / width) % length,
+                value,
+            ));
+        }
+        position += 1;
+        (value, shift) = (0, 0);
+    }
+    if position != width * height * length {
+        return Err(format!(
+            "Invalid schematic: {position} blocks for a {width}x{height}x{length} build"
+        ));
+    }
+    Ok(Prefab {
+        width,
+        length,
+        palette: states,
+        blocks,
+        unknown,
+    })
+}
+
+/// The landmark schematics of a run, loaded when first placed
+pub struct LandmarkBuilds {
+    dir: PathBuf,
+    prefabs: HashMap<String, Option<Prefab>>,
+}
+
+impl LandmarkBuilds {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            prefabs: HashMap::new(),
+        }
+    }
+
+    fn prefab(&mut self, file: &str) -> Option<&Prefab> {
+        let dir = &self.dir;
+        self.prefabs
+            .entry(file.to_string())
+            .or_insert_with(|| {
+                let path = dir.join(file);
+                let prefab = std::fs::read(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| parse_schematic(&bytes));
+                match prefab {
+                    Ok(prefab) => {
+                        if !prefab.unknown.is_empty() {
+                            eprintln!(
+                                "{} {} uses blocks Arnis does not have, left out: {}",
+                                "Warning:".yellow().bold(),
+                                path.display(),
+                                prefab.unknown.join(", ")
+                            );
+                        }
+                        Some(prefab)
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "{} Landmark build {} unavailable: {e}. The landmark is generated as usual.",
+                            "Warning:".yellow().bold(),
+                            path.display()
+                        );
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
+    /// Builds the landmark of a tagged element from its schematic, centred on the landmark's
+    /// whole footprint with its bottom layer at ground level. Returns false when the
+    /// schematic cannot be loaded.
+    pub fn place(
+        &mut self,
+        editor: &mut WorldEditor,
+        element: &ProcessedElement,
+        file: &str,
+    ) -> bool {
+        let Some((x, z)) = anchor(element) else {
+            return false;
+        };
+        let Some(prefab) = self.prefab(file) else {
+            return false;
+        };
+        let (x0, z0) = (x - prefab.width / 2, z - prefab.length / 2);
+        let y0 = editor.get_ground_level(x, z);
+        for &(bx, by, bz, state) in &prefab.blocks {
+            if let Some(block) = &prefab.palette[state] {
+                editor.set_block_with_properties_absolute(
+                    block.clone(),
+                    x0 + bx,
+                    y0 + by,
+                    z0 + bz,
+                    None,
+                    Some(&[]),
+                );
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate_system::geographic::LLBBox;
+    use crate::osm_parser;
+    use flate2::write::GzEncoder;
+    use serde::Serialize;
+    use std::io::Write;
+
+    #[test]
+    fn builds_each_landmark_once() {
+        let built_in: Vec<String> = load_registry(Path::new("/nonexistent"))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(
+            built_in,
+            ["Rundetårn", "Den Lille Havfrue", "Storebæltsbroen"]
+        );
+        let registry = parse_registry(
+            r#"[{"name":"Rundetårn","prefab":"rundetaarn.schem","match":{"wikidata":"Q1"}}]"#,
+        )
+        .unwrap();
+        assert!(parse_registry(r#"[{"name":"Any tower","prefab":"t.schem","match":{}}]"#).is_err());
+        assert!(parse_registry(
+            r#"[{"name":"Tower","prefab":"t.schem","match":{"name":"Tower"}}]"#
+        )
+        .is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("rundetaarn.schem"), b"").unwrap();
+
+        // The tower mapped as a node and as a building, and a church next to it
+        let data: OsmData = serde_json::from_str(
+            r#"{"elements":[
+            {"type":"node","id":1,"lat":55.6814,"lon":12.5758,
+             "tags":{"wikidata":"Q1","tourism":"attraction"}},
+            {"type":"node","id":3,"lat":55.6813,"lon":12.5757},
+            {"type":"node","id":4,"lat":55.6813,"lon":12.5759},
+            {"type":"node","id":5,"lat":55.6815,"lon":12.5759},
+            {"type":"way","id":2,"nodes":[3,4,5,3],"tags":{"wikidata":"Q1","building":"yes"}},
+            {"type":"node","id":7,"lat":55.6820,"lon":12.5770},
+            {"type":"node","id":8,"lat":55.6821,"lon":12.5771},
+            {"type":"node","id":9,"lat":55.6821,"lon":12.5770},
+            {"type":"way","id":6,"nodes":[7,8,9,7],"tags":{"building":"church"}}]}"#,
+        )
+        .unwrap();
+        let sites = locate_landmarks(&data, &registry, dir.path());
+        assert_eq!(sites.len(), 1);
+        assert_eq!(sites[0].element, ("way".to_string(), 2));
+        assert_eq!(sites[0].duplicates, vec![("node".to_string(), 1)]);
+
+        let bbox = LLBBox::new(55.68, 12.57, 55.69, 12.58).unwrap();
+        let (transformer, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&bbox, 1.0).unwrap();
+        let (elements, _) =
+            osm_parser::parse_osm_data_with_transformer(data, &transformer, xzbbox.clone(), false);
+
+        // The building is built, the node of the same tower dropped and the church kept
+        let mut tile = elements.clone();
+        let found = tag_landmarks(&mut tile, &sites, &transformer, &xzbbox);
+        assert_eq!(found, vec!["Rundetårn".to_string()]);
+        assert_eq!(tile.len(), 2);
+        let tower = tile.iter().find(|element| element.id() == 2).unwrap();
+        assert_eq!(tower.tags()[LANDMARK_TAG], "rundetaarn.schem");
+        let centre = transformer.transform_point(LLPoint::new(55.6814, 12.5758).unwrap());
+        assert_eq!(anchor(tower), Some((centre.x, centre.z)));
+
+        // A tile without the tower's centre leaves the whole tower to the tile that has it
+        let mut other_tile = elements;
+        let elsewhere = XZBBox::rect_from_xz_lengths(10.0, 10.0).unwrap();
+        assert!(tag_landmarks(&mut other_tile, &sites, &transformer, &elsewhere).is_empty());
+        assert!(other_tile.iter().all(|element| element.id() == 6));
+    }
+
+    #[test]
+    fn parses_sponge_schematics() {
+        #[derive(Serialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct V2 {
+            version: i32,
+            width: i16,
+            height: i16,
+            length: i16,
+            palette: HashMap<String, i32>,
+            block_data: ByteArray,
+        }
+        let schematic = V2 {
+            version: 2,
+            width: 2,
+            height: 2,
+            length: 1,
+            palette: HashMap::from([
+                ("minecraft:air".to_string(), 0),
+                ("minecraft:stone_bricks".to_string(), 1),
+                (
+                    "minecraft:oak_stairs[facing=north,half=bottom]".to_string(),
+                    2,
+                ),
+                ("minecraft:no_such_block".to_string(), 3),
+            ]),
+            block_data: ByteArray::new(vec![1, 0, 2, 3]),
+        };
+        let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&fastnbt::to_bytes(&schematic).unwrap())
+            .unwrap();
+
+        let prefab = parse_schematic(&gz.finish().unwrap()).unwrap();
+        assert_eq!((prefab.width, prefab.length), (2, 1));
+        assert_eq!(prefab.unknown, vec!["minecraft:no_such_block".to_string()]);
+        assert_eq!(prefab.blocks.len(), 2);
+        let (x, y, z, state) = prefab.blocks[1];
+        assert_eq!((x, y, z), (0, 1, 0));
+        assert!(prefab.palette[state].as_ref().unwrap().properties.is_some());
+    }
+}
@@ -33,7 +33,7 @@ pub fn build_generation_plan_with_limit(
     scale: f64,
     max_job_dimension_blocks: u32,
 ) -> Result<GenerationPlan, String> {
-    let (_, full_xzbbox) = CoordTransformer::llbbox_to_xzbbox(&full_bbox, scale)?;
+    let (transformer, full_xzbbox) = CoordTransformer::llbbox_to_xzbbox(&full_bbox, scale)?;
     let full_rect = full_xzbbox.bounding_rect();
     let total_blocks_x = full_rect.total_blocks_x();
     let total_blocks_z = full_rect.total_blocks_z();
@@ -80,7 +80,28 @@ pub fn build_generation_plan_with_limit(
             let min_lng = full_bbox.min().lng() + west_ratio * full_lng_span;
             let max_lng = full_bbox.min().lng() + east_ratio * full_lng_span;
 
-            let llbbox = LLBBox::new(min_lat, min_lng, max_lat, max_lng)?;
+            // Under the sinusoidal projection a tile's blocks reach further east and west
+            // towards the pole than its share of the longitudes, so its data is fetched out to
+            // its corners
+            let llbbox = if transformer.is_linear() {
+                LLBBox::new(min_lat, min_lng, max_lat, max_lng)?
+            } else {
+                let corner_lngs = [start_block_x, end_block_x].map(|x| {
+                    [start_block_z, end_block_z]
+                        .map(|z| transformer.inverse_transform(x as f64, z as f64).1)
+                });
+                let corner_lngs = corner_lngs.iter().flatten();
+                let west = corner_lngs
+                    .clone()
+                    .fold(min_lng, |west, &lng| west.min(lng));
+                let east = corner_lngs.fold(max_lng, |east, &lng| east.max(lng));
+                LLBBox::new(
+                    min_lat,
+                    west.max(full_bbox.min().lng()),
+                    max_lat,
+                    east.min(full_bbox.max().lng()),
+                )?
+            };
 
             let tile_xzbbox = if x_block_count == 0 || z_block_count == 0 {
                 return Err("Generated an empty tile while splitting a large area".to_string());
@@ -132,5 +153,28 @@ mod tests {
         assert_eq!(last.max().x, plan.full_xzbbox.max_x());
         assert_eq!(last.max().z, plan.full_xzbbox.max_z());
     }
+
+    #[test]
+    fn arctic_tiles_reach_out_to_their_corners() {
+        let bbox = LLBBox::new(64.0, -52.0, 66.0, -50.0).unwrap();
+        let plan = build_generation_plan_with_limit(bbox, 1.0, 20_000).unwrap();
+        let (transformer, _) = CoordTransformer::llbbox_to_xzbbox(&bbox, 1.0).unwrap();
+        assert!(!transformer.is_linear());
+
+        // West of the central meridian, a tile's north-west corner lies west of its share of
+        // the longitudes, inside its western neighbour's share
+        assert!(plan.tiles[1].llbbox.min().lng() < plan.tiles[0].llbbox.max().lng());
+        for tile in &plan.tiles {
+            let rect = tile.xzbbox.bounding_rect();
+            for x in [rect.min().x, rect.max().x] {
+                for z in [rect.min().z, rect.max().z] {
+                    let (_, lng) = transformer.inverse_transform(x as f64, z as f64);
+                    let lng = lng.clamp(bbox.min().lng(), bbox.max().lng());
+                    assert!(tile.llbbox.min().lng() <= lng + 1e-9);
+                    assert!(lng <= tile.llbbox.max().lng() + 1e-9);
+                }
+            }
+        }
+    }
 }
 
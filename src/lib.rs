@@ -0,0 +1,87 @@
+//! Arnis as a library: the generation pipeline behind the `arnis` binary, also driven by
+//! the benchmarks in `benches/`.
+
+// The manifest config has outgrown the default for serde_json::json!
+#![recursion_limit = "256"]
+
+pub mod area;
+pub mod args;
+pub mod bathymetry;
+pub mod bbr_codes;
+#[cfg(feature = "bedrock")]
+pub mod bedrock_block_map;
+pub mod biomes;
+pub mod block_definitions;
+pub mod bresenham;
+pub mod camera_path;
+pub mod clipping;
+pub mod colors;
+pub mod content_filter;
+pub mod coordinate_system;
+pub mod corridor_grading;
+pub mod coverage;
+pub mod data_processing;
+pub mod data_sources;
+pub mod deterministic_rng;
+pub mod dhm;
+pub mod element_processing;
+pub mod elevation_data;
+pub mod enrichment;
+pub mod exclusion;
+pub mod feature_index;
+pub mod floodfill;
+pub mod floodfill_cache;
+pub mod geology;
+pub mod gis_export;
+pub mod ground;
+pub mod height_estimation;
+pub mod history;
+pub mod info_book;
+pub mod land_polygons;
+pub mod landmarks;
+pub mod large_area;
+pub mod manifest;
+pub mod map_renderer;
+pub mod map_transformation;
+pub mod ortho_alignment;
+pub mod osm_file;
+pub mod osm_parser;
+pub mod overlay;
+pub mod overpass_endpoints;
+pub mod privacy;
+#[cfg(feature = "gui")]
+pub mod progress;
+pub mod progress_aggregator;
+pub mod retrieve_data;
+pub mod seam;
+pub mod task_graph;
+#[cfg(feature = "gui")]
+pub mod telemetry;
+pub mod terrain_pipeline;
+#[cfg(test)]
+mod test_utilities;
+pub mod topology_repair;
+pub mod tour;
+pub mod urban_ground;
+pub mod version_check;
+pub mod world_editor;
+pub mod world_merge;
+pub mod world_utils;
+
+#[cfg(feature = "gui")]
+pub mod gui;
+
+// If the user does not want the GUI, it's easiest to just mock the progress module to do nothing
+#[cfg(not(feature = "gui"))]
+pub mod progress {
+    pub fn emit_gui_error(_message: &str) {}
+    pub fn emit_gui_progress_update(_progress: f64, _message: &str) {}
+    pub fn emit_map_preview_ready() {}
+    pub fn emit_open_mcworld_file(_path: &str) {}
+    pub fn is_running_with_gui() -> bool {
+        false
+    }
+    pub fn cancel_requested() -> bool {
+        false
+    }
+}
@@ -1,60 +1,28 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod args;
-#[cfg(feature = "bedrock")]
-mod bedrock_block_map;
-mod block_definitions;
-mod bresenham;
-mod clipping;
-mod colors;
-mod coordinate_system;
-mod data_processing;
-mod deterministic_rng;
-mod dhm;
-mod element_processing;
-mod elevation_data;
-mod floodfill;
-mod floodfill_cache;
-mod ground;
-mod land_polygons;
-mod large_area;
-mod map_renderer;
-mod map_transformation;
-mod osm_parser;
-#[cfg(feature = "gui")]
-mod progress;
-mod retrieve_data;
-#[cfg(feature = "gui")]
-mod telemetry;
-#[cfg(test)]
-mod test_utilities;
-mod urban_ground;
-mod version_check;
-mod world_editor;
-mod world_utils;
-
-use args::Args;
+use arnis::{
+    area, args, bbr_codes, content_filter, coordinate_system, data_processing, data_sources,
+    elevation_data, enrichment, exclusion, feature_index, floodfill_cache, gis_export, ground,
+    height_estimation, history, info_book, landmarks, large_area, manifest, map_transformation,
+    osm_file, osm_parser, overpass_endpoints, privacy, retrieve_data, seam, task_graph,
+    version_check, world_editor, world_merge, world_utils,
+};
+
+use args::{
+    Args, CacheAction, Cli, CodesArgs, Command, FetchBundleArgs, HistoryAction, MergeArgs,
+    PreviewArgs, StripArgs, VerifyArgs,
+};
 use clap::Parser;
 use colored::*;
 use coordinate_system::transformation::CoordTransformer;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use std::{env, fs, io::Write};
+use task_graph::TaskGraph;
 use world_editor::WorldFormat;
 
 #[cfg(feature = "gui")]
-mod gui;
-
-// If the user does not want the GUI, it's easiest to just mock the progress module to do nothing
-#[cfg(not(feature = "gui"))]
-mod progress {
-    pub fn emit_gui_error(_message: &str) {}
-    pub fn emit_gui_progress_update(_progress: f64, _message: &str) {}
-    pub fn emit_map_preview_ready() {}
-    pub fn emit_open_mcworld_file(_path: &str) {}
-    pub fn is_running_with_gui() -> bool {
-        false
-    }
-}
+use arnis::gui;
 #[cfg(target_os = "windows")]
 use windows::Win32::System::Console::{AttachConsole, FreeConsole, ATTACH_PARENT_PROCESS};
 
@@ -114,6 +82,7 @@ fn run_cli_job(
     job_bbox: coordinate_system::geographic::LLBBox,
     target_xzbbox: Option<coordinate_system::cartesian::XZBBox>,
     full_transformer: Option<&CoordTransformer>,
+    block_medians: Option<&height_estimation::BlockMedians>,
     generation_path: &PathBuf,
     world_format: WorldFormat,
     level_name: Option<String>,
@@ -121,48 +90,201 @@ fn run_cli_job(
     total_tiles: usize,
     save_json_path: Option<&str>,
 ) -> Result<(), String> {
-    let raw_data = match &args.file {
-        Some(file) => retrieve_data::fetch_data_from_file(file).map_err(|e| e.to_string())?,
-        None => retrieve_data::fetch_data_from_overpass(
-            job_bbox,
-            args.debug,
-            args.downloader.as_str(),
-            save_json_path,
-        )
-        .map_err(|e| e.to_string())?,
-    };
+    // The Overpass and register downloads only need the bbox, so they are started first as
+    // one task graph and run alongside the terrain download and processing, and the OSM parse
+    let realm = data_sources::Realm::of(&job_bbox);
+    let mut downloads = TaskGraph::new();
+    // A local extract takes the place of Overpass and is read alongside the terrain the same way
+    let overpass = args.file.is_none().then(|| {
+        let (debug, downloader) = (args.debug, args.downloader.clone());
+        let save_json_path = save_json_path.map(str::to_string);
+        let (osm_file, area) = (args.osm_file.clone(), args.bbox);
+        let layers = retrieve_data::OptionalLayers::from_args(args);
+        let fetch = move || match osm_file {
+            Some(osm_file) => osm_file::read_osm_file(&osm_file, &area, &job_bbox),
+            None => retrieve_data::fetch_data_from_overpass(
+                job_bbox,
+                layers,
+                debug,
+                &downloader,
+                save_json_path.as_deref(),
+            )
+            .map_err(|e| e.to_string()),
+        };
+        downloads.add("osm", &[], fetch).1
+    });
+    let prefetch =
+        enrichment::Prefetch::add_to(&mut downloads, args, job_bbox, realm.has_danish_data());
+    if args.debug {
+        println!("Downloads: {}", downloads.order().join(", "));
+    }
+    downloads.run(task_graph::DOWNLOAD_WORKERS);
 
     let mut job_args = Args {
         bbox: job_bbox,
+        area: args.area.clone(),
+        area_boundary: args.area_boundary.clone(),
+        exclusion_zones: args.exclusion_zones.clone(),
         file: args.file.clone(),
+        osm_file: args.osm_file.clone(),
+        no_history: args.no_history,
         land_polygons: args.land_polygons.clone(),
         save_json_file: save_json_path.map(str::to_string),
         path: Some(generation_path.clone()),
         bedrock: args.bedrock,
         downloader: args.downloader.clone(),
+        overpass_urls: args.overpass_urls.clone(),
         scale: args.scale,
+        snap: args.snap,
         ground_level: args.ground_level,
+        elevation_mode: args.elevation_mode,
+        meters_per_block: args.meters_per_block,
+        sea_level_y: args.sea_level_y,
+        terrain_exaggeration: args.terrain_exaggeration,
         terrain: args.terrain,
         interior: args.interior,
+        interiors: args.interiors,
+        bbr_codes: args.bbr_codes.clone(),
+        edge_margin: args.edge_margin,
         roof: args.roof,
         fillground: args.fillground,
         city_boundaries: args.city_boundaries,
+        urban_infill: args.urban_infill,
+        addresses: args.addresses,
+        place_names: args.place_names,
+        ortho: args.ortho,
+        coverage_map: args.coverage_map,
+        min_coverage: args.min_coverage,
+        ortho_check: args.ortho_check,
+        geodanmark_coast: args.geodanmark_coast,
+        geodanmark_roads: args.geodanmark_roads,
+        protected_nature: args.protected_nature,
+        field_crops: args.field_crops,
+        field_crops_year: args.field_crops_year,
+        forest_types: args.forest_types,
+        land_cover: args.land_cover,
+        wind_turbines: args.wind_turbines,
+        ancient_monuments: args.ancient_monuments,
+        landmarks: args.landmarks.clone(),
+        gtfs: args.gtfs.clone(),
+        privacy_audit: args.privacy_audit,
         dhm_token: args.dhm_token.clone(),
+        dem: args.dem.clone(),
+        dhm_resample: args.dhm_resample,
+        dhm_resolution: args.dhm_resolution,
+        dhm_retries: args.dhm_retries,
+        dhm_rounds: args.dhm_rounds,
+        dhm_round_pause: args.dhm_round_pause,
+        dhm_total_timeout: args.dhm_total_timeout,
+        bathymetry: args.bathymetry,
+        bathymetry_token: args.bathymetry_token.clone(),
+        carve_sea: args.carve_sea,
+        tide: args.tide,
+        flood_scenario: args.flood_scenario,
+        navigable_waterways: args.navigable_waterways,
+        anchor: args.anchor,
+        export_gis: args.export_gis.clone(),
+        seam_export: args.seam_export.clone(),
+        seam_from: args.seam_from.clone(),
+        accessibility: args.accessibility,
+        school_mode: args.school_mode,
+        overlay: args.overlay.clone(),
+        minecart_transit: args.minecart_transit,
+        tour: args.tour,
+        camera_path: args.camera_path,
+        info_book: args.info_book,
+        time_of_day: args.time_of_day,
+        gamemode: args.gamemode,
+        border: args.border,
+        showcase: args.showcase,
+        feature_index: args.feature_index,
+        manifest: args.manifest,
         debug: args.debug,
         timeout: args.timeout,
     };
 
+    let local_transformer;
+    let (transformer, job_xzbbox) = match (full_transformer, target_xzbbox) {
+        (Some(transformer), Some(tile_xzbbox)) => (transformer, tile_xzbbox),
+        _ => {
+            let (transformer, xzbbox) = CoordTransformer::llbbox_to_xzbbox(&job_bbox, args.scale)?;
+            local_transformer = transformer.with_snap(args.snap);
+            (&local_transformer, xzbbox)
+        }
+    };
     let mut ground = ground::generate_ground_data(&job_args);
+    ground.reproject(&job_bbox, transformer, &job_xzbbox);
 
-    let (mut parsed_elements, mut xzbbox) = match (target_xzbbox, full_transformer) {
-        (Some(tile_xzbbox), Some(transformer)) => osm_parser::parse_osm_data_with_transformer(
-            raw_data,
-            transformer,
-            tile_xzbbox,
-            args.debug,
-        ),
-        _ => osm_parser::parse_osm_data(raw_data, job_bbox, args.scale, args.debug),
+    if let Some(data) = ground.elevation_data_mut() {
+        for path in &args.seam_from {
+            match seam::read_sidecar(path) {
+                Ok(neighbour) => match seam::pin_to_neighbour(data, &job_bbox, &neighbour) {
+                    Some(edge) => println!("Seam: {edge:?} border pinned to {}", path.display()),
+                    None => eprintln!(
+                        "{} {} does not border this bbox; no seam is pinned.",
+                        "Warning:".yellow().bold(),
+                        path.display()
+                    ),
+                },
+                Err(e) => eprintln!("{} {e}", "Warning:".yellow().bold()),
+            }
+        }
+        if let Some(path) = &args.seam_export {
+            let path = if total_tiles > 1 {
+                path.with_extension(format!("tile-{tile_index:02}-of-{total_tiles:02}.json"))
+            } else {
+                path.clone()
+            };
+            let sidecar = seam::SeamSidecar::from_elevation(&job_bbox, data);
+            if let Err(e) = seam::write_sidecar(&path, &sidecar) {
+                eprintln!("{} {e}", "Warning:".yellow().bold());
+            }
+        }
+    } else if !args.seam_from.is_empty() || args.seam_export.is_some() {
+        eprintln!(
+            "{} Seam matching needs --terrain; no seam is pinned or exported.",
+            "Warning:".yellow().bold()
+        );
+    }
+
+    let waiting_since = Instant::now();
+    let raw_data = match (overpass, &args.file) {
+        (Some(overpass), _) => overpass.join()?,
+        (None, Some(file)) => {
+            retrieve_data::fetch_data_from_file(file).map_err(|e| e.to_string())?
+        }
+        (None, None) => {
+            return Err(
+                "No OSM data to generate from: no file was given and no download was started"
+                    .to_string(),
+            )
+        }
     };
+    // Landmarks are found before the data is clipped to the tile, so one straddling tiles is
+    // centred on its whole footprint and built once
+    let mut landmark_sites = Vec::new();
+    if let Some(dir) = &args.landmarks {
+        if args.scale != 1.0 {
+            eprintln!(
+                "{} Landmark builds are made at 1:1 and only placed at --scale 1; the landmarks are generated as usual.",
+                "Warning:".yellow().bold()
+            );
+        } else {
+            match landmarks::load_registry(dir) {
+                Ok(registry) => {
+                    landmark_sites = landmarks::locate_landmarks(&raw_data, &registry, dir);
+                }
+                Err(e) => eprintln!(
+                    "{} {e}. The landmarks are generated as usual.",
+                    "Warning:".yellow().bold()
+                ),
+            }
+        }
+    }
+    let (mut parsed_elements, mut xzbbox) =
+        osm_parser::parse_osm_data_with_transformer(raw_data, transformer, job_xzbbox, args.debug);
+    history::record_duration("osm_data", waiting_since.elapsed());
+    history::record_elements("osm_elements", &parsed_elements);
     parsed_elements
         .sort_by_key(|element: &osm_parser::ProcessedElement| osm_parser::get_priority(element));
 
@@ -170,24 +292,379 @@ fn run_cli_job(
         write_debug_osm_dump(&parsed_elements, tile_index, total_tiles);
     }
 
+    if let Some(warning) = data_sources::danish_only_warning(args, realm) {
+        eprintln!("{} {warning}", "Warning:".yellow().bold());
+    }
+
+    if let Some(pending) = prefetch.addresses {
+        match pending.join() {
+            Ok(addresses) => {
+                let nodes = enrichment::addresses::address_nodes(&addresses, transformer, &xzbbox);
+                println!("DAWA: {} addresses in the area", nodes.len());
+                parsed_elements.extend(nodes);
+            }
+            Err(e) => eprintln!(
+                "{} Addresses unavailable: {e}. No address signs are placed.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if let Some(pending) = prefetch.nature {
+        match pending.join() {
+            Ok(areas) => {
+                let ways = enrichment::nature::nature_ways(&areas, transformer, &parsed_elements);
+                println!("Danmarks Miljøportal: {} §3 nature areas added", ways.len());
+                // Ahead of the OSM elements of the same priority, so they win over plain grass
+                parsed_elements.splice(0..0, ways);
+                parsed_elements.sort_by_key(osm_parser::get_priority);
+            }
+            Err(e) => eprintln!(
+                "{} §3 nature areas unavailable: {e}. OSM landuse is used as is.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if let Some(pending) = prefetch.crops {
+        match pending.join() {
+            Ok(fields) => {
+                let ways = enrichment::crops::field_ways(&fields, transformer);
+                println!("Markkort: {} fields with known crops", ways.len());
+                // Ahead of the OSM farmland, so the declared crop is planted
+                parsed_elements.splice(0..0, ways);
+                parsed_elements.sort_by_key(osm_parser::get_priority);
+            }
+            Err(e) => eprintln!(
+                "{} Field crops unavailable: {e}. Farmland gets the generic crop mix.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if let Some(pending) = prefetch.forests {
+        match pending.join() {
+            Ok(areas) => {
+                let typed = enrichment::forests::apply_forest_types(
+                    &mut parsed_elements,
+                    &areas,
+                    transformer,
+                );
+                println!("Miljøstyrelsen: {typed} forests typed");
+            }
+            Err(e) => eprintln!(
+                "{} Forest types unavailable: {e}. Forests use their OSM leaf_type.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    // Land cover only fills what the elements leave empty, once the sea is in
+    let mut land_cover_ways = Vec::new();
+    if let Some(pending) = prefetch.land_cover {
+        match pending.join() {
+            Ok(areas) => {
+                land_cover_ways = enrichment::land_cover::cover_ways(&areas, transformer, &xzbbox);
+                println!("CORINE: {} land cover areas", land_cover_ways.len());
+            }
+            Err(e) => eprintln!(
+                "{} Land cover unavailable: {e}. Unmapped ground stays grass.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if args.geodanmark_coast && realm.has_danish_data() {
+        match prefetch.coast {
+            None => eprintln!(
+                "{} --geodanmark-coast needs a Dataforsyningen token (--dhm-token); the OSM \
+                 coastline is used.",
+                "Warning:".yellow().bold()
+            ),
+            Some(pending) => match pending.join() {
+                Ok(features) => {
+                    let (added, replaced) = enrichment::coast::apply_coast(
+                        &mut parsed_elements,
+                        &features,
+                        transformer,
+                    );
+                    println!(
+                        "GeoDanmark: {added} coastline, harbour basin and breakwater ways added, \
+                         {replaced} OSM coastline ways replaced"
+                    );
+                    parsed_elements.sort_by_key(osm_parser::get_priority);
+                }
+                Err(e) => eprintln!(
+                    "{} GeoDanmark coastline unavailable: {e}. The OSM coastline is used.",
+                    "Warning:".yellow().bold()
+                ),
+            },
+        }
+    }
+
+    if args.geodanmark_roads && realm.has_danish_data() {
+        match prefetch.roads {
+            None => eprintln!(
+                "{} --geodanmark-roads needs a Dataforsyningen token (--dhm-token); roads keep \
+                 their OSM widths.",
+                "Warning:".yellow().bold()
+            ),
+            Some(pending) => match pending.join() {
+                Ok(roads) => {
+                    let (attributed, split) = enrichment::roads::apply_road_attributes(
+                        &mut parsed_elements,
+                        &roads,
+                        transformer,
+                    );
+                    println!(
+                        "GeoDanmark: {attributed} roads given widths, lanes or surfaces, {split} \
+                         motorways split into carriageways"
+                    );
+                    parsed_elements.sort_by_key(osm_parser::get_priority);
+                }
+                Err(e) => eprintln!(
+                    "{} GeoDanmark road centerlines unavailable: {e}. Roads keep their OSM widths.",
+                    "Warning:".yellow().bold()
+                ),
+            },
+        }
+    }
+
+    if args.interiors && realm.has_danish_data() {
+        match prefetch.buildings {
+            None => eprintln!(
+                "{} BBR building usages need a Dataforsyningen token (--dhm-token); buildings \
+                 are furnished by their OSM tags only.",
+                "Warning:".yellow().bold()
+            ),
+            Some(pending) => match pending.join() {
+                Ok(buildings) => {
+                    // The extension file was checked when the arguments were
+                    let codes = bbr_codes::CodeTables::load(args.bbr_codes.as_deref())
+                        .unwrap_or_else(|_| bbr_codes::CodeTables::built_in());
+                    let tagged = enrichment::bbr::apply_building_data(
+                        &mut parsed_elements,
+                        &buildings,
+                        transformer,
+                        &codes,
+                    );
+                    println!("BBR: {tagged} buildings given their registered usage");
+                }
+                Err(e) => eprintln!(
+                    "{} BBR building usages unavailable: {e}. Buildings are furnished by their \
+                     OSM tags only.",
+                    "Warning:".yellow().bold()
+                ),
+            },
+        }
+    }
+
+    if let Some(pending) = prefetch.wind_turbines {
+        match pending.join() {
+            Ok(turbines) => {
+                let added = enrichment::wind_turbines::turbine_elements(
+                    &turbines,
+                    transformer,
+                    &mut parsed_elements,
+                );
+                println!(
+                    "Energistyrelsen: {} turbines in the area, {} added",
+                    turbines.len(),
+                    added.len()
+                );
+                parsed_elements.extend(added);
+                parsed_elements.sort_by_key(osm_parser::get_priority);
+            }
+            Err(e) => eprintln!(
+                "{} Turbine register unavailable: {e}. OSM turbines get the default size.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if let Some(pending) = prefetch.monuments {
+        match pending.join() {
+            Ok(monuments) => {
+                let added = enrichment::monuments::monument_nodes(
+                    &monuments,
+                    transformer,
+                    &parsed_elements,
+                );
+                println!(
+                    "Fund og Fortidsminder: {} monuments in the area, {} added",
+                    monuments.len(),
+                    added.len()
+                );
+                parsed_elements.extend(added);
+                parsed_elements.sort_by_key(osm_parser::get_priority);
+            }
+            Err(e) => eprintln!(
+                "{} Fund og Fortidsminder unavailable: {e}. Only OSM monuments are built.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if let Some(dir) = &args.gtfs {
+        match enrichment::transit::read_gtfs_stops(dir, &job_bbox) {
+            Ok(stops) => {
+                let added = enrichment::transit::transit_elements(
+                    &stops,
+                    transformer,
+                    &mut parsed_elements,
+                );
+                println!(
+                    "GTFS: {} stops in the area, {} stops and platforms added",
+                    stops.len(),
+                    added.len()
+                );
+                parsed_elements.extend(added);
+                parsed_elements.sort_by_key(osm_parser::get_priority);
+            }
+            Err(e) => eprintln!(
+                "{} Transit stops unavailable: {e}. Only the OSM stops are placed.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if let Some(boundary) = &args.area_boundary {
+        parsed_elements.extend(boundary.marker_ways(transformer));
+    }
+
+    if let Some(path) = &args.exclusion_zones {
+        let zones = exclusion::load_zones(path)?;
+        println!("Exclusion zones: {} polygons kept free", zones.len());
+        parsed_elements.extend(exclusion::marker_ways(&zones, transformer));
+    }
+
+    if !landmark_sites.is_empty() {
+        let found =
+            landmarks::tag_landmarks(&mut parsed_elements, &landmark_sites, transformer, &xzbbox);
+        if !found.is_empty() {
+            println!("Landmarks: built from prefabs: {}", found.join(", "));
+        }
+    }
+
+    if let Some(pending) = prefetch.place_names {
+        match pending.join() {
+            Ok(places) => {
+                let nodes = enrichment::place_names::place_nodes(&places, transformer, &xzbbox);
+                println!("Danske Stednavne: {} named places in the area", nodes.len());
+                parsed_elements.extend(nodes);
+            }
+            Err(e) => eprintln!(
+                "{} Place names unavailable: {e}. No place-name markers are built.",
+                "Warning:".yellow().bold()
+            ),
+        }
+    }
+
+    if args.privacy_audit {
+        let report = privacy::audit_elements(&mut parsed_elements);
+        if report.is_clean() {
+            println!(
+                "Privacy audit: {} register elements checked, nothing removed",
+                report.register_elements
+            );
+        } else {
+            println!(
+                "Privacy audit: {} register elements checked, {} fields and {} identifiers removed",
+                report.register_elements,
+                report
+                    .removed_fields
+                    .values()
+                    .flat_map(|fields| fields.values())
+                    .sum::<usize>(),
+                report.removed_identifiers.values().sum::<usize>()
+            );
+        }
+        privacy::record(report);
+    }
+
+    // Filtered before anything is exported, so the GIS layers match the world
+    if args.school_mode {
+        let filtered = content_filter::apply_school_mode(&mut parsed_elements);
+        println!("School mode: filtered {filtered} age-restricted venues");
+    }
+
+    if let Some(export_dir) = &args.export_gis {
+        let export_dir = if total_tiles > 1 {
+            export_dir.join(format!("tile-{tile_index:02}-of-{total_tiles:02}"))
+        } else {
+            export_dir.clone()
+        };
+        if let Err(e) = gis_export::export_gis_layers(
+            &export_dir,
+            &parsed_elements,
+            &ground,
+            transformer,
+            &xzbbox,
+            args.scale,
+            args.geodanmark_roads,
+        ) {
+            eprintln!("{} {}", "Warning:".yellow().bold(), e);
+        }
+    }
+
     map_transformation::transform_map(&mut parsed_elements, &mut xzbbox, &mut ground);
 
+    // The whole world this job is a tile of, for what goes around its outer edge
+    let mut world_xzbbox = CoordTransformer::llbbox_to_xzbbox(&args.bbox, args.scale)?.1;
+    if let Some(anchor) = &args.anchor {
+        let shift = map_transformation::anchor::align_to_anchor(
+            anchor,
+            transformer,
+            &mut parsed_elements,
+            &mut xzbbox,
+        );
+        println!(
+            "Anchor: map shifted by {}, {} blocks; world spans X {}..{}, Z {}..{}",
+            shift.dx,
+            shift.dz,
+            xzbbox.min_x(),
+            xzbbox.max_x(),
+            xzbbox.min_z(),
+            xzbbox.max_z()
+        );
+        world_xzbbox += shift;
+    }
+
+    let coverage_map = args.coverage_map.then(|| {
+        report_dir(generation_path).join(if total_tiles > 1 {
+            format!("arnis-coverage.tile-{tile_index:02}-of-{total_tiles:02}.png")
+        } else {
+            "arnis-coverage.png".to_string()
+        })
+    });
     let generation_options = data_processing::GenerationOptions {
         path: generation_path.clone(),
         format: world_format,
         level_name,
         spawn_point: None,
         update_spawn_after_generation: true,
+        coverage_map,
+        world_xzbbox,
+        block_medians: block_medians.cloned(),
     };
 
+    for key in ["building", "highway"] {
+        let tagged = parsed_elements
+            .iter()
+            .filter(|e| e.tags().contains_key(key));
+        history::record_elements(&format!("{key}s"), tagged);
+    }
+    let generation_started = Instant::now();
     data_processing::generate_world_with_options(
         parsed_elements,
+        land_cover_ways,
         xzbbox,
         job_bbox,
         ground,
         &job_args,
         generation_options,
     )?;
+    history::record_duration("generation", generation_started.elapsed());
 
     // Keep the args path pointed at the generated world in case GUI-specific code is compiled in.
     job_args.path = Some(generation_path.clone());
@@ -195,13 +672,17 @@ fn run_cli_job(
     Ok(())
 }
 
-fn run_cli() {
-    // Configure thread pool with 90% CPU cap to keep system responsive
-    floodfill_cache::configure_rayon_thread_pool(0.9);
-
-    // Clean up old cached elevation tiles on startup
-    elevation_data::cleanup_old_cached_tiles();
+/// Directory the reports of a run go into: the world directory, or the directory holding
+/// the world file
+fn report_dir(generation_path: &Path) -> &Path {
+    if generation_path.is_dir() {
+        generation_path
+    } else {
+        generation_path.parent().unwrap_or(Path::new("."))
+    }
+}
 
+fn print_banner() {
     let version: &str = env!("CARGO_PKG_VERSION");
     let repository: &str = env!("CARGO_PKG_REPOSITORY");
     println!(
@@ -222,6 +703,288 @@ fn run_cli() {
         version,
         repository.bright_white().bold()
     );
+}
+
+fn run_cli() {
+    let (argv, area_boundary) = match area::resolve_area_args(std::env::args_os().collect()) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+    let mut command = match Cli::parse_from(argv).into_command() {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}: {}", "Error".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+    if let Command::Generate(args) = &mut command {
+        args.area_boundary = area_boundary;
+    }
+
+    print_banner();
+
+    let result = match command {
+        Command::Generate(args) => {
+            run_generate(&args);
+            Ok(())
+        }
+        Command::Preview(args) => run_preview(&args),
+        Command::FetchBundle(args) => run_fetch_bundle(&args),
+        Command::Verify(args) => run_verify(&args),
+        Command::Strip(args) => run_strip(&args),
+        Command::Merge(args) => run_merge(&args),
+        Command::Update => run_update(),
+        Command::Cache { action } => {
+            run_cache(action);
+            Ok(())
+        }
+        Command::History { action } => run_history(&action),
+        Command::Codes(args) => run_codes(&args),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{} {}", "Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+}
+
+fn run_preview(args: &PreviewArgs) -> Result<(), String> {
+    let plan = large_area::build_generation_plan(args.bbox, args.scale)?;
+    let (len_z, len_x) =
+        coordinate_system::transformation::geo_distance(args.bbox.min(), args.bbox.max());
+    let bounds = plan.full_xzbbox.bounding_rect();
+
+    println!("Area: {len_x:.0} x {len_z:.0} m");
+    println!(
+        "World size: {} x {} blocks (scale {})",
+        bounds.total_blocks_x(),
+        bounds.total_blocks_z(),
+        args.scale
+    );
+    println!(
+        "Generation jobs: {} (max tile: {} x {} blocks)",
+        plan.tiles.len(),
+        large_area::MAX_JOB_DIMENSION_BLOCKS,
+        large_area::MAX_JOB_DIMENSION_BLOCKS
+    );
+    if plan.requires_tiling() {
+        for tile in &plan.tiles {
+            println!(
+                "  tile {}/{}: {:.6},{:.6},{:.6},{:.6}",
+                tile.index,
+                tile.total,
+                tile.llbbox.min().lat(),
+                tile.llbbox.min().lng(),
+                tile.llbbox.max().lat(),
+                tile.llbbox.max().lng()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_fetch_bundle(args: &FetchBundleArgs) -> Result<(), String> {
+    let output = args.output.to_string_lossy();
+    retrieve_data::fetch_data_from_overpass(
+        args.bbox,
+        retrieve_data::OptionalLayers::all(),
+        args.debug,
+        args.downloader.as_str(),
+        Some(&output),
+    )
+    .map_err(|e| e.to_string())?;
+    println!(
+        "{} Generate from it with: --file {}",
+        "Done!".green().bold(),
+        output
+    );
+    Ok(())
+}
+
+fn run_verify(args: &VerifyArgs) -> Result<(), String> {
+    let report = manifest::verify_world(&args.world)?;
+    for key in &report.mismatched {
+        println!("{} {}", "modified:".red().bold(), key);
+    }
+    for key in &report.missing {
+        println!("{} {}", "missing:".red().bold(), key);
+    }
+    for key in &report.unlisted {
+        println!("{} {}", "not in manifest:".yellow().bold(), key);
+    }
+    if !report.is_ok() {
+        return Err(format!(
+            "World does not match its manifest ({} of {} files verified)",
+            report.matched,
+            report.matched + report.mismatched.len() + report.missing.len()
+        ));
+    }
+    println!(
+        "{} All {} output files match the manifest",
+        "Verified!".green().bold(),
+        report.matched
+    );
+    Ok(())
+}
+
+fn run_strip(args: &StripArgs) -> Result<(), String> {
+    match &args.regenerate_from {
+        Some(source) => {
+            let (removed, copied) =
+                feature_index::regenerate_feature(&args.world, source, args.feature)?;
+            println!(
+                "{} Removed {} blocks and copied {} from {}",
+                "Done!".green().bold(),
+                removed,
+                copied,
+                source.display()
+            );
+        }
+        None => {
+            let removed = feature_index::strip_feature(&args.world, args.feature)?;
+            println!("{} Removed {} blocks", "Done!".green().bold(), removed);
+        }
+    }
+    Ok(())
+}
+
+fn run_merge(args: &MergeArgs) -> Result<(), String> {
+    let report = world_merge::merge_worlds(&args.world_a, &args.world_b, &args.out)?;
+    if !report.overlapping.is_empty() {
+        println!(
+            "{} regions were in both worlds; the newer one was kept",
+            report.overlapping.len()
+        );
+    }
+    println!(
+        "{} Merged {} regions from {} and {} from {} into {}",
+        "Done!".green().bold(),
+        report.from_a,
+        args.world_a.display(),
+        report.from_b,
+        args.world_b.display(),
+        args.out.display()
+    );
+    Ok(())
+}
+
+fn run_update() -> Result<(), String> {
+    let newer_available = version_check::check_for_updates().map_err(|e| e.to_string())?;
+    if newer_available {
+        println!(
+            "Download it from: {}",
+            env!("CARGO_PKG_REPOSITORY").bright_white().bold()
+        );
+    } else {
+        println!("No newer version found.");
+    }
+    Ok(())
+}
+
+fn run_cache(action: CacheAction) {
+    match action {
+        CacheAction::Info => {
+            let (tiles, bytes) = elevation_data::cached_tiles_usage();
+            println!(
+                "{} cached elevation tiles ({:.1} MB) in {}",
+                tiles,
+                bytes as f64 / (1024.0 * 1024.0),
+                elevation_data::TILE_CACHE_DIR
+            );
+        }
+        CacheAction::Clean => {
+            elevation_data::cleanup_old_cached_tiles();
+            let (tiles, _) = elevation_data::cached_tiles_usage();
+            println!(
+                "{tiles} cached elevation tiles remain (tiles older than {} days are removed)",
+                elevation_data::TILE_CACHE_MAX_AGE_DAYS
+            );
+        }
+        CacheAction::Clear => {
+            let deleted = elevation_data::clear_cached_tiles();
+            println!("Deleted {deleted} cached elevation tiles");
+        }
+    }
+}
+
+fn run_history(action: &HistoryAction) -> Result<(), String> {
+    let runs = history::load_runs(&history::history_path())?;
+    match action {
+        HistoryAction::List => {
+            if runs.is_empty() {
+                println!("No runs recorded in {}", history::history_path().display());
+            }
+            for run in &runs {
+                let bbox = run.config.get("bbox").map(|b| b.to_string());
+                println!(
+                    "{:>4}  {}  bbox {}  {:.0} s",
+                    run.id,
+                    info_book::civil_date((run.started / 86_400) as i64),
+                    bbox.as_deref().unwrap_or("-"),
+                    run.durations.get("total").copied().unwrap_or_default()
+                );
+            }
+        }
+        HistoryAction::Diff { run1, run2 } => {
+            let (a, b) = (
+                history::find_run(&runs, run1)?,
+                history::find_run(&runs, run2)?,
+            );
+            println!("Run {} -> run {}", a.id, b.id);
+            for line in history::diff_runs(a, b) {
+                println!("{line}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_codes(args: &CodesArgs) -> Result<(), String> {
+    let tables = bbr_codes::CodeTables::load(args.bbr_codes.as_deref())?;
+    let runs = history::load_runs(&history::history_path())?;
+    let mut unmapped: Vec<(bbr_codes::CodeField, u16, f64)> = runs
+        .last()
+        .into_iter()
+        .flat_map(|run| &run.stats)
+        .filter_map(|(stat, &count)| {
+            bbr_codes::CodeField::from_unmapped_stat(stat).map(|(field, code)| (field, code, count))
+        })
+        // Codes an extension has mapped since are no longer missing
+        .filter(|&(field, code, _)| tables.value(field, code).is_none())
+        .collect();
+    unmapped.sort_by_key(|&(field, code, _)| (field, code));
+
+    for field in bbr_codes::CodeField::ALL {
+        println!("{}", field.to_string().bold());
+        for (code, entry) in tables.entries(field) {
+            let extension = if entry.extension { " (extension)" } else { "" };
+            println!(
+                "{code:>5}  {:<48} {}{extension}",
+                entry.description, entry.value
+            );
+        }
+        for &(_, code, count) in unmapped.iter().filter(|&&(f, ..)| f == field) {
+            println!(
+                "{}",
+                format!("{code:>5}  no mapping, met on {count:.0} buildings in the last run")
+                    .yellow()
+            );
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn run_generate(args: &Args) {
+    let started = std::time::SystemTime::now();
+    // Configure thread pool with 90% CPU cap to keep system responsive
+    floodfill_cache::configure_rayon_thread_pool(0.9);
+
+    // Clean up old cached elevation tiles on startup
+    elevation_data::cleanup_old_cached_tiles();
 
     if let Err(e) = version_check::check_for_updates() {
         eprintln!(
@@ -231,9 +994,7 @@ fn run_cli() {
         );
     }
 
-    let args: Args = Args::parse();
-
-    if let Err(e) = args::validate_args(&args) {
+    if let Err(e) = args::validate_args(args) {
         eprintln!("{}: {}", "Error".red().bold(), e);
         std::process::exit(1);
     }
@@ -246,6 +1007,45 @@ fn run_cli() {
         std::process::exit(1);
     }
 
+    if args.manifest {
+        manifest::enable_recording();
+    }
+    if args.privacy_audit {
+        privacy::enable();
+    }
+    overpass_endpoints::configure(&args.overpass_urls);
+
+    if args.bedrock && (args.time_of_day.is_some() || args.gamemode.is_some() || args.showcase) {
+        eprintln!(
+            "{} --time-of-day, --gamemode and --showcase only apply to Java worlds.",
+            "Warning:".yellow().bold()
+        );
+    }
+    if args.bedrock && args.border.is_some_and(|border| !border.is_built()) {
+        eprintln!(
+            "{} The world-border and void borders only apply to Java worlds.",
+            "Warning:".yellow().bold()
+        );
+    }
+    if args.bedrock && (args.tour || args.camera_path) {
+        eprintln!(
+            "{} The --tour and --camera-path datapacks only apply to Java worlds.",
+            "Warning:".yellow().bold()
+        );
+    }
+    if args.bedrock && args.info_book {
+        eprintln!(
+            "{} --info-book only applies to Java worlds.",
+            "Warning:".yellow().bold()
+        );
+    }
+    if args.bedrock && args.feature_index {
+        eprintln!(
+            "{} --feature-index only applies to Java worlds.",
+            "Warning:".yellow().bold()
+        );
+    }
+
     let world_format = if args.bedrock {
         WorldFormat::BedrockMcWorld
     } else {
@@ -257,7 +1057,8 @@ fn run_cli() {
             .path
             .clone()
             .unwrap_or_else(world_utils::get_bedrock_output_directory);
-        let (output_path, lvl_name) = world_utils::build_bedrock_output(&args.bbox, output_dir);
+        let (output_path, mut lvl_name) = world_utils::build_bedrock_output(&args.bbox, output_dir);
+        privacy::audit_lines("level name", std::slice::from_mut(&mut lvl_name));
         (output_path, Some(lvl_name))
     } else {
         let base_dir = args.path.clone().unwrap();
@@ -272,6 +1073,7 @@ fn run_cli() {
             "Created new world at: {}",
             world_path.display().to_string().bright_white().bold()
         );
+
         (world_path, None)
     };
 
@@ -284,8 +1086,22 @@ fn run_cli() {
                 std::process::exit(1);
             }
         };
-        let (full_transformer, _) = CoordTransformer::llbbox_to_xzbbox(&args.bbox, args.scale)
-            .expect("Failed to build full-area coordinate transformer");
+        let full_transformer = CoordTransformer::llbbox_to_xzbbox(&args.bbox, args.scale)
+            .expect("Failed to build full-area coordinate transformer")
+            .0
+            .with_snap(args.snap);
+
+        // The world border goes around the whole world, where the anchor puts it
+        let mut world_xzbbox = plan.full_xzbbox.clone();
+        if let Some(anchor) = &args.anchor {
+            world_xzbbox += anchor.vector(&full_transformer);
+        }
+        let level_settings = world_utils::LevelSettings::from_args(args, &world_xzbbox);
+        if !level_settings.is_default() {
+            if let Err(e) = world_utils::apply_level_settings(&generation_path, &level_settings) {
+                eprintln!("{} {}", "Warning:".yellow().bold(), e);
+            }
+        }
 
         if plan.requires_tiling() {
             println!(
@@ -299,6 +1115,24 @@ fn run_cli() {
             );
         }
 
+        // Building heights are estimated from medians over the whole world, so a block cut
+        // by a tile seam gets one estimate
+        let block_medians = if plan.requires_tiling() {
+            match height_estimation::world_medians(args, &full_transformer, &plan.full_xzbbox) {
+                Ok(medians) => Some(medians),
+                Err(e) => {
+                    eprintln!(
+                        "{} Building heights of the whole area unavailable: {e}. Heights are \
+                         estimated per tile.",
+                        "Warning:".yellow().bold()
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         for tile in &plan.tiles {
             if plan.requires_tiling() {
                 println!(
@@ -312,10 +1146,11 @@ fn run_cli() {
             let save_json_path =
                 tile_output_path(args.save_json_file.as_deref(), tile.index, tile.total);
             if let Err(e) = run_cli_job(
-                &args,
+                args,
                 tile.llbbox,
                 Some(tile.xzbbox.clone()),
                 Some(&full_transformer),
+                block_medians.as_ref(),
                 &generation_path,
                 world_format,
                 level_name.clone(),
@@ -327,11 +1162,26 @@ fn run_cli() {
                 std::process::exit(1);
             }
         }
+
+        // Checked once the whole world is generated, on its map
+        if args.ortho_check {
+            let path = report_dir(&generation_path).join("arnis-ortho-check.png");
+            let check = data_processing::OrthoCheck::new(
+                path,
+                args.bbox,
+                args.scale,
+                args.dhm_token.as_deref(),
+            );
+            if let Some(check) = check {
+                data_processing::check_ortho_alignment(&generation_path, &world_xzbbox, &check);
+            }
+        }
     } else if let Err(e) = run_cli_job(
-        &args,
+        args,
         args.bbox,
         None,
         None,
+        None,
         &generation_path,
         world_format,
         level_name,
@@ -349,6 +1199,40 @@ fn run_cli() {
             "Done!".green().bold(),
             generation_path.display()
         );
+        if args.ortho_check {
+            eprintln!(
+                "{} --ortho-check renders Java worlds only; no check is made.",
+                "Warning:".yellow().bold()
+            );
+        }
+    }
+
+    if args.manifest {
+        match manifest::write_manifest(&generation_path, args) {
+            Ok(path) => println!(
+                "Checksum manifest written to: {}",
+                path.display().to_string().bright_white().bold()
+            ),
+            Err(e) => eprintln!("{} {}", "Warning:".yellow().bold(), e),
+        }
+    }
+
+    if args.privacy_audit {
+        let report = privacy::take_report();
+        let removed_lines = report.removed_lines.values().sum::<usize>();
+        if removed_lines > 0 {
+            println!("Privacy audit: {removed_lines} lines of signs, books and metadata removed");
+        }
+        let path = report_dir(&generation_path).join("arnis-privacy-audit.json");
+        if let Err(e) = privacy::write_report(&path, &report) {
+            eprintln!("{} {e}", "Warning:".yellow().bold());
+        }
+    }
+
+    if !args.no_history {
+        if let Err(e) = history::record_run(&generation_path, args, started) {
+            eprintln!("{} {}", "Warning:".yellow().bold(), e);
+        }
     }
 }
 
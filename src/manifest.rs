@@ -0,0 +1,334 @@
+//! Checksum manifest for reproducible, verifiable output.
+//!
+//! With `--manifest`, every downloaded input (Overpass response, DHM coverage, sea-floor
+//! depth) is hashed as it arrives, and after generation `arnis-manifest.json` is written
+//! into the world next to the region files. It records the Arnis version, the generation
+//! config, the input checksums and a SHA-256 per output file, so `arnis verify` can later
+//! confirm that a published world was produced from the listed official data unchanged.
+
+use crate::args::Args;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File name of the manifest inside the world directory (or next to a .mcworld file)
+pub const MANIFEST_FILE: &str = "arnis-manifest.json";
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static INPUTS: Mutex<Vec<InputRecord>> = Mutex::new(Vec::new());
+static SYNTHETIC: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// One downloaded or loaded input dataset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputRecord {
+    /// Dataset kind, e.g. `overpass`, `dhm`, `bathymetry`, `osm-file`
+    pub kind: String,
+    /// Where the data came from, without credentials
+    pub source: String,
+    pub bytes: usize,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub arnis_version: String,
+    /// When the world was generated, in seconds since the Unix epoch; kept through copies,
+    /// unlike the file times
+    #[serde(default)]
+    pub generated: Option<u64>,
+    pub config: serde_json::Value,
+    pub inputs: Vec<InputRecord>,
+    /// SHA-256 per output file, keyed by path relative to the world directory
+    pub outputs: BTreeMap<String, String>,
+    /// Content generated without source data, such as `--urban-infill` massing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub synthetic: Vec<String>,
+}
+
+/// Result of re-checking a world against its manifest.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub matched: usize,
+    pub mismatched: Vec<String>,
+    pub missing: Vec<String>,
+    pub unlisted: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.unlisted.is_empty()
+    }
+}
+
+/// Starts recording input checksums for the manifest.
+pub fn enable_recording() {
+    RECORDING.store(true, Ordering::Relaxed);
+}
+
+/// Records the checksum of an input dataset. Does nothing unless `--manifest` is enabled.
+pub fn record_input(kind: &str, source: &str, data: &[u8]) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+    let record = InputRecord {
+        kind: kind.to_string(),
+        source: source.to_string(),
+        bytes: data.len(),
+        sha256: sha256_hex(data),
+    };
+    if let Ok(mut inputs) = INPUTS.lock() {
+        inputs.push(record);
+    }
+}
+
+/// Records content that was generated without source data, so the manifest flags it as
+/// synthetic. Does nothing unless `--manifest` is enabled.
+pub fn record_synthetic(description: &str) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut synthetic) = SYNTHETIC.lock() {
+        synthetic.push(description.to_string());
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The value of an option as it is given on the command line, e.g. `storm-surge`
+fn cli_value<T: clap::ValueEnum>(value: &T) -> Option<String> {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+}
+
+/// Generation settings that influence the output. Tokens are deliberately left out.
+pub fn config_json(args: &Args) -> serde_json::Value {
+    serde_json::json!({
+        "bbox": [
+            args.bbox.min().lat(),
+            args.bbox.min().lng(),
+            args.bbox.max().lat(),
+            args.bbox.max().lng(),
+        ],
+        "area": args.area,
+        "exclusion_zones": args.exclusion_zones.as_ref().map(|p| p.display().to_string()),
+        "scale": args.scale,
+        "snap": cli_value(&args.snap),
+        "ground_level": args.ground_level,
+        "elevation_mode": cli_value(&args.elevation_mode),
+        "meters_per_block": args.meters_per_block,
+        "sea_level_y": args.sea_level_y,
+        "terrain_exaggeration": args.terrain_exaggeration,
+        "bedrock": args.bedrock,
+        "terrain": args.terrain,
+        "dhm": args.dhm_token.is_some(),
+        "dem": args.dem.as_ref().map(|p| p.display().to_string()),
+        "dhm_resolution": args.dhm_resolution,
+        "seam_from": args.seam_from.iter().map(|p| p.display().to_string()).collect::<Vec<_>>(),
+        "bathymetry": args.bathymetry,
+        "tide": args.tide.as_ref().and_then(cli_value),
+        "flood_scenario": cli_value(&args.flood_scenario),
+        "navigable_waterways": args.navigable_waterways,
+        "interior": args.interior,
+        "interiors": args.interiors,
+        "bbr_codes": args.bbr_codes.as_ref().map(|p| p.display().to_string()),
+        "roof": args.roof,
+        "edge_margin": args.edge_margin,
+        "border": args.border.as_ref().and_then(cli_value),
+        "fillground": args.fillground,
+        "city_boundaries": args.city_boundaries,
+        "urban_infill": args.urban_infill,
+        "addresses": args.addresses,
+        "place_names": args.place_names,
+        "ortho": args.ortho,
+        "geodanmark_coast": args.geodanmark_coast,
+        "geodanmark_roads": args.geodanmark_roads,
+        "protected_nature": args.protected_nature,
+        "field_crops": args.field_crops,
+        "field_crops_year": args.field_crops_year,
+        "forest_types": args.forest_types,
+        "land_cover": args.land_cover,
+        "wind_turbines": args.wind_turbines,
+        "ancient_monuments": args.ancient_monuments,
+        "landmarks": args.landmarks.as_ref().map(|p| p.display().to_string()),
+        "gtfs": args.gtfs.as_ref().map(|p| p.display().to_string()),
+        "privacy_audit": args.privacy_audit,
+        "anchor": args.anchor.map(|a| [a.point.lat(), a.point.lng(), a.x as f64, a.z as f64]),
+        "overlay": args.overlay.as_ref().map(|o| o.source.clone()),
+        "land_polygons": args.land_polygons.as_ref().map(|p| p.display().to_string()),
+        "file": args.file,
+        "osm_file": args.osm_file.as_ref().map(|p| p.display().to_string()),
+    })
+}
+
+/// Directory the manifest lives in, and the output files it covers.
+/// Java worlds cover `region/*.mca`; a Bedrock `.mcworld` covers the archive itself.
+fn output_layout(output: &Path) -> (PathBuf, Vec<PathBuf>) {
+    if output.is_file() {
+        let dir = output.parent().map(Path::to_path_buf).unwrap_or_default();
+        return (dir, vec![output.to_path_buf()]);
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(output.join("region"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "mca"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    (output.to_path_buf(), files)
+}
+
+fn relative_key(dir: &Path, path: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn hash_outputs(dir: &Path, files: &[PathBuf]) -> Result<BTreeMap<String, String>, String> {
+    files
+        .iter()
+        .map(|path| Ok((relative_key(dir, path), sha256_file(path)?)))
+        .collect()
+}
+
+/// Writes the manifest for a finished world and returns its path.
+pub fn write_manifest(output: &Path, args: &Args) -> Result<PathBuf, String> {
+    let mut inputs = INPUTS.lock().map(|i| i.clone()).unwrap_or_default();
+    // Downloads run side by side and arrive in no fixed order
+    inputs.sort_by(|a, b| (&a.kind, &a.source).cmp(&(&b.kind, &b.source)));
+    if let Some(file) = &args.file {
+        let data = std::fs::read(file).map_err(|e| format!("Failed to read {file}: {e}"))?;
+        inputs.push(InputRecord {
+            kind: "osm-file".to_string(),
+            source: file.clone(),
+            bytes: data.len(),
+            sha256: sha256_hex(&data),
+        });
+    }
+    if let Some(path) = &args.osm_file {
+        let bytes = std::fs::metadata(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?
+            .len() as usize;
+        inputs.push(InputRecord {
+            kind: "osm-file".to_string(),
+            source: path.display().to_string(),
+            bytes,
+            sha256: sha256_file(path)?,
+        });
+    }
+
+    let (dir, files) = output_layout(output);
+    let manifest = Manifest {
+        arnis_version: env!("CARGO_PKG_VERSION").to_string(),
+        generated: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|since| since.as_secs()),
+        config: config_json(args),
+        inputs,
+        outputs: hash_outputs(&dir, &files)?,
+        synthetic: SYNTHETIC.lock().map(|s| s.clone()).unwrap_or_default(),
+    };
+
+    let path = dir.join(MANIFEST_FILE);
+    let mut value = serde_json::to_value(&manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {e}"))?;
+    crate::privacy::audit_json("manifest", &mut value);
+    let json = serde_json::to_string_pretty(&value)
+        .map_err(|e| format!("Failed to serialize manifest: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+/// Re-hashes the outputs of a world and compares them with its manifest.
+pub fn verify_world(output: &Path) -> Result<VerifyReport, String> {
+    let (dir, files) = output_layout(output);
+    let path = dir.join(MANIFEST_FILE);
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let manifest: Manifest =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid manifest: {e}"))?;
+
+    let actual = hash_outputs(&dir, &files)?;
+    let mut report = VerifyReport::default();
+    for (key, expected) in &manifest.outputs {
+        match actual.get(key) {
+            Some(hash) if hash == expected => report.matched += 1,
+            Some(_) => report.mismatched.push(key.clone()),
+            None => report.missing.push(key.clone()),
+        }
+    }
+    report.unlisted = actual
+        .keys()
+        .filter(|key| !manifest.outputs.contains_key(*key))
+        .cloned()
+        .collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn verify_detects_modified_and_unlisted_regions() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let world = tmpdir.path();
+        std::fs::create_dir(world.join("region")).unwrap();
+        std::fs::write(world.join("region/r.0.0.mca"), b"region a").unwrap();
+        std::fs::write(world.join("region/r.0.1.mca"), b"region b").unwrap();
+
+        let args = Args::parse_from(["arnis", "--bbox", "1,2,3,4"]);
+        write_manifest(world, &args).unwrap();
+        assert!(verify_world(world).unwrap().is_ok());
+
+        std::fs::write(world.join("region/r.0.1.mca"), b"tampered").unwrap();
+        std::fs::write(world.join("region/r.1.1.mca"), b"extra").unwrap();
+        let report = verify_world(world).unwrap();
+        assert_eq!(report.matched, 1);
+        assert_eq!(report.mismatched, vec!["region/r.0.1.mca"]);
+        assert_eq!(report.unlisted, vec!["region/r.1.1.mca"]);
+    }
+
+    #[test]
+    fn options_are_recorded_as_given_on_the_command_line() {
+        let args = Args::parse_from([
+            "arnis",
+            "--bbox",
+            "1,2,3,4",
+            "--flood-scenario",
+            "storm-surge",
+        ]);
+        assert_eq!(config_json(&args)["flood_scenario"], "storm-surge");
+    }
+
+    #[test]
+    fn sha256_matches_known_digest() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -25,6 +25,29 @@ pub fn render_world_map(
     min_z: i32,
     max_z: i32,
 ) -> Result<std::path::PathBuf, String> {
+    let img = render_world_image(world_dir, min_x, max_x, min_z, max_z)?;
+    save_world_map(world_dir, &img)
+}
+
+/// Saves a rendered map where the GUI preview looks for it.
+/// Returns the path to the saved image file.
+pub fn save_world_map(world_dir: &Path, img: &RgbImage) -> Result<std::path::PathBuf, String> {
+    let output_path = world_dir.join("arnis_world_map.png");
+    img.save(&output_path)
+        .map_err(|e| format!("Failed to save map image: {}", e))?;
+
+    Ok(output_path)
+}
+
+/// Renders a top-down view of the generated Minecraft world, one pixel per block.
+/// Columns without any generated chunk stay white.
+pub fn render_world_image(
+    world_dir: &Path,
+    min_x: i32,
+    max_x: i32,
+    min_z: i32,
+    max_z: i32,
+) -> Result<RgbImage, String> {
     let width = (max_x - min_x + 1) as u32;
     let height = (max_z - min_z + 1) as u32;
 
@@ -82,14 +105,7 @@ pub fn render_world_map(
         }
     });
 
-    // Save the image
-    let output_path = world_dir.join("arnis_world_map.png");
-    img.into_inner()
-        .unwrap()
-        .save(&output_path)
-        .map_err(|e| format!("Failed to save map image: {}", e))?;
-
-    Ok(output_path)
+    Ok(img.into_inner().unwrap())
 }
 
 /// Renders all chunks within a region and returns pixel data
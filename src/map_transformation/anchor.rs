@@ -0,0 +1,102 @@
+//! Fixed coordinate frame across runs (`--anchor`).
+//!
+//! By default the world origin is the north-west corner of the bbox, so two exports of
+//! overlapping or adjacent areas put the same place at different block coordinates. An
+//! anchor names a lat/lon and the block X/Z it must land on; the map is translated so it
+//! does, and every run using the same anchor and scale shares one coordinate frame (to
+//! within a block or two, as each bbox is projected on its own).
+
+use super::translate::translate_by_vector;
+use crate::coordinate_system::cartesian::{XZBBox, XZVector};
+use crate::coordinate_system::geographic::LLPoint;
+use crate::coordinate_system::transformation::CoordTransformer;
+use crate::osm_parser::ProcessedElement;
+use std::str::FromStr;
+
+/// A lat/lon pinned to a block coordinate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Anchor {
+    pub point: LLPoint,
+    pub x: i32,
+    pub z: i32,
+}
+
+impl FromStr for Anchor {
+    type Err = String;
+
+    /// Parses `lat,lng,x,z`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split([',', ' ']).filter(|p| !p.is_empty()).collect();
+        let [lat, lng, x, z] = parts[..] else {
+            return Err(format!("Anchor '{s}' must be lat,lng,x,z"));
+        };
+        let coordinate = |v: &str| {
+            v.parse::<f64>()
+                .map_err(|_| format!("Invalid anchor coordinate '{v}'"))
+        };
+        let block = |v: &str| {
+            v.parse::<i32>()
+                .map_err(|_| format!("Invalid anchor block coordinate '{v}'"))
+        };
+        Ok(Self {
+            point: LLPoint::new(coordinate(lat)?, coordinate(lng)?)?,
+            x: block(x)?,
+            z: block(z)?,
+        })
+    }
+}
+
+impl Anchor {
+    /// Translation that moves the anchor from where the bbox projection puts it onto its block
+    pub fn vector(&self, transformer: &CoordTransformer) -> XZVector {
+        let (x, z) = transformer.project(self.point);
+        XZVector {
+            dx: self.x - x.floor() as i32,
+            dz: self.z - z.floor() as i32,
+        }
+    }
+}
+
+/// Translates the elements and bbox so the anchor lands on its block coordinate.
+pub fn align_to_anchor(
+    anchor: &Anchor,
+    transformer: &CoordTransformer,
+    elements: &mut Vec<ProcessedElement>,
+    xzbbox: &mut XZBBox,
+) -> XZVector {
+    let vector = anchor.vector(transformer);
+    translate_by_vector(vector, elements, xzbbox);
+    vector
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate_system::geographic::LLBBox;
+
+    #[test]
+    fn parses_anchors() {
+        let anchor: Anchor = "55.6761,12.5683,100,-200".parse().unwrap();
+        assert_eq!((anchor.x, anchor.z), (100, -200));
+        assert_eq!(anchor.point.lat(), 55.6761);
+        assert!("55.6761,12.5683".parse::<Anchor>().is_err());
+        assert!("95,12,0,0".parse::<Anchor>().is_err());
+    }
+
+    #[test]
+    fn adjacent_boxes_share_the_anchor_frame() {
+        let west = LLBBox::new(55.0, 12.0, 55.01, 12.01).unwrap();
+        let east = LLBBox::new(55.0, 12.01, 55.01, 12.02).unwrap();
+        let anchor: Anchor = "55.0,12.0,0,0".parse().unwrap();
+
+        let (west_t, west_box) = CoordTransformer::llbbox_to_xzbbox(&west, 1.0).unwrap();
+        let (east_t, _) = CoordTransformer::llbbox_to_xzbbox(&east, 1.0).unwrap();
+        let west_shift = anchor.vector(&west_t);
+        let east_shift = anchor.vector(&east_t);
+
+        // The east box starts where the west box ends
+        let west_end = west_box.max_x() + west_shift.dx;
+        assert!((east_shift.dx - west_end).abs() <= 2, "{east_shift:?}");
+        assert_eq!(west_shift.dz, east_shift.dz);
+    }
+}
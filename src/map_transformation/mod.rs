@@ -1,3 +1,4 @@
+pub mod anchor;
 mod operator;
 mod transform_map;
 
@@ -6,7 +6,7 @@ mod startend_translator;
 mod vector_translator;
 
 // interface for generation from json
-pub use translator::translator_from_json;
+pub use translator::{translate_by_vector, translator_from_json};
 
 // interface for direct generation in memory, currently only used by test
 #[cfg(test)]
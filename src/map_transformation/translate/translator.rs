@@ -47,14 +47,10 @@ pub fn translate_by_vector(
 
     for element in elements {
         match element {
-            ProcessedElement::Node(n) => {
-                n.x += vector.dx;
-                n.z += vector.dz;
-            }
+            ProcessedElement::Node(n) => n.translate(vector.dx, vector.dz),
             ProcessedElement::Way(w) => {
                 for n in &mut w.nodes {
-                    n.x += vector.dx;
-                    n.z += vector.dz;
+                    n.translate(vector.dx, vector.dz);
                 }
             }
             _ => {}
@@ -0,0 +1,163 @@
+//! Alignment check of the generated world against the orthophoto (`--ortho-check`).
+//!
+//! A projection or scale mistake shifts or stretches the whole world, and it is easy to miss
+//! until hours have gone into a large area. After a (small) test run the world is rendered
+//! top down and blended at 50% over the GeoDanmark orthophoto of the bbox, so roads and
+//! coastlines that do not line up show as double edges. The offset between the two is
+//! estimated by matching their green cover: vegetation is green in both the render and the
+//! photo, while roofs, roads and water are not.
+
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+
+/// Longest side of the images compared to estimate the offset
+const COMPARE_PIXELS: u32 = 256;
+/// Largest offset searched, in compared pixels
+const MAX_SHIFT: i32 = 16;
+/// Fewest pixels two shifted images must share for their match to count
+const MIN_OVERLAP: usize = 64;
+
+/// Blends the render half and half over the photo, which is scaled to the render's size.
+/// Parts of the render without any generated blocks show the photo alone.
+pub fn blend(render: &RgbImage, photo: &RgbImage) -> RgbImage {
+    let photo = imageops::resize(photo, render.width(), render.height(), FilterType::Triangle);
+    RgbImage::from_fn(render.width(), render.height(), |x, z| {
+        let (Rgb(r), Rgb(p)) = (*render.get_pixel(x, z), *photo.get_pixel(x, z));
+        if is_blank(Rgb(r)) {
+            Rgb(p)
+        } else {
+            Rgb([0, 1, 2].map(|i| ((r[i] as u16 + p[i] as u16) / 2) as u8))
+        }
+    })
+}
+
+/// Pixels the renderer leaves white and the WMS leaves black or white outside its coverage
+fn is_blank(Rgb([r, g, b]): Rgb<u8>) -> bool {
+    (r > 250 && g > 250 && b > 250) || (r < 5 && g < 5 && b < 5)
+}
+
+/// How green a pixel is, or `None` for blank pixels
+fn greenness(pixel: Rgb<u8>) -> Option<f64> {
+    if is_blank(pixel) {
+        return None;
+    }
+    let Rgb([r, g, b]) = pixel;
+    Some((2.0 * g as f64 - r as f64 - b as f64) / 510.0)
+}
+
+/// Greenness of the image scaled down to `size`
+fn greenness_grid(image: &RgbImage, size: (u32, u32)) -> Vec<Option<f64>> {
+    imageops::resize(image, size.0, size.1, FilterType::Triangle)
+        .pixels()
+        .map(|&pixel| greenness(pixel))
+        .collect()
+}
+
+/// Correlation of the render with the photo shifted by (dx, dz), over the pixels both have
+fn correlation(
+    render: &[Option<f64>],
+    photo: &[Option<f64>],
+    size: (u32, u32),
+    dx: i32,
+    dz: i32,
+) -> Option<f64> {
+    let (width, height) = (size.0 as i32, size.1 as i32);
+    let mut pairs = Vec::new();
+    for z in 0.max(-dz)..height.min(height - dz) {
+        for x in 0.max(-dx)..width.min(width - dx) {
+            let render_value = render[(z * width + x) as usize];
+            let photo_value = photo[((z + dz) * width + x + dx) as usize];
+            if let (Some(a), Some(b)) = (render_value, photo_value) {
+                pairs.push((a, b));
+            }
+        }
+    }
+    if pairs.len() < MIN_OVERLAP {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_a = pairs.iter().map(|(a, _)| a).sum::<f64>() / n;
+    let mean_b = pairs.iter().map(|(_, b)| b).sum::<f64>() / n;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (a, b) in pairs {
+        cov += (a - mean_a) * (b - mean_b);
+        var_a += (a - mean_a).powi(2);
+        var_b += (b - mean_b).powi(2);
+    }
+    (var_a > 0.0 && var_b > 0.0).then(|| cov / (var_a * var_b).sqrt())
+}
+
+/// Estimates how far the render sits from the photo, in render pixels east (x) and south
+/// (z): positive when the render's features lie east or south of the same features in the
+/// photo. `None` when the images have too little in common to tell.
+pub fn estimate_offset(render: &RgbImage, photo: &RgbImage) -> Option<(f64, f64)> {
+    let longest = render.width().max(render.height()).max(1);
+    let factor = (COMPARE_PIXELS as f64 / longest as f64).min(1.0);
+    let size = (
+        ((render.width() as f64 * factor).round() as u32).max(1),
+        ((render.height() as f64 * factor).round() as u32).max(1),
+    );
+    let render_grid = greenness_grid(render, size);
+    let photo_grid = greenness_grid(photo, size);
+
+    let mut best: Option<(f64, i32, i32)> = None;
+    for dz in -MAX_SHIFT..=MAX_SHIFT {
+        for dx in -MAX_SHIFT..=MAX_SHIFT {
+            let Some(score) = correlation(&render_grid, &photo_grid, size, dx, dz) else {
+                continue;
+            };
+            if best.is_none_or(|(best_score, _, _)| score > best_score) {
+                best = Some((score, dx, dz));
+            }
+        }
+    }
+    // The render at (x, z) matches the photo at (x + dx, z + dz), so it sits (-dx, -dz) off
+    let (_, dx, dz) = best?;
+    Some((-dx as f64 / factor, -dz as f64 / factor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Grass with a grey road grid and a blue lake
+    fn scene(width: u32, height: u32, shift: (i32, i32)) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, z| {
+            let (x, z) = (x as i32 - shift.0, z as i32 - shift.1);
+            if x.rem_euclid(37) < 4 || z.rem_euclid(29) < 3 {
+                Rgb([120, 120, 120])
+            } else if (x - 60).pow(2) + (z - 40).pow(2) < 200 {
+                Rgb([40, 70, 160])
+            } else {
+                Rgb([70, 130, 50])
+            }
+        })
+    }
+
+    #[test]
+    fn blends_half_and_half_and_keeps_the_photo_where_nothing_was_rendered() {
+        let mut render = RgbImage::from_pixel(4, 4, Rgb([100, 100, 100]));
+        render.put_pixel(0, 0, Rgb([255, 255, 255]));
+        let photo = RgbImage::from_pixel(2, 2, Rgb([200, 0, 50]));
+        let blended = blend(&render, &photo);
+        assert_eq!(*blended.get_pixel(0, 0), Rgb([200, 0, 50]));
+        assert_eq!(*blended.get_pixel(3, 3), Rgb([150, 50, 75]));
+    }
+
+    #[test]
+    fn finds_the_offset_of_a_shifted_render() {
+        let photo = scene(200, 160, (0, 0));
+        let (dx, dz) = estimate_offset(&scene(200, 160, (6, -4)), &photo).unwrap();
+        assert_eq!((dx, dz), (6.0, -4.0));
+
+        let (dx, dz) = estimate_offset(&photo, &photo).unwrap();
+        assert_eq!((dx, dz), (0.0, 0.0));
+    }
+
+    #[test]
+    fn blank_renders_give_no_offset() {
+        let photo = scene(100, 100, (0, 0));
+        let render = RgbImage::from_pixel(100, 100, Rgb([255, 255, 255]));
+        assert_eq!(estimate_offset(&render, &photo), None);
+    }
+}
@@ -0,0 +1,561 @@
+//! Local OSM extracts (`--osm-file`), e.g. Geofabrik's Denmark extract.
+//!
+//! `.osm` (XML) and `.osm.pbf` files are read instead of querying Overpass, so a run can work
+//! offline and large or repeated runs do not load the public servers. Only what the bbox
+//! needs is kept, as Overpass would return it: the nodes inside the bbox, the ways with a
+//! node inside it together with all their nodes, so they are clipped at the edge like
+//! downloaded ways, and the relations with a kept member together with all their member
+//! ways. Extracts list nodes before ways before relations, so the file is streamed up to
+//! three times: for what lies in the bbox, for the remaining member ways of its relations,
+//! and for the nodes outside the bbox the kept ways run through.
+//!
+//! A tiled run reads the file once, for its whole area, and keeps that part in memory; each
+//! tile picks its own part from it the same way.
+
+use crate::coordinate_system::geographic::{LLBBox, LLPoint};
+use crate::osm_parser::{OsmData, OsmElement, OsmMember};
+use crate::progress::emit_gui_progress_update;
+use colored::Colorize;
+use osmpbf::{ElementReader, RelMemberType};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The part of an extract a run's whole area needs, kept for the run's other tiles
+struct AreaExtract {
+    path: PathBuf,
+    area: LLBBox,
+    elements: Vec<Element>,
+}
+
+static AREA_EXTRACT: Mutex<Option<AreaExtract>> = Mutex::new(None);
+
+/// An element as read from the extract
+#[derive(Clone)]
+enum Element {
+    Node {
+        id: u64,
+        lat: f64,
+        lon: f64,
+        tags: HashMap<String, String>,
+    },
+    Way {
+        id: u64,
+        refs: Vec<u64>,
+        tags: HashMap<String, String>,
+    },
+    Relation {
+        id: u64,
+        /// Member type, ID and role
+        members: Vec<(String, u64, String)>,
+        tags: HashMap<String, String>,
+    },
+}
+
+impl Element {
+    fn kind(&self) -> &'static str {
+        match self {
+            Element::Node { .. } => "node",
+            Element::Way { .. } => "way",
+            Element::Relation { .. } => "relation",
+        }
+    }
+
+    fn tags_mut(&mut self) -> &mut HashMap<String, String> {
+        match self {
+            Element::Node { tags, .. }
+            | Element::Way { tags, .. }
+            | Element::Relation { tags, .. } => tags,
+        }
+    }
+
+    fn into_osm(self) -> OsmElement {
+        let tags = |tags: HashMap<String, String>| (!tags.is_empty()).then_some(tags);
+        match self {
+            Element::Node {
+                id,
+                lat,
+                lon,
+                tags: t,
+            } => OsmElement {
+                r#type: "node".to_string(),
+                id,
+                lat: Some(lat),
+                lon: Some(lon),
+                nodes: None,
+                tags: tags(t),
+                members: Vec::new(),
+            },
+            Element::Way { id, refs, tags: t } => OsmElement {
+                r#type: "way".to_string(),
+                id,
+                lat: None,
+                lon: None,
+                nodes: Some(refs),
+                tags: tags(t),
+                members: Vec::new(),
+            },
+            Element::Relation {
+                id,
+                members,
+                tags: t,
+            } => OsmElement {
+                r#type: "relation".to_string(),
+                id,
+                lat: None,
+                lon: None,
+                nodes: None,
+                tags: tags(t),
+                members: members
+                    .into_iter()
+                    .map(|(r#type, r#ref, role)| OsmMember {
+                        r#type,
+                        r#ref,
+                        role,
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Reads the part of a local extract the bbox needs. `area` is the run's whole area, of which
+/// `bbox` may be one tile; the file is only read for the first tile of an area.
+pub fn read_osm_file(path: &Path, area: &LLBBox, bbox: &LLBBox) -> Result<OsmData, String> {
+    let mut cache = AREA_EXTRACT.lock().unwrap();
+    let cached = cache
+        .as_ref()
+        .is_some_and(|extract| extract.path == path && extract.area == *area);
+    if !cached {
+        println!("{} Loading data from {}...", "[1/7]".bold(), path.display());
+        emit_gui_progress_update(1.0, "Loading data from file...");
+        let elements = select(area, &mut |f: &mut dyn FnMut(&Element)| {
+            for_each_element(path, f)
+        })?;
+        *cache = Some(AreaExtract {
+            path: path.to_path_buf(),
+            area: *area,
+            elements,
+        });
+    }
+    let extract = cache.as_ref().expect("the area's extract was just read");
+    let elements = if bbox == area {
+        extract.elements.clone()
+    } else {
+        select(bbox, &mut |f: &mut dyn FnMut(&Element)| {
+            extract.elements.iter().for_each(f);
+            Ok(())
+        })?
+    };
+
+    let count = |kind: &str| elements.iter().filter(|e| e.kind() == kind).count();
+    println!(
+        "{} nodes, {} ways and {} relations read from {}",
+        count("node"),
+        count("way"),
+        count("relation"),
+        path.display()
+    );
+    Ok(OsmData::from_elements(
+        elements.into_iter().map(Element::into_osm).collect(),
+    ))
+}
+
+/// Elements in file order, handed to a callback by a pass over the extract
+type Pass<'a> = dyn FnMut(&mut dyn FnMut(&Element)) -> Result<(), String> + 'a;
+
+/// Picks what the bbox needs from the elements `pass` goes through, nodes before ways before
+/// relations, in up to three passes.
+fn select(bbox: &LLBBox, pass: &mut Pass<'_>) -> Result<Vec<Element>, String> {
+    let mut inside: HashSet<u64> = HashSet::new();
+    let mut way_ids: HashSet<u64> = HashSet::new();
+    let mut nodes: Vec<Element> = Vec::new();
+    let mut ways: Vec<Element> = Vec::new();
+    let mut relations: Vec<Element> = Vec::new();
+    let mut member_ways: HashSet<u64> = HashSet::new();
+    pass(&mut |element: &Element| match element {
+        Element::Node { id, lat, lon, .. } => {
+            if LLPoint::new(*lat, *lon).is_ok_and(|point| bbox.contains(&point)) {
+                inside.insert(*id);
+                nodes.push(element.clone());
+            }
+        }
+        Element::Way { id, refs, .. } => {
+            if refs.iter().any(|node| inside.contains(node)) {
+                way_ids.insert(*id);
+                ways.push(element.clone());
+            }
+        }
+        Element::Relation { members, .. } => {
+            let kept = members.iter().any(|(kind, id, _)| match kind.as_str() {
+                "way" => way_ids.contains(id),
+                "node" => inside.contains(id),
+                _ => false,
+            });
+            if kept {
+                member_ways.extend(
+                    members
+                        .iter()
+                        .filter(|(kind, id, _)| kind == "way" && !way_ids.contains(id))
+                        .map(|(_, id, _)| *id),
+                );
+                relations.push(element.clone());
+            }
+        }
+    })?;
+
+    if !member_ways.is_empty() {
+        pass(&mut |element: &Element| {
+            if let Element::Way { id, .. } = element {
+                if member_ways.remove(id) {
+                    ways.push(element.clone());
+                }
+            }
+        })?;
+    }
+
+    let mut outside: HashSet<u64> = ways
+        .iter()
+        .flat_map(|way| match way {
+            Element::Way { refs, .. } => refs.as_slice(),
+            _ => &[],
+        })
+        .filter(|node| !inside.contains(node))
+        .copied()
+        .collect();
+    if !outside.is_empty() {
+        pass(&mut |element: &Element| {
+            if let Element::Node { id, .. } = element {
+                if outside.remove(id) {
+                    nodes.push(element.clone());
+                }
+            }
+        })?;
+    }
+
+    nodes.extend(ways);
+    nodes.extend(relations);
+    Ok(nodes)
+}
+
+/// Streams every element of the extract to `f`, in file order.
+fn for_each_element(path: &Path, f: &mut dyn FnMut(&Element)) -> Result<(), String> {
+    let pbf = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pbf"));
+    let result = if pbf {
+        read_pbf(path, f)
+    } else {
+        read_xml(path, f)
+    };
+    result.map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn owned_tags<'a>(tags: impl Iterator<Item = (&'a str, &'a str)>) -> HashMap<String, String> {
+    tags.map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+/// PBF through osmpbf, which decodes one blob at a time and refuses oversized blobs
+fn read_pbf(path: &Path, f: &mut dyn FnMut(&Element)) -> Result<(), String> {
+    let reader = ElementReader::from_path(path).map_err(|e| format!("failed to open: {e}"))?;
+    reader
+        .for_each(|element| {
+            let element = match element {
+                osmpbf::Element::Node(node) => Element::Node {
+                    id: node.id() as u64,
+                    lat: node.lat(),
+                    lon: node.lon(),
+                    tags: owned_tags(node.tags()),
+                },
+                osmpbf::Element::DenseNode(node) => Element::Node {
+                    id: node.id() as u64,
+                    lat: node.lat(),
+                    lon: node.lon(),
+                    tags: owned_tags(node.tags()),
+                },
+                osmpbf::Element::Way(way) => Element::Way {
+                    id: way.id() as u64,
+                    refs: way.refs().map(|node| node as u64).collect(),
+                    tags: owned_tags(way.tags()),
+                },
+                osmpbf::Element::Relation(relation) => Element::Relation {
+                    id: relation.id() as u64,
+                    members: relation
+                        .members()
+                        .map(|member| {
+                            let kind = match member.member_type {
+                                RelMemberType::Node => "node",
+                                RelMemberType::Way => "way",
+                                RelMemberType::Relation => "relation",
+                            };
+                            // A role that is not valid UTF-8 is left empty
+                            let role = member.role().unwrap_or_default().to_string();
+                            (kind.to_string(), member.member_id as u64, role)
+                        })
+                        .collect(),
+                    tags: owned_tags(relation.tags()),
+                },
+            };
+            f(&element);
+        })
+        .map_err(|e| format!("failed to read: {e}"))
+}
+
+// XML: the .osm format written by JOSM, osmium and the OSM API
+
+/// Attributes of a start tag, unescaped
+fn attributes(tag: &BytesStart) -> Result<HashMap<String, String>, String> {
+    tag.attributes()
+        .map(|attribute| {
+            let attribute = attribute.map_err(|e| e.to_string())?;
+            let name = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+            let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+            Ok((name, value.into_owned()))
+        })
+        .collect()
+}
+
+fn read_xml(path: &Path, f: &mut dyn FnMut(&Element)) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("failed to open: {e}"))?;
+    let mut reader = Reader::from_reader(BufReader::new(file));
+    let id = |attributes: &HashMap<String, String>, name: &str| -> Result<u64, String> {
+        attributes
+            .get(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("element without a valid {name}"))
+    };
+    let coordinate = |attributes: &HashMap<String, String>, name: &str| -> Result<f64, String> {
+        attributes
+            .get(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("node without a valid {name}"))
+    };
+
+    let mut current: Option<Element> = None;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| format!("at byte {}: {e}", reader.error_position()))?;
+        let (tag, self_closing) = match &event {
+            Event::Start(tag) => (tag, false),
+            Event::Empty(tag) => (tag, true),
+            Event::End(tag) => {
+                if matches!(tag.name().as_ref(), b"node" | b"way" | b"relation") {
+                    if let Some(element) = current.take() {
+                        f(&element);
+                    }
+                }
+                continue;
+            }
+            Event::Eof => return Ok(()),
+            _ => continue,
+        };
+        let name = tag.name();
+        let attrs = attributes(tag)?;
+        match name.as_ref() {
+            b"node" => {
+                current = Some(Element::Node {
+                    id: id(&attrs, "id")?,
+                    lat: coordinate(&attrs, "lat")?,
+                    lon: coordinate(&attrs, "lon")?,
+                    tags: HashMap::new(),
+                })
+            }
+            b"way" => {
+                current = Some(Element::Way {
+                    id: id(&attrs, "id")?,
+                    refs: Vec::new(),
+                    tags: HashMap::new(),
+                })
+            }
+            b"relation" => {
+                current = Some(Element::Relation {
+                    id: id(&attrs, "id")?,
+                    members: Vec::new(),
+                    tags: HashMap::new(),
+                })
+            }
+            b"tag" => {
+                if let (Some(element), Some(k), Some(v)) =
+                    (current.as_mut(), attrs.get("k"), attrs.get("v"))
+                {
+                    element.tags_mut().insert(k.clone(), v.clone());
+                }
+            }
+            b"nd" => {
+                if let Some(Element::Way { refs, .. }) = current.as_mut() {
+                    refs.push(id(&attrs, "ref")?);
+                }
+            }
+            b"member" => {
+                if let Some(Element::Relation { members, .. }) = current.as_mut() {
+                    members.push((
+                        attrs.get("type").cloned().unwrap_or_default(),
+                        id(&attrs, "ref")?,
+                        attrs.get("role").cloned().unwrap_or_default(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+        if self_closing && matches!(name.as_ref(), b"node" | b"way" | b"relation") {
+            if let Some(element) = current.take() {
+                f(&element);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    fn temp_file(dir: &tempfile::TempDir, name: &str, bytes: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn ids(data: &OsmData, kind: &str) -> Vec<u64> {
+        let mut ids: Vec<u64> = data
+            .elements()
+            .iter()
+            .filter(|e| e.r#type == kind)
+            .map(|e| e.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn keeps_what_the_bbox_needs_from_xml() {
+        // Node 1 lies inside the bbox, 2 and 3 outside; way 10 crosses the edge and is kept
+        // with node 2, way 11 lies outside but is an outer of the kept relation 20
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <!-- extract -->
+  <node id="1" lat="55.005" lon="10.005"><tag k="name" v="Kro &amp; Bageri"/></node>
+  <node id="2" lat="55.02" lon="10.005"/>
+  <node id="3" lat="55.03" lon="10.03"/>
+  <node id="4" lat="55.04" lon="10.03"/>
+  <way id="10"><nd ref="1"/><nd ref="2"/><tag k="highway" v="residential"/></way>
+  <way id="11"><nd ref="3"/><nd ref="4"/></way>
+  <way id="12"><nd ref="4"/><nd ref="3"/></way>
+  <relation id="20">
+    <member type="way" ref="10" role="outer"/>
+    <member type="way" ref="11" role="outer"/>
+    <tag k="type" v="multipolygon"/>
+  </relation>
+</osm>"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = temp_file(&dir, "extract.osm", xml.as_bytes());
+        let bbox = LLBBox::new(55.0, 10.0, 55.01, 10.01).unwrap();
+        let data = read_osm_file(&path, &bbox, &bbox).unwrap();
+
+        assert_eq!(ids(&data, "node"), vec![1, 2, 3, 4]);
+        assert_eq!(ids(&data, "way"), vec![10, 11]);
+        assert_eq!(ids(&data, "relation"), vec![20]);
+        let node = data.elements().iter().find(|e| e.id == 1).unwrap();
+        assert_eq!(node.tags.as_ref().unwrap()["name"], "Kro & Bageri");
+
+        // A tile of a larger area takes its part of the area's extract: only node 4 lies in
+        // it, but ways 11 and 12 run through it, and relation 20 brings its way 10 along
+        let area = LLBBox::new(55.0, 10.0, 55.05, 10.05).unwrap();
+        let tile = LLBBox::new(55.035, 10.0, 55.05, 10.05).unwrap();
+        let data = read_osm_file(&path, &area, &tile).unwrap();
+        assert_eq!(ids(&data, "node"), vec![1, 2, 3, 4]);
+        assert_eq!(ids(&data, "way"), vec![10, 11, 12]);
+        assert_eq!(ids(&data, "relation"), vec![20]);
+    }
+
+    #[test]
+    fn reads_dense_nodes_and_ways_from_pbf() {
+        fn varint(mut value: u64, out: &mut Vec<u8>) {
+            while value >= 0x80 {
+                out.push((value as u8) | 0x80);
+                value >>= 7;
+            }
+            out.push(value as u8);
+        }
+        fn bytes(field: u64, data: &[u8], out: &mut Vec<u8>) {
+            varint(field << 3 | 2, out);
+            varint(data.len() as u64, out);
+            out.extend_from_slice(data);
+        }
+        fn packed(field: u64, values: &[u64], out: &mut Vec<u8>) {
+            let mut data = Vec::new();
+            values.iter().for_each(|&v| varint(v, &mut data));
+            bytes(field, &data, out);
+        }
+        let zz = |v: i64| ((v << 1) ^ (v >> 63)) as u64;
+        let blob = |kind: &str, data: &[u8], compress: bool, out: &mut Vec<u8>| {
+            let mut blob = Vec::new();
+            if compress {
+                let mut zlib = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                zlib.write_all(data).unwrap();
+                varint(2 << 3, &mut blob);
+                varint(data.len() as u64, &mut blob);
+                bytes(3, &zlib.finish().unwrap(), &mut blob);
+            } else {
+                bytes(1, data, &mut blob);
+            }
+            let mut header = Vec::new();
+            bytes(1, kind.as_bytes(), &mut header);
+            varint(3 << 3, &mut header);
+            varint(blob.len() as u64, &mut header);
+            out.extend_from_slice(&(header.len() as u32).to_be_bytes());
+            out.extend(header);
+            out.extend(blob);
+        };
+
+        let mut header = Vec::new();
+        bytes(4, b"OsmSchema-V0.6", &mut header);
+        bytes(4, b"DenseNodes", &mut header);
+
+        // Strings: "", "highway", "residential"; nodes 5 and 7 at 55.005,10.005 and 55.02,10.005
+        let mut table = Vec::new();
+        for s in ["", "highway", "residential"] {
+            bytes(1, s.as_bytes(), &mut table);
+        }
+        let mut dense = Vec::new();
+        packed(1, &[zz(5), zz(2)], &mut dense);
+        packed(8, &[zz(550_050_000), zz(150_000)], &mut dense);
+        packed(9, &[zz(100_050_000), zz(0)], &mut dense);
+        packed(10, &[0, 0], &mut dense);
+        let mut way = Vec::new();
+        varint(1 << 3, &mut way);
+        varint(30, &mut way);
+        packed(2, &[1], &mut way);
+        packed(3, &[2], &mut way);
+        packed(8, &[zz(5), zz(2)], &mut way);
+        let mut group = Vec::new();
+        bytes(2, &dense, &mut group);
+        bytes(3, &way, &mut group);
+        let mut block = Vec::new();
+        bytes(1, &table, &mut block);
+        bytes(2, &group, &mut block);
+
+        let mut file = Vec::new();
+        blob("OSMHeader", &header, false, &mut file);
+        blob("OSMData", &block, true, &mut file);
+        let dir = tempfile::tempdir().unwrap();
+        let path = temp_file(&dir, "extract.osm.pbf", &file);
+        let bbox = LLBBox::new(55.0, 10.0, 55.01, 10.01).unwrap();
+        let data = read_osm_file(&path, &bbox, &bbox).unwrap();
+
+        assert_eq!(ids(&data, "node"), vec![5, 7]);
+        let way = data.elements().iter().find(|e| e.id == 30).unwrap();
+        assert_eq!(way.nodes.as_deref(), Some(&[5, 7][..]));
+        assert_eq!(way.tags.as_ref().unwrap()["highway"], "residential");
+        let node = data.elements().iter().find(|e| e.id == 7).unwrap();
+        assert!((node.lat.unwrap() - 55.02).abs() < 1e-9);
+    }
+}
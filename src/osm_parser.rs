@@ -2,6 +2,7 @@ use crate::clipping::clip_way_to_bbox;
 use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
 use crate::coordinate_system::geographic::{LLBBox, LLPoint};
 use crate::coordinate_system::transformation::CoordTransformer;
+use crate::element_processing::tunnels;
 use crate::progress::emit_gui_progress_update;
 use colored::Colorize;
 use serde::Deserialize;
@@ -11,14 +12,14 @@ use std::sync::Arc;
 // Raw data from OSM
 
 #[derive(Debug, Deserialize)]
-struct OsmMember {
-    r#type: String,
-    r#ref: u64,
-    r#role: String,
+pub(crate) struct OsmMember {
+    pub r#type: String,
+    pub r#ref: u64,
+    pub r#role: String,
 }
 
 #[derive(Debug, Deserialize)]
-struct OsmElement {
+pub(crate) struct OsmElement {
     pub r#type: String,
     pub id: u64,
     pub lat: Option<f64>,
@@ -37,6 +38,18 @@ pub struct OsmData {
 }
 
 impl OsmData {
+    /// OSM data read from elsewhere than Overpass, e.g. a local extract
+    pub(crate) fn from_elements(elements: Vec<OsmElement>) -> Self {
+        Self {
+            elements,
+            remark: None,
+        }
+    }
+
+    pub(crate) fn elements(&self) -> &[OsmElement] {
+        &self.elements
+    }
+
     /// Returns true if there are no elements in the OSM data
     pub fn is_empty(&self) -> bool {
         self.elements.is_empty()
@@ -89,9 +102,25 @@ pub struct ProcessedNode {
     // Minecraft coordinates
     pub x: i32,
     pub z: i32,
+    /// Fractional block position the node was projected to, before it was snapped to
+    /// `x` and `z`. `None` for nodes placed on whole blocks.
+    pub exact: Option<(f64, f64)>,
 }
 
 impl ProcessedNode {
+    /// Fractional block position of the node, for geometry that should not build on
+    /// snapped positions
+    pub fn position(&self) -> (f64, f64) {
+        self.exact.unwrap_or((self.x as f64, self.z as f64))
+    }
+
+    /// Moves the node by whole blocks.
+    pub fn translate(&mut self, dx: i32, dz: i32) {
+        self.x += dx;
+        self.z += dz;
+        self.exact = self.exact.map(|(x, z)| (x + dx as f64, z + dz as f64));
+    }
+
     pub fn xz(&self) -> XZPoint {
         XZPoint {
             x: self.x,
@@ -143,6 +172,14 @@ impl ProcessedElement {
         }
     }
 
+    pub fn tags_mut(&mut self) -> &mut HashMap<String, String> {
+        match self {
+            ProcessedElement::Node(n) => &mut n.tags,
+            ProcessedElement::Way(w) => &mut w.tags,
+            ProcessedElement::Relation(r) => &mut r.tags,
+        }
+    }
+
     pub fn id(&self) -> u64 {
         match self {
             ProcessedElement::Node(n) => n.id,
@@ -151,7 +188,7 @@ impl ProcessedElement {
         }
     }
 
-    pub fn kind(&self) -> &str {
+    pub fn kind(&self) -> &'static str {
         match self {
             ProcessedElement::Node(_) => "node",
             ProcessedElement::Way(_) => "way",
@@ -222,6 +259,7 @@ pub fn parse_osm_data_with_transformer(
                 tags: element.tags.clone().unwrap_or_default(),
                 x: xzpoint.x,
                 z: xzpoint.z,
+                exact: Some(coord_transformer.project(llpoint)),
             };
 
             nodes_map.insert(element.id, processed.clone());
@@ -268,8 +306,13 @@ pub fn parse_osm_data_with_transformer(
         }
 
         // Coastlines need to stay unclipped until after segment merging so ocean
-        // reconstruction can use the full shoreline context near bbox edges.
-        let nodes = if is_coastline_way(&way.tags) {
+        // reconstruction can use the full shoreline context near bbox edges. Road tunnels
+        // stay whole too, so their ramps count from the portals, wherever those lie, and so
+        // do waterways, so their water level runs on from upstream across tile seams.
+        let nodes = if is_coastline_way(&way.tags)
+            || is_road_tunnel_way(&way.tags)
+            || is_waterway_line(&way.tags)
+        {
             way.nodes.clone()
         } else {
             clipped_nodes
@@ -307,7 +350,8 @@ pub fn parse_osm_data_with_transformer(
         let is_building_multipolygon = (tags.contains_key("building")
             || tags.contains_key("building:part"))
             && relation_type == Some("multipolygon");
-        let keep_unclipped = is_water_relation || is_building_multipolygon;
+        let keep_unclipped =
+            is_water_relation || is_building_multipolygon || is_filled_multipolygon(tags);
 
         let members: Vec<ProcessedMember> = element
             .members
@@ -393,6 +437,32 @@ fn is_coastline_way(tags: &HashMap<String, String>) -> bool {
     tags.get("natural") == Some(&"coastline".to_string())
 }
 
+/// Returns true if tags indicate a road tunnel, whose profile is ramped from its portals.
+fn is_road_tunnel_way(tags: &HashMap<String, String>) -> bool {
+    tags.contains_key("highway") && tunnels::is_tunnel(tags)
+}
+
+/// Returns true if tags indicate a waterway drawn as a line, whose water level follows it
+/// downstream. Docks and riverbanks are water areas.
+fn is_waterway_line(tags: &HashMap<String, String>) -> bool {
+    tags.get("waterway")
+        .is_some_and(|w| w != "dock" && w != "riverbank")
+}
+
+/// Whether a relation is a landuse, natural or park multipolygon, whose rings are assembled
+/// and filled with the inner rings left empty. Buildings and water assemble their own.
+pub(crate) fn is_filled_multipolygon(tags: &HashMap<String, String>) -> bool {
+    tags.get("type").is_some_and(|t| t == "multipolygon")
+        && !tags.contains_key("building")
+        && !tags.contains_key("building:part")
+        && !is_water_element(tags)
+        && (tags.contains_key("landuse")
+            || tags
+                .get("natural")
+                .is_some_and(|v| v != "tree" && v != "coastline")
+            || tags.get("leisure").is_some_and(|v| v == "park"))
+}
+
 fn is_water_element(tags: &HashMap<String, String>) -> bool {
     // Check for explicit water tag
     if tags.contains_key("water") {
@@ -0,0 +1,377 @@
+//! Thematic overlay: colours road surfaces by tag attributes (`--overlay`).
+//!
+//! An overlay is a list of `condition => colour` rules separated by `;`. The first rule
+//! whose condition matches a highway's tags decides the concrete colour of its surface,
+//! roads that match no rule keep their normal look. Conditions are a small expression
+//! language over OSM tags:
+//! - `key` - the tag is present and not `no`
+//! - `key == value`, `key != value` - string comparison (quote values with spaces, `;` or
+//!   `=>`)
+//! - `key < 50`, `<=`, `>`, `>=` - numeric comparison on the leading number of the value,
+//!   so `maxspeed=30 mph` compares as 30; non-numeric values never match
+//! - `!cond`, `a && b`, `a || b` and parentheses
+//!
+//! The presets `speed`, `road-class` and `cycling` can be used instead of a rule list,
+//! e.g. `--overlay speed` or `--overlay "maxspeed >= 80 => red; bicycle => lime"`.
+
+use crate::block_definitions::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const PRESETS: &[(&str, &str)] = &[
+    (
+        "speed",
+        "maxspeed >= 100 => red; maxspeed >= 70 => orange; \
+         maxspeed >= 50 => yellow; maxspeed > 0 => lime",
+    ),
+    (
+        "road-class",
+        "highway == motorway || highway == trunk => red; highway == primary => orange; \
+         highway == secondary => yellow; highway == tertiary => lime; \
+         highway == residential || highway == unclassified => light_gray; \
+         highway == service => gray",
+    ),
+    (
+        "cycling",
+        "highway == cycleway => blue; \
+         cycleway || cycleway:both || cycleway:left || cycleway:right => light_blue; \
+         bicycle == designated => cyan",
+    ),
+];
+
+/// Overlay colours, named like the Minecraft dyes
+const COLOURS: &[(&str, Block)] = &[
+    ("white", WHITE_CONCRETE),
+    ("light_gray", LIGHT_GRAY_CONCRETE),
+    ("gray", GRAY_CONCRETE),
+    ("black", BLACK_CONCRETE),
+    ("brown", BROWN_CONCRETE),
+    ("red", RED_CONCRETE),
+    ("orange", ORANGE_CONCRETE),
+    ("yellow", YELLOW_CONCRETE),
+    ("lime", LIME_CONCRETE),
+    ("green", GREEN_CONCRETE),
+    ("cyan", CYAN_CONCRETE),
+    ("light_blue", LIGHT_BLUE_CONCRETE),
+    ("blue", BLUE_CONCRETE),
+    ("purple", PURPLE_CONCRETE),
+    ("magenta", MAGENTA_CONCRETE),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CmpOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Has(String),
+    Eq(String, String),
+    Ne(String, String),
+    Cmp(String, CmpOp, f64),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn matches(&self, tags: &HashMap<String, String>) -> bool {
+        match self {
+            Expr::Has(key) => tags.get(key).is_some_and(|v| v != "no"),
+            Expr::Eq(key, value) => tags.get(key) == Some(value),
+            Expr::Ne(key, value) => tags.get(key) != Some(value),
+            Expr::Cmp(key, op, rhs) => {
+                tags.get(key)
+                    .and_then(|v| leading_number(v))
+                    .is_some_and(|lhs| match op {
+                        CmpOp::Lt => lhs < *rhs,
+                        CmpOp::Le => lhs <= *rhs,
+                        CmpOp::Gt => lhs > *rhs,
+                        CmpOp::Ge => lhs >= *rhs,
+                    })
+            }
+            Expr::Not(inner) => !inner.matches(tags),
+            Expr::And(a, b) => a.matches(tags) && b.matches(tags),
+            Expr::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+}
+
+/// Parses the number at the start of a tag value, e.g. `30 mph` -> 30
+fn leading_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Op(&'static str),
+}
+
+const OPERATORS: &[&str] = &["==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")"];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| format!("Unterminated string in overlay rule: {input}"))?;
+            tokens.push(Token::Word(rest[1..=end].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "()!=<>&|\"'".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("Unexpected '{c}' in overlay rule: {input}"));
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Byte offsets of `pattern` in `input` outside quoted strings
+fn find_unquoted(input: &str, pattern: &str) -> Result<Vec<usize>, String> {
+    let mut found = Vec::new();
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if input[i..].starts_with(pattern) => found.push(i),
+            None => {}
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("Unterminated string in overlay: {input}"));
+    }
+    Ok(found)
+}
+
+/// Recursive descent parser: `or := and ("||" and)*`, `and := unary ("&&" unary)*`,
+/// `unary := "!" unary | "(" or ")" | key [op value]`
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self, op: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Op(o)) if *o == op)
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(word)) => {
+                self.pos += 1;
+                Ok(word.clone())
+            }
+            _ => Err(format!("Expected {what} in overlay rule")),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek_op("||") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek_op("&&") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek_op("!") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.peek_op("(") {
+            self.pos += 1;
+            let expr = self.or()?;
+            if !self.peek_op(")") {
+                return Err("Missing ')' in overlay rule".to_string());
+            }
+            self.pos += 1;
+            return Ok(expr);
+        }
+
+        let key = self.word("tag key")?;
+        let op = match self.tokens.get(self.pos) {
+            Some(Token::Op(op @ ("==" | "!=" | "<" | "<=" | ">" | ">="))) => *op,
+            _ => return Ok(Expr::Has(key)),
+        };
+        self.pos += 1;
+        let value = self.word("value")?;
+
+        let cmp = match op {
+            "==" => return Ok(Expr::Eq(key, value)),
+            "!=" => return Ok(Expr::Ne(key, value)),
+            "<" => CmpOp::Lt,
+            "<=" => CmpOp::Le,
+            ">" => CmpOp::Gt,
+            _ => CmpOp::Ge,
+        };
+        let number = value
+            .parse()
+            .map_err(|_| format!("'{key} {op} {value}' needs a number"))?;
+        Ok(Expr::Cmp(key, cmp, number))
+    }
+}
+
+fn parse_condition(input: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    let expr = parser.or()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(format!(
+            "Unexpected trailing input in overlay rule: {input}"
+        ));
+    }
+    Ok(expr)
+}
+
+/// Parsed `--overlay` rules.
+#[derive(Clone, Debug)]
+pub struct Overlay {
+    /// The rule list as given (presets expanded)
+    pub source: String,
+    rules: Vec<(Expr, Block)>,
+}
+
+impl Overlay {
+    /// Returns the overlay colour for a highway, or `None` when no rule matches.
+    pub fn colour_for(&self, tags: &HashMap<String, String>) -> Option<Block> {
+        self.rules
+            .iter()
+            .find(|(condition, _)| condition.matches(tags))
+            .map(|(_, block)| *block)
+    }
+}
+
+impl FromStr for Overlay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = PRESETS
+            .iter()
+            .find(|(name, _)| *name == s.trim())
+            .map_or(s, |(_, rules)| rules);
+
+        let mut sources = Vec::new();
+        let mut start = 0;
+        for end in find_unquoted(source, ";")? {
+            sources.push(&source[start..end]);
+            start = end + 1;
+        }
+        sources.push(&source[start..]);
+
+        let mut rules = Vec::new();
+        for rule in sources.into_iter().map(str::trim).filter(|r| !r.is_empty()) {
+            let arrow = *find_unquoted(rule, "=>")?
+                .last()
+                .ok_or_else(|| format!("Overlay rule '{rule}' is missing '=> colour'"))?;
+            let (condition, colour) = (&rule[..arrow], rule[arrow + 2..].trim());
+            let block = COLOURS
+                .iter()
+                .find(|(name, _)| *name == colour)
+                .map(|(_, block)| *block)
+                .ok_or_else(|| format!("Unknown overlay colour '{colour}'"))?;
+            rules.push((parse_condition(condition)?, block));
+        }
+
+        if rules.is_empty() {
+            return Err("Overlay has no rules".to_string());
+        }
+        Ok(Self {
+            source: source.to_string(),
+            rules,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::tags;
+
+    #[test]
+    fn first_matching_rule_decides_the_colour() {
+        let overlay: Overlay = "maxspeed >= 80 && !(highway == service) => red; \
+                                cycleway:right || bicycle == 'designated' => lime"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            overlay.colour_for(&tags(&[("highway", "primary"), ("maxspeed", "80")])),
+            Some(RED_CONCRETE)
+        );
+        assert_eq!(
+            overlay.colour_for(&tags(&[("maxspeed", "50 mph"), ("cycleway:right", "lane")])),
+            Some(LIME_CONCRETE)
+        );
+        assert_eq!(
+            overlay.colour_for(&tags(&[("highway", "service"), ("maxspeed", "90")])),
+            None
+        );
+        assert_eq!(
+            overlay.colour_for(&tags(&[("maxspeed", "DK:urban"), ("cycleway:right", "no")])),
+            None
+        );
+    }
+
+    #[test]
+    fn presets_and_invalid_rules() {
+        let speed: Overlay = "speed".parse().unwrap();
+        assert_eq!(
+            speed.colour_for(&tags(&[("maxspeed", "130")])),
+            Some(RED_CONCRETE)
+        );
+
+        assert!("maxspeed > fast => red".parse::<Overlay>().is_err());
+        assert!("highway == primary => pink".parse::<Overlay>().is_err());
+        assert!("(highway => red".parse::<Overlay>().is_err());
+        assert!("highway".parse::<Overlay>().is_err());
+        assert!("name == 'A;B => red".parse::<Overlay>().is_err());
+    }
+
+    #[test]
+    fn quoted_values_may_hold_separators() {
+        let overlay: Overlay = "name == 'Vej; gl. => ny' => blue; highway => red"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            overlay.colour_for(&tags(&[("name", "Vej; gl. => ny")])),
+            Some(BLUE_CONCRETE)
+        );
+        assert_eq!(
+            overlay.colour_for(&tags(&[("highway", "primary")])),
+            Some(RED_CONCRETE)
+        );
+    }
+}
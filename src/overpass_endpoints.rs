@@ -0,0 +1,206 @@
+//! Overpass mirrors (`--overpass-url`), their rotation and failover.
+//!
+//! A single overloaded Overpass server should not end a run. The mirrors, the public ones or
+//! those given with `--overpass-url`, are tried in turn, each download starting at the next
+//! one so repeated and tiled runs spread their load. A mirror answering 429 (too many
+//! requests) or 504 (gateway timeout) is rested, for as long as its `Retry-After` asks or two
+//! minutes, and tried after the others until then. A mirror whose status page reports no
+//! free slot is tried last, and requests to the same mirror are spaced out so a run of many
+//! tiles stays within the public servers' usage policy.
+
+use reqwest::blocking::Client;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The public mirrors and whether each is only a last resort
+const PUBLIC_ENDPOINTS: &[(&str, bool)] = &[
+    ("https://overpass-api.de/api/interpreter", false),
+    ("https://lz4.overpass-api.de/api/interpreter", false),
+    ("https://z.overpass-api.de/api/interpreter", false),
+    //("https://overpass.kumi.systems/api/interpreter", false), // This server is not reliable anymore
+    //("https://overpass.private.coffee/api/interpreter", false), // This server is not reliable anymore
+    (
+        "https://maps.mail.ru/osm/tools/overpass/api/interpreter",
+        true,
+    ),
+];
+
+/// Shortest time between two requests to the same mirror
+const MIN_INTERVAL: Duration = Duration::from_secs(10);
+/// How long an overloaded mirror is rested when it does not say
+const DEFAULT_REST: Duration = Duration::from_secs(120);
+/// Timeout of a status page check
+const STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Endpoint {
+    url: String,
+    last_resort: bool,
+    last_request: Option<Instant>,
+    rested_until: Option<Instant>,
+}
+
+struct Pool {
+    endpoints: Vec<Endpoint>,
+    /// Index of the mirror the next download starts at
+    next: usize,
+}
+
+impl Pool {
+    fn new<'a>(endpoints: impl IntoIterator<Item = (&'a str, bool)>, next: usize) -> Self {
+        Self {
+            endpoints: endpoints
+                .into_iter()
+                .map(|(url, last_resort)| Endpoint {
+                    url: url.to_string(),
+                    last_resort,
+                    last_request: None,
+                    rested_until: None,
+                })
+                .collect(),
+            next,
+        }
+    }
+
+    fn get_mut(&mut self, url: &str) -> Option<&mut Endpoint> {
+        self.endpoints.iter_mut().find(|e| e.url == url)
+    }
+
+    /// Mirrors in the order to try them: from the next in rotation, then the last resorts,
+    /// with mirrors still resting moved behind the others
+    fn order(&mut self, now: Instant) -> Vec<String> {
+        let (mut rotation, last_resorts): (Vec<&Endpoint>, Vec<&Endpoint>) =
+            self.endpoints.iter().partition(|e| !e.last_resort);
+        if !rotation.is_empty() {
+            let start = self.next % rotation.len();
+            rotation.rotate_left(start);
+        }
+        self.next = self.next.wrapping_add(1);
+        rotation.extend(last_resorts);
+        rotation.sort_by_key(|e| e.rested_until.filter(|until| *until > now));
+        rotation.into_iter().map(|e| e.url.clone()).collect()
+    }
+}
+
+static POOL: Mutex<Option<Pool>> = Mutex::new(None);
+
+fn with_pool<T>(f: impl FnOnce(&mut Pool) -> T) -> T {
+    let mut pool = POOL.lock().unwrap_or_else(|e| e.into_inner());
+    let pool = pool.get_or_insert_with(|| {
+        let rotating = PUBLIC_ENDPOINTS.iter().filter(|(_, last)| !last).count();
+        Pool::new(
+            PUBLIC_ENDPOINTS.iter().copied(),
+            rand::random_range(0..rotating.max(1)),
+        )
+    });
+    f(pool)
+}
+
+/// Replaces the public mirrors with the given ones, tried in the order given.
+pub fn configure(urls: &[String]) {
+    if urls.is_empty() {
+        return;
+    }
+    with_pool(|pool| *pool = Pool::new(urls.iter().map(|url| (url.as_str(), false)), 0));
+}
+
+/// The mirrors to try for the next download, in order
+pub fn rotation() -> Vec<String> {
+    with_pool(|pool| pool.order(Instant::now()))
+}
+
+/// Waits until the mirror may be asked again, and books the request.
+pub fn wait_turn(url: &str) {
+    let wait = with_pool(|pool| {
+        let now = Instant::now();
+        let Some(endpoint) = pool.get_mut(url) else {
+            return Duration::ZERO;
+        };
+        let at = endpoint
+            .last_request
+            .map_or(now, |last| (last + MIN_INTERVAL).max(now));
+        endpoint.last_request = Some(at);
+        at - now
+    });
+    if !wait.is_zero() {
+        println!("Waiting {}s before asking {url} again...", wait.as_secs());
+        std::thread::sleep(wait);
+    }
+}
+
+/// Rests a mirror that answered it is overloaded.
+pub fn rest(url: &str, retry_after: Option<Duration>) {
+    with_pool(|pool| {
+        if let Some(endpoint) = pool.get_mut(url) {
+            endpoint.rested_until = Some(Instant::now() + retry_after.unwrap_or(DEFAULT_REST));
+        }
+    });
+}
+
+/// Asks a mirror's status page whether it has a free slot. Mirrors without a status page
+/// count as free; unreachable ones do not.
+pub fn has_free_slot(url: &str) -> bool {
+    let Some(status_url) = url
+        .strip_suffix("/interpreter")
+        .map(|base| format!("{base}/status"))
+    else {
+        return true;
+    };
+    let Ok(client) = Client::builder().timeout(STATUS_TIMEOUT).build() else {
+        return true;
+    };
+    match client.get(&status_url).send() {
+        Ok(response) if response.status().is_success() => response
+            .text()
+            .map_or(true, |status| status_reports_free_slot(&status)),
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
+
+/// Whether an Overpass status page reports a free slot, e.g. "2 slots available now."
+/// rather than "0 slots available now." followed by "Slot available after: ..."
+fn status_reports_free_slot(status: &str) -> bool {
+    let available = status.lines().find_map(|line| {
+        let count = line
+            .strip_suffix(" available now.")?
+            .split_whitespace()
+            .next()?;
+        count.parse::<u32>().ok()
+    });
+    match available {
+        Some(count) => count > 0,
+        None => !status.contains("Slot available after"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_and_moves_rested_mirrors_last() {
+        let mut pool = Pool::new([("a", false), ("b", false), ("c", false), ("z", true)], 1);
+        let now = Instant::now();
+        assert_eq!(pool.order(now), ["b", "c", "a", "z"]);
+        assert_eq!(pool.order(now), ["c", "a", "b", "z"]);
+
+        pool.get_mut("a").unwrap().rested_until = Some(now + Duration::from_secs(60));
+        assert_eq!(pool.order(now), ["b", "c", "z", "a"]);
+        // Once rested, the mirror takes its turn again
+        let later = now + Duration::from_secs(61);
+        assert_eq!(pool.order(later), ["b", "c", "a", "z"]);
+    }
+
+    #[test]
+    fn reads_free_slots_from_the_status_page() {
+        let free = "Connected as: 1234\nRate limit: 2\n2 slots available now.\n";
+        let busy = "Connected as: 1234\nRate limit: 2\n0 slots available now.\n\
+                    Slot available after: 2026-01-01T12:00:00Z, in 25 seconds.\n";
+        assert!(status_reports_free_slot(free));
+        assert!(!status_reports_free_slot(busy));
+        assert!(!status_reports_free_slot(
+            "Rate limit: 2\nSlot available after: 2026-01-01T12:00:00Z, in 3 seconds.\n"
+        ));
+        assert!(status_reports_free_slot("Connected as: 1234\n"));
+    }
+}
@@ -0,0 +1,338 @@
+//! Data minimisation audit for the Danish register integrations (`--privacy-audit`).
+//!
+//! The audit runs on the elements once all enrichments are applied. Elements a register
+//! added, marked with [`SOURCE_KEY`], keep only the fields on an allowlist, and values
+//! anywhere that look like CPR numbers, or CVR and phone numbers on register elements, are
+//! dropped. Text that only takes shape during generation is checked as it is written: sign
+//! lines, book pages, the level name and the manifest lose any line with a CPR-shaped
+//! number. What was removed is written to `arnis-privacy-audit.json` as counts per source
+//! and field, never the values themselves, so the report can be shared as evidence for a
+//! municipal deployment.
+
+use crate::enrichment::SOURCE_KEY;
+use crate::osm_parser::ProcessedElement;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Whether the audit is on, so text is checked as it is written
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Findings of the run so far, over all of its tiles
+static REPORT: Lazy<Mutex<AuditReport>> = Lazy::new(Default::default);
+
+/// Fields register elements may carry into the world
+const ALLOWED_FIELDS: &[&str] = &[
+    SOURCE_KEY,
+    // Addresses and place names
+    "addr:street",
+    "addr:housenumber",
+    "stednavn",
+    "stednavn:type",
+    // Nature areas and fields
+    "natural",
+    "wetland",
+    "water",
+    "protect_class",
+    "landuse",
+    "crop",
+    "crop:season",
+    // Coast
+    "man_made",
+    // Transit stops
+    "highway",
+    "railway",
+    "public_transport",
+    "train",
+    "name",
+    "route_ref",
+    "shelter",
+    // Wind turbines
+    "power",
+    "generator:source",
+    "generator:method",
+    "height:hub",
+    "rotor:diameter",
+    // Ancient monuments
+    "historic",
+    "site_type",
+    "megalith_type",
+];
+
+/// What the audit removed, by count only
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct AuditReport {
+    pub elements_checked: usize,
+    pub register_elements: usize,
+    /// Fields not on the allowlist, by register source and field
+    pub removed_fields: BTreeMap<String, BTreeMap<String, usize>>,
+    /// Values that looked like personal identifiers, by field
+    pub removed_identifiers: BTreeMap<String, usize>,
+    /// Lines of text checked as they were written, by output: signs, books and metadata
+    pub lines_checked: BTreeMap<String, usize>,
+    /// Lines of text blanked for a CPR-shaped number, by output
+    pub removed_lines: BTreeMap<String, usize>,
+}
+
+impl AuditReport {
+    /// Whether nothing had to be removed
+    pub fn is_clean(&self) -> bool {
+        self.removed_fields.is_empty()
+            && self.removed_identifiers.is_empty()
+            && self.removed_lines.is_empty()
+    }
+
+    /// Adds the counts of `other`, e.g. the element audit of another tile
+    fn merge(&mut self, other: AuditReport) {
+        self.elements_checked += other.elements_checked;
+        self.register_elements += other.register_elements;
+        for (source, fields) in other.removed_fields {
+            let merged = self.removed_fields.entry(source).or_default();
+            for (field, count) in fields {
+                *merged.entry(field).or_default() += count;
+            }
+        }
+        for (counts, other) in [
+            (&mut self.removed_identifiers, other.removed_identifiers),
+            (&mut self.lines_checked, other.lines_checked),
+            (&mut self.removed_lines, other.removed_lines),
+        ] {
+            for (key, count) in other {
+                *counts.entry(key).or_default() += count;
+            }
+        }
+    }
+}
+
+/// Runs of digits in `text`, with a single hyphen allowed inside a run (as in "010203-1234")
+fn number_tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_ascii_digit() && c != '-')
+        .map(|token| token.trim_matches('-'))
+        .filter(|token| !token.is_empty())
+}
+
+/// Whether `text` contains a number shaped like a CPR number: a valid DDMMYY birth date
+/// followed by four digits, with or without the hyphen
+pub fn contains_cpr_number(text: &str) -> bool {
+    number_tokens(text).any(|token| {
+        let digits: String = match token.split_once('-') {
+            Some((date, serial)) if date.len() == 6 && serial.len() == 4 => {
+                format!("{date}{serial}")
+            }
+            Some(_) => return false,
+            None => token.to_string(),
+        };
+        if digits.len() != 10 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return false;
+        }
+        let day: u32 = digits[0..2].parse().unwrap_or(0);
+        let month: u32 = digits[2..4].parse().unwrap_or(0);
+        (1..=31).contains(&day) && (1..=12).contains(&month)
+    })
+}
+
+/// Whether `text` contains an eight-digit number, the shape of CVR and phone numbers
+fn contains_eight_digit_number(text: &str) -> bool {
+    number_tokens(text).any(|token| token.len() == 8 && token.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Strips the fields and values that must not reach the world and reports what was removed.
+pub fn audit_elements(elements: &mut [ProcessedElement]) -> AuditReport {
+    let mut report = AuditReport {
+        elements_checked: elements.len(),
+        ..Default::default()
+    };
+    for element in elements {
+        let tags = element.tags_mut();
+        let register = tags.get(SOURCE_KEY).cloned();
+
+        if let Some(source) = &register {
+            report.register_elements += 1;
+            let removed: Vec<String> = tags
+                .keys()
+                .filter(|key| !ALLOWED_FIELDS.contains(&key.as_str()))
+                .cloned()
+                .collect();
+            for key in removed {
+                tags.remove(&key);
+                *report
+                    .removed_fields
+                    .entry(source.clone())
+                    .or_default()
+                    .entry(key)
+                    .or_default() += 1;
+            }
+        }
+
+        let identifiers: Vec<String> = tags
+            .iter()
+            .filter(|(_, value)| {
+                contains_cpr_number(value)
+                    || (register.is_some() && contains_eight_digit_number(value))
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in identifiers {
+            tags.remove(&key);
+            *report.removed_identifiers.entry(key).or_default() += 1;
+        }
+    }
+    report
+}
+
+/// Turns on the checks of text written during generation.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Adds the audit of a tile's elements to the report of the run.
+pub fn record(report: AuditReport) {
+    if let Ok(mut run) = REPORT.lock() {
+        run.merge(report);
+    }
+}
+
+/// Blanks the lines of text about to be written to `output` (e.g. "signs") that contain a
+/// CPR-shaped number. Does nothing unless the audit is on.
+pub fn audit_lines(output: &str, lines: &mut [String]) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let removed = lines
+        .iter_mut()
+        .filter(|line| contains_cpr_number(line))
+        .map(String::clear)
+        .count();
+    if let Ok(mut run) = REPORT.lock() {
+        *run.lines_checked.entry(output.to_string()).or_default() += lines.len();
+        if removed > 0 {
+            *run.removed_lines.entry(output.to_string()).or_default() += removed;
+        }
+    }
+}
+
+/// Blanks the string values of JSON metadata about to be written to `output` that contain a
+/// CPR-shaped number. Does nothing unless the audit is on.
+pub fn audit_json(output: &str, value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(text) => audit_lines(output, std::slice::from_mut(text)),
+        serde_json::Value::Array(items) => {
+            for item in items {
+                audit_json(output, item);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for item in fields.values_mut() {
+                audit_json(output, item);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The report of the run, leaving an empty one behind
+pub fn take_report() -> AuditReport {
+    REPORT
+        .lock()
+        .map(|mut run| std::mem::take(&mut *run))
+        .unwrap_or_default()
+}
+
+/// Writes the audit report as JSON.
+pub fn write_report(path: &Path, report: &AuditReport) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(report)
+        .map_err(|e| format!("Failed to serialize privacy audit: {e}"))?;
+    fs::write(path, json).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_parser::ProcessedNode;
+    use crate::test_utilities::tags;
+
+    // Fixtures use made-up numbers; 000000 is no birth date, so they never match a real CPR
+    #[test]
+    fn detects_cpr_shaped_numbers() {
+        assert!(contains_cpr_number("Ejer 311299-0000"));
+        assert!(contains_cpr_number("0101000000"));
+        assert!(!contains_cpr_number("000000-0000"));
+        assert!(!contains_cpr_number("2026-10-14"));
+        assert!(!contains_cpr_number("Vestergade 12"));
+        assert!(!contains_cpr_number("12345678901"));
+
+        // No test fixture may contain a CPR-shaped number
+        let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if let Ok(text) = fs::read_to_string(&path) {
+                    assert!(!contains_cpr_number(&text), "{}", path.display());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_only_allowed_register_fields() {
+        let node = |id, pairs: &[(&str, &str)]| {
+            ProcessedElement::Node(ProcessedNode {
+                id,
+                tags: tags(pairs),
+                x: 0,
+                z: 0,
+                exact: None,
+            })
+        };
+        let mut elements = vec![
+            node(
+                1,
+                &[
+                    (SOURCE_KEY, "DAWA"),
+                    ("addr:street", "Vestergade"),
+                    ("addr:housenumber", "12"),
+                    ("owner", "A. Jensen"),
+                    ("name", "tlf 12345678"),
+                ],
+            ),
+            // A mapper's own source tag does not make an element a register element
+            node(
+                2,
+                &[("name", "Kiosk"), ("phone", "12345678"), ("source", "DAWA")],
+            ),
+            node(3, &[("note", "cpr 010100-0000")]),
+        ];
+        let report = audit_elements(&mut elements);
+
+        assert_eq!(report.register_elements, 1);
+        assert_eq!(report.removed_fields["DAWA"]["owner"], 1);
+        assert_eq!(report.removed_identifiers["name"], 1);
+        assert_eq!(report.removed_identifiers["note"], 1);
+        assert_eq!(elements[0].tags().len(), 3);
+        // Eight-digit numbers are only removed from register elements
+        assert_eq!(elements[1].tags().len(), 3);
+        assert!(elements[2].tags().is_empty());
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn blanks_cpr_numbers_in_signs_and_metadata() {
+        enable();
+        let mut lines = ["Vestergade 12".to_string(), "Ejer 311299-0000".to_string()];
+        audit_lines("signs", &mut lines);
+        assert_eq!(lines, ["Vestergade 12".to_string(), String::new()]);
+
+        let mut manifest = serde_json::json!({
+            "config": {"area": "0101000000", "scale": 1.0},
+            "synthetic": ["Urban infill"],
+        });
+        audit_json("manifest", &mut manifest);
+        assert_eq!(manifest["config"]["area"], "");
+        assert_eq!(manifest["synthetic"][0], "Urban infill");
+    }
+}
@@ -2,10 +2,14 @@
 use crate::telemetry::{send_log, LogLevel};
 use once_cell::sync::OnceCell;
 use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::{Emitter, WebviewWindow};
 
 pub static MAIN_WINDOW: OnceCell<WebviewWindow> = OnceCell::new();
 
+/// Set by the GUI's cancel button, checked by long-running waits
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+
 pub fn set_main_window(window: WebviewWindow) {
     MAIN_WINDOW.set(window).ok();
 }
@@ -20,6 +24,21 @@ pub fn is_running_with_gui() -> bool {
     get_main_window().is_some()
 }
 
+/// Requests cancellation of the running generation.
+pub fn request_cancel() {
+    CANCEL_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Clears a previous cancellation before a new generation starts.
+pub fn reset_cancel() {
+    CANCEL_REQUESTED.store(false, Ordering::Relaxed);
+}
+
+/// Whether the GUI asked to cancel the running generation.
+pub fn cancel_requested() -> bool {
+    CANCEL_REQUESTED.load(Ordering::Relaxed)
+}
+
 /// This code manages a multi-step process with a progress bar indicating the overall completion.
 /// The progress updates are mapped to specific steps in the pipeline:
 ///
@@ -0,0 +1,197 @@
+//! Merging the progress of parallel work into one overall percentage for the GUI.
+//!
+//! A step of the pipeline owns a slice of the progress bar (e.g. 90% to 99% for saving) and
+//! splits it into stages weighted by their estimated cost. Worker threads advance their
+//! stage by the units they complete; the aggregator sums the weighted stage fractions and
+//! hands `emit_gui_progress_update` a percentage that only ever goes up, however the
+//! threads interleave.
+
+use crate::progress::emit_gui_progress_update;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// A stage of work with its share of the overall cost.
+struct Stage {
+    weight: f64,
+    total_units: u64,
+    completed_units: AtomicU64,
+}
+
+impl Stage {
+    fn fraction(&self) -> f64 {
+        if self.total_units == 0 {
+            return 1.0;
+        }
+        let completed = self.completed_units.load(Ordering::Relaxed);
+        (completed as f64 / self.total_units as f64).min(1.0)
+    }
+}
+
+/// Aggregates the progress of weighted stages, advanced from any thread, into the
+/// `start..end` range of the GUI progress bar.
+pub struct ProgressAggregator {
+    start: f64,
+    end: f64,
+    message: String,
+    show_percent: bool,
+    /// Percentage points of this aggregator's own range between two updates
+    step: u32,
+    stages: Vec<Stage>,
+    /// Last emitted step bucket. Emitting under the lock keeps updates in order.
+    last_bucket: Mutex<u32>,
+}
+
+impl ProgressAggregator {
+    pub fn new(start: f64, end: f64, message: &str) -> Self {
+        Self {
+            start,
+            end,
+            message: message.to_string(),
+            show_percent: false,
+            step: 1,
+            stages: Vec::new(),
+            last_bucket: Mutex::new(0),
+        }
+    }
+
+    /// Appends the aggregated percentage of this range to the message, e.g. "Saving... 40%".
+    pub fn show_percent(mut self) -> Self {
+        self.show_percent = true;
+        self
+    }
+
+    /// Emits an update only every `step` percent of this range.
+    pub fn step(mut self, step: u32) -> Self {
+        self.step = step.clamp(1, 100);
+        self
+    }
+
+    /// Adds a stage of `total_units` units whose share of the range is its estimated cost
+    /// relative to the other stages. Returns the stage index to advance.
+    pub fn stage(&mut self, estimated_cost: f64, total_units: u64) -> usize {
+        self.stages.push(Stage {
+            weight: estimated_cost.max(0.0),
+            total_units,
+            completed_units: AtomicU64::new(0),
+        });
+        self.stages.len() - 1
+    }
+
+    /// Fraction of the whole work done, from 0 to 1.
+    pub fn fraction(&self) -> f64 {
+        let total_weight: f64 = self.stages.iter().map(|s| s.weight).sum();
+        if total_weight <= 0.0 {
+            return if self.stages.iter().all(|s| s.fraction() >= 1.0) {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        self.stages
+            .iter()
+            .map(|s| s.weight * s.fraction())
+            .sum::<f64>()
+            / total_weight
+    }
+
+    /// Records `units` completed units of a stage and emits an update if the overall
+    /// percentage reached the next step. Returns the percentage of this range that was
+    /// emitted, if any.
+    pub fn advance(&self, stage: usize, units: u64) -> Option<u32> {
+        self.stages[stage]
+            .completed_units
+            .fetch_add(units, Ordering::Relaxed);
+        self.emit()
+    }
+
+    /// Marks a stage as complete, e.g. when its work ended early.
+    pub fn finish_stage(&self, stage: usize) -> Option<u32> {
+        let stage_ref = &self.stages[stage];
+        stage_ref
+            .completed_units
+            .fetch_max(stage_ref.total_units, Ordering::Relaxed);
+        self.emit()
+    }
+
+    fn emit(&self) -> Option<u32> {
+        // Every thread waits its turn, so the last unit's update is never dropped. Reading the
+        // stages under the lock sees the units of every thread that took it before.
+        let mut last_bucket = self.last_bucket.lock().unwrap();
+        let fraction = self.fraction();
+        let bucket = (fraction * 100.0).floor() as u32 / self.step;
+        if bucket <= *last_bucket {
+            return None;
+        }
+        *last_bucket = bucket;
+
+        let percent = (bucket * self.step).min(100);
+        let progress = self.start + (self.end - self.start) * fraction;
+        if self.show_percent {
+            emit_gui_progress_update(progress, &format!("{} {percent}%", self.message));
+        } else {
+            emit_gui_progress_update(progress, &self.message);
+        }
+        Some(percent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Barrier;
+    use std::thread;
+
+    #[test]
+    fn stages_count_by_their_estimated_cost() {
+        let mut aggregator = ProgressAggregator::new(0.0, 100.0, "");
+        let cheap = aggregator.stage(1.0, 10);
+        let costly = aggregator.stage(3.0, 10);
+
+        aggregator.finish_stage(cheap);
+        assert!((aggregator.fraction() - 0.25).abs() < 1e-9);
+        aggregator.advance(costly, 5);
+        assert!((aggregator.fraction() - 0.625).abs() < 1e-9);
+        // Overshooting a stage does not count beyond its share
+        aggregator.advance(costly, 50);
+        assert!((aggregator.fraction() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parallel_updates_only_go_up() {
+        const THREADS: u64 = 8;
+        let mut aggregator = ProgressAggregator::new(90.0, 99.0, "Saving world...");
+        let rows = aggregator.stage(1.0, 1000);
+        let columns = aggregator.stage(2.0, 1000);
+
+        // The threads start together and advance as fast as they can, so they contend for
+        // every update
+        let start = Barrier::new(THREADS as usize);
+        let emitted = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let (aggregator, start, emitted) = (&aggregator, &start, &emitted);
+                scope.spawn(move || {
+                    start.wait();
+                    // Highest percentage this thread was handed so far
+                    let mut high_water = 0;
+                    for i in 0..2000 / THREADS {
+                        let stage = if (thread + i) % 2 == 0 { rows } else { columns };
+                        if let Some(percent) = aggregator.advance(stage, 1) {
+                            assert!(percent > high_water, "{percent}% after {high_water}%");
+                            high_water = percent;
+                            emitted.lock().unwrap().push(percent);
+                        }
+                    }
+                });
+            }
+        });
+
+        // Each step is emitted once, and the last one is never lost to a busy thread
+        let emitted = emitted.into_inner().unwrap();
+        let distinct: HashSet<u32> = emitted.iter().copied().collect();
+        assert_eq!(distinct.len(), emitted.len(), "a step was emitted twice");
+        assert!(emitted.contains(&100));
+        assert!(aggregator.finish_stage(rows).is_none());
+    }
+}
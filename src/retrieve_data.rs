@@ -1,21 +1,42 @@
+use crate::args::Args;
 use crate::coordinate_system::geographic::LLBBox;
 use crate::osm_parser::OsmData;
+use crate::overpass_endpoints;
 use crate::progress::{emit_gui_error, emit_gui_progress_update, is_running_with_gui};
 #[cfg(feature = "gui")]
 use crate::telemetry::{send_log, LogLevel};
 use colored::Colorize;
-use rand::prelude::IndexedRandom;
 use reqwest::blocking::Client;
 use reqwest::blocking::ClientBuilder;
 use serde::Deserialize;
 use serde_json::Value;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::{self, BufReader, Cursor, Write};
 use std::process::Command;
 use std::time::Duration;
 
+/// A failed download, and whether the server asked to back off
+struct DownloadFailure {
+    message: String,
+    /// The server answered 429 (too many requests) or 504 (gateway timeout)
+    overloaded: bool,
+    /// How long the server asked to be left alone, from its `Retry-After` header
+    retry_after: Option<Duration>,
+}
+
+impl<E: std::fmt::Display> From<E> for DownloadFailure {
+    fn from(error: E) -> Self {
+        Self {
+            message: error.to_string(),
+            overloaded: false,
+            retry_after: None,
+        }
+    }
+}
+
 /// Function to download data using reqwest
-fn download_with_reqwest(url: &str, query: &str) -> Result<String, Box<dyn std::error::Error>> {
+fn download_with_reqwest(url: &str, query: &str) -> Result<String, DownloadFailure> {
     let client: Client = ClientBuilder::new()
         .timeout(Duration::from_secs(360))
         .build()?;
@@ -33,7 +54,17 @@ fn download_with_reqwest(url: &str, query: &str) -> Result<String, Box<dyn std::
                 }
                 Ok(text)
             } else {
-                Err(format!("Error! Received response code: {}", resp.status()).into())
+                let status = resp.status().as_u16();
+                let retry_after = resp
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+                    .map(Duration::from_secs);
+                Err(DownloadFailure {
+                    message: format!("Error! Received response code: {}", resp.status()),
+                    overloaded: status == 429 || status == 504,
+                    retry_after,
+                })
             }
         }
         Err(e) => {
@@ -97,9 +128,97 @@ pub fn fetch_data_from_file(file: &str) -> Result<OsmData, Box<dyn std::error::E
     Ok(data)
 }
 
+/// Runs an Overpass query, failing over through the mirrors, and returns the mirror that
+/// answered and its response
+fn download_from_mirrors(
+    query: &str,
+    download_method: &str,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    // Mirrors without a free slot are put back to be tried after the others
+    let with_reqwest = !matches!(download_method, "curl" | "wget");
+    let mut mirrors: VecDeque<(String, bool)> = overpass_endpoints::rotation()
+        .into_iter()
+        .map(|url| (url, with_reqwest))
+        .collect();
+    let mut last_failure: Option<String> = None;
+    loop {
+        let Some((url, check_slot)) = mirrors.pop_front() else {
+            return Err(last_failure
+                .unwrap_or_else(|| "No Overpass mirror to download from".to_string())
+                .into());
+        };
+        if check_slot && !mirrors.is_empty() && !overpass_endpoints::has_free_slot(&url) {
+            println!("{url} has no free slot, trying it later...");
+            mirrors.push_back((url, false));
+            continue;
+        }
+
+        overpass_endpoints::wait_turn(&url);
+        println!("Downloading from {url} with method {download_method}...");
+        let result = match download_method {
+            "requests" => download_with_reqwest(&url, query),
+            "curl" => download_with_curl(&url, query).map_err(DownloadFailure::from),
+            "wget" => download_with_wget(&url, query).map_err(DownloadFailure::from),
+            _ => download_with_reqwest(&url, query), // Default to requests
+        };
+
+        match result {
+            Ok(response) => return Ok((url, response)),
+            Err(failure) => {
+                if failure.overloaded {
+                    overpass_endpoints::rest(&url, failure.retry_after);
+                }
+                if !mirrors.is_empty() {
+                    println!("Request failed. Switching to the next mirror...");
+                }
+                last_failure = Some(failure.message);
+            }
+        }
+    }
+}
+
+/// Optional layers whose tags are only worth fetching when they are rendered
+#[derive(Clone, Copy, Default)]
+pub struct OptionalLayers {
+    /// `tactile_paving=*` on kerbs, for --accessibility
+    pub accessibility: bool,
+    /// `seamark:type=*` buoys and beacons, for --navigable-waterways
+    pub seamarks: bool,
+}
+
+impl OptionalLayers {
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            accessibility: args.accessibility,
+            seamarks: args.navigable_waterways,
+        }
+    }
+
+    /// Every optional layer, for data generated from later with any flags
+    pub fn all() -> Self {
+        Self {
+            accessibility: true,
+            seamarks: true,
+        }
+    }
+
+    /// Overpass clauses fetching the tags of the enabled layers
+    fn clauses(&self) -> String {
+        let mut clauses = String::new();
+        if self.accessibility {
+            clauses.push_str("        nwr[\"tactile_paving\"];\n");
+        }
+        if self.seamarks {
+            clauses.push_str("        nwr[\"seamark:type\"];\n");
+        }
+        clauses
+    }
+}
+
 /// Main function to fetch data
 pub fn fetch_data_from_overpass(
     bbox: LLBBox,
+    layers: OptionalLayers,
     debug: bool,
     download_method: &str,
     save_file: Option<&str>,
@@ -107,18 +226,6 @@ pub fn fetch_data_from_overpass(
     println!("{} Fetching data...", "[1/7]".bold());
     emit_gui_progress_update(1.0, "Fetching data...");
 
-    // List of Overpass API servers
-    let api_servers: Vec<&str> = vec![
-        "https://overpass-api.de/api/interpreter",
-        "https://lz4.overpass-api.de/api/interpreter",
-        "https://z.overpass-api.de/api/interpreter",
-        //"https://overpass.kumi.systems/api/interpreter", // This server is not reliable anymore
-        //"https://overpass.private.coffee/api/interpreter", // This server is not reliable anymore
-    ];
-    let fallback_api_servers: Vec<&str> =
-        vec!["https://maps.mail.ru/osm/tools/overpass/api/interpreter"];
-    let mut url: &&str = api_servers.choose(&mut rand::rng()).unwrap();
-
     // Generate Overpass API query for bounding box
     let query: String = format!(
         r#"[out:json][timeout:360][bbox:{},{},{},{}];
@@ -145,7 +252,8 @@ pub fn fetch_data_from_overpass(
         nwr["advertising"];
         nwr["man_made"];
         nwr["aeroway"];
-        way["place"];
+{optional}        way["place"];
+        way["route"="ferry"];
         way;
     )->.relsinbbox;
     (
@@ -162,34 +270,12 @@ pub fn fetch_data_from_overpass(
         bbox.min().lng(),
         bbox.max().lat(),
         bbox.max().lng(),
+        optional = layers.clauses(),
     );
 
     {
-        // Fetch data from Overpass API
-        let mut attempt = 0;
-        let max_attempts = 1;
-        let response: String = loop {
-            println!("Downloading from {url} with method {download_method}...");
-            let result = match download_method {
-                "requests" => download_with_reqwest(url, &query),
-                "curl" => download_with_curl(url, &query).map_err(|e| e.into()),
-                "wget" => download_with_wget(url, &query).map_err(|e| e.into()),
-                _ => download_with_reqwest(url, &query), // Default to requests
-            };
-
-            match result {
-                Ok(response) => break response,
-                Err(error) => {
-                    if attempt >= max_attempts {
-                        return Err(error);
-                    }
-
-                    println!("Request failed. Switching to fallback url...");
-                    url = fallback_api_servers.choose(&mut rand::rng()).unwrap();
-                    attempt += 1;
-                }
-            }
-        };
+        let (url, response) = download_from_mirrors(&query, download_method)?;
+        crate::manifest::record_input("overpass", &url, response.as_bytes());
 
         if let Some(save_file) = save_file {
             let mut file: File = File::create(save_file)?;
@@ -240,6 +326,40 @@ pub fn fetch_data_from_overpass(
     }
 }
 
+/// Fetches the landuse areas and the buildings mapped with a height in the bbox, all a
+/// tiled run needs to estimate building heights over its whole world
+pub fn fetch_building_heights_from_overpass(
+    bbox: LLBBox,
+    download_method: &str,
+) -> Result<OsmData, Box<dyn std::error::Error>> {
+    println!("Fetching building heights of the whole area...");
+    let query: String = format!(
+        r#"[out:json][timeout:360][bbox:{},{},{},{}];
+    (
+        nwr["landuse"];
+        nwr["building"]["building:levels"];
+        nwr["building"]["height"];
+    )->.relsinbbox;
+    (
+        way(r.relsinbbox);
+    )->.waysinbbox;
+    (
+        node(w.waysinbbox);
+        node(w.relsinbbox);
+    )->.nodesinbbox;
+    .relsinbbox out body;
+    .waysinbbox out body;
+    .nodesinbbox out skel qt;"#,
+        bbox.min().lat(),
+        bbox.min().lng(),
+        bbox.max().lat(),
+        bbox.max().lng(),
+    );
+    let (url, response) = download_from_mirrors(&query, download_method)?;
+    crate::manifest::record_input("overpass-heights", &url, response.as_bytes());
+    Ok(serde_json::from_str(&response)?)
+}
+
 /// Fetches a short area name using Nominatim for the given lat/lon
 pub fn fetch_area_name(lat: f64, lon: f64) -> Result<Option<String>, Box<dyn std::error::Error>> {
     let client = Client::builder().timeout(Duration::from_secs(20)).build()?;
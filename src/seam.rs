@@ -0,0 +1,225 @@
+//! Matching terrain along the shared border of adjacent exports (`--seam-export`,
+//! `--seam-from`).
+//!
+//! Adjacent bounding boxes are generated independently, so their terrain rarely meets at
+//! the same Y where the worlds are stitched together. A run can export its border rows and
+//! sea level as a sidecar file; the neighbouring run pins its own border to those values and
+//! blends back into its own terrain over a few blocks, so both sides match exactly at the
+//! seam. Both runs should use `--elevation-mode absolute` to share one height mapping.
+
+use crate::coordinate_system::geographic::LLBBox;
+use crate::elevation_data::ElevationData;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Bbox edges closer than this (degrees, about a meter) are treated as shared
+const EDGE_TOLERANCE_DEG: f64 = 1e-5;
+/// Cells over which the pinned border blends into the run's own terrain
+const SEAM_BLEND: usize = 16;
+
+/// Border rows of an export, written next to the world for the neighbouring run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeamSidecar {
+    /// min_lat, min_lng, max_lat, max_lng
+    pub bbox: [f64; 4],
+    pub sea_level_y: Option<i32>,
+    /// Heights along each border, west to east for north/south, north to south for west/east
+    pub north: Vec<i32>,
+    pub south: Vec<i32>,
+    pub west: Vec<i32>,
+    pub east: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    North,
+    South,
+    West,
+    East,
+}
+
+fn bbox_array(bbox: &LLBBox) -> [f64; 4] {
+    [
+        bbox.min().lat(),
+        bbox.min().lng(),
+        bbox.max().lat(),
+        bbox.max().lng(),
+    ]
+}
+
+impl SeamSidecar {
+    pub fn from_elevation(bbox: &LLBBox, data: &ElevationData) -> Self {
+        let heights = &data.heights;
+        Self {
+            bbox: bbox_array(bbox),
+            sea_level_y: data.sea_level_y,
+            north: heights.first().cloned().unwrap_or_default(),
+            south: heights.last().cloned().unwrap_or_default(),
+            west: heights
+                .iter()
+                .filter_map(|row| row.first().copied())
+                .collect(),
+            east: heights
+                .iter()
+                .filter_map(|row| row.last().copied())
+                .collect(),
+        }
+    }
+}
+
+pub fn write_sidecar(path: &Path, sidecar: &SeamSidecar) -> Result<(), String> {
+    let json = serde_json::to_string(sidecar)
+        .map_err(|e| format!("Failed to serialize seam sidecar: {e}"))?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+pub fn read_sidecar(path: &Path) -> Result<SeamSidecar, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    serde_json::from_str(&text).map_err(|e| format!("Invalid seam sidecar {}: {e}", path.display()))
+}
+
+/// Edge of `bbox` that the neighbour's bbox borders, with the neighbour's heights along it
+fn shared_edge<'a>(bbox: &[f64; 4], neighbour: &'a SeamSidecar) -> Option<(Edge, &'a [i32])> {
+    let [min_lat, min_lng, max_lat, max_lng] = *bbox;
+    let [n_min_lat, n_min_lng, n_max_lat, n_max_lng] = neighbour.bbox;
+    let near = |a: f64, b: f64| (a - b).abs() < EDGE_TOLERANCE_DEG;
+    let lng_overlap = n_min_lng < max_lng && n_max_lng > min_lng;
+    let lat_overlap = n_min_lat < max_lat && n_max_lat > min_lat;
+
+    if near(n_min_lat, max_lat) && lng_overlap {
+        Some((Edge::North, &neighbour.south))
+    } else if near(n_max_lat, min_lat) && lng_overlap {
+        Some((Edge::South, &neighbour.north))
+    } else if near(n_max_lng, min_lng) && lat_overlap {
+        Some((Edge::West, &neighbour.east))
+    } else if near(n_min_lng, max_lng) && lat_overlap {
+        Some((Edge::East, &neighbour.west))
+    } else {
+        None
+    }
+}
+
+/// Neighbour height at a fraction `t` (0..1) of our edge, or `None` past the neighbour's end.
+/// `ours` and `theirs` are the (start, end) coordinates of the two edges.
+fn sample_edge(values: &[i32], t: f64, ours: (f64, f64), theirs: (f64, f64)) -> Option<i32> {
+    if values.is_empty() || theirs.1 == theirs.0 {
+        return None;
+    }
+    let coord = ours.0 + t * (ours.1 - ours.0);
+    let u = (coord - theirs.0) / (theirs.1 - theirs.0);
+    if !(-1e-9..=1.0 + 1e-9).contains(&u) {
+        return None;
+    }
+    let index = (u.clamp(0.0, 1.0) * (values.len() - 1) as f64).round() as usize;
+    values.get(index).copied()
+}
+
+/// Pins the border of `data` that `bbox` shares with the neighbour to the neighbour's
+/// heights and takes over its sea level. Returns the pinned edge, if the boxes touch.
+pub fn pin_to_neighbour(
+    data: &mut ElevationData,
+    bbox: &LLBBox,
+    neighbour: &SeamSidecar,
+) -> Option<Edge> {
+    let bbox = bbox_array(bbox);
+    let (edge, values) = shared_edge(&bbox, neighbour)?;
+    let (w, h) = (data.width, data.height);
+    if w == 0 || h == 0 {
+        return None;
+    }
+    let [min_lat, min_lng, max_lat, max_lng] = bbox;
+    let [n_min_lat, n_min_lng, n_max_lat, n_max_lng] = neighbour.bbox;
+
+    // Cells along the edge, the edge's coordinate span and how far the blend reaches inwards
+    let (along, ours, theirs, depth) = match edge {
+        Edge::North | Edge::South => (w, (min_lng, max_lng), (n_min_lng, n_max_lng), h),
+        // Rows run north to south
+        Edge::West | Edge::East => (h, (max_lat, min_lat), (n_max_lat, n_min_lat), w),
+    };
+    let blend = SEAM_BLEND.min(depth);
+    for i in 0..along {
+        let t = if along > 1 {
+            i as f64 / (along - 1) as f64
+        } else {
+            0.0
+        };
+        let Some(pinned) = sample_edge(values, t, ours, theirs) else {
+            continue;
+        };
+        for d in 0..blend {
+            let (x, z) = match edge {
+                Edge::North => (i, d),
+                Edge::South => (i, h - 1 - d),
+                Edge::West => (d, i),
+                Edge::East => (w - 1 - d, i),
+            };
+            let weight = d as f64 / blend as f64;
+            let own = data.heights[z][x] as f64;
+            data.heights[z][x] = (pinned as f64 * (1.0 - weight) + own * weight).round() as i32;
+        }
+    }
+
+    if neighbour.sea_level_y.is_some() && data.sea_level_y.is_some() {
+        data.sea_level_y = neighbour.sea_level_y;
+    }
+    Some(edge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(size: usize, y: i32) -> ElevationData {
+        ElevationData {
+            heights: vec![vec![y; size]; size],
+            width: size,
+            height: size,
+            sea_level_y: Some(62),
+            carved_sea: None,
+            coastal_cliffs: None,
+            measured: None,
+        }
+    }
+
+    #[test]
+    fn pins_the_shared_border_and_blends_inwards() {
+        let bbox = LLBBox::new(55.0, 12.0, 55.01, 12.01).unwrap();
+        let north = LLBBox::new(55.01, 12.0, 55.02, 12.01).unwrap();
+        let mut neighbour = SeamSidecar::from_elevation(&north, &flat(32, 80));
+        neighbour.sea_level_y = Some(63);
+
+        let mut data = flat(32, 70);
+        assert_eq!(
+            pin_to_neighbour(&mut data, &bbox, &neighbour),
+            Some(Edge::North)
+        );
+        assert!(data.heights[0].iter().all(|&y| y == 80));
+        assert_eq!(data.heights[8][5], 75);
+        assert_eq!(data.heights[SEAM_BLEND][5], 70);
+        assert_eq!(data.heights[31][5], 70);
+        assert_eq!(data.sea_level_y, Some(63));
+    }
+
+    #[test]
+    fn ignores_boxes_that_do_not_touch() {
+        let bbox = LLBBox::new(55.0, 12.0, 55.01, 12.01).unwrap();
+        let apart = LLBBox::new(55.02, 12.0, 55.03, 12.01).unwrap();
+        let neighbour = SeamSidecar::from_elevation(&apart, &flat(8, 80));
+        let mut data = flat(8, 70);
+        assert_eq!(pin_to_neighbour(&mut data, &bbox, &neighbour), None);
+        assert_eq!(data.heights[0][0], 70);
+    }
+
+    #[test]
+    fn sidecar_edges_follow_the_grid() {
+        let bbox = LLBBox::new(55.0, 12.0, 55.01, 12.01).unwrap();
+        let mut data = flat(3, 0);
+        data.heights = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let sidecar = SeamSidecar::from_elevation(&bbox, &data);
+        assert_eq!(sidecar.north, vec![1, 2, 3]);
+        assert_eq!(sidecar.south, vec![7, 8, 9]);
+        assert_eq!(sidecar.west, vec![1, 4, 7]);
+        assert_eq!(sidecar.east, vec![3, 6, 9]);
+    }
+}
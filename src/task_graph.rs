@@ -0,0 +1,186 @@
+//! The downloads of a run as one task graph.
+//!
+//! Each download is a task that may wait for other tasks to finish first, such as two
+//! downloads from a service that should not be asked twice at once. The graph runs on a
+//! fixed number of worker threads in the background, and every task hands back a
+//! [`Pending`] that is joined where its data is needed.
+
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Worker threads a graph runs on. The tasks mostly wait on the network, so this is about
+/// how many services are asked at once rather than about cores.
+pub const DOWNLOAD_WORKERS: usize = 6;
+
+/// The result of a task, once it has run
+pub struct Pending<T>(Receiver<Result<T, String>>);
+
+impl<T> Pending<T> {
+    /// Waits for the task to finish.
+    pub fn join(self) -> Result<T, String> {
+        self.0
+            .recv()
+            .unwrap_or_else(|_| Err("the download task never ran".to_string()))
+    }
+}
+
+/// A task added to a [`TaskGraph`], for later tasks to wait on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskId(usize);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Task {
+    name: &'static str,
+    job: Job,
+    after: Vec<usize>,
+}
+
+/// Tasks that have not run yet, and what each waits for
+#[derive(Default)]
+pub struct TaskGraph {
+    tasks: Vec<Task>,
+}
+
+impl TaskGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a task that runs once the tasks in `after` have finished, whatever their
+    /// result. A task can only wait for tasks added before it, so the graph has no cycles.
+    pub fn add<T: Send + 'static>(
+        &mut self,
+        name: &'static str,
+        after: &[TaskId],
+        fetch: impl FnOnce() -> Result<T, String> + Send + 'static,
+    ) -> (TaskId, Pending<T>) {
+        let (sender, receiver) = mpsc::channel();
+        let job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(fetch))
+                .unwrap_or_else(|_| Err(format!("the {name} download panicked")));
+            // Nobody waiting on the result is fine: the data was not needed after all
+            let _ = sender.send(result);
+        });
+        let id = self.tasks.len();
+        self.tasks.push(Task {
+            name,
+            job,
+            after: after.iter().map(|task| task.0).collect(),
+        });
+        (TaskId(id), Pending(receiver))
+    }
+
+    /// Names of the tasks in the order they can start on a single worker
+    pub fn order(&self) -> Vec<&'static str> {
+        let mut order = Vec::new();
+        let mut state = Schedule::new(&self.tasks);
+        while let Some(task) = state.ready.pop_front() {
+            order.push(self.tasks[task].name);
+            state.finish(task);
+        }
+        order
+    }
+
+    /// Starts the tasks on `workers` background threads and returns at once; the results
+    /// arrive through the tasks' [`Pending`]s.
+    pub fn run(self, workers: usize) {
+        if self.tasks.is_empty() {
+            return;
+        }
+        let workers = workers.clamp(1, self.tasks.len());
+        let schedule = Schedule::new(&self.tasks);
+        let jobs: Vec<Option<Job>> = self.tasks.into_iter().map(|task| Some(task.job)).collect();
+        let shared = Arc::new((Mutex::new((schedule, jobs)), Condvar::new()));
+        for _ in 0..workers {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                let (state, ready) = &*shared;
+                loop {
+                    let (task, job) = {
+                        let mut guard = state.lock().unwrap();
+                        loop {
+                            let (schedule, jobs) = &mut *guard;
+                            if let Some(task) = schedule.ready.pop_front() {
+                                break (task, jobs[task].take());
+                            }
+                            if schedule.left == 0 {
+                                return;
+                            }
+                            guard = ready.wait(guard).unwrap();
+                        }
+                    };
+                    if let Some(job) = job {
+                        job();
+                    }
+                    state.lock().unwrap().0.finish(task);
+                    ready.notify_all();
+                }
+            });
+        }
+    }
+}
+
+/// Which tasks can start, and how many each is still waiting for
+struct Schedule {
+    ready: VecDeque<usize>,
+    waiting_for: Vec<usize>,
+    dependents: Vec<Vec<usize>>,
+    /// Tasks that have not finished
+    left: usize,
+}
+
+impl Schedule {
+    fn new(tasks: &[Task]) -> Self {
+        let mut dependents = vec![Vec::new(); tasks.len()];
+        for (task, Task { after, .. }) in tasks.iter().enumerate() {
+            for &before in after {
+                dependents[before].push(task);
+            }
+        }
+        let waiting_for: Vec<usize> = tasks.iter().map(|task| task.after.len()).collect();
+        Self {
+            ready: (0..tasks.len()).filter(|&t| waiting_for[t] == 0).collect(),
+            waiting_for,
+            dependents,
+            left: tasks.len(),
+        }
+    }
+
+    fn finish(&mut self, task: usize) {
+        self.left -= 1;
+        for &dependent in &self.dependents[task] {
+            self.waiting_for[dependent] -= 1;
+            if self.waiting_for[dependent] == 0 {
+                self.ready.push_back(dependent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_wait_for_the_tasks_they_follow() {
+        let mut graph = TaskGraph::new();
+        let (coast, coast_data) = graph.add("coast", &[], || Ok(1));
+        let (_, roads_data) = graph.add("roads", &[coast], || Ok(2));
+        let (_, failing) = graph.add::<u8>("nature", &[], || Err("offline".to_string()));
+        let (_, panicking) = graph.add::<u8>("crops", &[], || panic!("bad data"));
+        assert_eq!(graph.order(), ["coast", "nature", "crops", "roads"]);
+
+        graph.run(DOWNLOAD_WORKERS);
+        assert_eq!(roads_data.join(), Ok(2));
+        assert_eq!(coast_data.join(), Ok(1));
+        assert_eq!(failing.join(), Err("offline".to_string()));
+        assert_eq!(
+            panicking.join(),
+            Err("the crops download panicked".to_string())
+        );
+    }
+}
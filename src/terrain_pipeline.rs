@@ -0,0 +1,390 @@
+//! Post-processing of the DHM height grid before it is mapped to Minecraft Y.
+//!
+//! Each step is a [`TerrainPass`] over the grid of heights in meters, and the DHM path
+//! runs them as a [`TerrainPipeline`]: smoothing, putting back unsmoothed cliff faces,
+//! keeping carved sea flats flat and a slope analysis of the result. Passes that touch
+//! every cell work on rows in parallel with rayon.
+
+use crate::progress::{emit_gui_progress_update, is_running_with_gui};
+use crate::progress_aggregator::ProgressAggregator;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+/// A post-processing step over the height grid (meters, row-major).
+pub trait TerrainPass {
+    fn name(&self) -> &'static str;
+
+    /// Processes the grid in place. Returns a summary worth logging, if any.
+    fn apply(&self, grid: &mut Vec<Vec<f64>>) -> Option<String>;
+}
+
+/// Passes run in order over the height grid.
+#[derive(Default)]
+pub struct TerrainPipeline<'a> {
+    passes: Vec<Box<dyn TerrainPass + 'a>>,
+}
+
+impl<'a> TerrainPipeline<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, pass: impl TerrainPass + 'a) {
+        self.passes.push(Box::new(pass));
+    }
+
+    pub fn run(self, mut grid: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        for pass in &self.passes {
+            if let Some(summary) = pass.apply(&mut grid) {
+                println!("{}: {summary}", pass.name());
+            }
+        }
+        grid
+    }
+}
+
+/// Separable Gaussian blur with the given sigma in cells.
+pub struct GaussianBlur {
+    pub sigma: f64,
+}
+
+impl TerrainPass for GaussianBlur {
+    fn name(&self) -> &'static str {
+        "Smoothing"
+    }
+
+    fn apply(&self, grid: &mut Vec<Vec<f64>>) -> Option<String> {
+        println!("Smoothing DHM terrain (sigma={:.1})...", self.sigma);
+        emit_gui_progress_update(16.0, "Smoothing DHM terrain... 0%");
+        *grid = gaussian_blur(grid, self.sigma);
+        emit_gui_progress_update(19.5, "Smoothing DHM terrain... 100%");
+        None
+    }
+}
+
+/// Puts unsmoothed heights back for the given cells, e.g. coastal cliff faces.
+pub struct RestoreUnsmoothed {
+    /// (x, z, height) of the cells to restore
+    pub cells: Vec<(usize, usize, f64)>,
+    /// Cells over which the restored heights blend into the smoothed terrain
+    pub blend: usize,
+}
+
+impl TerrainPass for RestoreUnsmoothed {
+    fn name(&self) -> &'static str {
+        "Unsmoothed faces"
+    }
+
+    fn apply(&self, grid: &mut Vec<Vec<f64>>) -> Option<String> {
+        restore_unsmoothed(grid, &self.cells, self.blend);
+        Some(format!("restored {} cells", self.cells.len()))
+    }
+}
+
+/// Caps the masked cells at a height, so smoothing does not lift them (e.g. carved sea).
+pub struct FlattenMask<'a> {
+    pub mask: &'a [Vec<bool>],
+    pub max_height: f64,
+}
+
+impl TerrainPass for FlattenMask<'_> {
+    fn name(&self) -> &'static str {
+        "Flattening"
+    }
+
+    fn apply(&self, grid: &mut Vec<Vec<f64>>) -> Option<String> {
+        let max_height = self.max_height;
+        let flattened: usize = grid
+            .par_iter_mut()
+            .zip(self.mask.par_iter())
+            .map(|(row, mask_row)| {
+                let mut flattened = 0;
+                for (h, _) in row.iter_mut().zip(mask_row).filter(|(_, &masked)| masked) {
+                    if *h > max_height {
+                        *h = max_height;
+                        flattened += 1;
+                    }
+                }
+                flattened
+            })
+            .sum();
+        (flattened > 0).then(|| format!("{flattened} masked cells kept at {max_height}m"))
+    }
+}
+
+/// Reports how steep the processed terrain is; leaves the grid unchanged.
+pub struct SlopeAnalysis {
+    /// Meters per grid cell
+    pub cell_size: f64,
+    /// Slopes (degrees) counted as steep
+    pub steep_deg: f64,
+}
+
+impl SlopeAnalysis {
+    /// Slope in degrees of every cell, from central differences
+    fn slopes(&self, grid: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let h = grid.len();
+        (0..h)
+            .into_par_iter()
+            .map(|z| {
+                let w = grid[z].len();
+                (0..w)
+                    .map(|x| {
+                        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(w - 1));
+                        let (z0, z1) = (z.saturating_sub(1), (z + 1).min(h - 1));
+                        let gx = (grid[z][x1] - grid[z][x0])
+                            / ((x1 - x0).max(1) as f64 * self.cell_size);
+                        let gz = (grid[z1][x] - grid[z0][x])
+                            / ((z1 - z0).max(1) as f64 * self.cell_size);
+                        gx.hypot(gz).atan().to_degrees()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl TerrainPass for SlopeAnalysis {
+    fn name(&self) -> &'static str {
+        "Slope analysis"
+    }
+
+    fn apply(&self, grid: &mut Vec<Vec<f64>>) -> Option<String> {
+        let slopes = self.slopes(grid);
+        let cells = slopes.iter().map(Vec::len).sum::<usize>();
+        if cells == 0 {
+            return None;
+        }
+        let (steepest, steep) = slopes
+            .par_iter()
+            .map(|row| {
+                row.iter().fold((0.0f64, 0usize), |(max, steep), &deg| {
+                    (max.max(deg), steep + usize::from(deg >= self.steep_deg))
+                })
+            })
+            .reduce(|| (0.0, 0), |(m1, s1), (m2, s2)| (m1.max(m2), s1 + s2));
+        Some(format!(
+            "steepest {steepest:.0}°, {:.1}% of cells steeper than {:.0}°",
+            steep as f64 * 100.0 / cells as f64,
+            self.steep_deg
+        ))
+    }
+}
+
+/// Puts the unsmoothed heights of cliffs back after smoothing, blending into the smoothed
+/// terrain over `blend` cells so the face stays sheer without a step at its edge.
+fn restore_unsmoothed(grid: &mut [Vec<f64>], unsmoothed: &[(usize, usize, f64)], blend: usize) {
+    let h = grid.len();
+    let w = grid.first().map_or(0, Vec::len);
+    let mut distance: HashMap<(usize, usize), usize> =
+        unsmoothed.iter().map(|&(x, z, _)| ((x, z), 0)).collect();
+    let mut queue: VecDeque<(usize, usize)> = unsmoothed.iter().map(|&(x, z, _)| (x, z)).collect();
+    let mut raw: HashMap<(usize, usize), f64> = unsmoothed
+        .iter()
+        .map(|&(x, z, height)| ((x, z), height))
+        .collect();
+    while let Some((x, z)) = queue.pop_front() {
+        let d = distance[&(x, z)];
+        if d >= blend {
+            continue;
+        }
+        let source = raw[&(x, z)];
+        let neighbours = [
+            (x.wrapping_sub(1), z),
+            (x + 1, z),
+            (x, z.wrapping_sub(1)),
+            (x, z + 1),
+        ];
+        for (nx, nz) in neighbours {
+            if nx < w && nz < h && !distance.contains_key(&(nx, nz)) {
+                distance.insert((nx, nz), d + 1);
+                // Blend cells ramp from the nearest unsmoothed height to the smoothed one
+                raw.insert((nx, nz), source);
+                queue.push_back((nx, nz));
+            }
+        }
+    }
+    for ((x, z), d) in distance {
+        let t = d as f64 / (blend.max(1) as f64);
+        grid[z][x] = raw[&(x, z)] * (1.0 - t) + grid[z][x] * t;
+    }
+}
+
+fn report_smoothing_progress(
+    progress: &ProgressAggregator,
+    stage: usize,
+    progress_bar: Option<&ProgressBar>,
+) {
+    if let Some(progress_bar) = progress_bar {
+        progress_bar.inc(1);
+    }
+    if let Some(percent) = progress.advance(stage, 1) {
+        if !is_running_with_gui() && percent > 0 && percent < 100 && percent.is_multiple_of(10) {
+            println!("Smoothing DHM terrain... {percent}%");
+        }
+    }
+}
+
+fn gaussian_blur(grid: &[Vec<f64>], sigma: f64) -> Vec<Vec<f64>> {
+    let h = grid.len();
+    if h == 0 {
+        return vec![];
+    }
+    let w = grid[0].len();
+
+    let radius = (sigma * 3.0).ceil() as usize;
+    let kernel_size = radius * 2 + 1;
+    let mut kernel = vec![0.0f64; kernel_size];
+    let mut sum = 0.0;
+    for (i, value) in kernel.iter_mut().enumerate() {
+        let x = i as f64 - radius as f64;
+        *value = (-x * x / (2.0 * sigma * sigma)).exp();
+        sum += *value;
+    }
+    for value in &mut kernel {
+        *value /= sum;
+    }
+
+    // One unit per row for each of the two passes, which cost about the same
+    let total_units = 2 * h;
+    let mut progress = ProgressAggregator::new(16.0, 19.5, "Smoothing DHM terrain...")
+        .show_percent()
+        .step(2);
+    let horizontal_stage = progress.stage(1.0, h as u64);
+    let vertical_stage = progress.stage(1.0, h as u64);
+    let progress_bar = if is_running_with_gui() {
+        None
+    } else {
+        let progress_bar = ProgressBar::new(total_units as u64);
+        progress_bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:45.cyan/blue}] {pos}/{len} smoothing units ({percent}%)")
+                .unwrap()
+                .progress_chars("#> "),
+        );
+        progress_bar.set_message("Smoothing DHM terrain");
+        Some(progress_bar)
+    };
+
+    let temp: Vec<Vec<f64>> = grid
+        .par_iter()
+        .map(|row| {
+            let mut temp_row = vec![0.0f64; w];
+            for (x, value) in temp_row.iter_mut().enumerate() {
+                let mut acc = 0.0;
+                for (k, kernel_value) in kernel.iter().enumerate() {
+                    let sx = (x as isize + k as isize - radius as isize).clamp(0, w as isize - 1)
+                        as usize;
+                    acc += row[sx] * kernel_value;
+                }
+                *value = acc;
+            }
+
+            report_smoothing_progress(&progress, horizontal_stage, progress_bar.as_ref());
+            temp_row
+        })
+        .collect();
+
+    // Vertical pass row by row, so reads stay contiguous and no transpose is needed
+    let result: Vec<Vec<f64>> = (0..h)
+        .into_par_iter()
+        .map(|y| {
+            let mut blurred_row = vec![0.0f64; w];
+            for (k, kernel_value) in kernel.iter().enumerate() {
+                let sy =
+                    (y as isize + k as isize - radius as isize).clamp(0, h as isize - 1) as usize;
+                for (value, &source) in blurred_row.iter_mut().zip(&temp[sy]) {
+                    *value += source * kernel_value;
+                }
+            }
+
+            report_smoothing_progress(&progress, vertical_stage, progress_bar.as_ref());
+            blurred_row
+        })
+        .collect();
+
+    if let Some(progress_bar) = &progress_bar {
+        progress_bar.finish_with_message("Smoothing DHM terrain... done");
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaussian_blur_spreads_an_impulse_symmetrically() {
+        let mut grid = vec![vec![0.0; 21]; 15];
+        grid[7][10] = 1.0;
+
+        let mut blurred = grid.clone();
+        GaussianBlur { sigma: 1.5 }.apply(&mut blurred);
+
+        let total: f64 = blurred.iter().flatten().sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!((blurred[7][8] - blurred[7][12]).abs() < 1e-12);
+        assert!((blurred[5][10] - blurred[9][10]).abs() < 1e-12);
+        assert!((blurred[7][8] - blurred[5][10]).abs() < 1e-12);
+        assert!(blurred[7][10] > blurred[7][11]);
+    }
+
+    #[test]
+    fn restored_cells_blend_into_the_smoothed_terrain() {
+        let mut grid = vec![vec![10.0; 9]; 3];
+        let pass = RestoreUnsmoothed {
+            cells: vec![(0, 1, 30.0)],
+            blend: 4,
+        };
+        pass.apply(&mut grid);
+        assert_eq!(grid[1][0], 30.0);
+        assert_eq!(grid[1][2], 20.0);
+        assert_eq!(grid[1][4], 10.0);
+        assert_eq!(grid[1][8], 10.0);
+    }
+
+    #[test]
+    fn flattening_caps_only_masked_cells() {
+        let mut grid = vec![vec![0.0, 2.0, 2.0]];
+        let mask = vec![vec![true, true, false]];
+        let pass = FlattenMask {
+            mask: &mask,
+            max_height: 0.3,
+        };
+        assert!(pass.apply(&mut grid).is_some());
+        assert_eq!(grid, vec![vec![0.0, 0.3, 2.0]]);
+    }
+
+    #[test]
+    fn slope_analysis_measures_grades() {
+        // 1m rise per 1m cell is 45 degrees
+        let grid: Vec<Vec<f64>> = (0..4).map(|_| (0..4).map(|x| x as f64).collect()).collect();
+        let slopes = SlopeAnalysis {
+            cell_size: 1.0,
+            steep_deg: 40.0,
+        }
+        .slopes(&grid);
+        assert!(slopes
+            .iter()
+            .flatten()
+            .all(|&deg| (deg - 45.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn pipeline_runs_passes_in_order() {
+        let mask = vec![vec![true; 5]; 5];
+        let mut grid = vec![vec![0.0; 5]; 5];
+        grid[2][2] = 10.0;
+        let mut pipeline = TerrainPipeline::new();
+        pipeline.push(GaussianBlur { sigma: 1.0 });
+        pipeline.push(FlattenMask {
+            mask: &mask,
+            max_height: 1.0,
+        });
+        let grid = pipeline.run(grid);
+        assert!(grid.iter().flatten().all(|&h| h <= 1.0));
+        assert!(grid[2][1] > 0.0, "blurred before flattening");
+    }
+}
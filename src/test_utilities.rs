@@ -1,13 +1,15 @@
 use crate::coordinate_system::cartesian::XZBBox;
 use crate::coordinate_system::geographic::LLBBox;
 use crate::osm_parser;
-use crate::osm_parser::ProcessedElement;
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
 use crate::retrieve_data;
+use std::collections::HashMap;
 
 // this is copied from main.rs
 pub fn generate_example(llbbox: LLBBox) -> (XZBBox, Vec<ProcessedElement>) {
     // Fetch data
-    let raw_data = retrieve_data::fetch_data_from_overpass(llbbox, false, "requests", None)
+    let layers = retrieve_data::OptionalLayers::default();
+    let raw_data = retrieve_data::fetch_data_from_overpass(llbbox, layers, false, "requests", None)
         .expect("Failed to fetch data");
 
     // Parse raw data
@@ -26,3 +28,36 @@ pub fn generate_default_example() -> (XZBBox, Vec<ProcessedElement>) {
 pub fn get_llbbox_arnis() -> LLBBox {
     LLBBox::new(54.627053, 9.927928, 54.634902, 9.937563).unwrap()
 }
+
+/// Element tags from (key, value) pairs
+pub fn tags(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// A node at (`x`, `z`) with tags from (key, value) pairs
+pub fn node(id: u64, x: i32, z: i32, pairs: &[(&str, &str)]) -> ProcessedNode {
+    ProcessedNode {
+        id,
+        tags: tags(pairs),
+        x,
+        z,
+        exact: None,
+    }
+}
+
+/// A way through `points` with tags from (key, value) pairs. Its nodes are numbered from
+/// 100 times the way's id.
+pub fn way(id: u64, pairs: &[(&str, &str)], points: &[(i32, i32)]) -> ProcessedElement {
+    ProcessedElement::Way(ProcessedWay {
+        id,
+        nodes: points
+            .iter()
+            .enumerate()
+            .map(|(i, &(x, z))| node(id * 100 + i as u64, x, z, &[]))
+            .collect(),
+        tags: tags(pairs),
+    })
+}
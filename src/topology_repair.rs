@@ -0,0 +1,250 @@
+//! Geometry cleaning for broken OSM area polygons before rasterization.
+//!
+//! Malformed areas are common in OSM: rings that stop a few blocks short of their start,
+//! nodes doubled at the same position, and outlines that cross themselves (bow-ties, folded
+//! corners). Flood fill and wall tracing assume a simple closed ring, so such areas leak
+//! fills or leave gaps. This pass repairs what can be repaired safely and reports the
+//! element IDs it touched:
+//!
+//! - Duplicate nodes: consecutive nodes at the same position are merged, keeping tagged ones
+//! - Unclosed rings: rings whose ends lie within `CLOSE_TOLERANCE` blocks are closed
+//! - Self-intersections: crossing edges are uncrossed by reversing the ring section between
+//!   them, which keeps every node and always shortens the outline, so it terminates
+
+use crate::floodfill_cache::FloodFillCache;
+use crate::osm_parser::{ProcessedElement, ProcessedNode, ProcessedWay};
+
+/// Open rings whose ends are at most this many blocks apart are closed
+const CLOSE_TOLERANCE: i64 = 4;
+/// Rings with more nodes than this are not checked for self-intersections (quadratic)
+const MAX_UNTANGLE_NODES: usize = 2000;
+/// Uncrossing passes per ring before giving up on it
+const MAX_UNTANGLE_PASSES: usize = 64;
+
+/// `natural=*` values mapped as lines, which must never be closed into rings
+const LINEAR_NATURAL: &[&str] = &[
+    "cliff",
+    "ridge",
+    "arete",
+    "tree_row",
+    "valley",
+    "gorge",
+    "earth_bank",
+];
+
+/// Element IDs of the repaired ways, per kind of repair
+#[derive(Debug, Default, PartialEq)]
+pub struct RepairReport {
+    pub deduplicated: Vec<u64>,
+    pub closed: Vec<u64>,
+    pub untangled: Vec<u64>,
+}
+
+impl RepairReport {
+    pub fn is_empty(&self) -> bool {
+        self.deduplicated.is_empty() && self.closed.is_empty() && self.untangled.is_empty()
+    }
+}
+
+fn is_area(way: &ProcessedWay) -> bool {
+    FloodFillCache::way_needs_flood_fill(way)
+        && !way
+            .tags
+            .get("natural")
+            .is_some_and(|natural| LINEAR_NATURAL.contains(&natural.as_str()))
+}
+
+/// Repairs the area ways in `elements`. Relation members are left alone, they are open ring
+/// segments until the relation is assembled.
+pub fn repair_polygons(elements: &mut [ProcessedElement]) -> RepairReport {
+    let mut report = RepairReport::default();
+    for element in elements {
+        let ProcessedElement::Way(way) = element else {
+            continue;
+        };
+        if !is_area(way) {
+            continue;
+        }
+        if remove_duplicate_nodes(&mut way.nodes) {
+            report.deduplicated.push(way.id);
+        }
+        if close_ring(&mut way.nodes) {
+            report.closed.push(way.id);
+        }
+        if untangle_ring(&mut way.nodes) {
+            report.untangled.push(way.id);
+        }
+    }
+    report
+}
+
+fn same_position(a: &ProcessedNode, b: &ProcessedNode) -> bool {
+    a.x == b.x && a.z == b.z
+}
+
+/// Merges consecutive nodes at the same position. Of two coincident nodes the tagged one
+/// is kept; two tagged nodes both stay. Returns whether any node was removed.
+fn remove_duplicate_nodes(nodes: &mut Vec<ProcessedNode>) -> bool {
+    let before = nodes.len();
+    let mut kept: Vec<ProcessedNode> = Vec::with_capacity(nodes.len());
+    for node in nodes.drain(..) {
+        match kept.last_mut() {
+            Some(last) if same_position(last, &node) => {
+                if last.tags.is_empty() {
+                    *last = node;
+                } else if !node.tags.is_empty() {
+                    kept.push(node);
+                }
+            }
+            _ => kept.push(node),
+        }
+    }
+    *nodes = kept;
+    nodes.len() != before
+}
+
+/// Closes a ring whose ends are within `CLOSE_TOLERANCE`. Returns whether it was closed.
+fn close_ring(nodes: &mut Vec<ProcessedNode>) -> bool {
+    if nodes.len() < 3 {
+        return false;
+    }
+    let (first, last) = (&nodes[0], &nodes[nodes.len() - 1]);
+    if same_position(first, last) {
+        return false;
+    }
+    let (dx, dz) = ((first.x - last.x) as i64, (first.z - last.z) as i64);
+    if dx * dx + dz * dz > CLOSE_TOLERANCE * CLOSE_TOLERANCE {
+        return false;
+    }
+    nodes.push(nodes[0].clone());
+    true
+}
+
+fn orientation(a: &ProcessedNode, b: &ProcessedNode, c: &ProcessedNode) -> i64 {
+    let cross = (b.x - a.x) as i64 * (c.z - a.z) as i64 - (b.z - a.z) as i64 * (c.x - a.x) as i64;
+    cross.signum()
+}
+
+/// Whether segments `a`-`b` and `c`-`d` cross at a point inside both of them
+fn segments_cross(
+    a: &ProcessedNode,
+    b: &ProcessedNode,
+    c: &ProcessedNode,
+    d: &ProcessedNode,
+) -> bool {
+    let (o1, o2) = (orientation(a, b, c), orientation(a, b, d));
+    let (o3, o4) = (orientation(c, d, a), orientation(c, d, b));
+    o1 * o2 < 0 && o3 * o4 < 0
+}
+
+/// First pair of crossing edges (by start index) of a closed ring
+fn find_crossing(nodes: &[ProcessedNode]) -> Option<(usize, usize)> {
+    let edges = nodes.len() - 1;
+    for i in 0..edges {
+        // Neighbouring edges share a node and cannot cross properly
+        for j in i + 2..edges {
+            if i == 0 && j == edges - 1 {
+                continue;
+            }
+            if segments_cross(&nodes[i], &nodes[i + 1], &nodes[j], &nodes[j + 1]) {
+                return Some((i, j));
+            }
+        }
+    }
+    None
+}
+
+/// Uncrosses the edges of a closed ring. Returns whether the ring was changed.
+fn untangle_ring(nodes: &mut [ProcessedNode]) -> bool {
+    if nodes.len() < 5
+        || nodes.len() > MAX_UNTANGLE_NODES
+        || !same_position(&nodes[0], &nodes[nodes.len() - 1])
+    {
+        return false;
+    }
+    let mut changed = false;
+    for _ in 0..MAX_UNTANGLE_PASSES {
+        let Some((i, j)) = find_crossing(nodes) else {
+            break;
+        };
+        nodes[i + 1..=j].reverse();
+        changed = true;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::node;
+    use std::collections::HashMap;
+
+    fn area(id: u64, points: &[(i32, i32)], key: &str, value: &str) -> ProcessedElement {
+        ProcessedElement::Way(ProcessedWay {
+            id,
+            nodes: points
+                .iter()
+                .enumerate()
+                .map(|(i, &(x, z))| node(i as u64, x, z, &[]))
+                .collect(),
+            tags: HashMap::from([(key.to_string(), value.to_string())]),
+        })
+    }
+
+    fn points(element: &ProcessedElement) -> Vec<(i32, i32)> {
+        element.nodes().map(|n| (n.x, n.z)).collect()
+    }
+
+    #[test]
+    fn repairs_broken_area_polygons() {
+        let mut elements = vec![
+            // Bow-tie: the edges 0-1 and 2-3 cross
+            area(
+                1,
+                &[(0, 0), (10, 10), (10, 0), (0, 10), (0, 0)],
+                "landuse",
+                "farmland",
+            ),
+            // Stops two blocks short of its start, with a doubled corner
+            area(
+                2,
+                &[(0, 0), (10, 0), (10, 0), (10, 10), (0, 10), (0, 2)],
+                "building",
+                "yes",
+            ),
+            // Cliffs are lines, even when their ends are close
+            area(3, &[(0, 0), (10, 0), (10, 10), (0, 2)], "natural", "cliff"),
+        ];
+
+        let report = repair_polygons(&mut elements);
+        assert_eq!(report.untangled, vec![1]);
+        assert_eq!(report.deduplicated, vec![2]);
+        assert_eq!(report.closed, vec![2]);
+
+        assert_eq!(
+            points(&elements[0]),
+            vec![(0, 0), (10, 0), (10, 10), (0, 10), (0, 0)]
+        );
+        assert_eq!(
+            points(&elements[1]),
+            vec![(0, 0), (10, 0), (10, 10), (0, 10), (0, 2), (0, 0)]
+        );
+        assert_eq!(points(&elements[2]).len(), 4);
+    }
+
+    #[test]
+    fn keeps_tagged_nodes_when_merging_duplicates() {
+        let mut nodes = vec![
+            node(1, 0, 0, &[]),
+            node(2, 5, 0, &[]),
+            node(3, 5, 0, &[]),
+            node(4, 5, 5, &[]),
+        ];
+        nodes[2]
+            .tags
+            .insert("entrance".to_string(), "yes".to_string());
+        assert!(remove_duplicate_nodes(&mut nodes));
+        let ids: Vec<u64> = nodes.iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+    }
+}
@@ -0,0 +1,387 @@
+//! Guided tour datapack (`--tour`).
+//!
+//! Picks the area's main landmarks with simple OSM importance heuristics (landmark kind,
+//! wikidata/wikipedia links, heritage status) and writes a datapack into the Java world
+//! with one function per stop. `/function arnis_tour:start` hands out an elytra and then
+//! teleports everyone from landmark to landmark, showing each name as a title, so a class
+//! can be led through the generated town. `/function arnis_tour:stop` ends the tour early.
+//!
+//! The selected stops are kept in `tour.json` inside the datapack, so tiled generation
+//! merges the landmarks of every tile into one route. [`Datapack`] writes the datapacks of
+//! the other features too.
+
+use crate::osm_parser::ProcessedElement;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of stops on a tour
+const MAX_STOPS: usize = 12;
+/// Landmarks closer than this (blocks) to a more important one are skipped
+const MIN_STOP_SPACING: i32 = 48;
+/// Seconds spent at every stop before flying on
+const STOP_SECONDS: u32 = 15;
+/// The camera hovers this far south of and above a landmark
+const VIEW_DISTANCE: i32 = 24;
+const VIEW_HEIGHT: i32 = 30;
+/// Datapack format of Minecraft 1.21.4, the version of the world template
+const PACK_FORMAT: u32 = 61;
+const NAMESPACE: &str = "arnis_tour";
+
+/// A datapack in a Java world, with its functions in the namespace it is named after
+pub struct Datapack {
+    pub dir: PathBuf,
+    namespace: &'static str,
+}
+
+impl Datapack {
+    /// The datapack `namespace` in a Java world, which may not have been written yet
+    pub fn new(world_dir: &Path, namespace: &'static str) -> Self {
+        Self {
+            dir: world_dir.join("datapacks").join(namespace),
+            namespace,
+        }
+    }
+
+    fn function_dir(&self) -> PathBuf {
+        self.dir.join("data").join(self.namespace).join("function")
+    }
+
+    /// Writes `pack.mcmeta` and clears the functions of an earlier run, so a route that
+    /// got shorter leaves none behind
+    pub fn create(&self, description: &str) -> Result<(), String> {
+        let function_dir = self.function_dir();
+        if function_dir.exists() {
+            fs::remove_dir_all(&function_dir)
+                .map_err(|e| format!("Failed to clear old {} functions: {e}", self.namespace))?;
+        }
+        fs::create_dir_all(&function_dir)
+            .map_err(|e| format!("Failed to create datapack {}: {e}", self.namespace))?;
+        self.write(
+            "pack.mcmeta",
+            serde_json::json!({
+                "pack": {
+                    "pack_format": PACK_FORMAT,
+                    "description": description
+                }
+            })
+            .to_string(),
+        )
+    }
+
+    /// Writes a file at the top of the datapack
+    pub fn write(&self, name: &str, contents: String) -> Result<(), String> {
+        let path = self.dir.join(name);
+        fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+
+    /// Writes the function `namespace:name`
+    pub fn write_function(&self, name: &str, contents: String) -> Result<(), String> {
+        let path = self.function_dir().join(format!("{name}.mcfunction"));
+        fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+}
+
+/// A landmark the tour visits. `y` is the ground level at the landmark.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TourStop {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub score: i32,
+}
+
+/// Importance of a landmark kind, or 0 if the element is not a landmark
+fn kind_score(tags: &HashMap<String, String>) -> i32 {
+    let tag = |key: &str| tags.get(key).map(String::as_str);
+    let mut score = match tag("historic") {
+        Some("castle") => 5,
+        Some("monument") => 4,
+        Some("manor") | Some("church") => 3,
+        Some(_) => 2,
+        None => 0,
+    };
+    score = score.max(match tag("tourism") {
+        Some("attraction") | Some("museum") | Some("theme_park") | Some("zoo") => 3,
+        Some("viewpoint") => 2,
+        Some("artwork") => 1,
+        _ => 0,
+    });
+    score = score.max(match tag("building") {
+        Some("cathedral") | Some("castle") => 5,
+        Some("church") => 3,
+        _ => 0,
+    });
+    score = score.max(match tag("man_made") {
+        Some("lighthouse") => 4,
+        Some("windmill") => 3,
+        Some("tower") => 2,
+        _ => 0,
+    });
+    score = score.max(match tag("amenity") {
+        Some("townhall") => 3,
+        Some("place_of_worship") | Some("theatre") | Some("university") => 2,
+        Some("library") => 1,
+        _ => 0,
+    });
+    score.max(match tag("leisure") {
+        Some("stadium") => 2,
+        _ => 0,
+    })
+}
+
+fn landmark_score(tags: &HashMap<String, String>) -> i32 {
+    let score = kind_score(tags);
+    if score == 0 {
+        return 0;
+    }
+    score
+        + if tags.contains_key("wikidata") { 3 } else { 0 }
+        + if tags.contains_key("wikipedia") { 2 } else { 0 }
+        + if tags.contains_key("heritage") { 1 } else { 0 }
+}
+
+/// Picks the most important named landmarks as tour stops, unordered.
+/// `ground_level` gives the ground Y at a world coordinate.
+pub fn select_landmarks(
+    elements: &[ProcessedElement],
+    ground_level: impl Fn(i32, i32) -> i32,
+) -> Vec<TourStop> {
+    let candidates = elements.iter().filter_map(|element| {
+        let tags = element.tags();
+        let name = tags.get("name")?;
+        let score = landmark_score(tags);
+        if score == 0 {
+            return None;
+        }
+        let (x, z) = match element {
+            ProcessedElement::Node(node) => (node.x, node.z),
+            ProcessedElement::Way(way) if !way.nodes.is_empty() => {
+                let count = way.nodes.len() as i64;
+                let (sum_x, sum_z) = way.nodes.iter().fold((0i64, 0i64), |(sx, sz), node| {
+                    (sx + node.x as i64, sz + node.z as i64)
+                });
+                ((sum_x / count) as i32, (sum_z / count) as i32)
+            }
+            _ => return None,
+        };
+        Some(TourStop {
+            name: name.clone(),
+            x,
+            y: ground_level(x, z),
+            z,
+            score,
+        })
+    });
+    pick_stops(candidates.collect())
+}
+
+/// Keeps the best spaced-out stops, one per name
+fn pick_stops(mut candidates: Vec<TourStop>) -> Vec<TourStop> {
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+
+    let mut stops: Vec<TourStop> = Vec::new();
+    for candidate in candidates {
+        if stops.len() >= MAX_STOPS {
+            break;
+        }
+        let too_close = stops.iter().any(|stop| {
+            stop.name == candidate.name
+                || (stop.x - candidate.x).pow(2) + (stop.z - candidate.z).pow(2)
+                    < MIN_STOP_SPACING.pow(2)
+        });
+        if !too_close {
+            stops.push(candidate);
+        }
+    }
+    stops
+}
+
+/// Orders stops into a short route: starts at the most important landmark and always
+/// flies on to the nearest unvisited one.
+fn order_route(mut stops: Vec<TourStop>) -> Vec<TourStop> {
+    let mut route = Vec::with_capacity(stops.len());
+    if stops.is_empty() {
+        return route;
+    }
+    let first = (0..stops.len())
+        .max_by_key(|&i| stops[i].score)
+        .unwrap_or(0);
+    route.push(stops.swap_remove(first));
+
+    while !stops.is_empty() {
+        let last = route.last().unwrap();
+        let next = (0..stops.len())
+            .min_by_key(|&i| (stops[i].x - last.x).pow(2) + (stops[i].z - last.z).pow(2))
+            .unwrap();
+        route.push(stops.swap_remove(next));
+    }
+    route
+}
+
+/// A JSON text component for `title` and `tellraw`
+pub fn text_component(text: &str) -> String {
+    format!(r#"{{"text":{}}}"#, serde_json::Value::from(text))
+}
+
+fn stop_function(stop: &TourStop, index: usize, total: usize) -> String {
+    let mut lines = vec![
+        format!(
+            "tp @a {} {} {} facing {} {} {}",
+            stop.x,
+            stop.y + VIEW_HEIGHT,
+            stop.z + VIEW_DISTANCE,
+            stop.x,
+            stop.y + 10,
+            stop.z
+        ),
+        format!("title @a title {}", text_component(&stop.name)),
+        format!(
+            "title @a subtitle {}",
+            text_component(&format!("Stop {} of {}", index + 1, total))
+        ),
+    ];
+    if index + 1 < total {
+        lines.push(format!(
+            "schedule function {NAMESPACE}:stop_{} {STOP_SECONDS}s",
+            index + 2
+        ));
+    } else {
+        lines.push(format!(
+            "schedule function {NAMESPACE}:finish {STOP_SECONDS}s"
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Writes (or extends) the tour datapack in a Java world and returns its directory.
+/// Returns `Ok(None)` when the world has no landmarks to visit.
+pub fn write_tour_datapack(
+    world_dir: &Path,
+    stops: Vec<TourStop>,
+) -> Result<Option<PathBuf>, String> {
+    let pack = Datapack::new(world_dir, NAMESPACE);
+
+    // Merge with the stops of previously generated tiles
+    let mut candidates = stops;
+    if let Ok(text) = fs::read_to_string(pack.dir.join("tour.json")) {
+        if let Ok(existing) = serde_json::from_str::<Vec<TourStop>>(&text) {
+            candidates.extend(existing);
+        }
+    }
+    let route = order_route(pick_stops(candidates));
+    if route.is_empty() {
+        return Ok(None);
+    }
+
+    pack.create("Arnis guided tour of the generated area")?;
+    pack.write(
+        "tour.json",
+        serde_json::to_string_pretty(&route)
+            .map_err(|e| format!("Failed to serialize tour stops: {e}"))?,
+    )?;
+
+    pack.write_function(
+        "start",
+        format!(
+            "# Guided tour of the generated area: /function {NAMESPACE}:start\n\
+             give @a minecraft:elytra\n\
+             give @a minecraft:firework_rocket 16\n\
+             function {NAMESPACE}:stop_1\n"
+        ),
+    )?;
+    for (index, stop) in route.iter().enumerate() {
+        pack.write_function(
+            &format!("stop_{}", index + 1),
+            stop_function(stop, index, route.len()),
+        )?;
+    }
+    pack.write_function(
+        "finish",
+        format!("title @a title {}\n", text_component("End of the tour")),
+    )?;
+    let clear: String = (1..=route.len())
+        .map(|i| format!("schedule clear {NAMESPACE}:stop_{i}\n"))
+        .chain(std::iter::once(format!(
+            "schedule clear {NAMESPACE}:finish\n"
+        )))
+        .collect();
+    pack.write_function("stop", clear)?;
+
+    Ok(Some(pack.dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_parser::ProcessedNode;
+    use crate::test_utilities::tags;
+
+    fn landmark(id: u64, x: i32, z: i32, pairs: &[(&str, &str)]) -> ProcessedElement {
+        ProcessedElement::Node(ProcessedNode {
+            id,
+            tags: tags(pairs),
+            x,
+            z,
+            exact: None,
+        })
+    }
+
+    #[test]
+    fn picks_important_spaced_landmarks() {
+        let elements = vec![
+            landmark(1, 0, 0, &[("historic", "castle"), ("name", "Slot")]),
+            // Too close to the castle
+            landmark(2, 10, 0, &[("tourism", "museum"), ("name", "Museum")]),
+            landmark(3, 200, 0, &[("amenity", "library"), ("name", "Bibliotek")]),
+            landmark(
+                4,
+                100,
+                0,
+                &[
+                    ("tourism", "artwork"),
+                    ("name", "Statue"),
+                    ("wikidata", "Q1"),
+                ],
+            ),
+            // Not a landmark, or unnamed
+            landmark(5, 300, 0, &[("shop", "bakery"), ("name", "Bager")]),
+            landmark(6, 400, 0, &[("historic", "ruins")]),
+        ];
+
+        let stops = select_landmarks(&elements, |_, _| 5);
+        let names: Vec<&str> = stops.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Slot", "Statue", "Bibliotek"]);
+        assert!(stops.iter().all(|s| s.y == 5));
+    }
+
+    #[test]
+    fn writes_a_chained_datapack() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let stop = |name: &str, x: i32, score: i32| TourStop {
+            name: name.to_string(),
+            x,
+            y: 0,
+            z: 0,
+            score,
+        };
+
+        write_tour_datapack(tmpdir.path(), vec![stop("A", 0, 5), stop("C", 200, 4)]).unwrap();
+        // A second tile adds a landmark between the two
+        let pack = write_tour_datapack(tmpdir.path(), vec![stop("B", 100, 1)])
+            .unwrap()
+            .unwrap();
+
+        let functions = pack.join("data/arnis_tour/function");
+        let first = fs::read_to_string(functions.join("stop_1.mcfunction")).unwrap();
+        assert!(first.contains(r#"title @a title {"text":"A"}"#));
+        assert!(first.contains("schedule function arnis_tour:stop_2 15s"));
+        let second = fs::read_to_string(functions.join("stop_2.mcfunction")).unwrap();
+        assert!(second.contains(r#"{"text":"B"}"#));
+        let last = fs::read_to_string(functions.join("stop_3.mcfunction")).unwrap();
+        assert!(last.contains("schedule function arnis_tour:finish"));
+        assert!(pack.join("pack.mcmeta").exists());
+    }
+}
@@ -8,6 +8,7 @@ use super::WorldMetadata;
 use crate::bedrock_block_map::{
     to_bedrock_block_with_properties, BedrockBlock, BedrockBlockStateValue,
 };
+use crate::biomes::Biome;
 use crate::coordinate_system::cartesian::XZBBox;
 use crate::coordinate_system::geographic::LLBBox;
 use crate::ground::Ground;
@@ -549,17 +550,21 @@ impl BedrockWriter {
 
     /// Creates a Data3D record containing heightmap and biome data.
     ///
-    /// Format: 512 bytes heightmap (256 x i16 LE) + 28 bytes minimal biome data
-    fn create_data3d(&self, _chunk: &ChunkToModify) -> Vec<u8> {
-        let mut buffer = Vec::with_capacity(540);
+    /// Format: 512 bytes heightmap (256 x i16 LE) + the biomes of each subchunk from the
+    /// bottom of the overworld up
+    fn create_data3d(&self, chunk: &ChunkToModify) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(512);
 
         // Heightmap: 256 entries (16x16) as i16 LE, fixed height of 4 for flat world
         for _ in 0..256 {
             buffer.extend_from_slice(&4i16.to_le_bytes());
         }
 
-        // Minimal biome data padding (biomes will be regenerated by the game)
-        buffer.extend_from_slice(&[0u8; 28]);
+        // The generated biomes run all the way up each column, so every subchunk gets the same
+        let biomes = encode_biomes(chunk.biomes.as_ref());
+        for _ in 0..OVERWORLD_SUBCHUNKS {
+            buffer.extend_from_slice(&biomes);
+        }
 
         buffer
     }
@@ -797,6 +802,54 @@ fn add_directory_to_zip(
 
 /// Calculate bits per block using valid Bedrock values: {1, 2, 3, 4, 5, 6, 8, 16}
 #[inline]
+/// Subchunks of the overworld, from y = -64 to y = 319
+const OVERWORLD_SUBCHUNKS: usize = 24;
+
+/// Encodes the biomes of one subchunk for Data3D: a palette of numeric biome IDs and the
+/// packed indices in XZY order, like the blocks. A chunk without biomes is plains, the
+/// biome of the flat world's layers.
+fn encode_biomes(biomes: Option<&[Biome; 16]>) -> Vec<u8> {
+    // Biome of each column, in X-major order
+    let columns: Vec<i32> = (0..256)
+        .map(|column| {
+            let (x, z) = (column / 16, column % 16);
+            biomes
+                .map_or(Biome::Plains, |biomes| biomes[(z / 4) * 4 + x / 4])
+                .bedrock_id()
+        })
+        .collect();
+    let mut palette = columns.clone();
+    palette.sort_unstable();
+    palette.dedup();
+
+    let mut buffer = Vec::new();
+    if palette.len() == 1 {
+        // A single biome is written without indices or palette count
+        buffer.push(1);
+    } else {
+        let bits_per_value = bedrock_bits_per_block(palette.len() as u32);
+        buffer.push(bits_per_value << 1);
+        let values_per_word = 32 / bits_per_value as usize;
+        let indices: Vec<u32> = (0..4096)
+            .map(|index| palette.binary_search(&columns[index / 16]).unwrap_or(0) as u32)
+            .collect();
+        for word_indices in indices.chunks(values_per_word) {
+            let word = word_indices
+                .iter()
+                .enumerate()
+                .fold(0u32, |word, (i, &index)| {
+                    word | index << (i * bits_per_value as usize)
+                });
+            buffer.extend_from_slice(&word.to_le_bytes());
+        }
+        buffer.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+    }
+    for id in palette {
+        buffer.extend_from_slice(&id.to_le_bytes());
+    }
+    buffer
+}
+
 fn bedrock_bits_per_block(palette_count: u32) -> u8 {
     const VALID_BITS: [u8; 8] = [1, 2, 3, 4, 5, 6, 8, 16];
     for &bits in &VALID_BITS {
@@ -1203,6 +1256,34 @@ mod tests {
         assert_eq!(metadata["chunk_count"], 0); // empty world structure
     }
 
+    #[test]
+    fn biomes_are_written_as_a_palette_of_ids() {
+        // Plains only: the header and the biome
+        assert_eq!(encode_biomes(None), [1, 1, 0, 0, 0]);
+
+        let mut biomes = [Biome::Forest; 16];
+        biomes[1] = Biome::River;
+        let encoded = encode_biomes(Some(&biomes));
+        // One bit per value packs 32 values a word: 128 words after the header
+        assert_eq!(encoded[0], 1 << 1);
+        assert_eq!(encoded.len(), 1 + 128 * 4 + 4 + 2 * 4);
+        // The river's columns, x 4 to 7 and z 0 to 3, are index 1 of the palette
+        let word = |i: usize| u32::from_le_bytes(encoded[1 + i * 4..5 + i * 4].try_into().unwrap());
+        assert_eq!(word(0), 0);
+        assert_eq!(word(32), u32::MAX);
+        assert_eq!(word(33), u32::MAX);
+        assert_eq!(word(34), 0);
+        assert_eq!(
+            encoded[encoded.len() - 12..encoded.len() - 8],
+            2u32.to_le_bytes()
+        );
+        assert_eq!(
+            encoded[encoded.len() - 8..encoded.len() - 4],
+            4i32.to_le_bytes()
+        );
+        assert_eq!(encoded[encoded.len() - 4..], 7i32.to_le_bytes());
+    }
+
     #[test]
     fn writes_mcworld_with_custom_spawn_point() {
         let temp_dir = tempfile::tempdir().expect("tempdir");
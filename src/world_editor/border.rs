@@ -0,0 +1,162 @@
+//! Treatment of the world outside the generated area (`--border`).
+//!
+//! The ocean fade-out and the wall are built just outside the area, by the world writer
+//! itself since the block setters clip to the bbox. They follow the terrain and sea level
+//! along the edge, so a river or coast running off the area flows into the ocean, and are
+//! only built along the outer edge of the whole area, not between the tiles of a large one.
+//! The vanilla world border and the void are level.dat settings, see
+//! [`crate::world_utils::LevelSettings`].
+
+use super::WorldEditor;
+use crate::block_definitions::*;
+use crate::coordinate_system::cartesian::XZBBox;
+
+const MIN_Y: i32 = -64;
+/// Width of the ocean fade-out beyond the area
+const OCEAN_WIDTH: i32 = 24;
+/// Depth below sea level the ocean floor reaches at its outer edge
+const OCEAN_DEPTH: i32 = 12;
+/// Height of the glass wall above the ground at the edge
+const WALL_HEIGHT: i32 = 24;
+
+/// How the world ends at the edge of the generated area
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BorderStyle {
+    /// Vanilla world border around the area (Java)
+    WorldBorder,
+    /// The terrain slopes down into open sea
+    Ocean,
+    /// A bedrock wall up to the ground with glass above
+    Wall,
+    /// Nothing is generated outside the area (Java)
+    Void,
+}
+
+impl BorderStyle {
+    /// Whether the style is built as blocks around the area, rather than set in level.dat
+    pub fn is_built(self) -> bool {
+        matches!(self, Self::Ocean | Self::Wall)
+    }
+}
+
+/// A column outside the area, with the edge column inside the area it continues
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BorderColumn {
+    x: i32,
+    z: i32,
+    edge: (i32, i32),
+    /// Blocks from the edge column; diagonal steps count as one
+    distance: i32,
+}
+
+/// Columns up to `width` blocks outside the sides of `tile` that lie on the edge of `world`
+fn border_columns(tile: &XZBBox, world: &XZBBox, width: i32) -> Vec<BorderColumn> {
+    let grow = |on_edge: bool| if on_edge { width } else { 0 };
+    let (min_x, max_x) = (
+        tile.min_x() - grow(tile.min_x() <= world.min_x()),
+        tile.max_x() + grow(tile.max_x() >= world.max_x()),
+    );
+    let (min_z, max_z) = (
+        tile.min_z() - grow(tile.min_z() <= world.min_z()),
+        tile.max_z() + grow(tile.max_z() >= world.max_z()),
+    );
+
+    let mut columns = Vec::new();
+    for x in min_x..=max_x {
+        for z in min_z..=max_z {
+            let edge = (
+                x.clamp(tile.min_x(), tile.max_x()),
+                z.clamp(tile.min_z(), tile.max_z()),
+            );
+            let distance = (x - edge.0).abs().max((z - edge.1).abs());
+            if distance > 0 {
+                columns.push(BorderColumn {
+                    x,
+                    z,
+                    edge,
+                    distance,
+                });
+            }
+        }
+    }
+    columns
+}
+
+impl WorldEditor<'_> {
+    /// Builds the ocean or wall border along the sides of this editor's area that lie on the
+    /// edge of the whole `world`. Other styles are not built and do nothing.
+    pub fn build_border(&mut self, style: BorderStyle, world: &XZBBox, sea_level: Option<i32>) {
+        let width = match style {
+            BorderStyle::Ocean => OCEAN_WIDTH,
+            BorderStyle::Wall => 1,
+            BorderStyle::WorldBorder | BorderStyle::Void => return,
+        };
+        for BorderColumn {
+            x,
+            z,
+            edge,
+            distance,
+        } in border_columns(self.xzbbox, world, width)
+        {
+            let edge_y = self.get_ground_level(edge.0, edge.1);
+            self.world.set_block(x, MIN_Y, z, BEDROCK);
+            if style == BorderStyle::Wall {
+                self.world
+                    .fill_column(x, z, MIN_Y + 1, edge_y, BEDROCK, false);
+                self.world
+                    .fill_column(x, z, edge_y + 1, edge_y + WALL_HEIGHT, GLASS, false);
+                continue;
+            }
+
+            // Slope from the ground at the edge down to the ocean floor
+            let water_y = sea_level.unwrap_or(edge_y - 1);
+            let bottom = water_y - OCEAN_DEPTH;
+            let floor = (edge_y - (edge_y - bottom) * distance / OCEAN_WIDTH).max(MIN_Y + 3);
+            self.world.set_block(x, floor - 2, z, STONE);
+            self.world.set_block(x, floor - 1, z, SAND);
+            self.world.set_block(x, floor, z, SAND);
+            if floor < water_y {
+                self.world
+                    .fill_column(x, z, floor + 1, water_y, WATER, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borders_only_the_outer_edge_of_the_world() {
+        let world = XZBBox::rect_from_xz_lengths(19.0, 9.0).unwrap();
+        let total = border_columns(&world, &world, 2).len();
+        assert_eq!(total, 24 * 14 - 20 * 10);
+
+        // A tile on the west side of the world gets no border along its east side
+        let tile = XZBBox::rect_from_xz_lengths(9.0, 9.0).unwrap();
+        let columns = border_columns(&tile, &world, 2);
+        assert!(columns.iter().all(|column| column.x <= tile.max_x()));
+        assert!(columns.contains(&BorderColumn {
+            x: -2,
+            z: -2,
+            edge: (0, 0),
+            distance: 2
+        }));
+    }
+
+    #[test]
+    fn edge_distance_is_measured_to_the_whole_world() {
+        let world = XZBBox::rect_from_xz_lengths(99.0, 99.0).unwrap();
+        let tile = XZBBox::rect_from_xz_lengths(19.0, 19.0).unwrap();
+        let llbbox = crate::coordinate_system::geographic::LLBBox::new(0.0, 0.0, 1.0, 1.0).unwrap();
+        let mut editor = WorldEditor::new(std::env::temp_dir(), &tile, llbbox);
+        assert_eq!(editor.edge_distance(10, 19), 0);
+
+        editor.set_world_xzbbox(&world);
+        // The tile's east and south sides lie inside the world
+        assert_eq!(editor.edge_distance(10, 19), 10);
+        assert_eq!(editor.edge_distance(0, 10), 0);
+        assert_eq!(editor.edge_distance(-5, 10), 0);
+    }
+}
@@ -3,6 +3,7 @@
 //! This module contains the internal data structures used to track block changes
 //! before they are written to either Java or Bedrock format.
 
+use crate::biomes::Biome;
 use crate::block_definitions::*;
 
 /// Minimum Y coordinate in Minecraft (1.18+)
@@ -12,6 +13,7 @@ const MAX_Y: i32 = 319;
 use fastnbt::{LongArray, Value};
 use fnv::FnvHashMap;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Chunk structure for Java Edition NBT format
 #[derive(Serialize, Deserialize)]
@@ -235,7 +237,7 @@ impl SectionToModify {
         if self.properties.is_empty() {
             if let BlockStorage::Uniform(block) = &self.storage {
                 let palette_item = PaletteItem {
-                    name: format!("{}:{}", block.namespace(), block.name()),
+                    name: format!("{}:{}", block.namespace(), block.name().unwrap_or("air")),
                     properties: block.properties(),
                 };
                 return Section {
@@ -302,7 +304,7 @@ impl SectionToModify {
         let palette = unique_blocks
             .iter()
             .map(|(block, stored_props)| PaletteItem {
-                name: format!("{}:{}", block.namespace(), block.name()),
+                name: format!("{}:{}", block.namespace(), block.name().unwrap_or("air")),
                 properties: stored_props.clone().or_else(|| block.properties()),
             })
             .collect();
@@ -333,6 +335,8 @@ impl Default for SectionToModify {
 pub(crate) struct ChunkToModify {
     pub sections: FnvHashMap<i8, SectionToModify>,
     pub other: FnvHashMap<String, Value>,
+    /// Biome of each 4x4 column, indexed `z * 4 + x`; `None` until one is set
+    pub biomes: Option<[Biome; 16]>,
 }
 
 impl ChunkToModify {
@@ -369,9 +373,59 @@ impl ChunkToModify {
         section.set_block_with_properties(x, (y & 15) as u8, z, block_with_props);
     }
 
+    /// Sets the biome of the 4x4 column containing (`x`, `z`), the rest of the chunk staying
+    /// plains until set
+    pub fn set_biome(&mut self, x: u8, z: u8, biome: Biome) {
+        let biomes = self.biomes.get_or_insert_with(Default::default);
+        biomes[usize::from(z / 4) * 4 + usize::from(x / 4)] = biome;
+    }
+
     pub fn sections(&self) -> impl Iterator<Item = Section> + '_ {
-        self.sections.iter().map(|(y, s)| s.to_section(*y))
+        self.sections.iter().map(|(y, s)| {
+            let mut section = s.to_section(*y);
+            if let Some(biomes) = &self.biomes {
+                section
+                    .other
+                    .insert("biomes".to_string(), biome_section(biomes));
+            }
+            section
+        })
+    }
+}
+
+/// Java Edition biomes of a section: the same biome all the way up each 4x4 column, as a
+/// palette and the 4x4x4 cells packed into longs like block states, without a minimum width
+fn biome_section(biomes: &[Biome; 16]) -> Value {
+    let mut palette: Vec<Biome> = Vec::new();
+    for biome in biomes {
+        if !palette.contains(biome) {
+            palette.push(*biome);
+        }
+    }
+    let mut section = HashMap::from([(
+        "palette".to_string(),
+        Value::List(
+            palette
+                .iter()
+                .map(|biome| Value::String(biome.name().to_string()))
+                .collect(),
+        ),
+    )]);
+    if palette.len() > 1 {
+        let bits = (usize::BITS - (palette.len() - 1).leading_zeros()) as usize;
+        let per_long = 64 / bits;
+        let mut data = vec![0i64; 64usize.div_ceil(per_long)];
+        // Cells in YZX order; every layer repeats the columns
+        for i in 0..64 {
+            let index = palette
+                .iter()
+                .position(|biome| *biome == biomes[i % 16])
+                .unwrap_or(0) as i64;
+            data[i / per_long] |= index << ((i % per_long) * bits);
+        }
+        section.insert("data".to_string(), Value::LongArray(LongArray::new(data)));
     }
+    Value::Compound(section)
 }
 
 /// A region being modified (32x32 chunks)
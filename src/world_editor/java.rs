@@ -6,18 +6,28 @@ use super::common::{Chunk, ChunkToModify, Section};
 use super::WorldEditor;
 use crate::block_definitions::GRASS_BLOCK;
 use crate::progress::emit_gui_progress_update;
+use crate::progress_aggregator::ProgressAggregator;
 use colored::Colorize;
 use fastanvil::Region;
-use fastnbt::Value;
+use fastnbt::{ByteArray, Value};
 use fnv::FnvHashMap;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
-use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::OnceLock;
 
+/// Generation status written with every chunk. Anything short of `full` makes the game run the
+/// remaining generation steps over the chunk, scattering trees and ores through the city.
+const CHUNK_STATUS: &str = "minecraft:full";
+
+/// Sections from the bottom of the world to the top, Y -64 to 319
+const SECTIONS: std::ops::RangeInclusive<i8> = -4..=19;
+
+/// Region file of fully generated, empty chunks every region starts from
+const REGION_TEMPLATE: &[u8] = include_bytes!("../../assets/minecraft/region.template");
+
 /// Cached base chunk sections (grass at Y=-62)
 /// Computed once on first use and reused for all empty chunks
 static BASE_CHUNK_SECTIONS: OnceLock<Vec<Section>> = OnceLock::new();
@@ -47,8 +57,6 @@ impl<'a> WorldEditor<'a> {
         // Ensure region directory exists before creating region files
         std::fs::create_dir_all(&region_dir).expect("Failed to create region directory");
 
-        const REGION_TEMPLATE: &[u8] = include_bytes!("../../assets/minecraft/region.template");
-
         let mut region_file: File = File::options()
             .read(true)
             .write(true)
@@ -115,24 +123,16 @@ impl<'a> WorldEditor<'a> {
                 .progress_chars("█▓░"),
         );
 
-        let regions_processed = AtomicU64::new(0);
+        // Regions finish out of order across threads; update the GUI every 10%
+        let mut progress = ProgressAggregator::new(90.0, 99.0, "Saving world...").step(10);
+        let regions_stage = progress.stage(1.0, total_regions);
 
         self.world
             .regions
             .par_iter()
             .for_each(|((region_x, region_z), region_to_modify)| {
                 self.save_single_region(*region_x, *region_z, region_to_modify);
-
-                // Update progress
-                let regions_done = regions_processed.fetch_add(1, Ordering::SeqCst) + 1;
-
-                // Update progress at regular intervals (every ~10% or at least every 10 regions)
-                let update_interval = (total_regions / 10).max(1);
-                if regions_done.is_multiple_of(update_interval) || regions_done == total_regions {
-                    let progress = 90.0 + (regions_done as f64 / total_regions as f64) * 9.0;
-                    emit_gui_progress_update(progress, "Saving world...");
-                }
-
+                progress.advance(regions_stage, 1);
                 save_pb.inc(1);
             });
 
@@ -167,7 +167,11 @@ impl<'a> WorldEditor<'a> {
                             x_pos: abs_chunk_x,
                             z_pos: abs_chunk_z,
                             is_light_on: 0,
-                            other: chunk_to_modify.other.clone(),
+                            other: chunk_safe_entities(
+                                &chunk_to_modify.other,
+                                abs_chunk_x,
+                                abs_chunk_z,
+                            ),
                         };
 
                         let level_data = create_level_wrapper(&chunk);
@@ -216,10 +220,45 @@ impl<'a> WorldEditor<'a> {
         chunks
     }
 }
-/// Helper function to get entity coordinates
-/// Note: Currently unused since we write directly without merging, but kept for potential future use
+/// Keeps only the entities and block entities the game accepts in this chunk. Block entities
+/// outside the chunk are dropped, as are all but the last one placed at a position (the last
+/// write wins, as with blocks); entities are dropped when their position is in another chunk.
+fn chunk_safe_entities(
+    other: &FnvHashMap<String, Value>,
+    chunk_x: i32,
+    chunk_z: i32,
+) -> FnvHashMap<String, Value> {
+    let in_chunk = |(x, _, z): (i32, i32, i32)| x >> 4 == chunk_x && z >> 4 == chunk_z;
+    let mut other = other.clone();
+
+    if let Some(Value::List(block_entities)) = other.get_mut("block_entities") {
+        let mut seen = HashSet::new();
+        // Walk from the back so the last block entity placed at a position survives
+        let mut kept: Vec<Value> = block_entities
+            .drain(..)
+            .rev()
+            .filter(|entity| match entity {
+                Value::Compound(map) => {
+                    get_entity_coords(map).is_some_and(|pos| in_chunk(pos) && seen.insert(pos))
+                }
+                _ => false,
+            })
+            .collect();
+        kept.reverse();
+        *block_entities = kept;
+    }
+
+    if let Some(Value::List(entities)) = other.get_mut("entities") {
+        entities.retain(|entity| {
+            matches!(entity, Value::Compound(map) if get_entity_coords(map).is_some_and(in_chunk))
+        });
+    }
+
+    other
+}
+
+/// Block coordinates of an entity (`Pos`) or block entity (`x`, `y`, `z`)
 #[inline]
-#[allow(dead_code)]
 fn get_entity_coords(entity: &HashMap<String, Value>) -> Option<(i32, i32, i32)> {
     if let Some(Value::List(pos)) = entity.get("Pos") {
         if pos.len() == 3 {
@@ -247,6 +286,47 @@ fn get_entity_coords(entity: &HashMap<String, Value>) -> Option<(i32, i32, i32)>
 /// Creates a Level wrapper for chunk data (Java Edition format)
 #[inline]
 fn create_level_wrapper(chunk: &Chunk) -> HashMap<String, Value> {
+    // Nothing above the highest block casts a shadow, so sky light is full from there up
+    let open_from = chunk
+        .sections
+        .iter()
+        .filter(|section| {
+            let palette = &section.block_states.palette;
+            palette.iter().any(|item| item.name != "minecraft:air")
+        })
+        .map(|section| section.y + 1)
+        .max()
+        .unwrap_or(*SECTIONS.start());
+    let full_sky_light = || Value::ByteArray(ByteArray::new(vec![-1; 2048]));
+    let biomes = chunk
+        .sections
+        .iter()
+        .find_map(|section| section.other.get("biomes"));
+    let open_sections = (open_from..=*SECTIONS.end())
+        .filter(|y| !chunk.sections.iter().any(|section| section.y == *y))
+        .map(|y| {
+            let air = HashMap::from([(
+                "Name".to_string(),
+                Value::String("minecraft:air".to_string()),
+            )]);
+            let mut section_map = HashMap::from([
+                ("Y".to_string(), Value::Byte(y)),
+                (
+                    "block_states".to_string(),
+                    Value::Compound(HashMap::from([(
+                        "palette".to_string(),
+                        Value::List(vec![Value::Compound(air)]),
+                    )])),
+                ),
+                ("SkyLight".to_string(), full_sky_light()),
+            ]);
+            if let Some(biomes) = biomes {
+                section_map.insert("biomes".to_string(), biomes.clone());
+            }
+            Value::Compound(section_map)
+        })
+        .collect::<Vec<_>>();
+
     let mut level_map = HashMap::from([
         ("xPos".to_string(), Value::Int(chunk.x_pos)),
         ("zPos".to_string(), Value::Int(chunk.z_pos)),
@@ -254,6 +334,17 @@ fn create_level_wrapper(chunk: &Chunk) -> HashMap<String, Value> {
             "isLightOn".to_string(),
             Value::Byte(i8::try_from(chunk.is_light_on).unwrap()),
         ),
+        // Fully generated, so the game never populates the chunk again. Only the open sky above
+        // the build is lit here, so isLightOn and LightPopulated stay off and the game lights the
+        // rest on load.
+        (
+            "Status".to_string(),
+            Value::String(CHUNK_STATUS.to_string()),
+        ),
+        ("TerrainPopulated".to_string(), Value::Byte(1)),
+        ("LightPopulated".to_string(), Value::Byte(0)),
+        ("InhabitedTime".to_string(), Value::Long(0)),
+        ("LastUpdate".to_string(), Value::Long(0)),
         (
             "sections".to_string(),
             Value::List(
@@ -292,11 +383,20 @@ fn create_level_wrapper(chunk: &Chunk) -> HashMap<String, Value> {
                             }
                         }
 
-                        Value::Compound(HashMap::from([
+                        let mut section_map = HashMap::from([
                             ("Y".to_string(), Value::Byte(section.y)),
                             ("block_states".to_string(), Value::Compound(block_states)),
-                        ]))
+                        ]);
+                        // Biomes, where they were set
+                        for (key, value) in &section.other {
+                            section_map.insert(key.clone(), value.clone());
+                        }
+                        if section.y >= open_from {
+                            section_map.insert("SkyLight".to_string(), full_sky_light());
+                        }
+                        Value::Compound(section_map)
                     })
+                    .chain(open_sections)
                     .collect(),
             ),
         ),
@@ -338,17 +438,302 @@ fn merge_compound_list(chunk: &mut Chunk, chunk_to_modify: &ChunkToModify, key:
     }
 }
 
-/// Convert NBT Value to i32
-/// Note: Currently unused since we write directly without merging, but kept for potential future use
-#[allow(dead_code)]
+/// Convert NBT Value to i32, flooring fractional positions to their block
 fn value_to_i32(value: &Value) -> Option<i32> {
     match value {
         Value::Byte(v) => Some(i32::from(*v)),
         Value::Short(v) => Some(i32::from(*v)),
         Value::Int(v) => Some(*v),
         Value::Long(v) => i32::try_from(*v).ok(),
-        Value::Float(v) => Some(*v as i32),
-        Value::Double(v) => Some(*v as i32),
+        Value::Float(v) => Some(v.floor() as i32),
+        Value::Double(v) => Some(v.floor() as i32),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biomes::Biome;
+    use crate::block_definitions::{OAK_PLANKS, STONE};
+    use crate::coordinate_system::cartesian::XZBBox;
+    use crate::coordinate_system::geographic::LLBBox;
+    use std::io::Cursor;
+
+    fn block_entity(x: i32, y: i32, z: i32, id: &str) -> Value {
+        Value::Compound(HashMap::from([
+            ("id".to_string(), Value::String(id.to_string())),
+            ("x".to_string(), Value::Int(x)),
+            ("y".to_string(), Value::Int(y)),
+            ("z".to_string(), Value::Int(z)),
+        ]))
+    }
+
+    fn entity(x: f64, y: f64, z: f64) -> Value {
+        Value::Compound(HashMap::from([(
+            "Pos".to_string(),
+            Value::List(vec![Value::Double(x), Value::Double(y), Value::Double(z)]),
+        )]))
+    }
+
+    /// A chunk the game generated and saved itself, from the same spot of the region template
+    fn vanilla_chunk(
+        template: &mut Region<Cursor<Vec<u8>>>,
+        chunk_x: usize,
+        chunk_z: usize,
+    ) -> Value {
+        let nbt = template
+            .read_chunk(chunk_x, chunk_z)
+            .unwrap()
+            .expect("the template holds every chunk of its region");
+        fastnbt::from_bytes(&nbt).unwrap()
+    }
+
+    /// Compares every tag the writer shares with a vanilla chunk against the vanilla tag type,
+    /// and light arrays against the vanilla length
+    fn same_layout(written: &Value, vanilla: &Value, path: &str) -> Result<(), String> {
+        match (written, vanilla) {
+            (Value::Compound(written), Value::Compound(vanilla)) => {
+                written
+                    .iter()
+                    .try_for_each(|(key, value)| match vanilla.get(key) {
+                        Some(expected) => same_layout(value, expected, &format!("{path}.{key}")),
+                        None => Ok(()),
+                    })
+            }
+            (Value::List(written), Value::List(vanilla)) => match vanilla.first() {
+                Some(expected) => written
+                    .iter()
+                    .try_for_each(|value| same_layout(value, expected, &format!("{path}[]"))),
+                None => Ok(()),
+            },
+            (Value::ByteArray(written), Value::ByteArray(vanilla))
+                if written.len() != vanilla.len() =>
+            {
+                Err(format!(
+                    "{path} holds {} bytes, vanilla {}",
+                    written.len(),
+                    vanilla.len()
+                ))
+            }
+            _ if std::mem::discriminant(written) == std::mem::discriminant(vanilla) => Ok(()),
+            _ => Err(format!("{path} is not the tag type vanilla writes there")),
+        }
+    }
+
+    /// Checks a written chunk against the vanilla chunk at its spot and returns the first
+    /// problem found
+    fn validate_chunk(
+        nbt: &[u8],
+        vanilla: &Value,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<(), String> {
+        let root: HashMap<String, Value> =
+            fastnbt::from_bytes(nbt).map_err(|e| format!("unreadable NBT: {e}"))?;
+        let Some(level_value) = root.get("Level") else {
+            return Err("missing Level compound".to_string());
+        };
+        same_layout(level_value, vanilla, "Level")?;
+        let (Value::Compound(level), Value::Compound(vanilla)) = (level_value, vanilla) else {
+            return Err("Level is not a compound".to_string());
+        };
+        if level.get("Status") != vanilla.get("Status") {
+            return Err(format!(
+                "status {:?}, vanilla {:?}",
+                level.get("Status"),
+                vanilla.get("Status")
+            ));
+        }
+        if level.get("xPos") != Some(&Value::Int(chunk_x))
+            || level.get("zPos") != Some(&Value::Int(chunk_z))
+        {
+            return Err("position does not match the region slot".to_string());
+        }
+
+        let Some(Value::List(sections)) = level.get("sections") else {
+            return Err("missing sections".to_string());
+        };
+        let Some(Value::List(vanilla_sections)) = vanilla.get("sections") else {
+            return Err("vanilla chunk without sections".to_string());
+        };
+        let section_y = |section: &Value| match section {
+            Value::Compound(map) => match map.get("Y") {
+                Some(Value::Byte(y)) => Some(*y),
+                _ => None,
+            },
+            _ => None,
+        };
+        let vanilla_top = vanilla_sections.iter().filter_map(section_y).max();
+        let mut seen = HashSet::new();
+        for section in sections {
+            let y = section_y(section).ok_or("section without Y")?;
+            if !vanilla_sections.iter().any(|s| section_y(s) == Some(y)) || !seen.insert(y) {
+                return Err(format!("section {y} outside the world or written twice"));
+            }
+            let Value::Compound(section) = section else {
+                return Err("section is not a compound".to_string());
+            };
+            // Nothing is built at the top of the world, so the sky is open there
+            if Some(y) == vanilla_top
+                && !matches!(section.get("SkyLight"),
+                    Some(Value::ByteArray(light)) if light.iter().all(|&level| level == -1))
+            {
+                return Err("top section without full sky light".to_string());
+            }
+            let Some(Value::Compound(states)) = section.get("block_states") else {
+                return Err("section without block_states".to_string());
+            };
+            let Some(Value::List(palette)) = states.get("palette") else {
+                return Err("block_states without palette".to_string());
+            };
+            let named = |item: &Value| {
+                matches!(item, Value::Compound(map)
+                    if matches!(map.get("Name"), Some(Value::String(name)) if name.contains(':')))
+            };
+            if palette.is_empty() || !palette.iter().all(named) {
+                return Err("empty palette or palette entry without a name".to_string());
+            }
+            match states.get("data") {
+                None if palette.len() == 1 => {}
+                Some(Value::LongArray(data)) => {
+                    let bits = (usize::BITS - (palette.len() - 1).leading_zeros()).max(4) as usize;
+                    let per_long = 64 / bits;
+                    if data.len() != 4096usize.div_ceil(per_long) {
+                        return Err(format!("{} longs of block data", data.len()));
+                    }
+                    let mask = (1u64 << bits) - 1;
+                    let out_of_palette = (0..4096).any(|i| {
+                        let value = (data[i / per_long] as u64 >> ((i % per_long) * bits)) & mask;
+                        value as usize >= palette.len()
+                    });
+                    if out_of_palette {
+                        return Err("block data points outside the palette".to_string());
+                    }
+                }
+                other => return Err(format!("block data is {other:?}")),
+            }
+            if let Some(biomes) = section.get("biomes") {
+                let Value::Compound(biomes) = biomes else {
+                    return Err("biomes are not a compound".to_string());
+                };
+                let Some(Value::List(palette)) = biomes.get("palette") else {
+                    return Err("biomes without palette".to_string());
+                };
+                let named =
+                    |item: &Value| matches!(item, Value::String(name) if name.contains(':'));
+                if palette.is_empty() || !palette.iter().all(named) {
+                    return Err("empty biome palette or biome without a name".to_string());
+                }
+                match biomes.get("data") {
+                    None if palette.len() == 1 => {}
+                    Some(Value::LongArray(data)) if palette.len() > 1 => {
+                        let bits = (usize::BITS - (palette.len() - 1).leading_zeros()) as usize;
+                        if data.len() != 64usize.div_ceil(64 / bits) {
+                            return Err(format!("{} longs of biome data", data.len()));
+                        }
+                    }
+                    other => return Err(format!("biome data is {other:?}")),
+                }
+            }
+        }
+
+        let in_chunk = |(x, _, z): (i32, i32, i32)| x >> 4 == chunk_x && z >> 4 == chunk_z;
+        if let Some(Value::List(block_entities)) = level.get("block_entities") {
+            let mut positions = HashSet::new();
+            for block_entity in block_entities {
+                let Value::Compound(map) = block_entity else {
+                    return Err("block entity is not a compound".to_string());
+                };
+                let pos = get_entity_coords(map).ok_or("block entity without position")?;
+                if !matches!(map.get("id"), Some(Value::String(_))) {
+                    return Err(format!("block entity at {pos:?} without id"));
+                }
+                if !in_chunk(pos) || !positions.insert(pos) {
+                    return Err(format!("stray or duplicate block entity at {pos:?}"));
+                }
+            }
+        }
+        if let Some(Value::List(entities)) = level.get("entities") {
+            for entity in entities {
+                let Value::Compound(map) = entity else {
+                    return Err("entity is not a compound".to_string());
+                };
+                if !get_entity_coords(map).is_some_and(in_chunk) {
+                    return Err("entity outside its chunk".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stray_and_duplicate_entities_are_dropped() {
+        let other = FnvHashMap::from_iter([
+            (
+                "block_entities".to_string(),
+                Value::List(vec![
+                    block_entity(17, 64, 18, "minecraft:sign"),
+                    block_entity(17, 64, 18, "minecraft:chest"),
+                    block_entity(40, 64, 18, "minecraft:sign"),
+                ]),
+            ),
+            (
+                "entities".to_string(),
+                Value::List(vec![entity(16.5, 64.0, 31.9), entity(15.5, 64.0, 20.0)]),
+            ),
+        ]);
+
+        let safe = chunk_safe_entities(&other, 1, 1);
+        assert_eq!(
+            safe["block_entities"],
+            Value::List(vec![block_entity(17, 64, 18, "minecraft:chest")])
+        );
+        assert_eq!(
+            safe["entities"],
+            Value::List(vec![entity(16.5, 64.0, 31.9)])
+        );
+    }
+
+    #[test]
+    fn written_regions_pass_nbt_validation() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let xzbbox = XZBBox::rect_from_xz_lengths(40.0, 40.0).unwrap();
+        let llbbox = LLBBox::new(0.0, 0.0, 1.0, 1.0).unwrap();
+        let mut editor = WorldEditor::new(temp_dir.path().to_path_buf(), &xzbbox, llbbox);
+
+        for x in 0..=40 {
+            for z in 0..=40 {
+                editor.set_block_absolute(STONE, x, -63, z, None, None);
+            }
+        }
+        editor.set_block_absolute(OAK_PLANKS, 3, -60, 3, None, None);
+        // Two signs on one spot and a villager next to a chunk border
+        for text in ["first", "second"] {
+            let line = || text.to_string();
+            editor.set_sign(line(), line(), line(), line(), 5, -61, 5, 0);
+        }
+        editor.add_entity("minecraft:villager", 16, -62, 15, None);
+        // Woods and a river in one chunk, the rest of it plains
+        editor.set_biome(2, 2, Biome::Forest);
+        editor.set_biome(6, 2, Biome::River);
+        editor.save();
+
+        let region_path = temp_dir.path().join("region").join("r.0.0.mca");
+        let mut region = Region::from_stream(File::open(region_path).unwrap()).unwrap();
+        let mut template = Region::from_stream(Cursor::new(REGION_TEMPLATE.to_vec())).unwrap();
+        for chunk_x in 0..32 {
+            for chunk_z in 0..32 {
+                let nbt = region
+                    .read_chunk(chunk_x, chunk_z)
+                    .unwrap()
+                    .expect("every chunk of the region is written");
+                let vanilla = vanilla_chunk(&mut template, chunk_x, chunk_z);
+                let checked = validate_chunk(&nbt, &vanilla, chunk_x as i32, chunk_z as i32);
+                if let Err(problem) = checked {
+                    panic!("chunk {chunk_x},{chunk_z}: {problem}");
+                }
+            }
+        }
+    }
+}
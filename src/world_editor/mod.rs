@@ -7,10 +7,13 @@
 //!
 //! - `common` - Shared data structures for world modification
 //! - `java` - Java Edition Anvil format saving
+//! - `border` - Ocean and wall borders around the generated area
 //! - `bedrock` - Bedrock Edition .mcworld format saving (behind `bedrock` feature)
 
+mod border;
 mod common;
 mod java;
+pub mod sign_text;
 
 #[cfg(feature = "bedrock")]
 pub mod bedrock;
@@ -18,12 +21,16 @@ pub mod bedrock;
 // Re-export common types used internally
 pub(crate) use common::WorldToModify;
 
+pub use border::BorderStyle;
+
 #[cfg(feature = "bedrock")]
 pub(crate) use bedrock::{BedrockSaveError, BedrockWriter};
 
+use crate::biomes::Biome;
 use crate::block_definitions::*;
 use crate::coordinate_system::cartesian::{XZBBox, XZPoint};
 use crate::coordinate_system::geographic::LLBBox;
+use crate::floodfill_cache::CoordinateBitmap;
 use crate::ground::Ground;
 use crate::progress::emit_gui_progress_update;
 use colored::Colorize;
@@ -71,8 +78,12 @@ pub struct WorldEditor<'a> {
     world_dir: PathBuf,
     world: WorldToModify,
     xzbbox: &'a XZBBox,
+    /// Bounds of the whole world, of which `xzbbox` may be one tile
+    world_xzbbox: &'a XZBBox,
     llbbox: LLBBox,
     ground: Option<Arc<Ground>>,
+    /// Blocks nothing may be placed on, e.g. the exclusion zones while features are built
+    excluded: Option<&'a CoordinateBitmap>,
     format: WorldFormat,
     /// Optional level name for Bedrock worlds (e.g., "Arnis World: New York City")
     #[cfg(feature = "bedrock")]
@@ -92,8 +103,10 @@ impl<'a> WorldEditor<'a> {
             world_dir,
             world: WorldToModify::default(),
             xzbbox,
+            world_xzbbox: xzbbox,
             llbbox,
             ground: None,
+            excluded: None,
             format: WorldFormat::JavaAnvil,
             #[cfg(feature = "bedrock")]
             bedrock_level_name: None,
@@ -122,8 +135,10 @@ impl<'a> WorldEditor<'a> {
             world_dir,
             world: WorldToModify::default(),
             xzbbox,
+            world_xzbbox: xzbbox,
             llbbox,
             ground: None,
+            excluded: None,
             format,
             #[cfg(feature = "bedrock")]
             bedrock_level_name,
@@ -174,6 +189,58 @@ impl<'a> WorldEditor<'a> {
         }
     }
 
+    /// Get the terrain slope in degrees at a specific world coordinate, if terrain is enabled
+    pub fn get_ground_slope(&self, x: i32, z: i32) -> Option<f64> {
+        self.ground.as_ref().and_then(|ground| {
+            ground.slope(XZPoint::new(
+                x - self.xzbbox.min_x(),
+                z - self.xzbbox.min_z(),
+            ))
+        })
+    }
+
+    /// Whether the terrain model carved open sea at a specific world coordinate
+    pub fn is_carved_sea(&self, x: i32, z: i32) -> bool {
+        self.ground.as_ref().is_some_and(|ground| {
+            ground.is_carved_sea(XZPoint::new(
+                x - self.xzbbox.min_x(),
+                z - self.xzbbox.min_z(),
+            ))
+        })
+    }
+
+    /// Whether a specific world coordinate lies on a coastal cliff face found in the DHM
+    pub fn is_coastal_cliff(&self, x: i32, z: i32) -> bool {
+        self.ground.as_ref().is_some_and(|ground| {
+            ground.is_coastal_cliff(XZPoint::new(
+                x - self.xzbbox.min_x(),
+                z - self.xzbbox.min_z(),
+            ))
+        })
+    }
+
+    /// Sets the bounds of the whole world when this editor generates one tile of it
+    pub fn set_world_xzbbox(&mut self, world_xzbbox: &'a XZBBox) {
+        self.world_xzbbox = world_xzbbox;
+    }
+
+    /// Bounds of the whole world; the editor's own area when it is not a tile
+    pub fn world_xzbbox(&self) -> &'a XZBBox {
+        self.world_xzbbox
+    }
+
+    /// Sets the blocks nothing may be placed on until the mask is changed again
+    pub fn set_excluded(&mut self, mask: Option<&'a CoordinateBitmap>) {
+        self.excluded = mask;
+    }
+
+    /// Whether blocks may be placed in the column at (`x`, `z`)
+    #[inline]
+    fn writable(&self, x: i32, z: i32) -> bool {
+        self.xzbbox.contains(&XZPoint::new(x, z))
+            && !self.excluded.is_some_and(|mask| mask.contains(x, z))
+    }
+
     /// Returns the minimum world coordinates
     pub fn get_min_coords(&self) -> (i32, i32) {
         (self.xzbbox.min_x(), self.xzbbox.min_z())
@@ -184,6 +251,23 @@ impl<'a> WorldEditor<'a> {
         (self.xzbbox.max_x(), self.xzbbox.max_z())
     }
 
+    /// Whether (`x`, `z`) lies in the tile being generated
+    #[inline]
+    pub fn in_tile(&self, x: i32, z: i32) -> bool {
+        self.xzbbox.contains(&XZPoint::new(x, z))
+    }
+
+    /// Distance in blocks from (`x`, `z`) to the nearest edge of the whole world, not the
+    /// tile; 0 on the edge or outside it
+    pub fn edge_distance(&self, x: i32, z: i32) -> u32 {
+        let world = self.world_xzbbox;
+        let distance = (x - world.min_x())
+            .min(world.max_x() - x)
+            .min(z - world.min_z())
+            .min(world.max_z() - z);
+        u32::try_from(distance).unwrap_or(0)
+    }
+
     /// Checks if there's a block at the given coordinates
     #[allow(unused)]
     #[inline]
@@ -193,7 +277,7 @@ impl<'a> WorldEditor<'a> {
     }
 
     /// Sets a sign at the given coordinates
-    #[allow(clippy::too_many_arguments, dead_code)]
+    #[allow(clippy::too_many_arguments)]
     pub fn set_sign(
         &mut self,
         line1: String,
@@ -205,6 +289,12 @@ impl<'a> WorldEditor<'a> {
         z: i32,
         _rotation: i8,
     ) {
+        if !self.writable(x, z) {
+            return;
+        }
+        let mut lines = [line1, line2, line3, line4];
+        crate::privacy::audit_lines("signs", &mut lines);
+        let [line1, line2, line3, line4] = lines;
         let absolute_y = self.get_absolute_y(x, y, z);
         let chunk_x = x >> 4;
         let chunk_z = z >> 4;
@@ -253,6 +343,18 @@ impl<'a> WorldEditor<'a> {
         self.set_block(SIGN, x, y, z, None, None);
     }
 
+    /// Sets the biome of the 4x4 column containing (`x`, `z`), Java worlds only
+    pub fn set_biome(&mut self, x: i32, z: i32, biome: Biome) {
+        if !self.writable(x, z) {
+            return;
+        }
+        let (chunk_x, chunk_z) = (x >> 4, z >> 4);
+        self.world
+            .get_or_create_region(chunk_x >> 5, chunk_z >> 5)
+            .get_or_create_chunk(chunk_x & 31, chunk_z & 31)
+            .set_biome((x & 15) as u8, (z & 15) as u8, biome);
+    }
+
     /// Adds an entity at the given coordinates (Y is ground-relative).
     #[allow(dead_code)]
     pub fn add_entity(
@@ -263,7 +365,7 @@ impl<'a> WorldEditor<'a> {
         z: i32,
         extra_data: Option<HashMap<String, Value>>,
     ) {
-        if !self.xzbbox.contains(&XZPoint::new(x, z)) {
+        if !self.writable(x, z) {
             return;
         }
 
@@ -349,7 +451,7 @@ impl<'a> WorldEditor<'a> {
         z: i32,
         items: Vec<HashMap<String, Value>>,
     ) {
-        if !self.xzbbox.contains(&XZPoint::new(x, z)) {
+        if !self.writable(x, z) {
             return;
         }
 
@@ -422,7 +524,39 @@ impl<'a> WorldEditor<'a> {
         block_entity_id: &str,
         items: Vec<HashMap<String, Value>>,
     ) {
-        if !self.xzbbox.contains(&XZPoint::new(x, z)) {
+        let data = HashMap::from([(
+            "Items".to_string(),
+            Value::List(items.into_iter().map(Value::Compound).collect()),
+        )]);
+        self.set_block_entity_absolute(block_with_props, x, absolute_y, z, block_entity_id, data);
+    }
+
+    /// Places a block entity with arbitrary data, such as a lectern holding a book, at the
+    /// given coordinates (ground-relative Y).
+    pub fn set_block_entity(
+        &mut self,
+        block_with_props: BlockWithProperties,
+        x: i32,
+        y: i32,
+        z: i32,
+        block_entity_id: &str,
+        data: HashMap<String, Value>,
+    ) {
+        let absolute_y = self.get_absolute_y(x, y, z);
+        self.set_block_entity_absolute(block_with_props, x, absolute_y, z, block_entity_id, data);
+    }
+
+    /// Places a block entity with arbitrary data at the given coordinates (absolute Y).
+    fn set_block_entity_absolute(
+        &mut self,
+        block_with_props: BlockWithProperties,
+        x: i32,
+        absolute_y: i32,
+        z: i32,
+        block_entity_id: &str,
+        data: HashMap<String, Value>,
+    ) {
+        if !self.writable(x, z) {
             return;
         }
 
@@ -431,15 +565,11 @@ impl<'a> WorldEditor<'a> {
         let region_x: i32 = chunk_x >> 5;
         let region_z: i32 = chunk_z >> 5;
 
-        let mut block_entity = HashMap::new();
+        let mut block_entity = data;
         block_entity.insert("id".to_string(), Value::String(block_entity_id.to_string()));
         block_entity.insert("x".to_string(), Value::Int(x));
         block_entity.insert("y".to_string(), Value::Int(absolute_y));
         block_entity.insert("z".to_string(), Value::Int(z));
-        block_entity.insert(
-            "Items".to_string(),
-            Value::List(items.into_iter().map(Value::Compound).collect()),
-        );
         block_entity.insert("keepPacked".to_string(), Value::Byte(0));
 
         let region = self.world.get_or_create_region(region_x, region_z);
@@ -472,8 +602,8 @@ impl<'a> WorldEditor<'a> {
         override_whitelist: Option<&[Block]>,
         override_blacklist: Option<&[Block]>,
     ) {
-        // Check if coordinates are within bounds
-        if !self.xzbbox.contains(&XZPoint::new(x, z)) {
+        // Check if coordinates are within bounds and not excluded
+        if !self.writable(x, z) {
             return;
         }
 
@@ -513,8 +643,8 @@ impl<'a> WorldEditor<'a> {
         override_whitelist: Option<&[Block]>,
         override_blacklist: Option<&[Block]>,
     ) {
-        // Check if coordinates are within bounds
-        if !self.xzbbox.contains(&XZPoint::new(x, z)) {
+        // Check if coordinates are within bounds and not excluded
+        if !self.writable(x, z) {
             return;
         }
 
@@ -551,8 +681,8 @@ impl<'a> WorldEditor<'a> {
         override_whitelist: Option<&[Block]>,
         override_blacklist: Option<&[Block]>,
     ) {
-        // Check if coordinates are within bounds
-        if !self.xzbbox.contains(&XZPoint::new(x, z)) {
+        // Check if coordinates are within bounds and not excluded
+        if !self.writable(x, z) {
             return;
         }
 
@@ -684,7 +814,7 @@ impl<'a> WorldEditor<'a> {
     /// because it avoids the double HashMap traversal.
     #[inline]
     pub fn set_block_if_absent_absolute(&mut self, block: Block, x: i32, absolute_y: i32, z: i32) {
-        if !self.xzbbox.contains(&XZPoint::new(x, z)) {
+        if !self.writable(x, z) {
             return;
         }
         self.world.set_block_if_absent(x, absolute_y, z, block);
@@ -704,7 +834,7 @@ impl<'a> WorldEditor<'a> {
         y_max: i32,
         skip_existing: bool,
     ) {
-        if !self.xzbbox.contains(&XZPoint::new(x, z)) {
+        if !self.writable(x, z) {
             return;
         }
         self.world
@@ -0,0 +1,71 @@
+//! Text for the four lines of a sign.
+//!
+//! Lines are cut to what fits on a sign, and the quotes and backslashes that would break the
+//! sign's JSON text are left out.
+
+/// Characters that fit on one sign line
+const SIGN_LINE_LENGTH: usize = 15;
+
+/// `text` as one sign line
+pub fn sign_line(text: &str) -> String {
+    text.chars()
+        .filter(|c| *c != '"' && *c != '\\')
+        .take(SIGN_LINE_LENGTH)
+        .collect()
+}
+
+/// Wraps words onto at most `max_lines` sign lines, a space between words on the same line.
+/// Words that no longer fit are left out.
+pub fn wrap_words<'a>(words: impl IntoIterator<Item = &'a str>, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in words {
+        let word = sign_line(word);
+        if word.is_empty() {
+            continue;
+        }
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= SIGN_LINE_LENGTH => {
+                line.push(' ');
+                line.push_str(&word);
+            }
+            _ if lines.len() == max_lines => break,
+            _ => lines.push(word),
+        }
+    }
+    lines
+}
+
+/// Sets the first lines of a sign to `wrapped`, leaving the rest blank
+pub fn fill_lines(wrapped: Vec<String>) -> [String; 4] {
+    let mut lines: [String; 4] = Default::default();
+    for (line, text) in lines.iter_mut().zip(wrapped) {
+        *line = text;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_at_word_boundaries() {
+        assert_eq!(
+            sign_line("Say \"hi\" to the whole world"),
+            "Say hi to the w"
+        );
+        assert_eq!(
+            wrap_words("Runesten fra Glavendrup ved Skamby".split_whitespace(), 4),
+            vec!["Runesten fra", "Glavendrup ved", "Skamby"]
+        );
+        assert_eq!(
+            wrap_words("Runesten fra Glavendrup ved Skamby".split_whitespace(), 2),
+            vec!["Runesten fra", "Glavendrup ved"]
+        );
+        assert_eq!(
+            wrap_words("1A;5C;350S;14;15E;150S".split(';'), 2),
+            vec!["1A 5C 350S 14", "15E 150S"]
+        );
+        assert_eq!(fill_lines(vec!["A".to_string()])[1], "");
+    }
+}
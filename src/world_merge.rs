@@ -0,0 +1,239 @@
+//! Merging two generated Java worlds into one (`arnis merge`).
+//!
+//! Exports generated with the same `--anchor` and scale share one coordinate frame, so a
+//! large area can be generated in parts and combined region file by region file. Regions
+//! present in only one world are copied as they are; where both worlds have a region, the
+//! world generated last wins, by the time in its manifest. Everything else is taken from the first world,
+//! except the manifest and feature index, which no longer describe the merged world.
+
+use crate::feature_index::INDEX_FILE;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use colored::Colorize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// Where the regions of a merged world came from
+#[derive(Debug, Default, PartialEq)]
+pub struct MergeReport {
+    pub from_a: usize,
+    pub from_b: usize,
+    /// Regions both worlds had, resolved by generation time
+    pub overlapping: Vec<String>,
+}
+
+fn region_files(world: &Path) -> Result<BTreeSet<String>, String> {
+    let dir = world.join("region");
+    let entries =
+        fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".mca"))
+        .collect())
+}
+
+/// Copies a directory tree, leaving out the region files and per-world metadata
+fn copy_world_files(from: &Path, to: &Path, top_level: bool) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {e}", to.display()))?;
+    let entries =
+        fs::read_dir(from).map_err(|e| format!("Failed to read {}: {e}", from.display()))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if top_level && (name == "region" || name == MANIFEST_FILE || name == INDEX_FILE) {
+            continue;
+        }
+        let (source, target) = (entry.path(), to.join(&name));
+        if source.is_dir() {
+            copy_world_files(&source, &target, false)?;
+        } else {
+            fs::copy(&source, &target)
+                .map_err(|e| format!("Failed to copy {}: {e}", source.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn modified(path: &Path) -> Result<std::time::SystemTime, String> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))
+}
+
+fn manifest(world: &Path) -> Option<Manifest> {
+    let text = fs::read_to_string(world.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Anchor and scale a world was generated with, from its manifest if it has one
+fn frame(manifest: Option<&Manifest>) -> Option<(serde_json::Value, serde_json::Value)> {
+    let config = &manifest?.config;
+    Some((
+        config.get("anchor").cloned()?,
+        config.get("scale").cloned()?,
+    ))
+}
+
+/// Merges the Java worlds `a` and `b` into the new world directory `out`.
+pub fn merge_worlds(a: &Path, b: &Path, out: &Path) -> Result<MergeReport, String> {
+    if out.exists()
+        && fs::read_dir(out)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(true)
+    {
+        return Err(format!(
+            "Output {} already exists and is not empty",
+            out.display()
+        ));
+    }
+    let (regions_a, regions_b) = (region_files(a)?, region_files(b)?);
+
+    let (manifest_a, manifest_b) = (manifest(a), manifest(b));
+    match (frame(manifest_a.as_ref()), frame(manifest_b.as_ref())) {
+        (Some(frame_a), Some(frame_b)) if frame_a != frame_b => eprintln!(
+            "{} The worlds were generated with different --anchor or --scale; \
+             their regions will not line up.",
+            "Warning:".yellow().bold()
+        ),
+        (Some(_), Some(_)) => {}
+        _ => eprintln!(
+            "{} Without manifests the shared anchor cannot be checked; \
+             merge only worlds generated with the same --anchor and --scale.",
+            "Warning:".yellow().bold()
+        ),
+    }
+
+    // Which world is newer, from the generation times in the manifests. Without them the
+    // region file times decide, which copying the worlds may have changed.
+    let b_is_newer = match (
+        manifest_a.and_then(|manifest| manifest.generated),
+        manifest_b.and_then(|manifest| manifest.generated),
+    ) {
+        (Some(generated_a), Some(generated_b)) => Some(generated_b > generated_a),
+        _ => None,
+    };
+    if b_is_newer.is_none() && !regions_a.is_disjoint(&regions_b) {
+        eprintln!(
+            "{} The manifests do not say when the worlds were generated; \
+             overlapping regions are taken from the newer file.",
+            "Warning:".yellow().bold()
+        );
+    }
+
+    copy_world_files(a, out, true)?;
+    let region_dir = out.join("region");
+    fs::create_dir_all(&region_dir)
+        .map_err(|e| format!("Failed to create {}: {e}", region_dir.display()))?;
+
+    let mut report = MergeReport::default();
+    for name in regions_a.union(&regions_b) {
+        let (in_a, in_b) = (a.join("region").join(name), b.join("region").join(name));
+        let take_b = match (regions_a.contains(name), regions_b.contains(name)) {
+            (true, true) => {
+                report.overlapping.push(name.clone());
+                match b_is_newer {
+                    Some(b_is_newer) => b_is_newer,
+                    None => modified(&in_b)? > modified(&in_a)?,
+                }
+            }
+            (in_a_only, _) => !in_a_only,
+        };
+        let source = if take_b { &in_b } else { &in_a };
+        fs::copy(source, region_dir.join(name))
+            .map_err(|e| format!("Failed to copy {}: {e}", source.display()))?;
+        if take_b {
+            report.from_b += 1;
+        } else {
+            report.from_a += 1;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    fn world(
+        root: &Path,
+        name: &str,
+        generated: Option<u64>,
+        regions: &[(&str, &[u8], u64)],
+    ) -> std::path::PathBuf {
+        let world = root.join(name);
+        fs::create_dir_all(world.join("region")).unwrap();
+        fs::write(world.join("level.dat"), name).unwrap();
+        let manifest = Manifest {
+            arnis_version: "2.3.0".to_string(),
+            generated,
+            config: serde_json::json!({"anchor": null, "scale": 1.0}),
+            inputs: Vec::new(),
+            outputs: Default::default(),
+            synthetic: Vec::new(),
+        };
+        fs::write(
+            world.join(MANIFEST_FILE),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+        for &(region, content, age) in regions {
+            let path = world.join("region").join(region);
+            fs::write(&path, content).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(age))
+                .unwrap();
+        }
+        world
+    }
+
+    #[test]
+    fn merges_regions_and_keeps_the_newest_overlap() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let a = world(
+            tmpdir.path(),
+            "a",
+            None,
+            &[("r.0.0.mca", b"a0", 100), ("r.1.0.mca", b"a1", 100)],
+        );
+        let b = world(
+            tmpdir.path(),
+            "b",
+            None,
+            &[("r.1.0.mca", b"b1", 10), ("r.2.0.mca", b"b2", 10)],
+        );
+        let out = tmpdir.path().join("merged");
+
+        let report = merge_worlds(&a, &b, &out).unwrap();
+        assert_eq!(report.from_a, 1);
+        assert_eq!(report.from_b, 2);
+        assert_eq!(report.overlapping, vec!["r.1.0.mca"]);
+        assert_eq!(fs::read(out.join("region/r.1.0.mca")).unwrap(), b"b1");
+        assert_eq!(fs::read(out.join("level.dat")).unwrap(), b"a");
+        assert!(!out.join(MANIFEST_FILE).exists());
+
+        assert!(merge_worlds(&a, &b, &out).is_err(), "output is not empty");
+    }
+
+    #[test]
+    fn the_manifest_times_decide_over_the_file_times() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        // World a was generated last but copied before b, so its files look older
+        let a = world(
+            tmpdir.path(),
+            "a",
+            Some(2_000),
+            &[("r.0.0.mca", b"a0", 100)],
+        );
+        let b = world(tmpdir.path(), "b", Some(1_000), &[("r.0.0.mca", b"b0", 10)]);
+        let out = tmpdir.path().join("merged");
+
+        let report = merge_worlds(&a, &b, &out).unwrap();
+        assert_eq!((report.from_a, report.from_b), (1, 0));
+        assert_eq!(fs::read(out.join("region/r.0.0.mca")).unwrap(), b"a0");
+    }
+}
@@ -1,7 +1,11 @@
+use crate::args::Args;
+use crate::coordinate_system::cartesian::XZBBox;
 use crate::coordinate_system::geographic::LLBBox;
 use crate::retrieve_data;
+use crate::world_editor::BorderStyle;
 use fastnbt::Value;
 use flate2::read::GzDecoder;
+use std::collections::HashMap;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::{fs, io::Write};
@@ -205,3 +209,292 @@ pub fn create_new_world(base_path: &Path) -> Result<String, String> {
 
     Ok(new_world_path.display().to_string())
 }
+
+/// Initial time of day for showcase worlds, in game ticks
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TimeOfDay {
+    Sunrise,
+    Noon,
+    GoldenHour,
+    Sunset,
+    Night,
+}
+
+impl TimeOfDay {
+    fn ticks(self) -> i64 {
+        match self {
+            Self::Sunrise => 23_000,
+            Self::Noon => 6_000,
+            Self::GoldenHour => 11_500,
+            Self::Sunset => 12_500,
+            Self::Night => 18_000,
+        }
+    }
+}
+
+/// Default game mode of the world
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameMode {
+    fn id(self) -> i32 {
+        match self {
+            Self::Survival => 0,
+            Self::Creative => 1,
+            Self::Adventure => 2,
+            Self::Spectator => 3,
+        }
+    }
+}
+
+/// Vanilla world border: centre and side length in blocks. The border is always square.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    pub size: f64,
+}
+
+/// Game rules and initial conditions written into level.dat. Writing them turns mob
+/// spawning off as well; with the default settings level.dat is left as the world
+/// template has it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LevelSettings {
+    /// Locks the daylight cycle at this time
+    pub time_of_day: Option<TimeOfDay>,
+    pub game_mode: Option<GameMode>,
+    /// Clear weather with the weather cycle off
+    pub lock_weather: bool,
+    pub world_border: Option<WorldBorder>,
+    /// Generate nothing but air outside the area
+    pub void_outside: bool,
+}
+
+impl LevelSettings {
+    /// `--showcase` sets golden hour, clear weather and spectator mode,
+    /// unless `--time-of-day` or `--gamemode` are given explicitly. A world border is
+    /// fitted around `world`, the bounds of the whole world.
+    pub fn from_args(args: &Args, world: &XZBBox) -> Self {
+        Self {
+            time_of_day: args
+                .time_of_day
+                .or(args.showcase.then_some(TimeOfDay::GoldenHour)),
+            game_mode: args
+                .gamemode
+                .or(args.showcase.then_some(GameMode::Spectator)),
+            lock_weather: args.showcase,
+            world_border: (args.border == Some(BorderStyle::WorldBorder)).then(|| {
+                let rect = world.bounding_rect();
+                WorldBorder {
+                    center_x: (world.min_x() + world.max_x() + 1) as f64 / 2.0,
+                    center_z: (world.min_z() + world.max_z() + 1) as f64 / 2.0,
+                    size: rect.total_blocks_x().max(rect.total_blocks_z()) as f64,
+                }
+            }),
+            void_outside: args.border == Some(BorderStyle::Void),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn apply(&self, data: &mut HashMap<String, Value>) {
+        let mut rules: Vec<(&str, &str)> = vec![("doMobSpawning", "false")];
+
+        if let Some(time) = self.time_of_day {
+            data.insert("DayTime".to_string(), Value::Long(time.ticks()));
+            rules.push(("doDaylightCycle", "false"));
+        }
+
+        if let Some(mode) = self.game_mode {
+            data.insert("GameType".to_string(), Value::Int(mode.id()));
+            if let Some(Value::Compound(player)) = data.get_mut("Player") {
+                player.insert("playerGameType".to_string(), Value::Int(mode.id()));
+            }
+        }
+
+        if self.lock_weather {
+            data.insert("raining".to_string(), Value::Byte(0));
+            data.insert("thundering".to_string(), Value::Byte(0));
+            data.insert("rainTime".to_string(), Value::Int(0));
+            data.insert("thunderTime".to_string(), Value::Int(0));
+            rules.push(("doWeatherCycle", "false"));
+        }
+
+        if let Some(border) = self.world_border {
+            data.insert("BorderCenterX".to_string(), Value::Double(border.center_x));
+            data.insert("BorderCenterZ".to_string(), Value::Double(border.center_z));
+            data.insert("BorderSize".to_string(), Value::Double(border.size));
+            data.insert(
+                "BorderSizeLerpTarget".to_string(),
+                Value::Double(border.size),
+            );
+        }
+
+        // The overworld becomes a flat world without layers, in the void biome
+        if self.void_outside {
+            if let Some(Value::Compound(settings)) = data
+                .get_mut("WorldGenSettings")
+                .and_then(|gen| match gen {
+                    Value::Compound(gen) => gen.get_mut("dimensions"),
+                    _ => None,
+                })
+                .and_then(|dimensions| match dimensions {
+                    Value::Compound(dimensions) => dimensions.get_mut("minecraft:overworld"),
+                    _ => None,
+                })
+                .and_then(|overworld| match overworld {
+                    Value::Compound(overworld) => overworld.get_mut("generator"),
+                    _ => None,
+                })
+                .and_then(|generator| match generator {
+                    Value::Compound(generator) => generator.get_mut("settings"),
+                    _ => None,
+                })
+            {
+                settings.insert("layers".to_string(), Value::List(Vec::new()));
+                settings.insert(
+                    "biome".to_string(),
+                    Value::String("minecraft:the_void".to_string()),
+                );
+                settings.insert("features".to_string(), Value::Byte(0));
+                settings.insert("lakes".to_string(), Value::Byte(0));
+                settings.insert("structure_overrides".to_string(), Value::List(Vec::new()));
+            }
+        }
+
+        if let Value::Compound(game_rules) = data
+            .entry("GameRules".to_string())
+            .or_insert_with(|| Value::Compound(HashMap::new()))
+        {
+            for (rule, value) in rules {
+                game_rules.insert(rule.to_string(), Value::String(value.to_string()));
+            }
+        }
+    }
+}
+
+/// Writes game rules and initial conditions into the level.dat of a Java world.
+pub fn apply_level_settings(world_path: &Path, settings: &LevelSettings) -> Result<(), String> {
+    let level_path = world_path.join("level.dat");
+    let compressed = fs::read(&level_path).map_err(|e| format!("Failed to read level.dat: {e}"))?;
+
+    let mut decompressed_data = Vec::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut decompressed_data)
+        .map_err(|e| format!("Failed to decompress level.dat: {e}"))?;
+    let mut level_data: Value = fastnbt::from_bytes(&decompressed_data)
+        .map_err(|e| format!("Failed to parse level.dat: {e}"))?;
+
+    let Value::Compound(ref mut root) = level_data else {
+        return Err("Invalid level.dat: root is not a compound".to_string());
+    };
+    let Some(Value::Compound(data)) = root.get_mut("Data") else {
+        return Err("Invalid level.dat: missing Data compound".to_string());
+    };
+    settings.apply(data);
+
+    let serialized_level_data: Vec<u8> = fastnbt::to_bytes(&level_data)
+        .map_err(|e| format!("Failed to serialize updated level.dat: {e}"))?;
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(&serialized_level_data)
+        .map_err(|e| format!("Failed to compress updated level.dat: {e}"))?;
+    let compressed_level_data = encoder
+        .finish()
+        .map_err(|e| format!("Failed to finalize compression for level.dat: {e}"))?;
+
+    fs::write(level_path, compressed_level_data)
+        .map_err(|e| format!("Failed to write level.dat: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_data(world_path: &Path) -> HashMap<String, Value> {
+        let compressed = fs::read(world_path.join("level.dat")).unwrap();
+        let mut bytes = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut bytes)
+            .unwrap();
+        let Value::Compound(mut root) = fastnbt::from_bytes(&bytes).unwrap() else {
+            panic!("level.dat root is not a compound");
+        };
+        let Some(Value::Compound(data)) = root.remove("Data") else {
+            panic!("level.dat has no Data compound");
+        };
+        data
+    }
+
+    #[test]
+    fn showcase_settings_are_written_to_level_dat() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let world_path = PathBuf::from(create_new_world(tmpdir.path()).unwrap());
+
+        let settings = LevelSettings {
+            time_of_day: Some(TimeOfDay::GoldenHour),
+            game_mode: Some(GameMode::Spectator),
+            lock_weather: true,
+            ..Default::default()
+        };
+        apply_level_settings(&world_path, &settings).unwrap();
+
+        let data = read_data(&world_path);
+        assert_eq!(data.get("DayTime"), Some(&Value::Long(11_500)));
+        assert_eq!(data.get("GameType"), Some(&Value::Int(3)));
+        let Some(Value::Compound(rules)) = data.get("GameRules") else {
+            panic!("missing GameRules");
+        };
+        for rule in ["doDaylightCycle", "doWeatherCycle", "doMobSpawning"] {
+            assert_eq!(rules.get(rule), Some(&Value::String("false".to_string())));
+        }
+    }
+
+    #[test]
+    fn border_settings_are_written_to_level_dat() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let world_path = PathBuf::from(create_new_world(tmpdir.path()).unwrap());
+
+        let settings = LevelSettings {
+            world_border: Some(WorldBorder {
+                center_x: 100.0,
+                center_z: 50.0,
+                size: 200.0,
+            }),
+            void_outside: true,
+            ..Default::default()
+        };
+        apply_level_settings(&world_path, &settings).unwrap();
+
+        let data = read_data(&world_path);
+        assert_eq!(data.get("BorderSize"), Some(&Value::Double(200.0)));
+        assert_eq!(data.get("BorderCenterZ"), Some(&Value::Double(50.0)));
+        let Some(Value::Compound(gen)) = data.get("WorldGenSettings") else {
+            panic!("missing WorldGenSettings");
+        };
+        let Some(Value::Compound(dimensions)) = gen.get("dimensions") else {
+            panic!("missing dimensions");
+        };
+        let Some(Value::Compound(overworld)) = dimensions.get("minecraft:overworld") else {
+            panic!("missing overworld");
+        };
+        let Some(Value::Compound(generator)) = overworld.get("generator") else {
+            panic!("missing generator");
+        };
+        let Some(Value::Compound(settings)) = generator.get("settings") else {
+            panic!("missing generator settings");
+        };
+        assert_eq!(settings.get("layers"), Some(&Value::List(Vec::new())));
+        assert_eq!(
+            settings.get("biome"),
+            Some(&Value::String("minecraft:the_void".to_string()))
+        );
+    }
+}